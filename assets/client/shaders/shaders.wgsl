@@ -1,11 +1,19 @@
 const BLOCKS_IN_CHUNK_EDGE_F32: f32 = 16.0;
 const MAX_LIGHT_LEVEL_F32: f32 = 16.0;
+const AMBIENT_OCCLUSION_STRENGTH: f32 = 0.2;
 
 struct CameraUniform {
     chunk: vec3<i32>,
     _padding: u32,
     view_position: vec4<f32>,
     view_projection: mat4x4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    // w is the sky light factor
+    sun_direction: vec4<f32>,
+    zenith_color: vec4<f32>,
+    horizon_color: vec4<f32>,
+    // x is the fog start, y is the fog end
+    fog: vec4<f32>,
 };
 
 @group(0) @binding(0)
@@ -18,25 +26,28 @@ struct VertexDescription {
 struct QuadInput {
     @location(1) chunk: vec3<i32>,
     @location(2) texture_index: u32,
-    @location(3) vertex_0_position: vec3<f32>,
-    @location(4) vertex_0_texture_position: vec2<f32>,
-    @location(5) vertex_0_light_level: u32,
-    @location(6) vertex_1_position: vec3<f32>,
-    @location(7) vertex_1_texture_position: vec2<f32>,
-    @location(8) vertex_1_light_level: u32,
-    @location(9) vertex_2_position: vec3<f32>,
-    @location(10) vertex_2_texture_position: vec2<f32>,
-    @location(11) vertex_2_light_level: u32,
-    @location(12) vertex_3_position: vec3<f32>,
-    @location(13) vertex_3_texture_position: vec2<f32>,
-    @location(14) vertex_3_light_level: u32,
+    @location(3) texture_bounds: vec4<f32>,
+    @location(4) vertex_0_position: vec3<f32>,
+    @location(5) vertex_0_texture_position: vec2<f32>,
+    @location(6) vertex_0_light_level: u32,
+    @location(7) vertex_1_position: vec3<f32>,
+    @location(8) vertex_1_texture_position: vec2<f32>,
+    @location(9) vertex_1_light_level: u32,
+    @location(10) vertex_2_position: vec3<f32>,
+    @location(11) vertex_2_texture_position: vec2<f32>,
+    @location(12) vertex_2_light_level: u32,
+    @location(13) vertex_3_position: vec3<f32>,
+    @location(14) vertex_3_texture_position: vec2<f32>,
+    @location(15) vertex_3_light_level: u32,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) texture_index: u32,
     @location(1) texture_position: vec2<f32>,
-    @location(2) sky_light_level: f32,
+    @location(2) light_level: f32,
+    @location(3) @interpolate(flat) texture_bounds: vec4<f32>,
+    @location(4) view_distance: f32,
 };
 
 @vertex
@@ -74,42 +85,145 @@ fn vs_main(
     out.texture_position = texture_position_array[vertex_desc.index];
 
     out.clip_position = camera.view_projection * vec4<f32>(position, 1.0);
+    out.view_distance = distance(position, camera.view_position.xyz);
     
     out.texture_index = quad.texture_index;
+    out.texture_bounds = quad.texture_bounds;
 
     let sky_light_level: u32 = light_level_array[vertex_desc.index] & 0xFFu;
-    out.sky_light_level = f32(sky_light_level) / MAX_LIGHT_LEVEL_F32;
-    out.sky_light_level = pow(out.sky_light_level, 1.5);
-
-    // let light_r: u32 = in.joints >>  8u & 0xFFu;
-    // let light_g: u32 = in.joints >> 16u & 0xFFu;
-    // let light_b: u32 = in.joints >> 24u & 0xFFu;
+    let block_light_level: u32 = (light_level_array[vertex_desc.index] >> 8u) & 0xFFu;
+    // Sky light follows the time of the day
+    let sky_light = f32(sky_light_level) * camera.sun_direction.w;
+    out.light_level = max(sky_light, f32(block_light_level)) / MAX_LIGHT_LEVEL_F32;
+    out.light_level = pow(out.light_level, 1.5);
+
+    let ambient_occlusion: u32 = (light_level_array[vertex_desc.index] >> 16u) & 0x3u;
+    out.light_level *= 1.0 - AMBIENT_OCCLUSION_STRENGTH * f32(ambient_occlusion);
     
     return out;
 }
 
 
+const MAX_TEXTURE_ANIMATIONS: u32 = 64u;
+const ANIMATION_INDEX_SHIFT: u32 = 16u;
+
+struct TextureAnimations {
+    // x is the vertical offset of the current frame in pixels
+    frame_offsets: array<vec4<u32>, MAX_TEXTURE_ANIMATIONS>,
+};
+
 @group(1) @binding(0)
 var textures: texture_2d_array<u32>;
 
+@group(1) @binding(1)
+var<uniform> texture_animations: TextureAnimations;
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     var dimensions = textureDimensions(textures);
 
-    var texture_position = vec2<u32>(vec2<f32>(dimensions) * in.texture_position);
+    let texture_layer = in.texture_index & ((1u << ANIMATION_INDEX_SHIFT) - 1u);
+    let texture_animation = in.texture_index >> ANIMATION_INDEX_SHIFT;
+
+    // Mip level from the texture pixels covered by the screen pixel
+    let pixel_position = vec2<f32>(dimensions) * in.texture_position;
+    let pixel_footprint = max(length(dpdx(pixel_position)), length(dpdy(pixel_position)));
+    let mip_level = u32(clamp(
+        floor(log2(max(pixel_footprint, 1.0))),
+        0.0,
+        f32(textureNumLevels(textures) - 1u),
+    ));
+
+    var texture_position = vec2<u32>(pixel_position);
+
+    // Repeating the texture within the bounds
+    if (in.texture_bounds.z > in.texture_bounds.x) {
+        let bounds_min = in.texture_bounds.xy;
+        let bounds_size = in.texture_bounds.zw - bounds_min;
+        let wrapped = bounds_min + fract((in.texture_position - bounds_min) / bounds_size) * bounds_size;
+
+        texture_position = clamp(
+            vec2<u32>(vec2<f32>(dimensions) * wrapped),
+            vec2<u32>(vec2<f32>(dimensions) * bounds_min),
+            vec2<u32>(vec2<f32>(dimensions) * in.texture_bounds.zw),
+        );
+    }
+
+    if (texture_animation != 0u) {
+        texture_position.y += texture_animations.frame_offsets[texture_animation - 1u].x;
+    }
 
     var uint_output = textureLoad(
         textures,
-        texture_position,
-        in.texture_index,
-        0
+        texture_position >> vec2<u32>(mip_level),
+        texture_layer,
+        mip_level
     );
 
     var output: vec4<f32> = vec4<f32>(uint_output) / 255.0;
 
-    output[0] *= in.sky_light_level;
-    output[1] *= in.sky_light_level;
-    output[2] *= in.sky_light_level;
+    output[0] *= in.light_level;
+    output[1] *= in.light_level;
+    output[2] *= in.light_level;
+
+    // Distant blocks fade into the sky, hiding the edge of the loaded chunks.
+    // The color is premultiplied with the alpha
+    let fog_amount = smoothstep(camera.fog.x, camera.fog.y, in.view_distance);
+    let fog_color = camera.horizon_color.rgb * output.a;
+    output = vec4<f32>(mix(output.rgb, fog_color, fog_amount), output.a);
 
     return output;
 }
+
+
+const SUN_SIZE: f32 = 0.9995;
+const MOON_SIZE: f32 = 0.9997;
+const SUN_COLOR: vec3<f32> = vec3<f32>(1.0, 0.95, 0.8);
+const MOON_COLOR: vec3<f32> = vec3<f32>(0.8, 0.85, 0.9);
+
+struct SkyOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) screen_position: vec2<f32>,
+};
+
+@vertex
+fn vs_sky(@builtin(vertex_index) index: u32) -> SkyOutput {
+    var out: SkyOutput;
+
+    // Triangle covering the whole screen
+    let screen_position = vec2<f32>(
+        f32((index << 1u) & 2u) * 2.0 - 1.0,
+        f32(index & 2u) * 2.0 - 1.0,
+    );
+
+    out.clip_position = vec4<f32>(screen_position, 1.0, 1.0);
+    out.screen_position = screen_position;
+
+    return out;
+}
+
+@fragment
+fn fs_sky(in: SkyOutput) -> @location(0) vec4<f32> {
+    let far_position = camera.inverse_view_projection * vec4<f32>(in.screen_position, 1.0, 1.0);
+    let direction = normalize(far_position.xyz / far_position.w - camera.view_position.xyz);
+
+    let height = clamp(direction.z, 0.0, 1.0);
+    var color = mix(camera.horizon_color.rgb, camera.zenith_color.rgb, sqrt(height));
+
+    let sun_direction = camera.sun_direction.xyz;
+    let sun_cos = dot(direction, sun_direction);
+
+    // Glow around the sun
+    color += SUN_COLOR * pow(max(sun_cos, 0.0), 256.0) * 0.5;
+
+    if (sun_cos > SUN_SIZE) {
+        color = SUN_COLOR;
+    } else if (-sun_cos > MOON_SIZE) {
+        color = MOON_COLOR;
+    }
+
+    // Below the horizon the sky goes darker
+    color *= 1.0 - clamp(-direction.z, 0.0, 1.0) * 0.5;
+
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,443 @@
+//! A multi-producer, multi-consumer [`!Send`] and [`!Sync`] async broadcast channel.
+//!
+//! There are [`Sender`] and [`Receiver`] sides. Both are cloneable. Every value sent is observed
+//! by every [`Receiver`] that existed at the moment of sending. New receivers could be created with
+//! [`Sender::subscribe()`], they will only observe values sent after the subscription.
+//!
+//! The channel has a fixed capacity. If a receiver falls behind for more than the capacity, the
+//! oldest values are dropped for it and the next receive returns [`ReceiveError::Lagged`] with the
+//! number of values skipped. Values are dropped from the channel as soon as all receivers have
+//! seen them, the last receiver takes the value without cloning.
+//!
+//! When all [`Sender`]s are dropped, the channel becomes closed, but remaining messages can still
+//! be received. When all [`Receiver`]s are dropped, no more messages can be sent.
+//!
+//! # Examples
+//!
+//! ```
+//! futures_lite::future::block_on(async {
+//!     let (tx, mut rx1) = local_channel::broadcast::channel(16);
+//!     let mut rx2 = tx.subscribe();
+//!
+//!     assert!(tx.send("1").is_ok());
+//!     assert!(tx.send("2").is_ok());
+//!     assert_eq!(rx1.recv().await, Ok("1"));
+//!     assert_eq!(rx2.recv().await, Ok("1"));
+//!     assert_eq!(rx2.recv().await, Ok("2"));
+//!     assert_eq!(rx1.recv().await, Ok("2"));
+//! });
+//! ```
+
+use crate::SendError;
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    error::Error,
+    fmt,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{
+        Context,
+        Poll,
+        Waker,
+    },
+};
+
+/// Broadcast receive error.
+#[derive(PartialEq, Eq, Debug)]
+pub enum ReceiveError {
+    /// The receiver fell behind, contains the number of values skipped.
+    /// The next receive will return the oldest value still retained.
+    Lagged(u64),
+
+    /// The channel is closed, all senders have been dropped.
+    Closed,
+}
+
+impl fmt::Display for ReceiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl Error for ReceiveError {}
+
+/// Broadcast try receive error.
+#[derive(PartialEq, Eq, Debug)]
+pub enum TryReceiveError {
+    /// The channel buffer is empty, but senders are still active.
+    Empty,
+
+    /// The receiver fell behind, contains the number of values skipped.
+    /// The next receive will return the oldest value still retained.
+    Lagged(u64),
+
+    /// The channel is closed, all senders have been dropped.
+    Closed,
+}
+
+impl fmt::Display for TryReceiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl Error for TryReceiveError {}
+
+#[derive(Debug)]
+struct Entry<T> {
+    value: T,
+    // Receivers that have not seen the value yet
+    remaining: usize,
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    queue: VecDeque<Entry<T>>,
+    // Index of the front value of the queue
+    head: u64,
+    capacity: usize,
+    // Indexed by receiver slot
+    wakers: Vec<Option<Waker>>,
+    free_slots: Vec<usize>,
+    sender_count: usize,
+}
+
+impl<T> Shared<T> {
+    fn tail(&self) -> u64 {
+        self.head + self.queue.len() as u64
+    }
+
+    fn receiver_count(&self) -> usize {
+        self.wakers.len() - self.free_slots.len()
+    }
+
+    fn add_receiver(&mut self) -> usize {
+        match self.free_slots.pop() {
+            Some(slot) => slot,
+            None => {
+                self.wakers.push(None);
+                self.wakers.len() - 1
+            },
+        }
+    }
+
+    fn remove_receiver(&mut self, slot: usize, next: u64) {
+        let start = next.saturating_sub(self.head) as usize;
+
+        for entry in self.queue.iter_mut().skip(start) {
+            entry.remaining -= 1;
+        }
+
+        self.prune();
+
+        self.wakers[slot] = None;
+        self.free_slots.push(slot);
+    }
+
+    fn wake_all(&mut self) {
+        for waker in self.wakers.iter_mut() {
+            if let Some(waker) = waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    // Drops values that were seen by all receivers
+    fn prune(&mut self) {
+        while self.queue.front().is_some_and(|e| e.remaining == 0) {
+            self.queue.pop_front();
+            self.head += 1;
+        }
+    }
+}
+
+impl<T> Shared<T>
+where
+    T: Clone,
+{
+    fn take_next(&mut self, next: &mut u64) -> Result<T, TryReceiveError> {
+        if *next < self.head {
+            let lagged = self.head - *next;
+            *next = self.head;
+            return Err(TryReceiveError::Lagged(lagged));
+        }
+
+        let offset = (*next - self.head) as usize;
+
+        let Some(entry) = self.queue.get_mut(offset) else {
+            if self.sender_count == 0 {
+                return Err(TryReceiveError::Closed);
+            } else {
+                return Err(TryReceiveError::Empty);
+            }
+        };
+
+        *next += 1;
+        entry.remaining -= 1;
+
+        if entry.remaining == 0 && offset == 0 {
+            let entry = self.queue.pop_front().unwrap();
+            self.head += 1;
+            self.prune();
+            Ok(entry.value)
+        } else {
+            Ok(entry.value.clone())
+        }
+    }
+}
+
+/// Sends values to all associated `Receiver`s.
+#[derive(Debug)]
+pub struct Sender<T> {
+    shared: Rc<RefCell<Shared<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends a value to all currently existing `Receiver`s.
+    /// Returns either `Ok`, if the value sent successfully, or
+    /// `Err` with the sent value, if there are no receivers.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut shared = self.shared.borrow_mut();
+
+        let remaining = shared.receiver_count();
+
+        if remaining == 0 {
+            return Err(SendError(value));
+        }
+
+        if shared.queue.len() == shared.capacity {
+            shared.queue.pop_front();
+            shared.head += 1;
+        }
+
+        shared.queue.push_back(Entry { value, remaining });
+        shared.wake_all();
+
+        Ok(())
+    }
+
+    /// Creates a new `Receiver` that will observe values sent after this call.
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut shared = self.shared.borrow_mut();
+        let slot = shared.add_receiver();
+        let next = shared.tail();
+
+        Receiver {
+            shared: self.shared.clone(),
+            next,
+            slot,
+        }
+    }
+
+    /// Returns the number of the associated `Receiver`s alive.
+    pub fn receiver_count(&self) -> usize {
+        self.shared.borrow().receiver_count()
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut shared = self.shared.borrow_mut();
+        shared.sender_count -= 1;
+        if shared.sender_count == 0 {
+            shared.wake_all();
+        }
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.borrow_mut().sender_count += 1;
+
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+/// Receives values sent by the associated `Sender`s.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    shared: Rc<RefCell<Shared<T>>>,
+    // Index of the next value to receive
+    next: u64,
+    slot: usize,
+}
+
+impl<T> Receiver<T>
+where
+    T: Clone,
+{
+    /// Fetches a value from the queue or returns a `Future` that allows to wait for the
+    /// next value.
+    pub fn recv(&mut self) -> Receive<'_, T> {
+        Receive { receiver: self }
+    }
+
+    /// Tries to get an already sent value from the queue.
+    pub fn try_recv(&mut self) -> Result<T, TryReceiveError> {
+        self.shared.borrow_mut().take_next(&mut self.next)
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Checks whether there are any of the associated `Sender`s.
+    pub fn has_sender(&self) -> bool {
+        self.shared.borrow().sender_count > 0
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    /// The new `Receiver` starts at the same position as the cloned one.
+    fn clone(&self) -> Self {
+        let mut shared = self.shared.borrow_mut();
+        let start = self.next.saturating_sub(shared.head) as usize;
+
+        for entry in shared.queue.iter_mut().skip(start) {
+            entry.remaining += 1;
+        }
+
+        let slot = shared.add_receiver();
+
+        Self {
+            shared: self.shared.clone(),
+            next: self.next,
+            slot,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared
+            .borrow_mut()
+            .remove_receiver(self.slot, self.next);
+    }
+}
+
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Receive<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<'a, T> Future for Receive<'a, T>
+where
+    T: Clone,
+{
+    type Output = Result<T, ReceiveError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Receiver { shared, next, slot } = &mut *self.get_mut().receiver;
+
+        let mut shared = shared.borrow_mut();
+
+        match shared.take_next(next) {
+            Ok(value) => Poll::Ready(Ok(value)),
+            Err(TryReceiveError::Lagged(lagged)) => Poll::Ready(Err(ReceiveError::Lagged(lagged))),
+            Err(TryReceiveError::Closed) => Poll::Ready(Err(ReceiveError::Closed)),
+            Err(TryReceiveError::Empty) => {
+                shared.wakers[*slot] = Some(cx.waker().clone());
+                Poll::Pending
+            },
+        }
+    }
+}
+
+/// Creates a broadcast channel that retains at most `capacity` values
+/// not yet seen by all receivers.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "broadcast channel capacity must be non-zero");
+
+    let shared = Rc::new(RefCell::new(Shared {
+        queue: VecDeque::with_capacity(capacity),
+        head: 0,
+        capacity,
+        wakers: vec![None],
+        free_slots: Vec::new(),
+        sender_count: 1,
+    }));
+
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver {
+            shared,
+            next: 0,
+            slot: 0,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::future;
+
+    #[test]
+    fn test() {
+        future::block_on(async {
+            let (tx, mut rx1) = channel(2);
+            let mut rx2 = tx.subscribe();
+            assert_eq!(tx.receiver_count(), 2);
+
+            tx.send("test").unwrap();
+            assert_eq!(future::poll_once(rx1.recv()).await, Some(Ok("test")));
+            assert_eq!(future::poll_once(rx1.recv()).await, None);
+            assert_eq!(rx2.try_recv(), Ok("test"));
+            assert_eq!(rx2.try_recv(), Err(TryReceiveError::Empty));
+
+            // Values seen by all receivers are dropped
+            assert!(tx.shared.borrow().queue.is_empty());
+
+            // Late subscriber does not see earlier values
+            tx.send("test2").unwrap();
+            let mut rx3 = tx.subscribe();
+            tx.send("test3").unwrap();
+            assert_eq!(rx3.recv().await, Ok("test3"));
+            assert_eq!(rx1.recv().await, Ok("test2"));
+
+            // Clone starts at the same position
+            let mut rx4 = rx1.clone();
+            assert_eq!(rx4.recv().await, Ok("test3"));
+            assert_eq!(rx1.recv().await, Ok("test3"));
+            drop(rx4);
+
+            // rx2 still has "test2" and "test3" pending, the capacity is 2
+            tx.send("test4").unwrap();
+            assert_eq!(rx2.recv().await, Err(ReceiveError::Lagged(1)));
+            assert_eq!(rx2.recv().await, Ok("test3"));
+            assert_eq!(rx2.recv().await, Ok("test4"));
+            assert_eq!(rx1.recv().await, Ok("test4"));
+            assert_eq!(rx3.recv().await, Ok("test4"));
+
+            // Dropped receivers release their pending values
+            tx.send("test5").unwrap();
+            drop(rx3);
+            assert_eq!(rx1.recv().await, Ok("test5"));
+            assert_eq!(rx2.recv().await, Ok("test5"));
+            assert!(tx.shared.borrow().queue.is_empty());
+
+            let tx2 = tx.clone();
+            tx2.send("test6").unwrap();
+            drop(tx);
+            drop(tx2);
+            assert!(!rx1.has_sender());
+            assert_eq!(rx1.recv().await, Ok("test6"));
+            assert_eq!(rx1.recv().await, Err(ReceiveError::Closed));
+            assert_eq!(rx2.try_recv(), Ok("test6"));
+            assert_eq!(rx2.try_recv(), Err(TryReceiveError::Closed));
+
+            let (tx, rx) = channel(1);
+            drop(rx);
+            assert!(tx.send("test").is_err());
+        });
+    }
+}
@@ -1,17 +1,22 @@
 //! A collection of simple [`!Send`] and [`!Sync`] async channels with minimal dependencies.
 //!
-//! Currently, are two kinds of channels:
+//! Currently, there are four kinds of channels:
 //!
 //! 1. [`mpsc::channel()`] async channel with unlimited capacity.
 //! 2. [`oneshot::oneshot()`] async oneshot channel.
+//! 3. [`broadcast::channel()`] async bounded channel, where every receiver observes every value.
+//! 4. [`priority::channel()`] async channel with unlimited capacity, where the values of the
+//!    higher priority are received first.
 
 use std::{
     error::Error,
     fmt,
 };
 
+pub mod broadcast;
 pub mod mpsc;
 pub mod oneshot;
+pub mod priority;
 
 /// Error returned by the `Sender`s in case the associated `Receiver` was dropped.
 /// Contains the value that was tried to send.
@@ -5,6 +5,10 @@
 //! When all [`Sender`]s or the [`Receiver`] are dropped, the channel becomes closed. When a
 //! channel is closed, no more messages can be sent, but remaining messages can still be received.
 //!
+//! With the `stream` feature (enabled by default) the [`Receiver`] is a
+//! [`Stream`](futures_core::Stream) ending once the channel is closed and empty, so it can be
+//! merged with other streams directly.
+//!
 //! # Examples
 //!
 //! ```
@@ -23,6 +27,7 @@ use crate::{
     SendError,
     TryReceiveError,
 };
+#[cfg(feature = "stream")]
 use futures_core::Stream;
 use std::{
     cell::RefCell,
@@ -100,11 +105,25 @@ pub struct Receiver<T> {
 
 impl<T> Receiver<T> {
     /// Fetches a value from the queue or returns a `Future` that allows to wait for the
-    /// next value.
+    /// next value, or `ReceiveError::Closed` once all `Sender`s are dropped and the queue is
+    /// empty.
+    ///
+    /// The future is cancellation-safe: the values stay in the queue until it completes,
+    /// so it can be dropped unfinished, e.g. when racing it against other futures.
     pub fn recv(&mut self) -> Receive<'_, T> {
         Receive { receiver: self }
     }
 
+    /// Same as [`recv()`](Self::recv), but resolves to `None` once the channel is closed and
+    /// empty, like `StreamExt::next()` does without the `stream` feature.
+    ///
+    /// The future is cancellation-safe, just like [`recv()`](Self::recv).
+    pub fn recv_or_closed(&mut self) -> ReceiveOrClosed<'_, T> {
+        ReceiveOrClosed {
+            receive: self.recv(),
+        }
+    }
+
     /// Tries to get an already sent value from the queue.
     pub fn try_recv(&mut self) -> Result<T, TryReceiveError> {
         let mut shared = self.shared.borrow_mut();
@@ -116,12 +135,35 @@ impl<T> Receiver<T> {
         shared.queue.pop_front().ok_or(TryReceiveError::Empty)
     }
 
+    /// Waits for at least one value and moves up to `limit` queued values into `buffer`.
+    /// Returns the number of the values received, zero means that the channel is closed
+    /// and empty, or that the `limit` is zero.
+    ///
+    /// The future is cancellation-safe, just like [`recv()`](Self::recv).
+    pub fn recv_many<'a>(&'a mut self, buffer: &'a mut Vec<T>, limit: usize) -> ReceiveMany<'a, T> {
+        ReceiveMany {
+            receiver: self,
+            buffer,
+            limit,
+        }
+    }
+
+    /// Moves all values already sent into `buffer` without waiting.
+    /// Returns the number of the values moved.
+    pub fn drain_pending(&mut self, buffer: &mut Vec<T>) -> usize {
+        let mut shared = self.shared.borrow_mut();
+        let count = shared.queue.len();
+        buffer.extend(shared.queue.drain(..));
+        count
+    }
+
     /// Checks whether there are any of the associated `Sender`s.
     pub fn has_sender(&self) -> bool {
         Rc::strong_count(&self.shared) > 1
     }
 }
 
+#[cfg(feature = "stream")]
 impl<T> Stream for Receiver<T> {
     type Item = T;
 
@@ -166,6 +208,56 @@ impl<'a, T> Future for Receive<'a, T> {
     }
 }
 
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ReceiveOrClosed<'a, T> {
+    receive: Receive<'a, T>,
+}
+
+impl<'a, T> Future for ReceiveOrClosed<'a, T> {
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.receive).poll(cx).map(Result::ok)
+    }
+}
+
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ReceiveMany<'a, T> {
+    receiver: &'a mut Receiver<T>,
+    buffer: &'a mut Vec<T>,
+    limit: usize,
+}
+
+impl<'a, T> Future for ReceiveMany<'a, T> {
+    type Output = usize;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if this.limit == 0 {
+            return Poll::Ready(0);
+        }
+
+        let mut shared = this.receiver.shared.borrow_mut();
+
+        if shared.queue.is_empty() {
+            if Rc::strong_count(&this.receiver.shared) > 1 {
+                shared.waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            } else {
+                return Poll::Ready(0);
+            }
+        }
+
+        let count = shared.queue.len().min(this.limit);
+        this.buffer.extend(shared.queue.drain(.. count));
+
+        Poll::Ready(count)
+    }
+}
+
 impl<T> Drop for Receiver<T> {
     fn drop(&mut self) {
         let mut shared = self.shared.borrow_mut();
@@ -191,10 +283,9 @@ pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use futures_lite::{
-        future,
-        stream::StreamExt as _,
-    };
+    use futures_lite::future;
+    #[cfg(feature = "stream")]
+    use futures_lite::stream::StreamExt as _;
 
     #[test]
     fn test() {
@@ -214,7 +305,7 @@ mod tests {
             assert_eq!(future::poll_once(rx.recv()).await, Some(Ok("test")),);
             assert_eq!(future::poll_once(rx.recv()).await, Some(Ok("test2")),);
             drop(tx);
-            assert_eq!(rx.next().await, None);
+            assert_eq!(rx.recv_or_closed().await, None);
 
             let (tx, rx) = channel();
             tx.send("test").unwrap();
@@ -233,4 +324,75 @@ mod tests {
             assert!(rx.recv().await.is_err());
         });
     }
+
+    #[test]
+    fn recv_many() {
+        future::block_on(async {
+            let (tx, mut rx) = channel();
+            let mut buffer = Vec::new();
+
+            assert_eq!(future::poll_once(rx.recv_many(&mut buffer, 2)).await, None);
+            assert_eq!(rx.drain_pending(&mut buffer), 0);
+
+            for value in 0 .. 5 {
+                tx.send(value).unwrap();
+            }
+
+            assert_eq!(rx.recv_many(&mut buffer, 2).await, 2);
+            assert_eq!(buffer, [0, 1]);
+            assert_eq!(rx.recv_many(&mut buffer, 0).await, 0);
+            assert_eq!(rx.drain_pending(&mut buffer), 3);
+            assert_eq!(buffer, [0, 1, 2, 3, 4]);
+
+            tx.send(5).unwrap();
+            drop(tx);
+            buffer.clear();
+            assert_eq!(rx.recv_many(&mut buffer, 10).await, 1);
+            assert_eq!(rx.recv_many(&mut buffer, 10).await, 0);
+            assert_eq!(buffer, [5]);
+        });
+    }
+
+    #[test]
+    fn recv_cancelled() {
+        future::block_on(async {
+            let (tx, mut rx) = channel();
+
+            // Dropping the pending future loses nothing
+            let pending = future::or(async { rx.recv().await.ok() }, async { None }).await;
+            assert_eq!(pending, None);
+
+            tx.send("test").unwrap();
+            drop(tx);
+            assert_eq!(rx.recv().await, Ok("test"));
+            assert_eq!(rx.recv().await, Err(ReceiveError::Closed));
+        });
+    }
+
+    #[test]
+    fn recv_or_closed() {
+        future::block_on(async {
+            let (tx, mut rx) = channel();
+
+            assert_eq!(future::poll_once(rx.recv_or_closed()).await, None);
+
+            tx.send("test").unwrap();
+            drop(tx);
+            assert_eq!(rx.recv_or_closed().await, Some("test"));
+            assert_eq!(rx.recv_or_closed().await, None);
+        });
+    }
+
+    #[cfg(feature = "stream")]
+    #[test]
+    fn stream() {
+        future::block_on(async {
+            let (tx, mut rx) = channel();
+
+            tx.send("test").unwrap();
+            drop(tx);
+            assert_eq!(rx.next().await, Some("test"));
+            assert_eq!(rx.next().await, None);
+        });
+    }
 }
@@ -64,6 +64,12 @@ impl<T> Sender<T> {
             Err(SendError(value))
         }
     }
+
+    /// Returns `true` if the `Receiver` has been dropped or closed,
+    /// no more values can be sent.
+    pub fn is_closed(&self) -> bool {
+        !self.shared.borrow().is_open
+    }
 }
 
 impl<T> Drop for Sender<T> {
@@ -86,15 +92,22 @@ pub struct Receiver<T> {
 }
 
 impl<T> Receiver<T> {
-    /// Tries to get an already sent value.
+    /// Tries to get an already sent value without waiting.
+    /// The value sent before the channel was closed is still returned.
     pub fn try_recv(&mut self) -> Result<T, TryReceiveError> {
         let mut shared = self.shared.borrow_mut();
 
-        if !shared.is_open {
-            return Err(TryReceiveError::Closed);
+        match shared.value.take() {
+            Some(value) => Ok(value),
+            None if shared.is_open => Err(TryReceiveError::Empty),
+            None => Err(TryReceiveError::Closed),
         }
+    }
 
-        shared.value.take().ok_or(TryReceiveError::Empty)
+    /// Closes the channel, so that the subsequent sends fail with `SendError`.
+    /// The value sent before can still be received.
+    pub fn close(&mut self) {
+        self.shared.borrow_mut().is_open = false;
     }
 }
 
@@ -170,4 +183,36 @@ mod tests {
             assert_eq!(rx.await.unwrap(), "test1");
         });
     }
+
+    #[test]
+    fn try_recv() {
+        let (tx, mut rx) = oneshot();
+        assert_eq!(rx.try_recv(), Err(TryReceiveError::Empty));
+        tx.send("test").unwrap();
+        assert_eq!(rx.try_recv(), Ok("test"));
+        assert_eq!(rx.try_recv(), Err(TryReceiveError::Empty));
+
+        tx.send("test1").unwrap();
+        drop(tx);
+        assert_eq!(rx.try_recv(), Ok("test1"));
+        assert_eq!(rx.try_recv(), Err(TryReceiveError::Closed));
+    }
+
+    #[test]
+    fn close() {
+        future::block_on(async {
+            let (tx, mut rx) = oneshot();
+            assert!(!tx.is_closed());
+            tx.send("test").unwrap();
+            rx.close();
+            assert!(tx.is_closed());
+            assert!(tx.send("test1").is_err());
+            assert_eq!((&mut rx).await, Ok("test"));
+            assert_eq!(rx.await, Err(ReceiveError::Closed));
+
+            let (tx, rx) = oneshot::<()>();
+            drop(rx);
+            assert!(tx.is_closed());
+        });
+    }
 }
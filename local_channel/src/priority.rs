@@ -0,0 +1,243 @@
+//! A multi-producer, single-consumer [`!Send`] and [`!Sync`] async channel with the message
+//! priorities.
+//!
+//! Works the same way as [`mpsc`](crate::mpsc), but every value is sent with a priority level.
+//! The [`Receiver`] always gets the value of the highest level first, the values of the same
+//! level are received in the order they were sent.
+//!
+//! # Examples
+//!
+//! ```
+//! futures_lite::future::block_on(async {
+//!     let (tx, mut rx) = local_channel::priority::channel();
+//!
+//!     assert!(tx.send(0, "low").is_ok());
+//!     assert!(tx.send(1, "high").is_ok());
+//!     assert_eq!(rx.recv().await, Ok("high"));
+//!     assert_eq!(rx.recv().await, Ok("low"));
+//! });
+//! ```
+
+use crate::{
+    ReceiveError,
+    SendError,
+    TryReceiveError,
+};
+#[cfg(feature = "stream")]
+use futures_core::Stream;
+use std::{
+    cell::RefCell,
+    collections::{
+        BTreeMap,
+        VecDeque,
+    },
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{
+        Context,
+        Poll,
+        Waker,
+    },
+};
+
+#[derive(Debug)]
+struct Shared<T> {
+    /// Queues of the levels that have values.
+    queues: BTreeMap<u8, VecDeque<T>>,
+    waker: Option<Waker>,
+    has_receiver: bool,
+}
+
+impl<T> Shared<T> {
+    fn pop(&mut self) -> Option<T> {
+        let mut entry = self.queues.last_entry()?;
+        let value = entry.get_mut().pop_front();
+
+        if entry.get().is_empty() {
+            entry.remove();
+        }
+
+        value
+    }
+}
+
+/// Sends values to the associated `Receiver`.
+#[derive(Debug)]
+pub struct Sender<T> {
+    shared: Rc<RefCell<Shared<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends a value with the priority level, higher levels are received first.
+    /// Returns either `Ok`, if the value sent successfully, or
+    /// `Err` with the sent value, if the channel is closed.
+    pub fn send(&self, priority: u8, value: T) -> Result<(), SendError<T>> {
+        let mut shared = self.shared.borrow_mut();
+        if shared.has_receiver {
+            shared.queues.entry(priority).or_default().push_back(value);
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+            Ok(())
+        } else {
+            Err(SendError(value))
+        }
+    }
+
+    /// Checks whether the associated `Receiver` is still alive.
+    pub fn has_receiver(&self) -> bool {
+        self.shared.borrow().has_receiver
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut shared = self.shared.borrow_mut();
+        if Rc::strong_count(&self.shared) == 2 && shared.has_receiver {
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+/// Receives values sent by the associated `Sender`s, the highest priority first.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    shared: Rc<RefCell<Shared<T>>>,
+}
+
+impl<T> Receiver<T> {
+    /// Fetches the highest priority value or returns a `Future` that allows to wait for the
+    /// next value, or `ReceiveError::Closed` once all `Sender`s are dropped and nothing is left.
+    ///
+    /// The future is cancellation-safe, the values are kept until it completes.
+    pub fn recv(&mut self) -> Receive<'_, T> {
+        Receive { receiver: self }
+    }
+
+    /// Tries to get the highest priority value already sent.
+    pub fn try_recv(&mut self) -> Result<T, TryReceiveError> {
+        let mut shared = self.shared.borrow_mut();
+
+        match shared.pop() {
+            Some(value) => Ok(value),
+            None if Rc::strong_count(&self.shared) > 1 => Err(TryReceiveError::Empty),
+            None => Err(TryReceiveError::Closed),
+        }
+    }
+
+    /// Checks whether there are any of the associated `Sender`s.
+    pub fn has_sender(&self) -> bool {
+        Rc::strong_count(&self.shared) > 1
+    }
+
+    fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut shared = self.shared.borrow_mut();
+        match shared.pop() {
+            Some(value) => Poll::Ready(Some(value)),
+            None => {
+                if Rc::strong_count(&self.shared) > 1 {
+                    shared.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                } else {
+                    Poll::Ready(None)
+                }
+            },
+        }
+    }
+}
+
+#[cfg(feature = "stream")]
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_recv(cx)
+    }
+}
+
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Receive<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<'a, T> Future for Receive<'a, T> {
+    type Output = Result<T, ReceiveError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.receiver
+            .poll_recv(cx)
+            .map(|value| value.ok_or(ReceiveError::Closed))
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut shared = self.shared.borrow_mut();
+        shared.has_receiver = false;
+    }
+}
+
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Rc::new(RefCell::new(Shared {
+        queues: BTreeMap::new(),
+        waker: None,
+        has_receiver: true,
+    }));
+
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::future;
+
+    #[test]
+    fn test() {
+        future::block_on(async {
+            let (tx, mut rx) = channel();
+            tx.send(0, "low1").unwrap();
+            tx.send(2, "high").unwrap();
+            tx.send(0, "low2").unwrap();
+            tx.send(1, "middle").unwrap();
+            assert_eq!(rx.recv().await, Ok("high"));
+            assert_eq!(rx.recv().await, Ok("middle"));
+            assert_eq!(rx.try_recv(), Ok("low1"));
+
+            let tx2 = tx.clone();
+            tx2.send(1, "middle").unwrap();
+            drop(tx2);
+            assert_eq!(rx.recv().await, Ok("middle"));
+            assert_eq!(rx.recv().await, Ok("low2"));
+            assert_eq!(future::poll_once(rx.recv()).await, None);
+            assert_eq!(rx.try_recv(), Err(TryReceiveError::Empty));
+
+            tx.send(0, "low").unwrap();
+            drop(tx);
+            assert_eq!(rx.recv().await, Ok("low"));
+            assert_eq!(rx.recv().await, Err(ReceiveError::Closed));
+            assert_eq!(rx.try_recv(), Err(TryReceiveError::Closed));
+
+            let (tx, rx) = channel();
+            drop(rx);
+            assert!(tx.send(0, "test").is_err());
+        });
+    }
+}
@@ -0,0 +1,86 @@
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct DimensionKind(pub u32);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Dimension {
+    pub kind: DimensionKind,
+    pub phase: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Chunk {
+    pub position: [i32; 3],
+    pub dimension: Dimension,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub struct BlockClass(pub u64);
+
+/// Input of the script dispatched by the server.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DispatchInput<'a> {
+    pub payload: &'a [u8],
+}
+
+/// Block at the `offset` in blocks from the origin of the `chunk`,
+/// the block could be in another chunk.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetBlockClassRequest {
+    pub chunk: Chunk,
+    pub offset: [i32; 3],
+}
+
+/// Per-block state synchronized by the server, `kind` tells how to interpret the `data`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BlockEntity {
+    pub kind: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct PlayerPosition {
+    pub chunk: Chunk,
+    pub offset: [f32; 3],
+}
+
+/// Emits the particles of the client emitter with the `emitter` label
+/// at the `offset` in blocks from the origin of the `chunk`.
+/// `block_class` provides the texture for the emitters using the block texture.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SpawnParticlesRequest<'a> {
+    pub emitter: &'a str,
+    pub chunk: Chunk,
+    pub offset: [f32; 3],
+    pub block_class: Option<BlockClass>,
+}
+
+/// Point of the screen the HUD element is placed relative to.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum HudAnchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+/// Shows the text on the screen, replacing the element with the same `id`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ShowHudTextRequest<'a> {
+    pub id: &'a str,
+    pub text: &'a str,
+    pub anchor: HudAnchor,
+    /// Offset from the anchor in points.
+    pub offset: [f32; 2],
+    /// The text stays until removed if `None`.
+    pub duration_ms: Option<u64>,
+}
@@ -0,0 +1,9 @@
+mod common;
+pub use common::*;
+
+#[cfg(feature = "script")]
+mod script;
+
+#[cfg(feature = "script")]
+pub use script::*;
+pub use serde;
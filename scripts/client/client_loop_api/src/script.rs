@@ -0,0 +1,181 @@
+use crate::common::*;
+use postcard::ser_flavors::Flavor;
+use serde::{
+    de::DeserializeOwned,
+    Serialize,
+};
+use std::{
+    io::Write,
+    panic,
+    ptr,
+};
+
+static mut SHARED_BUFFER: Vec<u8> = Vec::new();
+
+mod import {
+    extern "C" {
+        pub fn handle_panic(ptr: *const u8, len: u32);
+        pub fn get_block_class_by_label(ptr: *const u8, len: u32);
+        pub fn get_block_class(ptr: *const u8, len: u32);
+        pub fn get_block_entity(ptr: *const u8, len: u32);
+        pub fn get_player_position(ptr: *const u8, len: u32);
+        pub fn show_hud_text(ptr: *const u8, len: u32);
+        pub fn remove_hud_element(ptr: *const u8, len: u32);
+        pub fn spawn_particles(ptr: *const u8, len: u32);
+    }
+}
+
+pub fn handle_panic(script_name: &'static str) {
+    panic::set_hook(Box::new(move |panic_info| {
+        unsafe {
+            let shared_buffer = &mut *ptr::addr_of_mut!(SHARED_BUFFER);
+
+            let _ = write!(shared_buffer, "script \"{}\": {}", script_name, panic_info);
+
+            import::handle_panic(shared_buffer.as_ptr(), shared_buffer.len() as u32);
+        }
+    }));
+}
+
+macro_rules! wrap_func {
+    ($name:ident, $input_type:ty) => {
+        pub fn $name(input: $input_type) {
+            let (req_ptr, req_len) = write_buffer(input);
+
+            unsafe { import::$name(req_ptr, req_len as u32) };
+        }
+    };
+    ($name:ident, $input_type:ty, $output_type:ty) => {
+        pub fn $name(input: $input_type) -> $output_type {
+            let (req_ptr, req_len) = write_buffer(input);
+
+            unsafe { import::$name(req_ptr, req_len as u32) };
+
+            read_buffer::<$output_type>().expect("incorrect host response")
+        }
+    };
+}
+
+/// Get pointer to the shared buffer of given length. Will reallocate the buffer if required.
+#[no_mangle]
+pub extern "C" fn get_buffer(len: u32) -> *mut u8 {
+    let len = len as usize;
+
+    unsafe {
+        let shared_buffer = &mut *ptr::addr_of_mut!(SHARED_BUFFER);
+
+        if shared_buffer.capacity() < len {
+            shared_buffer.reserve(len - shared_buffer.capacity());
+        }
+
+        shared_buffer.set_len(len);
+        shared_buffer.as_mut_ptr()
+    }
+}
+
+/// Deserialize value from the shared buffer.
+pub fn read_buffer<T>() -> Option<T>
+where
+    T: DeserializeOwned,
+{
+    // Safety: no reference must escape the block
+    unsafe {
+        let shared_buffer = &mut *ptr::addr_of_mut!(SHARED_BUFFER);
+
+        postcard::from_bytes(shared_buffer.as_slice()).ok()
+    }
+}
+
+/// Deserialize payload the server has dispatched the script with.
+pub fn read_dispatch_input<T>() -> Option<T>
+where
+    T: DeserializeOwned,
+{
+    // Safety: no reference must escape the block
+    unsafe {
+        let shared_buffer = &mut *ptr::addr_of_mut!(SHARED_BUFFER);
+
+        let input = postcard::from_bytes::<DispatchInput>(shared_buffer.as_slice()).ok()?;
+
+        postcard::from_bytes::<T>(input.payload).ok()
+    }
+}
+
+struct Writer<W> {
+    written: usize,
+    writer: W,
+}
+
+impl<W> Flavor for Writer<W>
+where
+    W: Write,
+{
+    type Output = usize;
+
+    fn try_push(&mut self, data: u8) -> postcard::Result<()> {
+        self.writer
+            .write_all(&[data])
+            .map_err(|_| postcard::Error::SerializeBufferFull)?;
+        self.written += 1;
+        Ok(())
+    }
+
+    fn finalize(mut self) -> postcard::Result<Self::Output> {
+        self.writer
+            .flush()
+            .map_err(|_| postcard::Error::SerializeBufferFull)?;
+        Ok(self.written)
+    }
+
+    fn try_extend(&mut self, data: &[u8]) -> postcard::Result<()> {
+        self.writer
+            .write_all(data)
+            .map_err(|_| postcard::Error::SerializeBufferFull)?;
+        self.written += data.len();
+        Ok(())
+    }
+}
+
+/// Serialize the value into the shared buffer.
+/// WARNING: this will overwrite content of the shared buffer.
+pub fn write_buffer<T>(value: T) -> (*const u8, usize)
+where
+    T: Serialize,
+{
+    // Safety: no reference must escape the block
+    unsafe {
+        let shared_buffer = &mut *ptr::addr_of_mut!(SHARED_BUFFER);
+
+        shared_buffer.clear();
+
+        postcard::serialize_with_flavor(
+            &value,
+            Writer {
+                written: 0,
+                writer: &mut *shared_buffer,
+            },
+        )
+        .unwrap();
+
+        let slice = shared_buffer.as_slice();
+
+        (slice.as_ptr(), slice.len())
+    }
+}
+
+wrap_func!(get_block_class_by_label, &str, Option<BlockClass>);
+
+// Returns `None` if the chunk of the block is not loaded on the client.
+wrap_func!(get_block_class, GetBlockClassRequest, Option<BlockClass>);
+
+// Takes the same request as `get_block_class`,
+// returns `None` if the block has no block entity or its chunk is not loaded.
+wrap_func!(get_block_entity, GetBlockClassRequest, Option<BlockEntity>);
+
+wrap_func!(get_player_position, (), Option<PlayerPosition>);
+
+wrap_func!(show_hud_text, ShowHudTextRequest);
+
+wrap_func!(remove_hud_element, &str);
+
+wrap_func!(spawn_particles, SpawnParticlesRequest);
@@ -100,12 +100,29 @@ pub struct GetTargetBlockRequest {
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub struct BlockClass(pub u64);
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ItemClass(pub u64);
+
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub struct Actor(pub u64);
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub struct Action(pub u64);
 
+/// Custom section of the script module listing the labels of the actions the script refers to
+/// with the `action!` macro, one per line. The host checks those when loading the scripts.
+pub const ACTION_LABEL_SECTION: &str = "action_labels";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Player(pub u64);
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Permission {
+    PlaceBlock,
+    RemoveBlock,
+    Administrate,
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub struct ActionInput<'a> {
     pub action: Action,
@@ -120,9 +137,261 @@ pub struct GetTargetBlockResponse {
     pub side: u8,
 }
 
+/// Blocks taken into account by the spatial queries.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum BlockFilter {
+    /// Blocks the actors collide with.
+    Solid,
+    /// Blocks of any of the classes.
+    Classes(Vec<BlockClass>),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RaycastRequest {
+    pub chunk: Chunk,
+    pub offset: [f32; 3],
+    pub direction: [f32; 3],
+    pub max_distance: f32,
+    pub filter: BlockFilter,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RaycastResponse {
+    /// Time of the hit in `direction` lengths.
+    pub time: f32,
+    pub chunk: Chunk,
+    pub block: Block,
+    pub side: u8,
+}
+
+/// Moves the box with the `radius` half-size by `movement`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CastBoxRequest {
+    pub chunk: Chunk,
+    pub offset: [f32; 3],
+    pub radius: [f32; 3],
+    pub movement: [f32; 3],
+    pub filter: BlockFilter,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CastBoxResponse {
+    /// Fraction of the movement done before the hit.
+    pub time: f32,
+    /// Zero if the box overlaps the block at the start.
+    pub normal: [f32; 3],
+    pub chunk: Chunk,
+    pub block: Block,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OverlapSphereRequest {
+    pub chunk: Chunk,
+    pub offset: [f32; 3],
+    pub radius: f32,
+    pub filter: BlockFilter,
+}
+
+/// Passed as the action data to the script of the projectile hit action.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProjectileHit {
+    pub chunk: Chunk,
+    pub block: Block,
+    pub side: u8,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PlayerHasPermissionRequest {
+    pub player: Player,
+    pub permission: Permission,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SetClassOfBlockRequest {
     pub chunk: Chunk,
     pub block: Block,
     pub block_class: BlockClass,
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ItemsRequest {
+    pub actor: Actor,
+    pub item_class: ItemClass,
+    pub amount: u32,
+}
+
+/// `source` is the actor that has caused the damage, if any.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DamageActorRequest {
+    pub actor: Actor,
+    pub amount: u32,
+    pub source: Option<Actor>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ActorHealth {
+    pub current: u32,
+    pub max: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Effect(pub u64);
+
+/// Replaces the instance of the effect the actor already has.
+/// The effect tick script runs every `tick_period` ticks, `0` disables the ticks.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApplyEffectRequest {
+    pub actor: Actor,
+    pub effect: Effect,
+    pub magnitude: u32,
+    pub duration_ticks: u64,
+    pub tick_period: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActorEffectRequest {
+    pub actor: Actor,
+    pub effect: Effect,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Recipe(pub u64);
+
+/// Moves the actor to the position, the chunk may be in another dimension.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TransferActorRequest {
+    pub actor: Actor,
+    pub chunk: Chunk,
+    pub offset: [f32; 3],
+}
+
+/// Keeps the chunks within the `radius` around the `chunk` active without players nearby,
+/// for `ticks` or until removed if not set.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AddChunkTicketRequest {
+    pub chunk: Chunk,
+    pub radius: i32,
+    pub ticks: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct RecipeIngredient {
+    pub item_class: ItemClass,
+    pub amount: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetRecipeResponse {
+    pub recipe: Recipe,
+    pub inputs: Vec<RecipeIngredient>,
+    pub outputs: Vec<RecipeIngredient>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CountItemsRequest {
+    pub actor: Actor,
+    pub item_class: ItemClass,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetBlockMetadataRequest {
+    pub chunk: Chunk,
+    pub block: Block,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ScheduleScriptRequest<'a> {
+    pub script: &'a str,
+    pub delay_ticks: u64,
+    pub payload: &'a [u8],
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DispatchClientScriptRequest<'a> {
+    pub player: Player,
+    pub script: &'a str,
+    pub payload: &'a [u8],
+}
+
+/// Input of the block class random tick script.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RandomTickInput {
+    pub chunk: Chunk,
+    pub block: Block,
+    pub block_class: BlockClass,
+}
+
+/// Input of the block class neighbor changed script,
+/// `changed_chunk` and `changed_block` locate the neighbor that has changed its class.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NeighborChangedInput {
+    pub chunk: Chunk,
+    pub block: Block,
+    pub block_class: BlockClass,
+    pub changed_chunk: Chunk,
+    pub changed_block: Block,
+}
+
+/// Input of the actor death script.
+/// Player actors are respawned and other actors are removed after the script has run,
+/// unless the script has restored their health.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeathInput {
+    pub actor: Actor,
+    pub source: Option<Actor>,
+}
+
+/// Input of the effect tick script.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EffectTickInput {
+    pub actor: Actor,
+    pub effect: Effect,
+    pub magnitude: u32,
+    pub remaining_ticks: u64,
+}
+
+/// Input of the script run by the schedule.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ScheduledInput<'a> {
+    pub payload: &'a [u8],
+}
+
+/// `metadata` of `None` removes the block metadata.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetBlockMetadataRequest {
+    pub chunk: Chunk,
+    pub block: Block,
+    pub metadata: Option<Vec<u8>>,
+}
+
+/// Box between the two corner blocks, both inclusive, filled with the `block_class`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FillRegionRequest {
+    pub chunk_a: Chunk,
+    pub block_a: Block,
+    pub chunk_b: Chunk,
+    pub block_b: Block,
+    pub block_class: BlockClass,
+}
+
+/// Per-block state synchronized to the clients viewing the chunk.
+/// `kind` tells the client scripts how to interpret the `data`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BlockEntity {
+    pub kind: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetBlockEntityRequest {
+    pub chunk: Chunk,
+    pub block: Block,
+}
+
+/// `block_entity` of `None` removes the block entity.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetBlockEntityRequest {
+    pub chunk: Chunk,
+    pub block: Block,
+    pub block_entity: Option<BlockEntity>,
+}
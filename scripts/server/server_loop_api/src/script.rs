@@ -17,12 +17,41 @@ mod import {
     extern "C" {
         pub fn handle_panic(ptr: *const u8, len: u32);
         pub fn get_blocks_in_chunk_edge() -> u32;
+        pub fn get_world_seed() -> u64;
+        pub fn get_random() -> u64;
 
         // The ones below use postcard to serialize input/output from/into shared buffer:
         pub fn get_target_block(ptr: *const u8, len: u32);
+        pub fn raycast(ptr: *const u8, len: u32);
+        pub fn cast_box(ptr: *const u8, len: u32);
+        pub fn overlap_sphere(ptr: *const u8, len: u32);
         pub fn set_class_of_block(ptr: *const u8, len: u32);
+        pub fn fill_region(ptr: *const u8, len: u32);
+        pub fn get_block_metadata(ptr: *const u8, len: u32);
+        pub fn set_block_metadata(ptr: *const u8, len: u32);
+        pub fn get_block_entity(ptr: *const u8, len: u32);
+        pub fn set_block_entity(ptr: *const u8, len: u32);
+        pub fn schedule_script(ptr: *const u8, len: u32);
+        pub fn dispatch_client_script(ptr: *const u8, len: u32);
+        pub fn get_item_class_by_label(ptr: *const u8, len: u32);
+        pub fn add_items(ptr: *const u8, len: u32);
+        pub fn remove_items(ptr: *const u8, len: u32);
+        pub fn count_items(ptr: *const u8, len: u32);
+        pub fn get_recipe_by_label(ptr: *const u8, len: u32);
+        pub fn damage_actor(ptr: *const u8, len: u32);
+        pub fn get_actor_health(ptr: *const u8, len: u32);
+        pub fn get_effect_by_label(ptr: *const u8, len: u32);
+        pub fn get_action_by_label(ptr: *const u8, len: u32);
+        pub fn apply_effect(ptr: *const u8, len: u32);
+        pub fn remove_effect(ptr: *const u8, len: u32);
+        pub fn get_dimension_kind_by_label(ptr: *const u8, len: u32);
+        pub fn transfer_actor(ptr: *const u8, len: u32);
         pub fn get_block_class_by_label(ptr: *const u8, len: u32);
         pub fn broadcast_action_local(ptr: *const u8, len: u32);
+        pub fn get_player_of_actor(ptr: *const u8, len: u32);
+        pub fn player_has_permission(ptr: *const u8, len: u32);
+        pub fn add_chunk_ticket(ptr: *const u8, len: u32);
+        pub fn remove_chunk_ticket(ptr: *const u8, len: u32);
     }
 }
 
@@ -115,6 +144,89 @@ where
     }
 }
 
+/// Deserialize payload of the scheduled script run from the shared buffer.
+pub fn read_scheduled_input<T>() -> Option<T>
+where
+    T: DeserializeOwned,
+{
+    // Safety: no reference must escape the block
+    unsafe {
+        let shared_buffer = &mut *ptr::addr_of_mut!(SHARED_BUFFER);
+
+        let input = postcard::from_bytes::<ScheduledInput>(shared_buffer.as_slice()).ok()?;
+
+        postcard::from_bytes::<T>(input.payload).ok()
+    }
+}
+
+/// Run the script with the given label in `delay_ticks` server ticks,
+/// the script receives the `payload` that can be read with `read_scheduled_input`.
+/// The delay of `0` runs the script on the next tick.
+pub fn schedule_script<T>(script: &str, delay_ticks: u64, payload: T)
+where
+    T: Serialize,
+{
+    static mut PAYLOAD_BUFFER: Vec<u8> = Vec::new();
+
+    // Safety: no reference must escape the block
+    unsafe {
+        let payload_buffer = &mut *ptr::addr_of_mut!(PAYLOAD_BUFFER);
+
+        payload_buffer.clear();
+
+        postcard::serialize_with_flavor(
+            &payload,
+            Writer {
+                written: 0,
+                writer: &mut *payload_buffer,
+            },
+        )
+        .unwrap();
+
+        let (input_slice_ptr, input_slice_len) = write_buffer(ScheduleScriptRequest {
+            script,
+            delay_ticks,
+            payload: payload_buffer.as_slice(),
+        });
+
+        import::schedule_script(input_slice_ptr, input_slice_len.try_into().unwrap());
+    }
+}
+
+/// Run the client script with the given label on the client of the `player`,
+/// the script receives the `payload` as its dispatch input.
+/// The dispatches are sent after the scripts of the current tick have run.
+pub fn dispatch_client_script<T>(player: Player, script: &str, payload: T)
+where
+    T: Serialize,
+{
+    static mut PAYLOAD_BUFFER: Vec<u8> = Vec::new();
+
+    // Safety: no reference must escape the block
+    unsafe {
+        let payload_buffer = &mut *ptr::addr_of_mut!(PAYLOAD_BUFFER);
+
+        payload_buffer.clear();
+
+        postcard::serialize_with_flavor(
+            &payload,
+            Writer {
+                written: 0,
+                writer: &mut *payload_buffer,
+            },
+        )
+        .unwrap();
+
+        let (input_slice_ptr, input_slice_len) = write_buffer(DispatchClientScriptRequest {
+            player,
+            script,
+            payload: payload_buffer.as_slice(),
+        });
+
+        import::dispatch_client_script(input_slice_ptr, input_slice_len.try_into().unwrap());
+    }
+}
+
 // TODO instead of None optionally have a possibility to pass a position.
 pub fn broadcast_action<T>(action: Action, actor: Option<Actor>, data: T)
 where
@@ -225,6 +337,28 @@ pub fn blocks_in_chunk_edge() -> usize {
     }
 }
 
+/// Seed of the world, for the scripts to derive consistent randomness from.
+pub fn world_seed() -> u64 {
+    unsafe { import::get_world_seed() }
+}
+
+/// Random number from the world generator shared with the server systems.
+/// The sequence is the same for the same world seed, snapshot and events,
+/// so the replays of the scripted randomness match the recording.
+pub fn random_u64() -> u64 {
+    unsafe { import::get_random() }
+}
+
+/// Uniformly distributed within `0 .. bound`, `bound` must not be zero.
+pub fn random_below(bound: u64) -> u64 {
+    ((random_u64() as u128 * bound as u128) >> 64) as u64
+}
+
+/// Uniformly distributed within `0.0 .. 1.0`.
+pub fn random_f32() -> f32 {
+    (random_u64() >> 40) as f32 / (1u64 << 24) as f32
+}
+
 pub fn blocks_in_chunk_layer() -> usize {
     unsafe {
         if BLOCKS_IN_CHUNK_LAYER == 0 {
@@ -295,6 +429,12 @@ wrap_func!(
     Option<GetTargetBlockResponse>
 );
 
+wrap_func!(raycast, RaycastRequest, Option<RaycastResponse>);
+
+wrap_func!(cast_box, CastBoxRequest, Option<CastBoxResponse>);
+
+wrap_func!(overlap_sphere, OverlapSphereRequest, Vec<(Chunk, Block)>);
+
 wrap_func!(get_block_class_by_label, &str, Option<BlockClass>);
 
 #[macro_export]
@@ -316,3 +456,96 @@ macro_rules! block_class {
 }
 
 wrap_func!(set_class_of_block, SetClassOfBlockRequest);
+
+// Returns the amount of the blocks changed, nothing is changed and `None` is returned
+// if the region is too large or some of its chunks are not loaded
+wrap_func!(fill_region, FillRegionRequest, Option<u64>);
+
+wrap_func!(get_block_metadata, GetBlockMetadataRequest, Option<Vec<u8>>);
+
+wrap_func!(set_block_metadata, SetBlockMetadataRequest);
+
+wrap_func!(get_block_entity, GetBlockEntityRequest, Option<BlockEntity>);
+
+wrap_func!(set_block_entity, SetBlockEntityRequest);
+
+wrap_func!(get_player_of_actor, Actor, Option<Player>);
+
+wrap_func!(get_item_class_by_label, &str, Option<ItemClass>);
+
+// Returns the amount that did not fit into the actor inventory
+wrap_func!(add_items, ItemsRequest, u32);
+
+// Removes nothing and returns `false` if the actor does not have enough items
+wrap_func!(remove_items, ItemsRequest, bool);
+
+wrap_func!(count_items, CountItemsRequest, u32);
+
+wrap_func!(get_recipe_by_label, &str, Option<GetRecipeResponse>);
+
+// Returns `true` if the damage has killed the actor.
+wrap_func!(damage_actor, DamageActorRequest, bool);
+
+wrap_func!(get_actor_health, Actor, Option<ActorHealth>);
+
+wrap_func!(get_effect_by_label, &str, Option<Effect>);
+
+wrap_func!(get_action_by_label, &str, Option<Action>);
+
+/// Line of the label custom section: the label followed by a newline.
+#[doc(hidden)]
+pub const fn label_section_line<const N: usize>(label: &str) -> [u8; N] {
+    let label = label.as_bytes();
+    let mut line = [b'\n'; N];
+    let mut i = 0;
+
+    while i < label.len() {
+        line[i] = label[i];
+        i += 1;
+    }
+
+    line
+}
+
+#[macro_export]
+macro_rules! action {
+    ($name:ident) => {
+        unsafe {
+            server_loop_api::paste! {
+                // The linker collects these into the `ACTION_LABEL_SECTION`
+                #[link_section = "action_labels"]
+                #[used]
+                static [<$name:upper _LABEL_LINE>]: [u8; stringify!($name).len() + 1] =
+                    ::server_loop_api::label_section_line(stringify!($name));
+                static [<$name:upper _NAME>]: &'static str = stringify!($name);
+                static mut [<$name:upper>]: Option<Action> = None;
+                if [<$name:upper>].is_none() {
+                    [<$name:upper>] = Some(::server_loop_api::get_action_by_label(
+                        [<$name:upper _NAME>]
+                    ).expect("action not found"))
+                }
+                [<$name:upper>].unwrap()
+            }
+        }
+    };
+}
+
+wrap_func!(apply_effect, ApplyEffectRequest);
+
+// Returns `true` if the actor has had the effect.
+wrap_func!(remove_effect, ActorEffectRequest, bool);
+
+wrap_func!(get_dimension_kind_by_label, &str, Option<DimensionKind>);
+
+// Returns `false` if the dimension kind is not registered.
+// The actor is moved after the script has finished.
+wrap_func!(transfer_actor, TransferActorRequest, bool);
+
+wrap_func!(player_has_permission, PlayerHasPermissionRequest, bool);
+
+// Returns the ticket to remove, `None` if the radius is out of range.
+// Tickets are not kept over the server restarts.
+wrap_func!(add_chunk_ticket, AddChunkTicketRequest, Option<u64>);
+
+// Returns `false` if the ticket does not exist.
+wrap_func!(remove_chunk_ticket, u64, bool);
@@ -10,5 +10,13 @@ pub const BLOCK_TEXTURE_LIST_PATH: &str = "assets/client/textures/blocks.json";
 pub const ACTOR_TEXTURE_PATH_PREFIX: &str = "assets/client/textures/actors";
 pub const BLOCK_TEXTURE_PATH_PREFIX: &str = "assets/client/textures/blocks";
 
+pub const PARTICLE_EMITTERS_PATH: &str = "assets/client/particles.json";
+
 pub const DEFAULT_FONT_PATH: &str = "assets/client/fonts/LanaPixel.ttf";
 pub const SHADERS_PATH: &str = "assets/client/shaders/shaders.wgsl";
+
+pub const CLIENT_LOOP_SCRIPT_LIST_PATH: &str = "assets/client/scripts/client_loop_list.json";
+pub const CLIENT_LOOP_SCRIPT_DIR_PATH: &str = "assets/client/scripts/client_loop";
+
+/// Local copies of the server assets, by the pack label.
+pub const ASSET_CACHE_PATH: &str = "cache/assets";
@@ -0,0 +1,556 @@
+//! Headless clients for load testing the server.
+//!
+//! Every bot logs in as its own player (registering on the first run, with the username as the
+//! password), wanders around in random directions and now and then removes or places a block.
+//! Bots need no window and no assets, they only read the label lists of the asset manifest.
+use crate::{
+    component::actor::{
+        orientation::OrientationActorComponent,
+        position::PositionActorComponent,
+        velocity::VelocityActorComponent,
+    },
+    scene::menu::{
+        self,
+        ActionType,
+    },
+    CONNECTION_TIMEOUT,
+};
+use futures_lite::future::FutureExt;
+use log::{
+    info,
+    warn,
+};
+use serde::Serialize;
+use std::{
+    f32::consts::{
+        FRAC_PI_4,
+        TAU,
+    },
+    net::SocketAddr,
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+use tokio::{
+    task,
+    time::{
+        self,
+        MissedTickBehavior,
+    },
+};
+use voxbrix_common::{
+    assets::{
+        ACTION_LIST_PATH,
+        BLOCK_CLASS_LIST_PATH,
+        STATE_COMPONENTS_PATH,
+    },
+    component::actor::{
+        orientation::Orientation,
+        position::PositionPacked,
+        velocity::Velocity,
+    },
+    entity::{
+        action::Action,
+        actor::Actor,
+        block_class::BlockClass,
+        chunk::Chunk,
+        snapshot::Snapshot,
+        state_component::StateComponent,
+    },
+    messages::{
+        client::{
+            AssetManifest,
+            ClientAccept,
+            InitData,
+        },
+        server::{
+            AssetRequest,
+            ServerAccept,
+        },
+        ActionsPacker,
+        ActorStateUnpack,
+        StatePacker,
+        StateUnpacker,
+    },
+    pack::{
+        self,
+        Packer,
+    },
+    system::position,
+    LabelMap,
+};
+use voxbrix_protocol::{
+    client::{
+        Connection,
+        Error as ClientError,
+        Receiver,
+        UnreliableSender,
+    },
+    KeepaliveParameters,
+};
+
+const USAGE: &str =
+    "usage: voxbrix_client --headless-bot <server address> [count] [username prefix]";
+
+/// Same as the state sending interval of the game scene.
+const SEND_STATE_INTERVAL: Duration = Duration::from_millis(50);
+/// Pause between the bot connections, so the handshakes do not come all at once.
+const SPAWN_INTERVAL: Duration = Duration::from_millis(100);
+/// Blocks per second.
+const BOT_SPEED: f32 = 4.0;
+/// Bots look down a bit to reach the blocks around them.
+const BOT_ACTION_PITCH: f32 = -FRAC_PI_4;
+/// One in this number of ticks a bot turns to a new direction.
+const TURN_CHANCE: u64 = 40;
+/// One in this number of ticks a bot removes or places a block.
+const ACTION_CHANCE: u64 = 20;
+
+pub struct BotParameters {
+    pub server: SocketAddr,
+    pub count: usize,
+    pub username_prefix: String,
+}
+
+impl BotParameters {
+    /// Parses the arguments following `--headless-bot`.
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let server = args
+            .next()
+            .and_then(|server| server.parse().ok())
+            .ok_or_else(|| USAGE.to_owned())?;
+
+        let count = match args.next() {
+            Some(count) => count.parse().map_err(|_| USAGE.to_owned())?,
+            None => 1,
+        };
+
+        let username_prefix = args.next().unwrap_or_else(|| "bot".to_owned());
+
+        Ok(Self {
+            server,
+            count,
+            username_prefix,
+        })
+    }
+}
+
+/// Connects the bots and runs them until all of them are disconnected.
+pub async fn run(parameters: BotParameters) {
+    let BotParameters {
+        server,
+        count,
+        username_prefix,
+    } = parameters;
+
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+
+    let mut bots = Vec::with_capacity(count);
+
+    for index in 0 .. count {
+        let username = format!("{}{}", username_prefix, index);
+        let seed = seed.rotate_left(index as u32) ^ index as u64;
+
+        bots.push(task::spawn_local(async move {
+            match Bot::connect(server, &username, seed).await {
+                Ok((bot, connection)) => {
+                    info!("bot \"{}\" joined", username);
+
+                    if let Err(err) = bot.run(connection).await {
+                        warn!("bot \"{}\" disconnected: {:?}", username, err);
+                    }
+                },
+                Err(err) => warn!("bot \"{}\" unable to join: {}", username, err),
+            }
+        }));
+
+        time::sleep(SPAWN_INTERVAL).await;
+    }
+
+    for bot in bots {
+        let _ = bot.await;
+    }
+}
+
+#[derive(Serialize)]
+struct RemoveBlock {
+    chunk: Chunk,
+    offset: [f32; 3],
+    direction: [f32; 3],
+}
+
+#[derive(Serialize)]
+struct PlaceBlock {
+    chunk: Chunk,
+    offset: [f32; 3],
+    direction: [f32; 3],
+    block_class: BlockClass,
+}
+
+struct Bot {
+    actor: Actor,
+    position_component: StateComponent,
+    place_block_class: Option<BlockClass>,
+    remove_block_action: Action,
+    place_block_action: Action,
+
+    packer: Packer,
+    state_packer: StatePacker,
+    state_unpacker: StateUnpacker,
+    actions_packer: ActionsPacker,
+
+    position_ac: PositionActorComponent,
+    velocity_ac: VelocityActorComponent,
+    orientation_ac: OrientationActorComponent,
+
+    snapshot: Snapshot,
+    last_client_snapshot: Snapshot,
+    last_server_snapshot: Snapshot,
+    last_correction_snapshot: Snapshot,
+    last_tick: Instant,
+
+    // Xorshift state, must never be zero
+    random_state: u64,
+    yaw: f32,
+}
+
+impl Bot {
+    async fn connect(
+        server: SocketAddr,
+        username: &str,
+        seed: u64,
+    ) -> Result<(Self, Connection), &'static str> {
+        let mut connection = menu::connect(server).await?;
+
+        let manifest = match menu::authenticate(
+            &mut connection,
+            ActionType::Login,
+            username,
+            username,
+        )
+        .await
+        {
+            Ok(manifest) => manifest,
+            // The server closes the connection on failure
+            Err(_) => {
+                connection = menu::connect(server).await?;
+
+                menu::authenticate(
+                    &mut connection,
+                    ActionType::Registration,
+                    username,
+                    username,
+                )
+                .await?
+            },
+        };
+
+        let state_components: LabelMap<StateComponent> =
+            label_map(&manifest, STATE_COMPONENTS_PATH)
+                .ok_or("Server sent no state component list")?;
+        let block_classes: Option<LabelMap<BlockClass>> =
+            label_map(&manifest, BLOCK_CLASS_LIST_PATH);
+        let actions: LabelMap<Action> =
+            label_map(&manifest, ACTION_LIST_PATH).ok_or("Server sent no action list")?;
+
+        let state_component = |label| {
+            state_components
+                .get(label)
+                .ok_or("Server has no player state components")
+        };
+
+        let position_component = state_component("actor_position")?;
+        let velocity_component = state_component("actor_velocity")?;
+        let orientation_component = state_component("actor_orientation")?;
+
+        let action = |label| actions.get(label).ok_or("Server has no block actions");
+
+        let remove_block_action = action("remove_block")?;
+        let place_block_action = action("place_block")?;
+
+        let mut packer = Packer::new();
+        let mut tx_buffer = Vec::new();
+
+        // Bots have no use for the asset files
+        packer.pack(&AssetRequest { files: Vec::new() }, &mut tx_buffer);
+
+        let InitData { actor, .. } = menu::send_recv::<InitData>(
+            &tx_buffer,
+            &mut connection.sender,
+            &mut connection.receiver,
+            &mut packer,
+        )
+        .await?;
+
+        let bot = Self {
+            actor,
+            position_component,
+            place_block_class: block_classes.and_then(|map| map.get("grass")),
+            remove_block_action,
+            place_block_action,
+
+            packer,
+            state_packer: StatePacker::new(),
+            state_unpacker: StateUnpacker::new(),
+            actions_packer: ActionsPacker::new(),
+
+            position_ac: PositionActorComponent::new(position_component, actor, true),
+            velocity_ac: VelocityActorComponent::new(velocity_component, actor, true),
+            orientation_ac: OrientationActorComponent::new(orientation_component, actor, true),
+
+            snapshot: Snapshot(1),
+            last_client_snapshot: Snapshot(0),
+            last_server_snapshot: Snapshot(0),
+            last_correction_snapshot: Snapshot(0),
+            last_tick: Instant::now(),
+
+            random_state: seed | 1,
+            yaw: 0.0,
+        };
+
+        Ok((bot, connection))
+    }
+
+    async fn run(mut self, connection: Connection) -> Result<(), ClientError> {
+        let Connection {
+            sender, receiver, ..
+        } = connection;
+
+        let (unreliable, mut reliable) = sender.split();
+
+        let keepalive = async {
+            reliable
+                .keepalive(KeepaliveParameters {
+                    timeout: CONNECTION_TIMEOUT,
+                    ..Default::default()
+                })
+                .await?;
+            unreachable!();
+        };
+
+        self.play(unreliable, receiver).or(keepalive).await
+    }
+
+    async fn play(
+        &mut self,
+        mut unreliable: UnreliableSender,
+        mut receiver: Receiver,
+    ) -> Result<(), ClientError> {
+        let mut send_state_interval = time::interval(SEND_STATE_INTERVAL);
+        send_state_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            let message = async { Some(receiver.recv().await) }
+                .or(async {
+                    send_state_interval.tick().await;
+                    None
+                })
+                .await;
+
+            match message {
+                Some(Ok((_channel, data))) => self.handle_message(data),
+                Some(Err(ClientError::ConnectionMigrated { .. })) => {},
+                Some(Err(err)) => return Err(err),
+                None => {
+                    self.tick();
+
+                    let packed = self.pack_state();
+
+                    unreliable.send_unreliable(0, &packed).await?;
+                },
+            }
+        }
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.random_state ^= self.random_state << 13;
+        self.random_state ^= self.random_state >> 7;
+        self.random_state ^= self.random_state << 17;
+
+        self.random_state
+    }
+
+    /// Only the state of the player actor is of interest,
+    /// everything else the server sends is dropped.
+    fn handle_message(&mut self, data: &[u8]) {
+        let Ok(message) = self.packer.unpack::<ClientAccept>(data) else {
+            return;
+        };
+
+        let ClientAccept::State {
+            snapshot,
+            last_client_snapshot,
+            last_server_snapshot: _,
+            state,
+            actions: _,
+            position_correction,
+            actors_left: _,
+        } = message
+        else {
+            return;
+        };
+
+        // The position of a new player comes with the first full state
+        if self.position_ac.get(&self.actor).is_none() {
+            let spawn_position = self
+                .state_unpacker
+                .unpack_state(state)
+                .ok()
+                .and_then(|state| state.get_component(&self.position_component))
+                .and_then(|buffer| {
+                    pack::decode_from_slice::<ActorStateUnpack<PositionPacked>>(buffer)
+                        .map(|(unpacked, _)| unpacked)
+                })
+                .and_then(|unpacked| {
+                    match unpacked {
+                        ActorStateUnpack::Full(full) => {
+                            full.into_iter()
+                                .find(|(actor, _)| *actor == self.actor)
+                                .and_then(|(_, position)| position.unpack(None))
+                        },
+                        ActorStateUnpack::Change(changes) => {
+                            changes
+                                .into_iter()
+                                .find(|(actor, _)| *actor == self.actor)
+                                .and_then(|(_, position)| position?.unpack(None))
+                        },
+                    }
+                });
+
+            if let Some(position) = spawn_position {
+                self.position_ac.insert(self.actor, position, self.snapshot);
+            }
+        }
+
+        if let Some(correction) = position_correction {
+            // Corrections are repeated until acknowledged,
+            // the movement since then is not replayed
+            if correction.snapshot > self.last_correction_snapshot {
+                self.last_correction_snapshot = correction.snapshot;
+                self.position_ac
+                    .insert(self.actor, correction.position, self.snapshot);
+            }
+        }
+
+        self.actions_packer.confirm_snapshot(last_client_snapshot);
+        self.last_client_snapshot = last_client_snapshot;
+        self.last_server_snapshot = snapshot;
+    }
+
+    /// Moves the bot and adds the block actions.
+    fn tick(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(self.last_tick).as_secs_f32();
+        self.last_tick = now;
+
+        // Not spawned yet
+        let Some(position) = self.position_ac.get(&self.actor).copied() else {
+            return;
+        };
+
+        if self.next_random() % TURN_CHANCE == 0 {
+            self.yaw = (self.next_random() % 360) as f32 / 360.0 * TAU;
+        }
+
+        let direction = Orientation::from_yaw_pitch(self.yaw, 0.0).forward();
+        let velocity = direction * BOT_SPEED;
+
+        let position =
+            position::to_chunk_position(position.chunk, position.offset + velocity * elapsed);
+
+        self.position_ac
+            .get_writable(&self.actor, self.snapshot)
+            .unwrap()
+            .update(position);
+
+        let velocity = Velocity { vector: velocity };
+
+        match self.velocity_ac.get_writable(&self.actor, self.snapshot) {
+            Some(mut writable) => writable.update(velocity),
+            None => {
+                self.velocity_ac.insert(self.actor, velocity, self.snapshot);
+            },
+        }
+
+        let orientation = Orientation::from_yaw_pitch(self.yaw, BOT_ACTION_PITCH);
+
+        match self.orientation_ac.get_writable(&self.actor, self.snapshot) {
+            Some(mut writable) => writable.update(orientation),
+            None => {
+                self.orientation_ac
+                    .insert(self.actor, orientation, self.snapshot);
+            },
+        }
+
+        if self.next_random() % ACTION_CHANCE != 0 {
+            return;
+        }
+
+        let chunk = position.chunk;
+        let offset: [f32; 3] = position.offset.into();
+        let direction: [f32; 3] = orientation.forward().into();
+        let place = self.next_random() % 2 == 0;
+
+        match self.place_block_class {
+            Some(block_class) if place => {
+                self.actions_packer.add_action(
+                    self.place_block_action,
+                    self.snapshot,
+                    PlaceBlock {
+                        chunk,
+                        offset,
+                        direction,
+                        block_class,
+                    },
+                );
+            },
+            _ => {
+                self.actions_packer.add_action(
+                    self.remove_block_action,
+                    self.snapshot,
+                    RemoveBlock {
+                        chunk,
+                        offset,
+                        direction,
+                    },
+                );
+            },
+        }
+    }
+
+    fn pack_state(&mut self) -> Vec<u8> {
+        self.position_ac
+            .pack_player(&mut self.state_packer, self.last_client_snapshot);
+        self.velocity_ac
+            .pack_player(&mut self.state_packer, self.last_client_snapshot);
+        self.orientation_ac
+            .pack_player(&mut self.state_packer, self.last_client_snapshot);
+
+        let packed = self.packer.pack_to_vec(&ServerAccept::State {
+            snapshot: self.snapshot,
+            last_server_snapshot: self.last_server_snapshot,
+            state: self.state_packer.pack_state(),
+            actions: self.actions_packer.pack_actions(),
+        });
+
+        self.snapshot = self.snapshot.next();
+
+        packed
+    }
+}
+
+fn label_map<T>(manifest: &AssetManifest, path: &str) -> Option<LabelMap<T>>
+where
+    T: voxbrix_common::AsFromUsize,
+{
+    manifest
+        .lists
+        .iter()
+        .find(|list| list.path == path)
+        .map(|list| LabelMap::from_list(&list.labels))
+}
@@ -1,4 +1,7 @@
-use crate::system::movement_interpolation::TARGET_QUEUE_LENGTH;
+use crate::system::movement_interpolation::{
+    SERVER_TICK_INTERVAL,
+    TARGET_QUEUE_LENGTH,
+};
 use arrayvec::ArrayVec;
 use nohash_hasher::IntMap;
 use serde::{
@@ -27,8 +30,12 @@ use voxbrix_common::{
 
 pub mod animation_state;
 pub mod class;
+pub mod effect;
+pub mod health;
+pub mod inventory;
 pub mod orientation;
 pub mod position;
+pub mod position_baseline;
 pub mod target_orientation;
 pub mod target_position;
 pub mod velocity;
@@ -121,6 +128,10 @@ where
     pub fn iter(&self) -> impl Iterator<Item = (Actor, &T)> {
         self.storage.iter().map(|(k, v)| (*k, v))
     }
+
+    pub fn remove(&mut self, i: &Actor) -> Option<T> {
+        self.storage.remove(i)
+    }
 }
 
 impl<T> ActorComponentPackable<T>
@@ -191,13 +202,18 @@ impl<T> ActorComponentUnpackable<T> {
     pub fn iter_mut(&mut self) -> impl Iterator<Item = (Actor, &mut T)> {
         self.storage.iter_mut().map(|(&a, t)| (a, t))
     }
+
+    pub fn remove(&mut self, i: &Actor) -> Option<T> {
+        self.storage.remove(i)
+    }
 }
 
 impl<T> ActorComponentUnpackable<T> {
+    /// The actor is left without the component if `convert` returns `None`.
     pub fn unpack_state_convert<'a, U>(
         &mut self,
         state: &StateUnpacked<'a>,
-        mut convert: impl FnMut(Actor, Option<T>, U) -> T,
+        mut convert: impl FnMut(Actor, Option<T>, U) -> Option<T>,
     ) where
         U: Deserialize<'a>,
     {
@@ -210,8 +226,9 @@ impl<T> ActorComponentUnpackable<T> {
                     for (actor, change) in changes {
                         if let Some(component) = change {
                             let previous = self.storage.remove(&actor);
-                            self.storage
-                                .insert(actor, convert(actor, previous, component));
+                            if let Some(component) = convert(actor, previous, component) {
+                                self.storage.insert(actor, component);
+                            }
                         } else {
                             self.storage.remove(&actor);
                         }
@@ -220,9 +237,9 @@ impl<T> ActorComponentUnpackable<T> {
                 ActorStateUnpack::Full(full) => {
                     let full = full
                         .into_iter()
-                        .map(|(actor, component)| {
+                        .filter_map(|(actor, component)| {
                             let previous = self.storage.remove(&actor);
-                            (actor, convert(actor, previous, component))
+                            Some((actor, convert(actor, previous, component)?))
                         })
                         .collect::<Vec<_>>();
 
@@ -266,35 +283,29 @@ where
         self.data.remove(&(*actor, *key))
     }
 
-    // pub fn remove_actor(&mut self, actor: &Actor) -> BTreeMap<(Actor, K), T> {
-    // let mut removed = self.data.split_off(&(*actor, K::MIN));
-    //
-    // let mut right_part = removed.split_off(&(*actor, K::MAX));
-    //
-    // self.data.append(&mut right_part);
-    //
-    // removed
-    // }
+    pub fn remove_actor(&mut self, actor: &Actor) {
+        self.data
+            .retain(|(subcomponent_actor, _), _| subcomponent_actor != actor);
+    }
 }
 
-const TARGET_QUEUE_LENGTH_EXTRA: usize = TARGET_QUEUE_LENGTH + 1;
-
 #[derive(Clone, Copy)]
 pub struct Target<T> {
     pub server_snapshot: Snapshot,
     pub value: T,
-    pub reach_time: Instant,
+    /// Time on the client timeline the value corresponds to.
+    pub time: Instant,
 }
 
+/// Last received server values of the actor component,
+/// ordered by the server snapshot.
 pub struct TargetQueue<T> {
-    pub starting: T,
-    pub target_queue: ArrayVec<Target<T>, TARGET_QUEUE_LENGTH_EXTRA>,
+    pub target_queue: ArrayVec<Target<T>, TARGET_QUEUE_LENGTH>,
 }
 
 impl<T> TargetQueue<T> {
     pub fn from_previous(
         previous: Option<TargetQueue<T>>,
-        current_value: T,
         new_target: T,
         current_time: Instant,
         server_snapshot: Snapshot,
@@ -303,26 +314,38 @@ impl<T> TargetQueue<T> {
         T: Copy,
     {
         let mut target_queue = previous.unwrap_or(TargetQueue {
-            starting: current_value,
             target_queue: ArrayVec::new(),
         });
 
-        let last = target_queue.target_queue.last().copied();
-
-        if last.is_none() || last.is_some() && last.unwrap().server_snapshot < server_snapshot {
-            let new = Target {
-                server_snapshot,
-                value: new_target,
-                reach_time: current_time,
-            };
-
-            if target_queue.target_queue.is_full() {
-                *target_queue.target_queue.last_mut().unwrap() = new;
-            } else {
-                target_queue.target_queue.push(new);
-            }
+        let time = match target_queue.target_queue.last() {
+            Some(last) if last.server_snapshot >= server_snapshot => return target_queue,
+            Some(last) => {
+                // Keep the server tick spacing between the targets,
+                // unless the arrival time drifts too far from it
+                let snapshots = (server_snapshot.0 - last.server_snapshot.0)
+                    .try_into()
+                    .unwrap_or(u32::MAX);
+                let time = last.time + SERVER_TICK_INTERVAL.saturating_mul(snapshots);
+
+                time.min(current_time).max(
+                    current_time
+                        .checked_sub(SERVER_TICK_INTERVAL)
+                        .unwrap_or(current_time),
+                )
+            },
+            None => current_time,
+        };
+
+        if target_queue.target_queue.is_full() {
+            target_queue.target_queue.remove(0);
         }
 
+        target_queue.target_queue.push(Target {
+            server_snapshot,
+            value: new_target,
+            time,
+        });
+
         target_queue
     }
 }
@@ -0,0 +1,68 @@
+use nohash_hasher::IntMap;
+use std::collections::VecDeque;
+use voxbrix_common::{
+    component::actor::position::{
+        Position,
+        POSITION_BASELINE_SNAPSHOTS,
+    },
+    entity::{
+        actor::Actor,
+        snapshot::Snapshot,
+    },
+};
+
+/// Positions of the actors received with the recent server snapshots,
+/// the server sends the positions as the deltas against them.
+pub struct PositionBaselineActorComponent {
+    storage: IntMap<Actor, VecDeque<(Snapshot, Position)>>,
+}
+
+impl PositionBaselineActorComponent {
+    pub fn new() -> Self {
+        Self {
+            storage: IntMap::default(),
+        }
+    }
+
+    pub fn insert(&mut self, actor: Actor, snapshot: Snapshot, position: Position) {
+        let history = self.storage.entry(actor).or_default();
+
+        // Late packets of the older snapshots are not baselines anymore
+        if history
+            .back()
+            .is_some_and(|(last_snapshot, _)| *last_snapshot > snapshot)
+        {
+            return;
+        }
+
+        if history
+            .back()
+            .is_some_and(|(last_snapshot, _)| *last_snapshot == snapshot)
+        {
+            history.pop_back();
+        }
+
+        history.push_back((snapshot, position));
+
+        // The latest position before the oldest allowed baseline is still needed
+        while history.get(1).is_some_and(|(past_snapshot, _)| {
+            snapshot.0 - past_snapshot.0 >= POSITION_BASELINE_SNAPSHOTS
+        }) {
+            history.pop_front();
+        }
+    }
+
+    /// Position of the actor as of the snapshot.
+    pub fn get(&self, actor: &Actor, snapshot: Snapshot) -> Option<&Position> {
+        self.storage
+            .get(actor)?
+            .iter()
+            .rev()
+            .find(|(past_snapshot, _)| *past_snapshot <= snapshot)
+            .map(|(_, position)| position)
+    }
+
+    pub fn remove(&mut self, actor: &Actor) {
+        self.storage.remove(actor);
+    }
+}
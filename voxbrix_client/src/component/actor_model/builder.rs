@@ -74,6 +74,7 @@ impl ActorModelBuilder {
             Quad {
                 chunk: position.chunk.position.into(),
                 texture_index: self.texture,
+                texture_bounds: [0.0; 4],
                 vertices: vertices
                     .iter()
                     .map(|vertex| {
@@ -1 +1,2 @@
+pub mod block_entity;
 pub mod class;
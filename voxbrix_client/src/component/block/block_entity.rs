@@ -0,0 +1,53 @@
+use ahash::AHashMap;
+use voxbrix_common::{
+    component::block::block_entity::{
+        BlockEntity,
+        ChunkBlockEntities,
+    },
+    entity::{
+        block::Block,
+        chunk::Chunk,
+    },
+};
+
+/// Block entities of the loaded chunks, as synchronized by the server.
+pub struct BlockEntityBlockComponent {
+    data: AHashMap<Chunk, ChunkBlockEntities>,
+}
+
+impl BlockEntityBlockComponent {
+    pub fn new() -> Self {
+        Self {
+            data: AHashMap::new(),
+        }
+    }
+
+    pub fn insert_chunk(&mut self, chunk: Chunk, block_entities: ChunkBlockEntities) {
+        self.data.insert(chunk, block_entities);
+    }
+
+    pub fn remove_chunk(&mut self, chunk: &Chunk) {
+        self.data.remove(chunk);
+    }
+
+    pub fn get(&self, chunk: &Chunk, block: Block) -> Option<&BlockEntity> {
+        self.data.get(chunk)?.get(block)
+    }
+
+    /// Sets the block entity of the block, `None` removes it.
+    /// Changes of the chunks that are not loaded are ignored.
+    pub fn set(&mut self, chunk: &Chunk, block: Block, block_entity: Option<BlockEntity>) {
+        let Some(chunk_block_entities) = self.data.get_mut(chunk) else {
+            return;
+        };
+
+        match block_entity {
+            Some(block_entity) => {
+                chunk_block_entities.0.insert(block, block_entity);
+            },
+            None => {
+                chunk_block_entities.0.remove(&block);
+            },
+        }
+    }
+}
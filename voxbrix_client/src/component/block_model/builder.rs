@@ -10,14 +10,20 @@ use crate::{
     },
 };
 use anyhow::Error;
-use bitflags::bitflags;
 use serde::Deserialize;
 use voxbrix_common::{
-    component::block::sky_light::SkyLight,
+    component::block::{
+        block_light::BlockLight,
+        sky_light::SkyLight,
+    },
     entity::{
         block::Block,
         chunk::Chunk,
     },
+    system::block_surface::{
+        CullFlags,
+        Occluders,
+    },
     ArrayExt,
     LabelMap,
 };
@@ -141,32 +147,6 @@ impl BlockModelBuilderDescriptor {
     }
 }
 
-bitflags! {
-    #[derive(Clone, Copy)]
-    pub struct CullFlags: u8 {
-        const X0 = 0b00000001;
-        const X1 = 0b00000010;
-        const Y0 = 0b00000100;
-        const Y1 = 0b00001000;
-        const Z0 = 0b00010000;
-        const Z1 = 0b00100000;
-    }
-}
-
-impl CullFlags {
-    pub fn from_index(i: usize) -> Self {
-        match i {
-            0 => Self::X0,
-            1 => Self::X1,
-            2 => Self::Y0,
-            3 => Self::Y1,
-            4 => Self::Z0,
-            5 => Self::Z1,
-            _ => panic!("incorrect side index"),
-        }
-    }
-}
-
 struct VertexBuilder {
     position: [f32; 3],
     texture_position: [f32; 2],
@@ -183,12 +163,25 @@ pub struct BlockModelBuilder {
 }
 
 impl BlockModelBuilder {
+    /// Texture index and the texture positions of the vertices of the first side,
+    /// used for the particles of the block.
+    pub fn particle_texture(&self) -> Option<(u32, [[f32; 2]; 4])> {
+        let quad = self.quads.first()?;
+
+        Some((
+            quad.texture_index,
+            quad.vertices.map_ref(|vertex| vertex.texture_position),
+        ))
+    }
+
     pub fn build<'a>(
         &'a self,
         chunk: &'a Chunk,
         block: Block,
         cull_mask: CullFlags,
         sky_light_level: [SkyLight; 6],
+        block_light_level: [BlockLight; 6],
+        occluders: Occluders,
     ) -> impl Iterator<Item = Quad> + 'a {
         let block = block.into_coords();
 
@@ -209,6 +202,7 @@ impl BlockModelBuilder {
                 Quad {
                     chunk: chunk.position,
                     texture_index: pb.texture_index,
+                    texture_bounds: [0.0; 4],
                     vertices: pb.vertices.map_ref(|vxb| {
                         let mut position = vxb.position;
 
@@ -216,25 +210,35 @@ impl BlockModelBuilder {
                         position[1] += block[1] as f32;
                         position[2] += block[2] as f32;
 
-                        let sky_light_level = match pb.culling_neighbor {
-                            // TODO better lighting for non-cullable quads
-                            CullingNeighbor::None => {
-                                let light_float = sky_light_level
-                                    .iter()
-                                    .map(|side_light| side_light.value() as f32)
-                                    .sum::<f32>()
-                                    / 6.0;
+                        // TODO better lighting for non-cullable quads
+                        let side_index = match pb.culling_neighbor {
+                            CullingNeighbor::None => None,
+                            CullingNeighbor::NegativeX => Some(0),
+                            CullingNeighbor::PositiveX => Some(1),
+                            CullingNeighbor::NegativeY => Some(2),
+                            CullingNeighbor::PositiveY => Some(3),
+                            CullingNeighbor::NegativeZ => Some(4),
+                            CullingNeighbor::PositiveZ => Some(5),
+                        };
 
-                                SkyLight::from_value(light_float as u8)
-                            },
-                            CullingNeighbor::NegativeX => sky_light_level[0],
-                            CullingNeighbor::PositiveX => sky_light_level[1],
-                            CullingNeighbor::NegativeY => sky_light_level[2],
-                            CullingNeighbor::PositiveY => sky_light_level[3],
-                            CullingNeighbor::NegativeZ => sky_light_level[4],
-                            CullingNeighbor::PositiveZ => sky_light_level[5],
+                        let side_light = |levels: [u8; 6]| {
+                            match side_index {
+                                Some(side) => levels[side],
+                                None => {
+                                    (levels.iter().map(|level| *level as f32).sum::<f32>() / 6.0)
+                                        as u8
+                                },
+                            }
                         };
 
+                        let sky_light_level = SkyLight::from_value(side_light(
+                            sky_light_level.map(|light| light.value()),
+                        ));
+
+                        let block_light_level = BlockLight::from_value(side_light(
+                            block_light_level.map(|light| light.value()),
+                        ));
+
                         let mut vertex = Vertex {
                             position,
                             texture_position: vxb.texture_position,
@@ -242,6 +246,13 @@ impl BlockModelBuilder {
                         };
 
                         vertex.set_sky_light(sky_light_level);
+                        vertex.set_block_light(block_light_level);
+
+                        if let Some(side) = side_index {
+                            vertex.set_ambient_occlusion(
+                                occluders.vertex_occlusion(side, vxb.position),
+                            );
+                        }
 
                         vertex
                     }),
@@ -1,12 +1,18 @@
 use crate::entity::texture::Texture;
 use voxbrix_common::AsFromUsize;
 
+/// Texture indices keep the animation slot in the bits starting from this one.
+pub const ANIMATION_INDEX_SHIFT: u32 = 16;
+
 #[derive(Clone)]
 pub struct Location {
     pub data_index: u32,
     pub position: [u32; 2],
+    /// Size of a single frame for the animated textures.
     pub size: [u32; 2],
     pub edge_correction: [f32; 2],
+    /// Animation slot increased by one, `0` for the static textures.
+    pub animation: u32,
 }
 
 pub struct LocationTextureComponent {
@@ -52,11 +58,14 @@ impl LocationTextureComponent {
         })
     }
 
+    /// Index of the atlas layer with the animation slot in the upper bits.
     pub fn get_index(&self, texture: Texture) -> u32 {
-        self.locations
+        let location = self
+            .locations
             .get(texture.as_usize())
-            .expect("texture not found")
-            .data_index
+            .expect("texture not found");
+
+        location.data_index | (location.animation << ANIMATION_INDEX_SHIFT)
     }
 
     pub fn get_edge_correction(&self, texture: Texture) -> [f32; 2] {
@@ -1,5 +1,6 @@
 use crate::assets::DEFAULT_FONT_PATH;
 use backtrace::Backtrace;
+use bot::BotParameters;
 use egui::{
     FontData,
     FontDefinitions,
@@ -9,12 +10,18 @@ use egui::{
 };
 use log::error;
 use scene::SceneManager;
+use settings::{
+    Settings,
+    SETTINGS_PATH,
+};
 use std::{
+    env,
     fmt,
     panic::{
         self,
         PanicHookInfo,
     },
+    path::Path,
     process,
     sync::Arc,
     thread::{
@@ -33,9 +40,12 @@ use tokio::{
 use window::Window;
 
 mod assets;
+mod bot;
 mod component;
 mod entity;
 mod scene;
+mod server_list;
+mod settings;
 mod system;
 mod window;
 
@@ -81,6 +91,34 @@ impl<'a> fmt::Debug for PanicLogEntry<'a> {
 fn main() {
     env_logger::init();
 
+    // `--headless-bot <server address> [count] [username prefix]` runs the load testing bots
+    // instead of the game
+    let mut args = env::args().skip(1);
+    if args.next().as_deref() == Some("--headless-bot") {
+        let parameters = match BotParameters::from_args(args) {
+            Ok(parameters) => parameters,
+            Err(usage) => {
+                eprintln!("{}", usage);
+                process::exit(1);
+            },
+        };
+
+        let rt = RuntimeBuilder::new_current_thread()
+            .enable_io()
+            .enable_time()
+            .build()
+            .expect("unable to build runtime");
+
+        rt.block_on(LocalSet::new().run_until(bot::run(parameters)));
+
+        return;
+    }
+
+    let settings = Settings::load(Path::new(SETTINGS_PATH)).unwrap_or_else(|err| {
+        error!("{:#}, using defaults", err);
+        Settings::default()
+    });
+
     let (window_tx, window_rx) = flume::bounded::<Window>(1);
     let (panic_tx, panic_rx) = flume::bounded(1);
     let main_thread = thread::current().id();
@@ -138,8 +176,7 @@ fn main() {
                         Ok(window) => {
                             let context = window.ui_context();
 
-                            // TODO defined dynamically in settings
-                            context.set_pixels_per_point(1.5);
+                            context.set_pixels_per_point(settings.pixels_per_point);
 
                             let font = voxbrix_common::read_file_async(DEFAULT_FONT_PATH)
                                 .await
@@ -186,7 +223,7 @@ fn main() {
 
                             context.set_style(style);
 
-                            let scene_manager = SceneManager { window };
+                            let scene_manager = SceneManager { window, settings };
 
                             if let Err(err) = scene_manager.run().await {
                                 error!("main_loop ended with error: {:#}", err);
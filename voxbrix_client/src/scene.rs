@@ -1,4 +1,7 @@
-use crate::window::Window;
+use crate::{
+    settings::Settings,
+    window::Window,
+};
 use anyhow::Result;
 use game::{
     GameScene,
@@ -20,14 +23,15 @@ pub enum SceneSwitch {
 
 pub struct SceneManager {
     pub window: Window,
+    pub settings: Settings,
 }
 
 impl SceneManager {
     pub async fn run(self) -> Result<()> {
-        let Self { window } = self;
+        let Self { window, settings } = self;
 
         let mut next_loop = Some(SceneSwitch::Menu {
-            parameters: MenuSceneParameters { window },
+            parameters: MenuSceneParameters { window, settings },
         });
 
         loop {
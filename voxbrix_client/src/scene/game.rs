@@ -9,13 +9,19 @@ use crate::{
         BLOCK_MODEL_PATH_PREFIX,
         BLOCK_TEXTURE_LIST_PATH,
         BLOCK_TEXTURE_PATH_PREFIX,
+        CLIENT_LOOP_SCRIPT_DIR_PATH,
+        CLIENT_LOOP_SCRIPT_LIST_PATH,
     },
     component::{
         actor::{
             animation_state::AnimationStateActorComponent,
             class::ClassActorComponent,
+            effect::EffectActorComponent,
+            health::HealthActorComponent,
+            inventory::InventoryActorComponent,
             orientation::OrientationActorComponent,
             position::PositionActorComponent,
+            position_baseline::PositionBaselineActorComponent,
             target_orientation::TargetOrientationActorComponent,
             target_position::TargetPositionActorComponent,
             velocity::VelocityActorComponent,
@@ -26,7 +32,10 @@ use crate::{
             ActorModelBuilderDescriptor,
             BuilderActorModelComponent,
         },
-        block::class::ClassBlockComponent,
+        block::{
+            block_entity::BlockEntityBlockComponent,
+            class::ClassBlockComponent,
+        },
         block_class::model::ModelBlockClassComponent,
         block_model::{
             builder::{
@@ -45,20 +54,41 @@ use crate::{
         menu::MenuSceneParameters,
         SceneSwitch,
     },
+    settings::Settings,
     system::{
         actor_render::ActorRenderSystemDescriptor,
+        asset_sync::ServerAssets,
+        block_prediction::BlockPredictionSystem,
         block_render::BlockRenderSystemDescriptor,
+        chat::ChatSystem,
         chunk_presence::ChunkPresenceSystem,
         controller::DirectControl,
+        debug_overlay::DebugOverlaySystem,
+        effect_hud::EffectHudSystem,
+        health_bar::HealthBarSystem,
         interface::InterfaceSystem,
+        inventory::InventorySystem,
         model_loading::ModelLoadingSystem,
-        movement_interpolation::MovementInterpolationSystem,
+        movement_interpolation::{
+            MovementInterpolationSystem,
+            DEFAULT_INTERPOLATION_DELAY,
+        },
+        particle::ParticleSystem,
         player_position::PlayerPositionSystem,
+        profiler::{
+            Profiler,
+            Section,
+        },
         render::{
             camera::CameraParameters,
             RenderSystemDescriptor,
         },
+        screenshot::ScreenshotSystem,
+        script_hud::ScriptHudSystem,
+        sky::SkySystem,
+        sky_render::SkyRenderSystemDescriptor,
         texture_loading::TextureLoadingSystem,
+        view_model::ViewModelSystem,
     },
     window::{
         Frame,
@@ -82,12 +112,22 @@ use futures_lite::{
         StreamExt,
     },
 };
+use local_channel::priority::Sender as PrioritySender;
 use local_input::LocalInput;
+use log::{
+    error,
+    info,
+};
 use network_input::NetworkInput;
 use process::Process;
+use reconnect::ResumedSession;
 use send_state::SendState;
 use std::{
+    cell::Cell,
     io::ErrorKind as StdIoErrorKind,
+    net::SocketAddr,
+    rc::Rc,
+    sync::Arc,
     task::Poll,
     time::{
         Duration,
@@ -100,11 +140,18 @@ use tokio::time::{
 };
 use voxbrix_common::{
     assets::{
+        ACTION_LIST_PATH,
+        ACTOR_CLASS_LIST_PATH,
         ACTOR_MODEL_LIST_PATH,
+        BLOCK_CLASS_LIST_PATH,
+        DIMENSION_KIND_LIST_PATH,
+        EFFECT_LIST_PATH,
+        ITEM_CLASS_LIST_PATH,
         STATE_COMPONENTS_PATH,
     },
     async_ext::{
         self,
+        ScopeTask,
         StreamExt as _,
     },
     component::{
@@ -113,12 +160,27 @@ use voxbrix_common::{
             position::Position,
             velocity::Velocity,
         },
-        block::sky_light::SkyLightBlockComponent,
+        actor_class::collider::{
+            Collider,
+            ColliderActorClassComponent,
+        },
+        block::{
+            block_light::BlockLightBlockComponent,
+            sky_light::SkyLightBlockComponent,
+        },
         block_class::{
             collision::{
                 Collision,
                 CollisionBlockClassComponent,
             },
+            fluid::{
+                Fluid,
+                FluidBlockClassComponent,
+            },
+            light::{
+                Light,
+                LightBlockClassComponent,
+            },
             opacity::{
                 Opacity,
                 OpacityBlockClassComponent,
@@ -129,6 +191,7 @@ use voxbrix_common::{
     compute,
     entity::{
         actor::Actor,
+        block::BLOCKS_IN_CHUNK_EDGE_F32,
         chunk::{
             Chunk,
             Dimension,
@@ -137,29 +200,41 @@ use voxbrix_common::{
     },
     math::Vec3F32,
     messages::{
+        client::AdminResponse,
         ActionsPacker,
         ActionsUnpacker,
         StatePacker,
         StateUnpacker,
+        ADMIN_CHANNEL,
     },
     pack::Packer,
+    script_registry::ScriptRegistryBuilder,
     system::{
         actor_class_loading::ActorClassLoadingSystem,
         block_class_loading::BlockClassLoadingSystem,
+        block_light::BlockLightSystem,
         list_loading::List,
+        recipe_loading::RecipeRegistry,
         sky_light::SkyLightSystem,
     },
 };
-use voxbrix_protocol::client::{
-    Error as ClientError,
-    Receiver,
-    Sender,
+use voxbrix_protocol::{
+    client::{
+        Connection,
+        Error as ClientError,
+        Receiver,
+        Resumption,
+        Sender,
+    },
+    ConnectionStats,
+    KeepaliveParameters,
 };
 
 mod data;
 mod local_input;
 mod network_input;
 mod process;
+mod reconnect;
 mod send_state;
 
 enum Event {
@@ -167,7 +242,11 @@ enum Event {
     SendState,
     LocalInput(InputEvent),
     NetworkInput(Result<Vec<u8>, ClientError>),
+    AdminResponse(Vec<u8>),
     ChunkCalculation,
+    /// Attempt of restoring the lost connection, counted from 1.
+    Reconnecting(u32),
+    Reconnected(Result<ResumedSession, &'static str>),
 }
 
 #[must_use = "must be handled"]
@@ -175,13 +254,26 @@ enum Transition {
     None,
     Exit,
     Menu,
+    /// Connection is lost, but the session may be resumed.
+    Reconnect,
+}
+
+/// Server the game is connected to, with the means to resume the connection if it is lost.
+#[derive(Clone)]
+pub struct Session {
+    pub server: SocketAddr,
+    pub resumption: Resumption,
 }
 
 pub struct GameSceneParameters {
     pub window: Window,
     pub connection: (Sender, Receiver),
+    pub session: Session,
     pub player_actor: Actor,
     pub player_chunk_view_radius: i32,
+    pub server_process_interval: Duration,
+    pub assets: ServerAssets,
+    pub settings: Settings,
 }
 
 pub struct GameScene {
@@ -195,14 +287,27 @@ impl GameScene {
                 GameSceneParameters {
                     mut window,
                     connection,
+                    session,
                     player_actor,
                     player_chunk_view_radius,
+                    server_process_interval,
+                    assets,
+                    settings,
                 },
         } = self;
 
-        let (reliable_tx, reliable_rx) = flume::unbounded::<Vec<u8>>();
-        let (unreliable_tx, unreliable_rx) = flume::unbounded::<Vec<u8>>();
+        // Chunks beyond the server radius are not sent anyway
+        let player_chunk_view_radius = player_chunk_view_radius.min(settings.view_radius);
+
         let (event_tx, event_rx) = flume::unbounded::<Event>();
+        let connection_stats = Rc::new(Cell::new(ConnectionStats::default()));
+
+        // Must be dropped when the loop ends
+        let (connection_tasks, unreliable_tx, mut reliable_tx) =
+            ConnectionTasks::spawn(connection, &event_tx, &connection_stats);
+        let mut connection_tasks = Some(connection_tasks);
+        // Restores the lost connection, set while the game is frozen
+        let mut reconnect_task = None;
 
         let state_packer = StatePacker::new();
 
@@ -213,73 +318,15 @@ impl GameScene {
 
         let packer = Packer::new();
 
-        let (tx, mut rx) = connection;
-
-        let (mut unreliable, mut reliable) = tx.split();
-
-        let _send_unrel_task = async_ext::spawn_scoped(async move {
-            while let Ok(msg) = unreliable_rx.recv_async().await {
-                unreliable
-                    .send_unreliable(0, &msg)
-                    .await
-                    .expect("send_unreliable should not fail");
-            }
-        });
-
-        let event_tx_network = event_tx.clone();
-
-        let _send_rel_task = async_ext::spawn_scoped(async move {
-            while let Ok(msg) = reliable_rx
-                .recv_async()
-                .or(async {
-                    let _ = future::zip(reliable.wait_complete(), future::pending::<()>()).await;
-                    unreachable!();
-                })
-                .await
-            {
-                // https://github.com/rust-lang/rust/issues/70142
-                let result =
-                    match time::timeout(CONNECTION_TIMEOUT, reliable.send_reliable(0, &msg))
-                        .await
-                        .map_err(|_| ClientError::Io(StdIoErrorKind::TimedOut.into()))
-                    {
-                        Ok(Ok(ok)) => Ok(ok),
-                        Ok(Err(err)) => Err(err),
-                        Err(err) => Err(err),
-                    };
-
-                if let Err(err) = result {
-                    let _ = event_tx_network.send(Event::NetworkInput(Err(err)));
-                    break;
-                }
-            }
-        });
-
-        let event_tx_network = event_tx.clone();
-
-        // Must be dropped when the loop ends
-        let _recv_task = async_ext::spawn_scoped(async move {
-            loop {
-                let data = match rx.recv().await {
-                    Ok((_channel, data)) => data,
-                    Err(err) => {
-                        let _ = event_tx_network.send(Event::NetworkInput(Err(err)));
-                        break;
-                    },
-                };
-
-                if event_tx_network
-                    .send(Event::NetworkInput(Ok(data.to_vec())))
-                    .is_err()
-                {
-                    break;
-                };
-            }
-        });
-
         let mut block_location_tc = LocationTextureComponent::new();
 
-        let block_class_loading_system = BlockClassLoadingSystem::load_data().await?;
+        let packs = assets.packs.clone();
+
+        let block_class_loading_system = BlockClassLoadingSystem::load_data(
+            packs.clone(),
+            assets.load_list(BLOCK_CLASS_LIST_PATH).await?,
+        )
+        .await?;
         let block_texture_loading_system = TextureLoadingSystem::load_data(
             window.device(),
             BLOCK_TEXTURE_LIST_PATH,
@@ -291,8 +338,11 @@ impl GameScene {
         let mut builder_bmc = BuilderBlockModelComponent::new();
         let mut culling_bmc = CullingBlockModelComponent::new();
 
-        let block_model_loading_system =
-            ModelLoadingSystem::load_data(BLOCK_MODEL_LIST_PATH, BLOCK_MODEL_PATH_PREFIX).await?;
+        let block_model_loading_system = ModelLoadingSystem::load_data(
+            List::load(BLOCK_MODEL_LIST_PATH).await?,
+            BLOCK_MODEL_PATH_PREFIX,
+        )
+        .await?;
 
         let block_model_context = BlockModelContext {
             texture_label_map: block_texture_loading_system.label_map(),
@@ -315,10 +365,13 @@ impl GameScene {
 
         let class_bc = ClassBlockComponent::new();
         let sky_light_bc = SkyLightBlockComponent::new();
+        let block_light_bc = BlockLightBlockComponent::new();
 
         let mut model_bcc = ModelBlockClassComponent::new();
         let mut collision_bcc = CollisionBlockClassComponent::new();
+        let mut fluid_bcc = FluidBlockClassComponent::new();
         let mut opacity_bcc = OpacityBlockClassComponent::new();
+        let mut light_bcc = LightBlockClassComponent::new();
 
         let block_model_label_map = block_model_loading_system.into_label_map();
 
@@ -341,21 +394,74 @@ impl GameScene {
             |desc: Collision| Ok(desc),
         )?;
 
+        block_class_loading_system
+            .load_component("fluid", &mut fluid_bcc, |desc: Fluid| Ok(desc))?;
+
         block_class_loading_system.load_component(
             "opacity",
             &mut opacity_bcc,
             |desc: Opacity| Ok(desc),
         )?;
 
+        block_class_loading_system
+            .load_component("light", &mut light_bcc, |desc: Light| Ok(desc))?;
+
         let block_class_label_map = block_class_loading_system.into_label_map();
 
+        let item_class_label_map = assets
+            .load_list(ITEM_CLASS_LIST_PATH)
+            .await?
+            .into_label_map();
+
+        let recipe_registry =
+            RecipeRegistry::load(packs.clone(), item_class_label_map.clone()).await?;
+
+        let effect_label_map = assets.load_list(EFFECT_LIST_PATH).await?.into_label_map();
+
+        let dimension_kind_label_map = assets
+            .load_list(DIMENSION_KIND_LIST_PATH)
+            .await?
+            .into_label_map();
+
+        let action_label_map = assets.load_list(ACTION_LIST_PATH).await?.into_label_map();
+
+        // The actions the client sends by itself
+        for label in ["remove_block", "place_block"] {
+            if action_label_map.get(label).is_none() {
+                return Err(anyhow::Error::msg(format!(
+                    "action \"{}\" is undefined",
+                    label
+                )));
+            }
+        }
+
+        let mut engine_config = wasmtime::Config::new();
+
+        engine_config
+            .wasm_multi_value(false)
+            .wasm_multi_memory(false);
+
+        let engine =
+            wasmtime::Engine::new(&engine_config).context("wasm engine failed to start")?;
+
+        let script_registry = data::setup_script_registry(
+            ScriptRegistryBuilder::load(
+                engine,
+                CLIENT_LOOP_SCRIPT_LIST_PATH,
+                CLIENT_LOOP_SCRIPT_DIR_PATH,
+            )
+            .await?,
+        );
+
         let last_process_time = Instant::now();
 
         let player_position_system = PlayerPositionSystem::new(player_actor);
-        let movement_interpolation_system = MovementInterpolationSystem::new();
-        let direct_control_system = DirectControl::new(player_actor, 10.0, 0.4);
+        let movement_interpolation_system =
+            MovementInterpolationSystem::new(DEFAULT_INTERPOLATION_DELAY);
+        let direct_control_system = DirectControl::new(player_actor, 10.0);
         let chunk_presence_system = ChunkPresenceSystem::new();
         let sky_light_system = SkyLightSystem::new();
+        let block_light_system = BlockLightSystem::new();
 
         let mut actor_location_tc = LocationTextureComponent::new();
         let actor_texture_loading_system = TextureLoadingSystem::load_data(
@@ -366,7 +472,10 @@ impl GameScene {
         )
         .await?;
 
-        let state_components_label_map = List::load(STATE_COMPONENTS_PATH).await?.into_label_map();
+        let state_components_label_map = assets
+            .load_list(STATE_COMPONENTS_PATH)
+            .await?
+            .into_label_map();
 
         let class_ac = ClassActorComponent::new(
             state_components_label_map.get("actor_class").unwrap(),
@@ -388,6 +497,21 @@ impl GameScene {
             player_actor,
             true,
         );
+        let inventory_ac = InventoryActorComponent::new(
+            state_components_label_map.get("actor_inventory").unwrap(),
+            player_actor,
+            false,
+        );
+        let health_ac = HealthActorComponent::new(
+            state_components_label_map.get("actor_health").unwrap(),
+            player_actor,
+            false,
+        );
+        let effect_ac = EffectActorComponent::new(
+            state_components_label_map.get("actor_effects").unwrap(),
+            player_actor,
+            false,
+        );
         let animation_state_ac = AnimationStateActorComponent::new();
         let target_orientation_ac = TargetOrientationActorComponent::new(
             state_components_label_map.get("actor_orientation").unwrap(),
@@ -402,9 +526,16 @@ impl GameScene {
             false,
         );
 
-        let actor_class_loading_system = ActorClassLoadingSystem::load_data().await?;
-        let actor_model_loading_system =
-            ModelLoadingSystem::load_data(ACTOR_MODEL_LIST_PATH, ACTOR_MODEL_PATH_PREFIX).await?;
+        let actor_class_loading_system = ActorClassLoadingSystem::load_data(
+            packs.clone(),
+            assets.load_list(ACTOR_CLASS_LIST_PATH).await?,
+        )
+        .await?;
+        let actor_model_loading_system = ModelLoadingSystem::load_data(
+            assets.load_list(ACTOR_MODEL_LIST_PATH).await?,
+            ACTOR_MODEL_PATH_PREFIX,
+        )
+        .await?;
         let mut builder_amc = BuilderActorModelComponent::new();
 
         let actor_bone_label_map = List::load(ACTOR_MODEL_BONE_LIST_PATH)
@@ -439,6 +570,14 @@ impl GameScene {
             },
         )?;
 
+        let mut collider_acc = ColliderActorClassComponent::new();
+
+        actor_class_loading_system.load_component(
+            "collider",
+            &mut collider_acc,
+            |desc: Collider| Ok(desc),
+        )?;
+
         let _actor_class_map = actor_class_loading_system.into_label_map();
 
         position_ac.insert(
@@ -470,7 +609,7 @@ impl GameScene {
 
         window.cursor_visible = false;
 
-        let (block_texture_bind_group_layout, block_texture_bind_group) =
+        let (block_texture_bind_group_layout, block_texture_bind_group, block_texture_animations) =
             block_texture_loading_system
                 .prepare_buffer(
                     window.device(),
@@ -481,7 +620,7 @@ impl GameScene {
                 .await
                 .context("unable to prepare block texture buffer")?;
 
-        let (actor_texture_bind_group_layout, actor_texture_bind_group) =
+        let (actor_texture_bind_group_layout, actor_texture_bind_group, actor_texture_animations) =
             actor_texture_loading_system
                 .prepare_buffer(
                     window.device(),
@@ -499,9 +638,10 @@ impl GameScene {
             // TODO hide?
             camera_parameters: CameraParameters {
                 aspect: 1.0,
-                fovy: 70f32.to_radians(),
+                fovy: settings.field_of_view.to_radians(),
                 near: 0.01,
-                far: 100.0,
+                far: (player_chunk_view_radius as f32 * BLOCKS_IN_CHUNK_EDGE_F32 * 2.0).max(100.0),
+                distance: 0.0,
             },
             position_ac: &position_ac,
             orientation_ac: &orientation_ac,
@@ -517,16 +657,30 @@ impl GameScene {
             render_parameters,
             block_texture_bind_group_layout,
             block_texture_bind_group,
+            block_texture_animations,
             block_texture_label_map: block_texture_loading_system.label_map(),
             location_tc: &block_location_tc,
+            greedy_meshing: settings.greedy_meshing,
         }
         .build(window)
         .await;
 
+        let particle_system = ParticleSystem::load(
+            settings.max_particles,
+            &block_texture_loading_system.label_map(),
+            &block_location_tc,
+        )
+        .context("unable to load particle emitters")?;
+
+        let sky_render_system = SkyRenderSystemDescriptor { render_parameters }
+            .build(window)
+            .await;
+
         let actor_render_system = ActorRenderSystemDescriptor {
             render_parameters,
             actor_texture_bind_group_layout,
             actor_texture_bind_group,
+            actor_texture_animations,
         }
         .build(window)
         .await;
@@ -536,6 +690,9 @@ impl GameScene {
 
         let mut chunk_calc_phase = 0;
 
+        // TODO select from the inventory
+        let held_block_class = block_class_label_map.get("grass");
+
         let mut sd = GameSharedData {
             packer,
 
@@ -543,48 +700,79 @@ impl GameScene {
             position_ac,
             velocity_ac,
             orientation_ac,
+            inventory_ac,
+            health_ac,
+            effect_ac,
             animation_state_ac,
             target_position_ac,
+            position_baseline_ac: PositionBaselineActorComponent::new(),
             target_orientation_ac,
 
             builder_amc,
 
             model_acc,
+            collider_acc,
 
             class_bc,
+            block_entity_bc: BlockEntityBlockComponent::new(),
             sky_light_bc,
+            block_light_bc,
 
             collision_bcc,
-            model_bcc,
+            fluid_bcc: Arc::new(fluid_bcc),
+            model_bcc: Arc::new(model_bcc),
             opacity_bcc,
+            light_bcc,
 
             status_cc,
 
-            builder_bmc,
-            culling_bmc,
+            builder_bmc: Arc::new(builder_bmc),
+            culling_bmc: Arc::new(culling_bmc),
 
             player_position_system,
             movement_interpolation_system,
             direct_control_system,
             chunk_presence_system,
             sky_light_system,
+            block_light_system,
             interface_system,
+            chat_system: ChatSystem::new(),
+            inventory_system: InventorySystem::new(),
+            health_bar_system: HealthBarSystem::new(),
+            effect_hud_system: EffectHudSystem::new(),
+            script_hud_system: ScriptHudSystem::new(),
+            debug_overlay_system: DebugOverlaySystem::new(),
+            screenshot_system: ScreenshotSystem::new(),
             render_system,
             actor_render_system,
+            block_prediction_system: BlockPredictionSystem::new(),
             block_render_system,
+            sky_render_system,
+            sky_system: SkySystem::new(server_process_interval),
+            view_model_system: ViewModelSystem::new(),
+            particle_system,
 
             block_class_label_map,
+            item_class_label_map,
+            recipe_registry,
+            effect_label_map,
+            dimension_kind_label_map,
+            script_registry,
 
+            action_label_map,
             player_actor,
             player_chunk_view_radius,
+            server_process_interval,
+            settings,
 
             snapshot,
             last_client_snapshot,
             last_server_snapshot,
 
             unreliable_tx,
-            reliable_tx,
-            event_tx,
+            reliable_queue: Vec::new(),
+            session,
+            reconnecting: false,
 
             state_packer,
             state_unpacker: StateUnpacker::new(),
@@ -592,9 +780,14 @@ impl GameScene {
             actions_unpacker: ActionsUnpacker::new(),
 
             last_process_time,
+            profiler: Profiler::new(),
+            connection_stats: ConnectionStats::default(),
 
             inventory_open: false,
             cursor_visible: false,
+            third_person: false,
+            debug_overlay_open: false,
+            held_block_class,
         };
 
         let mut send_state_interval = time::interval(Duration::from_millis(50));
@@ -617,8 +810,12 @@ impl GameScene {
             .next()
             .or(future::poll_fn(|_| {
                 // This works because the only update can come from the previous iteration of the
-                // loop
-                if sd.sky_light_system.is_queue_empty() && sd.block_render_system.is_queue_empty() {
+                // loop, except for the finished chunk meshes, those are picked up after the next
+                // frame wakes the loop
+                if sd.sky_light_system.is_queue_empty()
+                    && sd.block_light_system.is_queue_empty()
+                    && sd.block_render_system.is_idle()
+                {
                     return Poll::Pending;
                 }
 
@@ -628,11 +825,15 @@ impl GameScene {
         {
             let transition = match event {
                 Event::Process(frame) => {
+                    sd.connection_stats = connection_stats.get();
+
                     compute!((sd) Process {
                     shared_data: &mut sd,
                     frame,
                 }.run())
                 },
+                // The game is frozen until the connection is restored
+                Event::SendState if sd.reconnecting => Transition::None,
                 Event::SendState => {
                     SendState {
                         shared_data: &mut sd,
@@ -647,13 +848,86 @@ impl GameScene {
                     .run()
                 },
                 Event::NetworkInput(event) => {
-                    NetworkInput {
+                    let started = Instant::now();
+
+                    let transition = NetworkInput {
                         shared_data: &mut sd,
                         event,
                     }
-                    .run()
+                    .run();
+
+                    sd.profiler.record(Section::Network, started.elapsed());
+
+                    transition
+                },
+                Event::AdminResponse(data) => {
+                    if let Ok(AdminResponse { success, text }) = sd.packer.unpack(&data) {
+                        let text = if success {
+                            text
+                        } else {
+                            format!("command failed: {}", text)
+                        };
+
+                        sd.chat_system.add_notice(text);
+                    }
+
+                    Transition::None
+                },
+                Event::Reconnecting(attempt) => {
+                    sd.chat_system
+                        .add_notice(format!("Reconnecting, attempt {}", attempt));
+
+                    Transition::None
+                },
+                Event::Reconnected(Ok(resumed)) => {
+                    let ResumedSession {
+                        connection,
+                        init_data,
+                    } = resumed;
+
+                    let Connection {
+                        sender,
+                        receiver,
+                        resumption,
+                        ..
+                    } = connection;
+
+                    if init_data.actor != sd.player_actor {
+                        error!("game::run: resumed session has another player actor");
+                        return Ok(SceneSwitch::Menu {
+                            parameters: MenuSceneParameters {
+                                window: sd.render_system.into_window(),
+                                settings: sd.settings,
+                            },
+                        });
+                    }
+
+                    let (tasks, unreliable_tx, new_reliable_tx) =
+                        ConnectionTasks::spawn((sender, receiver), &event_tx, &connection_stats);
+
+                    connection_tasks = Some(tasks);
+                    sd.unreliable_tx = unreliable_tx;
+                    reliable_tx = new_reliable_tx;
+                    sd.session.resumption = resumption;
+                    sd.reconnecting = false;
+                    reconnect_task = None;
+
+                    // The server sends the full state again
+                    sd.last_client_snapshot = Snapshot(0);
+                    sd.last_server_snapshot = Snapshot(0);
+
+                    sd.chat_system.add_notice("Connection restored".to_owned());
+
+                    Transition::None
+                },
+                Event::Reconnected(Err(message)) => {
+                    error!("game::run: unable to restore the connection: {}", message);
+
+                    Transition::Menu
                 },
                 Event::ChunkCalculation => {
+                    let started = Instant::now();
+
                     chunk_calc_phase = match chunk_calc_phase {
                         0 => {
                             let changed_chunks = sd.sky_light_system.process(
@@ -667,17 +941,35 @@ impl GameScene {
                                 sd.block_render_system.enqueue_chunk(chunk);
                             }
 
+                            let changed_chunks = sd.block_light_system.process(
+                                voxbrix_common::entity::block::BLOCKS_IN_CHUNK,
+                                &sd.class_bc,
+                                &sd.opacity_bcc,
+                                &sd.light_bcc,
+                                &mut sd.block_light_bc,
+                            );
+
+                            for chunk in changed_chunks {
+                                sd.block_render_system.enqueue_chunk(chunk);
+                            }
+
+                            sd.profiler.record(Section::Lighting, started.elapsed());
+
                             1
                         },
                         1 => {
                             sd.block_render_system.process(
                                 &sd.class_bc,
                                 &sd.model_bcc,
+                                &sd.fluid_bcc,
                                 &sd.builder_bmc,
                                 &sd.culling_bmc,
                                 &sd.sky_light_bc,
+                                &sd.block_light_bc,
                             );
 
+                            sd.profiler.record(Section::Meshing, started.elapsed());
+
                             0
                         },
                         _ => unreachable!(),
@@ -687,8 +979,29 @@ impl GameScene {
                 },
             };
 
+            // The receiver is gone while the connection is lost, the messages are dropped
+            for (priority, message) in sd.reliable_queue.drain(..) {
+                let _ = reliable_tx.send(priority, message);
+            }
+
             match transition {
                 Transition::None => {},
+                Transition::Reconnect => {
+                    if reconnect_task.is_none() {
+                        info!("connection lost, reconnecting");
+
+                        drop(connection_tasks.take());
+
+                        // The server could have handled them already, the resumed session
+                        // must not repeat them
+                        sd.actions_packer.confirm_snapshot(sd.snapshot);
+
+                        sd.chat_system
+                            .add_notice("Connection lost, reconnecting".to_owned());
+                        sd.reconnecting = true;
+                        reconnect_task = Some(reconnect::start(&sd.session, event_tx.clone()));
+                    }
+                },
                 Transition::Exit => {
                     return Ok(SceneSwitch::Exit);
                 },
@@ -696,6 +1009,7 @@ impl GameScene {
                     return Ok(SceneSwitch::Menu {
                         parameters: MenuSceneParameters {
                             window: sd.render_system.into_window(),
+                            settings: sd.settings,
                         },
                     });
                 },
@@ -705,7 +1019,131 @@ impl GameScene {
         Ok(SceneSwitch::Menu {
             parameters: MenuSceneParameters {
                 window: sd.render_system.into_window(),
+                settings: sd.settings,
             },
         })
     }
 }
+
+/// Sends and receives the messages of the connection until dropped.
+struct ConnectionTasks {
+    _send_unreliable: ScopeTask<()>,
+    _send_reliable: ScopeTask<()>,
+    _recv: ScopeTask<()>,
+}
+
+impl ConnectionTasks {
+    fn spawn(
+        connection: (Sender, Receiver),
+        event_tx: &flume::Sender<Event>,
+        connection_stats: &Rc<Cell<ConnectionStats>>,
+    ) -> (
+        Self,
+        flume::Sender<Vec<u8>>,
+        PrioritySender<(usize, Vec<u8>)>,
+    ) {
+        let (reliable_tx, mut reliable_rx) = local_channel::priority::channel::<(usize, Vec<u8>)>();
+        let (unreliable_tx, unreliable_rx) = flume::unbounded::<Vec<u8>>();
+
+        let (tx, mut rx) = connection;
+
+        let (mut unreliable, mut reliable) = tx.split();
+
+        let send_unreliable_task = async_ext::spawn_scoped(async move {
+            while let Ok(msg) = unreliable_rx.recv_async().await {
+                unreliable
+                    .send_unreliable(0, &msg)
+                    .await
+                    .expect("send_unreliable should not fail");
+            }
+        });
+
+        let event_tx_network = event_tx.clone();
+
+        let send_reliable_task = async_ext::spawn_scoped(async move {
+            loop {
+                let msg = async { Ok::<_, ClientError>(reliable_rx.recv().await) }
+                    .or(async {
+                        reliable
+                            .keepalive(KeepaliveParameters {
+                                timeout: CONNECTION_TIMEOUT,
+                                ..Default::default()
+                            })
+                            .await?;
+                        unreachable!();
+                    })
+                    .await;
+
+                let (channel, msg) = match msg {
+                    Ok(Ok(msg)) => msg,
+                    // Game loop is closed
+                    Ok(Err(_)) => break,
+                    Err(err) => {
+                        let _ = event_tx_network.send(Event::NetworkInput(Err(err)));
+                        break;
+                    },
+                };
+
+                // https://github.com/rust-lang/rust/issues/70142
+                let result =
+                    match time::timeout(CONNECTION_TIMEOUT, reliable.send_reliable(channel, &msg))
+                        .await
+                        .map_err(|_| ClientError::Io(StdIoErrorKind::TimedOut.into()))
+                    {
+                        Ok(Ok(ok)) => Ok(ok),
+                        Ok(Err(err)) => Err(err),
+                        Err(err) => Err(err),
+                    };
+
+                if let Err(err) = result {
+                    let _ = event_tx_network.send(Event::NetworkInput(Err(err)));
+                    break;
+                }
+            }
+        });
+
+        let event_tx_network = event_tx.clone();
+        let connection_stats_network = connection_stats.clone();
+
+        let recv_task = async_ext::spawn_scoped(async move {
+            loop {
+                let result = rx
+                    .recv()
+                    .await
+                    .map(|(channel, data)| (channel, data.to_vec()));
+
+                connection_stats_network.set(rx.stats());
+
+                let (channel, data) = match result {
+                    Ok(msg) => msg,
+                    Err(ClientError::ConnectionMigrated { address }) => {
+                        info!("connection moved to local address {}", address);
+                        continue;
+                    },
+                    Err(err) => {
+                        let _ = event_tx_network.send(Event::NetworkInput(Err(err)));
+                        break;
+                    },
+                };
+
+                let event = if channel == ADMIN_CHANNEL {
+                    Event::AdminResponse(data)
+                } else {
+                    Event::NetworkInput(Ok(data))
+                };
+
+                if event_tx_network.send(event).is_err() {
+                    break;
+                };
+            }
+        });
+
+        let tasks = Self {
+            _send_unreliable: send_unreliable_task,
+            _send_reliable: send_reliable_task,
+            _recv: recv_task,
+        };
+
+        (tasks, unreliable_tx, reliable_tx)
+    }
+}
@@ -3,47 +3,92 @@ use crate::{
         actor::{
             animation_state::AnimationStateActorComponent,
             class::ClassActorComponent,
+            effect::EffectActorComponent,
+            health::HealthActorComponent,
+            inventory::InventoryActorComponent,
             orientation::OrientationActorComponent,
             position::PositionActorComponent,
+            position_baseline::PositionBaselineActorComponent,
             target_orientation::TargetOrientationActorComponent,
             target_position::TargetPositionActorComponent,
             velocity::VelocityActorComponent,
         },
         actor_class::model::ModelActorClassComponent,
         actor_model::builder::BuilderActorModelComponent,
-        block::class::ClassBlockComponent,
+        block::{
+            block_entity::BlockEntityBlockComponent,
+            class::ClassBlockComponent,
+        },
         block_class::model::ModelBlockClassComponent,
         block_model::{
             builder::BuilderBlockModelComponent,
             culling::CullingBlockModelComponent,
         },
     },
-    scene::game::Event,
+    scene::game::Session,
+    settings::Settings,
     system::{
         actor_render::ActorRenderSystem,
+        block_prediction::BlockPredictionSystem,
         block_render::BlockRenderSystem,
+        chat::ChatSystem,
         chunk_presence::ChunkPresenceSystem,
         controller::DirectControl,
+        debug_overlay::DebugOverlaySystem,
+        effect_hud::EffectHudSystem,
+        health_bar::HealthBarSystem,
         interface::InterfaceSystem,
+        inventory::InventorySystem,
         movement_interpolation::MovementInterpolationSystem,
+        particle::ParticleSystem,
         player_position::PlayerPositionSystem,
+        profiler::Profiler,
         render::RenderSystem,
+        screenshot::ScreenshotSystem,
+        script_hud::ScriptHudSystem,
+        sky::SkySystem,
+        sky_render::SkyRenderSystem,
+        view_model::ViewModelSystem,
     },
 };
+use client_loop_api::{
+    GetBlockClassRequest,
+    PlayerPosition,
+    ShowHudTextRequest,
+    SpawnParticlesRequest,
+};
 use flume::Sender;
-use std::time::Instant;
+use std::{
+    sync::Arc,
+    time::{
+        Duration,
+        Instant,
+    },
+};
 use voxbrix_common::{
     component::{
-        block::sky_light::SkyLightBlockComponent,
+        actor::position::Position,
+        actor_class::collider::ColliderActorClassComponent,
+        block::{
+            block_light::BlockLightBlockComponent,
+            sky_light::SkyLightBlockComponent,
+        },
         block_class::{
             collision::CollisionBlockClassComponent,
+            fluid::FluidBlockClassComponent,
+            light::LightBlockClassComponent,
             opacity::OpacityBlockClassComponent,
         },
         chunk::status::StatusChunkComponent,
     },
     entity::{
+        action::Action,
         actor::Actor,
+        block::Block,
         block_class::BlockClass,
+        chunk::DimensionKind,
+        effect::Effect,
+        item_class::ItemClass,
         snapshot::Snapshot,
     },
     messages::{
@@ -52,10 +97,33 @@ use voxbrix_common::{
         StatePacker,
         StateUnpacker,
     },
-    pack::Packer,
-    system::sky_light::SkyLightSystem,
+    pack::{
+        self,
+        Packer,
+    },
+    script_registry::{
+        self,
+        ScriptData,
+        ScriptRegistry,
+        ScriptRegistryBuilder,
+        SendMutPtr,
+        SendPtr,
+    },
+    system::{
+        block_light::BlockLightSystem,
+        recipe_loading::RecipeRegistry,
+        sky_light::SkyLightSystem,
+    },
     LabelMap,
 };
+use voxbrix_protocol::ConnectionStats;
+use wasmtime::Caller;
+
+/// Priority of the game messages in `GameSharedData::reliable_queue`.
+pub const GAME_MESSAGE_PRIORITY: u8 = 1;
+/// Priority of the admin commands in `GameSharedData::reliable_queue`,
+/// they wait for the game messages.
+pub const ADMIN_MESSAGE_PRIORITY: u8 = 0;
 
 /// All components and systems the loop has.
 pub struct GameSharedData {
@@ -65,51 +133,83 @@ pub struct GameSharedData {
     pub position_ac: PositionActorComponent,
     pub velocity_ac: VelocityActorComponent,
     pub orientation_ac: OrientationActorComponent,
+    pub inventory_ac: InventoryActorComponent,
+    pub health_ac: HealthActorComponent,
+    pub effect_ac: EffectActorComponent,
     pub animation_state_ac: AnimationStateActorComponent,
     pub target_position_ac: TargetPositionActorComponent,
+    pub position_baseline_ac: PositionBaselineActorComponent,
     pub target_orientation_ac: TargetOrientationActorComponent,
 
     pub builder_amc: BuilderActorModelComponent,
 
     pub model_acc: ModelActorClassComponent,
+    pub collider_acc: ColliderActorClassComponent,
 
     pub class_bc: ClassBlockComponent,
+    pub block_entity_bc: BlockEntityBlockComponent,
     pub sky_light_bc: SkyLightBlockComponent,
+    pub block_light_bc: BlockLightBlockComponent,
 
     pub collision_bcc: CollisionBlockClassComponent,
-    pub model_bcc: ModelBlockClassComponent,
+    pub fluid_bcc: Arc<FluidBlockClassComponent>,
+    pub model_bcc: Arc<ModelBlockClassComponent>,
     pub opacity_bcc: OpacityBlockClassComponent,
+    pub light_bcc: LightBlockClassComponent,
 
     pub status_cc: StatusChunkComponent,
 
-    pub builder_bmc: BuilderBlockModelComponent,
-    pub culling_bmc: CullingBlockModelComponent,
+    pub builder_bmc: Arc<BuilderBlockModelComponent>,
+    pub culling_bmc: Arc<CullingBlockModelComponent>,
 
     pub player_position_system: PlayerPositionSystem,
     pub movement_interpolation_system: MovementInterpolationSystem,
     pub direct_control_system: DirectControl,
     pub chunk_presence_system: ChunkPresenceSystem,
     pub sky_light_system: SkyLightSystem,
+    pub block_light_system: BlockLightSystem,
     pub interface_system: InterfaceSystem,
+    pub chat_system: ChatSystem,
+    pub inventory_system: InventorySystem,
+    pub health_bar_system: HealthBarSystem,
+    pub effect_hud_system: EffectHudSystem,
+    pub script_hud_system: ScriptHudSystem,
+    pub debug_overlay_system: DebugOverlaySystem,
+    pub screenshot_system: ScreenshotSystem,
     pub render_system: RenderSystem,
     pub actor_render_system: ActorRenderSystem,
+    pub block_prediction_system: BlockPredictionSystem,
     pub block_render_system: BlockRenderSystem,
+    pub sky_render_system: SkyRenderSystem,
+    pub sky_system: SkySystem,
+    pub view_model_system: ViewModelSystem,
+    pub particle_system: ParticleSystem,
 
     pub block_class_label_map: LabelMap<BlockClass>,
+    pub item_class_label_map: LabelMap<ItemClass>,
+    pub recipe_registry: RecipeRegistry,
+    pub effect_label_map: LabelMap<Effect>,
+    pub dimension_kind_label_map: LabelMap<DimensionKind>,
+    pub script_registry: ScriptRegistry<ClientScriptSharedData>,
 
-    // pub action_label_map: LabelMap<Action>,
+    pub action_label_map: LabelMap<Action>,
     pub player_actor: Actor,
     pub player_chunk_view_radius: i32,
+    pub server_process_interval: Duration,
+    pub settings: Settings,
 
     pub snapshot: Snapshot,
     pub last_client_snapshot: Snapshot,
     pub last_server_snapshot: Snapshot,
 
     pub unreliable_tx: Sender<Vec<u8>>,
-    #[allow(dead_code)]
-    pub reliable_tx: Sender<Vec<u8>>,
-    #[allow(dead_code)]
-    pub event_tx: Sender<Event>,
+    /// Reliable messages with the priority and the channel to send them on,
+    /// passed to the sending task by the game loop after every event.
+    /// The game messages are sent before the admin commands.
+    pub reliable_queue: Vec<(u8, (usize, Vec<u8>))>,
+    pub session: Session,
+    /// The lost connection is being restored, the game is frozen meanwhile.
+    pub reconnecting: bool,
 
     pub state_packer: StatePacker,
     pub state_unpacker: StateUnpacker,
@@ -117,7 +217,250 @@ pub struct GameSharedData {
     pub actions_unpacker: ActionsUnpacker,
 
     pub last_process_time: Instant,
+    pub profiler: Profiler,
+    /// Copy of the statistics the receiving task updates on every message,
+    /// taken before every frame.
+    pub connection_stats: ConnectionStats,
 
     pub inventory_open: bool,
     pub cursor_visible: bool,
+    pub third_person: bool,
+    pub debug_overlay_open: bool,
+    /// Block class placed by the player and shown in the hand.
+    pub held_block_class: Option<BlockClass>,
+}
+
+/// Data available to the client scripts, the scripts can only read the world.
+///
+/// For safely retrieving the data:
+/// 1. Make sure that the pointers (SendPtr and SendMutPtr) do not live after the wrapped function
+///    returns. Should be easy: do NOT replace SendPtr- and SendMutPtr-typed fields of the
+///    ClientScriptSharedData in the wrapped functions.
+/// 2. Pointers created must not violate borrowing rules.
+pub struct ClientScriptSharedData {
+    pub player_actor: Actor,
+    pub class_bc: SendPtr<ClassBlockComponent>,
+    pub block_entity_bc: SendPtr<BlockEntityBlockComponent>,
+    pub position_ac: SendPtr<PositionActorComponent>,
+    pub block_class_label_map: SendPtr<LabelMap<BlockClass>>,
+    pub script_hud_system: SendMutPtr<ScriptHudSystem>,
+    pub model_bcc: SendPtr<ModelBlockClassComponent>,
+    pub builder_bmc: SendPtr<BuilderBlockModelComponent>,
+    pub particle_system: SendMutPtr<ParticleSystem>,
+}
+
+impl GameSharedData {
+    pub fn script_shared_data(&mut self) -> ClientScriptSharedData {
+        ClientScriptSharedData {
+            player_actor: self.player_actor,
+            class_bc: SendPtr::new(&self.class_bc),
+            block_entity_bc: SendPtr::new(&self.block_entity_bc),
+            position_ac: SendPtr::new(&self.position_ac),
+            block_class_label_map: SendPtr::new(&self.block_class_label_map),
+            script_hud_system: SendMutPtr::new(&mut self.script_hud_system),
+            model_bcc: SendPtr::new(&*self.model_bcc),
+            builder_bmc: SendPtr::new(&*self.builder_bmc),
+            particle_system: SendMutPtr::new(&mut self.particle_system),
+        }
+    }
+}
+
+pub fn setup_script_registry(
+    mut registry: ScriptRegistryBuilder<ClientScriptSharedData>,
+) -> ScriptRegistry<ClientScriptSharedData> {
+    fn handle_panic(
+        caller: Caller<ScriptData<ClientScriptSharedData>>,
+        msg_ptr: u32,
+        msg_len: u32,
+    ) {
+        let ptr = msg_ptr as usize;
+        let len = msg_len as usize;
+        let memory = caller.data().memory();
+        let msg = std::str::from_utf8(&memory.data(&caller)[ptr .. ptr + len]).unwrap();
+
+        panic!("script ended with panic: {}", msg);
+    }
+
+    registry.func_wrap("env", "handle_panic", handle_panic);
+
+    fn get_block_class_by_label(
+        mut caller: Caller<ScriptData<ClientScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (label, _) = pack::decode_from_slice::<&str>(bytes).expect("invalid argument");
+
+        let sd = caller.data().shared();
+        let block_class_label_map = unsafe { sd.block_class_label_map.get() };
+
+        let response = block_class_label_map
+            .get(label)
+            .map(client_loop_api::BlockClass::from);
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "get_block_class_by_label", get_block_class_by_label);
+
+    fn get_block_class(
+        mut caller: Caller<ScriptData<ClientScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (request, _) =
+            pack::decode_from_slice::<GetBlockClassRequest>(bytes).expect("invalid argument");
+
+        let sd = caller.data().shared();
+        let class_bc = unsafe { sd.class_bc.get() };
+
+        // Blocks of the chunks that are not loaded on the client are unknown
+        let response = Block::from_chunk_offset(request.chunk.into(), request.offset)
+            .and_then(|(chunk, block)| Some(*class_bc.get_chunk(&chunk)?.get(block)))
+            .map(client_loop_api::BlockClass::from);
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "get_block_class", get_block_class);
+
+    fn get_block_entity(
+        mut caller: Caller<ScriptData<ClientScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (request, _) =
+            pack::decode_from_slice::<GetBlockClassRequest>(bytes).expect("invalid argument");
+
+        let sd = caller.data().shared();
+        let block_entity_bc = unsafe { sd.block_entity_bc.get() };
+
+        let response = Block::from_chunk_offset(request.chunk.into(), request.offset)
+            .and_then(|(chunk, block)| block_entity_bc.get(&chunk, block))
+            .map(|block_entity| {
+                client_loop_api::BlockEntity {
+                    kind: block_entity.kind.clone(),
+                    data: block_entity.data.clone(),
+                }
+            });
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "get_block_entity", get_block_entity);
+
+    fn get_player_position(
+        mut caller: Caller<ScriptData<ClientScriptSharedData>>,
+        _buf_ptr: u32,
+        _buf_len: u32,
+    ) {
+        let sd = caller.data().shared();
+        let position_ac = unsafe { sd.position_ac.get() };
+
+        let response = position_ac.get(&sd.player_actor).map(|position| {
+            PlayerPosition {
+                chunk: position.chunk.into(),
+                offset: position.offset.into(),
+            }
+        });
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "get_player_position", get_player_position);
+
+    fn show_hud_text(
+        mut caller: Caller<ScriptData<ClientScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (request, _) =
+            pack::decode_from_slice::<ShowHudTextRequest>(bytes).expect("invalid argument");
+
+        let id = request.id.to_owned();
+        let text = request.text.to_owned();
+        let anchor = request.anchor;
+        let offset = request.offset;
+        let duration = request.duration_ms.map(Duration::from_millis);
+
+        let sd = caller.data_mut().shared_mut();
+        let script_hud_system = unsafe { sd.script_hud_system.get_mut() };
+
+        script_hud_system.show_text(id, text, anchor, offset, duration);
+    }
+
+    registry.func_wrap("env", "show_hud_text", show_hud_text);
+
+    fn remove_hud_element(
+        mut caller: Caller<ScriptData<ClientScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (id, _) = pack::decode_from_slice::<&str>(bytes).expect("invalid argument");
+
+        let id = id.to_owned();
+
+        let sd = caller.data_mut().shared_mut();
+        let script_hud_system = unsafe { sd.script_hud_system.get_mut() };
+
+        script_hud_system.remove(&id);
+    }
+
+    registry.func_wrap("env", "remove_hud_element", remove_hud_element);
+
+    fn spawn_particles(
+        mut caller: Caller<ScriptData<ClientScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (request, _) =
+            pack::decode_from_slice::<SpawnParticlesRequest>(bytes).expect("invalid argument");
+
+        let emitter = request.emitter.to_owned();
+        let position = Position {
+            chunk: request.chunk.into(),
+            offset: request.offset.into(),
+        };
+        let block_class = request.block_class.map(BlockClass::from);
+
+        let sd = caller.data_mut().shared_mut();
+        let model_bcc = unsafe { sd.model_bcc.get() };
+        let builder_bmc = unsafe { sd.builder_bmc.get() };
+        let particle_system = unsafe { sd.particle_system.get_mut() };
+
+        particle_system.emit(&emitter, position, block_class, model_bcc, builder_bmc);
+    }
+
+    registry.func_wrap("env", "spawn_particles", spawn_particles);
+
+    registry.build()
 }
@@ -1,18 +1,37 @@
+#[cfg(feature = "gamepad")]
+use crate::window::{
+    GamepadAxis,
+    GamepadButton,
+    GamepadEvent,
+};
 use crate::{
     scene::game::{
         data::GameSharedData,
         Transition,
     },
+    settings::key_bindings::{
+        InputAction,
+        InputBinding,
+    },
     window::{
         InputEvent,
         WindowEvent,
     },
 };
-use voxbrix_common::entity::block::Block;
-use winit::event::{
-    DeviceEvent,
-    ElementState,
-    MouseButton,
+use voxbrix_common::entity::{
+    block::Block,
+    block_class::BlockClass,
+    chunk::Chunk,
+};
+use winit::{
+    event::{
+        DeviceEvent,
+        ElementState,
+    },
+    keyboard::{
+        KeyCode,
+        PhysicalKey,
+    },
 };
 
 pub struct LocalInput<'a> {
@@ -27,7 +46,51 @@ impl LocalInput<'_> {
             event,
         } = self;
 
+        // Stick positions are tracked all the time, otherwise they would stay
+        // deflected after closing the interface
+        #[cfg(feature = "gamepad")]
+        match &event {
+            InputEvent::GamepadEvent(GamepadEvent::AxisChanged(axis, value, _)) => {
+                sd.direct_control_system.process_gamepad_axis(*axis, *value);
+                return Transition::None;
+            },
+            InputEvent::GamepadEvent(GamepadEvent::Disconnected) => {
+                for axis in [
+                    GamepadAxis::LeftStickX,
+                    GamepadAxis::LeftStickY,
+                    GamepadAxis::RightStickX,
+                    GamepadAxis::RightStickY,
+                ] {
+                    sd.direct_control_system.process_gamepad_axis(axis, 0.0);
+                }
+                return Transition::None;
+            },
+            _ => {},
+        }
+
         if sd.inventory_open {
+            // Gamepad has no cursor to press the close button with
+            #[cfg(feature = "gamepad")]
+            if let InputEvent::GamepadEvent(GamepadEvent::ButtonPressed(button, _)) = &event {
+                if sd.settings.gamepad.bindings.action(*button) == Some(InputAction::OpenInventory)
+                {
+                    sd.inventory_open = false;
+                }
+            }
+
+            return Transition::None;
+        }
+
+        // Keyboard input goes to the chat panel while it is open
+        if sd.chat_system.is_input_open() {
+            if let InputEvent::WindowEvent(WindowEvent::KeyboardInput { event, .. }) = &event {
+                if event.state == ElementState::Pressed
+                    && event.physical_key == PhysicalKey::Code(KeyCode::Escape)
+                {
+                    sd.chat_system.close_input();
+                }
+            }
+
             return Transition::None;
         }
 
@@ -53,152 +116,241 @@ impl LocalInput<'_> {
                         event,
                         is_synthetic: _,
                     } => {
-                        if let winit::keyboard::PhysicalKey::Code(button) = event.physical_key {
-                            if matches!(event.state, winit::event::ElementState::Pressed) {
-                                match button {
-                                    winit::keyboard::KeyCode::Escape => {
-                                        return Transition::Menu;
-                                    },
-                                    winit::keyboard::KeyCode::KeyI => {
-                                        sd.inventory_open = !sd.inventory_open;
-                                    },
-                                    _ => {},
-                                }
+                        if let PhysicalKey::Code(key) = event.physical_key {
+                            let pressed = event.state == ElementState::Pressed;
+
+                            if pressed && key == KeyCode::Escape {
+                                return Transition::Menu;
+                            }
+
+                            if let Some(action) =
+                                sd.settings.key_bindings.action(InputBinding::Key(key))
+                            {
+                                run_action(sd, action, pressed);
                             }
                         }
-                        sd.direct_control_system.process_keyboard(&event);
                     },
                     WindowEvent::MouseInput { state, button, .. } => {
-                        if state == ElementState::Pressed {
-                            match button {
-                                MouseButton::Left => {
-                                    if sd
-                                        .player_position_system
-                                        .get_target_block(
-                                            &sd.position_ac,
-                                            &sd.orientation_ac,
-                                            |chunk, block| {
-                                                sd.class_bc
-                                                    .get_chunk(&chunk)
-                                                    .map(|blocks| {
-                                                        let class = blocks.get(block);
-                                                        sd.collision_bcc.get(class).is_some()
-                                                    })
-                                                    .unwrap_or(false)
-                                            },
-                                        )
-                                        .is_some()
-                                    {
-                                        // TODO Handle with script
-                                        use serde::{
-                                            Deserialize,
-                                            Serialize,
-                                        };
-                                        use voxbrix_common::entity::{
-                                            action::Action,
-                                            chunk::Chunk,
-                                        };
-
-                                        let (position, direction) =
-                                            sd.player_position_system.position_direction(
-                                                &sd.position_ac,
-                                                &sd.orientation_ac,
-                                            );
-
-                                        #[derive(Serialize, Deserialize)]
-                                        pub struct RemoveBlock {
-                                            chunk: Chunk,
-                                            offset: [f32; 3],
-                                            direction: [f32; 3],
-                                        }
-
-                                        sd.actions_packer.add_action(
-                                            Action(0),
-                                            sd.snapshot,
-                                            RemoveBlock {
-                                                chunk: position.chunk,
-                                                offset: position.offset.into(),
-                                                direction: direction.into(),
-                                            },
-                                        );
-                                    }
-                                },
-                                MouseButton::Right => {
-                                    if let Some((chunk, block, side)) =
-                                        sd.player_position_system.get_target_block(
-                                            &sd.position_ac,
-                                            &sd.orientation_ac,
-                                            |chunk, block| {
-                                                sd.class_bc
-                                                    .get_chunk(&chunk)
-                                                    .map(|blocks| {
-                                                        let class = blocks.get(block);
-                                                        sd.collision_bcc.get(class).is_some()
-                                                    })
-                                                    .unwrap_or(false)
-                                            },
-                                        )
-                                    {
-                                        let axis = side / 2;
-                                        let direction = match side % 2 {
-                                            0 => -1,
-                                            1 => 1,
-                                            _ => panic!("incorrect side index"),
-                                        };
-                                        let mut block = block.into_coords().map(|u| u as i32);
-                                        block[axis] += direction;
-
-                                        if Block::from_chunk_offset(chunk, block).is_some() {
-                                            // TODO Handle with script
-                                            use serde::{
-                                                Deserialize,
-                                                Serialize,
-                                            };
-                                            use voxbrix_common::entity::{
-                                                action::Action,
-                                                block_class::BlockClass,
-                                                chunk::Chunk,
-                                            };
-
-                                            let (position, direction) =
-                                                sd.player_position_system.position_direction(
-                                                    &sd.position_ac,
-                                                    &sd.orientation_ac,
-                                                );
-
-                                            #[derive(Serialize, Deserialize)]
-                                            pub struct PlaceBlock {
-                                                chunk: Chunk,
-                                                offset: [f32; 3],
-                                                direction: [f32; 3],
-                                                block_class: BlockClass,
-                                            }
-
-                                            sd.actions_packer.add_action(
-                                                Action(1),
-                                                sd.snapshot,
-                                                PlaceBlock {
-                                                    chunk: position.chunk,
-                                                    offset: position.offset.into(),
-                                                    direction: direction.into(),
-                                                    block_class: sd
-                                                        .block_class_label_map
-                                                        .get("grass")
-                                                        .unwrap(),
-                                                },
-                                            );
-                                        }
-                                    }
-                                },
-                                _ => {},
-                            }
+                        if let Some(action) =
+                            sd.settings.key_bindings.action(InputBinding::Mouse(button))
+                        {
+                            run_action(sd, action, state == ElementState::Pressed);
                         }
                     },
                     _ => {},
                 }
             },
+            #[cfg(feature = "gamepad")]
+            InputEvent::GamepadEvent(event) => {
+                let (button, pressed) = match event {
+                    GamepadEvent::ButtonPressed(button, _) => (button, true),
+                    GamepadEvent::ButtonReleased(button, _) => (button, false),
+                    _ => return Transition::None,
+                };
+
+                if pressed && button == GamepadButton::Start {
+                    return Transition::Menu;
+                }
+
+                if let Some(action) = sd.settings.gamepad.bindings.action(button) {
+                    run_action(sd, action, pressed);
+                }
+            },
         }
 
         Transition::None
     }
 }
+
+fn run_action(sd: &mut GameSharedData, action: InputAction, pressed: bool) {
+    match action {
+        InputAction::MoveForward
+        | InputAction::MoveBackward
+        | InputAction::MoveLeft
+        | InputAction::MoveRight
+        | InputAction::MoveUp
+        | InputAction::MoveDown => {
+            sd.direct_control_system.process_action(action, pressed);
+        },
+        // The rest happen once on press
+        _ if !pressed => {},
+        // Not sent until the lost connection is restored
+        InputAction::RemoveBlock | InputAction::PlaceBlock if sd.reconnecting => {},
+        InputAction::OpenInventory => {
+            sd.inventory_open = !sd.inventory_open;
+        },
+        InputAction::OpenChat => {
+            sd.chat_system.open_input();
+        },
+        InputAction::ToggleCamera => {
+            sd.third_person = !sd.third_person;
+        },
+        InputAction::ToggleDebugOverlay => {
+            sd.debug_overlay_open = !sd.debug_overlay_open;
+        },
+        InputAction::Screenshot => {
+            sd.screenshot_system.request();
+        },
+        InputAction::RemoveBlock => {
+            sd.view_model_system.swing();
+            remove_block(sd);
+        },
+        InputAction::PlaceBlock => {
+            sd.view_model_system.swing();
+            place_block(sd);
+        },
+    }
+}
+
+/// Applies the block change the action is expected to make on the server,
+/// see `BlockPredictionSystem`.
+fn predict_block(sd: &mut GameSharedData, chunk: Chunk, block: Block, block_class: BlockClass) {
+    let Some(previous_class) = sd.block_prediction_system.predict(
+        chunk,
+        block,
+        block_class,
+        sd.snapshot,
+        &mut sd.class_bc,
+    ) else {
+        return;
+    };
+
+    let (label, particle_class) = if sd.model_bcc.get(&block_class).is_some() {
+        ("block_place", block_class)
+    } else {
+        ("block_break", previous_class)
+    };
+
+    sd.particle_system.emit_block(
+        label,
+        chunk,
+        block,
+        particle_class,
+        &sd.model_bcc,
+        &sd.builder_bmc,
+    );
+
+    sd.sky_light_system.block_change(&chunk, block);
+    sd.block_light_system.block_change(&chunk, block);
+    sd.block_render_system.block_change(&chunk, block);
+}
+
+fn remove_block(sd: &mut GameSharedData) {
+    if let Some((target_chunk, target_block, _)) = sd.player_position_system.get_target_block(
+        &sd.position_ac,
+        &sd.orientation_ac,
+        |chunk, block| {
+            sd.class_bc
+                .get_chunk(&chunk)
+                .map(|blocks| {
+                    let class = blocks.get(block);
+                    sd.collision_bcc.get(class).is_some()
+                })
+                .unwrap_or(false)
+        },
+    ) {
+        // TODO Handle with script
+        use serde::{
+            Deserialize,
+            Serialize,
+        };
+
+        let (position, direction) = sd
+            .player_position_system
+            .position_direction(&sd.position_ac, &sd.orientation_ac);
+
+        #[derive(Serialize, Deserialize)]
+        pub struct RemoveBlock {
+            chunk: Chunk,
+            offset: [f32; 3],
+            direction: [f32; 3],
+        }
+
+        let action = sd
+            .action_label_map
+            .get("remove_block")
+            .expect("checked on load");
+
+        sd.actions_packer.add_action(
+            action,
+            sd.snapshot,
+            RemoveBlock {
+                chunk: position.chunk,
+                offset: position.offset.into(),
+                direction: direction.into(),
+            },
+        );
+
+        // The server script replaces the removed blocks with air
+        if let Some(air) = sd.block_class_label_map.get("air") {
+            predict_block(sd, target_chunk, target_block, air);
+        }
+    }
+}
+
+fn place_block(sd: &mut GameSharedData) {
+    let Some(held_block_class) = sd.held_block_class else {
+        return;
+    };
+
+    if let Some((chunk, block, side)) = sd.player_position_system.get_target_block(
+        &sd.position_ac,
+        &sd.orientation_ac,
+        |chunk, block| {
+            sd.class_bc
+                .get_chunk(&chunk)
+                .map(|blocks| {
+                    let class = blocks.get(block);
+                    sd.collision_bcc.get(class).is_some()
+                })
+                .unwrap_or(false)
+        },
+    ) {
+        let axis = side / 2;
+        let direction = match side % 2 {
+            0 => -1,
+            1 => 1,
+            _ => panic!("incorrect side index"),
+        };
+        let mut block = block.into_coords().map(|u| u as i32);
+        block[axis] += direction;
+
+        if let Some((target_chunk, target_block)) = Block::from_chunk_offset(chunk, block) {
+            // TODO Handle with script
+            use serde::{
+                Deserialize,
+                Serialize,
+            };
+
+            let (position, direction) = sd
+                .player_position_system
+                .position_direction(&sd.position_ac, &sd.orientation_ac);
+
+            #[derive(Serialize, Deserialize)]
+            pub struct PlaceBlock {
+                chunk: Chunk,
+                offset: [f32; 3],
+                direction: [f32; 3],
+                block_class: BlockClass,
+            }
+
+            let action = sd
+                .action_label_map
+                .get("place_block")
+                .expect("checked on load");
+
+            sd.actions_packer.add_action(
+                action,
+                sd.snapshot,
+                PlaceBlock {
+                    chunk: position.chunk,
+                    offset: position.offset.into(),
+                    direction: direction.into(),
+                    block_class: held_block_class,
+                },
+            );
+
+            predict_block(sd, target_chunk, target_block, held_block_class);
+        }
+    }
+}
@@ -5,14 +5,22 @@ use crate::{
         Transition,
     },
 };
-use log::error;
-use std::time::Instant;
+use client_loop_api::DispatchInput;
+use log::{
+    error,
+    warn,
+};
+use std::{
+    mem,
+    time::Instant,
+};
 use voxbrix_common::{
     component::{
         actor::{
-            orientation::Orientation,
-            position::Position,
+            orientation::OrientationPacked,
+            position::PositionPacked,
         },
+        block::block_entity::ChunkBlockEntities,
         chunk::status::ChunkStatus,
     },
     entity::actor::Actor,
@@ -20,7 +28,10 @@ use voxbrix_common::{
     pack,
     ChunkData,
 };
-use voxbrix_protocol::client::Error as ClientError;
+use voxbrix_protocol::{
+    client::Error as ClientError,
+    DROP_DISCONNECT_REASON,
+};
 
 pub struct NetworkInput<'a> {
     pub shared_data: &'a mut GameSharedData,
@@ -36,11 +47,23 @@ impl NetworkInput<'_> {
 
         let message = match event {
             Ok(m) => m,
-            Err(err) => {
-                // TODO handle properly, pass error to menu to display there
-                error!("game::run: connection error: {:?}", err);
+            // The errors of the lost connection arrive from several tasks
+            Err(_) if sd.reconnecting => return Transition::None,
+            // Explicit disconnects end the session, the dropped connections may be resumed
+            Err(ClientError::Disconnect { reason, payload })
+                if reason != DROP_DISCONNECT_REASON =>
+            {
+                error!(
+                    "game::run: disconnected by server, reason {}: {}",
+                    reason,
+                    String::from_utf8_lossy(&payload)
+                );
                 return Transition::Menu;
             },
+            Err(err) => {
+                warn!("game::run: connection error: {:?}", err);
+                return Transition::Reconnect;
+            },
         };
 
         let message = match sd.packer.unpack::<ClientAccept>(&message) {
@@ -52,8 +75,11 @@ impl NetworkInput<'_> {
             ClientAccept::State {
                 snapshot: new_lss,
                 last_client_snapshot: new_lcs,
+                last_server_snapshot: baseline_snapshot,
                 state,
                 actions,
+                position_correction,
+                actors_left,
             } => {
                 let current_time = Instant::now();
 
@@ -62,48 +88,71 @@ impl NetworkInput<'_> {
                 };
 
                 sd.class_ac.unpack_state(&state);
+                sd.inventory_ac.unpack_state(&state);
+                sd.health_ac.unpack_state(&state);
+                sd.effect_ac.unpack_state(&state);
                 sd.model_acc.unpack_state(&state);
                 sd.velocity_ac.unpack_state(&state);
                 sd.target_orientation_ac.unpack_state_convert(
                     &state,
-                    |actor, previous, orientation: Orientation| {
-                        let current_value = if let Some(p) = sd.orientation_ac.get(&actor) {
-                            *p
-                        } else {
+                    |actor, previous, orientation: OrientationPacked| {
+                        let orientation = orientation.unpack();
+
+                        if sd.orientation_ac.get(&actor).is_none() {
                             sd.orientation_ac.insert(actor, orientation, sd.snapshot);
-                            orientation
-                        };
+                        }
 
-                        TargetQueue::from_previous(
+                        Some(TargetQueue::from_previous(
                             previous,
-                            current_value,
                             orientation,
                             current_time,
                             new_lss,
-                        )
+                        ))
                     },
                 );
                 sd.target_position_ac.unpack_state_convert(
                     &state,
-                    |actor, previous, position: Position| {
-                        let current_value = if let Some(p) = sd.position_ac.get(&actor) {
-                            *p
-                        } else {
-                            sd.position_ac.insert(actor, position, sd.snapshot);
-                            position
+                    |actor, previous, position: PositionPacked| {
+                        let baseline = sd.position_baseline_ac.get(&actor, baseline_snapshot);
+
+                        let Some(position) = position.unpack(baseline) else {
+                            warn!("no baseline position of {:?} to apply the delta", actor);
+                            return previous;
                         };
 
-                        TargetQueue::from_previous(
+                        sd.position_baseline_ac.insert(actor, new_lss, position);
+
+                        if sd.position_ac.get(&actor).is_none() {
+                            sd.position_ac.insert(actor, position, sd.snapshot);
+                        }
+
+                        Some(TargetQueue::from_previous(
                             previous,
-                            current_value,
                             position,
                             current_time,
                             new_lss,
-                        )
+                        ))
                     },
                 );
 
+                // Actors out of the player's view
+                for actor in actors_left {
+                    sd.class_ac.remove(&actor);
+                    sd.position_ac.remove(&actor);
+                    sd.position_baseline_ac.remove(&actor);
+                    sd.velocity_ac.remove(&actor);
+                    sd.orientation_ac.remove(&actor);
+                    sd.inventory_ac.remove(&actor);
+                    sd.health_ac.remove(&actor);
+                    sd.effect_ac.remove(&actor);
+                    sd.animation_state_ac.remove_actor(&actor);
+                    sd.target_position_ac.remove(&actor);
+                    sd.target_orientation_ac.remove(&actor);
+                    sd.model_acc.remove_override(&actor);
+                }
+
                 sd.actions_packer.confirm_snapshot(new_lcs);
+                sd.block_prediction_system.confirm(new_lcs);
 
                 let actions = match sd.actions_unpacker.unpack_actions(actions) {
                     Ok(m) => m,
@@ -128,6 +177,19 @@ impl NetworkInput<'_> {
                     );
                 }
 
+                if let Some(correction) = position_correction {
+                    sd.player_position_system.reconcile(
+                        correction,
+                        &sd.class_bc,
+                        &sd.collision_bcc,
+                        &sd.fluid_bcc,
+                        &sd.class_ac,
+                        &sd.collider_acc,
+                        &mut sd.position_ac,
+                        sd.snapshot,
+                    );
+                }
+
                 sd.last_client_snapshot = new_lcs;
                 sd.last_server_snapshot = new_lss;
             },
@@ -135,10 +197,20 @@ impl NetworkInput<'_> {
                 chunk,
                 block_classes,
             }) => {
+                // The server sends the whole chunk again instead of the heavy changes,
+                // the block entities of such chunk are kept up to date by their own changes
+                if sd.class_bc.get_chunk(&chunk).is_none() {
+                    // Block entities, if the chunk has any, arrive right after
+                    sd.block_entity_bc
+                        .insert_chunk(chunk, ChunkBlockEntities::default());
+                }
+
                 sd.class_bc.insert_chunk(chunk, block_classes);
+                sd.block_prediction_system.remove_chunk(&chunk);
                 sd.status_cc.insert(chunk, ChunkStatus::Active);
 
                 sd.sky_light_system.enqueue_chunk(chunk);
+                sd.block_light_system.enqueue_chunk(chunk);
             },
             ClientAccept::ChunkChanges(changes) => {
                 let Ok(mut chunk_decoder) = changes.decode_chunks() else {
@@ -162,14 +234,90 @@ impl NetworkInput<'_> {
                             return Transition::Menu;
                         };
 
+                        // The predicted block keeps its class until the action is confirmed
+                        if !sd
+                            .block_prediction_system
+                            .server_change(&chunk, block, block_class)
+                        {
+                            continue;
+                        }
+
                         if let Some(ref mut chunk_classes) = chunk_classes {
-                            *chunk_classes.get_mut(block) = block_class;
+                            let previous_class =
+                                mem::replace(chunk_classes.get_mut(block), block_class);
+
+                            // Flowing fluids change the blocks all the time
+                            let is_fluid_flow = sd.fluid_bcc.get(&block_class).is_some()
+                                || sd.fluid_bcc.get(&previous_class).is_some();
+
+                            if previous_class != block_class && !is_fluid_flow {
+                                // Placed blocks have a model, removed ones are replaced
+                                // with the ones without
+                                let (label, particle_class) =
+                                    if sd.model_bcc.get(&block_class).is_some() {
+                                        ("block_place", block_class)
+                                    } else {
+                                        ("block_break", previous_class)
+                                    };
+
+                                sd.particle_system.emit_block(
+                                    label,
+                                    chunk,
+                                    block,
+                                    particle_class,
+                                    &sd.model_bcc,
+                                    &sd.builder_bmc,
+                                );
+                            }
+
                             sd.sky_light_system.block_change(&chunk, block);
+                            sd.block_light_system.block_change(&chunk, block);
                             sd.block_render_system.block_change(&chunk, block);
                         }
                     }
                 }
             },
+            ClientAccept::ChunkBlockEntities {
+                chunk,
+                block_entities,
+            } => {
+                if sd.class_bc.get_chunk(&chunk).is_some() {
+                    sd.block_entity_bc.insert_chunk(chunk, block_entities);
+                }
+            },
+            ClientAccept::BlockEntityChanges(changes) => {
+                for (chunk, block, block_entity) in changes {
+                    sd.block_entity_bc.set(&chunk, block, block_entity);
+                }
+            },
+            ClientAccept::ChatMessage { sender, text } => {
+                sd.chat_system.add_message(sender, text);
+            },
+            ClientAccept::DimensionChange { dimension } => {
+                // Chunks of the previous dimension are dropped by the chunk presence system
+                // once the position correction arrives
+                let label = sd
+                    .dimension_kind_label_map
+                    .get_label(&dimension.kind)
+                    .unwrap_or("unknown");
+
+                sd.chat_system
+                    .add_notice(format!("entered dimension \"{}\"", label));
+            },
+            ClientAccept::ScriptDispatch { script, payload } => {
+                let Some(script) = sd.script_registry.get_script_by_label(&script) else {
+                    warn!("server dispatched unknown client script \"{}\"", script);
+                    return Transition::None;
+                };
+
+                let shared = sd.script_shared_data();
+
+                sd.script_registry
+                    .run_script(&script, shared, DispatchInput { payload: &payload });
+            },
+            ClientAccept::WorldTime { time, day_length } => {
+                sd.sky_system.set_time(time, day_length);
+            },
         }
 
         Transition::None
@@ -1,11 +1,32 @@
 use super::Transition;
 use crate::{
-    scene::game::data::GameSharedData,
-    system::render::Renderer,
+    scene::game::data::{
+        GameSharedData,
+        ADMIN_MESSAGE_PRIORITY,
+        GAME_MESSAGE_PRIORITY,
+    },
+    system::{
+        inventory::InventoryRequest,
+        profiler::Section,
+        render::Renderer,
+    },
     window::Frame,
 };
 use rayon::prelude::*;
 use std::time::Instant;
+use voxbrix_common::{
+    entity::block::BLOCKS_IN_CHUNK_EDGE_F32,
+    messages::{
+        server::{
+            AdminCommand,
+            ServerAccept,
+        },
+        ADMIN_CHANNEL,
+    },
+};
+
+/// Distance from the player to the third-person camera, if there are no blocks in between.
+const THIRD_PERSON_CAMERA_DISTANCE: f32 = 4.0;
 
 pub struct Process<'a> {
     pub shared_data: &'a mut GameSharedData,
@@ -19,10 +40,13 @@ impl Process<'_> {
             mut frame,
         } = self;
 
-        if sd.inventory_open && !sd.cursor_visible {
+        // Interface receives input only while the cursor is visible
+        let interface_open = sd.inventory_open || sd.chat_system.is_input_open();
+
+        if interface_open && !sd.cursor_visible {
             sd.render_system.cursor_visibility(true);
             sd.cursor_visible = true;
-        } else if !sd.inventory_open && sd.cursor_visible {
+        } else if !interface_open && sd.cursor_visible {
             sd.render_system.cursor_visibility(false);
             sd.cursor_visible = false;
         }
@@ -31,6 +55,8 @@ impl Process<'_> {
         let elapsed = now.saturating_duration_since(sd.last_process_time);
         sd.last_process_time = now;
 
+        let started = Instant::now();
+
         sd.chunk_presence_system.process(
             sd.player_chunk_view_radius,
             &sd.player_actor,
@@ -38,33 +64,59 @@ impl Process<'_> {
             &mut sd.status_cc,
             |chunk| {
                 sd.class_bc.remove_chunk(&chunk);
+                sd.block_entity_bc.remove_chunk(&chunk);
                 sd.sky_light_bc.remove_chunk(&chunk);
+                sd.block_light_bc.remove_chunk(&chunk);
                 sd.block_render_system.remove_chunk(&chunk);
                 sd.sky_light_system.remove_chunk(&chunk);
+                sd.block_light_system.remove_chunk(&chunk);
+                sd.block_prediction_system.remove_chunk(&chunk);
             },
         );
 
-        sd.player_position_system.process(
-            elapsed,
-            &sd.class_bc,
-            &sd.collision_bcc,
-            &mut sd.position_ac,
-            &sd.velocity_ac,
-            sd.snapshot,
-        );
-        sd.direct_control_system.process(
-            elapsed,
-            &mut sd.velocity_ac,
-            &mut sd.orientation_ac,
-            sd.snapshot,
-        );
-        sd.movement_interpolation_system.process(
-            &mut sd.target_position_ac,
-            &mut sd.target_orientation_ac,
-            &mut sd.position_ac,
-            &mut sd.orientation_ac,
-            sd.snapshot,
-        );
+        sd.block_prediction_system
+            .process(&mut sd.class_bc, |chunk, block| {
+                sd.sky_light_system.block_change(&chunk, block);
+                sd.block_light_system.block_change(&chunk, block);
+                sd.block_render_system.block_change(&chunk, block);
+            });
+
+        sd.profiler
+            .record(Section::ChunkPresence, started.elapsed());
+        let started = Instant::now();
+
+        // The world is frozen until the lost connection is restored
+        if !sd.reconnecting {
+            sd.player_position_system.process(
+                elapsed,
+                &sd.class_bc,
+                &sd.collision_bcc,
+                &sd.fluid_bcc,
+                &sd.class_ac,
+                &sd.collider_acc,
+                &mut sd.position_ac,
+                &sd.velocity_ac,
+                sd.snapshot,
+            );
+            if let Some(position) = sd.position_ac.get(&sd.player_actor) {
+                sd.block_render_system.set_view_center(position.chunk);
+            }
+
+            sd.direct_control_system.process(
+                elapsed,
+                &sd.settings,
+                &mut sd.velocity_ac,
+                &mut sd.orientation_ac,
+                sd.snapshot,
+            );
+            sd.movement_interpolation_system.process(
+                &mut sd.target_position_ac,
+                &mut sd.target_orientation_ac,
+                &mut sd.position_ac,
+                &mut sd.orientation_ac,
+                sd.snapshot,
+            );
+        }
 
         let target = sd.player_position_system.get_target_block(
             &sd.position_ac,
@@ -83,19 +135,156 @@ impl Process<'_> {
 
         sd.block_render_system.build_target_highlight(target);
 
+        sd.profiler.record(Section::Movement, started.elapsed());
+        let started = Instant::now();
+
+        if let Some(notice) = sd.screenshot_system.take_notice() {
+            sd.chat_system.add_notice(notice);
+        }
+
         sd.interface_system.start(&mut frame);
 
+        let mut inventory_request = None;
+
         sd.interface_system.add_interface(|ctx| {
-            egui::Window::new("Inventory")
-                .open(&mut sd.inventory_open)
-                .show(ctx, |ui| {
-                    ui.label("Hello World!");
-                });
+            inventory_request = sd.inventory_system.interface(
+                ctx,
+                &mut sd.inventory_open,
+                sd.inventory_ac.get(&sd.player_actor),
+                &sd.item_class_label_map,
+                &sd.recipe_registry,
+            );
+        });
+
+        if let Some(request) = inventory_request {
+            let message = match request {
+                InventoryRequest::MoveItem { from, to } => ServerAccept::MoveItem { from, to },
+                InventoryRequest::Craft(recipe) => ServerAccept::Craft { recipe },
+            };
+
+            let packed = sd.packer.pack_to_vec(&message);
+
+            sd.reliable_queue.push((GAME_MESSAGE_PRIORITY, (0, packed)));
+        }
+
+        sd.interface_system.add_interface(|ctx| {
+            sd.health_bar_system
+                .interface(ctx, sd.health_ac.get(&sd.player_actor));
+        });
+
+        sd.interface_system.add_interface(|ctx| {
+            sd.effect_hud_system.interface(
+                ctx,
+                sd.effect_ac.get(&sd.player_actor),
+                &sd.effect_label_map,
+                sd.last_server_snapshot,
+                sd.server_process_interval,
+            );
+        });
+
+        sd.interface_system.add_interface(|ctx| {
+            sd.script_hud_system.interface(ctx);
+        });
+
+        if sd.debug_overlay_open {
+            sd.interface_system.add_interface(|ctx| {
+                sd.debug_overlay_system.interface(
+                    ctx,
+                    &sd.profiler,
+                    sd.block_render_system.queue_len(),
+                    &sd.connection_stats,
+                    sd.position_ac.get(&sd.player_actor),
+                    &sd.dimension_kind_label_map,
+                );
+            });
+        }
+
+        let mut chat_message = None;
+
+        sd.interface_system.add_interface(|ctx| {
+            chat_message = sd.chat_system.interface(ctx);
         });
 
+        if let Some((scope, text)) = chat_message {
+            // Lines starting with a slash are admin commands
+            if let Some(command) = text.strip_prefix('/') {
+                let packed = sd.packer.pack_to_vec(&AdminCommand {
+                    command: command.to_owned(),
+                });
+
+                sd.reliable_queue
+                    .push((ADMIN_MESSAGE_PRIORITY, (ADMIN_CHANNEL, packed)));
+            } else {
+                let packed = sd
+                    .packer
+                    .pack_to_vec(&ServerAccept::ChatMessage { scope, text });
+
+                sd.reliable_queue.push((GAME_MESSAGE_PRIORITY, (0, packed)));
+            }
+        }
+
+        sd.profiler.record(Section::Interface, started.elapsed());
+        let started = Instant::now();
+
+        let camera_distance = if sd.third_person {
+            sd.player_position_system.get_camera_distance(
+                &sd.position_ac,
+                &sd.orientation_ac,
+                THIRD_PERSON_CAMERA_DISTANCE,
+                |chunk, block| {
+                    sd.class_bc
+                        .get_chunk(&chunk)
+                        .map(|blocks| {
+                            let class = blocks.get(block);
+                            sd.collision_bcc.get(class).is_some()
+                        })
+                        .unwrap_or(false)
+                },
+            )
+        } else {
+            0.0
+        };
+
+        sd.render_system
+            .set_field_of_view(sd.settings.field_of_view.to_radians());
+        sd.render_system.set_camera_distance(camera_distance);
+        sd.render_system.set_sky(
+            sd.sky_system
+                .parameters(sd.player_chunk_view_radius as f32 * BLOCKS_IN_CHUNK_EDGE_F32),
+        );
         sd.render_system.update(&sd.position_ac, &sd.orientation_ac);
-        sd.actor_render_system.update(
+
+        sd.view_model_system.update(
             sd.player_actor,
+            // No hand in the third-person view
+            sd.held_block_class.filter(|_| !sd.third_person),
+            &sd.position_ac,
+            &sd.orientation_ac,
+            &sd.model_bcc,
+            &sd.builder_bmc,
+            &sd.sky_light_bc,
+            &sd.block_light_bc,
+        );
+        sd.block_render_system
+            .set_view_model(sd.view_model_system.quads());
+
+        sd.particle_system.process(
+            elapsed,
+            sd.player_actor,
+            &sd.position_ac,
+            &sd.orientation_ac,
+            &sd.class_bc,
+            &sd.collision_bcc,
+            &sd.sky_light_bc,
+            &sd.block_light_bc,
+            &sd.model_bcc,
+            &sd.builder_bmc,
+        );
+        sd.block_render_system
+            .set_particles(sd.particle_system.quads());
+
+        sd.actor_render_system.update(
+            (!sd.third_person).then_some(sd.player_actor),
             &sd.class_ac,
             &sd.position_ac,
             &sd.velocity_ac,
@@ -106,9 +295,16 @@ impl Process<'_> {
             &mut sd.animation_state_ac,
         );
 
+        sd.profiler.record(Section::Models, started.elapsed());
+        let started = Instant::now();
+
+        sd.screenshot_system.capture(&mut frame);
         sd.render_system.start_render(frame);
 
-        let render_systems: [&mut (dyn FnMut(Renderer) + Send); 3] = [
+        let render_systems: [&mut (dyn FnMut(Renderer) + Send); 4] = [
+            &mut |renderer| {
+                sd.sky_render_system.render(renderer);
+            },
             &mut |renderer| {
                 sd.block_render_system.render(renderer);
             },
@@ -121,7 +317,7 @@ impl Process<'_> {
         ];
 
         sd.render_system
-            .get_renderers::<3>()
+            .get_renderers::<4>()
             .into_iter()
             .zip(render_systems.into_iter())
             .par_bridge()
@@ -129,6 +325,9 @@ impl Process<'_> {
 
         sd.render_system.finish_render();
 
+        sd.profiler.record(Section::Render, started.elapsed());
+        sd.profiler.finish_frame(elapsed);
+
         Transition::None
     }
 }
@@ -0,0 +1,128 @@
+//! Restores the lost connection to the server. The session is resumed with the protocol
+//! resumption, so the server keeps the player in the world for its grace period and the client
+//! keeps the world it has, with no login or asset synchronization. The game is frozen meanwhile.
+use crate::{
+    scene::{
+        game::{
+            Event,
+            Session,
+        },
+        menu,
+    },
+    CONNECTION_TIMEOUT,
+};
+use flume::Sender;
+use log::warn;
+use std::time::Duration;
+use tokio::time;
+use voxbrix_common::{
+    async_ext::{
+        self,
+        ScopeTask,
+    },
+    messages::client::{
+        InitData,
+        ResumeResult,
+    },
+    pack::Packer,
+};
+use voxbrix_protocol::{
+    client::{
+        Connection,
+        Error as ClientError,
+    },
+    HandshakeError,
+};
+
+/// Delay before the first attempt, doubled after every failed one.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+/// About a minute in total, the default grace period of the server.
+const MAX_ATTEMPTS: u32 = 10;
+
+/// Connection the session continues with.
+pub struct ResumedSession {
+    pub connection: Connection,
+    pub init_data: InitData,
+}
+
+enum Failure {
+    /// The server may be back later.
+    Retry,
+    /// The session cannot be resumed.
+    Fatal(&'static str),
+}
+
+/// Retries to resume the session until it succeeds or fails for good, reporting the attempts
+/// with `Event::Reconnecting` and the outcome with `Event::Reconnected`.
+/// The attempts stop when the task is dropped.
+pub fn start(session: &Session, event_tx: Sender<Event>) -> ScopeTask<()> {
+    let Session { server, resumption } = session.clone();
+
+    async_ext::spawn_scoped(async move {
+        let mut backoff = INITIAL_BACKOFF;
+
+        for attempt in 1 ..= MAX_ATTEMPTS {
+            time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+
+            if event_tx.send(Event::Reconnecting(attempt)).is_err() {
+                return;
+            }
+
+            let result = match resume(&Session {
+                server,
+                resumption: resumption.clone(),
+            })
+            .await
+            {
+                Ok(resumed) => Ok(resumed),
+                Err(Failure::Retry) => continue,
+                Err(Failure::Fatal(message)) => Err(message),
+            };
+
+            let _ = event_tx.send(Event::Reconnected(result));
+
+            return;
+        }
+
+        let _ = event_tx.send(Event::Reconnected(Err("Server is unreachable")));
+    })
+}
+
+async fn resume(session: &Session) -> Result<ResumedSession, Failure> {
+    let mut connection = time::timeout(CONNECTION_TIMEOUT, async {
+        menu::client_parameters()
+            .bind(menu::local_address(session.server))
+            .await?
+            .resume(session.server, &session.resumption)
+            .await
+    })
+    .await
+    .map_err(|_| Failure::Retry)?
+    .map_err(|err| {
+        match err {
+            ClientError::Handshake(HandshakeError::UnknownSession) => {
+                Failure::Fatal("Server does not know the session")
+            },
+            ClientError::Handshake(_) => Failure::Fatal("Server version is incompatible"),
+            err => {
+                warn!("unable to resume the connection: {:?}", err);
+                Failure::Retry
+            },
+        }
+    })?;
+
+    let mut packer = Packer::new();
+
+    match menu::recv::<ResumeResult>(&mut connection.receiver, &mut packer).await {
+        Ok(ResumeResult::Success(init_data)) => {
+            Ok(ResumedSession {
+                connection,
+                init_data,
+            })
+        },
+        Ok(ResumeResult::Failure) => Err(Failure::Fatal("Player has left the world")),
+        Err(_) => Err(Failure::Retry),
+    }
+}
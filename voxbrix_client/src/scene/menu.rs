@@ -1,8 +1,33 @@
 use crate::{
     scene::{
-        game::GameSceneParameters,
+        game::{
+            GameSceneParameters,
+            Session,
+        },
         SceneSwitch,
     },
+    server_list::{
+        ServerList,
+        SERVER_LIST_PATH,
+    },
+    settings::{
+        Settings,
+        SETTINGS_PATH,
+    },
+    system::{
+        asset_sync::{
+            AssetSyncSystem,
+            ServerAssets,
+        },
+        server_browser::{
+            ServerBrowserRequest,
+            ServerBrowserSystem,
+        },
+        settings_menu::{
+            SettingsMenuSystem,
+            SettingsRequest,
+        },
+    },
     window::{
         Frame,
         InputEvent,
@@ -27,8 +52,20 @@ use k256::ecdsa::{
     SigningKey,
     VerifyingKey,
 };
-use log::warn;
+use log::{
+    error,
+    warn,
+};
 use serde::Deserialize;
+use std::{
+    net::{
+        Ipv4Addr,
+        Ipv6Addr,
+        SocketAddr,
+    },
+    path::Path,
+    time::Duration,
+};
 use tokio::{
     task::{
         self,
@@ -40,12 +77,16 @@ use voxbrix_common::{
     async_ext::StreamExt as _,
     messages::{
         client::{
+            AssetFile,
+            AssetManifest,
             InitData,
             InitResponse,
             LoginResult,
             RegisterResult,
         },
+        features,
         server::{
+            AssetRequest,
             InitRequest,
             LoginRequest,
             RegisterRequest,
@@ -56,19 +97,25 @@ use voxbrix_common::{
         Packer,
     },
 };
-use voxbrix_protocol::client::{
-    Client,
-    Connection,
-    Receiver,
-    Sender,
+use voxbrix_protocol::{
+    client::{
+        ClientParameters,
+        Connection,
+        Error as ClientError,
+        Receiver,
+        Sender,
+    },
+    Features,
+    HandshakeError,
 };
 
 pub struct MenuSceneParameters {
     pub window: Window,
+    pub settings: Settings,
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
-enum ActionType {
+pub enum ActionType {
     Login,
     Registration,
 }
@@ -85,7 +132,11 @@ pub struct MenuScene {
 impl MenuScene {
     pub async fn run(self) -> Result<SceneSwitch> {
         let Self {
-            parameters: MenuSceneParameters { mut window },
+            parameters:
+                MenuSceneParameters {
+                    mut window,
+                    mut settings,
+                },
         } = self;
 
         window.cursor_visible = true;
@@ -100,6 +151,16 @@ impl MenuScene {
 
         let mut error_message = String::new();
 
+        let server_list = task::spawn_blocking(|| ServerList::load(Path::new(SERVER_LIST_PATH)))
+            .await
+            .unwrap()
+            .unwrap_or_else(|err| {
+                error!("{:#}, starting empty", err);
+                ServerList::default()
+            });
+
+        let mut server_browser = ServerBrowserSystem::new(server_list);
+
         let mut prev_form = Form {
             server_address: Default::default(),
             username: Default::default(),
@@ -110,6 +171,8 @@ impl MenuScene {
 
         let mut is_registration = false;
 
+        let mut settings_menu: Option<SettingsMenuSystem> = None;
+
         let mut form = prev_form.clone();
 
         let mut connect_task: Option<JoinHandle<Result<_, String>>> = None;
@@ -123,8 +186,18 @@ impl MenuScene {
                 Event::Process(mut frame) => {
                     let input = frame.take_ui_input();
 
+                    let mut settings_request = None;
+                    let mut browser_request = None;
+
+                    server_browser.process();
+
                     let full_output = window.ui_context().run(input, |ctx| {
                         CentralPanel::default().show(&ctx, |ui| {
+                            if let Some(settings_menu) = settings_menu.as_mut() {
+                                settings_request = settings_menu.interface(ui);
+                                return;
+                            }
+
                             ui.label("Voxbrix");
                             ui.label(&error_message);
                             ui.label("Server socket address:");
@@ -152,9 +225,52 @@ impl MenuScene {
                             }
                             ui.add_space(16.0);
                             ui.checkbox(&mut is_registration, "Registration");
+                            ui.add_space(16.0);
+                            if ui.button("Settings").clicked() {
+                                settings_menu = Some(SettingsMenuSystem::new(&settings));
+                            }
+                            ui.add_space(16.0);
+                            browser_request = server_browser.interface(ui, &form.server_address);
                         });
                     });
 
+                    match browser_request {
+                        Some(ServerBrowserRequest::Select(address)) => {
+                            form.server_address = address;
+                        },
+                        Some(ServerBrowserRequest::Save(server_list)) => {
+                            task::spawn_blocking(move || {
+                                if let Err(err) = server_list.save(Path::new(SERVER_LIST_PATH)) {
+                                    error!("{:#}", err);
+                                }
+                            });
+                        },
+                        None => {},
+                    }
+
+                    match settings_request {
+                        Some(SettingsRequest::Save(new_settings)) => {
+                            window
+                                .ui_context()
+                                .set_pixels_per_point(new_settings.pixels_per_point);
+
+                            settings = new_settings;
+                            settings_menu = None;
+
+                            let settings = settings.clone();
+
+                            task::spawn_blocking(move || {
+                                if let Err(err) = settings.save(Path::new(SETTINGS_PATH)) {
+                                    error!("{:#}", err);
+                                }
+                            });
+                        },
+                        Some(SettingsRequest::Back) => {
+                            settings_menu = None;
+                        },
+                        None => {},
+                    }
+
                     let mut encoder =
                         window
                             .device()
@@ -193,18 +309,25 @@ impl MenuScene {
                     if let Some(ct) = connect_task.as_ref() {
                         if ct.is_finished() {
                             match connect_task.take().unwrap().await.unwrap() {
-                                Ok((tx, rx, init_data)) => {
+                                Ok((tx, rx, session, init_data, assets)) => {
                                     let InitData {
                                         actor,
                                         player_chunk_view_radius,
+                                        process_interval_ms,
                                     } = init_data;
 
                                     return Ok(SceneSwitch::Game {
                                         parameters: GameSceneParameters {
                                             window,
                                             connection: (tx, rx),
+                                            session,
                                             player_actor: actor,
                                             player_chunk_view_radius,
+                                            server_process_interval: Duration::from_millis(
+                                                process_interval_ms,
+                                            ),
+                                            assets,
+                                            settings,
                                         },
                                     });
                                 },
@@ -216,6 +339,10 @@ impl MenuScene {
                     }
                 },
                 Event::Input(event) => {
+                    if let Some(settings_menu) = settings_menu.as_mut() {
+                        settings_menu.process_input(&event);
+                    }
+
                     if let InputEvent::WindowEvent(event) = event {
                         match event {
                             WindowEvent::CloseRequested => {
@@ -252,20 +379,7 @@ where
                     .await
                     .map_err(|_| "Unable to send initialization request")
             },
-            async {
-                loop {
-                    let (_channel, bytes) = rx
-                        .recv()
-                        .await
-                        .map_err(|_| "Unable to get initialization response")?;
-
-                    if let Ok(res) = packer.unpack::<R>(bytes) {
-                        return Ok(res);
-                    } else {
-                        warn!("unknown message, skipping");
-                    }
-                }
-            },
+            async { recv_message::<R>(rx, packer).await },
         )
         .await
     })
@@ -277,6 +391,34 @@ where
     recv_res
 }
 
+pub async fn recv<R>(rx: &mut Receiver, packer: &mut Packer) -> Result<R, &'static str>
+where
+    for<'a> R: Pack + Deserialize<'a>,
+{
+    time::timeout(CONNECTION_TIMEOUT, recv_message::<R>(rx, packer))
+        .await
+        .map_err(|_| "Connection timeout")?
+}
+
+async fn recv_message<R>(rx: &mut Receiver, packer: &mut Packer) -> Result<R, &'static str>
+where
+    for<'a> R: Pack + Deserialize<'a>,
+{
+    loop {
+        let (_channel, bytes) = match rx.recv().await {
+            Ok(message) => message,
+            Err(ClientError::ConnectionMigrated { .. }) => continue,
+            Err(_) => return Err("Unable to get initialization response"),
+        };
+
+        if let Ok(res) = packer.unpack::<R>(bytes) {
+            return Ok(res);
+        } else {
+            warn!("unknown message, skipping");
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 struct Form {
     server_address: String,
@@ -298,119 +440,230 @@ impl PartialEq<Form> for Form {
 impl Eq for Form {}
 
 impl Form {
-    pub async fn connect(&self) -> Result<(Sender, Receiver, InitData), &'static str> {
-        let mut tx_buffer = Vec::new();
-        let mut packer = Packer::new();
-        let socket: std::net::SocketAddr = ([0, 0, 0, 0], 0).into();
-        let server: std::net::SocketAddr = self
+    pub async fn connect(
+        &self,
+    ) -> Result<(Sender, Receiver, Session, InitData, ServerAssets), &'static str> {
+        if let ActionType::Registration = self.action {
+            if self.password != self.password_confirmation {
+                return Err("Password and self.password confirmation do not match");
+            }
+        }
+
+        let server: SocketAddr = self
             .server_address
             .parse()
             .map_err(|_| "Incorrect server socket address format")?;
 
+        let mut connection = connect(server).await?;
+
+        let manifest = authenticate(
+            &mut connection,
+            self.action.clone(),
+            &self.username,
+            &self.password,
+        )
+        .await?;
+
         let Connection {
-            self_key,
-            peer_key,
             mut sender,
             mut receiver,
-        } = time::timeout(CONNECTION_TIMEOUT, async {
-            Client::bind(socket)
-                .await
-                .map_err(|_| "Unable to bind socket")?
-                .connect(server)
-                .await
-                .map_err(|_| "Connection error")
-        })
-        .await
-        .map_err(|_| "Connection timeout")??;
+            resumption,
+            ..
+        } = connection;
+
+        let session = Session { server, resumption };
+
+        let mut tx_buffer = Vec::new();
+        let mut packer = Packer::new();
 
         let tx = &mut sender;
         let rx = &mut receiver;
 
-        match self.action {
-            ActionType::Login => packer.pack(&InitRequest::Login, &mut tx_buffer),
-            ActionType::Registration => packer.pack(&InitRequest::Register, &mut tx_buffer),
-        };
+        let (asset_sync_system, missing_files) = task::spawn_blocking(move || {
+            let asset_sync_system = AssetSyncSystem::new(manifest)?;
+            asset_sync_system.remove_stale_files()?;
+            let missing_files = asset_sync_system.missing_files();
 
-        let InitResponse {
-            public_key: server_key,
-            key_signature,
-        } = send_recv::<InitResponse>(&tx_buffer, tx, rx, &mut packer).await?;
+            Ok::<_, anyhow::Error>((asset_sync_system, missing_files))
+        })
+        .await
+        .unwrap()
+        .map_err(|err| {
+            warn!("unable to prepare asset cache: {:?}", err);
+            "Unable to prepare asset cache"
+        })?;
+
+        packer.pack(
+            &AssetRequest {
+                files: missing_files.clone(),
+            },
+            &mut tx_buffer,
+        );
 
-        let server_key = VerifyingKey::from_sec1_bytes(&server_key)
-            .map_err(|_| "Server provided incorrect public key")?;
+        let mut files = Vec::with_capacity(missing_files.len());
 
-        let key_signature = Signature::from_bytes((&key_signature).into())
-            .map_err(|_| "Server provided incorrect signature")?;
+        let init_data = if missing_files.is_empty() {
+            send_recv::<InitData>(&tx_buffer, tx, rx, &mut packer).await?
+        } else {
+            files.push(send_recv::<AssetFile>(&tx_buffer, tx, rx, &mut packer).await?);
 
-        server_key
-            .verify(&peer_key, &key_signature)
-            .map_err(|_| "Server signature does not match the public key provided")?;
+            while files.len() < missing_files.len() {
+                files.push(recv::<AssetFile>(rx, &mut packer).await?);
+            }
 
-        if let ActionType::Registration = self.action {
-            if self.password != self.password_confirmation {
-                return Err("Password and self.password confirmation do not match");
+            recv::<InitData>(rx, &mut packer).await?
+        };
+
+        let assets = task::spawn_blocking(move || {
+            for file in files {
+                asset_sync_system.store_file(file)?;
             }
-        }
 
-        let mut signing_key = [0; 32];
-        Argon2::default()
-            .hash_password_into(
-                self.password.as_bytes(),
-                self.username.as_bytes(),
-                &mut signing_key,
-            )
-            .unwrap();
-
-        let signing_key =
-            SigningKey::from_bytes((&signing_key).into()).expect("signing key derive");
-
-        let init_data = match self.action {
-            ActionType::Login => {
-                let signature: Signature = signing_key.sign(&self_key);
-                packer.pack(
-                    &LoginRequest {
-                        username: self.username.clone(),
-                        key_signature: signature.to_bytes().into(),
-                    },
-                    &mut tx_buffer,
-                );
+            Ok::<_, anyhow::Error>(asset_sync_system.finish())
+        })
+        .await
+        .unwrap()
+        .map_err(|err| {
+            warn!("unable to store server assets: {:?}", err);
+            "Unable to store server assets"
+        })?;
 
-                let response = send_recv::<LoginResult>(&tx_buffer, tx, rx, &mut packer).await?;
+        Ok((sender, receiver, session, init_data, assets))
+    }
+}
 
-                match response {
-                    LoginResult::Success(data) => data,
-                    LoginResult::Failure(_) => {
-                        // TODO: display actual error
-                        return Err("Incorrect login credentials");
-                    },
-                }
-            },
-            ActionType::Registration => {
-                packer.pack(
-                    &RegisterRequest {
-                        username: self.username.clone(),
-                        public_key: signing_key
-                            .verifying_key()
-                            .to_encoded_point(true)
-                            .as_bytes()
-                            .try_into()
-                            .unwrap(),
-                    },
-                    &mut tx_buffer,
-                );
+/// Any local port of the same address family as the server.
+pub fn local_address(server: SocketAddr) -> SocketAddr {
+    if server.is_ipv6() {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    }
+}
 
-                let response = send_recv::<RegisterResult>(&tx_buffer, tx, rx, &mut packer).await?;
+pub fn client_parameters() -> ClientParameters {
+    ClientParameters {
+        features: Features(features::SUPPORTED),
+        required_features: Features(features::REQUIRED),
+        ..Default::default()
+    }
+}
 
-                match response {
-                    RegisterResult::Success(data) => data,
-                    RegisterResult::Failure(_) => {
-                        // TODO: display actual error
-                        return Err("Username already taken");
+/// Opens the connection to the server.
+pub async fn connect(server: SocketAddr) -> Result<Connection, &'static str> {
+    time::timeout(CONNECTION_TIMEOUT, async {
+        client_parameters()
+            .bind(local_address(server))
+            .await
+            .map_err(|_| "Unable to bind socket")?
+            .connect(server)
+            .await
+            .map_err(|err| {
+                match err {
+                    ClientError::Handshake(HandshakeError::VersionMismatch { .. }) => {
+                        "Server uses incompatible protocol version"
                     },
+                    ClientError::Handshake(HandshakeError::MissingFeatures(_)) => {
+                        "Server version is incompatible"
+                    },
+                    _ => "Connection error",
                 }
-            },
-        };
+            })
+    })
+    .await
+    .map_err(|_| "Connection timeout")?
+}
+
+/// Logs the player in or registers them over the new connection.
+/// Returns the asset manifest the server sends on success.
+pub async fn authenticate(
+    connection: &mut Connection,
+    action: ActionType,
+    username: &str,
+    password: &str,
+) -> Result<AssetManifest, &'static str> {
+    let mut tx_buffer = Vec::new();
+    let mut packer = Packer::new();
+
+    let Connection {
+        self_key,
+        peer_key,
+        sender: tx,
+        receiver: rx,
+        ..
+    } = connection;
+
+    match action {
+        ActionType::Login => packer.pack(&InitRequest::Login, &mut tx_buffer),
+        ActionType::Registration => packer.pack(&InitRequest::Register, &mut tx_buffer),
+    };
+
+    let InitResponse {
+        public_key: server_key,
+        key_signature,
+    } = send_recv::<InitResponse>(&tx_buffer, tx, rx, &mut packer).await?;
+
+    let server_key = VerifyingKey::from_sec1_bytes(&server_key)
+        .map_err(|_| "Server provided incorrect public key")?;
+
+    let key_signature = Signature::from_bytes((&key_signature).into())
+        .map_err(|_| "Server provided incorrect signature")?;
+
+    server_key
+        .verify(&peer_key[..], &key_signature)
+        .map_err(|_| "Server signature does not match the public key provided")?;
+
+    let mut signing_key = [0; 32];
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), username.as_bytes(), &mut signing_key)
+        .unwrap();
+
+    let signing_key = SigningKey::from_bytes((&signing_key).into()).expect("signing key derive");
+
+    match action {
+        ActionType::Login => {
+            let signature: Signature = signing_key.sign(&self_key[..]);
+            packer.pack(
+                &LoginRequest {
+                    username: username.to_owned(),
+                    key_signature: signature.to_bytes().into(),
+                },
+                &mut tx_buffer,
+            );
+
+            let response = send_recv::<LoginResult>(&tx_buffer, tx, rx, &mut packer).await?;
+
+            match response {
+                LoginResult::Success(manifest) => Ok(manifest),
+                LoginResult::Failure(_) => {
+                    // TODO: display actual error
+                    Err("Incorrect login credentials")
+                },
+            }
+        },
+        ActionType::Registration => {
+            packer.pack(
+                &RegisterRequest {
+                    username: username.to_owned(),
+                    public_key: signing_key
+                        .verifying_key()
+                        .to_encoded_point(true)
+                        .as_bytes()
+                        .try_into()
+                        .unwrap(),
+                },
+                &mut tx_buffer,
+            );
+
+            let response = send_recv::<RegisterResult>(&tx_buffer, tx, rx, &mut packer).await?;
 
-        Ok((sender, receiver, init_data))
+            match response {
+                RegisterResult::Success(manifest) => Ok(manifest),
+                RegisterResult::Failure(_) => {
+                    // TODO: display actual error
+                    Err("Username already taken")
+                },
+            }
+        },
     }
 }
@@ -0,0 +1,58 @@
+//! Servers saved by the player, loaded from the `servers.json` file when the menu opens
+//! and saved from its server list.
+use anyhow::{
+    Context,
+    Result,
+};
+use log::info;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    fs,
+    io::ErrorKind as IoErrorKind,
+    path::Path,
+};
+
+pub const SERVER_LIST_PATH: &str = "servers.json";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SavedServer {
+    pub name: String,
+    /// Socket address, e.g. `127.0.0.1:12000`.
+    pub address: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerList {
+    pub servers: Vec<SavedServer>,
+}
+
+impl ServerList {
+    /// Blocking IO, must not be used directly in async
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = match fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == IoErrorKind::NotFound => {
+                info!("server list {:?} not found, starting empty", path);
+                return Ok(Self::default());
+            },
+            Err(err) => {
+                return Err(err).with_context(|| format!("unable to read server list {:?}", path));
+            },
+        };
+
+        serde_json::from_str(&data)
+            .with_context(|| format!("unable to parse server list {:?}", path))
+    }
+
+    /// Blocking IO, must not be used directly in async
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_string_pretty(self).expect("server list serialization");
+
+        fs::write(path, data).with_context(|| format!("unable to write server list {:?}", path))
+    }
+}
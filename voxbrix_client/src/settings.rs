@@ -0,0 +1,92 @@
+//! Client settings, loaded from the `settings.json` file at startup
+//! and saved from the settings screen of the menu.
+//! Any of the fields could be omitted, the default value is used in that case.
+use anyhow::{
+    Context,
+    Result,
+};
+#[cfg(feature = "gamepad")]
+use gamepad::GamepadSettings;
+use key_bindings::KeyBindings;
+use log::info;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    fs,
+    io::ErrorKind as IoErrorKind,
+    path::Path,
+};
+
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+pub mod key_bindings;
+
+pub const SETTINGS_PATH: &str = "settings.json";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Scale of the interface.
+    pub pixels_per_point: f32,
+    /// Vertical field of view, in degrees.
+    pub field_of_view: f32,
+    pub mouse_sensitivity: f32,
+    /// Radius of chunks around the player that are kept and rendered.
+    /// The server view radius is used if it is smaller.
+    pub view_radius: i32,
+    pub greedy_meshing: bool,
+    /// Limit of the particles alive at once.
+    pub max_particles: usize,
+    pub key_bindings: KeyBindings,
+    #[cfg(feature = "gamepad")]
+    pub gamepad: GamepadSettings,
+    /// Kept as is for the builds with the gamepad support.
+    #[cfg(not(feature = "gamepad"))]
+    #[serde(skip_serializing_if = "serde_json::Value::is_null")]
+    gamepad: serde_json::Value,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            pixels_per_point: 1.5,
+            field_of_view: 70.0,
+            mouse_sensitivity: 0.4,
+            view_radius: 32,
+            greedy_meshing: true,
+            max_particles: 2048,
+            key_bindings: KeyBindings::default(),
+            #[cfg(feature = "gamepad")]
+            gamepad: GamepadSettings::default(),
+            #[cfg(not(feature = "gamepad"))]
+            gamepad: serde_json::Value::Null,
+        }
+    }
+}
+
+impl Settings {
+    /// Blocking IO, must not be used directly in async
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = match fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == IoErrorKind::NotFound => {
+                info!("settings {:?} not found, using defaults", path);
+                return Ok(Self::default());
+            },
+            Err(err) => {
+                return Err(err).with_context(|| format!("unable to read settings {:?}", path));
+            },
+        };
+
+        serde_json::from_str(&data).with_context(|| format!("unable to parse settings {:?}", path))
+    }
+
+    /// Blocking IO, must not be used directly in async
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_string_pretty(self).expect("settings serialization");
+
+        fs::write(path, data).with_context(|| format!("unable to write settings {:?}", path))
+    }
+}
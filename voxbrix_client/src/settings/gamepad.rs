@@ -0,0 +1,131 @@
+use crate::{
+    settings::key_bindings::InputAction,
+    window::GamepadButton,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct GamepadSettings {
+    /// Stick deflection below this is ignored, from 0 to 1.
+    pub dead_zone: f32,
+    /// Camera rotation speed at the full right stick deflection.
+    pub camera_sensitivity: f32,
+    pub bindings: GamepadBindings,
+}
+
+impl Default for GamepadSettings {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.15,
+            camera_sensitivity: 3.0,
+            bindings: GamepadBindings::default(),
+        }
+    }
+}
+
+impl GamepadSettings {
+    /// Applies the radial dead zone to the stick position,
+    /// the rest of the range is rescaled to start from zero.
+    pub fn apply_dead_zone(&self, stick: [f32; 2]) -> [f32; 2] {
+        let [x, y] = stick;
+        let length = (x * x + y * y).sqrt();
+
+        if length <= self.dead_zone || self.dead_zone >= 1.0 {
+            return [0.0, 0.0];
+        }
+
+        let scale = ((length - self.dead_zone) / (1.0 - self.dead_zone)).min(1.0) / length;
+
+        [x * scale, y * scale]
+    }
+}
+
+/// Gamepad button of each `InputAction`.
+/// Horizontal movement and the camera are always on the sticks,
+/// buttons could be bound to them additionally.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct GamepadBindings {
+    pub move_forward: Option<GamepadButton>,
+    pub move_backward: Option<GamepadButton>,
+    pub move_left: Option<GamepadButton>,
+    pub move_right: Option<GamepadButton>,
+    pub move_up: Option<GamepadButton>,
+    pub move_down: Option<GamepadButton>,
+    pub remove_block: Option<GamepadButton>,
+    pub place_block: Option<GamepadButton>,
+    pub open_inventory: Option<GamepadButton>,
+    pub open_chat: Option<GamepadButton>,
+    pub toggle_camera: Option<GamepadButton>,
+    pub toggle_debug_overlay: Option<GamepadButton>,
+    pub screenshot: Option<GamepadButton>,
+}
+
+impl Default for GamepadBindings {
+    fn default() -> Self {
+        Self {
+            move_forward: Some(GamepadButton::DPadUp),
+            move_backward: Some(GamepadButton::DPadDown),
+            move_left: Some(GamepadButton::DPadLeft),
+            move_right: Some(GamepadButton::DPadRight),
+            move_up: Some(GamepadButton::South),
+            move_down: Some(GamepadButton::East),
+            remove_block: Some(GamepadButton::RightTrigger2),
+            place_block: Some(GamepadButton::LeftTrigger2),
+            open_inventory: Some(GamepadButton::North),
+            open_chat: None,
+            toggle_camera: Some(GamepadButton::RightThumb),
+            toggle_debug_overlay: None,
+            screenshot: None,
+        }
+    }
+}
+
+impl GamepadBindings {
+    pub fn get_mut(&mut self, action: InputAction) -> &mut Option<GamepadButton> {
+        match action {
+            InputAction::MoveForward => &mut self.move_forward,
+            InputAction::MoveBackward => &mut self.move_backward,
+            InputAction::MoveLeft => &mut self.move_left,
+            InputAction::MoveRight => &mut self.move_right,
+            InputAction::MoveUp => &mut self.move_up,
+            InputAction::MoveDown => &mut self.move_down,
+            InputAction::RemoveBlock => &mut self.remove_block,
+            InputAction::PlaceBlock => &mut self.place_block,
+            InputAction::OpenInventory => &mut self.open_inventory,
+            InputAction::OpenChat => &mut self.open_chat,
+            InputAction::ToggleCamera => &mut self.toggle_camera,
+            InputAction::ToggleDebugOverlay => &mut self.toggle_debug_overlay,
+            InputAction::Screenshot => &mut self.screenshot,
+        }
+    }
+
+    pub fn get(&self, action: InputAction) -> Option<GamepadButton> {
+        match action {
+            InputAction::MoveForward => self.move_forward,
+            InputAction::MoveBackward => self.move_backward,
+            InputAction::MoveLeft => self.move_left,
+            InputAction::MoveRight => self.move_right,
+            InputAction::MoveUp => self.move_up,
+            InputAction::MoveDown => self.move_down,
+            InputAction::RemoveBlock => self.remove_block,
+            InputAction::PlaceBlock => self.place_block,
+            InputAction::OpenInventory => self.open_inventory,
+            InputAction::OpenChat => self.open_chat,
+            InputAction::ToggleCamera => self.toggle_camera,
+            InputAction::ToggleDebugOverlay => self.toggle_debug_overlay,
+            InputAction::Screenshot => self.screenshot,
+        }
+    }
+
+    /// Action bound to the gamepad button.
+    pub fn action(&self, button: GamepadButton) -> Option<InputAction> {
+        InputAction::ALL
+            .into_iter()
+            .find(|action| self.get(*action) == Some(button))
+    }
+}
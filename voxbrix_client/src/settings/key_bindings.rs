@@ -0,0 +1,163 @@
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::fmt;
+use winit::{
+    event::MouseButton,
+    keyboard::KeyCode,
+};
+
+/// Player actions the keys and the mouse buttons are bound to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputAction {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    RemoveBlock,
+    PlaceBlock,
+    OpenInventory,
+    OpenChat,
+    ToggleCamera,
+    ToggleDebugOverlay,
+    Screenshot,
+}
+
+impl InputAction {
+    pub const ALL: [Self; 13] = [
+        Self::MoveForward,
+        Self::MoveBackward,
+        Self::MoveLeft,
+        Self::MoveRight,
+        Self::MoveUp,
+        Self::MoveDown,
+        Self::RemoveBlock,
+        Self::PlaceBlock,
+        Self::OpenInventory,
+        Self::OpenChat,
+        Self::ToggleCamera,
+        Self::ToggleDebugOverlay,
+        Self::Screenshot,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MoveForward => "Move forward",
+            Self::MoveBackward => "Move backward",
+            Self::MoveLeft => "Move left",
+            Self::MoveRight => "Move right",
+            Self::MoveUp => "Move up",
+            Self::MoveDown => "Move down",
+            Self::RemoveBlock => "Remove block",
+            Self::PlaceBlock => "Place block",
+            Self::OpenInventory => "Inventory",
+            Self::OpenChat => "Chat",
+            Self::ToggleCamera => "Toggle camera",
+            Self::ToggleDebugOverlay => "Debug overlay",
+            Self::Screenshot => "Screenshot",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum InputBinding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+impl fmt::Display for InputBinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Key(code) => write!(f, "{:?}", code),
+            Self::Mouse(button) => write!(f, "Mouse {:?}", button),
+        }
+    }
+}
+
+/// Key or mouse button of each `InputAction`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyBindings {
+    pub move_forward: InputBinding,
+    pub move_backward: InputBinding,
+    pub move_left: InputBinding,
+    pub move_right: InputBinding,
+    pub move_up: InputBinding,
+    pub move_down: InputBinding,
+    pub remove_block: InputBinding,
+    pub place_block: InputBinding,
+    pub open_inventory: InputBinding,
+    pub open_chat: InputBinding,
+    pub toggle_camera: InputBinding,
+    pub toggle_debug_overlay: InputBinding,
+    pub screenshot: InputBinding,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            move_forward: InputBinding::Key(KeyCode::KeyW),
+            move_backward: InputBinding::Key(KeyCode::KeyS),
+            move_left: InputBinding::Key(KeyCode::KeyA),
+            move_right: InputBinding::Key(KeyCode::KeyD),
+            move_up: InputBinding::Key(KeyCode::Space),
+            move_down: InputBinding::Key(KeyCode::ShiftLeft),
+            remove_block: InputBinding::Mouse(MouseButton::Left),
+            place_block: InputBinding::Mouse(MouseButton::Right),
+            open_inventory: InputBinding::Key(KeyCode::KeyI),
+            open_chat: InputBinding::Key(KeyCode::Enter),
+            toggle_camera: InputBinding::Key(KeyCode::F5),
+            toggle_debug_overlay: InputBinding::Key(KeyCode::F3),
+            screenshot: InputBinding::Key(KeyCode::F2),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn get_mut(&mut self, action: InputAction) -> &mut InputBinding {
+        match action {
+            InputAction::MoveForward => &mut self.move_forward,
+            InputAction::MoveBackward => &mut self.move_backward,
+            InputAction::MoveLeft => &mut self.move_left,
+            InputAction::MoveRight => &mut self.move_right,
+            InputAction::MoveUp => &mut self.move_up,
+            InputAction::MoveDown => &mut self.move_down,
+            InputAction::RemoveBlock => &mut self.remove_block,
+            InputAction::PlaceBlock => &mut self.place_block,
+            InputAction::OpenInventory => &mut self.open_inventory,
+            InputAction::OpenChat => &mut self.open_chat,
+            InputAction::ToggleCamera => &mut self.toggle_camera,
+            InputAction::ToggleDebugOverlay => &mut self.toggle_debug_overlay,
+            InputAction::Screenshot => &mut self.screenshot,
+        }
+    }
+
+    pub fn get(&self, action: InputAction) -> InputBinding {
+        match action {
+            InputAction::MoveForward => self.move_forward,
+            InputAction::MoveBackward => self.move_backward,
+            InputAction::MoveLeft => self.move_left,
+            InputAction::MoveRight => self.move_right,
+            InputAction::MoveUp => self.move_up,
+            InputAction::MoveDown => self.move_down,
+            InputAction::RemoveBlock => self.remove_block,
+            InputAction::PlaceBlock => self.place_block,
+            InputAction::OpenInventory => self.open_inventory,
+            InputAction::OpenChat => self.open_chat,
+            InputAction::ToggleCamera => self.toggle_camera,
+            InputAction::ToggleDebugOverlay => self.toggle_debug_overlay,
+            InputAction::Screenshot => self.screenshot,
+        }
+    }
+
+    /// Action bound to the key or the mouse button.
+    pub fn action(&self, binding: InputBinding) -> Option<InputAction> {
+        InputAction::ALL
+            .into_iter()
+            .find(|action| self.get(*action) == binding)
+    }
+}
@@ -1,11 +1,27 @@
 pub mod actor_render;
+pub mod asset_sync;
+pub mod block_prediction;
 pub mod block_render;
+pub mod chat;
 pub mod chunk_presence;
 pub mod controller;
+pub mod debug_overlay;
+pub mod effect_hud;
+pub mod health_bar;
 pub mod interface;
+pub mod inventory;
 pub mod model_loading;
 pub mod movement_interpolation;
+pub mod particle;
 pub mod player_position;
+pub mod profiler;
 pub mod render;
+pub mod screenshot;
+pub mod script_hud;
+pub mod server_browser;
+pub mod settings_menu;
+pub mod sky;
+pub mod sky_render;
 pub mod texture_loading;
 pub mod velocity;
+pub mod view_model;
@@ -15,14 +15,17 @@ use crate::{
         actor_model::builder::BuilderActorModelComponent,
     },
     entity::actor_model::ActorBone,
-    system::render::{
-        gpu_vec::GpuVec,
-        primitives::{
-            Quad,
-            VertexDescription,
+    system::{
+        render::{
+            gpu_vec::GpuVec,
+            primitives::{
+                Quad,
+                VertexDescription,
+            },
+            RenderParameters,
+            Renderer,
         },
-        RenderParameters,
-        Renderer,
+        texture_loading::TextureAnimations,
     },
     window::Window,
 };
@@ -53,6 +56,7 @@ pub struct ActorRenderSystemDescriptor<'a> {
     pub render_parameters: RenderParameters<'a>,
     pub actor_texture_bind_group_layout: wgpu::BindGroupLayout,
     pub actor_texture_bind_group: wgpu::BindGroup,
+    pub actor_texture_animations: TextureAnimations,
 }
 
 impl<'a> ActorRenderSystemDescriptor<'a> {
@@ -65,6 +69,7 @@ impl<'a> ActorRenderSystemDescriptor<'a> {
                 },
             actor_texture_bind_group_layout,
             actor_texture_bind_group,
+            actor_texture_animations,
         } = self;
 
         let shaders = voxbrix_common::read_file_async(SHADERS_PATH)
@@ -160,6 +165,7 @@ impl<'a> ActorRenderSystemDescriptor<'a> {
         ActorRenderSystem {
             render_pipeline,
             actor_texture_bind_group,
+            actor_texture_animations,
             bone_transformations: IntMap::default(),
             quads: Vec::new(),
             vertex_buffer,
@@ -171,6 +177,7 @@ impl<'a> ActorRenderSystemDescriptor<'a> {
 pub struct ActorRenderSystem {
     render_pipeline: wgpu::RenderPipeline,
     actor_texture_bind_group: wgpu::BindGroup,
+    actor_texture_animations: TextureAnimations,
     bone_transformations: IntMap<ActorBone, Mat4F32>,
     quads: Vec<Quad>,
     vertex_buffer: wgpu::Buffer,
@@ -178,9 +185,10 @@ pub struct ActorRenderSystem {
 }
 
 impl ActorRenderSystem {
+    /// `hidden_actor` is not drawn, it is the player in the first-person view.
     pub fn update(
         &mut self,
-        player_actor: Actor,
+        hidden_actor: Option<Actor>,
         class_ac: &ClassActorComponent,
         position_ac: &PositionActorComponent,
         velocity_ac: &VelocityActorComponent,
@@ -194,7 +202,7 @@ impl ActorRenderSystem {
 
         for (actor, position, model) in position_ac
             .iter()
-            .filter(|(actor, _)| *actor != player_actor)
+            .filter(|(actor, _)| Some(*actor) != hidden_actor)
             .filter_map(|(actor, position)| {
                 let class = class_ac.get(&actor)?;
                 let model = model_acc.get(&actor, class)?;
@@ -310,6 +318,8 @@ impl ActorRenderSystem {
     }
 
     pub fn render(&mut self, renderer: Renderer) {
+        self.actor_texture_animations.update(renderer.queue);
+
         let quads_len = self.quads.len();
 
         if quads_len == 0 {
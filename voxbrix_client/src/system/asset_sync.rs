@@ -0,0 +1,200 @@
+use crate::assets::ASSET_CACHE_PATH;
+use anyhow::{
+    Context,
+    Error,
+};
+use std::{
+    fmt::Debug,
+    fs,
+    path::{
+        Component,
+        Path,
+        PathBuf,
+    },
+};
+use voxbrix_common::{
+    messages::client::{
+        AssetFile,
+        AssetList,
+        AssetManifest,
+    },
+    system::{
+        list_loading::List,
+        pack_loading::{
+            self,
+            Pack,
+            PackSet,
+        },
+    },
+};
+
+/// Checks that the path from the server is relative and stays inside of the cache.
+fn safe_path(path: &str) -> Option<PathBuf> {
+    let path = Path::new(path);
+
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+        .then(|| path.to_owned())
+}
+
+/// Assets of the server the client is connected to.
+#[derive(Clone)]
+pub struct ServerAssets {
+    pub packs: PackSet,
+    lists: Vec<AssetList>,
+}
+
+impl ServerAssets {
+    /// Label list as the server has it, the label ids must match the server ones.
+    /// Lists the server does not send are merged from the packs.
+    pub async fn load_list(
+        &self,
+        path: impl 'static + AsRef<Path> + Debug + Send + Clone,
+    ) -> Result<List, Error> {
+        match self
+            .lists
+            .iter()
+            .find(|list| Path::new(&list.path) == path.as_ref())
+        {
+            Some(list) => {
+                Ok(List {
+                    list: list.labels.clone(),
+                })
+            },
+            None => self.packs.load_list(path).await,
+        }
+    }
+}
+
+/// Keeps the cached copies of the server packs up to date with the manifest.
+pub struct AssetSyncSystem {
+    manifest: AssetManifest,
+    pack_roots: Vec<PathBuf>,
+    file_paths: Vec<PathBuf>,
+}
+
+impl AssetSyncSystem {
+    pub fn new(manifest: AssetManifest) -> Result<Self, Error> {
+        let cache_root = Path::new(ASSET_CACHE_PATH);
+
+        let pack_roots = manifest
+            .packs
+            .iter()
+            .map(|label| {
+                safe_path(label)
+                    .filter(|path| path.components().count() == 1)
+                    .map(|path| cache_root.join(path))
+                    .ok_or_else(|| Error::msg(format!("incorrect pack label \"{}\"", label)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let file_paths = manifest
+            .files
+            .iter()
+            .map(|entry| {
+                let root = pack_roots
+                    .get(entry.pack as usize)
+                    .ok_or_else(|| Error::msg(format!("undefined pack of \"{}\"", entry.path)))?;
+
+                let path = safe_path(&entry.path).ok_or_else(|| {
+                    Error::msg(format!("incorrect asset path \"{}\"", entry.path))
+                })?;
+
+                Ok(root.join(path))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Self {
+            manifest,
+            pack_roots,
+            file_paths,
+        })
+    }
+
+    /// Indices of the manifest files that are missing from the cache or differ from the server ones.
+    /// Blocking IO, must not be used directly in async
+    pub fn missing_files(&self) -> Vec<u32> {
+        self.manifest
+            .files
+            .iter()
+            .zip(self.file_paths.iter())
+            .enumerate()
+            .filter(|(_, (entry, path))| {
+                fs::read(path)
+                    .map(|data| pack_loading::asset_hash(&data) != entry.hash)
+                    .unwrap_or(true)
+            })
+            .map(|(index, _)| index as u32)
+            .collect()
+    }
+
+    /// Removes the cached files the server does not have,
+    /// they would be read along with the server ones otherwise.
+    /// Blocking IO, must not be used directly in async
+    pub fn remove_stale_files(&self) -> Result<(), Error> {
+        fn remove_in(dir: &Path, keep: &[PathBuf]) -> Result<(), Error> {
+            if !dir.is_dir() {
+                return Ok(());
+            }
+
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+
+                if path.is_dir() {
+                    remove_in(&path, keep)?;
+                } else if !keep.contains(&path) {
+                    fs::remove_file(&path)
+                        .with_context(|| format!("unable to remove {:?}", path))?;
+                }
+            }
+
+            Ok(())
+        }
+
+        for root in self.pack_roots.iter() {
+            remove_in(root, &self.file_paths)?;
+        }
+
+        Ok(())
+    }
+
+    /// Blocking IO, must not be used directly in async
+    pub fn store_file(&self, file: AssetFile) -> Result<(), Error> {
+        let AssetFile { index, data } = file;
+
+        let (entry, path) = self
+            .manifest
+            .files
+            .get(index as usize)
+            .zip(self.file_paths.get(index as usize))
+            .ok_or_else(|| Error::msg(format!("asset file {} is not in the manifest", index)))?;
+
+        if pack_loading::asset_hash(&data) != entry.hash {
+            return Err(Error::msg(format!(
+                "asset file \"{}\" does not match the manifest",
+                entry.path
+            )));
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("unable to create {:?}", dir))?;
+        }
+
+        fs::write(path, data).with_context(|| format!("unable to write {:?}", path))
+    }
+
+    pub fn finish(self) -> ServerAssets {
+        let packs = self
+            .manifest
+            .packs
+            .into_iter()
+            .zip(self.pack_roots)
+            .map(|(label, root)| Pack { label, root })
+            .collect();
+
+        ServerAssets {
+            packs: PackSet::from_packs(packs),
+            lists: self.manifest.lists,
+        }
+    }
+}
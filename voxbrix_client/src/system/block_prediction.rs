@@ -0,0 +1,145 @@
+use crate::component::block::class::ClassBlockComponent;
+use ahash::AHashMap;
+use std::{
+    mem,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+use voxbrix_common::entity::{
+    block::Block,
+    block_class::BlockClass,
+    chunk::Chunk,
+    snapshot::Snapshot,
+};
+
+/// Time the server has to deliver the block changes of an action after confirming the action.
+/// The changes are sent reliably while the confirmation is not, so they may arrive later.
+const ROLLBACK_DELAY: Duration = Duration::from_millis(500);
+
+struct Prediction {
+    /// Client snapshot of the action the prediction is made for.
+    snapshot: Snapshot,
+    predicted: BlockClass,
+    /// The latest class the server has sent for the block.
+    authoritative: BlockClass,
+    confirmed_at: Option<Instant>,
+}
+
+/// Applies the block changes of the player actions before the server does.
+///
+/// The server block changes of a predicted block are held back until the server confirms
+/// the action, the ones arriving after that end the prediction. If the server does not change
+/// the block within [`ROLLBACK_DELAY`] after the confirmation, the prediction is rolled back
+/// to the class the server has sent last.
+pub struct BlockPredictionSystem {
+    predictions: AHashMap<(Chunk, Block), Prediction>,
+}
+
+impl BlockPredictionSystem {
+    pub fn new() -> Self {
+        Self {
+            predictions: AHashMap::new(),
+        }
+    }
+
+    /// Sets the block class right away, `snapshot` is the one the action is packed with.
+    /// Returns the previous class, `None` if the chunk is not loaded or the class is the same.
+    pub fn predict(
+        &mut self,
+        chunk: Chunk,
+        block: Block,
+        block_class: BlockClass,
+        snapshot: Snapshot,
+        class_bc: &mut ClassBlockComponent,
+    ) -> Option<BlockClass> {
+        let classes = class_bc.get_mut_chunk(&chunk)?;
+
+        if *classes.get(block) == block_class {
+            return None;
+        }
+
+        let previous = mem::replace(classes.get_mut(block), block_class);
+
+        let authoritative = self
+            .predictions
+            .get(&(chunk, block))
+            .map(|prediction| prediction.authoritative)
+            .unwrap_or(previous);
+
+        self.predictions.insert(
+            (chunk, block),
+            Prediction {
+                snapshot,
+                predicted: block_class,
+                authoritative,
+                confirmed_at: None,
+            },
+        );
+
+        Some(previous)
+    }
+
+    /// Should be called with every `last_client_snapshot` received from the server.
+    pub fn confirm(&mut self, last_client_snapshot: Snapshot) {
+        let now = Instant::now();
+
+        for prediction in self.predictions.values_mut() {
+            if prediction.confirmed_at.is_none() && prediction.snapshot <= last_client_snapshot {
+                prediction.confirmed_at = Some(now);
+            }
+        }
+    }
+
+    /// Should be called for every block change received from the server.
+    /// Returns `false` if the change must not be applied yet.
+    pub fn server_change(&mut self, chunk: &Chunk, block: Block, block_class: BlockClass) -> bool {
+        let Some(prediction) = self.predictions.get_mut(&(*chunk, block)) else {
+            return true;
+        };
+
+        if prediction.confirmed_at.is_none() {
+            prediction.authoritative = block_class;
+            return false;
+        }
+
+        self.predictions.remove(&(*chunk, block));
+
+        true
+    }
+
+    /// The server has sent the whole chunk, or the chunk is dropped.
+    pub fn remove_chunk(&mut self, chunk: &Chunk) {
+        self.predictions.retain(|(other, _), _| other != chunk);
+    }
+
+    /// Rolls back the confirmed predictions the server has not agreed with in time.
+    /// `rolled_back` is called for each block that has changed the class.
+    pub fn process(
+        &mut self,
+        class_bc: &mut ClassBlockComponent,
+        mut rolled_back: impl FnMut(Chunk, Block),
+    ) {
+        let now = Instant::now();
+
+        self.predictions.retain(|(chunk, block), prediction| {
+            let expired = prediction.confirmed_at.is_some_and(|confirmed_at| {
+                now.saturating_duration_since(confirmed_at) > ROLLBACK_DELAY
+            });
+
+            if !expired {
+                return true;
+            }
+
+            if prediction.authoritative != prediction.predicted {
+                if let Some(classes) = class_bc.get_mut_chunk(chunk) {
+                    *classes.get_mut(*block) = prediction.authoritative;
+                    rolled_back(*chunk, *block);
+                }
+            }
+
+            false
+        });
+    }
+}
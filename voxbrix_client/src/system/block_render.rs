@@ -4,10 +4,7 @@ use crate::{
         block::class::ClassBlockComponent,
         block_class::model::ModelBlockClassComponent,
         block_model::{
-            builder::{
-                BuilderBlockModelComponent,
-                CullFlags,
-            },
+            builder::BuilderBlockModelComponent,
             culling::{
                 Culling,
                 CullingBlockModelComponent,
@@ -16,15 +13,18 @@ use crate::{
         texture::location::LocationTextureComponent,
     },
     entity::texture::Texture,
-    system::render::{
-        gpu_vec::GpuVec,
-        primitives::{
-            Quad,
-            Vertex,
-            VertexDescription,
+    system::{
+        render::{
+            gpu_vec::GpuVec,
+            primitives::{
+                Quad,
+                Vertex,
+                VertexDescription,
+            },
+            RenderParameters,
+            Renderer,
         },
-        RenderParameters,
-        Renderer,
+        texture_loading::TextureAnimations,
     },
     window::Window,
 };
@@ -34,23 +34,45 @@ use ahash::{
 };
 use arrayvec::ArrayVec;
 use rayon::prelude::*;
+use scheduler::MeshBuildScheduler;
 use std::{
-    collections::VecDeque,
+    collections::{
+        hash_map::Entry,
+        VecDeque,
+    },
     iter,
     mem,
+    ops::{
+        BitOr,
+        BitOrAssign,
+    },
+    sync::Arc,
 };
 use voxbrix_common::{
-    component::block::{
-        sky_light::{
-            SkyLight,
-            SkyLightBlockComponent,
+    component::{
+        block::{
+            block_light::{
+                BlockLight,
+                BlockLightBlockComponent,
+            },
+            sky_light::{
+                SkyLight,
+                SkyLightBlockComponent,
+            },
+            BlockComponent,
+            BlocksVec,
+        },
+        block_class::fluid::{
+            Fluid,
+            FluidBlockClassComponent,
         },
-        BlocksVec,
     },
     entity::{
         block::{
             Block,
             Neighbor,
+            BLOCKS_IN_CHUNK_EDGE,
+            BLOCKS_IN_CHUNK_LAYER,
         },
         block_class::BlockClass,
         chunk::{
@@ -58,57 +80,214 @@ use voxbrix_common::{
             Dimension,
         },
     },
+    system::block_surface::{
+        ChunkSurface,
+        CullFlags,
+        Occluders,
+    },
     LabelMap,
 };
 use wgpu::util::DeviceExt;
 
+mod greedy;
+mod lod;
+mod scheduler;
+
 const QUAD_SIZE: usize = Quad::size() as usize;
 
-fn neighbors_to_cull_flags(
+fn neighbors_to_light_levels<T>(
+    neighbors: &[Neighbor; 6],
+    this_chunk: &BlocksVec<T>,
+    neighbor_chunks: &[Option<&BlocksVec<T>>; 6],
+    unloaded: T,
+) -> [T; 6]
+where
+    T: Copy,
+{
+    neighbors
+        .iter()
+        .zip(neighbor_chunks)
+        .map(|(neighbor, neighbor_chunk)| {
+            Some(match neighbor {
+                Neighbor::ThisChunk(block) => *this_chunk.get(*block),
+                Neighbor::OtherChunk(block) => *neighbor_chunk.as_ref()?.get(*block),
+            })
+        })
+        .map(|light| light.unwrap_or(unloaded))
+        .collect::<ArrayVec<_, 6>>()
+        .into_inner()
+        .unwrap_or_else(|_| unreachable!())
+}
+
+/// Fluid hides its sides covered by the same fluid of the same or higher level.
+/// Returns the height of the fluid surface, the block under the same fluid is full.
+fn fluid_surface<C, F>(
+    fluid: &Fluid,
     neighbors: &[Neighbor; 6],
-    this_chunk: &BlocksVec<BlockClass>,
-    neighbor_chunks: &[Option<&BlocksVec<BlockClass>>; 6],
-    model_bcc: &ModelBlockClassComponent,
-    culling_bmc: &CullingBlockModelComponent,
-) -> CullFlags {
-    let mut cull_flags = CullFlags::all();
-    for (i, (neighbor, neighbor_chunk)) in neighbors.iter().zip(neighbor_chunks.iter()).enumerate()
-    {
+    surface: &ChunkSurface<C, F>,
+    fluid_bcc: &FluidBlockClassComponent,
+    cull_flags: &mut CullFlags,
+) -> f32
+where
+    C: BlockComponent<BlockClass>,
+    F: Fn(&BlockClass) -> bool,
+{
+    let mut height = fluid.height();
+
+    for (i, neighbor) in neighbors.iter().enumerate() {
+        let Some(other) = surface
+            .neighbor_class(i, neighbor)
+            .and_then(|class| fluid_bcc.get(class))
+            .filter(|other| other.is_same_fluid(fluid))
+        else {
+            continue;
+        };
+
         let side = CullFlags::from_index(i);
 
-        match neighbor {
-            Neighbor::ThisChunk(n) => {
-                let class = this_chunk.get(*n);
-                let culling = model_bcc
-                    .get(class)
-                    .and_then(|model| culling_bmc.get(model));
-                match culling {
-                    Some(Culling::Full) => {
-                        cull_flags.remove(side);
-                    },
-                    None => {},
-                }
+        match i {
+            // Below
+            4 => cull_flags.remove(side),
+            // Above
+            5 => {
+                cull_flags.remove(side);
+                height = 1.0;
             },
-            Neighbor::OtherChunk(n) => {
-                if let Some(chunk) = neighbor_chunk {
-                    let class = chunk.get(*n);
-                    let culling = model_bcc
-                        .get(class)
-                        .and_then(|model| culling_bmc.get(model));
-                    match culling {
-                        Some(Culling::Full) => {
-                            cull_flags.remove(side);
-                        },
-                        None => {},
+            _ if other.level >= fluid.level => cull_flags.remove(side),
+            _ => {},
+        }
+    }
+
+    height
+}
+
+/// Block layers along the z axis ("slabs") of a chunk which quads need to be rebuilt.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct DirtySlabs(u32);
+
+impl DirtySlabs {
+    const ALL: Self = Self(u32::MAX >> (u32::BITS as usize - BLOCKS_IN_CHUNK_EDGE));
+
+    fn slab(z: usize) -> Self {
+        Self(1 << z)
+    }
+
+    fn contains(self, z: usize) -> bool {
+        self.0 & (1 << z) != 0
+    }
+}
+
+impl BitOr for DirtySlabs {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for DirtySlabs {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+/// Quads of a chunk, grouped by slabs, so that the untouched slabs are reused on rebuild.
+#[derive(Clone, Default)]
+struct ChunkShard {
+    quads: Vec<Quad>,
+    /// Quads of the slab `z` end before `slab_ends[z]` and start at the end of the previous one.
+    slab_ends: [usize; BLOCKS_IN_CHUNK_EDGE],
+    /// Level of detail the quads were built with, slabs are only used for the full detail.
+    lod: usize,
+}
+
+impl ChunkShard {
+    fn clear(&mut self) {
+        self.quads.clear();
+        self.slab_ends = [0; BLOCKS_IN_CHUNK_EDGE];
+        self.lod = 0;
+    }
+
+    /// Replaces all quads with the simplified mesh of the given level of detail.
+    fn replace_lod(&mut self, lod: usize, quads: Vec<Quad>) {
+        self.quads = quads;
+        self.slab_ends = [self.quads.len(); BLOCKS_IN_CHUNK_EDGE];
+        self.lod = lod;
+    }
+
+    /// Replaces the quads of the dirty slabs with the built ones.
+    /// `built` must be ordered by the slab.
+    fn replace_slabs(&mut self, dirty: DirtySlabs, built: Vec<Vec<Quad>>) {
+        let old_quads = mem::take(&mut self.quads);
+        let mut built = built.into_iter();
+        let mut old_start = 0;
+
+        self.quads.reserve(old_quads.len());
+
+        for z in 0 .. BLOCKS_IN_CHUNK_EDGE {
+            let old_end = self.slab_ends[z];
+
+            if dirty.contains(z) {
+                self.quads.extend(built.next().expect("slab was not built"));
+            } else {
+                self.quads
+                    .extend_from_slice(&old_quads[old_start .. old_end]);
+            }
+
+            old_start = old_end;
+            self.slab_ends[z] = self.quads.len();
+        }
+
+        self.lod = 0;
+    }
+}
+
+/// Copies of the block data of a chunk and its neighbors, the mesh is built from them
+/// in the background while the game changes the originals.
+struct BuildData {
+    class_bc: ClassBlockComponent,
+    sky_light_bc: SkyLightBlockComponent,
+    block_light_bc: BlockLightBlockComponent,
+}
+
+impl BuildData {
+    fn copy(
+        chunk: &Chunk,
+        class_bc: &ClassBlockComponent,
+        sky_light_bc: &SkyLightBlockComponent,
+        block_light_bc: &BlockLightBlockComponent,
+    ) -> Self {
+        let mut data = Self {
+            class_bc: ClassBlockComponent::new(),
+            sky_light_bc: SkyLightBlockComponent::new(),
+            block_light_bc: BlockLightBlockComponent::new(),
+        };
+
+        // Occluders are looked up diagonally, so all the surrounding chunks are needed
+        for z in -1 ..= 1 {
+            for y in -1 ..= 1 {
+                for x in -1 ..= 1 {
+                    let Some(chunk) = chunk.checked_add([x, y, z]) else {
+                        continue;
+                    };
+
+                    if let Some(blocks) = class_bc.get_chunk(&chunk) {
+                        data.class_bc.insert_chunk(chunk, blocks.clone());
+                    }
+
+                    if let Some(blocks) = sky_light_bc.get_chunk(&chunk) {
+                        data.sky_light_bc.insert_chunk(chunk, blocks.clone());
+                    }
+
+                    if let Some(blocks) = block_light_bc.get_chunk(&chunk) {
+                        data.block_light_bc.insert_chunk(chunk, blocks.clone());
                     }
-                } else {
-                    cull_flags.remove(side);
                 }
-            },
+            }
         }
-    }
 
-    cull_flags
+        data
+    }
 }
 
 struct ChunkInfo<'a> {
@@ -129,8 +308,11 @@ pub struct BlockRenderSystemDescriptor<'a> {
     pub render_parameters: RenderParameters<'a>,
     pub block_texture_bind_group_layout: wgpu::BindGroupLayout,
     pub block_texture_bind_group: wgpu::BindGroup,
+    pub block_texture_animations: TextureAnimations,
     pub block_texture_label_map: LabelMap<Texture>,
     pub location_tc: &'a LocationTextureComponent,
+    /// Merge coplanar block faces into larger quads, reducing the number of quads drawn.
+    pub greedy_meshing: bool,
 }
 
 impl<'a> BlockRenderSystemDescriptor<'a> {
@@ -143,8 +325,10 @@ impl<'a> BlockRenderSystemDescriptor<'a> {
                 },
             block_texture_bind_group_layout,
             block_texture_bind_group,
+            block_texture_animations,
             block_texture_label_map,
             location_tc,
+            greedy_meshing,
         } = self;
 
         let shaders = voxbrix_common::read_file_async(SHADERS_PATH)
@@ -173,11 +357,11 @@ impl<'a> BlockRenderSystemDescriptor<'a> {
                     push_constant_ranges: &[],
                 });
 
-        let render_pipeline =
+        let create_pipeline = |label, depth_compare| {
             window
                 .device()
                 .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                    label: Some("Render Pipeline"),
+                    label: Some(label),
                     layout: Some(&render_pipeline_layout),
                     vertex: wgpu::VertexState {
                         module: &shaders,
@@ -207,7 +391,7 @@ impl<'a> BlockRenderSystemDescriptor<'a> {
                     depth_stencil: Some(wgpu::DepthStencilState {
                         format: wgpu::TextureFormat::Depth32Float,
                         depth_write_enabled: true,
-                        depth_compare: wgpu::CompareFunction::Less,
+                        depth_compare,
                         stencil: wgpu::StencilState::default(),
                         bias: wgpu::DepthBiasState::default(),
                     }),
@@ -218,7 +402,14 @@ impl<'a> BlockRenderSystemDescriptor<'a> {
                     },
                     multiview: None,
                     cache: None,
-                });
+                })
+        };
+
+        let render_pipeline = create_pipeline("Render Pipeline", wgpu::CompareFunction::Less);
+
+        // Held block is drawn over the world, it must not get into the walls nearby
+        let view_model_pipeline =
+            create_pipeline("View Model Render Pipeline", wgpu::CompareFunction::Always);
 
         let vertex_buffer = window
             .device()
@@ -252,10 +443,11 @@ impl<'a> BlockRenderSystemDescriptor<'a> {
 
         BlockRenderSystem {
             block_change_queue: VecDeque::new(),
-            block_change_neighbors: AHashMap::new(),
+            block_change_slabs: AHashMap::new(),
             chunk_queue: VecDeque::new(),
             enqueued_chunks: AHashSet::new(),
             render_pipeline,
+            view_model_pipeline,
             chunk_buffer_shards: AHashMap::new(),
             free_shards: Vec::new(),
             prepared_vertex_buffer: vertex_buffer,
@@ -264,12 +456,53 @@ impl<'a> BlockRenderSystemDescriptor<'a> {
             superchunk_side_size: 4,
             free_quad_buffers: Vec::new(),
             block_texture_bind_group,
+            block_texture_animations,
             target_highlighting: TargetHighlighting::None,
             target_highlight_quad_buffer,
             highlight_texture_index,
             highlight_texture_coords,
+            greedy_meshing,
+            view_center: None,
+            scheduler: MeshBuildScheduler::new(),
+            particles: QuadList::new(window.device()),
+            view_model: QuadList::new(window.device()),
+        }
+    }
+}
+
+/// Quads rebuilt every frame, drawn in addition to the chunks.
+struct QuadList {
+    quads: Vec<Quad>,
+    buffer: GpuVec,
+}
+
+impl QuadList {
+    fn new(device: &wgpu::Device) -> Self {
+        Self {
+            quads: Vec::new(),
+            buffer: GpuVec::new(device, wgpu::BufferUsages::VERTEX),
         }
     }
+
+    fn set(&mut self, quads: &[Quad]) {
+        self.quads.clear();
+        self.quads.extend_from_slice(quads);
+    }
+
+    /// Returns the number of quads written.
+    fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> u32 {
+        if !self.quads.is_empty() {
+            let mut writer =
+                self.buffer
+                    .get_writer(device, queue, (self.quads.len() * QUAD_SIZE) as u64);
+
+            writer
+                .as_mut()
+                .copy_from_slice(bytemuck::cast_slice(self.quads.as_slice()));
+        }
+
+        self.quads.len() as u32
+    }
 }
 
 enum TargetHighlighting {
@@ -327,32 +560,42 @@ impl SuperChunk {
 
 pub struct BlockRenderSystem {
     block_change_queue: VecDeque<Chunk>,
-    block_change_neighbors: AHashMap<Chunk, [bool; 6]>,
+    block_change_slabs: AHashMap<Chunk, DirtySlabs>,
     chunk_queue: VecDeque<Chunk>,
     enqueued_chunks: AHashSet<Chunk>,
     render_pipeline: wgpu::RenderPipeline,
-    chunk_buffer_shards: AHashMap<Chunk, Vec<Quad>>,
-    free_shards: Vec<Vec<Quad>>,
+    view_model_pipeline: wgpu::RenderPipeline,
+    chunk_buffer_shards: AHashMap<Chunk, ChunkShard>,
+    free_shards: Vec<ChunkShard>,
     prepared_vertex_buffer: wgpu::Buffer,
     superchunk_side_size: i32,
     prepared_quad_buffers: AHashMap<SuperChunk, QuadBuffer>,
     updated_quad_buffers: AHashSet<SuperChunk>,
     free_quad_buffers: Vec<QuadBuffer>,
     block_texture_bind_group: wgpu::BindGroup,
+    block_texture_animations: TextureAnimations,
     target_highlighting: TargetHighlighting,
     target_highlight_quad_buffer: wgpu::Buffer,
     highlight_texture_index: u32,
     highlight_texture_coords: [[f32; 2]; 4],
+    greedy_meshing: bool,
+    view_center: Option<Chunk>,
+    scheduler: MeshBuildScheduler,
+    particles: QuadList,
+    view_model: QuadList,
 }
 
 impl BlockRenderSystem {
-    fn build_chunk_buffer_shard<'a>(
+    fn build_slab<'a>(
         chunk: &'a Chunk,
+        slab: usize,
         class_bc: &'a ClassBlockComponent,
         model_bcc: &'a ModelBlockClassComponent,
+        fluid_bcc: &'a FluidBlockClassComponent,
         builder_bmc: &'a BuilderBlockModelComponent,
         culling_bmc: &'a CullingBlockModelComponent,
         sky_light_bc: &'a SkyLightBlockComponent,
+        block_light_bc: &'a BlockLightBlockComponent,
     ) -> impl ParallelIterator<Item = Quad> + 'a {
         let neighbor_chunk_ids = [
             [-1, 0, 0],
@@ -364,57 +607,88 @@ impl BlockRenderSystem {
         ]
         .map(|offset| chunk.checked_add(offset));
 
-        let this_chunk_class = class_bc.get_chunk(chunk).unwrap();
-        let this_chunk_light = sky_light_bc.get_chunk(chunk).unwrap();
+        let surface = ChunkSurface::new(chunk, class_bc, move |class: &BlockClass| {
+            let culling = model_bcc
+                .get(class)
+                .and_then(|model| culling_bmc.get(model));
 
-        let neighbor_chunk_class = neighbor_chunk_ids.map(|chunk| {
-            let block_classes = class_bc.get_chunk(&chunk?)?;
+            matches!(culling, Some(Culling::Full))
+        })
+        .unwrap();
 
-            Some(block_classes)
-        });
+        let this_chunk_class = surface.this_chunk();
+        let this_chunk_sky_light = sky_light_bc.get_chunk(chunk).unwrap();
+        let this_chunk_block_light = block_light_bc.get_chunk(chunk).unwrap();
 
-        let neighbor_chunk_light = neighbor_chunk_ids.map(|chunk| {
-            let block_light = sky_light_bc.get_chunk(&chunk?)?;
+        let neighbor_chunk_sky_light =
+            neighbor_chunk_ids.map(|chunk| sky_light_bc.get_chunk(&chunk?));
 
-            Some(block_light)
-        });
+        let neighbor_chunk_block_light =
+            neighbor_chunk_ids.map(|chunk| block_light_bc.get_chunk(&chunk?));
 
         this_chunk_class
             .par_iter()
-            .flat_map_iter(move |(block, block_class)| {
-                model_bcc
+            .skip(slab * BLOCKS_IN_CHUNK_LAYER)
+            .take(BLOCKS_IN_CHUNK_LAYER)
+            .filter_map(move |(block, block_class)| {
+                let model_builder = model_bcc
                     .get(block_class)
-                    .and_then(|m| builder_bmc.get(m))
-                    .into_iter()
-                    .flat_map(move |model_builder| {
-                        let neighbors = block.neighbors();
-
-                        let cull_flags = neighbors_to_cull_flags(
-                            &neighbors,
-                            this_chunk_class,
-                            &neighbor_chunk_class,
-                            model_bcc,
-                            culling_bmc,
-                        );
-
-                        let sky_light_levels = neighbors
-                            .iter()
-                            .zip(neighbor_chunk_light)
-                            .map(|(neighbor, neighbor_chunk_light)| {
-                                Some(match neighbor {
-                                    Neighbor::ThisChunk(block) => *this_chunk_light.get(*block),
-                                    Neighbor::OtherChunk(block) => {
-                                        *neighbor_chunk_light?.get(*block)
-                                    },
-                                })
-                            })
-                            .map(|light| light.unwrap_or(SkyLight::MIN))
-                            .collect::<ArrayVec<_, 6>>()
-                            .into_inner()
-                            .unwrap_or_else(|_| unreachable!());
+                    .and_then(|m| builder_bmc.get(m))?;
 
-                        model_builder.build(chunk, block, cull_flags, sky_light_levels)
-                    })
+                let neighbors = block.neighbors();
+
+                let mut cull_flags = surface.visible_sides(&neighbors);
+
+                let fluid_height = fluid_bcc.get(block_class).map(|fluid| {
+                    fluid_surface(fluid, &neighbors, &surface, fluid_bcc, &mut cull_flags)
+                });
+
+                let occluders = if cull_flags.is_empty() {
+                    Occluders::default()
+                } else {
+                    surface.occluders(block)
+                };
+
+                let sky_light_levels = neighbors_to_light_levels(
+                    &neighbors,
+                    this_chunk_sky_light,
+                    &neighbor_chunk_sky_light,
+                    SkyLight::MIN,
+                );
+
+                let block_light_levels = neighbors_to_light_levels(
+                    &neighbors,
+                    this_chunk_block_light,
+                    &neighbor_chunk_block_light,
+                    BlockLight::MIN,
+                );
+
+                let quads = model_builder.build(
+                    chunk,
+                    block,
+                    cull_flags,
+                    sky_light_levels,
+                    block_light_levels,
+                    occluders,
+                );
+
+                Some((block, quads, fluid_height))
+            })
+            .flat_map_iter(move |(block, quads, fluid_height)| {
+                let block_z = block.into_coords()[2] as f32;
+
+                quads.map(move |mut quad| {
+                    // Partially filled fluid blocks have the top lowered
+                    if let Some(height) = fluid_height.filter(|h| *h < 1.0) {
+                        for vertex in quad.vertices.iter_mut() {
+                            if vertex.position[2] > block_z + 0.5 {
+                                vertex.position[2] = block_z + height;
+                            }
+                        }
+                    }
+
+                    quad
+                })
             })
     }
 
@@ -422,18 +696,37 @@ impl BlockRenderSystem {
     /// should be used for adding new chunks after they are processed through other systems.
     /// The previous steps should take care and manually add neighbors if necessary.
     pub fn enqueue_chunk(&mut self, chunk: Chunk) {
-        // Ignore if already exists in either queue.
-        if self.block_change_neighbors.contains_key(&chunk) || !self.enqueued_chunks.insert(chunk) {
+        // The build in flight has the outdated data
+        self.scheduler.cancel(&chunk);
+
+        // Already in the high-priority queue, make it a full rebuild there.
+        if let Some(slabs) = self.block_change_slabs.get_mut(&chunk) {
+            *slabs = DirtySlabs::ALL;
             return;
         }
 
-        self.chunk_queue.push_back(chunk);
+        if self.enqueued_chunks.insert(chunk) {
+            self.chunk_queue.push_back(chunk);
+        }
     }
 
     /// Block changes enqueued into high-priority queue.
-    /// Also re-renders neighbor chunks if necessary.
+    /// Only the slabs around the block are rebuilt, including the neighbor chunks' ones
+    /// if the block is on the chunk border.
     pub fn block_change(&mut self, chunk: &Chunk, block: Block) {
-        let mut neighbor_needs_render = [false; 6];
+        let [_, _, z] = block.into_coords();
+
+        let mut slabs = DirtySlabs::slab(z);
+
+        if z > 0 {
+            slabs |= DirtySlabs::slab(z - 1);
+        }
+
+        if z < BLOCKS_IN_CHUNK_EDGE - 1 {
+            slabs |= DirtySlabs::slab(z + 1);
+        }
+
+        self.mark_dirty(*chunk, slabs);
 
         let neighbor_chunks = [
             [-1, 0, 0],
@@ -450,33 +743,74 @@ impl BlockRenderSystem {
 
         let neighbors = block.neighbors();
 
-        for (side, _chunk) in neighbor_chunks {
-            if let Neighbor::OtherChunk(_) = neighbors[side] {
-                neighbor_needs_render[side] = true;
+        for (side, neighbor_chunk) in neighbor_chunks {
+            if let Neighbor::OtherChunk(neighbor) = neighbors[side] {
+                let [_, _, neighbor_z] = neighbor.into_coords();
+                self.mark_dirty(neighbor_chunk, DirtySlabs::slab(neighbor_z));
             }
         }
+    }
+
+    fn mark_dirty(&mut self, chunk: Chunk, mut slabs: DirtySlabs) {
+        // The build in flight has the outdated data, its slabs are rebuilt along with the new ones
+        if let Some(building) = self.scheduler.cancel(&chunk) {
+            slabs |= building;
+        }
 
-        if let Some(prev) = self.block_change_neighbors.get(chunk) {
-            // Add to-be-rendered neighbors instead of replacing existing
-            for i in 0 .. 6 {
-                neighbor_needs_render[i] = neighbor_needs_render[i] || prev[i];
-            }
-        } else {
-            self.block_change_queue.push_back(*chunk);
-            self.block_change_neighbors
-                .insert(*chunk, neighbor_needs_render);
-            // This queue is high priority, remove from the other one
-            self.enqueued_chunks.remove(chunk);
+        // This queue is high priority, remove from the other one,
+        // keeping the full rebuild if it was requested there
+        if self.enqueued_chunks.remove(&chunk) {
+            slabs = DirtySlabs::ALL;
+        }
+
+        match self.block_change_slabs.entry(chunk) {
+            Entry::Occupied(mut entry) => {
+                *entry.get_mut() |= slabs;
+            },
+            Entry::Vacant(entry) => {
+                entry.insert(slabs);
+                self.block_change_queue.push_back(chunk);
+            },
+        }
+    }
+
+    /// Updates the chunk the level of detail is calculated from,
+    /// rebuilding the chunks which level has changed.
+    pub fn set_view_center(&mut self, chunk: Chunk) {
+        if self.view_center == Some(chunk) {
+            return;
         }
+
+        self.view_center = Some(chunk);
+
+        let changed = self
+            .chunk_buffer_shards
+            .iter()
+            .filter(|(shard_chunk, shard)| lod::level(&chunk, shard_chunk) != shard.lod)
+            .map(|(shard_chunk, _)| *shard_chunk)
+            .collect::<Vec<_>>();
+
+        for chunk in changed {
+            self.enqueue_chunk(chunk);
+        }
+    }
+
+    /// Nothing to do until the builds in flight finish or more chunks are enqueued.
+    pub fn is_idle(&self) -> bool {
+        !self.scheduler.has_finished()
+            && (self.scheduler.available() == 0
+                || self.enqueued_chunks.is_empty() && self.block_change_slabs.is_empty())
     }
 
-    pub fn is_queue_empty(&mut self) -> bool {
-        self.enqueued_chunks.is_empty() && self.block_change_neighbors.is_empty()
+    /// Number of the chunks waiting for their meshes to be built, including the ones being built.
+    pub fn queue_len(&self) -> usize {
+        self.enqueued_chunks.len() + self.block_change_slabs.len() + self.scheduler.in_flight()
     }
 
     pub fn remove_chunk(&mut self, chunk: &Chunk) {
+        self.scheduler.cancel(chunk);
         self.enqueued_chunks.remove(chunk);
-        self.block_change_neighbors.remove(chunk);
+        self.block_change_slabs.remove(chunk);
         if let Some(shard) = self.chunk_buffer_shards.remove(chunk) {
             self.free_shards.push(shard);
             let superchunk = SuperChunk::of_chunk(self.superchunk_side_size, chunk);
@@ -496,93 +830,137 @@ impl BlockRenderSystem {
     pub fn process(
         &mut self,
         class_bc: &ClassBlockComponent,
-        model_bcc: &ModelBlockClassComponent,
-        builder_bmc: &BuilderBlockModelComponent,
-        culling_bmc: &CullingBlockModelComponent,
+        model_bcc: &Arc<ModelBlockClassComponent>,
+        fluid_bcc: &Arc<FluidBlockClassComponent>,
+        builder_bmc: &Arc<BuilderBlockModelComponent>,
+        culling_bmc: &Arc<CullingBlockModelComponent>,
         sky_light_bc: &SkyLightBlockComponent,
+        block_light_bc: &BlockLightBlockComponent,
     ) {
-        let chunk_exists = |chunk: &Chunk| -> bool {
-            class_bc.get_chunk(chunk).is_some() && sky_light_bc.get_chunk(chunk).is_some()
-        };
+        for (chunk, shard) in self.scheduler.take_finished() {
+            if let Some(old_shard) = self.chunk_buffer_shards.insert(chunk, shard) {
+                self.free_shards.push(old_shard);
+            }
 
-        let mut get_shard = |chunk: Chunk| -> (Chunk, Vec<Quad>) {
-            let mut shard = self
-                .chunk_buffer_shards
-                .remove(&chunk)
-                .or_else(|| self.free_shards.pop())
-                .unwrap_or_default();
+            let superchunk = SuperChunk::of_chunk(self.superchunk_side_size, &chunk);
+            self.updated_quad_buffers.insert(superchunk);
+        }
 
-            shard.clear();
+        let available = self.scheduler.available();
 
-            (chunk, shard)
+        if available == 0 {
+            return;
+        }
+
+        let chunk_exists = |chunk: &Chunk| -> bool {
+            class_bc.get_chunk(chunk).is_some()
+                && sky_light_bc.get_chunk(chunk).is_some()
+                && block_light_bc.get_chunk(chunk).is_some()
         };
 
         let mut selected_chunks = iter::from_fn(|| self.block_change_queue.pop_front())
-            .filter_map(|chunk| self.block_change_neighbors.get_key_value(&chunk))
-            .flat_map(|(chunk, neighbors)| {
-                let offsets = [
-                    [-1, 0, 0],
-                    [1, 0, 0],
-                    [0, -1, 0],
-                    [0, 1, 0],
-                    [0, 0, -1],
-                    [0, 0, 1],
-                ];
-
-                let neighbor_iter = neighbors.into_iter().zip(offsets.into_iter()).filter_map(
-                    |(needs_render, offset)| {
-                        if !needs_render {
-                            return None;
-                        }
-
-                        chunk.checked_add(offset)
-                    },
-                );
-
-                iter::once(*chunk).chain(neighbor_iter)
-            })
-            .filter(chunk_exists)
-            .map(&mut get_shard)
+            .filter_map(|chunk| self.block_change_slabs.remove_entry(&chunk))
+            .filter(|(chunk, _)| chunk_exists(chunk))
+            .take(available)
             .collect::<Vec<_>>();
 
-        for (chunk, _) in selected_chunks.iter() {
-            self.block_change_neighbors.remove(chunk);
-            self.enqueued_chunks.remove(chunk);
-        }
-
         // Add some from non-priority queue
-        let to_add = rayon::current_num_threads()
-            .saturating_sub(2)
-            .max(1)
-            .saturating_sub(selected_chunks.len());
+        let to_add = available.saturating_sub(selected_chunks.len());
 
         selected_chunks.extend(
             iter::from_fn(|| self.chunk_queue.pop_front())
                 .filter(|chunk| self.enqueued_chunks.remove(chunk))
                 .filter(chunk_exists)
-                .map(get_shard)
+                .map(|chunk| (chunk, DirtySlabs::ALL))
                 .take(to_add),
         );
 
-        for (chunk, _) in selected_chunks.iter() {
-            let superchunk = SuperChunk::of_chunk(self.superchunk_side_size, chunk);
-            self.updated_quad_buffers.insert(superchunk);
-        }
+        let greedy_meshing = self.greedy_meshing;
+        let view_center = self.view_center;
 
-        let par_iter = selected_chunks.into_par_iter().map(|(chunk, mut shard)| {
-            shard.par_extend(Self::build_chunk_buffer_shard(
-                &chunk,
-                class_bc,
-                model_bcc,
-                builder_bmc,
-                culling_bmc,
-                sky_light_bc,
-            ));
-
-            (chunk, shard)
-        });
+        for (chunk, mut slabs) in selected_chunks {
+            if let Some(building) = self.scheduler.cancel(&chunk) {
+                slabs |= building;
+            }
+
+            // Slabs can only be rebuilt partially if the rest of the chunk is already built,
+            // the current quads stay on screen until the new ones are ready
+            let (mut shard, mut slabs) = match self.chunk_buffer_shards.get(&chunk) {
+                Some(shard) if slabs != DirtySlabs::ALL => (shard.clone(), slabs),
+                _ => {
+                    let mut shard = self.free_shards.pop().unwrap_or_default();
+                    shard.clear();
+
+                    (shard, DirtySlabs::ALL)
+                },
+            };
+
+            let data = BuildData::copy(&chunk, class_bc, sky_light_bc, block_light_bc);
+            let model_bcc = model_bcc.clone();
+            let fluid_bcc = fluid_bcc.clone();
+            let builder_bmc = builder_bmc.clone();
+            let culling_bmc = culling_bmc.clone();
+
+            self.scheduler.spawn(chunk, slabs, move |cancellation| {
+                let lod = view_center
+                    .map(|view_center| lod::level(&view_center, &chunk))
+                    .unwrap_or(0);
+
+                if lod != 0 {
+                    let quads = lod::build_chunk(
+                        &chunk,
+                        lod,
+                        &data.class_bc,
+                        &model_bcc,
+                        &builder_bmc,
+                        &culling_bmc,
+                        &data.sky_light_bc,
+                        &data.block_light_bc,
+                    );
+
+                    shard.replace_lod(lod, quads);
+
+                    return Some(shard);
+                }
 
-        self.chunk_buffer_shards.par_extend(par_iter);
+                if shard.lod != 0 {
+                    slabs = DirtySlabs::ALL;
+                }
+
+                let built = (0 .. BLOCKS_IN_CHUNK_EDGE)
+                    .into_par_iter()
+                    .filter(|z| slabs.contains(*z))
+                    .map(|z| {
+                        if cancellation.is_cancelled() {
+                            return None;
+                        }
+
+                        let quads = Self::build_slab(
+                            &chunk,
+                            z,
+                            &data.class_bc,
+                            &model_bcc,
+                            &fluid_bcc,
+                            &builder_bmc,
+                            &culling_bmc,
+                            &data.sky_light_bc,
+                            &data.block_light_bc,
+                        )
+                        .collect::<Vec<_>>();
+
+                        if greedy_meshing {
+                            Some(greedy::merge_faces(quads))
+                        } else {
+                            Some(quads)
+                        }
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+
+                shard.replace_slabs(slabs, built);
+
+                Some(shard)
+            });
+        }
     }
 
     pub fn build_target_highlight(&mut self, target: Option<(Chunk, Block, usize)>) {
@@ -641,6 +1019,7 @@ impl BlockRenderSystem {
             let quad = Quad {
                 chunk: chunk.position,
                 texture_index: self.highlight_texture_index,
+                texture_bounds: [0.0; 4],
                 vertices: [0, 1, 2, 3]
                     .map(|i| {
                         Vertex {
@@ -661,13 +1040,29 @@ impl BlockRenderSystem {
         }
     }
 
+    /// Particle quads, using the block textures.
+    pub fn set_particles(&mut self, quads: &[Quad]) {
+        self.particles.set(quads);
+    }
+
+    /// Held block quads, drawn on top of the world.
+    pub fn set_view_model(&mut self, quads: &[Quad]) {
+        self.view_model.set(quads);
+    }
+
     pub fn render(&mut self, renderer: Renderer) {
+        self.block_texture_animations.update(renderer.queue);
+
+        let particles_len = self.particles.upload(renderer.device, renderer.queue);
+        let view_model_len = self.view_model.upload(renderer.device, renderer.queue);
+
         for superchunk in self.updated_quad_buffers.drain() {
             let mut quads_len = 0;
 
             let mut chunk_info = superchunk
                 .chunks(self.superchunk_side_size)
                 .filter_map(|chunk| self.chunk_buffer_shards.get(&chunk))
+                .map(|shard| &shard.quads)
                 .map(|quads| {
                     quads_len += quads.len();
 
@@ -750,5 +1145,18 @@ impl BlockRenderSystem {
             render_pass.set_vertex_buffer(1, self.target_highlight_quad_buffer.slice(..));
             render_pass.draw(0 .. 6, 0 .. 1);
         }
+
+        if particles_len != 0 {
+            render_pass.set_vertex_buffer(0, self.prepared_vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, self.particles.buffer.get_slice());
+            render_pass.draw(0 .. 6, 0 .. particles_len);
+        }
+
+        if view_model_len != 0 {
+            render_pass.set_pipeline(&self.view_model_pipeline);
+            render_pass.set_vertex_buffer(0, self.prepared_vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, self.view_model.buffer.get_slice());
+            render_pass.draw(0 .. 6, 0 .. view_model_len);
+        }
     }
 }
@@ -0,0 +1,257 @@
+use crate::system::render::primitives::{
+    Quad,
+    Vertex,
+};
+use ahash::AHashMap;
+use voxbrix_common::entity::block::BLOCKS_IN_CHUNK_EDGE;
+
+const TEXTURE_AFFINITY_EPSILON: f32 = 1e-5;
+
+/// Corners of the full block face: `(along first axis, along second axis)`.
+type Corner = (bool, bool);
+
+/// Quads that can be merged with each other if they are adjacent.
+#[derive(Hash, PartialEq, Eq)]
+struct FaceKey {
+    normal_axis: usize,
+    plane: u32,
+    texture_index: u32,
+    vertices: [(Corner, [u32; 2], u32); 4],
+}
+
+struct FaceGroup {
+    template: Quad,
+    corners: [Corner; 4],
+    cells: [[bool; BLOCKS_IN_CHUNK_EDGE]; BLOCKS_IN_CHUNK_EDGE],
+}
+
+/// Axes of the face plane in the ascending order.
+fn plane_axes(normal_axis: usize) -> [usize; 2] {
+    match normal_axis {
+        0 => [1, 2],
+        1 => [0, 2],
+        _ => [0, 1],
+    }
+}
+
+/// Describes the quad if it covers a single full block face
+/// and its texture can be stretched along the face.
+/// Returns the merge key, the cell of the face in its plane and the corners of the vertices.
+fn describe_face(quad: &Quad) -> Option<(FaceKey, [usize; 2], [Corner; 4])> {
+    let positions = quad.vertices.map(|v| v.position);
+
+    let normal_axis = (0 .. 3).find(|axis| {
+        positions
+            .iter()
+            .all(|position| position[*axis] == positions[0][*axis])
+    })?;
+
+    let axes = plane_axes(normal_axis);
+
+    let cell = axes.map(|axis| {
+        positions
+            .iter()
+            .map(|position| position[axis])
+            .fold(f32::INFINITY, f32::min)
+    });
+
+    if cell
+        .iter()
+        .any(|c| c.fract() != 0.0 || *c < 0.0 || *c >= BLOCKS_IN_CHUNK_EDGE as f32)
+    {
+        return None;
+    }
+
+    let mut corners = [(false, false); 4];
+
+    for (corner, position) in corners.iter_mut().zip(positions.iter()) {
+        let [a, b] = [0, 1].map(|i| {
+            let offset = position[axes[i]] - cell[i];
+
+            if offset == 0.0 {
+                Some(false)
+            } else if offset == 1.0 {
+                Some(true)
+            } else {
+                None
+            }
+        });
+
+        *corner = (a?, b?);
+    }
+
+    for expected in [(false, false), (true, false), (false, true), (true, true)] {
+        if !corners.contains(&expected) {
+            return None;
+        }
+    }
+
+    // Texture must change linearly along the face for the merged quad to repeat it
+    let texture_at = |corner: Corner| {
+        let index = corners.iter().position(|c| *c == corner).unwrap();
+        quad.vertices[index].texture_position
+    };
+
+    let t00 = texture_at((false, false));
+    let t10 = texture_at((true, false));
+    let t01 = texture_at((false, true));
+    let t11 = texture_at((true, true));
+
+    for i in 0 .. 2 {
+        if (t10[i] + t01[i] - t00[i] - t11[i]).abs() > TEXTURE_AFFINITY_EPSILON {
+            return None;
+        }
+    }
+
+    let mut vertices = [((false, false), [0; 2], 0); 4];
+
+    for (i, vertex) in quad.vertices.iter().enumerate() {
+        vertices[i] = (
+            corners[i],
+            vertex.texture_position.map(f32::to_bits),
+            vertex.light_level,
+        );
+    }
+
+    let key = FaceKey {
+        normal_axis,
+        plane: positions[0][normal_axis].to_bits(),
+        texture_index: quad.texture_index,
+        vertices,
+    };
+
+    Some((key, cell.map(|c| c as usize), corners))
+}
+
+fn merged_quad(group: &FaceGroup, normal_axis: usize, start: [usize; 2], size: [usize; 2]) -> Quad {
+    let FaceGroup {
+        template, corners, ..
+    } = group;
+
+    let axes = plane_axes(normal_axis);
+
+    // Single face keeps its texture positions as they are
+    if size == [1, 1] {
+        let mut quad = *template;
+
+        for (vertex, corner) in quad.vertices.iter_mut().zip(corners.iter()) {
+            vertex.position[axes[0]] = (start[0] + corner.0 as usize) as f32;
+            vertex.position[axes[1]] = (start[1] + corner.1 as usize) as f32;
+        }
+
+        return quad;
+    }
+
+    let texture_at = |corner: Corner| {
+        let index = corners.iter().position(|c| *c == corner).unwrap();
+        template.vertices[index].texture_position
+    };
+
+    let t00 = texture_at((false, false));
+    let t10 = texture_at((true, false));
+    let t01 = texture_at((false, true));
+
+    let mut texture_bounds = [
+        f32::INFINITY,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+        f32::NEG_INFINITY,
+    ];
+
+    for vertex in template.vertices.iter() {
+        for i in 0 .. 2 {
+            texture_bounds[i] = texture_bounds[i].min(vertex.texture_position[i]);
+            texture_bounds[i + 2] = texture_bounds[i + 2].max(vertex.texture_position[i]);
+        }
+    }
+
+    let vertices = [0, 1, 2, 3].map(|i| {
+        let template_vertex = template.vertices[i];
+        let (along_a, along_b) = corners[i];
+        let scale_a = if along_a { size[0] as f32 } else { 0.0 };
+        let scale_b = if along_b { size[1] as f32 } else { 0.0 };
+
+        let mut position = template_vertex.position;
+        position[axes[0]] = start[0] as f32 + scale_a;
+        position[axes[1]] = start[1] as f32 + scale_b;
+
+        let texture_position =
+            [0, 1].map(|j| t00[j] + scale_a * (t10[j] - t00[j]) + scale_b * (t01[j] - t00[j]));
+
+        Vertex {
+            position,
+            texture_position,
+            light_level: template_vertex.light_level,
+        }
+    });
+
+    Quad {
+        chunk: template.chunk,
+        texture_index: template.texture_index,
+        texture_bounds,
+        vertices,
+    }
+}
+
+/// Merges adjacent coplanar full block faces with the same texture and light
+/// into larger quads repeating the texture.
+/// Quads that do not cover a full block face are kept as they are.
+pub fn merge_faces(quads: Vec<Quad>) -> Vec<Quad> {
+    let mut output = Vec::new();
+    let mut groups = AHashMap::<FaceKey, FaceGroup>::new();
+
+    for quad in quads {
+        let Some((key, cell, corners)) = describe_face(&quad) else {
+            output.push(quad);
+            continue;
+        };
+
+        let group = groups.entry(key).or_insert_with(|| {
+            FaceGroup {
+                template: quad,
+                corners,
+                cells: [[false; BLOCKS_IN_CHUNK_EDGE]; BLOCKS_IN_CHUNK_EDGE],
+            }
+        });
+
+        group.cells[cell[1]][cell[0]] = true;
+    }
+
+    for (key, mut group) in groups {
+        for b in 0 .. BLOCKS_IN_CHUNK_EDGE {
+            let mut a = 0;
+
+            while a < BLOCKS_IN_CHUNK_EDGE {
+                if !group.cells[b][a] {
+                    a += 1;
+                    continue;
+                }
+
+                let width = group.cells[b][a ..]
+                    .iter()
+                    .take_while(|filled| **filled)
+                    .count();
+
+                let height = group.cells[b ..]
+                    .iter()
+                    .take_while(|row| row[a .. a + width].iter().all(|filled| *filled))
+                    .count();
+
+                for row in group.cells[b .. b + height].iter_mut() {
+                    row[a .. a + width].fill(false);
+                }
+
+                output.push(merged_quad(
+                    &group,
+                    key.normal_axis,
+                    [a, b],
+                    [width, height],
+                ));
+
+                a += width;
+            }
+        }
+    }
+
+    output
+}
@@ -0,0 +1,288 @@
+use crate::{
+    component::{
+        block::class::ClassBlockComponent,
+        block_class::model::ModelBlockClassComponent,
+        block_model::{
+            builder::BuilderBlockModelComponent,
+            culling::{
+                Culling,
+                CullingBlockModelComponent,
+            },
+        },
+    },
+    system::render::primitives::Quad,
+};
+use voxbrix_common::{
+    component::block::{
+        block_light::{
+            BlockLight,
+            BlockLightBlockComponent,
+        },
+        sky_light::{
+            SkyLight,
+            SkyLightBlockComponent,
+        },
+        BlocksVec,
+    },
+    entity::{
+        block::{
+            Block,
+            BLOCKS_IN_CHUNK_EDGE,
+        },
+        block_class::BlockClass,
+        chunk::Chunk,
+    },
+    system::block_surface::{
+        CullFlags,
+        Occluders,
+    },
+};
+
+/// Distance in chunks from the view center starting from which the level is used,
+/// level `n + 1` merges `2^(n + 1)` blocks along each axis into a single cell.
+const LEVEL_DISTANCES: [u32; 2] = [8, 16];
+
+const SIDE_OFFSETS: [[i32; 3]; 6] = [
+    [-1, 0, 0],
+    [1, 0, 0],
+    [0, -1, 0],
+    [0, 1, 0],
+    [0, 0, -1],
+    [0, 0, 1],
+];
+
+/// Level of detail of the chunk mesh, `0` is the full detail.
+pub fn level(view_center: &Chunk, chunk: &Chunk) -> usize {
+    if view_center.dimension != chunk.dimension {
+        return 0;
+    }
+
+    let distance = (0 .. 3)
+        .map(|i| view_center.position[i].abs_diff(chunk.position[i]))
+        .max()
+        .unwrap();
+
+    LEVEL_DISTANCES
+        .iter()
+        .take_while(|level_distance| distance >= **level_distance)
+        .count()
+}
+
+struct LodContext<'a> {
+    cell_size: usize,
+    class_bc: &'a ClassBlockComponent,
+    model_bcc: &'a ModelBlockClassComponent,
+    culling_bmc: &'a CullingBlockModelComponent,
+    sky_light_bc: &'a SkyLightBlockComponent,
+    block_light_bc: &'a BlockLightBlockComponent,
+}
+
+impl LodContext<'_> {
+    fn cell_blocks(&self, cell: [usize; 3]) -> impl Iterator<Item = Block> {
+        let size = self.cell_size;
+        let [x0, y0, z0] = cell.map(|i| i * size);
+
+        (0 .. size).rev().flat_map(move |z| {
+            (0 .. size).flat_map(move |y| {
+                (0 .. size).map(move |x| Block::from_coords([x0 + x, y0 + y, z0 + z]))
+            })
+        })
+    }
+
+    fn is_full(&self, classes: &BlocksVec<BlockClass>, block: Block) -> bool {
+        let culling = self
+            .model_bcc
+            .get(classes.get(block))
+            .and_then(|model| self.culling_bmc.get(model));
+
+        matches!(culling, Some(Culling::Full))
+    }
+
+    fn full_blocks(&self, classes: &BlocksVec<BlockClass>, cell: [usize; 3]) -> usize {
+        self.cell_blocks(cell)
+            .filter(|block| self.is_full(classes, *block))
+            .count()
+    }
+
+    /// The cell is solid if at least half of it is filled with full blocks.
+    /// The highest full block represents the cell.
+    fn representative(&self, classes: &BlocksVec<BlockClass>, cell: [usize; 3]) -> Option<Block> {
+        let volume = self.cell_size.pow(3);
+
+        if self.full_blocks(classes, cell) * 2 < volume {
+            return None;
+        }
+
+        self.cell_blocks(cell)
+            .find(|block| self.is_full(classes, *block))
+    }
+
+    fn light_levels(&self, chunk: &Chunk, cell: [usize; 3]) -> (SkyLight, BlockLight) {
+        let sky_light = self
+            .sky_light_bc
+            .get_chunk(chunk)
+            .and_then(|lights| {
+                self.cell_blocks(cell)
+                    .map(|block| *lights.get(block))
+                    .max_by_key(|light| light.value())
+            })
+            .unwrap_or(SkyLight::MIN);
+
+        let block_light = self
+            .block_light_bc
+            .get_chunk(chunk)
+            .and_then(|lights| {
+                self.cell_blocks(cell)
+                    .map(|block| *lights.get(block))
+                    .max_by_key(|light| light.value())
+            })
+            .unwrap_or(BlockLight::MIN);
+
+        (sky_light, block_light)
+    }
+}
+
+/// Builds the simplified chunk mesh, merging cubes of `2^level` blocks into single cells.
+/// Only blocks with the full culling are taken into account.
+///
+/// Faces on the chunk border are culled only by the completely filled neighbor cells,
+/// so the border works as a skirt that hides the gaps between meshes of different levels.
+pub fn build_chunk(
+    chunk: &Chunk,
+    level: usize,
+    class_bc: &ClassBlockComponent,
+    model_bcc: &ModelBlockClassComponent,
+    builder_bmc: &BuilderBlockModelComponent,
+    culling_bmc: &CullingBlockModelComponent,
+    sky_light_bc: &SkyLightBlockComponent,
+    block_light_bc: &BlockLightBlockComponent,
+) -> Vec<Quad> {
+    let context = LodContext {
+        cell_size: 1 << level,
+        class_bc,
+        model_bcc,
+        culling_bmc,
+        sky_light_bc,
+        block_light_bc,
+    };
+
+    let cells_in_edge = BLOCKS_IN_CHUNK_EDGE / context.cell_size;
+    let cell_volume = context.cell_size.pow(3);
+
+    let Some(this_chunk_class) = context.class_bc.get_chunk(chunk) else {
+        return Vec::new();
+    };
+
+    let cell_index = |[x, y, z]: [usize; 3]| x + y * cells_in_edge + z * cells_in_edge.pow(2);
+
+    let cells = (0 .. cells_in_edge)
+        .flat_map(|z| {
+            (0 .. cells_in_edge).flat_map(move |y| (0 .. cells_in_edge).map(move |x| [x, y, z]))
+        })
+        .map(|cell| context.representative(this_chunk_class, cell))
+        .collect::<Vec<_>>();
+
+    let mut quads = Vec::new();
+
+    for z in 0 .. cells_in_edge {
+        for y in 0 .. cells_in_edge {
+            for x in 0 .. cells_in_edge {
+                let cell = [x, y, z];
+
+                let Some(representative) = cells[cell_index(cell)] else {
+                    continue;
+                };
+
+                let Some(model_builder) = model_bcc
+                    .get(this_chunk_class.get(representative))
+                    .and_then(|model| builder_bmc.get(model))
+                else {
+                    continue;
+                };
+
+                let mut cull_flags = CullFlags::empty();
+                let mut sky_light_levels = [SkyLight::MIN; 6];
+                let mut block_light_levels = [BlockLight::MIN; 6];
+
+                for (side, offset) in SIDE_OFFSETS.iter().enumerate() {
+                    let neighbor = [0, 1, 2].map(|i| cell[i] as i32 + offset[i]);
+
+                    let inside = neighbor
+                        .iter()
+                        .all(|i| *i >= 0 && *i < cells_in_edge as i32);
+
+                    let (neighbor_chunk, neighbor_cell, visible) = if inside {
+                        let neighbor_cell = neighbor.map(|i| i as usize);
+
+                        (
+                            *chunk,
+                            neighbor_cell,
+                            cells[cell_index(neighbor_cell)].is_none(),
+                        )
+                    } else {
+                        let Some(neighbor_chunk) = chunk.checked_add(*offset) else {
+                            continue;
+                        };
+
+                        let neighbor_cell =
+                            neighbor.map(|i| i.rem_euclid(cells_in_edge as i32) as usize);
+
+                        // Unloaded neighbors cull the side, like in the full detail meshes
+                        let Some(neighbor_class) = context.class_bc.get_chunk(&neighbor_chunk)
+                        else {
+                            continue;
+                        };
+
+                        let visible =
+                            context.full_blocks(neighbor_class, neighbor_cell) < cell_volume;
+
+                        (neighbor_chunk, neighbor_cell, visible)
+                    };
+
+                    if !visible {
+                        continue;
+                    }
+
+                    cull_flags.insert(CullFlags::from_index(side));
+
+                    let (sky_light, block_light) =
+                        context.light_levels(&neighbor_chunk, neighbor_cell);
+
+                    sky_light_levels[side] = sky_light;
+                    block_light_levels[side] = block_light;
+                }
+
+                if cull_flags.is_empty() {
+                    continue;
+                }
+
+                let cell_size = context.cell_size as f32;
+                let cell_origin = cell.map(|i| (i * context.cell_size) as f32);
+
+                quads.extend(
+                    model_builder
+                        .build(
+                            chunk,
+                            Block::from_coords([0, 0, 0]),
+                            cull_flags,
+                            sky_light_levels,
+                            block_light_levels,
+                            Occluders::default(),
+                        )
+                        .map(|mut quad| {
+                            for vertex in quad.vertices.iter_mut() {
+                                for i in 0 .. 3 {
+                                    vertex.position[i] =
+                                        cell_origin[i] + vertex.position[i] * cell_size;
+                                }
+                            }
+
+                            quad
+                        }),
+                );
+            }
+        }
+    }
+
+    quads
+}
@@ -0,0 +1,141 @@
+use super::{
+    ChunkShard,
+    DirtySlabs,
+};
+use ahash::AHashMap;
+use flume::{
+    Receiver,
+    Sender,
+};
+use std::sync::{
+    atomic::{
+        AtomicBool,
+        Ordering,
+    },
+    Arc,
+};
+use voxbrix_common::entity::chunk::Chunk;
+
+/// Tells the running build that its result is no longer needed.
+#[derive(Clone)]
+pub struct Cancellation(Arc<AtomicBool>);
+
+impl Cancellation {
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+struct InFlight {
+    generation: u64,
+    slabs: DirtySlabs,
+    cancellation: Cancellation,
+}
+
+struct Finished {
+    chunk: Chunk,
+    generation: u64,
+    shard: Option<ChunkShard>,
+}
+
+/// Builds the chunk meshes on the rayon thread pool, at most `max_in_flight` at once.
+/// The build of a chunk is cancelled when the chunk is dropped or its block data changes,
+/// the results of the cancelled builds are discarded.
+pub struct MeshBuildScheduler {
+    max_in_flight: usize,
+    next_generation: u64,
+    in_flight: AHashMap<Chunk, InFlight>,
+    finished_tx: Sender<Finished>,
+    finished_rx: Receiver<Finished>,
+}
+
+impl MeshBuildScheduler {
+    /// Leaves a couple of threads for the rest of the game.
+    pub fn new() -> Self {
+        let (finished_tx, finished_rx) = flume::unbounded();
+
+        Self {
+            max_in_flight: rayon::current_num_threads().saturating_sub(2).max(1),
+            next_generation: 0,
+            in_flight: AHashMap::new(),
+            finished_tx,
+            finished_rx,
+        }
+    }
+
+    /// Number of the builds that could be started now.
+    pub fn available(&self) -> usize {
+        self.max_in_flight.saturating_sub(self.in_flight.len())
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    pub fn has_finished(&self) -> bool {
+        !self.finished_rx.is_empty()
+    }
+
+    /// The chunk must have no build in flight, the previous one must be cancelled first.
+    /// `build` returns `None` if it has noticed the cancellation.
+    pub fn spawn<F>(&mut self, chunk: Chunk, slabs: DirtySlabs, build: F)
+    where
+        F: FnOnce(&Cancellation) -> Option<ChunkShard> + Send + 'static,
+    {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+
+        let cancellation = Cancellation(Arc::new(AtomicBool::new(false)));
+
+        self.in_flight.insert(
+            chunk,
+            InFlight {
+                generation,
+                slabs,
+                cancellation: cancellation.clone(),
+            },
+        );
+
+        let finished_tx = self.finished_tx.clone();
+
+        rayon::spawn(move || {
+            let shard = if cancellation.is_cancelled() {
+                None
+            } else {
+                build(&cancellation)
+            };
+
+            let _ = finished_tx.send(Finished {
+                chunk,
+                generation,
+                shard,
+            });
+        });
+    }
+
+    /// Returns the slabs the cancelled build was rebuilding, if there was one.
+    pub fn cancel(&mut self, chunk: &Chunk) -> Option<DirtySlabs> {
+        let in_flight = self.in_flight.remove(chunk)?;
+        in_flight.cancellation.0.store(true, Ordering::Relaxed);
+
+        Some(in_flight.slabs)
+    }
+
+    /// Shards built since the previous call, except the cancelled ones.
+    pub fn take_finished(&mut self) -> Vec<(Chunk, ChunkShard)> {
+        self.finished_rx
+            .try_iter()
+            .filter_map(|finished| {
+                match self.in_flight.get(&finished.chunk) {
+                    Some(in_flight) if in_flight.generation == finished.generation => {
+                        self.in_flight.remove(&finished.chunk);
+                    },
+                    // Cancelled, maybe already rebuilding with the newer data
+                    _ => return None,
+                }
+
+                Some((finished.chunk, finished.shard?))
+            })
+            .collect()
+    }
+}
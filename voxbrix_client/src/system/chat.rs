@@ -0,0 +1,130 @@
+use egui::{
+    Align2,
+    Context,
+    Key,
+    ScrollArea,
+};
+use std::{
+    collections::VecDeque,
+    mem,
+};
+use voxbrix_common::messages::server::{
+    ChatScope,
+    MAX_CHAT_MESSAGE_LENGTH,
+};
+
+/// Number of received messages kept in the chat history.
+const HISTORY_LENGTH: usize = 100;
+
+struct ChatLine {
+    /// `None` for the notices of the client itself.
+    sender: Option<String>,
+    text: String,
+}
+
+pub struct ChatSystem {
+    history: VecDeque<ChatLine>,
+    input: String,
+    global: bool,
+    input_open: bool,
+}
+
+impl ChatSystem {
+    pub fn new() -> Self {
+        Self {
+            history: VecDeque::with_capacity(HISTORY_LENGTH),
+            input: String::new(),
+            global: false,
+            input_open: false,
+        }
+    }
+
+    pub fn is_input_open(&self) -> bool {
+        self.input_open
+    }
+
+    pub fn open_input(&mut self) {
+        self.input_open = true;
+    }
+
+    pub fn close_input(&mut self) {
+        self.input_open = false;
+    }
+
+    pub fn add_message(&mut self, sender: String, text: String) {
+        self.push_line(ChatLine {
+            sender: Some(sender),
+            text,
+        });
+    }
+
+    /// Add a line that is not a player message.
+    pub fn add_notice(&mut self, text: String) {
+        self.push_line(ChatLine { sender: None, text });
+    }
+
+    fn push_line(&mut self, line: ChatLine) {
+        if self.history.len() >= HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(line);
+    }
+
+    /// Show the chat panel.
+    /// Returns the message if the player has submitted one.
+    pub fn interface(&mut self, ctx: &Context) -> Option<(ChatScope, String)> {
+        let mut submitted = None;
+
+        egui::Window::new("Chat")
+            .anchor(Align2::LEFT_BOTTOM, [8.0, -8.0])
+            .collapsible(false)
+            .resizable(false)
+            .title_bar(false)
+            .show(ctx, |ui| {
+                ScrollArea::vertical()
+                    .max_height(200.0)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for line in self.history.iter() {
+                            match &line.sender {
+                                Some(sender) => ui.label(format!("<{}> {}", sender, line.text)),
+                                None => ui.label(format!("* {}", line.text)),
+                            };
+                        }
+                    });
+
+                if !self.input_open {
+                    return;
+                }
+
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.global, "Global");
+
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut self.input)
+                            .char_limit(MAX_CHAT_MESSAGE_LENGTH),
+                    );
+
+                    if response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                        let text = mem::take(&mut self.input);
+                        let scope = if self.global {
+                            ChatScope::Global
+                        } else {
+                            ChatScope::Local
+                        };
+
+                        if !text.trim().is_empty() {
+                            submitted = Some((scope, text));
+                        }
+
+                        self.input_open = false;
+                    } else {
+                        response.request_focus();
+                    }
+                });
+            });
+
+        submitted
+    }
+}
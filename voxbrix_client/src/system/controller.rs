@@ -1,6 +1,14 @@
-use crate::component::actor::{
-    orientation::OrientationActorComponent,
-    velocity::VelocityActorComponent,
+#[cfg(feature = "gamepad")]
+use crate::window::GamepadAxis;
+use crate::{
+    component::actor::{
+        orientation::OrientationActorComponent,
+        velocity::VelocityActorComponent,
+    },
+    settings::{
+        key_bindings::InputAction,
+        Settings,
+    },
 };
 use std::{
     f32::consts::{
@@ -23,16 +31,6 @@ use voxbrix_common::{
         Vec3F32,
     },
 };
-use winit::{
-    event::{
-        ElementState,
-        KeyEvent,
-    },
-    keyboard::{
-        KeyCode,
-        PhysicalKey,
-    },
-};
 
 const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
 const PI_2: f32 = PI * 2.0;
@@ -49,12 +47,17 @@ pub struct DirectControl {
     pitch: f32,
     rotate_horizontal: f32,
     rotate_vertical: f32,
+    /// Raw left stick position, movement.
+    #[cfg(feature = "gamepad")]
+    move_stick: [f32; 2],
+    /// Raw right stick position, camera.
+    #[cfg(feature = "gamepad")]
+    look_stick: [f32; 2],
     speed: f32,
-    sensitivity: f32,
 }
 
 impl DirectControl {
-    pub fn new(actor: Actor, speed: f32, sensitivity: f32) -> Self {
+    pub fn new(actor: Actor, speed: f32) -> Self {
         Self {
             actor,
             move_left: 0.0,
@@ -67,55 +70,41 @@ impl DirectControl {
             pitch: 0.0,
             rotate_horizontal: 0.0,
             rotate_vertical: 0.0,
+            #[cfg(feature = "gamepad")]
+            move_stick: [0.0, 0.0],
+            #[cfg(feature = "gamepad")]
+            look_stick: [0.0, 0.0],
             speed,
-            sensitivity,
         }
     }
 
-    pub fn process_keyboard(&mut self, input: &KeyEvent) -> bool {
-        // TODO use scancodes?
-        let KeyEvent {
-            state,
-            physical_key,
-            ..
-        } = input;
-        let key = match physical_key {
-            PhysicalKey::Code(c) => c,
-            PhysicalKey::Unidentified(_) => return false,
+    /// Returns `true` if the action is a movement one.
+    pub fn process_action(&mut self, action: InputAction, pressed: bool) -> bool {
+        let amount = if pressed { 1.0 } else { 0.0 };
+
+        let target = match action {
+            InputAction::MoveForward => &mut self.move_forward,
+            InputAction::MoveBackward => &mut self.move_backward,
+            InputAction::MoveLeft => &mut self.move_left,
+            InputAction::MoveRight => &mut self.move_right,
+            InputAction::MoveUp => &mut self.move_up,
+            InputAction::MoveDown => &mut self.move_down,
+            _ => return false,
         };
 
-        let amount = if *state == ElementState::Pressed {
-            1.0
-        } else {
-            0.0
-        };
+        *target = amount;
 
-        match key {
-            KeyCode::KeyW | KeyCode::ArrowUp => {
-                self.move_forward = amount;
-                true
-            },
-            KeyCode::KeyS | KeyCode::ArrowDown => {
-                self.move_backward = amount;
-                true
-            },
-            KeyCode::KeyA | KeyCode::ArrowLeft => {
-                self.move_left = amount;
-                true
-            },
-            KeyCode::KeyD | KeyCode::ArrowRight => {
-                self.move_right = amount;
-                true
-            },
-            KeyCode::Space => {
-                self.move_up = amount;
-                true
-            },
-            KeyCode::ShiftLeft => {
-                self.move_down = amount;
-                true
-            },
-            _ => false,
+        true
+    }
+
+    #[cfg(feature = "gamepad")]
+    pub fn process_gamepad_axis(&mut self, axis: GamepadAxis, value: f32) {
+        match axis {
+            GamepadAxis::LeftStickX => self.move_stick[0] = value,
+            GamepadAxis::LeftStickY => self.move_stick[1] = value,
+            GamepadAxis::RightStickX => self.look_stick[0] = value,
+            GamepadAxis::RightStickY => self.look_stick[1] = value,
+            _ => {},
         }
     }
 
@@ -124,9 +113,28 @@ impl DirectControl {
         self.rotate_vertical = vertical;
     }
 
+    /// Movement and camera stick positions past the dead zone,
+    /// and the camera rotation speed at the full deflection.
+    #[cfg(feature = "gamepad")]
+    fn sticks(&self, settings: &Settings) -> ([f32; 2], [f32; 2], f32) {
+        let gamepad = &settings.gamepad;
+
+        (
+            gamepad.apply_dead_zone(self.move_stick),
+            gamepad.apply_dead_zone(self.look_stick),
+            gamepad.camera_sensitivity,
+        )
+    }
+
+    #[cfg(not(feature = "gamepad"))]
+    fn sticks(&self, _settings: &Settings) -> ([f32; 2], [f32; 2], f32) {
+        ([0.0, 0.0], [0.0, 0.0], 0.0)
+    }
+
     pub fn process(
         &mut self,
         dt: Duration,
+        settings: &Settings,
         velocity_component: &mut VelocityActorComponent,
         orientation_component: &mut OrientationActorComponent,
         snapshot: Snapshot,
@@ -140,8 +148,11 @@ impl DirectControl {
             .unwrap();
 
         let dt = dt.as_secs_f32();
-        self.yaw += self.rotate_horizontal * self.sensitivity * dt;
-        self.pitch += -self.rotate_vertical * self.sensitivity * dt;
+        let sensitivity = settings.mouse_sensitivity;
+        let ([stick_x, stick_y], [look_x, look_y], camera_sensitivity) = self.sticks(settings);
+
+        self.yaw += (self.rotate_horizontal * sensitivity + look_x * camera_sensitivity) * dt;
+        self.pitch += (-self.rotate_vertical * sensitivity + look_y * camera_sensitivity) * dt;
 
         self.rotate_horizontal = 0.0;
         self.rotate_vertical = 0.0;
@@ -170,14 +181,14 @@ impl DirectControl {
         } else {
             let right = Vec3F32::UP.cross(forward);
 
-            forward * (self.move_forward - self.move_backward)
-                + right * (self.move_right - self.move_left)
+            forward * (self.move_forward - self.move_backward + stick_y)
+                + right * (self.move_right - self.move_left + stick_x)
                 + Vec3F32::UP * (self.move_up - self.move_down)
         };
 
+        // Not normalized, partially deflected stick means slower movement
         actor_velocity.update(Velocity {
-            vector: Some(direction)
-                .map(|d| d.normalize())
+            vector: Some(direction.clamp_length_max(1.0))
                 .filter(|d| !d.is_nan())
                 .map(|d| d * self.speed)
                 .unwrap_or(Vec3F32::new(0.0, 0.0, 0.0)),
@@ -0,0 +1,161 @@
+use crate::system::profiler::{
+    Profiler,
+    Section,
+    FRAME_WINDOW,
+};
+use egui::{
+    pos2,
+    vec2,
+    Align2,
+    Color32,
+    Context,
+    Rect,
+    Sense,
+    Stroke,
+};
+use std::time::Duration;
+use voxbrix_common::{
+    component::actor::position::Position,
+    entity::{
+        block::BLOCKS_IN_CHUNK_EDGE_F32,
+        chunk::DimensionKind,
+    },
+    LabelMap,
+};
+use voxbrix_protocol::ConnectionStats;
+
+/// Frame time at the top of the graph, the longer frames are cut.
+const GRAPH_MAX_FRAME_TIME: Duration = Duration::from_millis(50);
+/// Frame time marked on the graph, 60 frames per second.
+const GRAPH_TARGET_FRAME_TIME: Duration = Duration::from_micros(16_667);
+const GRAPH_HEIGHT: f32 = 60.0;
+
+pub struct DebugOverlaySystem;
+
+impl DebugOverlaySystem {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Show the frame time graph, the game loop timings, the chunk mesh queue,
+    /// the connection statistics and the position of the player.
+    pub fn interface(
+        &self,
+        ctx: &Context,
+        profiler: &Profiler,
+        mesh_queue_length: usize,
+        connection_stats: &ConnectionStats,
+        position: Option<&Position>,
+        dimension_kind_label_map: &LabelMap<DimensionKind>,
+    ) {
+        egui::Window::new("Debug")
+            .anchor(Align2::LEFT_TOP, [8.0, 8.0])
+            .collapsible(false)
+            .resizable(false)
+            .title_bar(false)
+            .show(ctx, |ui| {
+                let frame_time = profiler.average_frame_time();
+
+                ui.label(format!(
+                    "{:.0} fps, {:.2}ms",
+                    1.0 / frame_time.as_secs_f64().max(f64::EPSILON),
+                    ms(frame_time),
+                ));
+
+                let (response, painter) =
+                    ui.allocate_painter(vec2(FRAME_WINDOW as f32, GRAPH_HEIGHT), Sense::hover());
+                let rect = response.rect;
+
+                let height = |time: Duration| {
+                    (time.as_secs_f32() / GRAPH_MAX_FRAME_TIME.as_secs_f32()).min(1.0)
+                        * GRAPH_HEIGHT
+                };
+
+                painter.rect_filled(rect, 0.0, Color32::from_black_alpha(128));
+
+                for (index, time) in profiler.frame_times().enumerate() {
+                    let x = rect.left() + index as f32;
+                    let color = if time > GRAPH_TARGET_FRAME_TIME {
+                        Color32::RED
+                    } else {
+                        Color32::GREEN
+                    };
+
+                    painter.rect_filled(
+                        Rect::from_min_max(
+                            pos2(x, rect.bottom() - height(time)),
+                            pos2(x + 1.0, rect.bottom()),
+                        ),
+                        0.0,
+                        color,
+                    );
+                }
+
+                painter.hline(
+                    rect.x_range(),
+                    rect.bottom() - height(GRAPH_TARGET_FRAME_TIME),
+                    Stroke::new(1.0, Color32::YELLOW),
+                );
+
+                for section in Section::ALL {
+                    ui.label(format!(
+                        "{}: {:.2}ms",
+                        section.name(),
+                        ms(profiler.average_section(section))
+                    ));
+                }
+
+                ui.separator();
+
+                ui.label(format!("chunk mesh queue: {}", mesh_queue_length));
+
+                ui.label(format!(
+                    "rtt: {}",
+                    connection_stats
+                        .rtt
+                        .map(|rtt| format!("{:.1}ms", ms(rtt)))
+                        .unwrap_or_else(|| "unknown".to_owned())
+                ));
+
+                ui.label(format!(
+                    "sent: {} KiB, received: {} KiB, retransmits: {}",
+                    connection_stats.bytes_sent / 1024,
+                    connection_stats.bytes_received / 1024,
+                    connection_stats.retransmits,
+                ));
+
+                ui.separator();
+
+                let Some(position) = position else {
+                    ui.label("no position");
+                    return;
+                };
+
+                let [chunk_x, chunk_y, chunk_z] = position.chunk.position;
+                let block =
+                    |chunk: i32, offset: f32| chunk as f32 * BLOCKS_IN_CHUNK_EDGE_F32 + offset;
+
+                ui.label(format!(
+                    "position: {:.2} {:.2} {:.2}",
+                    block(chunk_x, position.offset.x),
+                    block(chunk_y, position.offset.y),
+                    block(chunk_z, position.offset.z),
+                ));
+
+                ui.label(format!(
+                    "chunk: {} {} {} in {} (phase {})",
+                    chunk_x,
+                    chunk_y,
+                    chunk_z,
+                    dimension_kind_label_map
+                        .get_label(&position.chunk.dimension.kind)
+                        .unwrap_or("unknown"),
+                    position.chunk.dimension.phase,
+                ));
+            });
+    }
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,56 @@
+use egui::{
+    Align2,
+    Context,
+};
+use std::time::Duration;
+use voxbrix_common::{
+    component::actor::effect::ActiveEffects,
+    entity::{
+        effect::Effect,
+        snapshot::Snapshot,
+    },
+    LabelMap,
+};
+
+pub struct EffectHudSystem;
+
+impl EffectHudSystem {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Show the effects active on the player with their remaining time.
+    /// `server_snapshot` is the last snapshot received from the server,
+    /// effects expire on the server snapshots that advance every `server_process_interval`.
+    pub fn interface(
+        &self,
+        ctx: &Context,
+        effects: Option<&ActiveEffects>,
+        effect_label_map: &LabelMap<Effect>,
+        server_snapshot: Snapshot,
+        server_process_interval: Duration,
+    ) {
+        let Some(effects) = effects.filter(|effects| !effects.is_empty()) else {
+            return;
+        };
+
+        egui::Window::new("Effects")
+            .anchor(Align2::RIGHT_TOP, [-8.0, 8.0])
+            .collapsible(false)
+            .resizable(false)
+            .title_bar(false)
+            .show(ctx, |ui| {
+                for (effect, instance) in effects.iter() {
+                    let remaining = server_process_interval
+                        .saturating_mul(instance.remaining_ticks(server_snapshot) as u32);
+
+                    ui.label(format!(
+                        "{} {} ({}s)",
+                        effect_label_map.get_label(&effect).unwrap_or("unknown"),
+                        instance.magnitude,
+                        remaining.as_secs(),
+                    ));
+                }
+            });
+    }
+}
@@ -0,0 +1,43 @@
+use egui::{
+    Align2,
+    Context,
+    ProgressBar,
+};
+use voxbrix_common::component::actor::health::Health;
+
+/// Width of the health bar, in points.
+const BAR_WIDTH: f32 = 200.0;
+
+pub struct HealthBarSystem;
+
+impl HealthBarSystem {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Show the health bar of the player at the bottom of the screen.
+    pub fn interface(&self, ctx: &Context, health: Option<&Health>) {
+        let Some(health) = health else {
+            return;
+        };
+
+        let fraction = if health.max == 0 {
+            0.0
+        } else {
+            health.current as f32 / health.max as f32
+        };
+
+        egui::Window::new("Health")
+            .anchor(Align2::CENTER_BOTTOM, [0.0, -8.0])
+            .collapsible(false)
+            .resizable(false)
+            .title_bar(false)
+            .show(ctx, |ui| {
+                ui.add(
+                    ProgressBar::new(fraction)
+                        .desired_width(BAR_WIDTH)
+                        .text(format!("{} / {}", health.current, health.max)),
+                );
+            });
+    }
+}
@@ -0,0 +1,155 @@
+use egui::{
+    Context,
+    Grid,
+};
+use voxbrix_common::{
+    component::actor::inventory::Inventory,
+    entity::{
+        item_class::ItemClass,
+        recipe::Recipe,
+    },
+    system::recipe_loading::{
+        Ingredient,
+        RecipeRegistry,
+    },
+    LabelMap,
+};
+
+/// Number of slots in a single inventory row.
+const ROW_LENGTH: usize = 9;
+
+pub enum InventoryRequest {
+    MoveItem { from: u32, to: u32 },
+    Craft(Recipe),
+}
+
+fn describe_ingredients(
+    ingredients: &[Ingredient],
+    item_class_label_map: &LabelMap<ItemClass>,
+) -> String {
+    ingredients
+        .iter()
+        .map(|ingredient| {
+            format!(
+                "{} {}",
+                ingredient.amount,
+                item_class_label_map
+                    .get_label(&ingredient.item_class)
+                    .unwrap_or("unknown")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub struct InventorySystem {
+    selected: Option<usize>,
+}
+
+impl InventorySystem {
+    pub fn new() -> Self {
+        Self { selected: None }
+    }
+
+    /// Show the inventory window.
+    /// Clicking a slot selects it, clicking another slot afterwards moves the selected stack there.
+    /// Recipes are listed below the slots, each with a button to craft it.
+    /// Returns the request if the player has asked to move a stack or to craft a recipe.
+    pub fn interface(
+        &mut self,
+        ctx: &Context,
+        open: &mut bool,
+        inventory: Option<&Inventory>,
+        item_class_label_map: &LabelMap<ItemClass>,
+        recipe_registry: &RecipeRegistry,
+    ) -> Option<InventoryRequest> {
+        if !*open {
+            self.selected = None;
+            return None;
+        }
+
+        let mut request = None;
+
+        egui::Window::new("Inventory")
+            .open(open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let Some(inventory) = inventory else {
+                    ui.label("No inventory");
+                    return;
+                };
+
+                Grid::new("inventory_slots").show(ui, |ui| {
+                    for (slot, stack) in inventory.slots().iter().enumerate() {
+                        let text = match stack {
+                            Some(stack) => {
+                                format!(
+                                    "{}\n{}",
+                                    item_class_label_map
+                                        .get_label(&stack.class)
+                                        .unwrap_or("unknown"),
+                                    stack.amount
+                                )
+                            },
+                            None => "\n".to_owned(),
+                        };
+
+                        let is_selected = self.selected == Some(slot);
+
+                        if ui.selectable_label(is_selected, text).clicked() {
+                            match self.selected.take() {
+                                Some(from) if from != slot => {
+                                    request = Some(InventoryRequest::MoveItem {
+                                        from: from as u32,
+                                        to: slot as u32,
+                                    });
+                                },
+                                Some(_) => {},
+                                None if stack.is_some() => self.selected = Some(slot),
+                                None => {},
+                            }
+                        }
+
+                        if (slot + 1) % ROW_LENGTH == 0 {
+                            ui.end_row();
+                        }
+                    }
+                });
+
+                if recipe_registry.iter().len() == 0 {
+                    return;
+                }
+
+                ui.separator();
+
+                Grid::new("inventory_recipes").show(ui, |ui| {
+                    for (recipe, data) in recipe_registry.iter() {
+                        ui.label(
+                            recipe_registry
+                                .label_map()
+                                .get_label(&recipe)
+                                .unwrap_or("unknown"),
+                        );
+                        ui.label(format!(
+                            "{} -> {}",
+                            describe_ingredients(&data.inputs, item_class_label_map),
+                            describe_ingredients(&data.outputs, item_class_label_map),
+                        ));
+
+                        let can_craft = data.craft(&mut inventory.clone());
+
+                        if ui
+                            .add_enabled(can_craft, egui::Button::new("Craft"))
+                            .clicked()
+                        {
+                            request = Some(InventoryRequest::Craft(recipe));
+                        }
+
+                        ui.end_row();
+                    }
+                });
+            });
+
+        request
+    }
+}
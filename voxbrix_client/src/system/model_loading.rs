@@ -35,12 +35,9 @@ pub struct ModelLoadingSystem {
 }
 
 impl ModelLoadingSystem {
-    pub async fn load_data(
-        list_path: &'static str,
-        path_prefix: &'static str,
-    ) -> Result<Self, Error> {
+    pub async fn load_data(model_list: List, path_prefix: &'static str) -> Result<Self, Error> {
         task::spawn_blocking(move || {
-            let model_list = read_data_file::<List>(list_path)?.list;
+            let model_list = model_list.list;
 
             let mut components = BTreeMap::new();
 
@@ -4,7 +4,6 @@ use crate::component::actor::{
     target_orientation::TargetOrientationActorComponent,
     target_position::TargetPositionActorComponent,
     TargetQueue,
-    Writable,
 };
 use std::time::{
     Duration,
@@ -23,15 +22,60 @@ use voxbrix_common::{
     math::Vec3F32,
 };
 
-const SERVER_TICK_INTERVAL: Duration = Duration::from_millis(50);
-pub const TARGET_QUEUE_LENGTH: usize = 2;
-pub const TARGET_QUEUE_LENGTH_U32: u32 = TARGET_QUEUE_LENGTH as u32;
+pub const SERVER_TICK_INTERVAL: Duration = Duration::from_millis(50);
+/// Number of the last server values buffered per actor.
+pub const TARGET_QUEUE_LENGTH: usize = 8;
+pub const DEFAULT_INTERPOLATION_DELAY: Duration = Duration::from_millis(100);
+/// For how long the movement is continued after the last received value.
+const MAX_EXTRAPOLATION: Duration = Duration::from_millis(200);
+
+trait Interpolate: Copy {
+    /// `completion` of `0.0` gives `self` and `1.0` gives `other`.
+    /// Values above `1.0` extrapolate the change.
+    fn interpolate(&self, other: &Self, completion: f32) -> Self;
+}
+
+impl Interpolate for Position {
+    fn interpolate(&self, other: &Self, completion: f32) -> Self {
+        if self.chunk.dimension != other.chunk.dimension {
+            return *other;
+        }
+
+        let Some(chunk_difference) = other.chunk.position.checked_sub(self.chunk.position) else {
+            return *other;
+        };
+
+        let chunk_offset: Vec3F32 = chunk_difference
+            .map(|i| i as f32 * BLOCKS_IN_CHUNK_EDGE_F32)
+            .into();
+
+        let from_self_to_other = chunk_offset + (other.offset - self.offset);
+
+        Position {
+            chunk: other.chunk,
+            offset: other.offset - from_self_to_other * (1.0 - completion),
+        }
+    }
+}
 
-pub struct MovementInterpolationSystem;
+impl Interpolate for Orientation {
+    fn interpolate(&self, other: &Self, completion: f32) -> Self {
+        // Rotation is not extrapolated
+        Orientation {
+            rotation: self.rotation.lerp(other.rotation, completion.min(1.0)),
+        }
+    }
+}
+
+/// Moves the remote actors through the buffered server values,
+/// rendering them `delay` behind the latest received value.
+pub struct MovementInterpolationSystem {
+    delay: Duration,
+}
 
 impl MovementInterpolationSystem {
-    pub fn new() -> Self {
-        Self
+    pub fn new(delay: Duration) -> Self {
+        Self { delay }
     }
 
     pub fn process(
@@ -42,104 +86,67 @@ impl MovementInterpolationSystem {
         orientation_ac: &mut OrientationActorComponent,
         snapshot: Snapshot,
     ) {
-        let current_time = Instant::now() - SERVER_TICK_INTERVAL * TARGET_QUEUE_LENGTH_U32;
+        let Some(render_time) = Instant::now().checked_sub(self.delay) else {
+            return;
+        };
 
         for (actor, target_queue) in target_position_ac.iter_mut() {
-            let mut position = match position_ac.get_writable(&actor, snapshot) {
-                Some(v) => v,
-                None => continue,
+            let Some(value) = Self::sample(target_queue, render_time) else {
+                continue;
             };
 
-            if let Some((target_position, time_left)) =
-                Self::find_next_target(target_queue, &mut position, current_time)
-            {
-                let completion = (SERVER_TICK_INTERVAL - time_left).as_secs_f32()
-                    / SERVER_TICK_INTERVAL.as_secs_f32();
-
-                let starting = target_queue.starting;
-
-                if target_position.chunk.dimension != starting.chunk.dimension {
-                    position.update(target_position.clone());
-                    continue;
-                }
-
-                let chunk_offset: Vec3F32 = target_position
-                    .chunk
-                    .position
-                    .checked_sub(starting.chunk.position)
-                    .expect("should not fail")
-                    .map(|i| i as f32 * BLOCKS_IN_CHUNK_EDGE_F32)
-                    .into();
-
-                let from_start_to_target =
-                    chunk_offset + (target_position.offset - starting.offset);
-
-                let position_offset = (target_position.offset - from_start_to_target)
-                    .lerp(target_position.offset, completion);
-
-                position.update(Position {
-                    chunk: target_position.chunk,
-                    offset: position_offset,
-                });
+            if let Some(mut position) = position_ac.get_writable(&actor, snapshot) {
+                position.update(value);
             }
         }
 
         for (actor, target_queue) in target_orientation_ac.iter_mut() {
-            let mut orientation = match orientation_ac.get_writable(&actor, snapshot) {
-                Some(v) => v,
-                None => continue,
+            let Some(value) = Self::sample(target_queue, render_time) else {
+                continue;
             };
 
-            if let Some((target_orientation, time_left)) =
-                Self::find_next_target(target_queue, &mut orientation, current_time)
-            {
-                let completion = (SERVER_TICK_INTERVAL - time_left).as_secs_f32()
-                    / SERVER_TICK_INTERVAL.as_secs_f32();
-
-                let rotation = target_queue
-                    .starting
-                    .rotation
-                    .lerp(target_orientation.rotation, completion);
-
-                orientation.update(Orientation { rotation });
+            if let Some(mut orientation) = orientation_ac.get_writable(&actor, snapshot) {
+                orientation.update(value);
             }
         }
     }
 
-    /// Returns the next target and the time left to reach it.
-    fn find_next_target<T>(
-        target_queue: &mut TargetQueue<T>,
-        value: &mut Writable<T>,
-        current_time: Instant,
-    ) -> Option<(T, Duration)>
+    /// Value of the queue at the given time.
+    /// Drops the targets that are no longer needed for the interpolation.
+    fn sample<T>(target_queue: &mut TargetQueue<T>, time: Instant) -> Option<T>
     where
-        T: PartialEq + Copy,
+        T: Interpolate,
     {
-        let TargetQueue {
-            starting,
-            target_queue,
-        } = target_queue;
-
-        while let Some(target_orientation) = target_queue.first().copied() {
-            let time_left = target_orientation
-                .reach_time
-                .saturating_duration_since(current_time);
-
-            if !time_left.is_zero() {
-                if time_left <= SERVER_TICK_INTERVAL {
-                    // This target is NOT too far in the future
-                    return Some((target_orientation.value, time_left));
-                }
-
-                break;
-            }
+        let targets = &mut target_queue.target_queue;
+
+        // Keep a single target before the time to interpolate from
+        while targets.len() > 2 && targets[1].time <= time {
+            targets.remove(0);
+        }
+
+        let first = targets.first()?;
 
-            // This target is already reached
-            *starting = target_orientation.value;
-            value.update(*starting);
-            target_queue.pop_at(0);
+        if time <= first.time {
+            return Some(first.value);
         }
 
-        None
+        let Some(second) = targets.get(1) else {
+            return Some(first.value);
+        };
+
+        let span = second.time.saturating_duration_since(first.time);
+
+        if span.is_zero() {
+            return Some(second.value);
+        }
+
+        // Past the last target, the movement is continued for a short while
+        let elapsed = time
+            .saturating_duration_since(first.time)
+            .min(span + MAX_EXTRAPOLATION);
+
+        let completion = elapsed.as_secs_f32() / span.as_secs_f32();
+
+        Some(first.value.interpolate(&second.value, completion))
     }
 }
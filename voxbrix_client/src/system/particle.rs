@@ -0,0 +1,471 @@
+use crate::{
+    assets::PARTICLE_EMITTERS_PATH,
+    component::{
+        actor::{
+            orientation::OrientationActorComponent,
+            position::PositionActorComponent,
+        },
+        block::class::ClassBlockComponent,
+        block_class::model::ModelBlockClassComponent,
+        block_model::builder::BuilderBlockModelComponent,
+        texture::location::LocationTextureComponent,
+    },
+    entity::texture::Texture,
+    system::render::primitives::{
+        Quad,
+        Vertex,
+    },
+};
+use anyhow::Error;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    time::{
+        Duration,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+use voxbrix_common::{
+    component::{
+        actor::position::Position,
+        block::{
+            block_light::{
+                BlockLight,
+                BlockLightBlockComponent,
+            },
+            sky_light::{
+                SkyLight,
+                SkyLightBlockComponent,
+            },
+        },
+        block_class::collision::CollisionBlockClassComponent,
+    },
+    entity::{
+        actor::Actor,
+        block::Block,
+        block_class::BlockClass,
+        chunk::Chunk,
+    },
+    math::{
+        Round,
+        Vec3F32,
+    },
+    read_data_file,
+    LabelMap,
+};
+
+/// Part of the side texture shown on a single particle, from 0 to 1.
+const PARTICLE_TEXTURE_PART: f32 = 0.25;
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type")]
+enum ParticleTextureDescriptor {
+    /// Texture of the block the particles are emitted for.
+    Block,
+    /// Block texture by label.
+    Texture { label: String },
+}
+
+#[derive(Deserialize, Debug)]
+struct AmbientEmissionDescriptor {
+    /// Particles per second.
+    rate: f32,
+    /// Particles appear within this distance around the player.
+    radius: f32,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ParticleEmitterDescriptor {
+    count: u32,
+    /// Lifetime range in seconds.
+    lifetime: [f32; 2],
+    /// Maximum initial speed in blocks per second.
+    speed: f32,
+    /// Downward acceleration in blocks per second squared.
+    gravity: f32,
+    /// Edge length in blocks.
+    size: f32,
+    texture: ParticleTextureDescriptor,
+    /// Emitted around the player all the time if present.
+    ambient: Option<AmbientEmissionDescriptor>,
+}
+
+#[derive(Clone, Copy)]
+struct ParticleTexture {
+    index: u32,
+    /// Texture positions of the top left, top right, bottom right and bottom left corners.
+    coords: [[f32; 2]; 4],
+}
+
+enum EmitterTexture {
+    Block,
+    Fixed(ParticleTexture),
+}
+
+struct ParticleEmitter {
+    descriptor: ParticleEmitterDescriptor,
+    texture: EmitterTexture,
+}
+
+struct Particle {
+    position: Position,
+    velocity: Vec3F32,
+    gravity: f32,
+    size: f32,
+    texture: ParticleTexture,
+    /// Seconds left.
+    lifetime: f32,
+}
+
+/// Small and fast generator for the particle spread, the quality does not matter here.
+struct Rng(u64);
+
+impl Rng {
+    fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn range(&mut self, [min, max]: [f32; 2]) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    /// Random vector within the sphere of the given radius.
+    fn in_sphere(&mut self, radius: f32) -> Vec3F32 {
+        loop {
+            let vector = Vec3F32::new(
+                self.range([-1.0, 1.0]),
+                self.range([-1.0, 1.0]),
+                self.range([-1.0, 1.0]),
+            );
+
+            if vector.length_squared() <= 1.0 {
+                return vector * radius;
+            }
+        }
+    }
+}
+
+/// Short-living textured quads, emitted by the block changes, the scripts
+/// and the ambient emitters around the player.
+/// Number of the particles alive is limited by `max_particles`,
+/// the new ones are dropped once the limit is reached.
+pub struct ParticleSystem {
+    emitters: BTreeMap<String, ParticleEmitter>,
+    particles: Vec<Particle>,
+    /// Fractional ambient particles carried over to the next frame, by the emitter label.
+    ambient_remainders: BTreeMap<String, f32>,
+    quads: Vec<Quad>,
+    max_particles: usize,
+    rng: Rng,
+}
+
+impl ParticleSystem {
+    pub fn load(
+        max_particles: usize,
+        texture_label_map: &LabelMap<Texture>,
+        location_tc: &LocationTextureComponent,
+    ) -> Result<Self, Error> {
+        let descriptors: BTreeMap<String, ParticleEmitterDescriptor> =
+            read_data_file(PARTICLE_EMITTERS_PATH)?;
+
+        let emitters = descriptors
+            .into_iter()
+            .map(|(label, descriptor)| {
+                let texture = match &descriptor.texture {
+                    ParticleTextureDescriptor::Block => EmitterTexture::Block,
+                    ParticleTextureDescriptor::Texture {
+                        label: texture_label,
+                    } => {
+                        let texture = texture_label_map.get(texture_label).ok_or_else(|| {
+                            Error::msg(format!(
+                                "block texture label \"{}\" of particle emitter \"{}\" is \
+                                 undefined",
+                                texture_label, label
+                            ))
+                        })?;
+
+                        EmitterTexture::Fixed(ParticleTexture {
+                            index: location_tc.get_index(texture),
+                            coords: [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]
+                                .map(|coords| location_tc.get_coords(texture, coords)),
+                        })
+                    },
+                };
+
+                Ok((
+                    label,
+                    ParticleEmitter {
+                        descriptor,
+                        texture,
+                    },
+                ))
+            })
+            .collect::<Result<BTreeMap<_, _>, Error>>()?;
+
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or(0)
+            | 1;
+
+        Ok(Self {
+            emitters,
+            particles: Vec::new(),
+            ambient_remainders: BTreeMap::new(),
+            quads: Vec::new(),
+            max_particles,
+            rng: Rng(seed),
+        })
+    }
+
+    /// Emits the particles of the emitter with the label at the position.
+    /// `block_class` provides the texture for the emitters using the block texture,
+    /// nothing is emitted by them without one.
+    pub fn emit(
+        &mut self,
+        label: &str,
+        position: Position,
+        block_class: Option<BlockClass>,
+        model_bcc: &ModelBlockClassComponent,
+        builder_bmc: &BuilderBlockModelComponent,
+    ) {
+        let Some(emitter) = self.emitters.get(label) else {
+            return;
+        };
+
+        let texture = match emitter.texture {
+            EmitterTexture::Fixed(texture) => texture,
+            EmitterTexture::Block => {
+                let Some((index, coords)) = block_class
+                    .and_then(|class| model_bcc.get(&class))
+                    .and_then(|model| builder_bmc.get(model))
+                    .and_then(|builder| builder.particle_texture())
+                else {
+                    return;
+                };
+
+                ParticleTexture { index, coords }
+            },
+        };
+
+        let descriptor = &emitter.descriptor;
+        let count = (descriptor.count as usize)
+            .min(self.max_particles.saturating_sub(self.particles.len()));
+
+        for _ in 0 .. count {
+            let texture = match emitter.texture {
+                // Random part of the block side
+                EmitterTexture::Block => {
+                    let start = [
+                        self.rng.range([0.0, 1.0 - PARTICLE_TEXTURE_PART]),
+                        self.rng.range([0.0, 1.0 - PARTICLE_TEXTURE_PART]),
+                    ];
+
+                    sub_texture(texture, start, PARTICLE_TEXTURE_PART)
+                },
+                EmitterTexture::Fixed(texture) => texture,
+            };
+
+            self.particles.push(Particle {
+                position,
+                velocity: self.rng.in_sphere(descriptor.speed),
+                gravity: descriptor.gravity,
+                size: descriptor.size,
+                texture,
+                lifetime: self.rng.range(descriptor.lifetime),
+            });
+        }
+    }
+
+    /// Emits the particles of the block at the center of the block.
+    pub fn emit_block(
+        &mut self,
+        label: &str,
+        chunk: Chunk,
+        block: Block,
+        block_class: BlockClass,
+        model_bcc: &ModelBlockClassComponent,
+        builder_bmc: &BuilderBlockModelComponent,
+    ) {
+        let position = Position {
+            chunk,
+            offset: Vec3F32::from(block.into_coords().map(|c| c as f32)) + Vec3F32::splat(0.5),
+        };
+
+        self.emit(label, position, Some(block_class), model_bcc, builder_bmc);
+    }
+
+    /// Moves the particles, emits the ambient ones and builds the quads facing the player.
+    pub fn process(
+        &mut self,
+        dt: Duration,
+        player_actor: Actor,
+        position_ac: &PositionActorComponent,
+        orientation_ac: &OrientationActorComponent,
+        class_bc: &ClassBlockComponent,
+        collision_bcc: &CollisionBlockClassComponent,
+        sky_light_bc: &SkyLightBlockComponent,
+        block_light_bc: &BlockLightBlockComponent,
+        model_bcc: &ModelBlockClassComponent,
+        builder_bmc: &BuilderBlockModelComponent,
+    ) {
+        let dt = dt.as_secs_f32();
+
+        self.quads.clear();
+
+        let Some((player_position, orientation)) = position_ac
+            .get(&player_actor)
+            .zip(orientation_ac.get(&player_actor))
+        else {
+            return;
+        };
+
+        let player_position = *player_position;
+
+        let ambient = self
+            .emitters
+            .iter()
+            .filter_map(|(label, emitter)| {
+                Some((label.clone(), emitter.descriptor.ambient.as_ref()?))
+            })
+            .map(|(label, ambient)| {
+                let remainder = self.ambient_remainders.entry(label.clone()).or_insert(0.0);
+                *remainder += ambient.rate * dt;
+                let count = remainder.floor();
+                *remainder -= count;
+
+                (label, count as usize, ambient.radius)
+            })
+            .collect::<Vec<_>>();
+
+        for (label, count, radius) in ambient {
+            for _ in 0 .. count {
+                let position = Position {
+                    chunk: player_position.chunk,
+                    offset: player_position.offset + self.rng.in_sphere(radius),
+                };
+
+                self.emit(&label, position, None, model_bcc, builder_bmc);
+            }
+        }
+
+        let is_solid = |position: &Position| {
+            Block::from_chunk_offset(
+                position.chunk,
+                position.offset.to_array().map(|f| f.round_down()),
+            )
+            .and_then(|(chunk, block)| {
+                let class = class_bc.get_chunk(&chunk)?.get(block);
+                collision_bcc.get(class)
+            })
+            .is_some()
+        };
+
+        self.particles.retain_mut(|particle| {
+            particle.lifetime -= dt;
+
+            if particle.lifetime <= 0.0 {
+                return false;
+            }
+
+            particle.velocity.z -= particle.gravity * dt;
+
+            let next = Position {
+                chunk: particle.position.chunk,
+                offset: particle.position.offset + particle.velocity * dt,
+            };
+
+            // Particles stop at the blocks instead of bouncing
+            if is_solid(&next) {
+                particle.velocity = Vec3F32::ZERO;
+            } else {
+                particle.position = next;
+            }
+
+            true
+        });
+
+        let right = orientation.right();
+        let up = orientation.up();
+
+        self.quads.extend(self.particles.iter().map(|particle| {
+            let block = Block::from_chunk_offset(
+                particle.position.chunk,
+                particle.position.offset.to_array().map(|f| f.round_down()),
+            );
+
+            let sky_light = block
+                .and_then(|(chunk, block)| sky_light_bc.get_chunk(&chunk).map(|c| *c.get(block)))
+                .unwrap_or(SkyLight::MAX);
+
+            let block_light = block
+                .and_then(|(chunk, block)| block_light_bc.get_chunk(&chunk).map(|c| *c.get(block)))
+                .unwrap_or(BlockLight::MIN);
+
+            let half_right = right * particle.size / 2.0;
+            let half_up = up * particle.size / 2.0;
+            let center = particle.position.offset;
+
+            let positions = [
+                center - half_right + half_up,
+                center + half_right + half_up,
+                center + half_right - half_up,
+                center - half_right - half_up,
+            ];
+
+            Quad {
+                chunk: particle.position.chunk.position,
+                texture_index: particle.texture.index,
+                texture_bounds: [0.0; 4],
+                vertices: [0, 1, 2, 3].map(|i| {
+                    let mut vertex = Vertex {
+                        position: positions[i].into(),
+                        texture_position: particle.texture.coords[i],
+                        light_level: 0,
+                    };
+
+                    vertex.set_sky_light(sky_light);
+                    vertex.set_block_light(block_light);
+
+                    vertex
+                }),
+            }
+        }));
+    }
+
+    pub fn quads(&self) -> &[Quad] {
+        &self.quads
+    }
+}
+
+/// Square part of the texture starting at `start` with the edge of `size`,
+/// both relative to the texture corners.
+fn sub_texture(texture: ParticleTexture, start: [f32; 2], size: f32) -> ParticleTexture {
+    let [top_left, top_right, bottom_right, bottom_left] = texture.coords;
+
+    let lerp = |a: [f32; 2], b: [f32; 2], t: f32| [0, 1].map(|i| a[i] + (b[i] - a[i]) * t);
+
+    let point = |[x, y]: [f32; 2]| {
+        let top = lerp(top_left, top_right, x);
+        let bottom = lerp(bottom_left, bottom_right, x);
+        lerp(top, bottom, y)
+    };
+
+    let [x, y] = start;
+
+    ParticleTexture {
+        index: texture.index,
+        coords: [
+            point([x, y]),
+            point([x + size, y]),
+            point([x + size, y + size]),
+            point([x, y + size]),
+        ],
+    }
+}
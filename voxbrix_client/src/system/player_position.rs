@@ -1,34 +1,97 @@
 use crate::component::{
     actor::{
+        class::ClassActorComponent,
         orientation::OrientationActorComponent,
         position::PositionActorComponent,
         velocity::VelocityActorComponent,
     },
     block::class::ClassBlockComponent,
 };
-use std::time::Duration;
+use std::{
+    collections::VecDeque,
+    time::Duration,
+};
 use voxbrix_common::{
     component::{
-        actor::position::Position,
-        block_class::collision::CollisionBlockClassComponent,
+        actor::{
+            position::Position,
+            velocity::Velocity,
+        },
+        actor_class::collider::{
+            Collider,
+            ColliderActorClassComponent,
+        },
+        block_class::{
+            collision::CollisionBlockClassComponent,
+            fluid::FluidBlockClassComponent,
+        },
     },
     entity::{
         actor::Actor,
-        block::Block,
-        chunk::Chunk,
+        block::{
+            Block,
+            BLOCKS_IN_CHUNK_EDGE_F32,
+        },
+        chunk::{
+            Chunk,
+            ChunkPositionOperations,
+        },
         snapshot::Snapshot,
     },
-    math::Vec3F32,
+    math::{
+        query,
+        Vec3F32,
+    },
+    messages::client::PositionCorrection,
     system::position,
 };
 
+/// Movement steps kept for the replay after the server correction.
+const MAX_HISTORY_LENGTH: usize = 1024;
+/// Corrections further than this are applied immediately instead of smoothly.
+const MAX_SMOOTHED_DISTANCE: f32 = 4.0;
+/// Rate at which the visible correction error decays, per second.
+const SMOOTHING_RATE: f32 = 10.0;
+const MIN_CORRECTION_ERROR: f32 = 0.001;
+/// Gap kept between the third-person camera and the block behind it,
+/// so the near plane does not cut into the block.
+const CAMERA_COLLISION_MARGIN: f32 = 0.2;
+
+struct MovementStep {
+    snapshot: Snapshot,
+    dt: Duration,
+    velocity: Velocity,
+}
+
 pub struct PlayerPositionSystem {
     player_actor: Actor,
+    history: VecDeque<MovementStep>,
+    last_correction: Snapshot,
+    /// Difference between the displayed and the reconciled positions.
+    correction_error: Vec3F32,
 }
 
 impl PlayerPositionSystem {
     pub fn new(player_actor: Actor) -> Self {
-        Self { player_actor }
+        Self {
+            player_actor,
+            history: VecDeque::new(),
+            last_correction: Snapshot(0),
+            correction_error: Vec3F32::ZERO,
+        }
+    }
+
+    /// Player actor moves as a point until its class is received.
+    fn collider(
+        &self,
+        class_ac: &ClassActorComponent,
+        collider_acc: &ColliderActorClassComponent,
+    ) -> Collider {
+        class_ac
+            .get(&self.player_actor)
+            .and_then(|class| collider_acc.get(class))
+            .copied()
+            .unwrap_or_default()
     }
 
     pub fn process(
@@ -36,32 +99,142 @@ impl PlayerPositionSystem {
         dt: Duration,
         class_bc: &ClassBlockComponent,
         collision_bcc: &CollisionBlockClassComponent,
+        fluid_bcc: &FluidBlockClassComponent,
+        class_ac: &ClassActorComponent,
+        collider_acc: &ColliderActorClassComponent,
         position_ac: &mut PositionActorComponent,
         velocity_ac: &VelocityActorComponent,
         snapshot: Snapshot,
     ) {
-        // TODO: replace
-        let h_radius = 0.45;
-        let v_radius = 0.95;
-        let radius = [h_radius, h_radius, v_radius];
+        let collider = self.collider(class_ac, collider_acc);
 
         if let Some((velocity, mut writable_position)) = velocity_ac
             .get(&self.player_actor)
             .zip(position_ac.get_writable(&self.player_actor, snapshot))
         {
-            let new_pos = position::process_actor(
+            let mut new_pos = position::process_actor(
                 dt,
                 class_bc,
                 collision_bcc,
+                fluid_bcc,
                 &writable_position,
                 velocity,
-                &radius,
+                &collider,
             );
 
+            if self.history.len() >= MAX_HISTORY_LENGTH {
+                self.history.pop_front();
+            }
+
+            self.history.push_back(MovementStep {
+                snapshot,
+                dt,
+                velocity: *velocity,
+            });
+
+            if self.correction_error != Vec3F32::ZERO {
+                let remaining_error =
+                    self.correction_error * (-dt.as_secs_f32() * SMOOTHING_RATE).exp();
+
+                new_pos.offset -= self.correction_error - remaining_error;
+
+                self.correction_error = if remaining_error.length() < MIN_CORRECTION_ERROR {
+                    Vec3F32::ZERO
+                } else {
+                    remaining_error
+                };
+            }
+
             writable_position.update(new_pos);
         }
     }
 
+    /// Replay the movement made after the last client snapshot the server applied
+    /// on top of the corrected position.
+    /// The visible position is then moved to the result smoothly if it is close enough.
+    pub fn reconcile(
+        &mut self,
+        correction: PositionCorrection,
+        class_bc: &ClassBlockComponent,
+        collision_bcc: &CollisionBlockClassComponent,
+        fluid_bcc: &FluidBlockClassComponent,
+        class_ac: &ClassActorComponent,
+        collider_acc: &ColliderActorClassComponent,
+        position_ac: &mut PositionActorComponent,
+        snapshot: Snapshot,
+    ) {
+        // The server repeats the correction until the client acknowledges it
+        if correction.snapshot <= self.last_correction {
+            return;
+        }
+
+        self.last_correction = correction.snapshot;
+
+        while self
+            .history
+            .front()
+            .is_some_and(|step| step.snapshot <= correction.last_client_snapshot)
+        {
+            self.history.pop_front();
+        }
+
+        let collider = self.collider(class_ac, collider_acc);
+
+        let reconciled = self
+            .history
+            .iter()
+            .fold(correction.position, |position, step| {
+                position::process_actor(
+                    step.dt,
+                    class_bc,
+                    collision_bcc,
+                    fluid_bcc,
+                    &position,
+                    &step.velocity,
+                    &collider,
+                )
+            });
+
+        let Some(mut writable_position) = position_ac.get_writable(&self.player_actor, snapshot)
+        else {
+            return;
+        };
+
+        let displayed = *writable_position;
+
+        let error = (displayed.chunk.dimension == reconciled.chunk.dimension)
+            .then(|| {
+                displayed
+                    .chunk
+                    .position
+                    .checked_sub(reconciled.chunk.position)
+            })
+            .flatten()
+            .map(|chunk_difference| {
+                let chunk_offset: Vec3F32 = chunk_difference
+                    .map(|i| i as f32 * BLOCKS_IN_CHUNK_EDGE_F32)
+                    .into();
+
+                chunk_offset + displayed.offset - reconciled.offset
+            })
+            .filter(|error| error.length() <= MAX_SMOOTHED_DISTANCE);
+
+        match error {
+            Some(error) => {
+                self.correction_error = error;
+
+                writable_position.update(Position {
+                    chunk: reconciled.chunk,
+                    offset: reconciled.offset + error,
+                });
+            },
+            None => {
+                self.correction_error = Vec3F32::ZERO;
+                writable_position.update(reconciled);
+            },
+        }
+    }
+
     pub fn get_target_block(
         &self,
         position_ac: &PositionActorComponent,
@@ -76,6 +249,30 @@ impl PlayerPositionSystem {
             })
     }
 
+    /// Distance the camera could be moved back from the player, up to `max_distance`,
+    /// without getting behind the blocks accepted by `targeting`.
+    pub fn get_camera_distance(
+        &self,
+        position_ac: &PositionActorComponent,
+        orientation_ac: &OrientationActorComponent,
+        max_distance: f32,
+        targeting: impl FnMut(Chunk, Block) -> bool,
+    ) -> f32 {
+        let Some((position, orientation)) = position_ac
+            .get(&self.player_actor)
+            .zip(orientation_ac.get(&self.player_actor))
+        else {
+            return 0.0;
+        };
+
+        let back = -orientation.forward();
+
+        match query::raycast(position, back, max_distance, targeting) {
+            Some(hit) => (hit.time * back.length() - CAMERA_COLLISION_MARGIN).max(0.0),
+            None => max_distance,
+        }
+    }
+
     pub fn position_direction(
         &self,
         position_ac: &PositionActorComponent,
@@ -0,0 +1,107 @@
+use std::{
+    collections::VecDeque,
+    time::Duration,
+};
+
+/// Number of the latest frames kept.
+pub const FRAME_WINDOW: usize = 240;
+
+/// Measured parts of the game loop.
+#[derive(Clone, Copy, Debug)]
+pub enum Section {
+    ChunkPresence,
+    Movement,
+    Network,
+    Interface,
+    Models,
+    Render,
+    Lighting,
+    Meshing,
+}
+
+impl Section {
+    pub const ALL: [Section; 8] = [
+        Section::ChunkPresence,
+        Section::Movement,
+        Section::Network,
+        Section::Interface,
+        Section::Models,
+        Section::Render,
+        Section::Lighting,
+        Section::Meshing,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Section::ChunkPresence => "chunk presence",
+            Section::Movement => "movement",
+            Section::Network => "network",
+            Section::Interface => "interface",
+            Section::Models => "models",
+            Section::Render => "render",
+            Section::Lighting => "lighting",
+            Section::Meshing => "meshing",
+        }
+    }
+}
+
+/// Time between the frames and the durations of the sections within it.
+#[derive(Clone, Copy, Default)]
+struct Frame {
+    time: Duration,
+    sections: [Duration; Section::ALL.len()],
+}
+
+/// Time spent in the sections of the game loop over the latest frames.
+/// The events handled between the frames go into the next frame.
+pub struct Profiler {
+    current: Frame,
+    frames: VecDeque<Frame>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            current: Frame::default(),
+            frames: VecDeque::with_capacity(FRAME_WINDOW),
+        }
+    }
+
+    pub fn record(&mut self, section: Section, duration: Duration) {
+        self.current.sections[section as usize] += duration;
+    }
+
+    /// Should be called once every frame with the time since the previous one.
+    pub fn finish_frame(&mut self, time: Duration) {
+        let mut frame = std::mem::take(&mut self.current);
+        frame.time = time;
+
+        if self.frames.len() == FRAME_WINDOW {
+            self.frames.pop_front();
+        }
+
+        self.frames.push_back(frame);
+    }
+
+    /// Frame times from the oldest to the latest.
+    pub fn frame_times(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.frames.iter().map(|frame| frame.time)
+    }
+
+    pub fn average_frame_time(&self) -> Duration {
+        self.average(|frame| frame.time)
+    }
+
+    /// Average duration of the section per frame.
+    pub fn average_section(&self, section: Section) -> Duration {
+        self.average(|frame| frame.sections[section as usize])
+    }
+
+    fn average(&self, duration: impl Fn(&Frame) -> Duration) -> Duration {
+        if self.frames.is_empty() {
+            return Duration::ZERO;
+        }
+
+        self.frames.iter().map(duration).sum::<Duration>() / self.frames.len() as u32
+    }
+}
@@ -13,6 +13,7 @@ use arrayvec::ArrayVec;
 use camera::{
     Camera,
     CameraParameters,
+    SkyParameters,
 };
 use std::{
     iter,
@@ -186,6 +187,20 @@ impl RenderSystem {
             .update(self.window.queue(), position_ac, orientation_ac);
     }
 
+    /// Vertical field of view of the camera, in radians.
+    pub fn set_field_of_view(&mut self, fovy: f32) {
+        self.camera.parameters.fovy = fovy;
+    }
+
+    /// Distance the camera is moved back from the player, zero for the first-person view.
+    pub fn set_camera_distance(&mut self, distance: f32) {
+        self.camera.parameters.distance = distance;
+    }
+
+    pub fn set_sky(&mut self, sky: SkyParameters) {
+        self.camera.sky = sky;
+    }
+
     pub fn start_render(&mut self, frame: Frame) {
         let view_size = frame.size();
         self.camera.resize(view_size.width, view_size.height);
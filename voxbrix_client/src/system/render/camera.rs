@@ -26,6 +26,8 @@ pub struct CameraParameters {
     pub fovy: f32,
     pub near: f32,
     pub far: f32,
+    /// Distance the camera is moved back from the actor, zero for the first-person view.
+    pub distance: f32,
 }
 
 impl CameraParameters {
@@ -34,6 +36,35 @@ impl CameraParameters {
     }
 }
 
+/// Sky at the current time of the day, shared by the sky and the fog in the shaders.
+#[derive(Clone, Copy, Debug)]
+pub struct SkyParameters {
+    /// Direction towards the sun, the moon is on the opposite side.
+    pub sun_direction: Vec3F32,
+    /// Multiplier of the sky light level, from 0 to 1.
+    pub sky_light_factor: f32,
+    pub zenith_color: [f32; 3],
+    /// Color of the sky at the horizon, the distant blocks fade into it.
+    pub horizon_color: [f32; 3],
+    /// Distance the fog starts at, in blocks.
+    pub fog_start: f32,
+    /// Distance the fog fully covers the blocks at, in blocks.
+    pub fog_end: f32,
+}
+
+impl Default for SkyParameters {
+    fn default() -> Self {
+        Self {
+            sun_direction: Vec3F32::UP,
+            sky_light_factor: 1.0,
+            zenith_color: [0.35, 0.55, 0.9],
+            horizon_color: [0.7, 0.8, 0.9],
+            fog_start: f32::MAX,
+            fog_end: f32::MAX,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 struct CameraUniform {
@@ -41,25 +72,46 @@ struct CameraUniform {
     _padding: u32,
     view_position: [f32; 4],
     view_projection: [f32; 16],
+    inverse_view_projection: [f32; 16],
+    // w is the sky light factor
+    sun_direction: [f32; 4],
+    zenith_color: [f32; 4],
+    horizon_color: [f32; 4],
+    // x is the fog start, y is the fog end
+    fog: [f32; 4],
 }
 
 fn calc_uniform(
     actor: &Actor,
     parameters: &CameraParameters,
+    sky: &SkyParameters,
     position_ac: &PositionActorComponent,
     orientation_ac: &OrientationActorComponent,
 ) -> Result<CameraUniform, CameraError> {
     let position = position_ac.get(actor).ok_or(CameraError::InvalidActor)?;
     let orientation = orientation_ac.get(actor).ok_or(CameraError::InvalidActor)?;
 
-    let look_to = Mat4F32::look_to_lh(position.offset, orientation.forward(), Vec3F32::UP);
+    let forward = orientation.forward();
+    let eye = position.offset - forward * parameters.distance;
+
+    let look_to = Mat4F32::look_to_lh(eye, forward, Vec3F32::UP);
+
+    let view_projection = parameters.calc_perspective() * look_to;
+
+    let [zenith_r, zenith_g, zenith_b] = sky.zenith_color;
+    let [horizon_r, horizon_g, horizon_b] = sky.horizon_color;
 
     Ok(CameraUniform {
         chunk: position.chunk.position.into(),
         _padding: 0,
         // offset converted to homogeneous
-        view_position: position.offset.extend(1.0).into(),
-        view_projection: (parameters.calc_perspective() * look_to).to_cols_array(),
+        view_position: eye.extend(1.0).into(),
+        view_projection: view_projection.to_cols_array(),
+        inverse_view_projection: view_projection.inverse().to_cols_array(),
+        sun_direction: sky.sun_direction.extend(sky.sky_light_factor).into(),
+        zenith_color: [zenith_r, zenith_g, zenith_b, 1.0],
+        horizon_color: [horizon_r, horizon_g, horizon_b, 1.0],
+        fog: [sky.fog_start, sky.fog_end, 0.0, 0.0],
     })
 }
 
@@ -67,6 +119,7 @@ fn calc_uniform(
 pub struct Camera {
     pub actor: Actor,
     pub parameters: CameraParameters,
+    pub sky: SkyParameters,
     buffer: wgpu::Buffer,
     bind_group_layout: wgpu::BindGroupLayout,
     bind_group: wgpu::BindGroup,
@@ -80,7 +133,9 @@ impl Camera {
         position_ac: &PositionActorComponent,
         orientation_ac: &OrientationActorComponent,
     ) -> Self {
-        let uniform = calc_uniform(&actor, &parameters, position_ac, orientation_ac).unwrap();
+        let sky = SkyParameters::default();
+
+        let uniform = calc_uniform(&actor, &parameters, &sky, position_ac, orientation_ac).unwrap();
 
         let buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Camera Buffer"),
@@ -91,7 +146,7 @@ impl Camera {
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[wgpu::BindGroupLayoutEntry {
                 binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
                     has_dynamic_offset: false,
@@ -114,6 +169,7 @@ impl Camera {
         Self {
             actor,
             parameters,
+            sky,
             buffer,
             bind_group_layout,
             bind_group,
@@ -131,9 +187,13 @@ impl Camera {
         position_ac: &PositionActorComponent,
         orientation_ac: &OrientationActorComponent,
     ) {
-        if let Ok(uniform) =
-            calc_uniform(&self.actor, &self.parameters, position_ac, orientation_ac)
-        {
+        if let Ok(uniform) = calc_uniform(
+            &self.actor,
+            &self.parameters,
+            &self.sky,
+            position_ac,
+            orientation_ac,
+        ) {
             queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniform]));
         }
     }
@@ -3,7 +3,10 @@ use bytemuck::{
     Zeroable,
 };
 use std::mem;
-use voxbrix_common::component::block::sky_light::SkyLight;
+use voxbrix_common::component::block::{
+    block_light::BlockLight,
+    sky_light::SkyLight,
+};
 use wgpu::*;
 
 #[repr(C)]
@@ -42,6 +45,15 @@ impl Vertex {
 
         self.light_level = (self.light_level & !0xFF) | (sky_light.value() as u32)
     }
+
+    pub fn set_block_light(&mut self, block_light: BlockLight) {
+        self.light_level = (self.light_level & !0xFF00) | ((block_light.value() as u32) << 8)
+    }
+
+    /// Number of the occluding neighbors of the vertex, `0` to `3`.
+    pub fn set_ambient_occlusion(&mut self, occlusion: u8) {
+        self.light_level = (self.light_level & !0x30000) | (((occlusion & 0b11) as u32) << 16)
+    }
 }
 
 #[repr(C)]
@@ -49,26 +61,31 @@ impl Vertex {
 pub struct Quad {
     pub chunk: [i32; 3],
     pub texture_index: u32,
+    /// `[min_x, min_y, max_x, max_y]` of the texture area the texture positions wrap within,
+    /// used by quads repeating the texture several times.
+    /// Quads with the zeroed bounds use the texture positions as they are.
+    pub texture_bounds: [f32; 4],
     pub vertices: [Vertex; 4],
 }
 
 impl Quad {
     pub fn desc<'a>() -> VertexBufferLayout<'a> {
-        const VERTEX_ATTRIBUTES: &[VertexAttribute; 14] = &wgpu::vertex_attr_array![
+        const VERTEX_ATTRIBUTES: &[VertexAttribute; 15] = &wgpu::vertex_attr_array![
             1 => Sint32x3,
             2 => Uint32,
-            3 => Float32x3,
-            4 => Float32x2,
-            5 => Uint32,
-            6 => Float32x3,
-            7 => Float32x2,
-            8 => Uint32,
-            9 => Float32x3,
-            10 => Float32x2,
-            11 => Uint32,
-            12 => Float32x3,
-            13 => Float32x2,
-            14 => Uint32,
+            3 => Float32x4,
+            4 => Float32x3,
+            5 => Float32x2,
+            6 => Uint32,
+            7 => Float32x3,
+            8 => Float32x2,
+            9 => Uint32,
+            10 => Float32x3,
+            11 => Float32x2,
+            12 => Uint32,
+            13 => Float32x3,
+            14 => Float32x2,
+            15 => Uint32,
         ];
 
         VertexBufferLayout {
@@ -0,0 +1,124 @@
+use crate::window::{
+    Capture,
+    Frame,
+};
+use anyhow::{
+    Context,
+    Error,
+    Result,
+};
+use flume::{
+    Receiver,
+    Sender,
+};
+use image::RgbaImage;
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::{
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+use tokio::task;
+
+pub const SCREENSHOT_DIRECTORY: &str = "screenshots";
+
+/// Captures the requested frames and saves them into `SCREENSHOT_DIRECTORY` as PNG files,
+/// named by the time they were taken.
+pub struct ScreenshotSystem {
+    requested: bool,
+    result_tx: Sender<Result<PathBuf>>,
+    result_rx: Receiver<Result<PathBuf>>,
+}
+
+impl ScreenshotSystem {
+    pub fn new() -> Self {
+        let (result_tx, result_rx) = flume::unbounded();
+
+        Self {
+            requested: false,
+            result_tx,
+            result_rx,
+        }
+    }
+
+    /// Takes the screenshot of the next frame.
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    /// Should be called with every frame before it is submitted.
+    pub fn capture(&mut self, frame: &mut Frame) {
+        if !self.requested {
+            return;
+        }
+
+        self.requested = false;
+
+        let Some(capture_rx) = frame.capture() else {
+            let _ = self
+                .result_tx
+                .send(Err(Error::msg("screenshots are not supported by the GPU")));
+            return;
+        };
+
+        let result_tx = self.result_tx.clone();
+
+        task::spawn_local(async move {
+            let Ok(capture) = capture_rx.recv_async().await else {
+                let _ = result_tx.send(Err(Error::msg("unable to read the frame")));
+                return;
+            };
+
+            let result = task::spawn_blocking(move || save(capture)).await.unwrap();
+
+            let _ = result_tx.send(result);
+        });
+    }
+
+    /// Message for the player about the screenshot saved since the last call.
+    pub fn take_notice(&mut self) -> Option<String> {
+        match self.result_rx.try_recv().ok()? {
+            Ok(path) => Some(format!("Screenshot saved to {}", path.display())),
+            Err(err) => Some(format!("Unable to save the screenshot: {:#}", err)),
+        }
+    }
+}
+
+/// Blocking IO, must not be used directly in async
+fn save(capture: Capture) -> Result<PathBuf> {
+    let Capture {
+        width,
+        height,
+        mut pixels,
+    } = capture;
+
+    // The window is opaque whatever alpha is rendered
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel[3] = u8::MAX;
+    }
+
+    let image = RgbaImage::from_raw(width, height, pixels).context("invalid frame size")?;
+
+    let directory = Path::new(SCREENSHOT_DIRECTORY);
+
+    fs::create_dir_all(directory)
+        .with_context(|| format!("unable to create directory {:?}", directory))?;
+
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+
+    let path = directory.join(format!("{}.png", millis));
+
+    image
+        .save(&path)
+        .with_context(|| format!("unable to write {:?}", path))?;
+
+    Ok(path)
+}
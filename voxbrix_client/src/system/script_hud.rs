@@ -0,0 +1,87 @@
+use ahash::AHashMap;
+use client_loop_api::HudAnchor;
+use egui::{
+    Align2,
+    Context,
+    Id,
+};
+use std::time::{
+    Duration,
+    Instant,
+};
+
+struct HudText {
+    text: String,
+    anchor: Align2,
+    offset: [f32; 2],
+    expires_at: Option<Instant>,
+}
+
+/// HUD elements shown by the client scripts.
+pub struct ScriptHudSystem {
+    elements: AHashMap<String, HudText>,
+}
+
+impl ScriptHudSystem {
+    pub fn new() -> Self {
+        Self {
+            elements: AHashMap::new(),
+        }
+    }
+
+    /// Shows the text, replacing the element with the same `id`.
+    pub fn show_text(
+        &mut self,
+        id: String,
+        text: String,
+        anchor: HudAnchor,
+        offset: [f32; 2],
+        duration: Option<Duration>,
+    ) {
+        let anchor = match anchor {
+            HudAnchor::TopLeft => Align2::LEFT_TOP,
+            HudAnchor::Top => Align2::CENTER_TOP,
+            HudAnchor::TopRight => Align2::RIGHT_TOP,
+            HudAnchor::Left => Align2::LEFT_CENTER,
+            HudAnchor::Center => Align2::CENTER_CENTER,
+            HudAnchor::Right => Align2::RIGHT_CENTER,
+            HudAnchor::BottomLeft => Align2::LEFT_BOTTOM,
+            HudAnchor::Bottom => Align2::CENTER_BOTTOM,
+            HudAnchor::BottomRight => Align2::RIGHT_BOTTOM,
+        };
+
+        self.elements.insert(
+            id,
+            HudText {
+                text,
+                anchor,
+                offset,
+                expires_at: duration.map(|duration| Instant::now() + duration),
+            },
+        );
+    }
+
+    pub fn remove(&mut self, id: &str) {
+        self.elements.remove(id);
+    }
+
+    pub fn interface(&mut self, ctx: &Context) {
+        let now = Instant::now();
+
+        self.elements.retain(|_, element| {
+            element
+                .expires_at
+                .map(|expires_at| expires_at > now)
+                .unwrap_or(true)
+        });
+
+        for (id, element) in self.elements.iter() {
+            egui::Area::new(Id::new("script_hud").with(id))
+                .anchor(element.anchor, element.offset)
+                .interactable(false)
+                .show(ctx, |ui| {
+                    ui.label(&element.text);
+                });
+        }
+    }
+}
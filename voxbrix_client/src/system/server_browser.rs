@@ -0,0 +1,331 @@
+use crate::{
+    scene::menu,
+    server_list::{
+        SavedServer,
+        ServerList,
+    },
+};
+use ahash::AHashMap;
+use egui::{
+    Grid,
+    Ui,
+};
+use flume::{
+    Receiver,
+    Sender,
+};
+use log::warn;
+use std::{
+    collections::BTreeMap,
+    net::{
+        Ipv4Addr,
+        SocketAddr,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+use tokio::{
+    net::UdpSocket,
+    time,
+};
+use voxbrix_common::{
+    async_ext::{
+        self,
+        ScopeTask,
+    },
+    messages::{
+        LanBeacon,
+        LAN_BEACON_PORT,
+    },
+    pack::Packer,
+};
+use voxbrix_protocol::{
+    client::Client,
+    PROTOCOL_VERSION,
+};
+
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+/// Local servers not heard from for that long are removed from the list.
+const LAN_SERVER_TIMEOUT: Duration = Duration::from_secs(10);
+/// Beacons are much smaller, the larger datagrams are truncated and then ignored.
+const MAX_BEACON_SIZE: usize = 1024;
+
+pub enum ServerBrowserRequest {
+    /// Fill the connection form with the address.
+    Select(String),
+    /// Persist the edited list of the saved servers.
+    Save(ServerList),
+}
+
+enum Ping {
+    Pending,
+    Answered(Duration),
+    Unreachable,
+}
+
+struct LanServer {
+    beacon: LanBeacon,
+    last_seen: Instant,
+}
+
+/// Server list of the menu: the saved servers and the ones found in the local network,
+/// each with the round trip time measured without connecting.
+pub struct ServerBrowserSystem {
+    server_list: ServerList,
+    new_server_name: String,
+    lan_servers: BTreeMap<SocketAddr, LanServer>,
+    pings: AHashMap<SocketAddr, Ping>,
+    /// Replaced with the new ping of the same address.
+    ping_tasks: AHashMap<SocketAddr, ScopeTask<()>>,
+    ping_tx: Sender<(SocketAddr, Option<Duration>)>,
+    ping_rx: Receiver<(SocketAddr, Option<Duration>)>,
+    beacon_rx: Receiver<(SocketAddr, LanBeacon)>,
+    _discovery_task: ScopeTask<()>,
+}
+
+impl ServerBrowserSystem {
+    pub fn new(server_list: ServerList) -> Self {
+        let (ping_tx, ping_rx) = flume::unbounded();
+        let (beacon_tx, beacon_rx) = flume::unbounded();
+
+        let mut system = Self {
+            server_list,
+            new_server_name: String::new(),
+            lan_servers: BTreeMap::new(),
+            pings: AHashMap::new(),
+            ping_tasks: AHashMap::new(),
+            ping_tx,
+            ping_rx,
+            beacon_rx,
+            _discovery_task: async_ext::spawn_scoped(discover(beacon_tx)),
+        };
+
+        system.refresh();
+
+        system
+    }
+
+    /// Pings all the servers in the lists again.
+    pub fn refresh(&mut self) {
+        let addresses = self
+            .server_list
+            .servers
+            .iter()
+            .filter_map(|server| server.address.parse().ok())
+            .chain(self.lan_servers.keys().copied())
+            .collect::<Vec<_>>();
+
+        for address in addresses {
+            self.ping(address);
+        }
+    }
+
+    fn ping(&mut self, address: SocketAddr) {
+        let ping_tx = self.ping_tx.clone();
+
+        self.pings.insert(address, Ping::Pending);
+        self.ping_tasks.insert(
+            address,
+            async_ext::spawn_scoped(async move {
+                let _ = ping_tx.send((address, ping(address).await));
+            }),
+        );
+    }
+
+    /// Collects the pings and the beacons received since the last call.
+    pub fn process(&mut self) {
+        let now = Instant::now();
+
+        while let Ok((address, rtt)) = self.ping_rx.try_recv() {
+            self.ping_tasks.remove(&address);
+            self.pings.insert(
+                address,
+                match rtt {
+                    Some(rtt) => Ping::Answered(rtt),
+                    None => Ping::Unreachable,
+                },
+            );
+        }
+
+        while let Ok((address, beacon)) = self.beacon_rx.try_recv() {
+            let is_new = self
+                .lan_servers
+                .insert(
+                    address,
+                    LanServer {
+                        beacon,
+                        last_seen: now,
+                    },
+                )
+                .is_none();
+
+            if is_new && !self.pings.contains_key(&address) {
+                self.ping(address);
+            }
+        }
+
+        self.lan_servers.retain(|_, server| {
+            now.saturating_duration_since(server.last_seen) < LAN_SERVER_TIMEOUT
+        });
+    }
+
+    fn ping_text(&self, address: &SocketAddr) -> String {
+        match self.pings.get(address) {
+            None => "-".to_owned(),
+            Some(Ping::Pending) => "...".to_owned(),
+            Some(Ping::Answered(rtt)) => format!("{} ms", rtt.as_millis()),
+            Some(Ping::Unreachable) => "unreachable".to_owned(),
+        }
+    }
+
+    /// Show the server lists, `address` is the one currently in the connection form.
+    /// Returns the request if the player has pressed one of the buttons.
+    pub fn interface(&mut self, ui: &mut Ui, address: &str) -> Option<ServerBrowserRequest> {
+        let mut request = None;
+        let mut removed = None;
+
+        ui.label("Saved servers:");
+
+        Grid::new("saved_servers").show(ui, |ui| {
+            for (index, server) in self.server_list.servers.iter().enumerate() {
+                ui.label(&server.name);
+                ui.label(&server.address);
+
+                match server.address.parse() {
+                    Ok(address) => ui.label(self.ping_text(&address)),
+                    Err(_) => ui.label("invalid address"),
+                };
+
+                if ui.button("Select").clicked() {
+                    request = Some(ServerBrowserRequest::Select(server.address.clone()));
+                }
+
+                if ui.button("Remove").clicked() {
+                    removed = Some(index);
+                }
+
+                ui.end_row();
+            }
+        });
+
+        if let Some(index) = removed {
+            self.server_list.servers.remove(index);
+            request = Some(ServerBrowserRequest::Save(self.server_list.clone()));
+        }
+
+        let mut saved = None;
+
+        ui.horizontal(|ui| {
+            ui.label("Name:");
+            ui.text_edit_singleline(&mut self.new_server_name);
+
+            if ui.button("Save address").clicked() && !address.is_empty() {
+                saved = Some(SavedServer {
+                    name: self.new_server_name.clone(),
+                    address: address.to_owned(),
+                });
+            }
+        });
+
+        ui.add_space(16.0);
+        ui.label("Local network:");
+
+        if self.lan_servers.is_empty() {
+            ui.label("No servers found");
+        }
+
+        Grid::new("lan_servers").show(ui, |ui| {
+            for (address, server) in self.lan_servers.iter() {
+                let beacon = &server.beacon;
+
+                ui.label(&beacon.name);
+                ui.label(address.to_string());
+                ui.label(format!("{}/{} players", beacon.players, beacon.max_players));
+
+                if beacon.protocol_version == PROTOCOL_VERSION {
+                    ui.label(self.ping_text(address));
+                } else {
+                    ui.label("incompatible version");
+                }
+
+                if ui.button("Select").clicked() {
+                    request = Some(ServerBrowserRequest::Select(address.to_string()));
+                }
+
+                if ui.button("Save").clicked() {
+                    saved = Some(SavedServer {
+                        name: beacon.name.clone(),
+                        address: address.to_string(),
+                    });
+                }
+
+                ui.end_row();
+            }
+        });
+
+        if let Some(server) = saved {
+            if let Ok(address) = server.address.parse() {
+                self.ping(address);
+            }
+
+            self.new_server_name.clear();
+            self.server_list.servers.push(server);
+            request = Some(ServerBrowserRequest::Save(self.server_list.clone()));
+        }
+
+        ui.add_space(16.0);
+
+        if ui.button("Refresh").clicked() {
+            self.refresh();
+        }
+
+        request
+    }
+}
+
+/// Round trip time, `None` if the server has not answered in time.
+async fn ping(server: SocketAddr) -> Option<Duration> {
+    let client = Client::bind(menu::local_address(server)).await.ok()?;
+
+    time::timeout(PING_TIMEOUT, client.ping(server))
+        .await
+        .ok()?
+        .ok()
+}
+
+/// Listens for the beacons of the servers in the local network.
+async fn discover(beacon_tx: Sender<(SocketAddr, LanBeacon)>) {
+    // Only one process of the host could listen on the port
+    let socket = match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, LAN_BEACON_PORT)).await {
+        Ok(socket) => socket,
+        Err(err) => {
+            warn!("unable to listen for the local servers: {:?}", err);
+            return;
+        },
+    };
+
+    let mut packer = Packer::new();
+    let mut buffer = vec![0; MAX_BEACON_SIZE];
+
+    loop {
+        let (len, address) = match socket.recv_from(&mut buffer).await {
+            Ok(received) => received,
+            Err(err) => {
+                warn!("unable to receive the local server beacons: {:?}", err);
+                return;
+            },
+        };
+
+        let Ok(beacon) = packer.unpack::<LanBeacon>(&buffer[.. len]) else {
+            continue;
+        };
+
+        let server = SocketAddr::new(address.ip(), beacon.port);
+
+        if beacon_tx.send((server, beacon)).is_err() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,203 @@
+#[cfg(feature = "gamepad")]
+use crate::window::GamepadEvent;
+use crate::{
+    settings::{
+        key_bindings::{
+            InputAction,
+            InputBinding,
+        },
+        Settings,
+    },
+    window::{
+        InputEvent,
+        WindowEvent,
+    },
+};
+use egui::{
+    Grid,
+    Slider,
+    Ui,
+};
+use winit::{
+    event::ElementState,
+    keyboard::{
+        KeyCode,
+        PhysicalKey,
+    },
+};
+
+pub enum SettingsRequest {
+    /// Apply and persist the edited settings.
+    Save(Settings),
+    /// Leave the settings screen without saving.
+    Back,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Rebinding {
+    /// Waiting for a key or a mouse button.
+    Key(InputAction),
+    /// Waiting for a gamepad button.
+    #[cfg(feature = "gamepad")]
+    Gamepad(InputAction),
+}
+
+/// Settings screen of the menu.
+/// Edits a copy of the settings, they are applied only once saved.
+pub struct SettingsMenuSystem {
+    draft: Settings,
+    rebinding: Option<Rebinding>,
+}
+
+impl SettingsMenuSystem {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            draft: settings.clone(),
+            rebinding: None,
+        }
+    }
+
+    /// Show the settings form.
+    /// Returns the request if the player has pressed one of the buttons.
+    pub fn interface(&mut self, ui: &mut Ui) -> Option<SettingsRequest> {
+        let draft = &mut self.draft;
+
+        ui.label("Settings");
+        ui.add_space(16.0);
+        ui.add(Slider::new(&mut draft.pixels_per_point, 0.5 ..= 4.0).text("Interface scale"));
+        ui.add(Slider::new(&mut draft.field_of_view, 30.0 ..= 120.0).text("Field of view"));
+        ui.add(Slider::new(&mut draft.mouse_sensitivity, 0.05 ..= 2.0).text("Mouse sensitivity"));
+        ui.add(Slider::new(&mut draft.view_radius, 1 ..= 64).text("View radius"));
+        ui.checkbox(&mut draft.greedy_meshing, "Greedy meshing");
+        ui.add(Slider::new(&mut draft.max_particles, 0 ..= 8192).text("Particles"));
+        #[cfg(feature = "gamepad")]
+        {
+            ui.add(
+                Slider::new(&mut draft.gamepad.dead_zone, 0.0 ..= 0.9).text("Gamepad dead zone"),
+            );
+            ui.add(
+                Slider::new(&mut draft.gamepad.camera_sensitivity, 0.5 ..= 10.0)
+                    .text("Gamepad camera sensitivity"),
+            );
+        }
+        ui.add_space(16.0);
+
+        Grid::new("key_bindings").show(ui, |ui| {
+            for action in InputAction::ALL {
+                ui.label(action.name());
+
+                let text = if self.rebinding == Some(Rebinding::Key(action)) {
+                    "Press a key...".to_owned()
+                } else {
+                    draft.key_bindings.get(action).to_string()
+                };
+
+                if ui.button(text).clicked() {
+                    self.rebinding = Some(Rebinding::Key(action));
+                }
+
+                #[cfg(feature = "gamepad")]
+                {
+                    let text = if self.rebinding == Some(Rebinding::Gamepad(action)) {
+                        "Press a button...".to_owned()
+                    } else {
+                        match draft.gamepad.bindings.get(action) {
+                            Some(button) => format!("Gamepad {:?}", button),
+                            None => "-".to_owned(),
+                        }
+                    };
+
+                    let response = ui.button(text);
+
+                    if response.clicked() {
+                        self.rebinding = Some(Rebinding::Gamepad(action));
+                    }
+
+                    // Right click unbinds
+                    if response.secondary_clicked() {
+                        *draft.gamepad.bindings.get_mut(action) = None;
+                    }
+                }
+
+                ui.end_row();
+            }
+        });
+        ui.add_space(16.0);
+
+        let mut request = None;
+
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                request = Some(SettingsRequest::Save(self.draft.clone()));
+            }
+
+            if ui.button("Back").clicked() {
+                request = Some(SettingsRequest::Back);
+            }
+        });
+
+        request
+    }
+
+    /// Binds the next pressed key, mouse or gamepad button to the action being rebound.
+    /// Escape cancels the rebinding.
+    pub fn process_input(&mut self, event: &InputEvent) {
+        let Some(rebinding) = self.rebinding else {
+            return;
+        };
+
+        if let InputEvent::WindowEvent(WindowEvent::KeyboardInput { event, .. }) = event {
+            if event.state == ElementState::Pressed
+                && event.physical_key == PhysicalKey::Code(KeyCode::Escape)
+            {
+                self.rebinding = None;
+                return;
+            }
+        }
+
+        match rebinding {
+            Rebinding::Key(action) => {
+                let binding =
+                    match event {
+                        InputEvent::WindowEvent(WindowEvent::KeyboardInput { event, .. })
+                            if event.state == ElementState::Pressed =>
+                        {
+                            match event.physical_key {
+                                PhysicalKey::Code(code) => InputBinding::Key(code),
+                                PhysicalKey::Unidentified(_) => return,
+                            }
+                        },
+                        InputEvent::WindowEvent(WindowEvent::MouseInput {
+                            state, button, ..
+                        }) if *state == ElementState::Pressed => InputBinding::Mouse(*button),
+                        _ => return,
+                    };
+
+                // Only one action per binding, the previous owner takes the replaced one
+                let bindings = &mut self.draft.key_bindings;
+                let previous = bindings.get(action);
+                if let Some(other) = bindings.action(binding) {
+                    *bindings.get_mut(other) = previous;
+                }
+
+                *bindings.get_mut(action) = binding;
+            },
+            #[cfg(feature = "gamepad")]
+            Rebinding::Gamepad(action) => {
+                let InputEvent::GamepadEvent(GamepadEvent::ButtonPressed(button, _)) = event else {
+                    return;
+                };
+
+                let bindings = &mut self.draft.gamepad.bindings;
+                let previous = bindings.get(action);
+                if let Some(other) = bindings.action(*button) {
+                    *bindings.get_mut(other) = previous;
+                }
+
+                *bindings.get_mut(action) = Some(*button);
+            },
+        }
+
+        self.rebinding = None;
+    }
+}
@@ -0,0 +1,104 @@
+use crate::system::render::camera::SkyParameters;
+use std::{
+    f32::consts::TAU,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+use voxbrix_common::math::Vec3F32;
+
+const DAY_ZENITH_COLOR: [f32; 3] = [0.35, 0.55, 0.9];
+const DAY_HORIZON_COLOR: [f32; 3] = [0.7, 0.8, 0.9];
+const NIGHT_ZENITH_COLOR: [f32; 3] = [0.01, 0.01, 0.04];
+const NIGHT_HORIZON_COLOR: [f32; 3] = [0.04, 0.05, 0.1];
+const SUNSET_HORIZON_COLOR: [f32; 3] = [0.95, 0.55, 0.35];
+/// Sky light factor at night, the moon light.
+const NIGHT_SKY_LIGHT_FACTOR: f32 = 0.2;
+/// Part of the view radius not covered by the fog.
+const FOG_START_RATIO: f32 = 0.6;
+/// The sun path is tilted off the zenith a bit, so that it is never right above.
+const SUN_PATH_TILT: f32 = 0.3;
+
+fn mix(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t)
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Day and night cycle following the world time of the server.
+/// The time is advanced locally between the server updates.
+pub struct SkySystem {
+    time: u64,
+    day_length: u64,
+    time_received: Instant,
+    tick_interval: Duration,
+}
+
+impl SkySystem {
+    /// `tick_interval` is the interval of the server ticks.
+    pub fn new(tick_interval: Duration) -> Self {
+        Self {
+            time: 0,
+            day_length: 0,
+            time_received: Instant::now(),
+            tick_interval,
+        }
+    }
+
+    pub fn set_time(&mut self, time: u64, day_length: u64) {
+        self.time = time;
+        self.day_length = day_length;
+        self.time_received = Instant::now();
+    }
+
+    /// Part of the day passed, from 0 to 1.
+    /// The day starts with the sunrise, 0.25 is the noon and 0.75 is the midnight.
+    pub fn time_of_day(&self) -> f32 {
+        // Endless noon until the server tells otherwise
+        if self.day_length == 0 || self.tick_interval.is_zero() {
+            return 0.25;
+        }
+
+        let elapsed_ticks =
+            (self.time_received.elapsed().as_secs_f64() / self.tick_interval.as_secs_f64()) as u64;
+
+        let tick_of_day = self.time.wrapping_add(elapsed_ticks) % self.day_length;
+
+        tick_of_day as f32 / self.day_length as f32
+    }
+
+    /// `view_distance` is the distance the blocks are loaded to, in blocks.
+    /// The fog hides the edge of the loaded area.
+    pub fn parameters(&self, view_distance: f32) -> SkyParameters {
+        let sun_angle = self.time_of_day() * TAU;
+
+        // Sun rises in the forward direction and sets in the back one
+        let sun_direction =
+            Vec3F32::new(sun_angle.cos(), SUN_PATH_TILT, sun_angle.sin()).normalize();
+
+        let daylight = smoothstep(-0.1, 0.2, sun_direction.z);
+
+        // Sunrise and sunset color the horizon while the sun is close to it
+        let sunset = (1.0 - sun_direction.z.abs() / 0.3).max(0.0) * daylight;
+
+        let zenith_color = mix(NIGHT_ZENITH_COLOR, DAY_ZENITH_COLOR, daylight);
+        let horizon_color = mix(
+            mix(NIGHT_HORIZON_COLOR, DAY_HORIZON_COLOR, daylight),
+            SUNSET_HORIZON_COLOR,
+            sunset * 0.6,
+        );
+
+        SkyParameters {
+            sun_direction,
+            sky_light_factor: NIGHT_SKY_LIGHT_FACTOR + (1.0 - NIGHT_SKY_LIGHT_FACTOR) * daylight,
+            zenith_color,
+            horizon_color,
+            fog_start: view_distance * FOG_START_RATIO,
+            fog_end: view_distance,
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use crate::{
+    assets::SHADERS_PATH,
+    system::render::{
+        RenderParameters,
+        Renderer,
+    },
+    window::Window,
+};
+
+pub struct SkyRenderSystemDescriptor<'a> {
+    pub render_parameters: RenderParameters<'a>,
+}
+
+impl<'a> SkyRenderSystemDescriptor<'a> {
+    pub async fn build(self, window: &Window) -> SkyRenderSystem {
+        let Self {
+            render_parameters:
+                RenderParameters {
+                    camera_bind_group_layout,
+                    texture_format,
+                },
+        } = self;
+
+        let shaders = voxbrix_common::read_file_async(SHADERS_PATH)
+            .await
+            .expect("unable to read shaders file");
+
+        let shaders =
+            std::str::from_utf8(&shaders).expect("unable to convert binary file to UTF-8 string");
+
+        let shaders = window
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Sky Shaders"),
+                source: wgpu::ShaderSource::Wgsl(shaders.into()),
+            });
+
+        let render_pipeline_layout =
+            window
+                .device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Sky Pipeline Layout"),
+                    bind_group_layouts: &[&camera_bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+
+        // Single triangle covering the screen, drawn behind everything else
+        let render_pipeline =
+            window
+                .device()
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("Sky Pipeline"),
+                    layout: Some(&render_pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shaders,
+                        entry_point: Some("vs_sky"),
+                        buffers: &[],
+                        compilation_options: Default::default(),
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shaders,
+                        entry_point: Some("fs_sky"),
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: texture_format,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                        compilation_options: Default::default(),
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::TriangleList,
+                        strip_index_format: None,
+                        front_face: wgpu::FrontFace::Cw,
+                        cull_mode: None,
+                        polygon_mode: wgpu::PolygonMode::Fill,
+                        unclipped_depth: false,
+                        conservative: false,
+                    },
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: wgpu::TextureFormat::Depth32Float,
+                        depth_write_enabled: false,
+                        depth_compare: wgpu::CompareFunction::Always,
+                        stencil: wgpu::StencilState::default(),
+                        bias: wgpu::DepthBiasState::default(),
+                    }),
+                    multisample: wgpu::MultisampleState {
+                        count: 1,
+                        mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    },
+                    multiview: None,
+                    cache: None,
+                });
+
+        SkyRenderSystem { render_pipeline }
+    }
+}
+
+/// Sky gradient with the sun and the moon.
+/// Has to be rendered first, it covers the whole screen.
+pub struct SkyRenderSystem {
+    render_pipeline: wgpu::RenderPipeline,
+}
+
+impl SkyRenderSystem {
+    pub fn render(&mut self, renderer: Renderer) {
+        let mut render_pass = renderer.with_pipeline(&self.render_pipeline);
+
+        render_pass.draw(0 .. 3, 0 .. 1);
+    }
+}
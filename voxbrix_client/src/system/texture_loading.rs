@@ -9,12 +9,24 @@ use anyhow::{
     Context,
     Error,
 };
-use image::ImageFormat;
+use image::{
+    imageops::{
+        self,
+        FilterType,
+    },
+    ImageFormat,
+    RgbaImage,
+};
 use rect_packer::DensePacker;
 use serde::Deserialize;
 use std::{
+    collections::BTreeMap,
     fs,
     path::Path,
+    time::{
+        Duration,
+        Instant,
+    },
 };
 use tokio::task;
 use voxbrix_common::{
@@ -29,14 +41,67 @@ const TEXTURE_BYTES_PER_PIXEL: u32 = 4;
 const MIN_TEXTURE_ATLAS_SIZE: u32 = 512;
 const MAX_TEXTURE_ATLAS_SIZE: u32 = 8096;
 const EDGE_CORRECTION_PIXELS: f64 = 0.001;
+const MAX_MIP_LEVELS: u32 = 5;
+/// Must match the shader constant.
+const MAX_TEXTURE_ANIMATIONS: usize = 64;
+/// Size of the animation uniform element, `vec4<u32>` in the shader.
+const ANIMATION_UNIFORM_STRIDE: usize = 16;
+
+/// Animated texture has its frames stacked vertically in the image file.
+#[derive(Deserialize, Clone, Copy, Debug)]
+struct TextureAnimation {
+    frames: u32,
+    /// In seconds.
+    frame_time: f32,
+}
 
 #[derive(Deserialize, Debug)]
 struct TextureList {
     list: Vec<String>,
+    #[serde(default)]
+    animations: BTreeMap<String, TextureAnimation>,
+}
+
+#[derive(Clone, Copy)]
+struct AnimationSlot {
+    frames: u32,
+    frame_time: Duration,
+    frame_height: u32,
+}
+
+/// Per-frame uniform with the current frame offsets of the animated textures.
+pub struct TextureAnimations {
+    slots: Vec<AnimationSlot>,
+    buffer: wgpu::Buffer,
+    start: Instant,
+}
+
+impl TextureAnimations {
+    pub fn update(&self, queue: &wgpu::Queue) {
+        if self.slots.is_empty() {
+            return;
+        }
+
+        let elapsed = self.start.elapsed();
+
+        let offsets = self
+            .slots
+            .iter()
+            .map(|slot| {
+                let frame =
+                    (elapsed.as_nanos() / slot.frame_time.as_nanos().max(1)) % slot.frames as u128;
+
+                [frame as u32 * slot.frame_height, 0, 0, 0]
+            })
+            .collect::<Vec<[u32; 4]>>();
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&offsets));
+    }
 }
 
 pub struct TextureLoadingSystem {
     label_map: LabelMap<Texture>,
+    animation_slots: Vec<AnimationSlot>,
 }
 
 impl TextureLoadingSystem {
@@ -53,9 +118,11 @@ impl TextureLoadingSystem {
             .try_into()
             .unwrap();
 
-        let texture_list: TextureList = read_data_file(list_path)?;
+        let TextureList { list, animations } = read_data_file(list_path)?;
 
-        let label_map = LabelMap::from_list(&texture_list.list);
+        let label_map = LabelMap::from_list(&list);
+
+        let mut animation_slots = Vec::new();
 
         let mut packers = vec![DensePacker::new(
             MIN_TEXTURE_ATLAS_SIZE.try_into().unwrap(),
@@ -63,9 +130,7 @@ impl TextureLoadingSystem {
         )];
 
         let texture_dimensions = task::spawn_blocking(move || {
-            texture_list
-                .list
-                .into_iter()
+            list.into_iter()
                 .map(|texture_label| {
                     let file_path = Path::new(path_prefix)
                         .join(format!("{}.{}", texture_label, TEXTURE_FORMAT_NAME));
@@ -86,6 +151,41 @@ impl TextureLoadingSystem {
                 let tex_width = texture_dimensions.0.try_into().expect("texture too large");
                 let tex_height = texture_dimensions.1.try_into().expect("texture too large");
 
+                let (frame_height, animation) = match animations.get(label) {
+                    Some(animation) => {
+                        if animation.frames == 0
+                            || texture_dimensions.1 % animation.frames != 0
+                            || !animation.frame_time.is_finite()
+                            || animation.frame_time <= 0.0
+                        {
+                            anyhow::bail!(
+                                "incorrect animation of texture \"{}\": {} frames of {} seconds",
+                                label,
+                                animation.frames,
+                                animation.frame_time
+                            );
+                        }
+
+                        if animation_slots.len() == MAX_TEXTURE_ANIMATIONS {
+                            anyhow::bail!(
+                                "too many animated textures, maximum is {}",
+                                MAX_TEXTURE_ANIMATIONS
+                            );
+                        }
+
+                        let frame_height = texture_dimensions.1 / animation.frames;
+
+                        animation_slots.push(AnimationSlot {
+                            frames: animation.frames,
+                            frame_time: Duration::from_secs_f32(animation.frame_time),
+                            frame_height,
+                        });
+
+                        (frame_height, animation_slots.len() as u32)
+                    },
+                    None => (texture_dimensions.1, 0),
+                };
+
                 let idx_pos = packers.iter_mut().enumerate().find_map(|(idx, packer)| {
                     Some((idx, packer.pack(tex_width, tex_height, false)?))
                 });
@@ -134,8 +234,9 @@ impl TextureLoadingSystem {
                 Ok::<_, anyhow::Error>(Location {
                     data_index: idx.try_into().unwrap(),
                     position: [pos.x as u32, pos.y as u32],
-                    size: [pos.width as u32, pos.height as u32],
+                    size: [pos.width as u32, frame_height],
                     edge_correction: [0.0; 2],
+                    animation,
                 })
             })
             .collect::<Result<Vec<_>, _>>()?;
@@ -152,27 +253,37 @@ impl TextureLoadingSystem {
 
         location_tc.load(atlas_size, atlas_layers, locations);
 
-        Ok(Self { label_map })
+        Ok(Self {
+            label_map,
+            animation_slots,
+        })
     }
 
     pub fn label_map(&self) -> LabelMap<Texture> {
         self.label_map.clone()
     }
 
+    /// Prepares the texture array with the generated mip levels
+    /// and the uniform of the animated texture frames.
     pub async fn prepare_buffer(
         &self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         path_prefix: &'static str,
         location_tc: &LocationTextureComponent,
-    ) -> Result<(wgpu::BindGroupLayout, wgpu::BindGroup), Error> {
+    ) -> Result<(wgpu::BindGroupLayout, wgpu::BindGroup, TextureAnimations), Error> {
+        let [atlas_width, atlas_height] = location_tc.atlas_size();
+
+        let mip_level_count =
+            (atlas_width.min(atlas_height).max(1).ilog2() + 1).min(MAX_MIP_LEVELS);
+
         let texture_descriptior = wgpu::TextureDescriptor {
             size: wgpu::Extent3d {
-                width: location_tc.atlas_size()[0],
-                height: location_tc.atlas_size()[1],
+                width: atlas_width,
+                height: atlas_height,
                 depth_or_array_layers: location_tc.atlas_layers(),
             },
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: TEXTURE_FORMAT_SHADER,
@@ -183,6 +294,10 @@ impl TextureLoadingSystem {
 
         let texture = device.create_texture(&texture_descriptior);
 
+        let mut atlas_layers = (0 .. location_tc.atlas_layers())
+            .map(|_| RgbaImage::new(atlas_width, atlas_height))
+            .collect::<Vec<_>>();
+
         for (texture_id, location) in location_tc.iter() {
             let texture_label = self.label_map.get_label(&texture_id).ok_or_else(|| {
                 anyhow::anyhow!("texture not found, incorrect LocationTextureComponent provided")
@@ -202,38 +317,88 @@ impl TextureLoadingSystem {
                     .into_rgba8()
             };
 
+            let frames = match location.animation {
+                0 => 1,
+                slot => self.animation_slots[slot as usize - 1].frames,
+            };
+
             let dim_ctrl = texture_bytes.dimensions();
 
-            if dim_ctrl.0 != location.size[0] || dim_ctrl.1 != location.size[1] {
+            if dim_ctrl.0 != location.size[0] || dim_ctrl.1 != location.size[1] * frames {
                 anyhow::bail!("dimensions of texture \"{:?}\" changed", texture_label);
             }
 
-            queue.write_texture(
-                wgpu::ImageCopyTexture {
-                    texture: &texture,
-                    mip_level: 0,
-                    origin: wgpu::Origin3d {
-                        x: location.position[0],
-                        y: location.position[1],
-                        z: location.data_index,
-                    },
-                    aspect: wgpu::TextureAspect::All,
-                },
+            imageops::replace(
+                &mut atlas_layers[location.data_index as usize],
                 &texture_bytes,
-                wgpu::ImageDataLayout {
-                    offset: 0,
-                    bytes_per_row: Some(TEXTURE_BYTES_PER_PIXEL * location.size[0]),
-                    rows_per_image: Some(location.size[1]),
-                },
-                wgpu::Extent3d {
-                    width: location.size[0],
-                    height: location.size[1],
-                    depth_or_array_layers: 1,
-                },
+                location.position[0].into(),
+                location.position[1].into(),
             );
-            queue.submit([]);
         }
 
+        let mip_chains = task::spawn_blocking(move || {
+            atlas_layers
+                .into_iter()
+                .map(|layer| {
+                    let mut chain = vec![layer];
+
+                    for level in 1 .. mip_level_count {
+                        let previous = chain.last().unwrap();
+                        let level_image = imageops::resize(
+                            previous,
+                            (atlas_width >> level).max(1),
+                            (atlas_height >> level).max(1),
+                            FilterType::Triangle,
+                        );
+                        chain.push(level_image);
+                    }
+
+                    chain
+                })
+                .collect::<Vec<_>>()
+        })
+        .await
+        .unwrap();
+
+        for (layer, chain) in mip_chains.iter().enumerate() {
+            for (level, level_image) in chain.iter().enumerate() {
+                let (width, height) = level_image.dimensions();
+
+                queue.write_texture(
+                    wgpu::ImageCopyTexture {
+                        texture: &texture,
+                        mip_level: level as u32,
+                        origin: wgpu::Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: layer as u32,
+                        },
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    level_image,
+                    wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(TEXTURE_BYTES_PER_PIXEL * width),
+                        rows_per_image: Some(height),
+                    },
+                    wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                );
+            }
+        }
+
+        queue.submit([]);
+
+        let animation_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("texture_animation_buffer"),
+            size: (MAX_TEXTURE_ANIMATIONS * ANIMATION_UNIFORM_STRIDE) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
         let texture_view = texture.create_view(&wgpu::TextureViewDescriptor {
             dimension: Some(wgpu::TextureViewDimension::D2Array),
             ..Default::default()
@@ -242,27 +407,55 @@ impl TextureLoadingSystem {
         let texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("texture_bind_group_layout"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Uint,
-                        view_dimension: wgpu::TextureViewDimension::D2Array,
-                        multisampled: false,
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Uint,
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            multisampled: false,
+                        },
+                        count: None,
                     },
-                    count: None,
-                }],
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
             });
 
         let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::TextureView(&texture_view),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: animation_buffer.as_entire_binding(),
+                },
+            ],
             layout: &texture_bind_group_layout,
             label: Some("texture_bind_group"),
         });
 
-        Ok((texture_bind_group_layout, texture_bind_group))
+        let texture_animations = TextureAnimations {
+            slots: self.animation_slots.clone(),
+            buffer: animation_buffer,
+            start: Instant::now(),
+        };
+
+        Ok((
+            texture_bind_group_layout,
+            texture_bind_group,
+            texture_animations,
+        ))
     }
 }
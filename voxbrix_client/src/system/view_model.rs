@@ -0,0 +1,162 @@
+use crate::{
+    component::{
+        actor::{
+            orientation::OrientationActorComponent,
+            position::PositionActorComponent,
+        },
+        block_class::model::ModelBlockClassComponent,
+        block_model::builder::BuilderBlockModelComponent,
+    },
+    system::render::primitives::Quad,
+};
+use std::{
+    f32::consts::{
+        FRAC_PI_4,
+        PI,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+use voxbrix_common::{
+    component::block::{
+        block_light::{
+            BlockLight,
+            BlockLightBlockComponent,
+        },
+        sky_light::{
+            SkyLight,
+            SkyLightBlockComponent,
+        },
+    },
+    entity::{
+        actor::Actor,
+        block::Block,
+        block_class::BlockClass,
+    },
+    math::{
+        Directions,
+        QuatF32,
+        Round,
+        Vec3F32,
+    },
+    system::block_surface::{
+        CullFlags,
+        Occluders,
+    },
+};
+
+const SWING_DURATION: Duration = Duration::from_millis(250);
+/// Held block position relative to the eyes, in the forward, right, up directions.
+const HAND_POSITION: Vec3F32 = Vec3F32::new(0.6, 0.35, -0.35);
+/// Held block edge length.
+const HAND_SCALE: f32 = 0.25;
+
+/// Builds the block held in the hand in the first-person view.
+/// The quads are positioned in the world right in front of the player eyes.
+pub struct ViewModelSystem {
+    swing_start: Option<Instant>,
+    quads: Vec<Quad>,
+}
+
+impl ViewModelSystem {
+    pub fn new() -> Self {
+        Self {
+            swing_start: None,
+            quads: Vec::new(),
+        }
+    }
+
+    /// Starts the swing animation, played on placing or removing a block.
+    pub fn swing(&mut self) {
+        self.swing_start = Some(Instant::now());
+    }
+
+    pub fn update(
+        &mut self,
+        player_actor: Actor,
+        held_block_class: Option<BlockClass>,
+        position_ac: &PositionActorComponent,
+        orientation_ac: &OrientationActorComponent,
+        model_bcc: &ModelBlockClassComponent,
+        builder_bmc: &BuilderBlockModelComponent,
+        sky_light_bc: &SkyLightBlockComponent,
+        block_light_bc: &BlockLightBlockComponent,
+    ) {
+        self.quads.clear();
+
+        let Some((position, orientation)) = position_ac
+            .get(&player_actor)
+            .zip(orientation_ac.get(&player_actor))
+        else {
+            return;
+        };
+
+        let Some(model_builder) = held_block_class
+            .and_then(|class| model_bcc.get(&class))
+            .and_then(|model| builder_bmc.get(model))
+        else {
+            return;
+        };
+
+        // Swing progress from 0 to 1 and back
+        let swing = match self.swing_start {
+            Some(start) if start.elapsed() < SWING_DURATION => {
+                (start.elapsed().as_secs_f32() / SWING_DURATION.as_secs_f32() * PI).sin()
+            },
+            _ => {
+                self.swing_start = None;
+                0.0
+            },
+        };
+
+        let eye_block = Block::from_chunk_offset(
+            position.chunk,
+            position.offset.to_array().map(|f| f.round_down()),
+        );
+
+        let sky_light = eye_block
+            .and_then(|(chunk, block)| sky_light_bc.get_chunk(&chunk).map(|c| *c.get(block)))
+            .unwrap_or(SkyLight::MAX);
+
+        let block_light = eye_block
+            .and_then(|(chunk, block)| block_light_bc.get_chunk(&chunk).map(|c| *c.get(block)))
+            .unwrap_or(BlockLight::MIN);
+
+        // Turned to show three sides, tilted down and pushed forward by the swing
+        let model_rotation = QuatF32::from_axis_angle(Vec3F32::LEFT, -swing)
+            * QuatF32::from_axis_angle(Vec3F32::UP, FRAC_PI_4);
+
+        let hand_position = HAND_POSITION + Vec3F32::new(0.15, -0.05, -0.1) * swing;
+
+        self.quads.extend(
+            model_builder
+                .build(
+                    &position.chunk,
+                    Block::from_coords([0, 0, 0]),
+                    CullFlags::all(),
+                    [sky_light; 6],
+                    [block_light; 6],
+                    Occluders::default(),
+                )
+                .map(|mut quad| {
+                    for vertex in quad.vertices.iter_mut() {
+                        let model_position =
+                            (Vec3F32::from(vertex.position) - Vec3F32::splat(0.5)) * HAND_SCALE;
+
+                        let local_position = hand_position + model_rotation * model_position;
+
+                        vertex.position =
+                            (position.offset + orientation.rotation * local_position).into();
+                    }
+
+                    quad
+                }),
+        );
+    }
+
+    pub fn quads(&self) -> &[Quad] {
+        &self.quads
+    }
+}
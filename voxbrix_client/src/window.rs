@@ -4,7 +4,21 @@ use flume::{
     Sender,
     TrySendError,
 };
-use log::info;
+#[cfg(feature = "gamepad")]
+pub use gilrs::{
+    Axis as GamepadAxis,
+    Button as GamepadButton,
+    EventType as GamepadEvent,
+};
+#[cfg(feature = "gamepad")]
+use gilrs::{
+    Event as GilrsEvent,
+    Gilrs,
+};
+use log::{
+    info,
+    warn,
+};
 use std::{
     mem,
     sync::Arc,
@@ -53,6 +67,8 @@ struct Initialized {
     surface_texture: Option<wgpu::SurfaceTexture>,
     surface_config: wgpu::SurfaceConfiguration,
     surface_reconfigure: bool,
+    /// The surface textures could be copied from.
+    capture_supported: bool,
     frame_time: Option<Duration>,
     last_render: Instant,
     input_tx: Sender<InputEvent>,
@@ -101,6 +117,9 @@ impl ApplicationHandler<Frame> for App {
 
             let (input_tx, input_rx) = flume::bounded(32);
 
+            #[cfg(feature = "gamepad")]
+            spawn_gamepad_source(input_tx.clone());
+
             let adapter = instance
                 .enumerate_adapters(wgpu::Backends::VULKAN)
                 .into_iter()
@@ -137,8 +156,20 @@ impl ApplicationHandler<Frame> for App {
 
             let surface_size = window.inner_size();
 
+            let capture_supported = capabilities.usages.contains(wgpu::TextureUsages::COPY_SRC);
+
+            if !capture_supported {
+                warn!("the surface does not support copying, screenshots are unavailable");
+            }
+
+            let mut usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+
+            if capture_supported {
+                usage |= wgpu::TextureUsages::COPY_SRC;
+            }
+
             let surface_config = wgpu::SurfaceConfiguration {
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                usage,
                 format,
                 width: surface_size.width,
                 height: surface_size.height,
@@ -191,6 +222,8 @@ impl ApplicationHandler<Frame> for App {
                     io: UiRendererIo::Input(ui_state.take_egui_input(&window)),
                 },
                 cursor_visible,
+                capture_supported,
+                capture_tx: None,
             });
 
             window_tx
@@ -212,6 +245,7 @@ impl ApplicationHandler<Frame> for App {
                 surface_texture: Some(surface_texture),
                 surface_config,
                 surface_reconfigure: true,
+                capture_supported,
                 frame_time: None,
                 last_render: Instant::now(),
                 input_tx,
@@ -285,13 +319,26 @@ impl ApplicationHandler<Frame> for App {
             view: _,
             mut ui_renderer,
             cursor_visible,
+            capture_supported: _,
+            capture_tx,
         } = event;
 
         app.shared
             .queue
             .submit(encoders.drain(..).map(|enc| enc.finish()));
 
-        app.surface_texture.take().unwrap().present();
+        let surface_texture = app.surface_texture.take().unwrap();
+
+        if let Some(capture_tx) = capture_tx {
+            match read_texture(&app.shared, &surface_texture.texture) {
+                Some(capture) => {
+                    let _ = capture_tx.send(capture);
+                },
+                None => warn!("unable to read the frame"),
+            }
+        }
+
+        surface_texture.present();
 
         if let UiRendererIo::Output(output) = mem::take(&mut ui_renderer.io) {
             app.ui_state
@@ -346,6 +393,8 @@ impl ApplicationHandler<Frame> for App {
                 .create_view(&wgpu::TextureViewDescriptor::default()),
             ui_renderer,
             cursor_visible: app.cursor_visible,
+            capture_supported: app.capture_supported,
+            capture_tx: None,
         });
 
         app.surface_texture = Some(surface_texture);
@@ -484,6 +533,112 @@ impl Window {
 pub enum InputEvent {
     DeviceEvent(DeviceEvent),
     WindowEvent(WindowEvent),
+    #[cfg(feature = "gamepad")]
+    GamepadEvent(GamepadEvent),
+}
+
+/// Gamepads are polled in a separate thread, their events are sent
+/// into the same channel as the window ones.
+#[cfg(feature = "gamepad")]
+fn spawn_gamepad_source(input_tx: Sender<InputEvent>) {
+    thread::spawn(move || {
+        let mut gilrs = match Gilrs::new() {
+            Ok(gilrs) => gilrs,
+            Err(err) => {
+                warn!("gamepad input is unavailable: {}", err);
+                return;
+            },
+        };
+
+        loop {
+            let Some(GilrsEvent { event, .. }) = gilrs.next_event_blocking(None) else {
+                continue;
+            };
+
+            // Blocking send, dropped axis events would leave the stick stuck
+            if input_tx.send(InputEvent::GamepadEvent(event)).is_err() {
+                info!("event channel closed, exiting gamepad loop");
+                return;
+            }
+        }
+    });
+}
+
+/// Copies the texture into the memory, blocks until the GPU has rendered it.
+/// The texture must be of `SURFACE_TEXTURE_FORMAT`.
+fn read_texture(shared: &Shared, texture: &wgpu::Texture) -> Option<Capture> {
+    let size = texture.size();
+    let row_size = size.width * 4;
+    let padded_row_size =
+        row_size.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let buffer = shared.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Capture Buffer"),
+        size: padded_row_size as u64 * size.height as u64,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = shared
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Capture Encoder"),
+        });
+
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_size),
+                rows_per_image: None,
+            },
+        },
+        size,
+    );
+
+    shared.queue.submit([encoder.finish()]);
+
+    let slice = buffer.slice(..);
+    let (map_tx, map_rx) = flume::bounded(1);
+
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = map_tx.send(result);
+    });
+
+    let _ = shared.device.poll(wgpu::Maintain::Wait);
+
+    map_rx.try_recv().ok()?.ok()?;
+
+    let mut pixels = Vec::with_capacity(row_size as usize * size.height as usize);
+
+    for row in slice
+        .get_mapped_range()
+        .chunks_exact(padded_row_size as usize)
+    {
+        pixels.extend_from_slice(&row[.. row_size as usize]);
+    }
+
+    buffer.unmap();
+
+    Some(Capture {
+        width: size.width,
+        height: size.height,
+        pixels,
+    })
+}
+
+/// Frame as it was presented, in RGBA with 8 bits per channel, the rows going top to bottom.
+pub struct Capture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
 }
 
 pub struct Frame {
@@ -491,6 +646,8 @@ pub struct Frame {
     pub view: wgpu::TextureView,
     pub ui_renderer: UiRenderer,
     cursor_visible: bool,
+    capture_supported: bool,
+    capture_tx: Option<Sender<Capture>>,
 }
 
 impl Frame {
@@ -505,4 +662,18 @@ impl Frame {
 
         input
     }
+
+    /// Requests the copy of the frame once it is rendered.
+    /// Returns `None` if the surface does not support copying.
+    pub fn capture(&mut self) -> Option<Receiver<Capture>> {
+        if !self.capture_supported {
+            return None;
+        }
+
+        let (capture_tx, capture_rx) = flume::bounded(1);
+
+        self.capture_tx = Some(capture_tx);
+
+        Some(capture_rx)
+    }
 }
@@ -1,3 +1,16 @@
-pub const ACTOR_MODEL_LIST_PATH: &str = "assets/common/models/actors.json";
-pub const STATE_COMPONENTS_PATH: &str = "assets/common/state_components.json";
-pub const ACTION_LIST_PATH: &str = "assets/common/actions.json";
+/// Directory of the base pack, the paths below are relative to the pack roots.
+pub const BASE_PACK_PATH: &str = "assets";
+/// Directory of every pack the server sends to the clients.
+pub const COMMON_ASSET_DIR: &str = "common";
+
+pub const ACTOR_MODEL_LIST_PATH: &str = "common/models/actors.json";
+pub const STATE_COMPONENTS_PATH: &str = "common/state_components.json";
+pub const ACTION_LIST_PATH: &str = "common/actions.json";
+pub const ITEM_CLASS_LIST_PATH: &str = "common/item_classes.json";
+pub const EFFECT_LIST_PATH: &str = "common/effects.json";
+pub const RECIPE_LIST_PATH: &str = "common/recipes.json";
+pub const DIMENSION_KIND_LIST_PATH: &str = "common/dimension_kinds.json";
+pub const BLOCK_CLASS_LIST_PATH: &str = "common/block_classes.json";
+pub const BLOCK_CLASS_PATH_PREFIX: &str = "common/block_classes";
+pub const ACTOR_CLASS_LIST_PATH: &str = "common/actor_classes.json";
+pub const ACTOR_CLASS_PATH_PREFIX: &str = "common/actor_classes";
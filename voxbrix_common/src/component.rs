@@ -1,4 +1,5 @@
 pub mod actor;
+pub mod actor_class;
 pub mod block;
 pub mod block_class;
 pub mod chunk;
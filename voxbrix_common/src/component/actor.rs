@@ -1,3 +1,6 @@
+pub mod effect;
+pub mod health;
+pub mod inventory;
 pub mod orientation;
 pub mod position;
 pub mod velocity;
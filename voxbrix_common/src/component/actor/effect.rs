@@ -0,0 +1,105 @@
+use crate::entity::{
+    effect::Effect,
+    snapshot::Snapshot,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EffectInstance {
+    pub magnitude: u32,
+    /// Server snapshot the effect expires on.
+    pub expires_at: Snapshot,
+    /// The effect ticks every `tick_period` server ticks, `0` disables the ticks.
+    pub tick_period: u64,
+}
+
+impl EffectInstance {
+    pub fn remaining_ticks(&self, snapshot: Snapshot) -> u64 {
+        self.expires_at.0.saturating_sub(snapshot.0)
+    }
+
+    /// Whether the effect ticks on the snapshot.
+    /// Ticks are counted back from the expiration, so the last one lands right before it.
+    pub fn ticks_on(&self, snapshot: Snapshot) -> bool {
+        let remaining = self.remaining_ticks(snapshot);
+
+        self.tick_period != 0 && remaining != 0 && remaining % self.tick_period == 0
+    }
+}
+
+/// Effects currently applied to the actor, at most one instance of each effect.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Default, Debug)]
+pub struct ActiveEffects {
+    effects: BTreeMap<Effect, EffectInstance>,
+}
+
+impl ActiveEffects {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    pub fn get(&self, effect: &Effect) -> Option<&EffectInstance> {
+        self.effects.get(effect)
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (Effect, &EffectInstance)> {
+        self.effects
+            .iter()
+            .map(|(effect, instance)| (*effect, instance))
+    }
+
+    /// Replaces the previous instance of the effect, if any.
+    pub fn insert(&mut self, effect: Effect, instance: EffectInstance) -> Option<EffectInstance> {
+        self.effects.insert(effect, instance)
+    }
+
+    pub fn remove(&mut self, effect: &Effect) -> Option<EffectInstance> {
+        self.effects.remove(effect)
+    }
+
+    /// Removes the effects expired by the snapshot.
+    /// Returns `true` if any of the effects has been removed.
+    pub fn remove_expired(&mut self, snapshot: Snapshot) -> bool {
+        let len = self.effects.len();
+
+        self.effects
+            .retain(|_, instance| instance.expires_at > snapshot);
+
+        self.effects.len() != len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_effect_ticks_and_expiry() {
+        let instance = EffectInstance {
+            magnitude: 1,
+            expires_at: Snapshot(10),
+            tick_period: 4,
+        };
+
+        let ticks = (0 .. 12)
+            .filter(|i| instance.ticks_on(Snapshot(*i)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(ticks, vec![2, 6]);
+
+        let mut effects = ActiveEffects::new();
+        effects.insert(Effect(0), instance);
+
+        assert!(!effects.remove_expired(Snapshot(9)));
+        assert!(effects.remove_expired(Snapshot(10)));
+        assert!(effects.is_empty());
+    }
+}
@@ -0,0 +1,39 @@
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Health the player actors start with.
+pub const PLAYER_MAX_HEALTH: u32 = 100;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+}
+
+impl Health {
+    pub fn new(max: u32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current == 0
+    }
+
+    /// Returns `true` if the damage has killed the actor.
+    /// Damaging an already dead actor does nothing.
+    pub fn damage(&mut self, amount: u32) -> bool {
+        if self.is_dead() {
+            return false;
+        }
+
+        self.current = self.current.saturating_sub(amount);
+
+        self.is_dead()
+    }
+
+    pub fn heal(&mut self, amount: u32) {
+        self.current = self.current.saturating_add(amount).min(self.max);
+    }
+}
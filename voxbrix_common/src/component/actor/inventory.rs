@@ -0,0 +1,180 @@
+use crate::entity::item_class::ItemClass;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Number of slots in the player inventory.
+pub const PLAYER_INVENTORY_SIZE: usize = 36;
+/// Maximum amount of items in a single slot.
+pub const MAX_STACK_AMOUNT: u32 = 64;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ItemStack {
+    pub class: ItemClass,
+    pub amount: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Inventory {
+    slots: Vec<Option<ItemStack>>,
+}
+
+impl Inventory {
+    pub fn new(size: usize) -> Self {
+        Self {
+            slots: vec![None; size],
+        }
+    }
+
+    pub fn slots(&self) -> &[Option<ItemStack>] {
+        &self.slots
+    }
+
+    /// Total amount of the items of the class.
+    pub fn count(&self, class: ItemClass) -> u32 {
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|stack| stack.class == class)
+            .map(|stack| stack.amount)
+            .sum()
+    }
+
+    /// Adds the items filling the existing stacks first.
+    /// Returns the amount that did not fit.
+    pub fn add(&mut self, class: ItemClass, mut amount: u32) -> u32 {
+        for stack in self.slots.iter_mut().flatten() {
+            if amount == 0 {
+                return 0;
+            }
+
+            if stack.class == class {
+                let added = amount.min(MAX_STACK_AMOUNT.saturating_sub(stack.amount));
+                stack.amount += added;
+                amount -= added;
+            }
+        }
+
+        for slot in self.slots.iter_mut().filter(|slot| slot.is_none()) {
+            if amount == 0 {
+                return 0;
+            }
+
+            let added = amount.min(MAX_STACK_AMOUNT);
+            *slot = Some(ItemStack {
+                class,
+                amount: added,
+            });
+            amount -= added;
+        }
+
+        amount
+    }
+
+    /// Removes the items starting from the last slots.
+    /// Does nothing and returns `false` if there is not enough items.
+    pub fn remove(&mut self, class: ItemClass, mut amount: u32) -> bool {
+        if self.count(class) < amount {
+            return false;
+        }
+
+        for slot in self.slots.iter_mut().rev() {
+            if amount == 0 {
+                break;
+            }
+
+            let Some(stack) = slot.as_mut().filter(|stack| stack.class == class) else {
+                continue;
+            };
+
+            let removed = amount.min(stack.amount);
+            stack.amount -= removed;
+            amount -= removed;
+
+            if stack.amount == 0 {
+                *slot = None;
+            }
+        }
+
+        true
+    }
+
+    /// Moves the stack to another slot, merging it with the stack of the same class
+    /// or swapping it with the stack of a different class.
+    /// Returns `false` if any of the slots is out of bounds.
+    pub fn move_stack(&mut self, from: usize, to: usize) -> bool {
+        if from >= self.slots.len() || to >= self.slots.len() {
+            return false;
+        }
+
+        if from == to {
+            return true;
+        }
+
+        match (self.slots[from], self.slots[to]) {
+            (Some(source), Some(mut target)) if source.class == target.class => {
+                let moved = source
+                    .amount
+                    .min(MAX_STACK_AMOUNT.saturating_sub(target.amount));
+                target.amount += moved;
+                self.slots[to] = Some(target);
+                self.slots[from] = Some(ItemStack {
+                    class: source.class,
+                    amount: source.amount - moved,
+                })
+                .filter(|stack| stack.amount > 0);
+            },
+            _ => self.slots.swap(from, to),
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_inventory_add_remove() {
+        let stone = ItemClass(1);
+        let dirt = ItemClass(2);
+
+        let mut inventory = Inventory::new(3);
+
+        assert_eq!(inventory.add(stone, MAX_STACK_AMOUNT + 10), 0);
+        assert_eq!(inventory.add(dirt, 5), 0);
+        assert_eq!(inventory.count(stone), MAX_STACK_AMOUNT + 10);
+
+        // The existing stack is filled before the new one is taken
+        assert_eq!(inventory.add(stone, MAX_STACK_AMOUNT), 10);
+        assert_eq!(inventory.count(stone), MAX_STACK_AMOUNT * 2);
+
+        assert!(!inventory.remove(dirt, 6));
+        assert_eq!(inventory.count(dirt), 5);
+        assert!(inventory.remove(dirt, 5));
+        assert_eq!(inventory.slots()[2], None);
+    }
+
+    #[test]
+    fn check_inventory_move_stack() {
+        let stone = ItemClass(1);
+        let dirt = ItemClass(2);
+
+        let mut inventory = Inventory::new(3);
+        inventory.add(stone, MAX_STACK_AMOUNT + 10);
+        inventory.add(dirt, 1);
+
+        // Merging the partial stack into the full one changes nothing
+        assert!(inventory.move_stack(1, 0));
+        assert_eq!(inventory.slots()[0].unwrap().amount, MAX_STACK_AMOUNT);
+        assert_eq!(inventory.slots()[1].unwrap().amount, 10);
+
+        assert!(inventory.move_stack(1, 2));
+        assert_eq!(inventory.slots()[1].unwrap().class, dirt);
+        assert_eq!(inventory.slots()[2].unwrap().class, stone);
+
+        assert!(!inventory.move_stack(0, 3));
+    }
+}
@@ -7,6 +7,11 @@ use serde::{
     Deserialize,
     Serialize,
 };
+use std::f32::consts::SQRT_2;
+
+/// Bits of each of the three smallest quaternion components in the packed orientation.
+const COMPONENT_BITS: u32 = 15;
+const COMPONENT_MASK: u64 = (1 << COMPONENT_BITS) - 1;
 
 #[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
 pub struct Orientation {
@@ -32,4 +37,74 @@ impl Orientation {
                 * QuatF32::from_axis_angle(Vec3F32::LEFT, pitch),
         }
     }
+
+    /// Packs the three smallest components of the rotation,
+    /// the largest one is restored from the unit length.
+    pub fn pack(&self) -> OrientationPacked {
+        let components = self.rotation.normalize().to_array();
+
+        let largest = (0 .. 4)
+            .max_by(|a, b| components[*a].abs().total_cmp(&components[*b].abs()))
+            .unwrap();
+
+        // q and -q are the same rotation, the largest component is kept positive
+        let sign = components[largest].signum();
+
+        let mut packed = largest as u64;
+
+        for (_, component) in components.iter().enumerate().filter(|(i, _)| *i != largest) {
+            // The smaller components are within [-1/sqrt(2), 1/sqrt(2)]
+            let normalized = ((component * sign * SQRT_2 + 1.0) / 2.0).clamp(0.0, 1.0);
+
+            packed =
+                (packed << COMPONENT_BITS) | (normalized * COMPONENT_MASK as f32).round() as u64;
+        }
+
+        OrientationPacked(packed)
+    }
+}
+
+/// Compressed orientation as sent to the clients.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct OrientationPacked(u64);
+
+impl OrientationPacked {
+    pub fn unpack(&self) -> Orientation {
+        let largest = ((self.0 >> (COMPONENT_BITS * 3)) & 3) as usize;
+
+        let mut components = [0.0; 4];
+        let mut shift = COMPONENT_BITS * 3;
+        let mut length_squared = 0.0;
+
+        for i in (0 .. 4).filter(|i| *i != largest) {
+            shift -= COMPONENT_BITS;
+
+            let normalized = ((self.0 >> shift) & COMPONENT_MASK) as f32 / COMPONENT_MASK as f32;
+            let component = (normalized * 2.0 - 1.0) / SQRT_2;
+
+            components[i] = component;
+            length_squared += component * component;
+        }
+
+        components[largest] = (1.0 - length_squared).max(0.0).sqrt();
+
+        Orientation {
+            rotation: QuatF32::from_array(components).normalize(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_orientation_roundtrip() {
+        for (yaw, pitch) in [(0.0, 0.0), (1.0, -0.5), (-3.0, 1.5), (2.5, 0.1)] {
+            let orientation = Orientation::from_yaw_pitch(yaw, pitch);
+            let unpacked = orientation.pack().unpack();
+
+            assert!(orientation.rotation.angle_between(unpacked.rotation) < 1e-3);
+        }
+    }
 }
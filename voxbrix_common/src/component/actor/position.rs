@@ -1,5 +1,11 @@
 use crate::{
-    entity::chunk::Chunk,
+    entity::{
+        block::BLOCKS_IN_CHUNK_EDGE,
+        chunk::{
+            Chunk,
+            Dimension,
+        },
+    },
     math::Vec3F32,
 };
 use serde::{
@@ -8,12 +14,126 @@ use serde::{
 };
 use std::ops::Add;
 
+/// Positions are sent to the clients in the fixed point with this many steps per block.
+pub const POSITION_QUANTUM: i32 = 1024;
+/// Position deltas are only taken against the snapshots this recent.
+pub const POSITION_BASELINE_SNAPSHOTS: u64 = 32;
+const CHUNK_QUANTUM: i64 = BLOCKS_IN_CHUNK_EDGE as i64 * POSITION_QUANTUM as i64;
+
 #[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
 pub struct Position {
     pub chunk: Chunk,
     pub offset: Vec3F32,
 }
 
+impl Position {
+    /// Coordinates within the dimension in the quantization steps.
+    fn quantized(&self) -> [i64; 3] {
+        [0, 1, 2].map(|i| {
+            self.chunk.position[i] as i64 * CHUNK_QUANTUM
+                + (self.offset[i] * POSITION_QUANTUM as f32).round() as i64
+        })
+    }
+
+    fn from_quantized(dimension: Dimension, quantized: [i64; 3]) -> Option<Self> {
+        let mut position = [0; 3];
+        let mut offset = [0.0; 3];
+
+        for i in 0 .. 3 {
+            position[i] = quantized[i].div_euclid(CHUNK_QUANTUM).try_into().ok()?;
+            offset[i] = quantized[i].rem_euclid(CHUNK_QUANTUM) as f32 / POSITION_QUANTUM as f32;
+        }
+
+        Some(Self {
+            chunk: Chunk {
+                position,
+                dimension,
+            },
+            offset: offset.into(),
+        })
+    }
+
+    /// Vector from this position to the `other` one, `None` if it is in the other dimension.
+    pub fn vector_to(&self, other: &Position) -> Option<Vec3F32> {
+        if self.chunk.dimension != other.chunk.dimension {
+            return None;
+        }
+
+        let chunks = [0, 1, 2].map(|i| {
+            (other.chunk.position[i] as i64 - self.chunk.position[i] as i64) as f32
+                * BLOCKS_IN_CHUNK_EDGE as f32
+        });
+
+        Some(Vec3F32::from_array(chunks) + other.offset - self.offset)
+    }
+
+    pub fn pack_absolute(&self) -> PositionPacked {
+        PositionPacked::Absolute {
+            chunk: self.chunk,
+            offset: [0, 1, 2].map(|i| (self.offset[i] * POSITION_QUANTUM as f32).round() as i32),
+        }
+    }
+
+    /// Difference from the `baseline` position.
+    /// Falls back to the absolute value if the baseline is too far or in the other dimension.
+    pub fn pack_delta(&self, baseline: &Position) -> PositionPacked {
+        if self.chunk.dimension != baseline.chunk.dimension {
+            return self.pack_absolute();
+        }
+
+        let quantized = self.quantized();
+        let baseline = baseline.quantized();
+
+        let mut delta = [0; 3];
+
+        for i in 0 .. 3 {
+            let Ok(value) = (quantized[i] - baseline[i]).try_into() else {
+                return self.pack_absolute();
+            };
+
+            delta[i] = value;
+        }
+
+        PositionPacked::Delta(delta)
+    }
+}
+
+/// Quantized position as sent to the clients.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum PositionPacked {
+    Absolute {
+        chunk: Chunk,
+        offset: [i32; 3],
+    },
+    /// Difference from the baseline position in the quantization steps.
+    Delta([i32; 3]),
+}
+
+impl PositionPacked {
+    /// `baseline` is the position the delta was taken against, deltas cannot be unpacked without it.
+    pub fn unpack(&self, baseline: Option<&Position>) -> Option<Position> {
+        match self {
+            Self::Absolute { chunk, offset } => {
+                Some(Position {
+                    chunk: *chunk,
+                    offset: offset
+                        .map(|offset| offset as f32 / POSITION_QUANTUM as f32)
+                        .into(),
+                })
+            },
+            Self::Delta(delta) => {
+                let baseline = baseline?;
+                let quantized = baseline.quantized();
+
+                Position::from_quantized(
+                    baseline.chunk.dimension,
+                    [0, 1, 2].map(|i| quantized[i] + delta[i] as i64),
+                )
+            },
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct LocalPosition {
     pub vector: Vec3F32,
@@ -28,3 +148,49 @@ impl Add<LocalPosition> for LocalPosition {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::chunk::DimensionKind;
+
+    #[test]
+    fn check_position_delta_roundtrip() {
+        let dimension = Dimension {
+            kind: DimensionKind(0),
+            phase: 0,
+        };
+
+        let position = |chunk: [i32; 3], offset: [f32; 3]| {
+            Position {
+                chunk: Chunk {
+                    position: chunk,
+                    dimension,
+                },
+                offset: offset.into(),
+            }
+        };
+
+        let baseline = position([0, -1, 3], [15.9, 0.1, 7.5]);
+        let baseline = baseline.pack_absolute().unpack(None).unwrap();
+
+        let moved = position([1, -1, 3], [0.2, 0.35, 7.5]);
+        let packed = moved.pack_delta(&baseline);
+
+        assert!(matches!(packed, PositionPacked::Delta(_)));
+        assert!(packed.unpack(None).is_none());
+
+        let unpacked = packed.unpack(Some(&baseline)).unwrap();
+
+        assert_eq!(unpacked.chunk, moved.chunk);
+        assert!(
+            (unpacked.offset - moved.offset).abs().max_element() <= 0.5 / POSITION_QUANTUM as f32
+        );
+
+        // Chains of deltas do not accumulate errors
+        assert_eq!(
+            moved.pack_delta(&unpacked).unpack(Some(&unpacked)),
+            Some(unpacked)
+        );
+    }
+}
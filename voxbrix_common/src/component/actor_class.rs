@@ -0,0 +1,29 @@
+use crate::{
+    entity::actor_class::ActorClass,
+    system::actor_class_loading::LoadActorClassComponent,
+    AsFromUsize,
+};
+
+pub mod collider;
+
+pub struct ActorClassComponent<T> {
+    classes: Vec<Option<T>>,
+}
+
+impl<T> ActorClassComponent<T> {
+    pub fn new() -> Self {
+        Self {
+            classes: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, actor_class: &ActorClass) -> Option<&T> {
+        self.classes.get(actor_class.as_usize())?.as_ref()
+    }
+}
+
+impl<T> LoadActorClassComponent<T> for ActorClassComponent<T> {
+    fn reload_classes(&mut self, data: Vec<Option<T>>) {
+        self.classes = data;
+    }
+}
@@ -0,0 +1,15 @@
+use crate::component::actor_class::ActorClassComponent;
+use serde::Deserialize;
+
+pub type ColliderActorClassComponent = ActorClassComponent<Collider>;
+
+/// Axis-aligned box the actor collides with blocks by.
+/// Actor classes without it move as points.
+#[derive(Deserialize, Default, Clone, Copy, Debug)]
+pub struct Collider {
+    /// Half of the box size on each axis, the actor position is the center of the box.
+    pub radius: [f32; 3],
+    /// Height of the obstacles the actor walks onto without jumping.
+    #[serde(default)]
+    pub step_height: f32,
+}
@@ -19,15 +19,18 @@ use serde::{
     },
     ser::{
         Serialize,
-        SerializeTuple,
+        SerializeSeq,
         Serializer,
     },
 };
 use std::{
     collections::HashMap,
     fmt,
+    iter,
 };
 
+pub mod block_entity;
+pub mod block_light;
 pub mod sky_light;
 
 pub trait BlockComponent<T> {
@@ -66,24 +69,30 @@ impl<T> BlocksVecBuilder<T> {
 
 impl<'de, T> Visitor<'de> for BlocksVecBuilder<T>
 where
-    T: Deserialize<'de>,
+    T: Deserialize<'de> + Clone,
 {
     type Value = BlocksVec<T>;
 
     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        write!(formatter, "an array of {} elements", BLOCKS_IN_CHUNK)
+        write!(formatter, "runs of {} elements in total", BLOCKS_IN_CHUNK)
     }
 
     fn visit_seq<A>(mut self, mut seq: A) -> Result<Self::Value, A::Error>
     where
         A: SeqAccess<'de>,
     {
-        for _ in 0 .. BLOCKS_IN_CHUNK {
-            let value = seq
-                .next_element()?
-                .ok_or(A::Error::custom("not enough Blocks for Chunk"))?;
+        while let Some((length, value)) = seq.next_element::<(u16, T)>()? {
+            let length = length as usize;
+
+            if length == 0 || self.0.len() + length > BLOCKS_IN_CHUNK {
+                return Err(A::Error::custom("invalid run of Blocks for Chunk"));
+            }
+
+            self.0.extend(iter::repeat_n(value, length));
+        }
 
-            self.push(value);
+        if self.0.len() != BLOCKS_IN_CHUNK {
+            return Err(A::Error::custom("not enough Blocks for Chunk"));
         }
 
         Ok(self.build())
@@ -93,32 +102,34 @@ where
 #[derive(Clone, Debug)]
 pub struct BlocksVec<T>(Box<[T; BLOCKS_IN_CHUNK]>);
 
+/// Serialized as a sequence of `(length, value)` runs of equal values,
+/// since chunks mostly consist of long stretches of the same blocks (e.g. air or stone).
 impl<T> Serialize for BlocksVec<T>
 where
-    T: Serialize,
+    T: Serialize + PartialEq,
 {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let mut tup = serializer.serialize_tuple(BLOCKS_IN_CHUNK)?;
+        let mut seq = serializer.serialize_seq(Some(self.runs().count()))?;
 
-        for e in self.0.iter() {
-            tup.serialize_element(e)?;
+        for run in self.runs() {
+            seq.serialize_element(&run)?;
         }
 
-        tup.end()
+        seq.end()
     }
 }
 impl<'de, T> Deserialize<'de> for BlocksVec<T>
 where
-    T: Deserialize<'de>,
+    T: Deserialize<'de> + Clone,
 {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
-        deserializer.deserialize_tuple(BLOCKS_IN_CHUNK, BlocksVecBuilder::new())
+        deserializer.deserialize_seq(BlocksVecBuilder::new())
     }
 }
 
@@ -147,6 +158,25 @@ impl<T> BlocksVec<T> {
             .enumerate()
             .map(|(b, v)| (Block::from_usize(b).unwrap(), v))
     }
+
+    /// Iterate over runs of equal values as `(length, value)`.
+    fn runs(&self) -> impl Iterator<Item = (u16, &T)>
+    where
+        T: PartialEq,
+    {
+        let mut values = self.0.iter().peekable();
+
+        iter::from_fn(move || {
+            let value = values.next()?;
+            let mut length = 1;
+
+            while values.next_if(|next| *next == value).is_some() {
+                length += 1;
+            }
+
+            Some((length, value))
+        })
+    }
 }
 
 impl<'a, T> BlocksVec<T>
@@ -231,3 +261,48 @@ impl<T> BlockComponentSimple<T> {
         self.chunks.iter()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        entity::block_class::BlockClass,
+        pack,
+    };
+
+    #[test]
+    fn test_blocks_vec_serde() {
+        let mut initial = BlocksVec::new_cloned(BlockClass(0));
+
+        for i in 0 .. 100 {
+            *initial.get_mut(Block::from_usize(i * 7).unwrap()) = BlockClass(i as u64 % 3 + 1);
+        }
+
+        *initial.get_mut(Block::from_usize(BLOCKS_IN_CHUNK - 1).unwrap()) = BlockClass(5);
+
+        let mut buffer = Vec::new();
+        pack::encode_write(&initial, &mut buffer);
+        let (control, _) = pack::decode_from_slice::<BlocksVec<BlockClass>>(&buffer).unwrap();
+
+        for ((_, initial), (_, control)) in initial.iter().zip(control.iter()) {
+            assert_eq!(initial, control);
+        }
+
+        let air = BlocksVec::new_cloned(BlockClass(0));
+        let mut buffer = Vec::new();
+        pack::encode_write(&air, &mut buffer);
+
+        assert!(buffer.len() < 8);
+
+        let (control, _) = pack::decode_from_slice::<BlocksVec<BlockClass>>(&buffer).unwrap();
+        assert!(control.iter().all(|(_, class)| *class == BlockClass(0)));
+
+        // Runs exceeding the chunk size must be rejected
+        let mut buffer = Vec::new();
+        pack::encode_write(
+            &vec![(4000u16, BlockClass(0)), (200u16, BlockClass(1))],
+            &mut buffer,
+        );
+        assert!(pack::decode_from_slice::<BlocksVec<BlockClass>>(&buffer).is_none());
+    }
+}
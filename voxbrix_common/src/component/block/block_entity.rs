@@ -0,0 +1,36 @@
+use crate::{
+    entity::block::Block,
+    pack::Pack,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::collections::BTreeMap;
+
+/// Rich state of a single block instance, like the furnace progress or the sign text.
+/// Unlike the block metadata, it is synchronized to the clients viewing the chunk.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct BlockEntity {
+    /// Script-defined kind of the entity, for the client to tell how to interpret the `data`.
+    pub kind: String,
+    pub data: Vec<u8>,
+}
+
+/// Block entities of a chunk, only the blocks that have them are stored.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct ChunkBlockEntities(pub BTreeMap<Block, BlockEntity>);
+
+impl ChunkBlockEntities {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, block: Block) -> Option<&BlockEntity> {
+        self.0.get(&block)
+    }
+}
+
+impl Pack for ChunkBlockEntities {
+    const DEFAULT_COMPRESSED: bool = true;
+}
@@ -0,0 +1,27 @@
+use crate::component::block::{
+    BlockComponentSimple,
+    BlocksVec,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BlockLight(u8);
+
+impl BlockLight {
+    const DEFAULT_FADE: u8 = 1;
+    pub const MAX: Self = Self(16);
+    pub const MIN: Self = Self(0);
+
+    pub fn fade(self) -> Self {
+        Self(self.0.saturating_sub(Self::DEFAULT_FADE))
+    }
+
+    pub fn value(self) -> u8 {
+        self.0
+    }
+
+    pub fn from_value(value: u8) -> Self {
+        Self(value.min(Self::MAX.0))
+    }
+}
+
+pub type BlockLightBlockComponent = BlockComponentSimple<BlocksVec<BlockLight>>;
@@ -4,6 +4,8 @@ use crate::{
 };
 
 pub mod collision;
+pub mod fluid;
+pub mod light;
 pub mod opacity;
 
 pub struct BlockClassComponent<T> {
@@ -0,0 +1,35 @@
+use crate::component::block_class::BlockClassComponent;
+use serde::Deserialize;
+
+pub type FluidBlockClassComponent = BlockClassComponent<Fluid>;
+
+/// Number of the fluid levels, the block of the top level is full.
+pub const MAX_FLUID_LEVEL: u8 = 8;
+
+/// Block class filled with a fluid up to a certain level.
+/// Each level of the fluid is a separate block class,
+/// the fluid flows by replacing the block classes of its level.
+#[derive(Deserialize, Debug)]
+pub struct Fluid {
+    /// Label of the fluid, shared by the block classes of all its levels.
+    pub fluid: String,
+    /// From 1 to `MAX_FLUID_LEVEL`.
+    pub level: u8,
+    /// Part of the actor movement the fluid takes away, from 0 to 1.
+    #[serde(default)]
+    pub drag: f32,
+    /// Upward speed the fluid pushes the actors with, in blocks per second.
+    #[serde(default)]
+    pub buoyancy: f32,
+}
+
+impl Fluid {
+    /// Height of the fluid surface within the block, from 0 to 1.
+    pub fn height(&self) -> f32 {
+        self.level.min(MAX_FLUID_LEVEL) as f32 / MAX_FLUID_LEVEL as f32
+    }
+
+    pub fn is_same_fluid(&self, other: &Self) -> bool {
+        self.fluid == other.fluid
+    }
+}
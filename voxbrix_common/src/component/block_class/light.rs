@@ -0,0 +1,19 @@
+use crate::component::{
+    block::block_light::BlockLight,
+    block_class::BlockClassComponent,
+};
+use serde::Deserialize;
+
+pub type LightBlockClassComponent = BlockClassComponent<Light>;
+
+/// Light emitted by the blocks of the class.
+#[derive(Deserialize, Debug)]
+pub struct Light {
+    emission: u8,
+}
+
+impl Light {
+    pub fn emission(&self) -> BlockLight {
+        BlockLight::from_value(self.emission)
+    }
+}
@@ -5,6 +5,9 @@ pub mod actor_model;
 pub mod block;
 pub mod block_class;
 pub mod chunk;
+pub mod effect;
+pub mod item_class;
+pub mod recipe;
 pub mod script;
 pub mod snapshot;
 pub mod state_component;
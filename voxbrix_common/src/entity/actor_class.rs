@@ -4,7 +4,7 @@ use serde::{
     Serialize,
 };
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Copy, Clone, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Copy, Clone, Debug)]
 pub struct ActorClass(pub u64);
 
 impl AsFromUsize for ActorClass {
@@ -0,0 +1,20 @@
+use crate::AsFromUsize;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Debug)]
+pub struct Recipe(pub u64);
+
+impl nohash_hasher::IsEnabled for Recipe {}
+
+impl AsFromUsize for Recipe {
+    fn as_usize(&self) -> usize {
+        self.0.try_into().unwrap()
+    }
+
+    fn from_usize(i: usize) -> Self {
+        Self(i.try_into().unwrap())
+    }
+}
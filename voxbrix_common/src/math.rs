@@ -1,5 +1,7 @@
 use std::cmp::Ordering;
 
+pub mod query;
+
 pub type Vec3F32 = glam::Vec3;
 pub type Vec3I32 = glam::IVec3;
 pub type QuatF32 = glam::Quat;
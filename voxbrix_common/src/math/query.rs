@@ -0,0 +1,339 @@
+//! Spatial queries against the blocks. The blocks are unit cubes, `filter` decides which of
+//! them are taken into account. Coordinates are relative to the chunk of the `position`.
+
+use crate::{
+    component::actor::position::Position,
+    entity::{
+        block::Block,
+        chunk::Chunk,
+    },
+    math::{
+        Round,
+        Vec3F32,
+    },
+};
+use std::cmp::Ordering;
+
+/// Block hit by a ray.
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+    /// Time of the hit in `direction` lengths.
+    pub time: f32,
+    pub chunk: Chunk,
+    pub block: Block,
+    /// Index of the side in [x_m, x_p, y_m, y_p, z_m, z_p].
+    pub side: usize,
+}
+
+/// Block hit by a moving box.
+#[derive(Clone, Copy, Debug)]
+pub struct BoxHit {
+    /// Fraction of the movement done before the hit, `0.0` to `1.0`.
+    pub time: f32,
+    /// Normal of the block side that was hit, zero if the box overlaps the block at the start.
+    pub normal: Vec3F32,
+    pub chunk: Chunk,
+    pub block: Block,
+}
+
+/// Finds the first block within `max_distance` along the `direction` that is accepted by
+/// `filter`.
+pub fn raycast(
+    position: &Position,
+    direction: Vec3F32,
+    max_distance: f32,
+    mut filter: impl FnMut(Chunk, Block) -> bool,
+) -> Option<RayHit> {
+    let mut hit: Option<RayHit> = None;
+
+    for (axis_0, axis_1, axis_2) in [(0, 1, 2), (1, 2, 0), (2, 0, 1)] {
+        for axis_offset in 0 .. max_distance.ceil() as i32 {
+            // wall_offset helps to calculate the distance to the layer ("wall") of blocks
+            //     if we move to positive direction we need to add 1 after round_down()
+            //     while moving in the negative direction, the value is 0
+            // block_coord_offset helps to get the coordinate of the "wall" block layer
+            //     if we move to the negative direction the actual coordinate would be 1 block
+            //     behind the "wall" coordinate, because we "collide" with the front side of
+            //     the block in this case
+            //     while moving in the positive direction, the value is 0 as we collide with
+            //     the back side of the block, which is the same as it's coordinate
+            // side_index is a index of side/neighbor in [x_m, x_p, y_m, y_p, z_m, z_p]
+            let (axis_offset, wall_offset, block_coord_offset, side_index) =
+                match direction[axis_0].total_cmp(&0.0) {
+                    Ordering::Less => (-axis_offset, 0, -1, axis_0 * 2 + 1),
+                    Ordering::Greater => (axis_offset, 1, 0, axis_0 * 2),
+                    _ => continue,
+                };
+
+            // Distance to the colliding side
+            let block_side_axis_0 =
+                position.offset[axis_0].round_down() + axis_offset + wall_offset;
+
+            let time = (block_side_axis_0 as f32 - position.offset[axis_0]) / direction[axis_0];
+
+            if time * direction.length() > max_distance {
+                break;
+            }
+
+            // Distance to the colliding block
+            let block_axis_0 = block_side_axis_0 + block_coord_offset;
+
+            let is_record = hit.map(|hit| time < hit.time).unwrap_or(true);
+
+            if is_record {
+                let block_axis_1 =
+                    (position.offset[axis_1] + time * direction[axis_1]).round_down();
+
+                let block_axis_2 =
+                    (position.offset[axis_2] + time * direction[axis_2]).round_down();
+
+                let mut block_offset = [0; 3];
+
+                block_offset[axis_0] = block_axis_0;
+                block_offset[axis_1] = block_axis_1;
+                block_offset[axis_2] = block_axis_2;
+
+                if let Some((chunk, block)) = Block::from_chunk_offset(position.chunk, block_offset)
+                {
+                    if filter(chunk, block) {
+                        hit = Some(RayHit {
+                            time,
+                            chunk,
+                            block,
+                            side: side_index,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    hit
+}
+
+/// Moves the box with the `radius` half-size from the `position` by `movement` and finds
+/// the first block accepted by `filter` that it hits.
+/// Every block within the moved box bounds is checked, so the movement should be short.
+pub fn cast_box(
+    position: &Position,
+    radius: &[f32; 3],
+    movement: Vec3F32,
+    mut filter: impl FnMut(Chunk, Block) -> bool,
+) -> Option<BoxHit> {
+    let start = position.offset;
+    let finish = start + movement;
+    let radius = Vec3F32::from_array(*radius);
+
+    let bounds_min = (start.min(finish) - radius)
+        .to_array()
+        .map(Round::round_down);
+    let bounds_max = (start.max(finish) + radius)
+        .to_array()
+        .map(Round::round_down);
+
+    let mut hit: Option<BoxHit> = None;
+
+    for z in bounds_min[2] ..= bounds_max[2] {
+        for y in bounds_min[1] ..= bounds_max[1] {
+            for x in bounds_min[0] ..= bounds_max[0] {
+                let block_min = Vec3F32::new(x as f32, y as f32, z as f32);
+
+                // The box center hits the block grown by the box radius
+                let Some((time, normal)) = ray_box_intersection(
+                    start,
+                    movement,
+                    block_min - radius,
+                    block_min + Vec3F32::ONE + radius,
+                ) else {
+                    continue;
+                };
+
+                if hit.is_some_and(|hit| hit.time <= time) {
+                    continue;
+                }
+
+                let Some((chunk, block)) = Block::from_chunk_offset(position.chunk, [x, y, z])
+                else {
+                    continue;
+                };
+
+                if filter(chunk, block) {
+                    hit = Some(BoxHit {
+                        time,
+                        normal,
+                        chunk,
+                        block,
+                    });
+                }
+            }
+        }
+    }
+
+    hit
+}
+
+/// Blocks accepted by `filter` that the sphere with the `radius` around the `position`
+/// touches.
+pub fn overlap_sphere(
+    position: &Position,
+    radius: f32,
+    mut filter: impl FnMut(Chunk, Block) -> bool,
+) -> Vec<(Chunk, Block)> {
+    let center = position.offset;
+    let bounds_min = (center - Vec3F32::splat(radius))
+        .to_array()
+        .map(Round::round_down);
+    let bounds_max = (center + Vec3F32::splat(radius))
+        .to_array()
+        .map(Round::round_down);
+
+    let mut blocks = Vec::new();
+
+    for z in bounds_min[2] ..= bounds_max[2] {
+        for y in bounds_min[1] ..= bounds_max[1] {
+            for x in bounds_min[0] ..= bounds_max[0] {
+                let block_min = Vec3F32::new(x as f32, y as f32, z as f32);
+                let closest = center.clamp(block_min, block_min + Vec3F32::ONE);
+
+                if closest.distance_squared(center) > radius * radius {
+                    continue;
+                }
+
+                let Some((chunk, block)) = Block::from_chunk_offset(position.chunk, [x, y, z])
+                else {
+                    continue;
+                };
+
+                if filter(chunk, block) {
+                    blocks.push((chunk, block));
+                }
+            }
+        }
+    }
+
+    blocks
+}
+
+/// Time within `0.0 ..= 1.0` when the ray enters the box and the normal of the entered side.
+fn ray_box_intersection(
+    origin: Vec3F32,
+    direction: Vec3F32,
+    box_min: Vec3F32,
+    box_max: Vec3F32,
+) -> Option<(f32, Vec3F32)> {
+    let mut entry = f32::NEG_INFINITY;
+    let mut exit = f32::INFINITY;
+    let mut normal = Vec3F32::ZERO;
+
+    for axis in 0 .. 3 {
+        if direction[axis] == 0.0 {
+            if origin[axis] <= box_min[axis] || origin[axis] >= box_max[axis] {
+                return None;
+            }
+
+            continue;
+        }
+
+        let near = (box_min[axis] - origin[axis]) / direction[axis];
+        let far = (box_max[axis] - origin[axis]) / direction[axis];
+        let (near, far) = if near < far { (near, far) } else { (far, near) };
+
+        if near > entry {
+            entry = near;
+            normal = Vec3F32::ZERO;
+            normal[axis] = -direction[axis].signum();
+        }
+
+        exit = exit.min(far);
+    }
+
+    if entry >= exit || exit <= 0.0 || entry > 1.0 {
+        return None;
+    }
+
+    if entry < 0.0 {
+        return Some((0.0, Vec3F32::ZERO));
+    }
+
+    Some((entry, normal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::chunk::{
+        Dimension,
+        DimensionKind,
+    };
+
+    fn position(offset: [f32; 3]) -> Position {
+        Position {
+            chunk: Chunk {
+                position: [0, 0, 0],
+                dimension: Dimension {
+                    kind: DimensionKind(0),
+                    phase: 0,
+                },
+            },
+            offset: Vec3F32::from_array(offset),
+        }
+    }
+
+    fn is_floor(_: Chunk, block: Block) -> bool {
+        block.into_coords()[2] == 0
+    }
+
+    #[test]
+    fn raycast_hits_floor_top() {
+        let hit = raycast(
+            &position([2.5, 2.5, 4.5]),
+            Vec3F32::new(0.0, 0.0, -1.0),
+            8.0,
+            is_floor,
+        )
+        .unwrap();
+
+        assert_eq!(hit.block.into_coords(), [2, 2, 0]);
+        assert_eq!(hit.side, 5);
+        assert!((hit.time - 3.5).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn raycast_respects_max_distance() {
+        let hit = raycast(
+            &position([2.5, 2.5, 4.5]),
+            Vec3F32::new(0.0, 0.0, -1.0),
+            2.0,
+            is_floor,
+        );
+
+        assert!(hit.is_none());
+    }
+
+    #[test]
+    fn cast_box_stops_on_floor() {
+        let hit = cast_box(
+            &position([2.5, 2.5, 3.0]),
+            &[0.25, 0.25, 0.5],
+            Vec3F32::new(0.0, 0.0, -4.0),
+            is_floor,
+        )
+        .unwrap();
+
+        assert!((hit.time - 0.375).abs() < 1.0e-5);
+        assert_eq!(hit.normal, Vec3F32::new(0.0, 0.0, 1.0));
+        assert_eq!(hit.block.into_coords()[2], 0);
+    }
+
+    #[test]
+    fn overlap_sphere_touches_floor() {
+        let blocks = overlap_sphere(&position([2.5, 2.5, 1.4]), 0.5, is_floor);
+
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].1.into_coords(), [2, 2, 0]);
+
+        let blocks = overlap_sphere(&position([2.5, 2.5, 1.6]), 0.5, is_floor);
+
+        assert!(blocks.is_empty());
+    }
+}
@@ -7,6 +7,7 @@ use crate::{
     },
     pack::{
         self,
+        Pack,
         UnpackError,
     },
 };
@@ -26,6 +27,43 @@ use std::{
 pub mod client;
 pub mod server;
 
+/// Channel of `server::AdminCommand` and `client::AdminResponse`,
+/// kept apart so the commands are not delayed by the game messages.
+pub const ADMIN_CHANNEL: usize = 1;
+
+/// UDP port the servers broadcast `LanBeacon` to.
+pub const LAN_BEACON_PORT: u16 = 12001;
+
+/// Announces the server to the clients in the local network, sent outside of any connection.
+/// The server accepts the connections on the address the beacon came from, with the `port`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LanBeacon {
+    pub name: String,
+    pub port: u16,
+    pub players: u32,
+    pub max_players: u32,
+    /// Protocol version of the server, the clients of another one cannot connect.
+    pub protocol_version: u16,
+}
+
+impl Pack for LanBeacon {
+    const DEFAULT_COMPRESSED: bool = false;
+}
+
+/// Features of the messages announced in the protocol handshake, one bit per feature.
+/// A peer must not send the messages of a feature the connection did not negotiate.
+pub mod features {
+    /// Login success is followed by the asset synchronization, see `client::AssetManifest`.
+    pub const ASSET_SYNC: u64 = 1 << 0;
+    /// Admin commands are accepted on `ADMIN_CHANNEL`.
+    pub const ADMIN_COMMANDS: u64 = 1 << 1;
+
+    /// All the features this build supports.
+    pub const SUPPORTED: u64 = ASSET_SYNC | ADMIN_COMMANDS;
+    /// Features the peer must support, the messages of the older builds are incompatible.
+    pub const REQUIRED: u64 = ASSET_SYNC;
+}
+
 /// State container.
 /// The components supposed to be transfered in delta manner,
 /// meaining that only changed components are in the map.
@@ -1,9 +1,19 @@
 use crate::{
+    component::{
+        actor::position::Position,
+        block::block_entity::{
+            BlockEntity,
+            ChunkBlockEntities,
+        },
+    },
     entity::{
         actor::Actor,
         block::Block,
         block_class::BlockClass,
-        chunk::Chunk,
+        chunk::{
+            Chunk,
+            Dimension,
+        },
         snapshot::Snapshot,
     },
     messages::{
@@ -51,15 +61,58 @@ pub struct InitData {
     pub actor: Actor,
     // position: Position,
     pub player_chunk_view_radius: i32,
+    /// Interval of the server ticks, in milliseconds.
+    pub process_interval_ms: u64,
 }
 
 impl Pack for InitData {
     const DEFAULT_COMPRESSED: bool = false;
 }
 
+/// Asset file of one of the server packs.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AssetEntry {
+    /// Index in `AssetManifest::packs`.
+    pub pack: u32,
+    /// Path relative to the pack root, with `/` separators.
+    pub path: String,
+    pub hash: u64,
+}
+
+/// Label list in the order the server assigned the ids in.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AssetList {
+    pub path: String,
+    pub labels: Vec<String>,
+}
+
+/// Common assets of the server, sent on the successful login.
+/// The client requests the files it misses with `AssetRequest`
+/// and receives an `AssetFile` for each of them, followed by `InitData`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AssetManifest {
+    /// Pack labels in the load order.
+    pub packs: Vec<String>,
+    pub files: Vec<AssetEntry>,
+    /// Lists have to be taken from here rather than merged from the files,
+    /// the server keeps the label ids stable for the world.
+    pub lists: Vec<AssetList>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AssetFile {
+    /// Index in `AssetManifest::files`.
+    pub index: u32,
+    pub data: Vec<u8>,
+}
+
+impl Pack for AssetFile {
+    const DEFAULT_COMPRESSED: bool = true;
+}
+
 #[derive(Serialize, Deserialize)]
 pub enum LoginResult {
-    Success(InitData),
+    Success(AssetManifest),
     Failure(LoginFailure),
 }
 
@@ -69,7 +122,7 @@ impl Pack for LoginResult {
 
 #[derive(Serialize, Deserialize)]
 pub enum RegisterResult {
-    Success(InitData),
+    Success(AssetManifest),
     Failure(RegisterFailure),
 }
 
@@ -77,6 +130,30 @@ impl Pack for RegisterResult {
     const DEFAULT_COMPRESSED: bool = false;
 }
 
+/// Result of the `server::AdminCommand`, to be shown to the administrator.
+#[derive(Serialize, Deserialize)]
+pub struct AdminResponse {
+    pub success: bool,
+    pub text: String,
+}
+
+impl Pack for AdminResponse {
+    const DEFAULT_COMPRESSED: bool = false;
+}
+
+/// The first message of a resumed connection, instead of the login.
+/// Assets are not synchronized again, the client keeps the ones it has.
+#[derive(Serialize, Deserialize)]
+pub enum ResumeResult {
+    Success(InitData),
+    /// The player has been removed after the grace period, the client has to log in anew.
+    Failure,
+}
+
+impl Pack for ResumeResult {
+    const DEFAULT_COMPRESSED: bool = false;
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct ChunkChanges<'a>(&'a [u8]);
 
@@ -208,19 +285,67 @@ impl<'a> ChunkChangesChunkEncoder<'a> {
     }
 }
 
+/// Player position set by the server, overriding the one sent by the client.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct PositionCorrection {
+    /// Server snapshot the correction was made at.
+    pub snapshot: Snapshot,
+    /// The last client snapshot applied by the server before the correction.
+    /// Client movement after this snapshot should be replayed on top of the `position`.
+    pub last_client_snapshot: Snapshot,
+    pub position: Position,
+}
+
 #[derive(Serialize, Deserialize)]
 pub enum ClientAccept<'a> {
     State {
         snapshot: Snapshot,
         // last client's snapshot received by the server
         last_client_snapshot: Snapshot,
+        // last server's snapshot acknowledged by the client, positions are sent as deltas against it
+        last_server_snapshot: Snapshot,
         #[serde(borrow)]
         state: StatePacked<'a>,
         #[serde(borrow)]
         actions: ActionsPacked<'a>,
+        // sent until the client acknowledges the snapshot of the correction
+        position_correction: Option<PositionCorrection>,
+        // actors that are no longer relevant to the player and must be removed,
+        // sent until the client acknowledges the snapshot they have left on
+        actors_left: Vec<Actor>,
     },
     ChunkData(ChunkData),
     ChunkChanges(#[serde(borrow)] ChunkChanges<'a>),
+    /// Block entities of the chunk, sent after its `ChunkData` if the chunk has any.
+    ChunkBlockEntities {
+        chunk: Chunk,
+        block_entities: ChunkBlockEntities,
+    },
+    /// Block entity changes in the chunks the player views,
+    /// `None` means the block entity has been removed.
+    BlockEntityChanges(Vec<(Chunk, Block, Option<BlockEntity>)>),
+    ChatMessage {
+        sender: String,
+        text: String,
+    },
+    /// The player actor has been moved into another dimension.
+    /// The new position arrives with the position correction.
+    DimensionChange {
+        dimension: Dimension,
+    },
+    /// Run the client script with the `script` label, the script reads the `payload`.
+    ScriptDispatch {
+        script: String,
+        payload: Vec<u8>,
+    },
+    /// Current world time, sent on joining and periodically afterwards.
+    /// The client advances the time on its own in between.
+    WorldTime {
+        /// Ticks since the world creation.
+        time: u64,
+        /// Length of the day and night cycle, in ticks.
+        day_length: u64,
+    },
 }
 
 impl Pack for ClientAccept<'_> {
@@ -1,5 +1,8 @@
 use crate::{
-    entity::snapshot::Snapshot,
+    entity::{
+        recipe::Recipe,
+        snapshot::Snapshot,
+    },
     messages::{
         ActionsPacked,
         StatePacked,
@@ -11,6 +14,18 @@ use serde::{
     Serialize,
 };
 
+/// Maximum length of a chat message text, in bytes.
+/// Longer messages are discarded by the server.
+pub const MAX_CHAT_MESSAGE_LENGTH: usize = 512;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChatScope {
+    /// Only players nearby receive the message.
+    Local,
+    /// All players receive the message.
+    Global,
+}
+
 #[derive(Serialize, Deserialize)]
 pub enum ServerAccept<'a> {
     State {
@@ -22,6 +37,19 @@ pub enum ServerAccept<'a> {
         #[serde(borrow)]
         actions: ActionsPacked<'a>,
     },
+    ChatMessage {
+        scope: ChatScope,
+        text: String,
+    },
+    /// Move the item stack between the player inventory slots.
+    MoveItem {
+        from: u32,
+        to: u32,
+    },
+    /// Craft the recipe using the items of the player inventory.
+    Craft {
+        recipe: Recipe,
+    },
 }
 
 impl Pack for ServerAccept<'_> {
@@ -59,3 +87,25 @@ pub struct RegisterRequest {
 impl Pack for RegisterRequest {
     const DEFAULT_COMPRESSED: bool = false;
 }
+
+/// Command line of a server administrator, e.g. `kick <player>`.
+/// Answered with `client::AdminResponse`.
+#[derive(Serialize, Deserialize)]
+pub struct AdminCommand {
+    pub command: String,
+}
+
+impl Pack for AdminCommand {
+    const DEFAULT_COMPRESSED: bool = false;
+}
+
+/// Files of the `AssetManifest` the client does not have in its cache.
+#[derive(Serialize, Deserialize)]
+pub struct AssetRequest {
+    /// Indices in `AssetManifest::files`.
+    pub files: Vec<u32>,
+}
+
+impl Pack for AssetRequest {
+    const DEFAULT_COMPRESSED: bool = false;
+}
@@ -1,2 +1,5 @@
 #[cfg(feature = "server")]
 mod server_loop;
+
+#[cfg(feature = "client")]
+mod client_loop;
@@ -0,0 +1,68 @@
+use crate::entity::{
+    block_class::BlockClass,
+    chunk::{
+        Chunk,
+        Dimension,
+        DimensionKind,
+    },
+};
+
+impl From<client_loop_api::DimensionKind> for DimensionKind {
+    fn from(value: client_loop_api::DimensionKind) -> Self {
+        Self(value.0)
+    }
+}
+
+impl From<DimensionKind> for client_loop_api::DimensionKind {
+    fn from(value: DimensionKind) -> Self {
+        Self(value.0)
+    }
+}
+
+impl From<client_loop_api::Dimension> for Dimension {
+    fn from(value: client_loop_api::Dimension) -> Self {
+        Self {
+            kind: value.kind.into(),
+            phase: value.phase,
+        }
+    }
+}
+
+impl From<Dimension> for client_loop_api::Dimension {
+    fn from(value: Dimension) -> Self {
+        Self {
+            kind: value.kind.into(),
+            phase: value.phase,
+        }
+    }
+}
+
+impl From<client_loop_api::Chunk> for Chunk {
+    fn from(value: client_loop_api::Chunk) -> Self {
+        Self {
+            position: value.position,
+            dimension: value.dimension.into(),
+        }
+    }
+}
+
+impl From<Chunk> for client_loop_api::Chunk {
+    fn from(value: Chunk) -> Self {
+        Self {
+            position: value.position,
+            dimension: value.dimension.into(),
+        }
+    }
+}
+
+impl From<client_loop_api::BlockClass> for BlockClass {
+    fn from(value: client_loop_api::BlockClass) -> Self {
+        Self(value.0)
+    }
+}
+
+impl From<BlockClass> for client_loop_api::BlockClass {
+    fn from(value: BlockClass) -> Self {
+        Self(value.0)
+    }
+}
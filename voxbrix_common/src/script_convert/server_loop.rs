@@ -8,6 +8,9 @@ use crate::entity::{
         Dimension,
         DimensionKind,
     },
+    effect::Effect,
+    item_class::ItemClass,
+    recipe::Recipe,
 };
 
 impl From<server_loop_api::Block> for Block {
@@ -76,6 +79,42 @@ impl From<server_loop_api::BlockClass> for BlockClass {
     }
 }
 
+impl From<BlockClass> for server_loop_api::BlockClass {
+    fn from(value: BlockClass) -> Self {
+        Self(value.0)
+    }
+}
+
+impl From<server_loop_api::ItemClass> for ItemClass {
+    fn from(value: server_loop_api::ItemClass) -> Self {
+        Self(value.0)
+    }
+}
+
+impl From<ItemClass> for server_loop_api::ItemClass {
+    fn from(value: ItemClass) -> Self {
+        Self(value.0)
+    }
+}
+
+impl From<server_loop_api::Effect> for Effect {
+    fn from(value: server_loop_api::Effect) -> Self {
+        Self(value.0)
+    }
+}
+
+impl From<Effect> for server_loop_api::Effect {
+    fn from(value: Effect) -> Self {
+        Self(value.0)
+    }
+}
+
+impl From<Recipe> for server_loop_api::Recipe {
+    fn from(value: Recipe) -> Self {
+        Self(value.0)
+    }
+}
+
 impl From<server_loop_api::Action> for Action {
     fn from(value: server_loop_api::Action) -> Self {
         Self(value.0)
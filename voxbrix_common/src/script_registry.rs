@@ -2,18 +2,33 @@ use crate::{
     entity::script::Script,
     pack,
     read_data_file,
-    system::list_loading::List,
+    system::{
+        list_loading::List,
+        pack_loading::PackSet,
+    },
     LabelMap,
 };
 use anyhow::{
     Context,
     Error,
 };
-use serde::Serialize;
+use log::error;
+use serde::{
+    Deserialize,
+    Serialize,
+};
 use std::{
     fmt::Debug,
+    fs,
     mem,
-    path::Path,
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
 };
 use tokio::task;
 use wasmtime::{
@@ -24,9 +39,76 @@ use wasmtime::{
     Memory,
     Module,
     Store,
+    StoreLimits,
+    StoreLimitsBuilder,
     TypedFunc,
 };
 
+/// What happens to the script that has failed, e.g. ran out of fuel or memory.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptFailurePolicy {
+    /// The failed run is skipped, the script runs again next time.
+    /// The script memory is left as it was at the moment of the failure.
+    #[default]
+    Skip,
+    /// The script is not run anymore, until the scripts are reloaded.
+    Disable,
+}
+
+/// Limits of a single script run, so a faulty script could not stall the caller.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ScriptLimits {
+    /// Fuel given to each run, roughly the number of wasm instructions.
+    /// Requires the engine to be created with `Config::consume_fuel` enabled.
+    pub fuel: Option<u64>,
+    /// Maximum size of the memory of each script, in bytes.
+    pub max_memory_bytes: Option<usize>,
+    pub failure_policy: ScriptFailurePolicy,
+}
+
+impl ScriptLimits {
+    fn store_limits(&self) -> StoreLimits {
+        let mut builder = StoreLimitsBuilder::new();
+
+        if let Some(max_memory_bytes) = self.max_memory_bytes {
+            builder = builder.memory_size(max_memory_bytes);
+        }
+
+        builder.build()
+    }
+}
+
+pub struct SendMutPtr<T>(*mut T);
+unsafe impl<T> Send for SendMutPtr<T> where T: Send {}
+
+impl<T> SendMutPtr<T> {
+    pub fn new(value: &mut T) -> Self {
+        Self(value)
+    }
+
+    pub unsafe fn get<'a>(&'a self) -> &'a T {
+        &*self.0
+    }
+
+    pub unsafe fn get_mut<'a>(&'a mut self) -> &'a mut T {
+        &mut *self.0
+    }
+}
+
+pub struct SendPtr<T>(*const T);
+unsafe impl<T> Send for SendPtr<T> where T: Sync {}
+
+impl<T> SendPtr<T> {
+    pub fn new(value: &T) -> Self {
+        Self(value)
+    }
+
+    pub unsafe fn get<'a>(&'a self) -> &'a T {
+        unsafe { &*self.0 }
+    }
+}
+
 struct DynamicScriptData<T> {
     shared: T,
     buffer: Vec<u8>,
@@ -38,16 +120,22 @@ struct DynamicScriptData<T> {
     get_buffer_func: TypedFunc<u32, u32>,
 }
 
-pub struct ScriptData<T>(Option<DynamicScriptData<T>>);
+pub struct ScriptData<T> {
+    dynamic: Option<DynamicScriptData<T>>,
+    limits: StoreLimits,
+}
 
 impl<T> ScriptData<T> {
     // Empty, unusable ScriptData for initializing the store.
-    fn empty() -> Self {
-        Self(None)
+    fn empty(limits: StoreLimits) -> Self {
+        Self {
+            dynamic: None,
+            limits,
+        }
     }
 
     fn unset_dynamic(&mut self, common_buffer: &mut Vec<u8>) -> T {
-        let data = self.0.take().expect("dynamic data is already unset");
+        let data = self.dynamic.take().expect("dynamic data is already unset");
         *common_buffer = data.buffer;
         data.shared
     }
@@ -59,7 +147,7 @@ impl<T> ScriptData<T> {
         memory: Memory,
         get_buffer_func: TypedFunc<u32, u32>,
     ) {
-        self.0 = Some(DynamicScriptData {
+        self.dynamic = Some(DynamicScriptData {
             buffer: mem::take(common_buffer),
             shared,
             memory,
@@ -68,16 +156,16 @@ impl<T> ScriptData<T> {
     }
 
     pub fn shared(&self) -> &T {
-        &self.0.as_ref().expect("dynamic data unset").shared
+        &self.dynamic.as_ref().expect("dynamic data unset").shared
     }
 
     pub fn shared_mut(&mut self) -> &mut T {
-        &mut self.0.as_mut().expect("dynamic data unset").shared
+        &mut self.dynamic.as_mut().expect("dynamic data unset").shared
     }
 
     /// Complete memory of the store.
     pub fn memory(&self) -> Memory {
-        self.0
+        self.dynamic
             .as_ref()
             .expect("script data is not initialized")
             .memory
@@ -88,7 +176,7 @@ impl<T> ScriptData<T> {
     /// the memory of the store.
     /// Returns a pointer to the allocated memory inside the store.
     pub fn get_buffer_func(&self) -> TypedFunc<u32, u32> {
-        self.0
+        self.dynamic
             .as_ref()
             .expect("script data is not initialized")
             .get_buffer_func
@@ -98,7 +186,7 @@ impl<T> ScriptData<T> {
     /// Common buffer that can be used for e.g. serializing into it before copying to the store
     /// memory.
     pub fn buffer(&mut self) -> &mut Vec<u8> {
-        let buf = &mut self.0.as_mut().expect("dynamic data unset").buffer;
+        let buf = &mut self.dynamic.as_mut().expect("dynamic data unset").buffer;
 
         buf.clear();
 
@@ -139,12 +227,18 @@ pub fn write_script_buffer<T>(
 pub struct ScriptRegistryBuilder<T> {
     engine: Engine,
     label_map: LabelMap<Script>,
+    module_paths: Vec<PathBuf>,
     modules: Vec<Module>,
     linker: Linker<ScriptData<T>>,
+    limits: ScriptLimits,
     buffer: Vec<u8>,
 }
 
 impl<T> ScriptRegistryBuilder<T> {
+    pub fn set_limits(&mut self, limits: ScriptLimits) {
+        self.limits = limits;
+    }
+
     pub fn func_wrap<Params, Args>(
         &mut self,
         module: &str,
@@ -168,34 +262,74 @@ impl<T> ScriptRegistryBuilder<T> {
         }
         .with_context(|| format!("unable to load list \"{:?}\"", list_path))?;
 
+        let module_paths = list
+            .list
+            .iter()
+            .map(|file_name| dir_path.as_ref().join(file_name).with_extension("wasm"))
+            .collect::<Vec<_>>();
+
+        Self::load_modules(engine, list, module_paths).await
+    }
+
+    /// Loads the scripts listed by all the packs, each module is taken
+    /// from the last pack that has it.
+    pub async fn load_from_packs(
+        engine: Engine,
+        packs: PackSet,
+        list_path: impl 'static + AsRef<Path> + Debug + Send + Clone,
+        dir_path: impl 'static + AsRef<Path> + Debug + Send,
+    ) -> Result<Self, Error> {
+        let list = packs.load_list(list_path).await?;
+
+        let module_paths = {
+            let file_names = list.list.clone();
+
+            task::spawn_blocking(move || {
+                file_names
+                    .iter()
+                    .map(|file_name| {
+                        packs.resolve(dir_path.as_ref().join(file_name).with_extension("wasm"))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .await
+            .unwrap()?
+        };
+
+        Self::load_modules(engine, list, module_paths).await
+    }
+
+    async fn load_modules(
+        engine: Engine,
+        list: List,
+        module_paths: Vec<PathBuf>,
+    ) -> Result<Self, Error> {
         let engine_clone = engine.clone();
 
-        let label_map = list.clone().into_label_map();
-
-        let modules = task::spawn_blocking(move || {
-            list.list
-                .into_iter()
-                .map(|file_name| {
-                    let file_path = dir_path.as_ref().join(file_name).with_extension("wasm");
-
-                    Module::from_file(&engine_clone, &file_path)
-                        .map_err(|err| Error::from(err))
-                        .with_context(|| {
-                            format!("unable to load script module from \"{:?}\"", file_path)
-                        })
-                })
-                .collect::<Result<Vec<_>, _>>()
-        })
-        .await
-        .unwrap()?;
+        let label_map = list.into_label_map();
+
+        let modules = {
+            let module_paths = module_paths.clone();
+
+            task::spawn_blocking(move || {
+                module_paths
+                    .iter()
+                    .map(|file_path| load_module(&engine_clone, file_path))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .await
+            .unwrap()?
+        };
 
         let linker = Linker::new(&engine);
 
         Ok(Self {
             engine,
             label_map,
+            module_paths,
             modules,
             linker,
+            limits: ScriptLimits::default(),
             buffer: Vec::new(),
         })
     }
@@ -204,46 +338,151 @@ impl<T> ScriptRegistryBuilder<T> {
         let Self {
             engine,
             label_map,
+            module_paths,
             modules,
             linker,
+            limits,
             buffer,
         } = self;
 
-        let mut store = Store::new(&engine, ScriptData::empty());
-
-        let cache = modules
-            .into_iter()
-            .map(|module| {
-                let instance = linker
-                    .instantiate(&mut store, &module)
-                    .expect("instantiation should not fail");
-
-                let get_buffer_func = instance
-                    .get_typed_func::<u32, u32>(&mut store, "get_buffer")
-                    .unwrap();
-
-                let run_func = instance
-                    .get_typed_func::<(), ()>(&mut store, "run")
-                    .unwrap();
+        let (store, cache) = instantiate(&engine, &linker, &modules, &limits)
+            .expect("instantiation should not fail");
 
-                let memory = instance.get_memory(&mut store, "memory").unwrap();
-
-                CacheEntry {
-                    memory,
-                    get_buffer_func,
-                    run_func,
-                }
-            })
-            .collect();
+        let disabled = vec![false; cache.len()];
 
         ScriptRegistry {
             engine,
             label_map,
+            module_paths,
+            modules,
+            linker,
+            limits,
             store,
             cache,
+            disabled,
             buffer,
+            run_time: Duration::ZERO,
+        }
+    }
+}
+
+/// Compiles the script module from the file.
+pub fn load_module(engine: &Engine, file_path: &Path) -> Result<Module, Error> {
+    Module::from_file(engine, file_path)
+        .with_context(|| format!("unable to load script module from \"{:?}\"", file_path))
+}
+
+/// Labels listed in the custom sections named `section` of the script module, one per line.
+/// Scripts declare the labels they refer to this way, so those could be checked to exist
+/// before any of the scripts runs.
+///
+/// Blocking IO, must not be used directly in async
+pub fn read_module_labels(file_path: &Path, section: &str) -> Result<Vec<String>, Error> {
+    let bytes = fs::read(file_path)
+        .with_context(|| format!("unable to read script module \"{:?}\"", file_path))?;
+
+    let mut labels = Vec::new();
+
+    for payload in custom_sections(&bytes, section)
+        .with_context(|| format!("unable to parse script module \"{:?}\"", file_path))?
+    {
+        let text = std::str::from_utf8(payload).with_context(|| {
+            format!(
+                "labels of section \"{}\" in \"{:?}\" are not UTF-8",
+                section, file_path
+            )
+        })?;
+
+        labels.extend(text.lines().map(|label| label.to_owned()));
+    }
+
+    Ok(labels)
+}
+
+/// Payloads of the custom sections of the wasm module with the `name`, in the module order.
+/// The linker keeps the sections of the same name as they are or merges them into one.
+fn custom_sections<'a>(bytes: &'a [u8], name: &str) -> Result<Vec<&'a [u8]>, Error> {
+    let invalid = || Error::msg("invalid wasm module");
+
+    // Magic number and version
+    let mut rest = bytes.get(8 ..).ok_or_else(invalid)?;
+    let mut sections = Vec::new();
+
+    while let Some((&id, tail)) = rest.split_first() {
+        let (size, tail) = read_leb128(tail).ok_or_else(invalid)?;
+        let (content, tail) = tail.split_at_checked(size).ok_or_else(invalid)?;
+
+        rest = tail;
+
+        // Custom sections have id 0
+        if id != 0 {
+            continue;
+        }
+
+        let (name_len, content) = read_leb128(content).ok_or_else(invalid)?;
+        let (section_name, payload) = content.split_at_checked(name_len).ok_or_else(invalid)?;
+
+        if section_name == name.as_bytes() {
+            sections.push(payload);
+        }
+    }
+
+    Ok(sections)
+}
+
+/// Unsigned 32-bit LEB128 number, followed by the rest of the bytes.
+fn read_leb128(bytes: &[u8]) -> Option<(usize, &[u8])> {
+    let mut value = 0;
+
+    for (i, byte) in bytes.iter().take(5).enumerate() {
+        value |= ((byte & 0x7f) as usize) << (7 * i);
+
+        if byte & 0x80 == 0 {
+            return Some((value, &bytes[i + 1 ..]));
         }
     }
+
+    None
+}
+
+/// Instantiates the modules in a new store.
+fn instantiate<T>(
+    engine: &Engine,
+    linker: &Linker<ScriptData<T>>,
+    modules: &[Module],
+    limits: &ScriptLimits,
+) -> Result<(Store<ScriptData<T>>, Vec<CacheEntry>), Error> {
+    let mut store = Store::new(engine, ScriptData::empty(limits.store_limits()));
+
+    store.limiter(|data| &mut data.limits);
+
+    if let Some(fuel) = limits.fuel {
+        // Instantiation runs the start functions of the modules
+        store.set_fuel(fuel)?;
+    }
+
+    let cache = modules
+        .iter()
+        .map(|module| {
+            let instance = linker.instantiate(&mut store, module)?;
+
+            let get_buffer_func = instance.get_typed_func::<u32, u32>(&mut store, "get_buffer")?;
+
+            let run_func = instance.get_typed_func::<(), ()>(&mut store, "run")?;
+
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .ok_or_else(|| Error::msg("script memory is not exported"))?;
+
+            Ok(CacheEntry {
+                memory,
+                get_buffer_func,
+                run_func,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok((store, cache))
 }
 
 struct CacheEntry {
@@ -260,9 +499,17 @@ struct CacheEntry {
 pub struct ScriptRegistry<T> {
     engine: Engine,
     label_map: LabelMap<Script>,
+    module_paths: Vec<PathBuf>,
+    modules: Vec<Module>,
+    linker: Linker<ScriptData<T>>,
+    limits: ScriptLimits,
     store: Store<ScriptData<T>>,
     cache: Vec<CacheEntry>,
+    /// Scripts disabled after a failure, indexed by the script.
+    disabled: Vec<bool>,
     buffer: Vec<u8>,
+    /// Time spent running the scripts since the last `take_run_time`.
+    run_time: Duration,
 }
 
 impl<T> ScriptRegistry<T> {
@@ -274,10 +521,15 @@ impl<T> ScriptRegistry<T> {
         &self.label_map
     }
 
+    /// Runs the script, the failed runs are handled according to the limits.
     pub fn run_script<I>(&mut self, script: &Script, shared: T, input: I) -> T
     where
         I: Serialize,
     {
+        if self.disabled[script.0 as usize] {
+            return shared;
+        }
+
         self.buffer.clear();
 
         let cache = self
@@ -292,19 +544,107 @@ impl<T> ScriptRegistry<T> {
             cache.get_buffer_func.clone(),
         );
 
+        if let Some(fuel) = self.limits.fuel {
+            self.store
+                .set_fuel(fuel)
+                .expect("engine must have fuel consumption enabled");
+        }
+
         write_script_buffer(&mut self.store, &input);
 
-        cache
-            .run_func
-            .call(&mut self.store, ())
-            .expect("unable to run script");
+        let started = Instant::now();
+        let result = cache.run_func.call(&mut self.store, ());
+        self.run_time += started.elapsed();
 
         let shared = self.store.data_mut().unset_dynamic(&mut self.buffer);
 
+        if let Err(err) = result {
+            let label = self.label_map.get_label(script).unwrap_or("<unknown>");
+
+            match self.limits.failure_policy {
+                ScriptFailurePolicy::Skip => {
+                    error!("script \"{}\" failed: {:?}", label, err);
+                },
+                ScriptFailurePolicy::Disable => {
+                    error!("script \"{}\" failed and is disabled: {:?}", label, err);
+                    self.disabled[script.0 as usize] = true;
+                },
+            }
+        }
+
         shared
     }
 
+    /// Time spent running the scripts since the previous call.
+    pub fn take_run_time(&mut self) -> Duration {
+        mem::take(&mut self.run_time)
+    }
+
     pub fn engine(&self) -> &Engine {
         &self.engine
     }
+
+    /// Module file paths of the scripts, indexed by the script.
+    pub fn module_paths(&self) -> &[PathBuf] {
+        &self.module_paths
+    }
+
+    /// Replaces the modules of the scripts, e.g. recompiled after their files have changed.
+    /// All the scripts are instantiated anew and swapped in at once, their memory is reset.
+    /// Nothing is changed if any of the modules fails to instantiate.
+    ///
+    /// The script list stays the same, so the data mapped to the scripts by the labels
+    /// remains valid.
+    pub fn replace_modules(
+        &mut self,
+        replaced: impl IntoIterator<Item = (Script, Module)>,
+    ) -> Result<(), Error> {
+        let mut modules = self.modules.clone();
+
+        for (script, module) in replaced {
+            *modules
+                .get_mut(script.0 as usize)
+                .ok_or_else(|| Error::msg(format!("script {} does not exist", script.0)))? = module;
+        }
+
+        let (store, cache) = instantiate(&self.engine, &self.linker, &modules, &self.limits)?;
+
+        self.modules = modules;
+        self.store = store;
+        self.disabled = vec![false; cache.len()];
+        self.cache = cache;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_custom_sections() {
+        let mut module = b"\0asm\x01\0\0\0".to_vec();
+        // Type section with no types
+        module.extend([1, 1, 0]);
+        // Section size in the padded form the linkers write
+        module.extend([0, 0x90, 0x80, 0x80, 0x80, 0, 2]);
+        module.extend(b"ab");
+        module.extend(b"first\nsecond\n");
+        module.extend([0, 6, 2]);
+        module.extend(b"cd");
+        module.extend(b"odd");
+        module.extend([0, 8, 2]);
+        module.extend(b"ab");
+        module.extend(b"third");
+
+        assert_eq!(
+            custom_sections(&module, "ab").unwrap(),
+            [b"first\nsecond\n".as_slice(), b"third"]
+        );
+        assert!(custom_sections(&module, "ef").unwrap().is_empty());
+
+        module.truncate(module.len() - 1);
+        assert!(custom_sections(&module, "ab").is_err());
+    }
 }
@@ -1,5 +1,11 @@
 pub mod actor_class_loading;
 pub mod block_class_loading;
+pub mod block_light;
+pub mod block_surface;
+mod light_queues;
 pub mod list_loading;
+pub mod pack_loading;
 pub mod position;
+pub mod projectile;
+pub mod recipe_loading;
 pub mod sky_light;
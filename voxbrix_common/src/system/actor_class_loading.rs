@@ -1,6 +1,10 @@
 use crate::{
+    assets::ACTOR_CLASS_PATH_PREFIX,
     entity::actor_class::ActorClass,
-    read_data_file,
+    system::{
+        list_loading::List,
+        pack_loading::PackSet,
+    },
     LabelMap,
 };
 use anyhow::Error;
@@ -15,18 +19,10 @@ use std::{
 };
 use tokio::task;
 
-const PATH: &str = "assets/common/actor_classes";
-const LIST_PATH: &str = "assets/common/actor_classes.json";
-
 pub trait LoadActorClassComponent<T> {
     fn reload_classes(&mut self, data: Vec<Option<T>>);
 }
 
-#[derive(Deserialize, Debug)]
-struct ActorClassList {
-    list: Vec<String>,
-}
-
 #[derive(Deserialize, Debug)]
 struct ActorClassDescriptior {
     label: String,
@@ -39,9 +35,11 @@ pub struct ActorClassLoadingSystem {
 }
 
 impl ActorClassLoadingSystem {
-    pub async fn load_data() -> Result<Self, Error> {
-        task::spawn_blocking(|| {
-            let actor_class_list = read_data_file::<ActorClassList>(LIST_PATH)?.list;
+    /// Loads the definitions of the classes in the `actor_class_list` order,
+    /// the list defines the ids of the classes.
+    pub async fn load_data(packs: PackSet, actor_class_list: List) -> Result<Self, Error> {
+        task::spawn_blocking(move || {
+            let actor_class_list = actor_class_list.list;
 
             let mut components = BTreeMap::new();
 
@@ -49,7 +47,7 @@ impl ActorClassLoadingSystem {
                 let file_name = format!("{}.json", actor_class_label);
 
                 let descriptor: ActorClassDescriptior =
-                    read_data_file(Path::new(PATH).join(file_name))?;
+                    packs.read_data_file(Path::new(ACTOR_CLASS_PATH_PREFIX).join(file_name))?;
 
                 if descriptor.label != *actor_class_label {
                     return Err(Error::msg(format!(
@@ -1,7 +1,11 @@
 use crate::{
+    assets::BLOCK_CLASS_PATH_PREFIX,
     component::block_class::BlockClassComponent,
     entity::block_class::BlockClass,
-    read_data_file,
+    system::{
+        list_loading::List,
+        pack_loading::PackSet,
+    },
     LabelMap,
 };
 use anyhow::Error;
@@ -16,14 +20,6 @@ use std::{
 };
 use tokio::task;
 
-const PATH: &str = "assets/common/block_classes";
-const LIST_PATH: &str = "assets/common/block_classes.json";
-
-#[derive(Deserialize, Debug)]
-struct BlockClassList {
-    list: Vec<String>,
-}
-
 #[derive(Deserialize, Debug)]
 struct BlockClassDescriptior {
     label: String,
@@ -36,9 +32,11 @@ pub struct BlockClassLoadingSystem {
 }
 
 impl BlockClassLoadingSystem {
-    pub async fn load_data() -> Result<Self, Error> {
-        task::spawn_blocking(|| {
-            let block_class_list = read_data_file::<BlockClassList>(LIST_PATH)?.list;
+    /// Loads the definitions of the classes in the `block_class_list` order,
+    /// the list defines the ids of the classes.
+    pub async fn load_data(packs: PackSet, block_class_list: List) -> Result<Self, Error> {
+        task::spawn_blocking(move || {
+            let block_class_list = block_class_list.list;
 
             let mut components = BTreeMap::new();
 
@@ -46,7 +44,7 @@ impl BlockClassLoadingSystem {
                 let file_name = format!("{}.json", block_class_label);
 
                 let descriptor: BlockClassDescriptior =
-                    read_data_file(Path::new(PATH).join(file_name))?;
+                    packs.read_data_file(Path::new(BLOCK_CLASS_PATH_PREFIX).join(file_name))?;
 
                 if descriptor.label != *block_class_label {
                     return Err(Error::msg(format!(
@@ -0,0 +1,305 @@
+use crate::{
+    component::{
+        block::{
+            block_light::{
+                BlockLight,
+                BlockLightBlockComponent,
+            },
+            BlockComponent,
+            Blocks,
+            BlocksVec,
+        },
+        block_class::{
+            light::LightBlockClassComponent,
+            opacity::{
+                Opacity,
+                OpacityBlockClassComponent,
+            },
+        },
+    },
+    entity::{
+        block::{
+            Block,
+            Neighbor,
+        },
+        block_class::BlockClass,
+        chunk::Chunk,
+    },
+    system::light_queues::{
+        BlockQueue,
+        ChunkQueue,
+    },
+};
+use ahash::{
+    AHashMap,
+    AHashSet,
+};
+use arrayvec::ArrayVec;
+use rayon::prelude::*;
+
+/// Spreads the light emitted by the blocks, fading with each block passed.
+/// Opaque blocks keep their own emission, but do not pass the light through.
+pub struct BlockLightSystem {
+    chunk_queue: ChunkQueue,
+    block_queues: AHashMap<Chunk, BlockQueue>,
+    // TODO: can skip all neighbors need redraw things on server
+    buffer: Vec<(
+        Chunk,
+        Option<BlocksVec<BlockLight>>,
+        Option<BlockQueue>,
+        [bool; 6],
+    )>,
+    chunks_need_redraw: AHashSet<Chunk>,
+}
+
+impl BlockLightSystem {
+    pub fn new() -> Self {
+        Self {
+            chunk_queue: ChunkQueue::new(),
+            block_queues: AHashMap::new(),
+            buffer: Vec::new(),
+            chunks_need_redraw: AHashSet::new(),
+        }
+    }
+
+    pub fn is_queue_empty(&self) -> bool {
+        self.chunk_queue.is_empty()
+    }
+
+    pub fn enqueue_chunk(&mut self, chunk: Chunk) {
+        self.chunk_queue.push(chunk);
+    }
+
+    pub fn block_change(&mut self, chunk: &Chunk, block: Block) {
+        if let Some(queue) = self.block_queues.get_mut(&chunk) {
+            queue.push_this_chunk(block);
+            self.enqueue_chunk(*chunk);
+        }
+    }
+
+    pub fn remove_chunk(&mut self, chunk: &Chunk) {
+        self.chunk_queue.remove(chunk);
+        self.block_queues.remove(chunk);
+    }
+
+    pub fn process<'a>(
+        &'a mut self,
+        number_of_blocks: usize,
+        class_bc: &(impl BlockComponent<BlockClass> + Send + Sync),
+        opacity_bcc: &OpacityBlockClassComponent,
+        light_bcc: &LightBlockClassComponent,
+        block_light_bc: &mut BlockLightBlockComponent,
+    ) -> impl ExactSizeIterator<Item = Chunk> + 'a {
+        self.buffer.extend(
+            self.chunk_queue
+                .get_queue()
+                .map(|chunk| {
+                    let block_light = block_light_bc.remove_chunk(&chunk);
+                    let block_queue = self.block_queues.remove(&chunk);
+
+                    (chunk, block_light, block_queue, [false; 6])
+                })
+                .take(rayon::current_num_threads()),
+        );
+
+        self.buffer.par_iter_mut().for_each(
+            |(chunk, block_light, block_queue, neighbors_need_redraw)| {
+                let is_new_chunk = block_light.is_none();
+
+                if block_light.is_none() {
+                    *block_light = Some(BlocksVec::new_cloned(BlockLight::MIN));
+                }
+
+                if block_queue.is_none() {
+                    *block_queue = Some(BlockQueue::new_full(is_new_chunk));
+                }
+
+                let block_light = block_light.as_mut().unwrap();
+                let block_queue = block_queue.as_mut().unwrap();
+
+                let neighbor_chunk_ids = [
+                    [-1, 0, 0],
+                    [1, 0, 0],
+                    [0, -1, 0],
+                    [0, 1, 0],
+                    [0, 0, -1],
+                    [0, 0, 1],
+                ]
+                .map(|offset| chunk.checked_add(offset));
+
+                let neighbor_chunks = neighbor_chunk_ids
+                    .into_iter()
+                    .map(|chunk| {
+                        let chunk = chunk?;
+                        let neighbor_light = block_light_bc.get_chunk(&chunk)?;
+
+                        Some((chunk, neighbor_light))
+                    })
+                    .collect::<ArrayVec<_, 6>>()
+                    .into_inner()
+                    .unwrap_or_else(|_| unreachable!());
+
+                let classes = class_bc
+                    .get_chunk(&chunk)
+                    .expect("undefined block classes for chunk");
+
+                let mut block_counter = 0;
+
+                loop {
+                    if block_counter >= number_of_blocks {
+                        break;
+                    }
+
+                    block_counter += 1;
+
+                    let Some(block) = block_queue.pop() else {
+                        break;
+                    };
+
+                    let prev_light = *block_light.get(block);
+
+                    let class = classes.get(block);
+
+                    let emission = light_bcc
+                        .get(class)
+                        .map(|light| light.emission())
+                        .unwrap_or(BlockLight::MIN);
+
+                    let light = match opacity_bcc.get(class) {
+                        Some(Opacity::Full) => emission,
+                        None => {
+                            let mut light = emission;
+
+                            for (side, neighbor) in block.neighbors().into_iter().enumerate() {
+                                let neighbor_light = match neighbor {
+                                    Neighbor::ThisChunk(block) => *block_light.get(block),
+                                    Neighbor::OtherChunk(block) => {
+                                        match neighbor_chunks[side] {
+                                            Some((_, neighbor_light)) => *neighbor_light.get(block),
+                                            None => BlockLight::MIN,
+                                        }
+                                    },
+                                };
+
+                                light = light.max(neighbor_light.fade());
+                            }
+
+                            light
+                        },
+                    };
+
+                    *block_light.get_mut(block) = light;
+
+                    if light == prev_light {
+                        continue;
+                    }
+
+                    for (side, neighbor) in block.neighbors().into_iter().enumerate() {
+                        let neighbor_light = match neighbor {
+                            Neighbor::ThisChunk(block) => *block_light.get(block),
+                            Neighbor::OtherChunk(block) => {
+                                neighbors_need_redraw[side] = true;
+
+                                match neighbor_chunks[side] {
+                                    Some((_, neighbor_light)) => *neighbor_light.get(block),
+                                    None => BlockLight::MIN,
+                                }
+                            },
+                        };
+
+                        // For light increase:
+                        // Must be added to the block_queue, as current block maybe providing
+                        // light to them now.
+                        // For light decrease:
+                        // Previously we might have provided light to the neighbor
+                        // Add it to the queue to recalculate.
+
+                        if light > prev_light && light > neighbor_light
+                            || light < prev_light
+                                && light <= neighbor_light
+                                && prev_light > neighbor_light
+                        {
+                            match neighbor {
+                                Neighbor::ThisChunk(block) => block_queue.push_this_chunk(block),
+                                Neighbor::OtherChunk(block) => {
+                                    block_queue.push_other_chunk(side, block);
+                                },
+                            }
+                        }
+                    }
+                }
+            },
+        );
+
+        for (chunk, block_light, block_queue, neighbors_need_redraw) in self.buffer.drain(..) {
+            let block_light = block_light.unwrap();
+            let mut block_queue = block_queue.unwrap();
+
+            // Fine to do it before inserting everything from the batch into the components and
+            // queues, all neighbors are not in this batch
+            let neighbor_chunks = [
+                [-1, 0, 0],
+                [1, 0, 0],
+                [0, -1, 0],
+                [0, 1, 0],
+                [0, 0, -1],
+                [0, 0, 1],
+            ]
+            .into_iter()
+            .enumerate()
+            .filter_map(|(side, offset)| {
+                let chunk = chunk.checked_add(offset)?;
+
+                Some((side, chunk))
+            });
+
+            for (side, chunk) in neighbor_chunks {
+                let Some(queue) = self.block_queues.get_mut(&chunk) else {
+                    continue;
+                };
+
+                let mut has_new = false;
+
+                for block in block_queue.drain_other_chunk_on_side(side) {
+                    queue.push_this_chunk(block);
+                    has_new = true;
+                }
+
+                if has_new {
+                    self.chunk_queue.push(chunk);
+                }
+            }
+
+            if !block_queue.is_empty() {
+                self.chunk_queue.push(chunk);
+            }
+
+            block_light_bc.insert_chunk(chunk, block_light);
+            self.block_queues.insert(chunk, block_queue);
+
+            self.chunks_need_redraw.insert(chunk);
+
+            let need_redraw_iter = [
+                [-1, 0, 0],
+                [1, 0, 0],
+                [0, -1, 0],
+                [0, 1, 0],
+                [0, 0, -1],
+                [0, 0, 1],
+            ]
+            .map(|offset| chunk.checked_add(offset))
+            .into_iter()
+            .zip(neighbors_need_redraw)
+            .filter_map(|(chunk, needs_redraw)| {
+                if !needs_redraw {
+                    return None;
+                }
+                chunk
+            });
+
+            self.chunks_need_redraw.extend(need_redraw_iter);
+        }
+
+        self.chunks_need_redraw.drain()
+    }
+}
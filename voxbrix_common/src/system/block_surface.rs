@@ -0,0 +1,236 @@
+use crate::{
+    component::block::{
+        BlockComponent,
+        Blocks,
+    },
+    entity::{
+        block::{
+            Block,
+            Neighbor,
+            BLOCKS_IN_CHUNK_EDGE,
+        },
+        block_class::BlockClass,
+        chunk::Chunk,
+    },
+};
+use arrayvec::ArrayVec;
+use bitflags::bitflags;
+
+bitflags! {
+    /// Sides of a block that are not covered by the neighbors,
+    /// in the order of [`Block::neighbors`].
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub struct CullFlags: u8 {
+        const X0 = 0b00000001;
+        const X1 = 0b00000010;
+        const Y0 = 0b00000100;
+        const Y1 = 0b00001000;
+        const Z0 = 0b00010000;
+        const Z1 = 0b00100000;
+    }
+}
+
+impl CullFlags {
+    pub fn from_index(i: usize) -> Self {
+        match i {
+            0 => Self::X0,
+            1 => Self::X1,
+            2 => Self::Y0,
+            3 => Self::Y1,
+            4 => Self::Z0,
+            5 => Self::Z1,
+            _ => panic!("incorrect side index"),
+        }
+    }
+}
+
+/// Blocks around a block that occlude the ambient light, by the offsets in `-1 ..= 1`.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Occluders(u32);
+
+impl Occluders {
+    fn index(offset: [i32; 3]) -> usize {
+        let [x, y, z] = offset.map(|i| (i + 1) as usize);
+
+        x + y * 3 + z * 9
+    }
+
+    pub fn insert(&mut self, offset: [i32; 3]) {
+        self.0 |= 1 << Self::index(offset);
+    }
+
+    pub fn contains(&self, offset: [i32; 3]) -> bool {
+        self.0 & (1 << Self::index(offset)) != 0
+    }
+
+    /// Classic corner ambient occlusion of a vertex on the block side, `0` to `3`.
+    /// `position` is the vertex position within the block.
+    pub fn vertex_occlusion(&self, side: usize, position: [f32; 3]) -> u8 {
+        let normal_axis = side / 2;
+        let mut normal = [0; 3];
+        normal[normal_axis] = if side % 2 == 0 { -1 } else { 1 };
+
+        let [first, second] = [0, 1, 2]
+            .into_iter()
+            .filter(|axis| *axis != normal_axis)
+            .map(|axis| {
+                let mut direction = [0; 3];
+                direction[axis] = if position[axis] < 0.5 { -1 } else { 1 };
+                direction
+            })
+            .collect::<ArrayVec<_, 2>>()
+            .into_inner()
+            .unwrap();
+
+        let add = |a: [i32; 3], b: [i32; 3]| [a[0] + b[0], a[1] + b[1], a[2] + b[2]];
+
+        let first_side = self.contains(add(normal, first));
+        let second_side = self.contains(add(normal, second));
+        let corner = self.contains(add(add(normal, first), second));
+
+        if first_side && second_side {
+            3
+        } else {
+            first_side as u8 + second_side as u8 + corner as u8
+        }
+    }
+}
+
+/// Finds the visible sides of the blocks of a chunk.
+///
+/// `is_full` tells whether the block class completely covers the sides of the neighbors
+/// touching it, e.g. by the block model on the client or by the opacity on the server.
+/// The sides facing the chunks that are not loaded are considered covered.
+pub struct ChunkSurface<'a, C, F>
+where
+    C: BlockComponent<BlockClass>,
+{
+    chunk: Chunk,
+    class_bc: &'a C,
+    this_chunk: &'a C::Blocks,
+    neighbor_chunks: [Option<&'a C::Blocks>; 6],
+    is_full: F,
+}
+
+impl<'a, C, F> ChunkSurface<'a, C, F>
+where
+    C: BlockComponent<BlockClass>,
+    F: Fn(&BlockClass) -> bool,
+{
+    /// `None` if the chunk itself is not loaded.
+    pub fn new(chunk: &Chunk, class_bc: &'a C, is_full: F) -> Option<Self> {
+        let this_chunk = class_bc.get_chunk(chunk)?;
+
+        let neighbor_chunks = [
+            [-1, 0, 0],
+            [1, 0, 0],
+            [0, -1, 0],
+            [0, 1, 0],
+            [0, 0, -1],
+            [0, 0, 1],
+        ]
+        .map(|offset| class_bc.get_chunk(&chunk.checked_add(offset)?));
+
+        Some(Self {
+            chunk: *chunk,
+            class_bc,
+            this_chunk,
+            neighbor_chunks,
+            is_full,
+        })
+    }
+
+    pub fn this_chunk(&self) -> &'a C::Blocks {
+        self.this_chunk
+    }
+
+    /// Block class of the neighbor, `None` if it is in the chunk that is not loaded.
+    pub fn neighbor_class(&self, side: usize, neighbor: &Neighbor) -> Option<&'a BlockClass> {
+        match neighbor {
+            Neighbor::ThisChunk(block) => Some(self.this_chunk.get(*block)),
+            Neighbor::OtherChunk(block) => Some(self.neighbor_chunks[side]?.get(*block)),
+        }
+    }
+
+    /// `neighbors` must be the result of [`Block::neighbors`].
+    pub fn visible_sides(&self, neighbors: &[Neighbor; 6]) -> CullFlags {
+        let mut cull_flags = CullFlags::all();
+
+        for (side, neighbor) in neighbors.iter().enumerate() {
+            let covered = self
+                .neighbor_class(side, neighbor)
+                .map(|class| (self.is_full)(class))
+                .unwrap_or(true);
+
+            if covered {
+                cull_flags.remove(CullFlags::from_index(side));
+            }
+        }
+
+        cull_flags
+    }
+
+    /// Surrounding blocks, including the diagonal ones, that completely cover their sides.
+    pub fn occluders(&self, block: Block) -> Occluders {
+        let coords = block.into_coords().map(|i| i as i32);
+        let mut occluders = Occluders::default();
+
+        for z in -1 ..= 1 {
+            for y in -1 ..= 1 {
+                for x in -1 ..= 1 {
+                    let offset = [x, y, z];
+
+                    if offset == [0, 0, 0] {
+                        continue;
+                    }
+
+                    let Some((other_chunk, other_block)) = Block::from_chunk_offset(
+                        self.chunk,
+                        [coords[0] + x, coords[1] + y, coords[2] + z],
+                    ) else {
+                        continue;
+                    };
+
+                    let classes = if other_chunk == self.chunk {
+                        Some(self.this_chunk)
+                    } else {
+                        self.class_bc.get_chunk(&other_chunk)
+                    };
+
+                    let Some(classes) = classes else {
+                        continue;
+                    };
+
+                    if (self.is_full)(classes.get(other_block)) {
+                        occluders.insert(offset);
+                    }
+                }
+            }
+        }
+
+        occluders
+    }
+
+    /// Blocks of the layer `slab` along the z axis that have any side visible.
+    /// `output` turns a block with its class and the visible sides into the resulting items,
+    /// like the quads of the block model or the walkable surfaces.
+    pub fn extract_slab<'b, T, I, O>(
+        &'b self,
+        slab: usize,
+        mut output: O,
+    ) -> impl Iterator<Item = T> + use<'a, 'b, C, F, T, I, O>
+    where
+        O: FnMut(Block, &'a BlockClass, CullFlags) -> I + 'b,
+        I: IntoIterator<Item = T> + 'b,
+    {
+        (0 .. BLOCKS_IN_CHUNK_EDGE)
+            .flat_map(move |y| (0 .. BLOCKS_IN_CHUNK_EDGE).map(move |x| [x, y, slab]))
+            .map(Block::from_coords)
+            .filter_map(move |block| {
+                let visible = self.visible_sides(&block.neighbors());
+
+                (!visible.is_empty()).then_some((block, visible))
+            })
+            .flat_map(move |(block, visible)| output(block, self.this_chunk.get(block), visible))
+    }
+}
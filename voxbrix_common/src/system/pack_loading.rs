@@ -0,0 +1,304 @@
+use crate::{
+    read_data_file,
+    system::list_loading::List,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+use serde::{
+    de::DeserializeOwned,
+    Deserialize,
+};
+use std::{
+    fmt::Debug,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::Arc,
+};
+use tokio::task;
+
+/// File in the root of every mod pack directory.
+pub const PACK_DESCRIPTOR_FILE: &str = "pack.json";
+/// Label of the pack with the base assets, every other pack depends on it implicitly.
+pub const BASE_PACK_LABEL: &str = "base";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct PackDescriptor {
+    label: String,
+    /// Labels of the packs that must be loaded before this one.
+    #[serde(default)]
+    dependencies: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Pack {
+    pub label: String,
+    pub root: PathBuf,
+}
+
+/// Asset packs in the load order. Every pack mirrors the layout of the base assets directory,
+/// so the asset paths are relative to the pack roots. The files of the later packs override
+/// the ones of the earlier packs, while the lists are merged.
+#[derive(Clone, Debug)]
+pub struct PackSet {
+    packs: Arc<[Pack]>,
+}
+
+impl PackSet {
+    /// Only the base assets.
+    pub fn base(root: impl Into<PathBuf>) -> Self {
+        Self {
+            packs: Arc::new([Pack {
+                label: BASE_PACK_LABEL.to_owned(),
+                root: root.into(),
+            }]),
+        }
+    }
+
+    /// The base assets followed by the mod packs.
+    /// Each pack is placed after its dependencies, otherwise the given order is kept.
+    pub async fn load(base_root: PathBuf, pack_roots: Vec<PathBuf>) -> Result<Self, Error> {
+        task::spawn_blocking(move || {
+            let descriptors = pack_roots
+                .into_iter()
+                .map(|root| {
+                    let descriptor =
+                        read_data_file::<PackDescriptor>(root.join(PACK_DESCRIPTOR_FILE))
+                            .with_context(|| format!("unable to load pack {:?}", root))?;
+
+                    Ok((descriptor, root))
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+
+            let packs = order_packs(base_root, descriptors)?;
+
+            Ok(Self {
+                packs: packs.into(),
+            })
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Packs that are already in the load order.
+    pub fn from_packs(packs: Vec<Pack>) -> Self {
+        Self {
+            packs: packs.into(),
+        }
+    }
+
+    pub fn packs(&self) -> &[Pack] {
+        &self.packs
+    }
+
+    /// Paths of the file in all the packs that have it, in the load order.
+    /// Blocking IO, must not be used directly in async
+    pub fn resolve_all(&self, path: impl AsRef<Path>) -> Vec<PathBuf> {
+        self.packs
+            .iter()
+            .map(|pack| pack.root.join(path.as_ref()))
+            .filter(|path| path.is_file())
+            .collect()
+    }
+
+    /// Path of the file in the last pack that has it.
+    /// Blocking IO, must not be used directly in async
+    pub fn resolve(&self, path: impl AsRef<Path> + Debug) -> Result<PathBuf, Error> {
+        self.resolve_all(path.as_ref())
+            .pop()
+            .ok_or_else(|| Error::msg(format!("{:?} not found in any pack", path)))
+    }
+
+    /// Reads the file of the last pack that has it.
+    /// Blocking IO, must not be used directly in async
+    pub fn read_data_file<T>(&self, path: impl AsRef<Path> + Debug) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        read_data_file(self.resolve(path)?)
+    }
+
+    /// Reads the file of every pack that has it, in the load order.
+    /// Blocking IO, must not be used directly in async
+    pub fn read_data_files<T>(&self, path: impl AsRef<Path> + Debug) -> Result<Vec<T>, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let paths = self.resolve_all(path.as_ref());
+
+        if paths.is_empty() {
+            return Err(Error::msg(format!("{:?} not found in any pack", path)));
+        }
+
+        paths.into_iter().map(read_data_file).collect()
+    }
+
+    /// Lists of all the packs concatenated in the load order.
+    /// The labels repeated by the later packs keep their first position.
+    /// Blocking IO, must not be used directly in async
+    pub fn read_list(&self, path: impl AsRef<Path> + Debug) -> Result<List, Error> {
+        let mut list = Vec::new();
+
+        for pack_list in self.read_data_files::<List>(path)? {
+            for label in pack_list.list {
+                if !list.contains(&label) {
+                    list.push(label);
+                }
+            }
+        }
+
+        Ok(List { list })
+    }
+
+    pub async fn load_list(
+        &self,
+        path: impl 'static + AsRef<Path> + Debug + Send + Clone,
+    ) -> Result<List, Error> {
+        let packs = self.clone();
+        let read_path = path.clone();
+
+        task::spawn_blocking(move || packs.read_list(read_path))
+            .await
+            .unwrap()
+            .with_context(|| format!("unable to load list \"{:?}\"", path))
+    }
+}
+
+/// Hash of the asset file contents, used to check if the cached copy is up to date.
+/// FNV-1a, stable between the builds and the platforms.
+pub fn asset_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn order_packs(
+    base_root: PathBuf,
+    mut descriptors: Vec<(PackDescriptor, PathBuf)>,
+) -> Result<Vec<Pack>, Error> {
+    let mut packs = vec![Pack {
+        label: BASE_PACK_LABEL.to_owned(),
+        root: base_root,
+    }];
+
+    for (index, (descriptor, _)) in descriptors.iter().enumerate() {
+        if descriptor.label == BASE_PACK_LABEL
+            || descriptors[.. index]
+                .iter()
+                .any(|(other, _)| other.label == descriptor.label)
+        {
+            return Err(Error::msg(format!(
+                "pack \"{}\" is defined more than once",
+                descriptor.label
+            )));
+        }
+    }
+
+    for (descriptor, _) in descriptors.iter() {
+        for dependency in descriptor.dependencies.iter() {
+            if dependency != BASE_PACK_LABEL
+                && !descriptors
+                    .iter()
+                    .any(|(other, _)| other.label == *dependency)
+            {
+                return Err(Error::msg(format!(
+                    "pack \"{}\" depends on missing pack \"{}\"",
+                    descriptor.label, dependency
+                )));
+            }
+        }
+    }
+
+    while !descriptors.is_empty() {
+        let next = descriptors
+            .iter()
+            .position(|(descriptor, _)| {
+                descriptor
+                    .dependencies
+                    .iter()
+                    .all(|dependency| packs.iter().any(|pack| pack.label == *dependency))
+            })
+            .ok_or_else(|| {
+                let labels = descriptors
+                    .iter()
+                    .map(|(descriptor, _)| descriptor.label.as_str())
+                    .collect::<Vec<_>>();
+
+                Error::msg(format!("packs {:?} have circular dependencies", labels))
+            })?;
+
+        let (descriptor, root) = descriptors.remove(next);
+
+        packs.push(Pack {
+            label: descriptor.label,
+            root,
+        });
+    }
+
+    Ok(packs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn descriptor(label: &str, dependencies: &[&str]) -> (PackDescriptor, PathBuf) {
+        (
+            PackDescriptor {
+                label: label.to_owned(),
+                dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            },
+            PathBuf::from(label),
+        )
+    }
+
+    fn labels(packs: &[Pack]) -> Vec<&str> {
+        packs.iter().map(|pack| pack.label.as_str()).collect()
+    }
+
+    #[test]
+    fn check_pack_order() {
+        let packs = order_packs(
+            "assets".into(),
+            vec![
+                descriptor("ores", &["caves"]),
+                descriptor("trees", &[]),
+                descriptor("caves", &["base"]),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(labels(&packs), ["base", "trees", "caves", "ores"]);
+    }
+
+    #[test]
+    fn check_asset_hash() {
+        assert_eq!(asset_hash(b""), 0xcbf29ce484222325);
+        assert_eq!(asset_hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_ne!(asset_hash(b"ab"), asset_hash(b"ba"));
+    }
+
+    #[test]
+    fn check_pack_order_errors() {
+        assert!(order_packs("assets".into(), vec![descriptor("ores", &["caves"])]).is_err());
+
+        assert!(order_packs(
+            "assets".into(),
+            vec![
+                descriptor("ores", &["caves"]),
+                descriptor("caves", &["ores"])
+            ],
+        )
+        .is_err());
+
+        assert!(order_packs(
+            "assets".into(),
+            vec![descriptor("ores", &[]), descriptor("ores", &[])],
+        )
+        .is_err());
+    }
+}
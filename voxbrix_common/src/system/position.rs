@@ -4,13 +4,20 @@ use crate::{
             position::Position,
             velocity::Velocity,
         },
+        actor_class::collider::Collider,
         block::{
             BlockComponent,
             Blocks,
         },
-        block_class::collision::{
-            Collision,
-            CollisionBlockClassComponent,
+        block_class::{
+            collision::{
+                Collision,
+                CollisionBlockClassComponent,
+            },
+            fluid::{
+                Fluid,
+                FluidBlockClassComponent,
+            },
         },
     },
     entity::{
@@ -26,6 +33,8 @@ use crate::{
         },
     },
     math::{
+        query,
+        Directions,
         Round,
         Vec3F32,
     },
@@ -36,7 +45,9 @@ use std::{
     time::Duration,
 };
 
-const COLLISION_PUSHBACK: f32 = 1.0e-3;
+pub(crate) const COLLISION_PUSHBACK: f32 = 1.0e-3;
+/// How far below the actor the ground is looked for before stepping up.
+const STEP_GROUND_PROBE: f32 = 2.0 * COLLISION_PUSHBACK;
 const MAX_BLOCK_TARGET_DISTANCE: i32 = 8;
 
 enum MoveDirection {
@@ -77,18 +88,149 @@ pub fn process_actor<C>(
     dt: Duration,
     class_bc: &C,
     collision_bcc: &CollisionBlockClassComponent,
+    fluid_bcc: &FluidBlockClassComponent,
     position: &Position,
     velocity: &Velocity,
-    radius: &[f32; 3],
+    collider: &Collider,
 ) -> Position
 where
     C: BlockComponent<BlockClass>,
 {
     let Position {
-        chunk: mut center_chunk,
-        offset: mut start_position,
-    } = position;
+        chunk,
+        offset: start_position,
+    } = *position;
+
+    let mut movement = (*velocity * dt).vector;
+
+    // Fluids slow the actors down and push them up
+    if let Some(fluid) = fluid_at(class_bc, fluid_bcc, chunk, start_position) {
+        movement = movement * (1.0 - fluid.drag.clamp(0.0, 1.0))
+            + Vec3F32::UP * fluid.buoyancy * dt.as_secs_f32();
+    }
+
+    let sweep = |start_position, movement| {
+        sweep_box(
+            class_bc,
+            collision_bcc,
+            chunk,
+            start_position,
+            movement,
+            &collider.radius,
+        )
+    };
+
+    let mut finish_position = sweep(start_position, movement);
+
+    if collider.step_height > 0.0 && movement[2] <= 0.0 {
+        let horizontal_travel =
+            |finish_position: Vec3F32| (finish_position - start_position).truncate().length();
+
+        let is_blocked =
+            horizontal_travel(finish_position) < movement.truncate().length() - COLLISION_PUSHBACK;
+
+        // Stepping up only from the ground, not while falling along a wall
+        let is_grounded = || {
+            let probe = Vec3F32::DOWN * STEP_GROUND_PROBE;
+            sweep(start_position, probe)[2] > start_position[2] + probe[2]
+        };
+
+        if is_blocked && is_grounded() {
+            let raised = sweep(start_position, Vec3F32::UP * collider.step_height);
+            let moved = sweep(raised, Vec3F32::new(movement[0], movement[1], 0.0));
+            let lowered = sweep(
+                moved,
+                Vec3F32::new(0.0, 0.0, start_position[2] - raised[2] + movement[2]),
+            );
+
+            if horizontal_travel(lowered) > horizontal_travel(finish_position) + COLLISION_PUSHBACK
+            {
+                finish_position = lowered;
+            }
+        }
+    }
+
+    to_chunk_position(chunk, finish_position)
+}
+
+/// Moves the collider from the `position` towards the `target` the way the actor could get
+/// there, straight or, like stepping up, vertically first, and returns the closest position
+/// reached before the solid blocks.
+/// Used to check the movement made elsewhere, e.g. by the clients.
+/// `None` if the target is in the other dimension.
+pub fn sweep_actor<C>(
+    class_bc: &C,
+    collision_bcc: &CollisionBlockClassComponent,
+    position: &Position,
+    target: &Position,
+    collider: &Collider,
+) -> Option<Position>
+where
+    C: BlockComponent<BlockClass>,
+{
+    let movement = position.vector_to(target)?;
+    let finish_position = position.offset + movement;
+
+    let sweep = |start_position, movement| {
+        sweep_box(
+            class_bc,
+            collision_bcc,
+            position.chunk,
+            start_position,
+            movement,
+            &collider.radius,
+        )
+    };
+
+    let straight = sweep(position.offset, movement);
+
+    let stepped = sweep(
+        sweep(position.offset, Vec3F32::new(0.0, 0.0, movement[2])),
+        Vec3F32::new(movement[0], movement[1], 0.0),
+    );
+
+    let reached =
+        if stepped.distance_squared(finish_position) < straight.distance_squared(finish_position) {
+            stepped
+        } else {
+            straight
+        };
+
+    Some(to_chunk_position(position.chunk, reached))
+}
+
+/// Fluid the point is submerged in, the point above the fluid surface in the block is not.
+/// Coordinates are relative to the `chunk`.
+pub fn fluid_at<'a, C>(
+    class_bc: &C,
+    fluid_bcc: &'a FluidBlockClassComponent,
+    chunk: Chunk,
+    offset: Vec3F32,
+) -> Option<&'a Fluid>
+where
+    C: BlockComponent<BlockClass>,
+{
+    let coords = offset.to_array().map(|f| f.round_down());
+    let (chunk, block) = Block::from_chunk_offset(chunk, coords)?;
+    let block_class = class_bc.get_chunk(&chunk)?.get(block);
+    let fluid = fluid_bcc.get(block_class)?;
+
+    (offset[2] - (coords[2] as f32) < fluid.height()).then_some(fluid)
+}
 
+/// Moves the box with the `radius` half-size by `movement`, stopping it before solid blocks.
+/// Coordinates are relative to the `chunk`.
+fn sweep_box<C>(
+    class_bc: &C,
+    collision_bcc: &CollisionBlockClassComponent,
+    chunk: Chunk,
+    start_position: Vec3F32,
+    movement: Vec3F32,
+    radius: &[f32; 3],
+) -> Vec3F32
+where
+    C: BlockComponent<BlockClass>,
+{
     let calc_pass = |finish_position: Vec3F32, axis_set: [usize; 3]| {
         let [a0, a1, a2] = axis_set;
 
@@ -128,14 +270,12 @@ where
         };
 
         for block_a0 in block_range {
-            let t = ((block_a0 + block_offset) as f32 - actor_start) / velocity.vector[a0];
+            let t = ((block_a0 + block_offset) as f32 - actor_start) / movement[a0];
 
-            let actor_a1 = match velocity.vector[a1].total_cmp(&0.0) {
-                Ordering::Less => {
-                    (start_position[a1] + velocity.vector[a1] * t).max(finish_position[a1])
-                },
+            let actor_a1 = match movement[a1].total_cmp(&0.0) {
+                Ordering::Less => (start_position[a1] + movement[a1] * t).max(finish_position[a1]),
                 Ordering::Greater => {
-                    (start_position[a1] + velocity.vector[a1] * t).min(finish_position[a1])
+                    (start_position[a1] + movement[a1] * t).min(finish_position[a1])
                 },
                 Ordering::Equal => finish_position[a1],
             };
@@ -144,12 +284,12 @@ where
             let block_a1p = (actor_a1 + radius[a1]).round_down();
 
             for block_a1 in block_a1m ..= block_a1p {
-                let actor_a2 = match velocity.vector[a2].total_cmp(&0.0) {
+                let actor_a2 = match movement[a2].total_cmp(&0.0) {
                     Ordering::Less => {
-                        (start_position[a2] + velocity.vector[a2] * t).max(finish_position[a2])
+                        (start_position[a2] + movement[a2] * t).max(finish_position[a2])
                     },
                     Ordering::Greater => {
-                        (start_position[a2] + velocity.vector[a2] * t).min(finish_position[a2])
+                        (start_position[a2] + movement[a2] * t).min(finish_position[a2])
                     },
                     Ordering::Equal => finish_position[a2],
                 };
@@ -162,9 +302,7 @@ where
                     chunk_offset[a0] = block_a0;
                     chunk_offset[a1] = block_a1;
                     chunk_offset[a2] = block_a2;
-                    if let Some((chunk, block)) =
-                        Block::from_chunk_offset(center_chunk, chunk_offset)
-                    {
+                    if let Some((chunk, block)) = Block::from_chunk_offset(chunk, chunk_offset) {
                         if let Some(block_class) = class_bc.get_chunk(&chunk).map(|b| b.get(block))
                         {
                             if let Some(collision) = collision_bcc.get(block_class) {
@@ -205,7 +343,7 @@ where
         None
     };
 
-    let mut finish_position = start_position + (velocity.clone() * dt).vector;
+    let mut finish_position = start_position + movement;
 
     let axis_sets = [[0, 1, 2], [1, 0, 2], [2, 0, 1]];
 
@@ -246,104 +384,46 @@ where
         finish_position[move_limit.axis_set[0]] = move_limit.max_movement;
     }
 
-    // If we need to "move" actor to other chunk
-    if finish_position
+    finish_position
+}
+
+/// Moves the offset that went out of the chunk into the corresponding chunk.
+pub fn to_chunk_position(mut chunk: Chunk, mut offset: Vec3F32) -> Position {
+    if offset
         .as_ref()
         .iter()
         .any(|dist| dist.abs() > BLOCKS_IN_CHUNK_EDGE_F32)
     {
-        let chunk_diff = finish_position
+        let chunk_diff = offset
             .to_array()
             .map(|f| f as i32 / BLOCKS_IN_CHUNK_EDGE_I32);
 
-        let final_chunk = chunk_diff.saturating_add(center_chunk.position);
+        let final_chunk = chunk_diff.saturating_add(chunk.position);
 
         let actor_diff_vec: Vec3F32 = final_chunk
-            .checked_sub(center_chunk.position)
+            .checked_sub(chunk.position)
             .expect("cannot fail")
             .map(|i| i as f32 * BLOCKS_IN_CHUNK_EDGE_F32)
             .into();
 
-        center_chunk.position = final_chunk;
+        chunk.position = final_chunk;
 
-        finish_position = finish_position - actor_diff_vec;
+        offset = offset - actor_diff_vec;
     }
 
-    start_position = finish_position;
-
-    Position {
-        chunk: center_chunk,
-        offset: start_position,
-    }
+    Position { chunk, offset }
 }
 
 pub fn get_target_block(
     position: &Position,
     direction: Vec3F32,
-    mut targeting: impl FnMut(Chunk, Block) -> bool,
+    targeting: impl FnMut(Chunk, Block) -> bool,
 ) -> Option<(Chunk, Block, usize)> {
-    let mut time_block = None;
-
-    for (axis_0, axis_1, axis_2) in [(0, 1, 2), (1, 2, 0), (2, 0, 1)] {
-        for axis_offset in 0 .. MAX_BLOCK_TARGET_DISTANCE {
-            // wall_offset helps to calculate the distance to the layer ("wall") of blocks
-            //     if we move to positive direction we need to add 1 after round_down()
-            //     while moving in the negative direction, the value is 0
-            // block_coord_offset helps to get the coordinate of the "wall" block layer
-            //     if we move to the negative direction the actual coordinate would be 1 block
-            //     behind the "wall" coordinate, because we "collide" with the front side of
-            //     the block in this case
-            //     while moving in the positive direction, the value is 0 as we collide with
-            //     the back side of the block, which is the same as it's coordinate
-            // side_index is a index of side/neighbor in [x_m, x_p, y_m, y_p, z_m, z_p]
-            let (axis_offset, wall_offset, block_coord_offset, side_index) =
-                match direction[axis_0].total_cmp(&0.0) {
-                    Ordering::Less => (-axis_offset, 0, -1, axis_0 * 2 + 1),
-                    Ordering::Greater => (axis_offset, 1, 0, axis_0 * 2),
-                    _ => continue,
-                };
-
-            // Distance to the colliding side
-            let block_side_axis_0 =
-                position.offset[axis_0].round_down() + axis_offset + wall_offset;
-
-            let time = (block_side_axis_0 as f32 - position.offset[axis_0]) / direction[axis_0];
-
-            if time * direction.length() > MAX_BLOCK_TARGET_DISTANCE as f32 {
-                break;
-            }
-
-            // Distance to the colliding block
-            let block_axis_0 = block_side_axis_0 + block_coord_offset;
-
-            let is_record = if let Some((old_time, _)) = time_block {
-                time < old_time
-            } else {
-                true
-            };
-
-            if is_record {
-                let block_axis_1 =
-                    (position.offset[axis_1] + time * direction[axis_1]).round_down();
-
-                let block_axis_2 =
-                    (position.offset[axis_2] + time * direction[axis_2]).round_down();
-
-                let mut block_offset = [0; 3];
-
-                block_offset[axis_0] = block_axis_0;
-                block_offset[axis_1] = block_axis_1;
-                block_offset[axis_2] = block_axis_2;
-
-                if let Some((chunk, block)) = Block::from_chunk_offset(position.chunk, block_offset)
-                {
-                    if targeting(chunk, block) {
-                        time_block = Some((time, (chunk, block, side_index)));
-                    }
-                }
-            }
-        }
-    }
-
-    Some(time_block?.1)
+    query::raycast(
+        position,
+        direction,
+        MAX_BLOCK_TARGET_DISTANCE as f32,
+        targeting,
+    )
+    .map(|hit| (hit.chunk, hit.block, hit.side))
 }
@@ -0,0 +1,116 @@
+use crate::{
+    component::{
+        actor::{
+            position::Position,
+            velocity::Velocity,
+        },
+        block::{
+            BlockComponent,
+            Blocks,
+        },
+        block_class::collision::CollisionBlockClassComponent,
+    },
+    entity::{
+        block::Block,
+        block_class::BlockClass,
+        chunk::Chunk,
+    },
+    math::{
+        query::{
+            self,
+            RayHit,
+        },
+        Directions,
+        Vec3F32,
+    },
+    system::position::{
+        self,
+        COLLISION_PUSHBACK,
+    },
+};
+use std::time::Duration;
+
+/// Block the projectile has collided with.
+#[derive(Clone, Copy, Debug)]
+pub struct ProjectileHit {
+    pub chunk: Chunk,
+    pub block: Block,
+    /// Index of the side in [x_m, x_p, y_m, y_p, z_m, z_p].
+    pub side: usize,
+}
+
+pub struct ProjectileStep {
+    pub position: Position,
+    pub velocity: Velocity,
+    pub hit: Option<ProjectileHit>,
+}
+
+/// Moves the point-sized projectile for `dt`, applying `gravity` to its velocity.
+/// The projectile stops at the first solid block in its way, with the velocity dropped.
+pub fn process_projectile<C>(
+    dt: Duration,
+    class_bc: &C,
+    collision_bcc: &CollisionBlockClassComponent,
+    position: &Position,
+    velocity: &Velocity,
+    gravity: f32,
+) -> ProjectileStep
+where
+    C: BlockComponent<BlockClass>,
+{
+    let dt_secs = dt.as_secs_f32();
+
+    let next_velocity = Velocity {
+        vector: velocity.vector + Vec3F32::DOWN * gravity * dt_secs,
+    };
+
+    // Average velocity over the step
+    let displacement = (velocity.vector + next_velocity.vector) * 0.5 * dt_secs;
+
+    let distance = displacement.length();
+
+    if distance == 0.0 {
+        return ProjectileStep {
+            position: *position,
+            velocity: next_velocity,
+            hit: None,
+        };
+    }
+
+    let ray_hit = query::raycast(position, displacement, distance, |chunk, block| {
+        class_bc
+            .get_chunk(&chunk)
+            .map(|blocks| collision_bcc.get(blocks.get(block)).is_some())
+            .unwrap_or(false)
+    });
+
+    match ray_hit {
+        Some(RayHit {
+            time,
+            chunk,
+            block,
+            side,
+        }) => {
+            let offset = position.offset + displacement * time
+                - displacement / distance * COLLISION_PUSHBACK;
+
+            ProjectileStep {
+                position: position::to_chunk_position(position.chunk, offset),
+                velocity: Velocity {
+                    vector: Vec3F32::ZERO,
+                },
+                hit: Some(ProjectileHit { chunk, block, side }),
+            }
+        },
+        None => {
+            ProjectileStep {
+                position: position::to_chunk_position(
+                    position.chunk,
+                    position.offset + displacement,
+                ),
+                velocity: next_velocity,
+                hit: None,
+            }
+        },
+    }
+}
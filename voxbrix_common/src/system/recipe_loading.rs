@@ -0,0 +1,157 @@
+use crate::{
+    assets::RECIPE_LIST_PATH,
+    component::actor::inventory::Inventory,
+    entity::{
+        item_class::ItemClass,
+        recipe::Recipe,
+    },
+    system::pack_loading::PackSet,
+    LabelMap,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+use serde::Deserialize;
+use tokio::task;
+
+#[derive(Deserialize, Debug)]
+struct IngredientDescriptor {
+    item_class: String,
+    amount: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct RecipeDescriptor {
+    label: String,
+    inputs: Vec<IngredientDescriptor>,
+    outputs: Vec<IngredientDescriptor>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RecipeList {
+    list: Vec<RecipeDescriptor>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Ingredient {
+    pub item_class: ItemClass,
+    pub amount: u32,
+}
+
+#[derive(Debug)]
+pub struct RecipeData {
+    pub inputs: Vec<Ingredient>,
+    pub outputs: Vec<Ingredient>,
+}
+
+impl RecipeData {
+    /// Replaces the inputs with the outputs in the inventory.
+    /// Does nothing and returns `false` if the inputs are missing or the outputs do not fit.
+    pub fn craft(&self, inventory: &mut Inventory) -> bool {
+        let mut result = inventory.clone();
+
+        for input in self.inputs.iter() {
+            if !result.remove(input.item_class, input.amount) {
+                return false;
+            }
+        }
+
+        for output in self.outputs.iter() {
+            if result.add(output.item_class, output.amount) != 0 {
+                return false;
+            }
+        }
+
+        *inventory = result;
+
+        true
+    }
+}
+
+/// Crafting recipes, loaded from the recipe lists of the packs.
+/// A recipe of a later pack replaces the one with the same label.
+pub struct RecipeRegistry {
+    label_map: LabelMap<Recipe>,
+    recipes: Vec<RecipeData>,
+}
+
+impl RecipeRegistry {
+    pub async fn load(
+        packs: PackSet,
+        item_class_label_map: LabelMap<ItemClass>,
+    ) -> Result<Self, Error> {
+        task::spawn_blocking(move || {
+            let mut list: Vec<RecipeDescriptor> = Vec::new();
+
+            for pack_list in packs.read_data_files::<RecipeList>(RECIPE_LIST_PATH)? {
+                for recipe in pack_list.list {
+                    match list.iter_mut().find(|other| other.label == recipe.label) {
+                        Some(other) => *other = recipe,
+                        None => list.push(recipe),
+                    }
+                }
+            }
+
+            let labels = list
+                .iter()
+                .map(|recipe| recipe.label.clone())
+                .collect::<Vec<_>>();
+
+            let convert = |ingredients: Vec<IngredientDescriptor>| {
+                ingredients
+                    .into_iter()
+                    .map(|ingredient| {
+                        let item_class = item_class_label_map
+                            .get(&ingredient.item_class)
+                            .ok_or_else(|| {
+                                Error::msg(format!(
+                                    "item class \"{}\" not found in the item class list",
+                                    ingredient.item_class
+                                ))
+                            })?;
+
+                        Ok(Ingredient {
+                            item_class,
+                            amount: ingredient.amount,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, Error>>()
+            };
+
+            let recipes = list
+                .into_iter()
+                .map(|recipe| {
+                    let context = || format!("while loading recipe \"{}\"", recipe.label);
+
+                    Ok(RecipeData {
+                        inputs: convert(recipe.inputs).with_context(context)?,
+                        outputs: convert(recipe.outputs).with_context(context)?,
+                    })
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+
+            Ok(Self {
+                label_map: LabelMap::from_list(&labels),
+                recipes,
+            })
+        })
+        .await
+        .unwrap()
+    }
+
+    pub fn get(&self, recipe: &Recipe) -> Option<&RecipeData> {
+        self.recipes.get(recipe.0 as usize)
+    }
+
+    pub fn label_map(&self) -> &LabelMap<Recipe> {
+        &self.label_map
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (Recipe, &RecipeData)> {
+        self.recipes
+            .iter()
+            .enumerate()
+            .map(|(i, data)| (Recipe(i as u64), data))
+    }
+}
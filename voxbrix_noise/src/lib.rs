@@ -0,0 +1,441 @@
+//! Deterministic noise shared by the server and the chunk generation scripts.
+//! Values depend only on the seed and the coordinates, so the native and the wasm builds
+//! produce the same world for the same seed.
+//!
+//! Points are given in the noise units, the batch functions take global block
+//! coordinates and the `scale` in blocks per noise unit instead.
+pub use hash::Hasher64;
+use std::f64::consts::SQRT_2;
+
+pub mod hash;
+
+/// Noise over the horizontal plane.
+pub trait Noise2D {
+    /// Value at the `point`, about `-1.0 ..= 1.0` unless stated otherwise.
+    fn sample_2d(&self, point: [f64; 2]) -> f64;
+
+    /// Fills the `output` with the values of the `edge * edge` block columns of the chunk
+    /// with the horizontal position `chunk`, the value of the column `[x, y]`
+    /// being at `y * edge + x`. The block centers are sampled.
+    fn sample_chunk_column(&self, chunk: [i32; 2], edge: u32, scale: f64, output: &mut [f64]) {
+        assert!(output.len() >= (edge * edge) as usize);
+
+        for y in 0 .. edge {
+            let point_y = block_center(chunk[1], edge, y, scale);
+
+            for x in 0 .. edge {
+                let point_x = block_center(chunk[0], edge, x, scale);
+
+                output[(y * edge + x) as usize] = self.sample_2d([point_x, point_y]);
+            }
+        }
+    }
+}
+
+/// Noise over the whole space.
+pub trait Noise3D {
+    /// Value at the `point`, about `-1.0 ..= 1.0` unless stated otherwise.
+    fn sample_3d(&self, point: [f64; 3]) -> f64;
+}
+
+fn block_center(chunk: i32, edge: u32, block: u32, scale: f64) -> f64 {
+    ((chunk as i64 * edge as i64 + block as i64) as f64 + 0.5) / scale
+}
+
+// Extrasmoothstep for [0; 1.0]
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(v1: f64, v2: f64, c: f64) -> f64 {
+    (v2 - v1) * c + v1
+}
+
+/// Gradient (Perlin) noise with the gradients chosen by the hash of the grid node.
+#[derive(Clone, Copy, Debug)]
+pub struct GradientNoise {
+    seed: u64,
+}
+
+impl GradientNoise {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    fn hash_node(&self, node: &[i64]) -> u64 {
+        let mut hasher = Hasher64::new(self.seed);
+
+        for coord in node {
+            hasher.write(&coord.to_le_bytes());
+        }
+
+        // The hasher mixes the higher bits poorly on the last input
+        let hash = hasher.finish();
+        (hash ^ (hash >> 29)).wrapping_mul(0xbf58_476d_1ce4_e5b9)
+    }
+
+    fn gradient_2d(&self, node: [i64; 2]) -> [f64; 2] {
+        let hash = self.hash_node(&node);
+
+        let x = (hash >> 32) as u32 as f64 / u32::MAX as f64 * 2.0 - 1.0;
+        let mut y = (1.0 - x * x).sqrt();
+
+        if hash & 1 == 1 {
+            y = -y;
+        }
+
+        [x, y]
+    }
+
+    fn gradient_3d(&self, node: [i64; 3]) -> [f64; 3] {
+        // Edges of the cube, all of the same length
+        const GRADIENTS: [[f64; 3]; 12] = [
+            [1.0, 1.0, 0.0],
+            [-1.0, 1.0, 0.0],
+            [1.0, -1.0, 0.0],
+            [-1.0, -1.0, 0.0],
+            [1.0, 0.0, 1.0],
+            [-1.0, 0.0, 1.0],
+            [1.0, 0.0, -1.0],
+            [-1.0, 0.0, -1.0],
+            [0.0, 1.0, 1.0],
+            [0.0, -1.0, 1.0],
+            [0.0, 1.0, -1.0],
+            [0.0, -1.0, -1.0],
+        ];
+
+        GRADIENTS[((self.hash_node(&node) >> 32) % 12) as usize]
+    }
+
+    /// Gradients of the cell corners, X changes first.
+    fn cell_gradients_2d(&self, cell: [i64; 2]) -> [[f64; 2]; 4] {
+        [
+            self.gradient_2d(cell),
+            self.gradient_2d([cell[0] + 1, cell[1]]),
+            self.gradient_2d([cell[0], cell[1] + 1]),
+            self.gradient_2d([cell[0] + 1, cell[1] + 1]),
+        ]
+    }
+
+    /// Value from the cell gradients and the offset within the cell.
+    fn blend_2d(gradients: &[[f64; 2]; 4], dx: f64, dy: f64) -> f64 {
+        let dot = |gradient: [f64; 2], ox: f64, oy: f64| gradient[0] * ox + gradient[1] * oy;
+
+        let n00 = dot(gradients[0], dx, dy);
+        let n10 = dot(gradients[1], dx - 1.0, dy);
+        let n01 = dot(gradients[2], dx, dy - 1.0);
+        let n11 = dot(gradients[3], dx - 1.0, dy - 1.0);
+
+        let u = fade(dx);
+        let v = fade(dy);
+
+        // Unit gradients peak at `1 / SQRT_2`
+        (lerp(lerp(n00, n10, u), lerp(n01, n11, u), v) * SQRT_2).clamp(-1.0, 1.0)
+    }
+}
+
+impl Noise2D for GradientNoise {
+    fn sample_2d(&self, point: [f64; 2]) -> f64 {
+        let floor = point.map(f64::floor);
+        let cell = floor.map(|c| c as i64);
+
+        Self::blend_2d(
+            &self.cell_gradients_2d(cell),
+            point[0] - floor[0],
+            point[1] - floor[1],
+        )
+    }
+
+    /// Gradients are computed once per grid cell instead of once per column,
+    /// the inner loop is plain arithmetic over the row.
+    fn sample_chunk_column(&self, chunk: [i32; 2], edge: u32, scale: f64, output: &mut [f64]) {
+        assert!(output.len() >= (edge * edge) as usize);
+
+        for y in 0 .. edge {
+            let point_y = block_center(chunk[1], edge, y, scale);
+            let floor_y = point_y.floor();
+            let dy = point_y - floor_y;
+
+            let row = &mut output[(y * edge) as usize .. ((y + 1) * edge) as usize];
+
+            let mut cell_x = None;
+            let mut gradients = [[0.0; 2]; 4];
+
+            for (x, value) in row.iter_mut().enumerate() {
+                let point_x = block_center(chunk[0], edge, x as u32, scale);
+                let floor_x = point_x.floor();
+
+                if cell_x != Some(floor_x) {
+                    gradients = self.cell_gradients_2d([floor_x as i64, floor_y as i64]);
+                    cell_x = Some(floor_x);
+                }
+
+                *value = Self::blend_2d(&gradients, point_x - floor_x, dy);
+            }
+        }
+    }
+}
+
+impl Noise3D for GradientNoise {
+    fn sample_3d(&self, point: [f64; 3]) -> f64 {
+        let floor = point.map(f64::floor);
+        let cell = floor.map(|c| c as i64);
+        let offset = [0, 1, 2].map(|i| point[i] - floor[i]);
+
+        let mut corners = [0.0; 8];
+
+        for (i, corner) in corners.iter_mut().enumerate() {
+            let shift = [i & 1, (i >> 1) & 1, (i >> 2) & 1];
+
+            let gradient = self.gradient_3d([0, 1, 2].map(|a| cell[a] + shift[a] as i64));
+
+            *corner = (0 .. 3)
+                .map(|a| gradient[a] * (offset[a] - shift[a] as f64))
+                .sum();
+        }
+
+        let [u, v, w] = offset.map(fade);
+
+        let x00 = lerp(corners[0], corners[1], u);
+        let x10 = lerp(corners[2], corners[3], u);
+        let x01 = lerp(corners[4], corners[5], u);
+        let x11 = lerp(corners[6], corners[7], u);
+
+        lerp(lerp(x00, x10, v), lerp(x01, x11, v), w).clamp(-1.0, 1.0)
+    }
+}
+
+/// Parameters of the noise summed over several octaves.
+#[derive(Clone, Copy, Debug)]
+pub struct Octaves {
+    pub seed: u64,
+    pub octaves: u32,
+    /// Frequency multiplier of each next octave.
+    pub lacunarity: f64,
+    /// Amplitude multiplier of each next octave.
+    pub gain: f64,
+}
+
+impl Octaves {
+    pub fn new(seed: u64, octaves: u32) -> Self {
+        Self {
+            seed,
+            octaves,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+
+    /// Noise of each of the octaves with its frequency and amplitude.
+    fn iter(&self) -> impl Iterator<Item = (GradientNoise, f64, f64)> {
+        let Self {
+            seed,
+            lacunarity,
+            gain,
+            ..
+        } = *self;
+
+        (0 .. self.octaves.max(1)).scan((1.0, 1.0), move |(frequency, amplitude), octave| {
+            let item = (
+                GradientNoise::new(seed.wrapping_add(octave as u64)),
+                *frequency,
+                *amplitude,
+            );
+
+            *frequency *= lacunarity;
+            *amplitude *= gain;
+
+            Some(item)
+        })
+    }
+
+    fn total_amplitude(&self) -> f64 {
+        self.iter().map(|(_, _, amplitude)| amplitude).sum()
+    }
+
+    fn sample(&self, sample_octave: impl Fn(&GradientNoise, f64) -> f64) -> f64 {
+        let sum: f64 = self
+            .iter()
+            .map(|(noise, frequency, amplitude)| amplitude * sample_octave(&noise, frequency))
+            .sum();
+
+        sum / self.total_amplitude()
+    }
+
+    fn sample_chunk_column(
+        &self,
+        chunk: [i32; 2],
+        edge: u32,
+        scale: f64,
+        output: &mut [f64],
+        map: impl Fn(f64) -> f64,
+    ) {
+        let len = (edge * edge) as usize;
+        assert!(output.len() >= len);
+
+        let output = &mut output[.. len];
+        let mut octave_output = vec![0.0; len];
+
+        output.fill(0.0);
+
+        for (noise, frequency, amplitude) in self.iter() {
+            noise.sample_chunk_column(chunk, edge, scale / frequency, &mut octave_output);
+
+            for (value, octave_value) in output.iter_mut().zip(octave_output.iter()) {
+                *value += amplitude * map(*octave_value);
+            }
+        }
+
+        let total_amplitude = self.total_amplitude();
+
+        for value in output.iter_mut() {
+            *value /= total_amplitude;
+        }
+    }
+}
+
+/// Fractal Brownian motion, the gradient noise summed over the octaves.
+#[derive(Clone, Copy, Debug)]
+pub struct Fbm(pub Octaves);
+
+impl Noise2D for Fbm {
+    fn sample_2d(&self, point: [f64; 2]) -> f64 {
+        self.0
+            .sample(|noise, frequency| noise.sample_2d(point.map(|c| c * frequency)))
+    }
+
+    fn sample_chunk_column(&self, chunk: [i32; 2], edge: u32, scale: f64, output: &mut [f64]) {
+        self.0
+            .sample_chunk_column(chunk, edge, scale, output, |value| value)
+    }
+}
+
+impl Noise3D for Fbm {
+    fn sample_3d(&self, point: [f64; 3]) -> f64 {
+        self.0
+            .sample(|noise, frequency| noise.sample_3d(point.map(|c| c * frequency)))
+    }
+}
+
+/// Ridged multifractal, sharp ridges where the gradient noise crosses zero.
+/// Values are `0.0 ..= 1.0`, the ridges being `1.0`.
+#[derive(Clone, Copy, Debug)]
+pub struct Ridged(pub Octaves);
+
+fn ridge(value: f64) -> f64 {
+    (1.0 - value.abs()).powi(2)
+}
+
+impl Noise2D for Ridged {
+    fn sample_2d(&self, point: [f64; 2]) -> f64 {
+        self.0
+            .sample(|noise, frequency| ridge(noise.sample_2d(point.map(|c| c * frequency))))
+    }
+
+    fn sample_chunk_column(&self, chunk: [i32; 2], edge: u32, scale: f64, output: &mut [f64]) {
+        self.0
+            .sample_chunk_column(chunk, edge, scale, output, ridge)
+    }
+}
+
+impl Noise3D for Ridged {
+    fn sample_3d(&self, point: [f64; 3]) -> f64 {
+        self.0
+            .sample(|noise, frequency| ridge(noise.sample_3d(point.map(|c| c * frequency))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EDGE: u32 = 16;
+
+    fn column_points(chunk: [i32; 2], scale: f64) -> impl Iterator<Item = [f64; 2]> {
+        (0 .. EDGE).flat_map(move |y| {
+            (0 .. EDGE).map(move |x| {
+                [
+                    block_center(chunk[0], EDGE, x, scale),
+                    block_center(chunk[1], EDGE, y, scale),
+                ]
+            })
+        })
+    }
+
+    #[test]
+    fn deterministic_and_bounded() {
+        let noise = GradientNoise::new(42);
+
+        for i in -200 .. 200 {
+            let point = [i as f64 * 0.37, i as f64 * -0.61];
+            let value = noise.sample_2d(point);
+
+            assert_eq!(value, GradientNoise::new(42).sample_2d(point));
+            assert!((-1.0 ..= 1.0).contains(&value));
+
+            let value = noise.sample_3d([point[0], point[1], i as f64 * 0.13]);
+            assert!((-1.0 ..= 1.0).contains(&value));
+        }
+
+        assert_ne!(
+            noise.sample_2d([0.5, 0.5]),
+            GradientNoise::new(43).sample_2d([0.5, 0.5])
+        );
+    }
+
+    #[test]
+    fn zero_at_grid_nodes() {
+        let noise = GradientNoise::new(7);
+
+        assert_eq!(noise.sample_2d([3.0, -5.0]), 0.0);
+        assert_eq!(noise.sample_3d([3.0, -5.0, 2.0]), 0.0);
+    }
+
+    #[test]
+    fn batch_matches_points() {
+        let gradient = GradientNoise::new(1);
+        let fbm = Fbm(Octaves::new(2, 4));
+        let ridged = Ridged(Octaves::new(3, 3));
+
+        let mut output = vec![0.0; (EDGE * EDGE) as usize];
+
+        for chunk in [[0, 0], [-1, 3], [17, -42]] {
+            gradient.sample_chunk_column(chunk, EDGE, 24.0, &mut output);
+
+            for (value, point) in output.iter().zip(column_points(chunk, 24.0)) {
+                assert_eq!(*value, gradient.sample_2d(point));
+            }
+
+            fbm.sample_chunk_column(chunk, EDGE, 64.0, &mut output);
+
+            for (value, point) in output.iter().zip(column_points(chunk, 64.0)) {
+                assert!((value - fbm.sample_2d(point)).abs() < 1e-9);
+            }
+
+            ridged.sample_chunk_column(chunk, EDGE, 32.0, &mut output);
+
+            for (value, point) in output.iter().zip(column_points(chunk, 32.0)) {
+                assert!((value - ridged.sample_2d(point)).abs() < 1e-9);
+                assert!((0.0 ..= 1.0).contains(value));
+            }
+        }
+    }
+
+    #[test]
+    fn continuous_across_chunks() {
+        let fbm = Fbm(Octaves::new(5, 3));
+
+        let mut left = vec![0.0; (EDGE * EDGE) as usize];
+        let mut right = vec![0.0; (EDGE * EDGE) as usize];
+
+        fbm.sample_chunk_column([-1, 0], EDGE, 64.0, &mut left);
+        fbm.sample_chunk_column([0, 0], EDGE, 64.0, &mut right);
+
+        for y in 0 .. EDGE as usize {
+            let last = left[y * EDGE as usize + EDGE as usize - 1];
+            let first = right[y * EDGE as usize];
+
+            assert!((last - first).abs() < 0.1);
+        }
+    }
+}
@@ -4,11 +4,12 @@
 //!
 //! ```no_run
 //! use futures_lite::future;
-//! use std::time::Duration;
-//! use tokio::time;
-//! use voxbrix_protocol::client::{
-//!     Client,
-//!     Connection,
+//! use voxbrix_protocol::{
+//!     client::{
+//!         Client,
+//!         Connection,
+//!     },
+//!     KeepaliveParameters,
 //! };
 //!
 //! async fn example() {
@@ -35,15 +36,11 @@
 //!
 //!     let send_future = async {
 //!         sender.send_reliable(0, b"Hello Server!").await;
-//!         loop {
-//!             // Senders send no data passively by themselves and resending lost messages
-//!             // in reliable data transfer happens lazily, right before sending a new one.
-//!             // Therefore, it is highly recommended to send some kind of "ping" or "keepalive"
-//!             // messages periodically, so the lost packets could be retransmitted even if you
-//!             // do not send any meaningful data.
-//!             time::sleep(Duration::from_secs(1)).await;
-//!             sender.send_reliable(0, b"keepalive").await;
-//!         }
+//!         // Senders send no data passively by themselves and resending lost messages
+//!         // in reliable data transfer happens lazily, right before sending a new one.
+//!         // Polling the keepalive future retransmits the lost packets even if you
+//!         // do not send any meaningful data, and detects dead connections.
+//!         sender.keepalive(KeepaliveParameters::default()).await;
 //!     };
 //!
 //!     future::or(recv_future, send_future).await;
@@ -53,24 +50,46 @@
 use crate::{
     seek_read,
     seek_write,
-    AsSlice,
     Channel,
+    Congestion,
+    CongestionParameters,
+    ConnectionStats,
+    Echo,
+    Features,
+    HandshakeError,
+    Header,
     Id,
+    Ipv6Mode,
+    KeepaliveParameters,
     Key,
+    Keys,
+    ProtocolViolation,
+    ReceiveTimer,
+    RejectReason,
+    RekeyParameters,
+    ReplayWindow,
+    Secret,
     Sequence,
+    StatsCounter,
+    Token,
     Type,
     UnreliableBuffer,
     UnreliableBufferShard,
+    DROP_DISCONNECT_REASON,
+    ECHO_BUFFER,
     KEY_BUFFER,
     MAX_DATA_SIZE,
     MAX_PACKET_SIZE,
     MAX_SPLIT_DATA_SIZE,
-    MAX_SPLIT_PACKETS,
     NEW_CONNECTION_ID,
+    NONCE_SIZE,
+    PROTOCOL_VERSION,
+    PROTOCOL_VIOLATION_DISCONNECT_REASON,
     RELIABLE_QUEUE_LENGTH,
-    RELIABLE_RESEND_AFTER,
     SECRET_BUFFER,
     SERVER_ID,
+    TAG_SIZE,
+    TOKEN_BUFFER,
     UNRELIABLE_BUFFERS,
 };
 use chacha20poly1305::{
@@ -84,6 +103,7 @@ use flume::{
     Sender as ChannelTx,
     TryRecvError as TryReceiveError,
 };
+use futures_lite::future::FutureExt;
 use integer_encoding::{
     VarIntReader,
     VarIntWriter,
@@ -103,7 +123,10 @@ use local_channel::{
     TryReceiveError,
 };
 use log::debug;
-use rand_core::OsRng;
+use rand_core::{
+    OsRng,
+    RngCore,
+};
 #[cfg(feature = "single")]
 use std::rc::Rc;
 #[cfg(feature = "multi")]
@@ -113,18 +136,37 @@ use std::{
         self,
         Layout,
     },
-    collections::VecDeque,
+    collections::{
+        BTreeMap,
+        VecDeque,
+    },
     fmt,
     io::{
         Cursor,
         Error as StdIoError,
+        ErrorKind as StdIoErrorKind,
         Read,
         Write,
     },
     mem,
-    net::SocketAddr,
+    net::{
+        Ipv4Addr,
+        Ipv6Addr,
+        SocketAddr,
+    },
     slice,
-    time::Instant,
+    sync::{
+        atomic::{
+            AtomicBool,
+            AtomicU64,
+            Ordering,
+        },
+        Mutex,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
 };
 use tokio::{
     net::UdpSocket,
@@ -151,17 +193,98 @@ fn allocate_buffer() -> BoxBuffer {
     }
 }
 
-async fn send_ack(buffer: &mut Buffer, shared: &Shared, sequence: Sequence) -> Result<(), Error> {
+async fn send_ack(
+    buffer: &mut Buffer,
+    shared: &Shared,
+    channel: Channel,
+    sequence: Sequence,
+) -> Result<(), Error> {
     let (tag_start, len) = crate::write_in_buffer(buffer, shared.id, Type::ACKNOWLEDGE, |cursor| {
+        cursor.write_varint(channel).unwrap();
         cursor.write_varint(sequence).unwrap();
     });
 
-    crate::encode_in_buffer(buffer, &shared.cipher, tag_start, len);
+    crate::encode_in_buffer(buffer, &shared.keys.current(), tag_start, len);
+
+    let sent = transport_send(shared, &buffer[.. len]).await?;
+    shared.stats.sent(sent);
+    Ok(())
+}
+
+async fn send_signal(buffer: &mut Buffer, shared: &Shared, packet_type: u8) -> Result<(), Error> {
+    let (tag_start, _) = crate::write_in_buffer(buffer, shared.id, packet_type, |_| {});
+
+    let len = crate::tag_sign_in_buffer(buffer, &shared.keys.current(), tag_start);
+
+    let sent = transport_send(shared, &buffer[.. len]).await?;
+    shared.stats.sent(sent);
+    Ok(())
+}
+
+/// Encrypts a copy of the packed data with the current key.
+fn encrypt_copy(shared: &Shared, buffer: &Buffer, tag_start: usize, length: usize) -> Buffer {
+    let mut packet = *buffer;
+    crate::encode_in_buffer(&mut packet, &shared.keys.current(), tag_start, length);
+    packet
+}
+
+/// Sends the packet, moving the connection to a new socket if the current one failed.
+/// Refused packets mean that the server is gone, not the socket.
+async fn transport_send(shared: &Shared, packet: &[u8]) -> Result<usize, Error> {
+    match shared.transport().send(packet).await {
+        Ok(sent) => Ok(sent),
+        Err(err) if err.kind() == StdIoErrorKind::ConnectionRefused => Err(err.into()),
+        Err(err) => {
+            debug!("unable to send: {:?}, moving to a new socket", err);
+            migrate(shared).await?;
+            Ok(shared.transport().send(packet).await?)
+        },
+    }
+}
+
+async fn send_migrate(shared: &Shared) -> Result<(), Error> {
+    let mut buffer = ZEROED_BUFFER;
+
+    let (tag_start, len) =
+        crate::write_in_buffer(&mut buffer, shared.id, Type::MIGRATE, |cursor| {
+            cursor
+                .write_varint(shared.migration.load(Ordering::Relaxed))
+                .unwrap();
+        });
+
+    crate::encode_in_buffer(&mut buffer, &shared.keys.current(), tag_start, len);
 
-    shared.transport.send(&buffer[.. len]).await?;
+    let sent = shared.transport().send(&buffer[.. len]).await?;
+    shared.stats.sent(sent);
     Ok(())
 }
 
+/// Moves the connection to a new socket bound to any local address and tells the server
+/// the new address. Returns the new local address.
+async fn migrate(shared: &Shared) -> Result<SocketAddr, Error> {
+    let bind_address = if shared.server_address.is_ipv6() {
+        SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0))
+    } else {
+        SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))
+    };
+
+    let transport = crate::bind_socket(bind_address, shared.ipv6_mode)?;
+    transport.connect(shared.server_address).await?;
+    let address = transport.local_addr()?;
+
+    *shared.transport.lock().unwrap() = Rc::new(transport);
+    shared.migration.fetch_add(1, Ordering::Relaxed);
+    shared.migration_pending.store(true, Ordering::Relaxed);
+    shared.stats.migration();
+
+    send_migrate(shared).await?;
+
+    // Shared state keeps the receiver
+    let _ = shared.migration_sender.send(address);
+
+    Ok(address)
+}
+
 /// The error that can be returned by senders or by the receiver.
 #[derive(Debug)]
 pub enum Error {
@@ -169,12 +292,24 @@ pub enum Error {
     Io(StdIoError),
     /// Returned on attempt to send reliable message with the receiver dropped.
     ReceiverWasDropped,
-    /// Returned by the receiver in case the server dropped the connection handles.
-    Disconnect,
-    /// Currently internal variant, should not be returned.
+    /// Returned by the receiver in case the server disconnected explicitly or dropped
+    /// the connection handles. In the latter case the `reason` is `DROP_DISCONNECT_REASON`.
+    Disconnect { reason: u8, payload: Vec<u8> },
+    /// Returned by the keepalive in case nothing was received from the server within the
+    /// timeout.
     Timeout,
     /// Peer sent message that is too large.
     PeerMessageTooLarge,
+    /// Returned by `Client::connect()` if the server refused the connection
+    /// or does not support the required features.
+    Handshake(HandshakeError),
+    /// Returned by the receiver in strict mode after the server exceeded the limit of
+    /// malformed packets and was disconnected, contains the last violation.
+    ProtocolViolation(ProtocolViolation),
+    /// Returned by the receiver once the connection moved to a new local socket, either by
+    /// `Sender::rebind()` or because the previous socket failed. Not fatal, the connection
+    /// keeps working and the receiver should be polled further.
+    ConnectionMigrated { address: SocketAddr },
 }
 
 impl fmt::Display for Error {
@@ -191,9 +326,52 @@ impl From<StdIoError> for Error {
     }
 }
 
+/// Client parameters.
+#[derive(Debug, Default)]
+pub struct ClientParameters {
+    /// Congestion control of the reliable messages sent.
+    pub congestion: CongestionParameters,
+    /// Features the client supports.
+    pub features: Features,
+    /// Features the server must support for the connection to succeed.
+    pub required_features: Features,
+    /// Strict mode, the connection is closed if the server sent more malformed packets
+    /// than that. If `None`, the malformed packets are only dropped and counted in the stats.
+    pub max_protocol_violations: Option<u64>,
+    /// When to replace the connection key, the client always starts the rekeying.
+    pub rekey: RekeyParameters,
+    /// Whether the client bound to an IPv6 address can also connect to the IPv4 servers.
+    pub ipv6_mode: Ipv6Mode,
+}
+
+impl ClientParameters {
+    /// Bind the local socket and produce a `Client` with the given parameters.
+    pub async fn bind<A>(self, bind_address: A) -> Result<Client, Error>
+    where
+        A: Into<SocketAddr>,
+    {
+        let transport = crate::bind_socket(bind_address.into(), self.ipv6_mode)?;
+        Ok(Client {
+            transport,
+            congestion: self.congestion,
+            features: self.features,
+            required_features: self.required_features,
+            max_protocol_violations: self.max_protocol_violations,
+            rekey: self.rekey,
+            ipv6_mode: self.ipv6_mode,
+        })
+    }
+}
+
 /// Connection builder.
 pub struct Client {
     transport: UdpSocket,
+    congestion: CongestionParameters,
+    features: Features,
+    required_features: Features,
+    max_protocol_violations: Option<u64>,
+    rekey: RekeyParameters,
+    ipv6_mode: Ipv6Mode,
 }
 
 /// Returned by the `Client::connect()` method on successful connection to the server.
@@ -208,16 +386,91 @@ pub struct Connection {
     pub sender: Sender,
     /// Receiver part.
     pub receiver: Receiver,
+    /// Features supported by both the client and the server.
+    pub features: Features,
+    /// Allows to resume the connection if it is lost.
+    pub resumption: Resumption,
+}
+
+/// Resumption token issued by the server along with the secret of the connection.
+/// Must be kept private, as it allows to take over the session.
+#[derive(Clone)]
+pub struct Resumption {
+    token: Token,
+    secret: Secret,
+}
+
+impl fmt::Debug for Resumption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Resumption")
+    }
 }
 
 impl Client {
-    /// Bind the local socket.
+    /// Bind the local socket with the default parameters.
     pub async fn bind<A>(bind_address: A) -> Result<Self, Error>
     where
         A: Into<SocketAddr>,
     {
-        let transport = UdpSocket::bind(bind_address.into()).await?;
-        Ok(Self { transport })
+        ClientParameters::default().bind(bind_address).await
+    }
+
+    /// Measure the round trip time to the server without connecting to it.
+    /// The socket stays usable for the connection. Waits for the answer until the future
+    /// is dropped, so should be run with a timeout.
+    pub async fn ping<A>(&self, server_address: A) -> Result<Duration, Error>
+    where
+        A: Into<SocketAddr>,
+    {
+        let server_address = crate::canonical_address(server_address.into());
+        let is_ipv6_socket = self.transport.local_addr()?.is_ipv6();
+
+        let mut echo: Echo = ECHO_BUFFER;
+        OsRng.fill_bytes(&mut echo);
+
+        let mut buf = ZEROED_BUFFER;
+
+        let mut write_cursor = Cursor::new(buf.as_mut());
+
+        write_cursor.write_varint(NEW_CONNECTION_ID).unwrap();
+        write_cursor.write_varint(Type::PING).unwrap();
+        write_cursor.write_all(&echo).unwrap();
+
+        let len = write_cursor.position() as usize;
+
+        let sent_at = Instant::now();
+
+        self.transport
+            .send_to(
+                &buf[.. len],
+                crate::socket_address(is_ipv6_socket, server_address),
+            )
+            .await?;
+
+        loop {
+            let (len, address) = self.transport.recv_from(&mut buf).await?;
+
+            if crate::canonical_address(address) != server_address {
+                continue;
+            }
+
+            let mut read_cursor = Cursor::new(&buf[.. len]);
+
+            let sender: usize = seek_read!(read_cursor.read_varint(), "sender");
+
+            let mut packet_type = Type::UNDEFINED;
+            seek_read!(
+                read_cursor.read_exact(slice::from_mut(&mut packet_type)),
+                "type"
+            );
+
+            let mut answer: Echo = ECHO_BUFFER;
+            seek_read!(read_cursor.read_exact(&mut answer), "echo");
+
+            if sender == SERVER_ID && packet_type == Type::PONG && answer == echo {
+                return Ok(sent_at.elapsed());
+            }
+        }
     }
 
     /// Use bound socket to connect to the server.
@@ -225,11 +478,47 @@ impl Client {
     where
         A: Into<SocketAddr>,
     {
-        let Client { transport } = self;
+        self.handshake(server_address.into(), None).await
+    }
 
-        transport.connect(server_address.into()).await?;
+    /// Use bound socket to resume the lost connection to the server. The new connection gets
+    /// the same session on the server, so the application can restore its state without
+    /// the authentication. Fails with `HandshakeError::UnknownSession` if the server
+    /// does not recognize the resumption.
+    pub async fn resume<A>(
+        self,
+        server_address: A,
+        resumption: &Resumption,
+    ) -> Result<Connection, Error>
+    where
+        A: Into<SocketAddr>,
+    {
+        self.handshake(server_address.into(), Some(resumption))
+            .await
+    }
+
+    async fn handshake(
+        self,
+        server_address: SocketAddr,
+        resumption: Option<&Resumption>,
+    ) -> Result<Connection, Error> {
+        let Client {
+            transport,
+            congestion,
+            features,
+            required_features,
+            max_protocol_violations,
+            rekey,
+            ipv6_mode,
+        } = self;
+
+        let is_ipv6_socket = transport.local_addr()?.is_ipv6();
+        transport
+            .connect(crate::socket_address(is_ipv6_socket, server_address))
+            .await?;
 
         let (ack_sender, ack_receiver) = new_channel();
+        let (migration_sender, migration_receiver) = new_channel();
 
         let keypair = EphemeralSecret::random(&mut OsRng);
         let self_key: Key = EncodedPoint::from(keypair.public_key())
@@ -242,12 +531,37 @@ impl Client {
         let mut write_cursor = Cursor::new(buf.as_mut());
 
         write_cursor.write_varint(NEW_CONNECTION_ID).unwrap();
-        write_cursor.write_varint(Type::CONNECT).unwrap();
-        write_cursor.write_all(&self_key).unwrap();
 
-        transport.send(write_cursor.slice()).await?;
+        let len = match resumption {
+            None => {
+                write_cursor.write_varint(Type::CONNECT).unwrap();
+                write_cursor.write_all(&self_key).unwrap();
+                write_cursor.write_varint(PROTOCOL_VERSION).unwrap();
+                write_cursor.write_varint(features.0).unwrap();
 
-        let (peer_key, deciphered_peer_key, id) = loop {
+                write_cursor.position() as usize
+            },
+            Some(resumption) => {
+                write_cursor.write_varint(Type::RESUME).unwrap();
+                write_cursor.write_all(&resumption.token).unwrap();
+                let tag_start = write_cursor.position() as usize;
+                write_cursor.set_position((tag_start + TAG_SIZE + NONCE_SIZE) as u64);
+                write_cursor.write_all(&self_key).unwrap();
+                write_cursor.write_varint(PROTOCOL_VERSION).unwrap();
+                write_cursor.write_varint(features.0).unwrap();
+                let len = write_cursor.position() as usize;
+
+                // Proves the client owns the resumed connection
+                let cipher = ChaCha20Poly1305::new((&resumption.secret).into());
+                crate::encode_in_buffer(&mut buf, &cipher, tag_start, len);
+
+                len
+            },
+        };
+
+        transport.send(&buf[.. len]).await?;
+
+        let (peer_key, deciphered_peer_key, id, features, token) = loop {
             let len = transport.recv(&mut buf).await?;
 
             let mut read_cursor = Cursor::new(&buf[.. len]);
@@ -274,7 +588,48 @@ impl Client {
                 let deciphered_peer_key =
                     seek_read!(PublicKey::from_sec1_bytes(&key), "deciphered peer key");
 
-                break (key, deciphered_peer_key, id);
+                // Servers before versioning send neither
+                let version: u16 = read_cursor.read_varint().unwrap_or(0);
+                let features = Features(read_cursor.read_varint().unwrap_or(0));
+
+                if version != PROTOCOL_VERSION {
+                    return Err(Error::Handshake(HandshakeError::VersionMismatch {
+                        client: PROTOCOL_VERSION,
+                        server: version,
+                    }));
+                }
+
+                let mut token = TOKEN_BUFFER;
+                seek_read!(read_cursor.read_exact(&mut token), "token");
+
+                break (key, deciphered_peer_key, id, features, token);
+            }
+
+            if packet_type == Type::REJECT {
+                let version: u16 = seek_read!(read_cursor.read_varint(), "version");
+                let mut reason = 0;
+                seek_read!(
+                    read_cursor.read_exact(slice::from_mut(&mut reason)),
+                    "reason"
+                );
+                let missing = Features(seek_read!(read_cursor.read_varint(), "features"));
+
+                let error = match reason {
+                    _ if reason == RejectReason::VERSION || version != PROTOCOL_VERSION => {
+                        HandshakeError::VersionMismatch {
+                            client: PROTOCOL_VERSION,
+                            server: version,
+                        }
+                    },
+                    RejectReason::FEATURES => HandshakeError::MissingFeatures(missing),
+                    RejectReason::SESSION => HandshakeError::UnknownSession,
+                    _ => {
+                        debug!("unknown reject reason {}", reason);
+                        continue;
+                    },
+                };
+
+                return Err(Error::Handshake(error));
             }
         };
 
@@ -285,13 +640,21 @@ impl Client {
             .expand(&[], &mut secret)
             .unwrap();
 
-        let cipher = ChaCha20Poly1305::new((&secret).into());
-
         let shared = {
             let shared = Shared {
                 id,
-                cipher,
-                transport,
+                keys: Keys::new(secret),
+                transport: Mutex::new(Rc::new(transport)),
+                server_address: crate::canonical_address(server_address),
+                ipv6_mode,
+                migration: AtomicU64::new(0),
+                migration_pending: AtomicBool::new(false),
+                migration_sender,
+                receive_timer: ReceiveTimer::new(),
+                stats: StatsCounter::default(),
+                max_protocol_violations,
+                rekey_parameters: rekey,
+                pending_rekey: Mutex::new(None),
             };
 
             Rc::new(shared)
@@ -299,59 +662,97 @@ impl Client {
 
         let receiver = Receiver {
             shared: shared.clone(),
-            sequence: 0,
-            reliable_queue: vec![None; RELIABLE_QUEUE_LENGTH as usize].into(),
-            reliable_split_buffer: Vec::new(),
-            reliable_split_channel: None,
+            reliable_queues: BTreeMap::new(),
+            reliable_ready_channel: None,
             recv_buffer: allocate_buffer(),
             send_buffer: allocate_buffer(),
             ack_sender,
             unreliable_split_shards: VecDeque::with_capacity(UNRELIABLE_BUFFERS),
             unreliable_split_buffer: Vec::new(),
+            replay_window: ReplayWindow::new(),
+            migration_receiver,
         };
 
         let sender = Sender {
             unreliable: UnreliableSender {
                 shared: shared.clone(),
                 unreliable_split_id: 0,
+                unreliable_sequence: 0,
             },
             reliable: ReliableSender {
                 shared,
-                queue_front_sequence: 0,
-                queue: VecDeque::new(),
+                queues: BTreeMap::new(),
+                in_flight: 0,
+                congestion: Congestion::new(congestion),
                 ack_receiver,
+                last_ping: None,
+                migration: 0,
             },
         };
 
+        // Dropping the handles disconnects from the server
+        if !features.contains(required_features) {
+            return Err(Error::Handshake(HandshakeError::MissingFeatures(
+                required_features.difference(features),
+            )));
+        }
+
         Ok(Connection {
             self_key,
             peer_key,
             sender,
             receiver,
+            features,
+            resumption: Resumption { token, secret },
         })
     }
 }
 
+/// Rekeying request sent, but not answered yet.
+struct PendingRekey {
+    keypair: EphemeralSecret,
+    key: Key,
+    sent_at: Instant,
+}
+
 struct Shared {
     id: Id,
-    cipher: ChaCha20Poly1305,
-    transport: UdpSocket,
+    keys: Keys,
+    // Replaced on migration, the handles take a copy for the time of each call
+    transport: Mutex<Rc<UdpSocket>>,
+    server_address: SocketAddr,
+    ipv6_mode: Ipv6Mode,
+    // Sequence of the last migration
+    migration: AtomicU64,
+    // Nothing came to the new socket yet, the server may not know the address
+    migration_pending: AtomicBool,
+    migration_sender: ChannelTx<SocketAddr>,
+    receive_timer: ReceiveTimer,
+    stats: StatsCounter,
+    max_protocol_violations: Option<u64>,
+    rekey_parameters: RekeyParameters,
+    pending_rekey: Mutex<Option<PendingRekey>>,
+}
+
+impl Shared {
+    fn transport(&self) -> Rc<UdpSocket> {
+        self.transport.lock().unwrap().clone()
+    }
 }
 
 impl Drop for Shared {
     fn drop(&mut self) {
         let mut buffer = ZEROED_BUFFER;
 
-        let mut write_cursor = Cursor::new(buffer.as_mut_slice());
-
-        write_cursor.write_varint(self.id).unwrap();
-        write_cursor.write_varint(Type::DISCONNECT).unwrap();
-
-        let tag_start = write_cursor.position();
-
-        let len = crate::tag_sign_in_buffer(&mut buffer, &self.cipher, tag_start as usize);
+        let len = crate::disconnect_in_buffer(
+            &mut buffer,
+            self.id,
+            &self.keys.current(),
+            DROP_DISCONNECT_REASON,
+            &[],
+        );
 
-        let _ = self.transport.try_send(&buffer[0 .. len]);
+        let _ = self.transport().try_send(&buffer[0 .. len]);
     }
 }
 
@@ -361,22 +762,42 @@ struct QueueEntry {
     buffer: Option<BoxBuffer>,
     start: usize,
     stop: usize,
-    channel: Channel,
     is_split: bool,
 }
 
+/// Reliable messages of a channel, waiting to be received in order.
+struct ReceiveQueue {
+    sequence: Sequence,
+    queue: VecDeque<Option<QueueEntry>>,
+    split_buffer: Vec<u8>,
+    is_split: bool,
+}
+
+impl ReceiveQueue {
+    fn new() -> Self {
+        Self {
+            sequence: 0,
+            queue: vec![None; RELIABLE_QUEUE_LENGTH as usize].into(),
+            split_buffer: Vec::new(),
+            is_split: false,
+        }
+    }
+}
+
 /// Message-receiving part of the connection.
 pub struct Receiver {
     shared: Rc<Shared>,
-    sequence: Sequence,
-    reliable_queue: VecDeque<Option<QueueEntry>>,
-    reliable_split_buffer: Vec<u8>,
-    reliable_split_channel: Option<Channel>,
+    // TODO limit the number of channels the peer can open?
+    reliable_queues: BTreeMap<Channel, ReceiveQueue>,
+    // Channel that may have the first message in the queue ready to be received
+    reliable_ready_channel: Option<Channel>,
     recv_buffer: BoxBuffer,
     send_buffer: BoxBuffer,
-    ack_sender: ChannelTx<Sequence>,
+    ack_sender: ChannelTx<(Channel, Sequence)>,
     unreliable_split_shards: VecDeque<UnreliableBuffer>,
     unreliable_split_buffer: Vec<u8>,
+    replay_window: ReplayWindow,
+    migration_receiver: ChannelRx<SocketAddr>,
 }
 
 impl Receiver {
@@ -386,86 +807,92 @@ impl Receiver {
     /// using `Sender`!**
     pub async fn recv(&mut self) -> Result<(Channel, &[u8]), Error> {
         loop {
-            // Firstly, we check the reliable message queue
+            // Firstly, we check the reliable message queue of the channel
             // in case we have messages ready, handle these first
-            if self
-                .reliable_queue
-                .front()
-                .and_then(|f| f.as_ref())
-                .is_some()
-            {
+            if let Some(channel) = self.reliable_ready_channel {
+                let queue = self.reliable_queues.get_mut(&channel).unwrap();
+
+                if queue.queue.front().and_then(|f| f.as_ref()).is_none() {
+                    self.reliable_ready_channel = None;
+                    continue;
+                }
+
                 let QueueEntry {
                     buffer: queue_buffer_box,
                     start,
                     stop,
-                    channel,
                     is_split,
-                } = self.reliable_queue.pop_front().flatten().unwrap();
+                } = queue.queue.pop_front().flatten().unwrap();
 
-                self.reliable_queue.push_back(None);
-                self.sequence = self.sequence.wrapping_add(1);
+                queue.queue.push_back(None);
+                queue.sequence = queue.sequence.wrapping_add(1);
 
                 // None means it's in the recv_buffer, we just received that packet in the previous
                 // iteration of the loop
                 let queue_buffer =
                     &queue_buffer_box.as_ref().unwrap_or(&self.recv_buffer)[start .. stop];
 
-                if is_split {
-                    // Split started, if not started - cleanup & start,
-                    // if we already started - check the channel
-                    if self.reliable_split_channel.is_none() {
-                        self.reliable_split_channel = Some(channel);
-                        self.reliable_split_buffer.clear();
-                    } else if let Some(reliable_split_channel) = self.reliable_split_channel {
-                        if reliable_split_channel != channel {
-                            debug!("skipping mishappened packet with channel {}", channel);
-                            continue;
-                        }
+                if is_split || queue.is_split {
+                    // Split started, if not started - cleanup & start
+                    if !queue.is_split {
+                        queue.is_split = true;
+                        queue.split_buffer.clear();
                     }
 
-                    if self.reliable_split_buffer.len() + queue_buffer.len() > MAX_SPLIT_DATA_SIZE {
+                    if queue.split_buffer.len() + queue_buffer.len() > MAX_SPLIT_DATA_SIZE {
                         return Err(Error::PeerMessageTooLarge);
                     }
 
-                    self.reliable_split_buffer.extend_from_slice(queue_buffer);
+                    queue.split_buffer.extend_from_slice(queue_buffer);
 
-                    continue;
-                } else {
-                    let buf = if let Some(reliable_split_channel) = self.reliable_split_channel {
-                        // Split just completed, extending and returning
-                        if reliable_split_channel == channel {
-                            if self.reliable_split_buffer.len() + queue_buffer.len()
-                                > MAX_SPLIT_DATA_SIZE
-                            {
-                                return Err(Error::PeerMessageTooLarge);
-                            }
-
-                            self.reliable_split_buffer.extend_from_slice(queue_buffer);
-
-                            self.reliable_split_channel = None;
-
-                            self.reliable_split_buffer.as_slice()
-                        } else {
-                            continue;
-                        }
-                    } else {
-                        // Non-split packet arrived
-                        if let Some(queue_buffer) = queue_buffer_box {
-                            self.recv_buffer = queue_buffer;
-                        }
+                    if is_split {
+                        continue;
+                    }
 
-                        &self.recv_buffer[start .. stop]
-                    };
+                    // Split just completed, returning
+                    queue.is_split = false;
+
+                    let buf = self.reliable_queues[&channel].split_buffer.as_slice();
 
                     return Ok((channel, buf));
                 }
+
+                // Non-split packet arrived
+                if let Some(queue_buffer) = queue_buffer_box {
+                    self.recv_buffer = queue_buffer;
+                }
+
+                return Ok((channel, &self.recv_buffer[start .. stop]));
             }
 
-            let len = self
-                .shared
-                .transport
-                .recv(self.recv_buffer.as_mut())
-                .await?;
+            let transport = self.shared.transport();
+            let recv_buffer = &mut self.recv_buffer;
+            let migration_receiver = &mut self.migration_receiver;
+
+            let received = async { Ok(transport.recv(recv_buffer.as_mut()).await) }
+                .or(async {
+                    #[cfg(feature = "single")]
+                    let address = migration_receiver.recv().await;
+                    #[cfg(feature = "multi")]
+                    let address = migration_receiver.recv_async().await;
+
+                    // The sender is kept in the shared state
+                    Err(address.unwrap())
+                })
+                .await;
+
+            let len = match received {
+                Ok(Ok(len)) => len,
+                Ok(Err(err)) if err.kind() == StdIoErrorKind::ConnectionRefused => {
+                    return Err(err.into());
+                },
+                Ok(Err(err)) => {
+                    debug!("unable to receive: {:?}, moving to a new socket", err);
+                    migrate(&self.shared).await?;
+                    continue;
+                },
+                Err(address) => return Err(Error::ConnectionMigrated { address }),
+            };
 
             let mut read_cursor = Cursor::new(&self.recv_buffer[.. len]);
 
@@ -483,45 +910,93 @@ impl Receiver {
 
             let tag_start = read_cursor.position() as usize;
 
-            let decrypted_start = match crate::decode_in_buffer(
-                &mut self.recv_buffer[.. len],
-                tag_start,
-                &self.shared.cipher,
-            ) {
+            let (decrypted_start, previous_key) = match self
+                .shared
+                .keys
+                .decode(&mut self.recv_buffer[.. len], tag_start)
+            {
                 Ok(s) => s,
                 Err(()) => continue,
             };
 
-            let mut read_cursor = Cursor::new(&self.recv_buffer[.. len]);
-            read_cursor.set_position(decrypted_start as u64);
+            self.shared.receive_timer.mark();
+            self.shared.stats.received(len);
+            self.shared
+                .migration_pending
+                .store(false, Ordering::Relaxed);
+
+            let (header, data_start) =
+                match crate::read_header(packet_type, &self.recv_buffer[decrypted_start .. len]) {
+                    Ok(h) => h,
+                    Err(violation) => {
+                        debug!(
+                            "protocol violation {:?} in packet of type {}",
+                            violation, packet_type
+                        );
+
+                        let violations = self.shared.stats.violation(violation);
+
+                        if self
+                            .shared
+                            .max_protocol_violations
+                            .is_some_and(|max| violations > max)
+                        {
+                            let len = crate::disconnect_in_buffer(
+                                &mut self.send_buffer,
+                                self.shared.id,
+                                &self.shared.keys.current(),
+                                PROTOCOL_VIOLATION_DISCONNECT_REASON,
+                                &[],
+                            );
+
+                            let sent =
+                                transport_send(&self.shared, &self.send_buffer[.. len]).await?;
+                            self.shared.stats.sent(sent);
+
+                            return Err(Error::ProtocolViolation(violation));
+                        }
+
+                        continue;
+                    },
+                };
+
+            let start = decrypted_start + data_start;
 
-            match packet_type {
-                Type::ACKNOWLEDGE => {
-                    let sequence: Sequence = seek_read!(read_cursor.read_varint(), "sequence");
-                    let _ = self.ack_sender.send(sequence);
+            if let Some(sequence) = header.unreliable_sequence() {
+                if !self.replay_window.accept(sequence) {
+                    debug!("dropping replayed unreliable packet {}", sequence);
+                    self.shared.stats.unreliable_replayed();
+                    continue;
+                }
+            }
+
+            match header {
+                Header::Ping => {
+                    seek_write!(
+                        send_signal(&mut self.send_buffer, self.shared.as_ref(), Type::PONG).await,
+                        "pong message"
+                    );
+                },
+                Header::Acknowledge { channel, sequence } => {
+                    let _ = self.ack_sender.send((channel, sequence));
                 },
-                Type::DISCONNECT => {
-                    return Err(Error::Disconnect);
+                Header::Disconnect { reason } => {
+                    return Err(Error::Disconnect {
+                        reason,
+                        payload: self.recv_buffer[start .. len].to_vec(),
+                    });
                 },
-                Type::UNRELIABLE => {
-                    let channel: Channel = seek_read!(read_cursor.read_varint(), "channel");
-                    let start = read_cursor.position() as usize;
+                Header::Unreliable { channel, .. } => {
+                    self.shared.stats.unreliable_received();
                     return Ok((channel, &self.recv_buffer[start .. len]));
                 },
-                Type::UNRELIABLE_SPLIT_START => {
-                    let channel: Channel = seek_read!(read_cursor.read_varint(), "channel");
-                    let split_id: u16 = seek_read!(read_cursor.read_varint(), "split_id");
-                    let expected_packets: usize =
-                        seek_read!(read_cursor.read_varint(), "expected_packets");
-
-                    if expected_packets > MAX_SPLIT_PACKETS {
-                        log::debug!(
-                            "dropping packet with packet length {} more than maximum {}",
-                            expected_packets,
-                            MAX_SPLIT_PACKETS,
-                        );
-                        continue;
-                    }
+                Header::UnreliableSplitStart {
+                    channel,
+                    split_id,
+                    expected_packets,
+                    ..
+                } => {
+                    self.shared.stats.unreliable_received();
 
                     let mut split_buffer = if self.unreliable_split_shards.len()
                         == UNRELIABLE_BUFFERS
@@ -529,6 +1004,9 @@ impl Receiver {
                             && self.unreliable_split_shards.back().unwrap().is_complete()
                     {
                         let mut b = self.unreliable_split_shards.pop_back().unwrap();
+                        if !b.is_complete() {
+                            self.shared.stats.unreliable_dropped(b.complete_shards);
+                        }
                         let UnreliableBuffer {
                             split_id: b_split_id,
                             channel: b_channel,
@@ -550,8 +1028,6 @@ impl Receiver {
                         }
                     };
 
-                    let start = read_cursor.position() as usize;
-
                     let data_length = len - start;
 
                     let shard = split_buffer.shards.get_mut(0).unwrap();
@@ -564,11 +1040,13 @@ impl Receiver {
 
                     self.unreliable_split_shards.push_front(split_buffer);
                 },
-                Type::UNRELIABLE_SPLIT => {
-                    let channel: Channel = seek_read!(read_cursor.read_varint(), "channel");
-                    let split_id: u16 = seek_read!(read_cursor.read_varint(), "split_id");
-                    let count: usize = seek_read!(read_cursor.read_varint(), "count");
-                    let start = read_cursor.position() as usize;
+                Header::UnreliableSplit {
+                    channel,
+                    split_id,
+                    count,
+                    ..
+                } => {
+                    self.shared.stats.unreliable_received();
                     let data_length = len - start;
 
                     let split_buffer = match self.unreliable_split_shards.iter_mut().find(|b| {
@@ -577,6 +1055,7 @@ impl Receiver {
                         Some(b) => b,
                         None => {
                             debug!("split buffer not found for split {}", split_id);
+                            self.shared.stats.unreliable_dropped(1);
                             continue;
                         },
                     };
@@ -585,12 +1064,14 @@ impl Receiver {
                         Some(s) => s,
                         None => {
                             debug!("shard not found for count {}", count);
+                            self.shared.stats.unreliable_dropped(1);
                             continue;
                         },
                     };
 
                     if shard.written {
                         debug!("shard is already written for count {}", count);
+                        self.shared.stats.unreliable_dropped(1);
                         continue;
                     }
 
@@ -614,15 +1095,20 @@ impl Receiver {
                         return Ok((channel, self.unreliable_split_buffer.as_slice()));
                     }
                 },
-                Type::RELIABLE | Type::RELIABLE_SPLIT => {
-                    let channel: Channel = seek_read!(read_cursor.read_varint(), "channel");
-                    let sequence: Sequence = seek_read!(read_cursor.read_varint(), "sequence");
+                Header::Reliable {
+                    channel,
+                    sequence,
+                    is_split,
+                } => {
+                    let queue = self
+                        .reliable_queues
+                        .entry(channel)
+                        .or_insert_with(ReceiveQueue::new);
 
-                    let start = read_cursor.position() as usize;
                     // TODO verify correctness
-                    let index = sequence.wrapping_sub(self.sequence);
+                    let index = sequence.wrapping_sub(queue.sequence);
                     if index < RELIABLE_QUEUE_LENGTH {
-                        let queue_place = self.reliable_queue.get_mut(index as usize).unwrap();
+                        let queue_place = queue.queue.get_mut(index as usize).unwrap();
 
                         *queue_place = Some(QueueEntry {
                             buffer: if index == 0 {
@@ -634,21 +1120,55 @@ impl Receiver {
                             },
                             start,
                             stop: len,
-                            channel,
-                            is_split: packet_type == Type::RELIABLE_SPLIT,
+                            is_split,
                         });
+
+                        if index == 0 {
+                            self.reliable_ready_channel = Some(channel);
+                        }
                     }
 
                     // TODO: do not answer if the sequence is not previous, but random?
                     seek_write!(
-                        send_ack(&mut self.send_buffer, self.shared.as_ref(), sequence).await,
+                        send_ack(
+                            &mut self.send_buffer,
+                            self.shared.as_ref(),
+                            channel,
+                            sequence
+                        )
+                        .await,
                         "ack message"
                     );
                 },
-                _ => {},
+                Header::Rekey { key } => {
+                    // Answers to the earlier requests come with the previous key
+                    if previous_key {
+                        continue;
+                    }
+
+                    let Some(pending) = self.shared.pending_rekey.lock().unwrap().take() else {
+                        continue;
+                    };
+
+                    // Checked by read_header()
+                    let peer_key = PublicKey::from_sec1_bytes(&key).unwrap();
+
+                    self.shared.keys.rekey(
+                        &pending.keypair.diffie_hellman(&peer_key),
+                        self.shared.stats.bytes(),
+                    );
+                    self.shared.stats.rekey();
+                },
+                // Only the client migrates
+                Header::Pong | Header::Migrate { .. } => {},
             }
         }
     }
+
+    /// Get network statistics of the connection.
+    pub fn stats(&self) -> ConnectionStats {
+        self.shared.stats.get()
+    }
 }
 
 /// Message-sending part of the connection. Contains both reliable-sending and unreliable-sending
@@ -678,6 +1198,44 @@ impl Sender {
         self.reliable.wait_complete().await
     }
 
+    /// Keep the connection alive. See `ReliableSender::keepalive()`.
+    pub async fn keepalive(&mut self, parameters: KeepaliveParameters) -> Result<(), Error> {
+        self.reliable.keepalive(parameters).await
+    }
+
+    /// Move the connection to a new local socket. See `ReliableSender::rebind()`.
+    pub async fn rebind(&mut self) -> Result<SocketAddr, Error> {
+        self.reliable.rebind().await
+    }
+
+    /// Get network statistics of the connection.
+    pub fn stats(&self) -> ConnectionStats {
+        self.reliable.stats()
+    }
+
+    /// Disconnect from the server, the `reason` and the `payload` are returned by the server's
+    /// receiver in `Error::Disconnect`. The payload is truncated to `MAX_DATA_SIZE`.
+    ///
+    /// The message is sent once and its delivery is not guaranteed. If it is lost,
+    /// the server will only notice the disconnect by keepalive timeout.
+    pub async fn disconnect(self, reason: u8, payload: &[u8]) -> Result<(), Error> {
+        let shared = &self.unreliable.shared;
+        let mut buffer = ZEROED_BUFFER;
+
+        let len = crate::disconnect_in_buffer(
+            &mut buffer,
+            shared.id,
+            &shared.keys.current(),
+            reason,
+            payload,
+        );
+
+        let sent = transport_send(shared, &buffer[.. len]).await?;
+        shared.stats.sent(sent);
+
+        Ok(())
+    }
+
     /// Split the `Sender` into `ReliableSender` and `UnreliableSender` halves.
     pub fn split(self) -> (UnreliableSender, ReliableSender) {
         let Self {
@@ -693,11 +1251,12 @@ impl Sender {
 pub struct UnreliableSender {
     shared: Rc<Shared>,
     unreliable_split_id: u16,
+    unreliable_sequence: u64,
 }
 
 impl UnreliableSender {
     async fn send_unreliable_one(
-        &self,
+        &mut self,
         channel: Channel,
         data: &[u8],
         message_type: u8,
@@ -705,8 +1264,11 @@ impl UnreliableSender {
     ) -> Result<(), Error> {
         let mut buffer = ZEROED_BUFFER;
 
+        self.unreliable_sequence += 1;
+
         let (tag_start, len) =
             crate::write_in_buffer(&mut buffer, self.shared.id, message_type, |cursor| {
+                cursor.write_varint(self.unreliable_sequence).unwrap();
                 cursor.write_varint(channel).unwrap();
                 if let Some(len_or_count) = len_or_count {
                     cursor.write_varint(self.unreliable_split_id).unwrap();
@@ -715,9 +1277,10 @@ impl UnreliableSender {
                 cursor.write_all(data).unwrap();
             });
 
-        crate::encode_in_buffer(&mut buffer, &self.shared.cipher, tag_start, len);
+        crate::encode_in_buffer(&mut buffer, &self.shared.keys.current(), tag_start, len);
 
-        self.shared.transport.send(&buffer[.. len]).await?;
+        let sent = transport_send(&self.shared, &buffer[.. len]).await?;
+        self.shared.stats.sent(sent);
 
         Ok(())
     }
@@ -754,52 +1317,72 @@ impl UnreliableSender {
                 .await
         }
     }
+
+    /// Get network statistics of the connection.
+    pub fn stats(&self) -> ConnectionStats {
+        self.shared.stats.get()
+    }
 }
 
 enum PacketState {
     Done,
     Pending {
         sent_at: Instant,
+        // Unencrypted, the key may be replaced before the packet is acknowledged
         buffer: BoxBuffer,
+        tag_start: usize,
         length: usize,
+        // Resent packets are not used for RTT measurement
+        resent: bool,
     },
 }
 
+/// Reliable messages of a channel, waiting for the acknowledgement.
+#[derive(Default)]
+struct SendQueue {
+    front_sequence: Sequence,
+    queue: VecDeque<PacketState>,
+}
+
 /// Reliable-sending part of the connection.
 pub struct ReliableSender {
     shared: Rc<Shared>,
-    queue_front_sequence: Sequence,
-    queue: VecDeque<PacketState>,
-    ack_receiver: ChannelRx<Sequence>,
+    queues: BTreeMap<Channel, SendQueue>,
+    // Number of packets in all queues waiting for the acknowledgement
+    in_flight: usize,
+    congestion: Congestion,
+    ack_receiver: ChannelRx<(Channel, Sequence)>,
+    last_ping: Option<Instant>,
+    // Sequence of the last migration the packets in flight were resent after
+    migration: u64,
 }
 
 impl ReliableSender {
-    fn pack_data(&mut self, channel: Channel, data: &[u8], packet_type: u8) -> (BoxBuffer, usize) {
+    /// Returns the unencrypted packet, the start of its tag and its length.
+    fn pack_data(
+        &self,
+        channel: Channel,
+        sequence: Sequence,
+        data: &[u8],
+        packet_type: u8,
+    ) -> (BoxBuffer, usize, usize) {
         let mut buffer = allocate_buffer();
 
         let (tag_start, len) =
             crate::write_in_buffer(buffer.as_mut(), self.shared.id, packet_type, |cursor| {
                 cursor.write_varint(channel).unwrap();
-                cursor
-                    .write_varint(
-                        self.queue_front_sequence
-                            .wrapping_add(self.queue.len() as u16),
-                    )
-                    .unwrap();
+                cursor.write_varint(sequence).unwrap();
                 cursor.write_all(data).unwrap();
             });
 
-        crate::encode_in_buffer(buffer.as_mut(), &self.shared.cipher, tag_start, len);
-
-        (buffer, len)
+        (buffer, tag_start, len)
     }
 
-    async fn handle_acks_resend(&mut self, mut must_wait: bool) -> Result<(), Error> {
+    async fn handle_acks_resend(&mut self, mut wait: Option<Duration>) -> Result<(), Error> {
         loop {
             // Handling previous ACKs first
-            let ack = if must_wait {
-                must_wait = false;
-                let result = match time::timeout(RELIABLE_RESEND_AFTER, {
+            let (channel, ack) = if let Some(wait) = wait.take() {
+                let result = match time::timeout(wait, {
                     #[cfg(feature = "single")]
                     {
                         self.ack_receiver.recv()
@@ -827,45 +1410,91 @@ impl ReliableSender {
                 }
             };
 
-            let index = ack.wrapping_sub(self.queue_front_sequence);
+            let Some(queue) = self.queues.get_mut(&channel) else {
+                continue;
+            };
+
+            let index = ack.wrapping_sub(queue.front_sequence);
 
             if index < RELIABLE_QUEUE_LENGTH {
-                if let Some(queue_entry) = self.queue.get_mut(index as usize) {
+                if let Some(queue_entry) = queue.queue.get_mut(index as usize) {
+                    if let PacketState::Pending {
+                        sent_at, resent, ..
+                    } = queue_entry
+                    {
+                        if !*resent {
+                            self.shared.stats.rtt_sample(sent_at.elapsed());
+                            self.congestion.on_rtt_sample(sent_at.elapsed());
+                        }
+
+                        self.in_flight -= 1;
+                        self.congestion.on_ack();
+                    }
+
                     *queue_entry = PacketState::Done;
                 }
             }
         }
 
         // Getting rid of confirmed packets
-        while matches!(self.queue.front(), Some(PacketState::Done)) {
-            self.queue.pop_front();
-            self.queue_front_sequence = self.queue_front_sequence.wrapping_add(1);
+        for queue in self.queues.values_mut() {
+            while matches!(queue.queue.front(), Some(PacketState::Done)) {
+                queue.queue.pop_front();
+                queue.front_sequence = queue.front_sequence.wrapping_add(1);
+            }
         }
 
-        let mut queue = mem::take(&mut self.queue);
+        // The acknowledgements cannot be read until the rekeying is answered,
+        // the server uses the new key right after the answer
+        self.rekey(false).await?;
+
+        // Missing acknowledgements do not mean the loss then, resending would only crowd out
+        // the rekeying request
+        if self.shared.pending_rekey.lock().unwrap().is_some() {
+            return Ok(());
+        }
 
         // Lazily resending lost packages
-        for (sent_at, buffer, length) in queue.iter_mut().filter_map(|entry| {
-            match entry {
-                PacketState::Pending {
-                    sent_at,
-                    buffer,
-                    length,
-                } => Some((sent_at, buffer, length)),
-                PacketState::Done => None,
-            }
-        }) {
-            if sent_at.elapsed() > RELIABLE_RESEND_AFTER {
-                if let Err(err) = self.shared.transport.send(&buffer[.. *length]).await {
-                    self.queue = queue;
-                    return Err(err.into());
+        let retransmission_timeout = self.congestion.retransmission_timeout();
+        let mut is_lost = false;
+
+        // Packets in flight went to the old address, resending them right away
+        let migration = self.shared.migration.load(Ordering::Relaxed);
+        let migrated = migration != self.migration;
+        self.migration = migration;
+
+        for (sent_at, buffer, tag_start, length, resent) in self
+            .queues
+            .values_mut()
+            .flat_map(|queue| queue.queue.iter_mut())
+            .filter_map(|entry| {
+                match entry {
+                    PacketState::Pending {
+                        sent_at,
+                        buffer,
+                        tag_start,
+                        length,
+                        resent,
+                    } => Some((sent_at, buffer, tag_start, length, resent)),
+                    PacketState::Done => None,
                 }
+            })
+        {
+            if migrated || sent_at.elapsed() > retransmission_timeout {
+                if !is_lost && !migrated {
+                    is_lost = true;
+                    self.congestion.on_loss();
+                }
+
+                let packet = encrypt_copy(&self.shared, buffer, *tag_start, *length);
+                let sent = transport_send(&self.shared, &packet[.. *length]).await?;
                 *sent_at = Instant::now();
+                *resent = true;
+                self.shared.stats.sent(sent);
+                self.shared.stats.retransmit();
             }
         }
 
-        self.queue = queue;
-
         Ok(())
     }
 
@@ -875,28 +1504,40 @@ impl ReliableSender {
         data: &[u8],
         packet_type: u8,
     ) -> Result<(), Error> {
-        let mut must_wait = false;
+        let mut wait = None;
         loop {
-            self.handle_acks_resend(mem::replace(&mut must_wait, false))
-                .await?;
+            self.handle_acks_resend(wait.take()).await?;
 
-            if matches!(self.queue.front(), Some(PacketState::Pending { .. }))
-                && self.queue.len() >= RELIABLE_QUEUE_LENGTH as usize
+            let queue = self.queues.entry(channel).or_default();
+
+            if (matches!(queue.queue.front(), Some(PacketState::Pending { .. }))
+                && queue.queue.len() >= RELIABLE_QUEUE_LENGTH as usize)
+                || self.in_flight >= self.congestion.window()
             {
-                // Waiting list is full
-                must_wait = true;
+                // Waiting list is full or the congestion window is exhausted
+                wait = Some(self.congestion.retransmission_timeout());
                 continue;
             } else {
                 // Finally send our latest packet and add that to waiting list
-                let (buffer, length) = self.pack_data(channel, data, packet_type);
-                let result = self.shared.transport.send(&buffer[.. length]).await;
-                self.queue.push_back(PacketState::Pending {
-                    sent_at: Instant::now(),
-                    buffer,
-                    length,
-                });
-
-                result?;
+                let sequence = queue.front_sequence.wrapping_add(queue.queue.len() as u16);
+                let (buffer, tag_start, length) =
+                    self.pack_data(channel, sequence, data, packet_type);
+                let packet = encrypt_copy(&self.shared, &buffer, tag_start, length);
+                let result = transport_send(&self.shared, &packet[.. length]).await;
+                self.queues
+                    .get_mut(&channel)
+                    .unwrap()
+                    .queue
+                    .push_back(PacketState::Pending {
+                        sent_at: Instant::now(),
+                        buffer,
+                        tag_start,
+                        length,
+                        resent: false,
+                    });
+                self.in_flight += 1;
+
+                self.shared.stats.sent(result?);
 
                 return Ok(());
             }
@@ -930,10 +1571,126 @@ impl ReliableSender {
     /// Wait for all transmitted data to be delivered.
     /// Resends lost messages periodically internally.
     pub async fn wait_complete(&mut self) -> Result<(), Error> {
-        while !self.queue.is_empty() {
-            self.handle_acks_resend(true).await?;
+        while self.queues.values().any(|queue| !queue.queue.is_empty()) {
+            self.handle_acks_resend(Some(self.congestion.retransmission_timeout()))
+                .await?;
         }
 
         Ok(())
     }
+
+    /// Move the connection to a new socket bound to any local address, e.g. after the network
+    /// change, keeping the connection id and the key. The server is told the new address and
+    /// the messages in flight are sent again. Returns the new local address.
+    ///
+    /// The same happens automatically if the socket fails. Either way, the `Receiver` returns
+    /// `Error::ConnectionMigrated` once it switched to the new socket.
+    pub async fn rebind(&mut self) -> Result<SocketAddr, Error> {
+        migrate(&self.shared).await
+    }
+
+    /// Sends the rekeying request again if the answer is late,
+    /// or, if `start`, sends a new one if the current key is due.
+    async fn rekey(&mut self, start: bool) -> Result<(), Error> {
+        let key = {
+            let mut pending_rekey = self.shared.pending_rekey.lock().unwrap();
+            let now = Instant::now();
+
+            match &mut *pending_rekey {
+                Some(pending) => {
+                    if now < pending.sent_at + self.congestion.retransmission_timeout() {
+                        return Ok(());
+                    }
+
+                    pending.sent_at = now;
+                    pending.key
+                },
+                None => {
+                    if !start
+                        || !self
+                            .shared
+                            .keys
+                            .is_due(&self.shared.rekey_parameters, self.shared.stats.bytes())
+                    {
+                        return Ok(());
+                    }
+
+                    let keypair = EphemeralSecret::random(&mut OsRng);
+                    let key: Key = EncodedPoint::from(keypair.public_key())
+                        .as_ref()
+                        .try_into()
+                        .unwrap();
+
+                    *pending_rekey = Some(PendingRekey {
+                        keypair,
+                        key,
+                        sent_at: now,
+                    });
+
+                    key
+                },
+            }
+        };
+
+        let mut buffer = ZEROED_BUFFER;
+
+        let (tag_start, len) =
+            crate::write_in_buffer(&mut buffer, self.shared.id, Type::REKEY, |cursor| {
+                cursor.write_all(&key).unwrap();
+            });
+
+        crate::encode_in_buffer(&mut buffer, &self.shared.keys.current(), tag_start, len);
+
+        let sent = transport_send(&self.shared, &buffer[.. len]).await?;
+        self.shared.stats.sent(sent);
+
+        Ok(())
+    }
+
+    /// Keep the connection alive: periodically send PING packets to the server and resend lost
+    /// messages. Also replaces the connection key according to the `RekeyParameters` of
+    /// the client. Returns only on error, `Error::Timeout` means that nothing was received from the
+    /// server within the timeout.
+    ///
+    /// The future is safe to drop, so it can be raced with waiting for new messages to send.
+    /// **The `Receiver` must be polled for the keepalive to work.**
+    pub async fn keepalive(&mut self, parameters: KeepaliveParameters) -> Result<(), Error> {
+        loop {
+            if self.shared.receive_timer.elapsed() > parameters.timeout {
+                return Err(Error::Timeout);
+            }
+
+            self.rekey(true).await?;
+
+            let now = Instant::now();
+
+            let next_ping = match self.last_ping {
+                Some(last_ping) if now < last_ping + parameters.interval => {
+                    last_ping + parameters.interval
+                },
+                _ => {
+                    // The migration packet may be lost
+                    if self.shared.migration_pending.load(Ordering::Relaxed) {
+                        send_migrate(&self.shared).await?;
+                    }
+
+                    let mut buffer = ZEROED_BUFFER;
+                    send_signal(&mut buffer, &self.shared, Type::PING).await?;
+                    self.last_ping = Some(now);
+                    now + parameters.interval
+                },
+            };
+
+            let wait = next_ping
+                .saturating_duration_since(Instant::now())
+                .min(self.congestion.retransmission_timeout());
+
+            self.handle_acks_resend(Some(wait)).await?;
+        }
+    }
+
+    /// Get network statistics of the connection.
+    pub fn stats(&self) -> ConnectionStats {
+        self.shared.stats.get()
+    }
 }
@@ -16,23 +16,47 @@
 //!    with `single` feature.
 //! 3. `client` enables [`client`] functionality.
 //! 4. `server` enables [`server`] functionality.
+//! 5. `fuzzing` exposes the packet parsing for the fuzz targets in `fuzz/`.
 
 use chacha20poly1305::{
     aead::{
         rand_core::OsRng,
         AeadCore,
         AeadInPlace,
+        KeyInit,
     },
     ChaCha20Poly1305,
 };
-use integer_encoding::VarIntWriter;
+use integer_encoding::{
+    VarIntReader,
+    VarIntWriter,
+};
+use k256::{
+    ecdh::SharedSecret,
+    PublicKey,
+};
 use std::{
     io::{
         Cursor,
         Read,
+        Write,
     },
     mem,
-    time::Duration,
+    net::{
+        IpAddr,
+        SocketAddr,
+    },
+    sync::{
+        atomic::{
+            AtomicU64,
+            Ordering,
+        },
+        Mutex,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
 #[cfg(any(feature = "client", test))]
@@ -47,6 +71,7 @@ const MAX_HEADER_SIZE: usize = mem::size_of::<Id>() // sender
     + 1 // type
     + TAG_SIZE // tag
     + NONCE_SIZE // nonce
+    + mem::size_of::<u64>()
     + mem::size_of::<usize>()
     + 2
     + mem::size_of::<usize>();
@@ -56,6 +81,13 @@ const MAX_HEADER_SIZE: usize = mem::size_of::<Id>() // sender
 pub const MAX_DATA_SIZE: usize = MAX_PACKET_SIZE - MAX_HEADER_SIZE;
 /// Maximum amount of data per message.
 pub const MAX_SPLIT_DATA_SIZE: usize = MAX_SPLIT_PACKETS * MAX_DATA_SIZE;
+/// Disconnect reason sent when the connection handles are dropped without
+/// explicit `disconnect()` call.
+pub const DROP_DISCONNECT_REASON: u8 = 0;
+/// Disconnect reason sent to the peer that exceeded the protocol violation limit.
+pub const PROTOCOL_VIOLATION_DISCONNECT_REASON: u8 = 1;
+/// Version of the packet format, peers of different versions refuse to connect.
+pub const PROTOCOL_VERSION: u16 = 5;
 
 const SERVER_ID: usize = 0;
 const NEW_CONNECTION_ID: usize = 1;
@@ -64,16 +96,610 @@ const RELIABLE_QUEUE_LENGTH: u16 = 256;
 const RELIABLE_RESEND_AFTER: Duration = Duration::from_millis(1000);
 const MAX_SPLIT_PACKETS: usize = 2000;
 
+/// Set of the optional application features, one bit per feature.
+/// Both peers announce the features they support during the handshake and the connection gets
+/// the ones supported by both, so the application can change its messages without breaking
+/// the older peers.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub struct Features(pub u64);
+
+impl Features {
+    pub const NONE: Self = Self(0);
+
+    /// Checks if all the `other` features are in the set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    /// Features of the set that are not in the `other`.
+    pub const fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+}
+
+/// Reason the connection was refused during the handshake.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HandshakeError {
+    /// Peers use different protocol versions.
+    VersionMismatch { client: u16, server: u16 },
+    /// Peer does not support the features the other side requires, contains the missing ones.
+    MissingFeatures(Features),
+    /// Server does not know the session to resume, e.g. it was restarted since.
+    UnknownSession,
+}
+
+/// Keepalive parameters for the reliable senders.
+#[derive(Clone, Copy, Debug)]
+pub struct KeepaliveParameters {
+    /// How often to send PING packets to the peer.
+    pub interval: Duration,
+    /// The connection is considered dead if nothing was received from the peer for that long.
+    pub timeout: Duration,
+}
+
+impl Default for KeepaliveParameters {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Rekeying parameters of the client. The connection key is replaced by a new one, derived
+/// from another ECDH exchange, once any of the limits is reached.
+#[derive(Clone, Copy, Debug)]
+pub struct RekeyParameters {
+    /// Maximum time a key is used for.
+    pub interval: Duration,
+    /// Maximum amount of bytes sent and received with a key.
+    pub bytes: u64,
+}
+
+impl Default for RekeyParameters {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60 * 60),
+            bytes: 1 << 30,
+        }
+    }
+}
+
+/// How the sockets bound to an IPv6 address treat the IPv4 peers.
+/// Has no effect on the sockets bound to an IPv4 address.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Ipv6Mode {
+    /// Both IPv6 and IPv4 peers, the latter are seen as IPv4-mapped addresses
+    /// (`::ffff:a.b.c.d`) by the socket and reported as plain IPv4 addresses.
+    #[default]
+    DualStack,
+    /// Only IPv6 peers.
+    Only,
+    /// Whatever the system default is (`net.ipv6.bindv6only` on Linux).
+    System,
+}
+
+/// Converts the IPv4-mapped IPv6 address into the plain IPv4 one, other addresses are
+/// returned as-is. A peer then has the same address whether it came through an IPv4
+/// or a dual-stack socket.
+pub fn canonical_address(address: SocketAddr) -> SocketAddr {
+    match address {
+        SocketAddr::V6(v6) => {
+            match v6.ip().to_ipv4_mapped() {
+                Some(ip) => SocketAddr::new(IpAddr::V4(ip), v6.port()),
+                None => address,
+            }
+        },
+        SocketAddr::V4(_) => address,
+    }
+}
+
+/// Converts the canonical address into the one the socket can send to: IPv4 addresses
+/// become IPv4-mapped for the sockets bound to an IPv6 address.
+#[cfg(any(feature = "client", feature = "server", test))]
+fn socket_address(is_ipv6_socket: bool, address: SocketAddr) -> SocketAddr {
+    match address {
+        SocketAddr::V4(v4) if is_ipv6_socket => {
+            SocketAddr::new(IpAddr::V6(v4.ip().to_ipv6_mapped()), v4.port())
+        },
+        _ => address,
+    }
+}
+
+#[cfg(any(feature = "client", feature = "server", test))]
+fn bind_socket(
+    address: SocketAddr,
+    ipv6_mode: Ipv6Mode,
+) -> Result<tokio::net::UdpSocket, std::io::Error> {
+    use socket2::{
+        Domain,
+        Protocol,
+        Socket,
+    };
+
+    let socket = Socket::new(
+        Domain::for_address(address),
+        socket2::Type::DGRAM,
+        Some(Protocol::UDP),
+    )?;
+
+    if address.is_ipv6() {
+        match ipv6_mode {
+            Ipv6Mode::DualStack => socket.set_only_v6(false)?,
+            Ipv6Mode::Only => socket.set_only_v6(true)?,
+            Ipv6Mode::System => {},
+        }
+    }
+
+    socket.set_nonblocking(true)?;
+    socket.bind(&address.into())?;
+
+    tokio::net::UdpSocket::from_std(socket.into())
+}
+
+/// Congestion control parameters for the reliable senders.
+#[derive(Clone, Copy, Debug)]
+pub struct CongestionParameters {
+    /// Lower bound of the adaptive retransmission timeout.
+    pub min_retransmission_timeout: Duration,
+    /// Upper bound of the adaptive retransmission timeout.
+    pub max_retransmission_timeout: Duration,
+    /// Number of unacknowledged reliable packets allowed in flight at the connection start.
+    pub initial_window: usize,
+    /// The window never shrinks below this number of packets on packet loss.
+    pub min_window: usize,
+    /// The window never grows beyond this number of packets.
+    pub max_window: usize,
+}
+
+impl Default for CongestionParameters {
+    fn default() -> Self {
+        Self {
+            min_retransmission_timeout: Duration::from_millis(200),
+            max_retransmission_timeout: Duration::from_secs(10),
+            initial_window: 32,
+            min_window: 4,
+            max_window: 1024,
+        }
+    }
+}
+
+/// Adaptive retransmission timeout and congestion window of a reliable sender.
+struct Congestion {
+    parameters: CongestionParameters,
+    smoothed_rtt: Option<Duration>,
+    rtt_variation: Duration,
+    retransmission_timeout: Duration,
+    window: usize,
+    slow_start_threshold: usize,
+    // Acknowledgements received since the last window growth in congestion avoidance
+    window_acks: usize,
+    // Further losses are ignored until this moment, losses of the packets sent together
+    // shrink the window only once
+    recovery_until: Option<Instant>,
+}
+
+impl Congestion {
+    fn new(parameters: CongestionParameters) -> Self {
+        let window = parameters
+            .initial_window
+            .clamp(parameters.min_window, parameters.max_window);
+
+        Self {
+            parameters,
+            smoothed_rtt: None,
+            rtt_variation: Duration::ZERO,
+            retransmission_timeout: RELIABLE_RESEND_AFTER.clamp(
+                parameters.min_retransmission_timeout,
+                parameters.max_retransmission_timeout,
+            ),
+            window,
+            slow_start_threshold: parameters.max_window,
+            window_acks: 0,
+            recovery_until: None,
+        }
+    }
+
+    /// Retransmission timeout is calculated as in RFC 6298.
+    fn on_rtt_sample(&mut self, sample: Duration) {
+        let (smoothed_rtt, rtt_variation) = match self.smoothed_rtt {
+            None => (sample, sample / 2),
+            Some(smoothed_rtt) => {
+                let delta = smoothed_rtt.abs_diff(sample);
+
+                (
+                    (smoothed_rtt * 7 + sample) / 8,
+                    (self.rtt_variation * 3 + delta) / 4,
+                )
+            },
+        };
+
+        self.smoothed_rtt = Some(smoothed_rtt);
+        self.rtt_variation = rtt_variation;
+        self.retransmission_timeout = (smoothed_rtt + rtt_variation * 4).clamp(
+            self.parameters.min_retransmission_timeout,
+            self.parameters.max_retransmission_timeout,
+        );
+    }
+
+    /// Grows the window: exponentially before the first loss, linearly after.
+    fn on_ack(&mut self) {
+        if self.window < self.slow_start_threshold {
+            self.window += 1;
+        } else {
+            self.window_acks += 1;
+            if self.window_acks >= self.window {
+                self.window_acks = 0;
+                self.window += 1;
+            }
+        }
+
+        self.window = self.window.min(self.parameters.max_window);
+    }
+
+    /// Halves the window and backs off the retransmission timeout.
+    fn on_loss(&mut self) {
+        let now = Instant::now();
+
+        if matches!(self.recovery_until, Some(recovery_until) if now < recovery_until) {
+            return;
+        }
+
+        self.slow_start_threshold = (self.window / 2).max(self.parameters.min_window);
+        self.window = self.slow_start_threshold;
+        self.window_acks = 0;
+        self.retransmission_timeout =
+            (self.retransmission_timeout * 2).min(self.parameters.max_retransmission_timeout);
+        self.recovery_until = Some(now + self.retransmission_timeout);
+    }
+
+    fn retransmission_timeout(&self) -> Duration {
+        self.retransmission_timeout
+    }
+
+    fn window(&self) -> usize {
+        self.window
+    }
+}
+
+struct KeysInner {
+    // Mixed into the secret of the next key
+    secret: Secret,
+    current: ChaCha20Poly1305,
+    previous: Option<ChaCha20Poly1305>,
+    rekeyed_at: Instant,
+    rekeyed_at_bytes: u64,
+}
+
+/// Keys of the connection, the current one is replaced on rekeying.
+///
+/// The previous key is still accepted until the next rekeying, the peer may use it for
+/// the packets sent before the rekeying, including the retransmitted reliable ones.
+struct Keys(Mutex<KeysInner>);
+
+impl Keys {
+    fn new(secret: Secret) -> Self {
+        Self(Mutex::new(KeysInner {
+            secret,
+            current: ChaCha20Poly1305::new((&secret).into()),
+            previous: None,
+            rekeyed_at: Instant::now(),
+            rekeyed_at_bytes: 0,
+        }))
+    }
+
+    fn current(&self) -> ChaCha20Poly1305 {
+        self.0.lock().unwrap().current.clone()
+    }
+
+    #[cfg(any(feature = "server", test))]
+    fn previous(&self) -> Option<ChaCha20Poly1305> {
+        self.0.lock().unwrap().previous.clone()
+    }
+
+    /// Decrypts the packet with the current key or with the previous one.
+    /// Returns starting byte of the relevant data and whether the previous key was used.
+    fn decode(&self, buffer: &mut [u8], tag_start: usize) -> Result<(usize, bool), ()> {
+        let keys = self.0.lock().unwrap();
+
+        if let Ok(start) = decode_in_buffer(buffer, tag_start, &keys.current) {
+            return Ok((start, false));
+        }
+
+        match &keys.previous {
+            Some(previous) => decode_in_buffer(buffer, tag_start, previous).map(|s| (s, true)),
+            None => Err(()),
+        }
+    }
+
+    /// Checks if any of the limits of the current key is reached,
+    /// `bytes` is the total of the connection.
+    #[cfg(any(feature = "client", test))]
+    fn is_due(&self, parameters: &RekeyParameters, bytes: u64) -> bool {
+        let keys = self.0.lock().unwrap();
+
+        keys.rekeyed_at.elapsed() >= parameters.interval
+            || bytes.saturating_sub(keys.rekeyed_at_bytes) >= parameters.bytes
+    }
+
+    /// Replaces the current key with the one derived from the ECDH exchange of the rekeying
+    /// and the current secret, `bytes` is the total of the connection.
+    fn rekey(&self, shared_secret: &SharedSecret, bytes: u64) {
+        let mut keys = self.0.lock().unwrap();
+
+        let mut secret = SECRET_BUFFER;
+        shared_secret
+            .extract::<sha2::Sha256>(Some(&keys.secret))
+            .expand(&[], &mut secret)
+            .unwrap();
+
+        let current = mem::replace(&mut keys.current, ChaCha20Poly1305::new((&secret).into()));
+
+        keys.previous = Some(current);
+        keys.secret = secret;
+        keys.rekeyed_at = Instant::now();
+        keys.rekeyed_at_bytes = bytes;
+    }
+}
+
+/// Tracks time of the last authenticated packet received from the peer.
+struct ReceiveTimer {
+    start: Instant,
+    last_received: AtomicU64,
+}
+
+impl ReceiveTimer {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            last_received: AtomicU64::new(0),
+        }
+    }
+
+    fn mark(&self) {
+        let millis = self.start.elapsed().as_millis() as u64;
+        self.last_received.store(millis, Ordering::Relaxed);
+    }
+
+    fn elapsed(&self) -> Duration {
+        let last_received = Duration::from_millis(self.last_received.load(Ordering::Relaxed));
+        self.start.elapsed().saturating_sub(last_received)
+    }
+}
+
+/// Sliding window of the recently received unreliable sequences.
+/// Drops the replayed packets and the ones that fell behind the window.
+struct ReplayWindow {
+    highest: u64,
+    // Bit `n` is set if the sequence `highest - n` was received
+    received: u128,
+}
+
+impl ReplayWindow {
+    const SIZE: u64 = u128::BITS as u64;
+
+    fn new() -> Self {
+        Self {
+            highest: 0,
+            received: 0,
+        }
+    }
+
+    /// Returns `false` if the sequence was already received or is too old to tell.
+    fn accept(&mut self, sequence: u64) -> bool {
+        if sequence > self.highest {
+            let shift = sequence - self.highest;
+            self.received = if shift < Self::SIZE {
+                self.received << shift
+            } else {
+                0
+            };
+            self.received |= 1;
+            self.highest = sequence;
+            return true;
+        }
+
+        let offset = self.highest - sequence;
+
+        if offset >= Self::SIZE || self.received & (1 << offset) != 0 {
+            return false;
+        }
+
+        self.received |= 1 << offset;
+        true
+    }
+}
+
+/// Network statistics of a connection.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ConnectionStats {
+    /// Smoothed round-trip time, measured by the acknowledgements of reliable messages.
+    /// `None` if no acknowledgements were received yet.
+    pub rtt: Option<Duration>,
+    /// Number of reliable packets that were sent again, because they were considered lost.
+    pub retransmits: u64,
+    /// Number of unreliable packets received.
+    pub unreliable_received: u64,
+    /// Number of unreliable packets received, but discarded, e.g. parts of the split messages
+    /// that were never completed.
+    pub unreliable_dropped: u64,
+    /// Number of unreliable packets discarded as the duplicates of the already received ones
+    /// or as too old to tell.
+    pub unreliable_replayed: u64,
+    /// Total bytes sent to the peer, including the protocol overhead.
+    pub bytes_sent: u64,
+    /// Total bytes of the authenticated packets received from the peer, including the protocol
+    /// overhead.
+    pub bytes_received: u64,
+    /// Total packets sent to the peer, including the retransmits and the protocol ones.
+    pub packets_sent: u64,
+    /// Total authenticated packets received from the peer.
+    pub packets_received: u64,
+    /// Malformed packets received from the peer, by kind.
+    pub protocol_violations: ProtocolViolations,
+    /// Number of times the connection key was replaced.
+    pub rekeys: u64,
+    /// Number of times the client moved the connection to a new address.
+    pub migrations: u64,
+}
+
+/// Kind of the malformed packet.
+/// Only the authenticated packets are checked, so these come from the peer itself
+/// and mean it is either broken or hostile.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProtocolViolation {
+    /// Packet type is unknown or is not expected after the handshake.
+    UnexpectedType,
+    /// Packet ends before all of its header fields.
+    Truncated,
+    /// Header field value is out of its valid range.
+    OutOfRange,
+    /// Packet has bytes after the header that has no data.
+    TrailingData,
+}
+
+/// Number of the malformed packets received, by kind.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ProtocolViolations {
+    pub unexpected_type: u64,
+    pub truncated: u64,
+    pub out_of_range: u64,
+    pub trailing_data: u64,
+}
+
+impl ProtocolViolations {
+    pub fn total(&self) -> u64 {
+        self.unexpected_type + self.truncated + self.out_of_range + self.trailing_data
+    }
+}
+
+/// Collects statistics of the connection, shared between the connection halves.
+#[derive(Default)]
+struct StatsCounter {
+    // In microseconds, 0 means no samples yet
+    rtt: AtomicU64,
+    retransmits: AtomicU64,
+    unreliable_received: AtomicU64,
+    unreliable_dropped: AtomicU64,
+    unreliable_replayed: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    packets_sent: AtomicU64,
+    packets_received: AtomicU64,
+    // By `ProtocolViolation` discriminant
+    protocol_violations: [AtomicU64; 4],
+    rekeys: AtomicU64,
+    migrations: AtomicU64,
+}
+
+impl StatsCounter {
+    fn sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.packets_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn received(&self, bytes: usize) {
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        self.packets_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn retransmit(&self) {
+        self.retransmits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn unreliable_received(&self) {
+        self.unreliable_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn unreliable_dropped(&self, packets: usize) {
+        self.unreliable_dropped
+            .fetch_add(packets as u64, Ordering::Relaxed);
+    }
+
+    fn rekey(&self) {
+        self.rekeys.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn migration(&self) {
+        self.migrations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Bytes sent and received in total.
+    fn bytes(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed) + self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    fn unreliable_replayed(&self) {
+        self.unreliable_replayed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the total number of violations including this one.
+    fn violation(&self, violation: ProtocolViolation) -> u64 {
+        self.protocol_violations[violation as usize].fetch_add(1, Ordering::Relaxed);
+
+        self.protocol_violations
+            .iter()
+            .map(|counter| counter.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Only the reliable sender adds samples, so load-store is fine here.
+    fn rtt_sample(&self, sample: Duration) {
+        let sample = (sample.as_micros() as u64).max(1);
+        let rtt = match self.rtt.load(Ordering::Relaxed) {
+            0 => sample,
+            // Exponential smoothing as in RFC 6298
+            rtt => (rtt * 7 + sample) / 8,
+        };
+        self.rtt.store(rtt.max(1), Ordering::Relaxed);
+    }
+
+    fn get(&self) -> ConnectionStats {
+        let rtt = self.rtt.load(Ordering::Relaxed);
+        let violations = |violation: ProtocolViolation| {
+            self.protocol_violations[violation as usize].load(Ordering::Relaxed)
+        };
+
+        ConnectionStats {
+            rtt: (rtt != 0).then(|| Duration::from_micros(rtt)),
+            retransmits: self.retransmits.load(Ordering::Relaxed),
+            unreliable_received: self.unreliable_received.load(Ordering::Relaxed),
+            unreliable_dropped: self.unreliable_dropped.load(Ordering::Relaxed),
+            unreliable_replayed: self.unreliable_replayed.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            packets_sent: self.packets_sent.load(Ordering::Relaxed),
+            packets_received: self.packets_received.load(Ordering::Relaxed),
+            protocol_violations: ProtocolViolations {
+                unexpected_type: violations(ProtocolViolation::UnexpectedType),
+                truncated: violations(ProtocolViolation::Truncated),
+                out_of_range: violations(ProtocolViolation::OutOfRange),
+                trailing_data: violations(ProtocolViolation::TrailingData),
+            },
+            rekeys: self.rekeys.load(Ordering::Relaxed),
+            migrations: self.migrations.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(any(feature = "server", test))]
 trait AsSlice<T> {
     fn slice(&self) -> &[T];
 }
 
+#[cfg(any(feature = "server", test))]
 impl<T> AsSlice<T> for Cursor<&[T]> {
     fn slice(&self) -> &[T] {
         &self.get_ref()[.. self.position() as usize]
     }
 }
 
+#[cfg(any(feature = "server", test))]
 impl<T> AsSlice<T> for Cursor<&mut [T]> {
     fn slice(&self) -> &[T] {
         &self.get_ref()[.. self.position() as usize]
@@ -160,6 +786,13 @@ const TAG_SIZE: usize = 16;
 const TAG_BUFFER: [u8; TAG_SIZE] = [0; TAG_SIZE];
 const NONCE_SIZE: usize = 12;
 const NONCE_BUFFER: [u8; NONCE_SIZE] = [0; NONCE_SIZE];
+// Session and secret of the connection, sealed by the server
+const TOKEN_SIZE: usize = NONCE_SIZE + mem::size_of::<u64>() + mem::size_of::<Secret>() + TAG_SIZE;
+type Token = [u8; TOKEN_SIZE];
+const TOKEN_BUFFER: Token = [0; TOKEN_SIZE];
+// Random bytes of the unauthenticated ping, returned as-is
+type Echo = [u8; 8];
+const ECHO_BUFFER: Echo = [0; 8];
 
 struct Type;
 
@@ -167,25 +800,35 @@ struct Type;
 impl Type {
     const CONNECT: u8 = 0;
         // key: Key,
+        // version: u16,
+        // features: Features,
 
     const ACCEPT: u8 = 1;
         // key: Key,
         // id: Id,
+        // version: u16,
+        // features: Features, supported by both peers
+        // token: [u8; TOKEN_SIZE], resumption token, opaque for the client
 
     const ACKNOWLEDGE: u8 = 2;
         // tag: [u8; TAG_SIZE],
         // nonce: [u8; NONCE_SIZE],
         // encrypted fields:
+        // channel: Channel,
         // sequence: Sequence,
 
     const DISCONNECT: u8 = 3;
         // tag: [u8; TAG_SIZE],
         // nonce: [u8; NONCE_SIZE],
+        // encrypted fields:
+        // reason: u8,
+        // payload: &[u8],
 
     const UNRELIABLE: u8 = 4;
         // tag: [u8; TAG_SIZE],
         // nonce: [u8; NONCE_SIZE],
         // encrypted fields:
+        // sequence: u64, of the unreliable packets sent
         // channel: Channel,
         // data: &[u8],
 
@@ -193,6 +836,7 @@ impl Type {
         // tag: [u8; TAG_SIZE],
         // nonce: [u8; NONCE_SIZE],
         // encrypted fields:
+        // sequence: u64, of the unreliable packets sent
         // channel: Channel,
         // split_id: u16,
         // length: usize,
@@ -202,6 +846,7 @@ impl Type {
         // tag: [u8; TAG_SIZE],
         // nonce: [u8; NONCE_SIZE],
         // encrypted fields:
+        // sequence: u64, of the unreliable packets sent
         // channel: Channel,
         // split_id: u16,
         // count: usize,
@@ -223,9 +868,55 @@ impl Type {
         // sequence: Sequence,
         // data: &[u8],
 
+    const PING: u8 = 9;
+        // tag: [u8; TAG_SIZE],
+        // nonce: [u8; NONCE_SIZE],
+        // or, unauthenticated from NEW_CONNECTION_ID:
+        // echo: Echo,
+
+    const PONG: u8 = 10;
+        // tag: [u8; TAG_SIZE],
+        // nonce: [u8; NONCE_SIZE],
+        // or, unauthenticated answer to the one above:
+        // echo: Echo,
+
+    const REJECT: u8 = 11;
+        // version: u16, of the server
+        // reason: u8,
+        // features: Features, required by the server but missing in the client
+
+    const RESUME: u8 = 12;
+        // token: [u8; TOKEN_SIZE],
+        // tag: [u8; TAG_SIZE],
+        // nonce: [u8; NONCE_SIZE],
+        // encrypted with the secret of the resumed connection:
+        // key: Key,
+        // version: u16,
+        // features: Features,
+
+    const REKEY: u8 = 13;
+        // tag: [u8; TAG_SIZE],
+        // nonce: [u8; NONCE_SIZE],
+        // encrypted fields:
+        // key: Key, new one-time key of the sender
+
+    const MIGRATE: u8 = 14;
+        // tag: [u8; TAG_SIZE],
+        // nonce: [u8; NONCE_SIZE],
+        // encrypted fields:
+        // sequence: u64, of the client migrations, the new address is the one it came from
+
     const UNDEFINED: u8 = u8::MAX;
 }
 
+struct RejectReason;
+
+impl RejectReason {
+    const FEATURES: u8 = 1;
+    const SESSION: u8 = 2;
+    const VERSION: u8 = 0;
+}
+
 /// Returns tag start byte and total data length.
 fn write_in_buffer<F>(
     buffer: &mut [u8; MAX_PACKET_SIZE],
@@ -270,6 +961,27 @@ fn encode_in_buffer(
 }
 
 /// Returns total data length.
+/// Write and encrypt the DISCONNECT packet, payload is truncated to fit the packet.
+/// Returns the packet length.
+fn disconnect_in_buffer(
+    buffer: &mut [u8; MAX_PACKET_SIZE],
+    sender: Id,
+    cipher: &ChaCha20Poly1305,
+    reason: u8,
+    payload: &[u8],
+) -> usize {
+    let payload = &payload[.. payload.len().min(MAX_DATA_SIZE)];
+
+    let (tag_start, len) = write_in_buffer(buffer, sender, Type::DISCONNECT, |cursor| {
+        cursor.write_all(&[reason]).unwrap();
+        cursor.write_all(payload).unwrap();
+    });
+
+    encode_in_buffer(buffer, cipher, tag_start, len);
+
+    len
+}
+
 fn tag_sign_in_buffer(
     buffer: &mut [u8; MAX_PACKET_SIZE],
     cipher: &ChaCha20Poly1305,
@@ -324,32 +1036,268 @@ fn decode_in_buffer(
     Ok(encrypted_start)
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::{
-        client::{
-            self,
-            Client,
-        },
-        server::{
-            self,
-            ServerParameters,
+/// Decrypted header of a packet received after the handshake.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Header {
+    Acknowledge {
+        channel: Channel,
+        sequence: Sequence,
+    },
+    Disconnect {
+        reason: u8,
+    },
+    Unreliable {
+        sequence: u64,
+        channel: Channel,
+    },
+    UnreliableSplitStart {
+        sequence: u64,
+        channel: Channel,
+        split_id: u16,
+        expected_packets: usize,
+    },
+    UnreliableSplit {
+        sequence: u64,
+        channel: Channel,
+        split_id: u16,
+        count: usize,
+    },
+    Reliable {
+        channel: Channel,
+        sequence: Sequence,
+        is_split: bool,
+    },
+    Ping,
+    Pong,
+    Rekey {
+        key: Key,
+    },
+    Migrate {
+        sequence: u64,
+    },
+}
+
+impl Header {
+    fn unreliable_sequence(&self) -> Option<u64> {
+        match self {
+            Header::Unreliable { sequence, .. }
+            | Header::UnreliableSplitStart { sequence, .. }
+            | Header::UnreliableSplit { sequence, .. } => Some(*sequence),
+            _ => None,
+        }
+    }
+}
+
+/// Parses the decrypted part of the packet and checks the ranges of all the fields.
+/// Returns the header and the starting byte of the data after it.
+fn read_header(packet_type: u8, buffer: &[u8]) -> Result<(Header, usize), ProtocolViolation> {
+    fn read_field<T>(cursor: &mut Cursor<&[u8]>) -> Result<T, ProtocolViolation>
+    where
+        T: TryFrom<u64>,
+    {
+        let value: u64 = cursor
+            .read_varint()
+            .map_err(|_| ProtocolViolation::Truncated)?;
+
+        T::try_from(value).map_err(|_| ProtocolViolation::OutOfRange)
+    }
+
+    let mut cursor = Cursor::new(buffer);
+
+    let header = match packet_type {
+        Type::ACKNOWLEDGE => {
+            Header::Acknowledge {
+                channel: read_field(&mut cursor)?,
+                sequence: read_field(&mut cursor)?,
+            }
         },
-    };
-    use std::{
-        cell::RefCell,
-        iter,
-        net::{
-            SocketAddr,
-            UdpSocket,
+        Type::DISCONNECT => {
+            let mut reason = 0;
+            cursor
+                .read_exact(std::slice::from_mut(&mut reason))
+                .map_err(|_| ProtocolViolation::Truncated)?;
+
+            Header::Disconnect { reason }
         },
-        sync::atomic::{
-            AtomicU16,
-            AtomicU64,
-            Ordering,
+        Type::UNRELIABLE => {
+            Header::Unreliable {
+                sequence: read_field(&mut cursor)?,
+                channel: read_field(&mut cursor)?,
+            }
+        },
+        Type::UNRELIABLE_SPLIT_START => {
+            let sequence = read_field(&mut cursor)?;
+            let channel = read_field(&mut cursor)?;
+            let split_id = read_field(&mut cursor)?;
+            let expected_packets = read_field(&mut cursor)?;
+
+            // Messages that fit into one packet are never split
+            if !(2 ..= MAX_SPLIT_PACKETS).contains(&expected_packets) {
+                return Err(ProtocolViolation::OutOfRange);
+            }
+
+            Header::UnreliableSplitStart {
+                sequence,
+                channel,
+                split_id,
+                expected_packets,
+            }
+        },
+        Type::UNRELIABLE_SPLIT => {
+            let sequence = read_field(&mut cursor)?;
+            let channel = read_field(&mut cursor)?;
+            let split_id = read_field(&mut cursor)?;
+            let count = read_field(&mut cursor)?;
+
+            // The first shard comes in the UNRELIABLE_SPLIT_START
+            if !(1 .. MAX_SPLIT_PACKETS).contains(&count) {
+                return Err(ProtocolViolation::OutOfRange);
+            }
+
+            Header::UnreliableSplit {
+                sequence,
+                channel,
+                split_id,
+                count,
+            }
+        },
+        Type::RELIABLE | Type::RELIABLE_SPLIT => {
+            Header::Reliable {
+                channel: read_field(&mut cursor)?,
+                sequence: read_field(&mut cursor)?,
+                is_split: packet_type == Type::RELIABLE_SPLIT,
+            }
+        },
+        Type::PING => Header::Ping,
+        Type::PONG => Header::Pong,
+        Type::REKEY => {
+            let mut key = KEY_BUFFER;
+            cursor
+                .read_exact(&mut key)
+                .map_err(|_| ProtocolViolation::Truncated)?;
+
+            if PublicKey::from_sec1_bytes(&key).is_err() {
+                return Err(ProtocolViolation::OutOfRange);
+            }
+
+            Header::Rekey { key }
+        },
+        Type::MIGRATE => {
+            Header::Migrate {
+                sequence: read_field(&mut cursor)?,
+            }
+        },
+        _ => return Err(ProtocolViolation::UnexpectedType),
+    };
+
+    let data_start = cursor.position() as usize;
+    let data_length = buffer.len() - data_start;
+
+    match header {
+        Header::Acknowledge { .. }
+        | Header::Ping
+        | Header::Pong
+        | Header::Rekey { .. }
+        | Header::Migrate { .. }
+            if data_length > 0 =>
+        {
+            Err(ProtocolViolation::TrailingData)
+        },
+        // Senders never put more than that into one packet, the receivers rely on it
+        _ if data_length > MAX_DATA_SIZE => Err(ProtocolViolation::OutOfRange),
+        _ => Ok((header, data_start)),
+    }
+}
+
+/// Parses the packet as it comes decrypted, the first byte is the packet type.
+/// Panics if the accepted header breaks any of the guarantees the receivers rely on.
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub fn fuzz_read_header(packet: &[u8]) {
+    let Some((packet_type, buffer)) = packet.split_first() else {
+        return;
+    };
+
+    let Ok((header, data_start)) = read_header(*packet_type, buffer) else {
+        return;
+    };
+
+    let data_length = buffer.len() - data_start;
+    assert!(data_length <= MAX_DATA_SIZE);
+
+    match header {
+        Header::UnreliableSplitStart {
+            expected_packets, ..
+        } => assert!(expected_packets > 0 && expected_packets <= MAX_SPLIT_PACKETS),
+        Header::UnreliableSplit { count, .. } => assert!(count < MAX_SPLIT_PACKETS),
+        Header::Acknowledge { .. }
+        | Header::Ping
+        | Header::Pong
+        | Header::Rekey { .. }
+        | Header::Migrate { .. } => assert_eq!(data_length, 0),
+        _ => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        canonical_address,
+        client::{
+            self,
+            Client,
+        },
+        server::{
+            self,
+            ConnectFilter,
+            ServerParameters,
+        },
+        socket_address,
+        Congestion,
+        CongestionParameters,
+        Features,
+        HandshakeError,
+        Header,
+        KeepaliveParameters,
+        ProtocolViolation,
+        ProtocolViolations,
+        RekeyParameters,
+        ReplayWindow,
+        StatsCounter,
+        Type,
+        MAX_DATA_SIZE,
+        MAX_SPLIT_PACKETS,
+        NEW_CONNECTION_ID,
+        PROTOCOL_VERSION,
+        RELIABLE_RESEND_AFTER,
+        SERVER_ID,
+    };
+    use futures_lite::future;
+    use integer_encoding::{
+        VarIntReader,
+        VarIntWriter,
+    };
+    use std::{
+        cell::RefCell,
+        iter,
+        net::{
+            SocketAddr,
+            UdpSocket,
+        },
+        sync::{
+            atomic::{
+                AtomicBool,
+                AtomicU16,
+                AtomicU64,
+                Ordering,
+            },
+            Arc,
+        },
+        thread,
+        time::{
+            Duration,
+            Instant,
         },
-        thread,
-        time::Duration,
     };
     use tokio::{
         task::{
@@ -432,6 +1380,7 @@ mod tests {
 
                             assert_eq!(result.as_ref(), b"2HelloWorld2");
                             assert_eq!(channel, 1);
+                            assert!(!result.is_reliable());
                         }));
                     }
                 });
@@ -559,6 +1508,7 @@ mod tests {
 
                             assert_eq!(result.as_ref(), data.as_slice());
                             assert_eq!(channel, 1);
+                            assert!(!result.is_reliable());
                         }));
                     }
                 });
@@ -1266,6 +2216,89 @@ mod tests {
             .await;
     }
 
+    #[tokio::test]
+    async fn recv_ref_test() {
+        let _ = env_logger::try_init();
+
+        let test_num = TEST_NUM_DISPENCER.fetch_add(1, Ordering::Relaxed);
+
+        let client_port = 30000 + test_num * 10 + 1;
+        let server_port = 30000 + test_num * 10;
+
+        let task: &_ = Box::leak(Box::new(RefCell::new(None)));
+        let data: &_ = Box::leak(Box::new({
+            let data_slice = &[1, 2, 3, 4, 5];
+            iter::repeat(data_slice)
+                .take(300)
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>()
+        }));
+
+        LocalSet::new()
+            .run_until(async move {
+                task::spawn_local(async move {
+                    let mut server = ServerParameters::default()
+                        .bind(([127, 0, 0, 1], server_port))
+                        .await
+                        .expect("server socket bind");
+
+                    loop {
+                        let server::Connection {
+                            receiver: mut rx, ..
+                        } = server.accept().await.expect("connection accepted");
+
+                        *task.borrow_mut() = Some(task::spawn_local(async move {
+                            for _ in 0 .. 6 {
+                                let (channel, result) =
+                                    rx.recv_ref().await.expect("server received data");
+
+                                match channel {
+                                    0 | 1 => assert_eq!(result, data.as_slice()),
+                                    2 => assert_eq!(result, b"HelloWorld"),
+                                    _ => panic!("unexpected channel {}", channel),
+                                }
+                            }
+                        }));
+                    }
+                });
+
+                time::sleep(Duration::from_millis(5)).await;
+
+                let client = Client::bind(([127, 0, 0, 1], client_port))
+                    .await
+                    .expect("client bound");
+
+                let client::Connection {
+                    sender: mut tx,
+                    receiver: mut rx,
+                    ..
+                } = client
+                    .connect(([127, 0, 0, 1], server_port))
+                    .await
+                    .expect("client connection");
+
+                task::spawn_local(async move { while let Ok(_) = rx.recv().await {} });
+
+                for _ in 0 .. 2 {
+                    tx.send_reliable(0, data.as_ref())
+                        .await
+                        .expect("client sent data");
+
+                    tx.send_unreliable(1, data.as_ref())
+                        .await
+                        .expect("client sent data");
+
+                    tx.send_unreliable(2, b"HelloWorld")
+                        .await
+                        .expect("client sent data");
+                }
+
+                task.borrow_mut().take().unwrap().await.unwrap();
+            })
+            .await;
+    }
+
     #[tokio::test]
     async fn reliable_test_reliability() {
         let _ = env_logger::try_init();
@@ -1478,26 +2511,31 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn reliable_test_wait_complete() {
+    async fn reliable_test_channels() {
         let _ = env_logger::try_init();
 
         let test_num = TEST_NUM_DISPENCER.fetch_add(1, Ordering::Relaxed);
 
-        let amount = 1000;
-
         let client_port = 30000 + test_num * 10 + 1;
         let server_port = 30000 + test_num * 10;
 
         let client_addr = ([127, 0, 0, 1], client_port);
         let server_addr = ([127, 0, 0, 1], server_port);
 
+        // Loses the first reliable message sent by the server
         let proxy_addr = create_proxy(
             test_num,
             client_addr.into(),
             server_addr.into(),
-            |i, _addr| i % 3 != 2,
+            move |i, addr| !(addr == server_addr.into() && i == 1),
         );
 
+        let large: Vec<u8> = iter::repeat(b"HelloWorld".iter().copied())
+            .flatten()
+            .take(MAX_DATA_SIZE * 3)
+            .collect();
+        let large_client = large.clone();
+
         let task: &_ = Box::leak(Box::new(RefCell::new(None)));
         LocalSet::new()
             .run_until(async move {
@@ -1509,27 +2547,34 @@ mod tests {
 
                     loop {
                         let server::Connection {
-                            sender: tx,
-                            receiver: rx,
+                            sender: mut tx,
+                            receiver: mut rx,
                             ..
                         } = server.accept().await.expect("connection accepted");
 
+                        let large = large.clone();
+
                         *task.borrow_mut() = Some(task::spawn_local(async move {
-                            let mut tx = tx;
-                            let mut rx = rx;
-                            for i in 0 .. amount {
-                                tx.send_reliable(0, format!("HelloWorld{}", i).as_bytes())
-                                    .await
-                                    .expect("server sent packet");
-                            }
+                            tx.send_reliable(0, b"Blocked")
+                                .await
+                                .expect("server sent packet");
+                            tx.send_reliable(1, &large)
+                                .await
+                                .expect("server sent packet");
+                            tx.send_reliable(1, b"Free")
+                                .await
+                                .expect("server sent packet");
 
                             tx.wait_complete().await.expect("waiting for delivery");
 
-                            for i in 0 .. amount {
-                                let (channel, result) =
-                                    rx.recv().await.expect("client message receive");
-                                assert_eq!(result.as_ref(), format!("HelloWorld{}", i).as_bytes());
-                                assert_eq!(channel, 0);
+                            for (channel, expected) in
+                                [(2, b"Hello".as_slice()), (3, &large), (2, b"World")]
+                            {
+                                let (result_channel, result) =
+                                    rx.recv().await.expect("server message receive");
+                                assert_eq!(result_channel, channel);
+                                assert_eq!(result.as_ref(), expected);
+                                assert!(result.is_reliable());
                             }
 
                             task::spawn_local(async move { while let Ok(_) = rx.recv().await {} });
@@ -1547,19 +2592,26 @@ mod tests {
                     ..
                 } = client.connect(proxy_addr).await.expect("client connection");
 
-                for i in 0 .. amount {
-                    let (channel, result) = rx.recv().await.expect("client message receive");
-                    assert_eq!(result, format!("HelloWorld{}", i).as_bytes());
-                    assert_eq!(channel, 0);
+                // Channel 1 is not blocked by the lost message in channel 0
+                for (channel, expected) in
+                    [(1, large_client.as_slice()), (1, b"Free"), (0, b"Blocked")]
+                {
+                    let (result_channel, result) = rx.recv().await.expect("client message receive");
+                    assert_eq!(result_channel, channel);
+                    assert_eq!(result, expected);
                 }
 
                 task::spawn_local(async move { while let Ok(_) = rx.recv().await {} });
 
-                for i in 0 .. amount {
-                    tx.send_reliable(0, format!("HelloWorld{}", i).as_bytes())
-                        .await
-                        .expect("server sent packet");
-                }
+                tx.send_reliable(2, b"Hello")
+                    .await
+                    .expect("client sent packet");
+                tx.send_reliable(3, &large_client)
+                    .await
+                    .expect("client sent packet");
+                tx.send_reliable(2, b"World")
+                    .await
+                    .expect("client sent packet");
 
                 tx.wait_complete().await.expect("waiting for delivery");
 
@@ -1568,6 +2620,1209 @@ mod tests {
             .await;
     }
 
+    #[test]
+    fn read_header_test() {
+        fn packet(fields: &[u64], data_length: usize) -> Vec<u8> {
+            let mut packet = Vec::new();
+            for field in fields {
+                packet.write_varint(*field).unwrap();
+            }
+            packet.resize(packet.len() + data_length, 7);
+            packet
+        }
+
+        let split_start = packet(&[9, 3, 1, 4], MAX_DATA_SIZE);
+        assert_eq!(
+            crate::read_header(Type::UNRELIABLE_SPLIT_START, &split_start),
+            Ok((
+                Header::UnreliableSplitStart {
+                    sequence: 9,
+                    channel: 3,
+                    split_id: 1,
+                    expected_packets: 4,
+                },
+                4
+            ))
+        );
+
+        assert_eq!(
+            crate::read_header(Type::RELIABLE_SPLIT, &packet(&[1, 300], 10)),
+            Ok((
+                Header::Reliable {
+                    channel: 1,
+                    sequence: 300,
+                    is_split: true,
+                },
+                3
+            ))
+        );
+
+        assert_eq!(crate::read_header(Type::PING, &[]), Ok((Header::Ping, 0)));
+
+        assert_eq!(
+            crate::read_header(Type::MIGRATE, &packet(&[2], 0)),
+            Ok((Header::Migrate { sequence: 2 }, 1))
+        );
+
+        let violations = [
+            (
+                Type::UNRELIABLE,
+                packet(&[], 0),
+                ProtocolViolation::Truncated,
+            ),
+            (
+                Type::RELIABLE,
+                packet(&[0], 0),
+                ProtocolViolation::Truncated,
+            ),
+            (
+                Type::DISCONNECT,
+                packet(&[], 0),
+                ProtocolViolation::Truncated,
+            ),
+            (
+                Type::RELIABLE,
+                packet(&[0, u16::MAX as u64 + 1], 1),
+                ProtocolViolation::OutOfRange,
+            ),
+            (
+                Type::UNRELIABLE_SPLIT_START,
+                packet(&[1, 0, 1, 0], 1),
+                ProtocolViolation::OutOfRange,
+            ),
+            (
+                Type::UNRELIABLE_SPLIT_START,
+                packet(&[1, 0, 1, MAX_SPLIT_PACKETS as u64 + 1], 1),
+                ProtocolViolation::OutOfRange,
+            ),
+            (
+                Type::UNRELIABLE_SPLIT_START,
+                packet(&[1, 0, 1, 2], MAX_DATA_SIZE + 1),
+                ProtocolViolation::OutOfRange,
+            ),
+            (
+                Type::UNRELIABLE_SPLIT,
+                packet(&[1, 0, 1, 0], 1),
+                ProtocolViolation::OutOfRange,
+            ),
+            (
+                Type::UNRELIABLE,
+                packet(&[1, 0], MAX_DATA_SIZE + 1),
+                ProtocolViolation::OutOfRange,
+            ),
+            (Type::PONG, packet(&[], 1), ProtocolViolation::TrailingData),
+            (
+                Type::ACKNOWLEDGE,
+                packet(&[0, 1], 1),
+                ProtocolViolation::TrailingData,
+            ),
+            (
+                Type::MIGRATE,
+                packet(&[2], 1),
+                ProtocolViolation::TrailingData,
+            ),
+            (
+                Type::CONNECT,
+                packet(&[], 0),
+                ProtocolViolation::UnexpectedType,
+            ),
+            (
+                Type::UNDEFINED,
+                packet(&[], 0),
+                ProtocolViolation::UnexpectedType,
+            ),
+        ];
+
+        let stats = StatsCounter::default();
+
+        for (count, (packet_type, packet, violation)) in violations.into_iter().enumerate() {
+            assert_eq!(crate::read_header(packet_type, &packet), Err(violation));
+            assert_eq!(stats.violation(violation), count as u64 + 1);
+        }
+
+        assert_eq!(
+            stats.get().protocol_violations,
+            ProtocolViolations {
+                unexpected_type: 2,
+                truncated: 3,
+                out_of_range: 6,
+                trailing_data: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn replay_window_test() {
+        let mut window = ReplayWindow::new();
+
+        assert!(window.accept(1));
+        assert!(window.accept(3));
+        assert!(!window.accept(1));
+        assert!(!window.accept(3));
+        // Reordered, but not seen yet
+        assert!(window.accept(2));
+        assert!(!window.accept(2));
+
+        assert!(window.accept(4 + ReplayWindow::SIZE));
+        // Never received, but too old to tell
+        assert!(!window.accept(4));
+        assert!(window.accept(5));
+        assert!(!window.accept(5));
+
+        assert!(window.accept(1000));
+        assert!(!window.accept(5));
+        assert!(!window.accept(1000));
+        assert!(window.accept(999));
+    }
+
+    #[test]
+    fn congestion_test() {
+        let parameters = CongestionParameters {
+            min_retransmission_timeout: Duration::from_millis(100),
+            max_retransmission_timeout: Duration::from_secs(2),
+            initial_window: 8,
+            min_window: 2,
+            max_window: 16,
+        };
+
+        let mut congestion = Congestion::new(parameters);
+
+        assert_eq!(congestion.window(), 8);
+        assert_eq!(congestion.retransmission_timeout(), RELIABLE_RESEND_AFTER);
+
+        congestion.on_rtt_sample(Duration::from_millis(100));
+        // 100ms + 4 * 50ms
+        assert_eq!(
+            congestion.retransmission_timeout(),
+            Duration::from_millis(300)
+        );
+
+        congestion.on_rtt_sample(Duration::from_millis(100));
+        // 100ms + 4 * 37.5ms
+        assert_eq!(
+            congestion.retransmission_timeout(),
+            Duration::from_millis(250)
+        );
+
+        congestion.on_rtt_sample(Duration::from_millis(1));
+        assert!(congestion.retransmission_timeout() >= parameters.min_retransmission_timeout);
+
+        // Slow start
+        for _ in 0 .. 100 {
+            congestion.on_ack();
+        }
+        assert_eq!(congestion.window(), 16);
+
+        let retransmission_timeout = congestion.retransmission_timeout();
+        congestion.on_loss();
+        assert_eq!(congestion.window(), 8);
+        assert_eq!(
+            congestion.retransmission_timeout(),
+            retransmission_timeout * 2
+        );
+
+        // Ignored within the recovery period
+        congestion.on_loss();
+        assert_eq!(congestion.window(), 8);
+
+        congestion.recovery_until = None;
+        congestion.on_loss();
+        assert_eq!(congestion.window(), 4);
+
+        for _ in 0 .. 10 {
+            congestion.recovery_until = None;
+            congestion.on_loss();
+        }
+        assert_eq!(congestion.window(), 2);
+        assert_eq!(
+            congestion.retransmission_timeout(),
+            parameters.max_retransmission_timeout
+        );
+
+        // Congestion avoidance: one packet per window of acknowledgements
+        for _ in 0 .. 2 {
+            congestion.on_ack();
+        }
+        assert_eq!(congestion.window(), 3);
+        for _ in 0 .. 2 {
+            congestion.on_ack();
+        }
+        assert_eq!(congestion.window(), 3);
+        congestion.on_ack();
+        assert_eq!(congestion.window(), 4);
+    }
+
+    #[tokio::test]
+    async fn keepalive_test() {
+        let _ = env_logger::try_init();
+
+        let test_num = TEST_NUM_DISPENCER.fetch_add(1, Ordering::Relaxed);
+
+        let client_port = 30000 + test_num * 10 + 1;
+        let server_port = 30000 + test_num * 10;
+
+        let client_addr = ([127, 0, 0, 1], client_port);
+        let server_addr = ([127, 0, 0, 1], server_port);
+
+        let blocked = Arc::new(AtomicBool::new(false));
+        let blocked_proxy = blocked.clone();
+
+        // Loses the first message sent by the server, and everything once blocked
+        let proxy_addr = create_proxy(
+            test_num,
+            client_addr.into(),
+            server_addr.into(),
+            move |i, addr| {
+                !blocked_proxy.load(Ordering::Relaxed) && !(addr == server_addr.into() && i == 1)
+            },
+        );
+
+        let parameters = KeepaliveParameters {
+            interval: Duration::from_millis(100),
+            timeout: Duration::from_millis(500),
+        };
+
+        let task: &_ = Box::leak(Box::new(RefCell::new(None)));
+        LocalSet::new()
+            .run_until(async move {
+                task::spawn_local(async move {
+                    let mut server = ServerParameters::default()
+                        .bind(server_addr)
+                        .await
+                        .expect("server socket bind");
+
+                    loop {
+                        let server::Connection {
+                            sender: mut tx,
+                            receiver: mut rx,
+                            ..
+                        } = server.accept().await.expect("connection accepted");
+
+                        *task.borrow_mut() = Some(task::spawn_local(async move {
+                            tx.send_reliable(0, b"HelloWorld")
+                                .await
+                                .expect("server sent packet");
+
+                            let start = Instant::now();
+
+                            let result = future::or(
+                                async {
+                                    while let Ok(_) = rx.recv().await {}
+                                    future::pending().await
+                                },
+                                tx.keepalive(parameters),
+                            )
+                            .await;
+
+                            assert!(matches!(result, Err(server::Error::Timeout)));
+                            assert!(start.elapsed() > Duration::from_secs(1));
+                        }));
+                    }
+                });
+
+                time::sleep(Duration::from_millis(5)).await;
+
+                let client = Client::bind(client_addr).await.expect("client bound");
+
+                let client::Connection {
+                    sender: _tx,
+                    receiver: mut rx,
+                    ..
+                } = client.connect(proxy_addr).await.expect("client connection");
+
+                // Lost message must be resent by the keepalive
+                let (channel, result) = rx.recv().await.expect("client message receive");
+                assert_eq!(result, b"HelloWorld");
+                assert_eq!(channel, 0);
+
+                task::spawn_local(async move { while let Ok(_) = rx.recv().await {} });
+
+                // The connection must stay alive while the client answers pings
+                time::sleep(Duration::from_secs(1)).await;
+
+                blocked.store(true, Ordering::Relaxed);
+
+                task.borrow_mut().take().unwrap().await.unwrap();
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn rekey_test() {
+        let _ = env_logger::try_init();
+
+        let test_num = TEST_NUM_DISPENCER.fetch_add(1, Ordering::Relaxed);
+
+        let amount = 40;
+
+        let client_port = 30000 + test_num * 10 + 1;
+        let server_port = 30000 + test_num * 10;
+
+        let client_addr = ([127, 0, 0, 1], client_port);
+        let server_addr = ([127, 0, 0, 1], server_port);
+
+        // Loses some of the packets both ways, including the rekeying ones
+        let proxy_addr = create_proxy(
+            test_num,
+            client_addr.into(),
+            server_addr.into(),
+            move |i, _| i % 5 != 2,
+        );
+
+        let parameters = KeepaliveParameters {
+            interval: Duration::from_millis(10),
+            timeout: Duration::from_secs(10),
+        };
+
+        let task: &_ = Box::leak(Box::new(RefCell::new(None)));
+        LocalSet::new()
+            .run_until(async move {
+                task::spawn_local(async move {
+                    let mut server = ServerParameters::default()
+                        .bind(server_addr)
+                        .await
+                        .expect("server socket bind");
+
+                    loop {
+                        let server::Connection {
+                            sender: _tx,
+                            receiver: mut rx,
+                            ..
+                        } = server.accept().await.expect("connection accepted");
+
+                        *task.borrow_mut() = Some(task::spawn_local(async move {
+                            for i in 0 .. amount {
+                                let (_, result) = rx.recv().await.expect("server message receive");
+                                assert_eq!(result.as_ref(), format!("HelloWorld{}", i).as_bytes());
+                            }
+
+                            assert!(rx.stats().rekeys >= 2);
+                        }));
+                    }
+                });
+
+                time::sleep(Duration::from_millis(5)).await;
+
+                let client::Connection {
+                    sender: mut tx,
+                    receiver: mut rx,
+                    ..
+                } = client::ClientParameters {
+                    rekey: RekeyParameters {
+                        interval: Duration::from_millis(50),
+                        bytes: u64::MAX,
+                    },
+                    ..Default::default()
+                }
+                .bind(client_addr)
+                .await
+                .expect("client bound")
+                .connect(proxy_addr)
+                .await
+                .expect("client connection");
+
+                task::spawn_local(async move { while let Ok(_) = rx.recv().await {} });
+
+                for i in 0 .. amount {
+                    tx.send_reliable(0, format!("HelloWorld{}", i).as_bytes())
+                        .await
+                        .expect("client sent packet");
+
+                    let _ = future::or(
+                        async {
+                            time::sleep(Duration::from_millis(20)).await;
+                            Ok(())
+                        },
+                        tx.keepalive(parameters),
+                    )
+                    .await;
+                }
+
+                // The server may drop the connection before the last acknowledgment arrives,
+                // the lost messages are still resent until the server has them all
+                let server_task = task.borrow_mut().take().unwrap();
+                future::or(async { server_task.await.unwrap() }, async {
+                    let _ = tx.keepalive(parameters).await;
+                    future::pending().await
+                })
+                .await;
+
+                assert!(tx.stats().rekeys >= 2);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn stats_test() {
+        let _ = env_logger::try_init();
+
+        let test_num = TEST_NUM_DISPENCER.fetch_add(1, Ordering::Relaxed);
+
+        let amount = 10;
+
+        let client_port = 30000 + test_num * 10 + 1;
+        let server_port = 30000 + test_num * 10;
+
+        let client_addr = ([127, 0, 0, 1], client_port);
+        let server_addr = ([127, 0, 0, 1], server_port);
+
+        // Loses the first reliable message sent by the server
+        let proxy_addr = create_proxy(
+            test_num,
+            client_addr.into(),
+            server_addr.into(),
+            move |i, addr| !(addr == server_addr.into() && i == 1),
+        );
+
+        let task: &_ = Box::leak(Box::new(RefCell::new(None)));
+        LocalSet::new()
+            .run_until(async move {
+                task::spawn_local(async move {
+                    let mut server = ServerParameters::default()
+                        .bind(server_addr)
+                        .await
+                        .expect("server socket bind");
+
+                    loop {
+                        let server::Connection {
+                            sender: mut tx,
+                            receiver: mut rx,
+                            ..
+                        } = server.accept().await.expect("connection accepted");
+
+                        *task.borrow_mut() = Some(task::spawn_local(async move {
+                            tx.send_reliable(0, b"Hello")
+                                .await
+                                .expect("server sent packet");
+                            tx.wait_complete().await.expect("waiting for delivery");
+
+                            // The only packet sent was lost, no RTT samples
+                            assert!(tx.stats().rtt.is_none());
+
+                            tx.send_reliable(0, b"World")
+                                .await
+                                .expect("server sent packet");
+                            tx.wait_complete().await.expect("waiting for delivery");
+
+                            for _ in 0 .. amount {
+                                rx.recv().await.expect("server message receive");
+                            }
+
+                            let stats = tx.stats();
+                            assert!(stats.rtt.is_some());
+                            assert!(stats.retransmits >= 1);
+                            assert!(stats.bytes_sent > 0);
+                            assert!(stats.bytes_received > 0);
+                            assert_eq!(stats.unreliable_received, amount);
+                            assert_eq!(stats.unreliable_dropped, 0);
+                            assert!(stats.packets_received >= amount);
+                            assert!(stats.packets_sent >= 2);
+                        }));
+                    }
+                });
+
+                time::sleep(Duration::from_millis(5)).await;
+
+                let client = Client::bind(client_addr).await.expect("client bound");
+
+                let client::Connection {
+                    sender: mut tx,
+                    receiver: mut rx,
+                    ..
+                } = client.connect(proxy_addr).await.expect("client connection");
+
+                for expected in [b"Hello", b"World"] {
+                    let (_, result) = rx.recv().await.expect("client message receive");
+                    assert_eq!(result, expected);
+                }
+
+                let stats = rx.stats();
+                assert!(stats.rtt.is_none());
+                assert_eq!(stats.retransmits, 0);
+                assert!(stats.bytes_sent > 0);
+                assert!(stats.bytes_received > 0);
+
+                task::spawn_local(async move { while let Ok(_) = rx.recv().await {} });
+
+                for _ in 0 .. amount {
+                    tx.send_unreliable(0, b"HelloWorld")
+                        .await
+                        .expect("client sent packet");
+                }
+
+                task.borrow_mut().take().unwrap().await.unwrap();
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn reliable_test_wait_complete() {
+        let _ = env_logger::try_init();
+
+        let test_num = TEST_NUM_DISPENCER.fetch_add(1, Ordering::Relaxed);
+
+        let amount = 1000;
+
+        let client_port = 30000 + test_num * 10 + 1;
+        let server_port = 30000 + test_num * 10;
+
+        let client_addr = ([127, 0, 0, 1], client_port);
+        let server_addr = ([127, 0, 0, 1], server_port);
+
+        let proxy_addr = create_proxy(
+            test_num,
+            client_addr.into(),
+            server_addr.into(),
+            |i, _addr| i % 3 != 2,
+        );
+
+        let task: &_ = Box::leak(Box::new(RefCell::new(None)));
+        LocalSet::new()
+            .run_until(async move {
+                task::spawn_local(async move {
+                    let mut server = ServerParameters::default()
+                        .bind(server_addr)
+                        .await
+                        .expect("server socket bind");
+
+                    loop {
+                        let server::Connection {
+                            sender: tx,
+                            receiver: rx,
+                            ..
+                        } = server.accept().await.expect("connection accepted");
+
+                        *task.borrow_mut() = Some(task::spawn_local(async move {
+                            let mut tx = tx;
+                            let mut rx = rx;
+                            for i in 0 .. amount {
+                                tx.send_reliable(0, format!("HelloWorld{}", i).as_bytes())
+                                    .await
+                                    .expect("server sent packet");
+                            }
+
+                            tx.wait_complete().await.expect("waiting for delivery");
+
+                            for i in 0 .. amount {
+                                let (channel, result) =
+                                    rx.recv().await.expect("client message receive");
+                                assert_eq!(result.as_ref(), format!("HelloWorld{}", i).as_bytes());
+                                assert_eq!(channel, 0);
+                            }
+
+                            task::spawn_local(async move { while let Ok(_) = rx.recv().await {} });
+                        }));
+                    }
+                });
+
+                time::sleep(Duration::from_millis(5)).await;
+
+                let client = Client::bind(client_addr).await.expect("client bound");
+
+                let client::Connection {
+                    sender: mut tx,
+                    receiver: mut rx,
+                    ..
+                } = client.connect(proxy_addr).await.expect("client connection");
+
+                for i in 0 .. amount {
+                    let (channel, result) = rx.recv().await.expect("client message receive");
+                    assert_eq!(result, format!("HelloWorld{}", i).as_bytes());
+                    assert_eq!(channel, 0);
+                }
+
+                task::spawn_local(async move { while let Ok(_) = rx.recv().await {} });
+
+                for i in 0 .. amount {
+                    tx.send_reliable(0, format!("HelloWorld{}", i).as_bytes())
+                        .await
+                        .expect("server sent packet");
+                }
+
+                tx.wait_complete().await.expect("waiting for delivery");
+
+                task.borrow_mut().take().unwrap().await.unwrap();
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn disconnect_test() {
+        let _ = env_logger::try_init();
+
+        let test_num = TEST_NUM_DISPENCER.fetch_add(1, Ordering::Relaxed);
+
+        let client_port = 30000 + test_num * 10 + 1;
+        let server_port = 30000 + test_num * 10;
+
+        LocalSet::new()
+            .run_until(async move {
+                task::spawn_local(async move {
+                    let mut server = ServerParameters::default()
+                        .bind(([127, 0, 0, 1], server_port))
+                        .await
+                        .expect("server socket bind");
+
+                    let server::Connection {
+                        sender: tx,
+                        receiver: mut rx,
+                        ..
+                    } = server.accept().await.expect("connection accepted");
+
+                    task::spawn_local(async move {
+                        match rx.recv().await {
+                            Err(server::Error::Disconnect { reason, payload }) => {
+                                assert_eq!(reason, 3);
+                                assert_eq!(payload, b"Bye");
+                            },
+                            _ => panic!("client disconnect expected"),
+                        }
+
+                        tx.disconnect(7, b"Server shutting down")
+                            .await
+                            .expect("server disconnected");
+
+                        // Keep the receiver alive, so the drop disconnect is not sent
+                        future::pending::<()>().await;
+                    });
+
+                    loop {
+                        let _ = server.accept().await.expect("connection accepted");
+                    }
+                });
+
+                time::sleep(Duration::from_millis(5)).await;
+
+                let client = Client::bind(([127, 0, 0, 1], client_port))
+                    .await
+                    .expect("client bound");
+
+                let client::Connection {
+                    sender: tx,
+                    receiver: mut rx,
+                    ..
+                } = client
+                    .connect(([127, 0, 0, 1], server_port))
+                    .await
+                    .expect("client connection");
+
+                tx.disconnect(3, b"Bye").await.expect("client disconnected");
+
+                match rx.recv().await {
+                    Err(client::Error::Disconnect { reason, payload }) => {
+                        assert_eq!(reason, 7);
+                        assert_eq!(payload, b"Server shutting down");
+                    },
+                    _ => panic!("server disconnect expected"),
+                }
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn connect_filter_test() {
+        let _ = env_logger::try_init();
+
+        let test_num = TEST_NUM_DISPENCER.fetch_add(1, Ordering::Relaxed);
+
+        let banned_port = 30000 + test_num * 10 + 1;
+        let client_port = 30000 + test_num * 10 + 3;
+        let server_port = 30000 + test_num * 10;
+
+        LocalSet::new()
+            .run_until(async move {
+                task::spawn_local(async move {
+                    let mut server = ServerParameters::default()
+                        .with_connect_filter(ConnectFilter::new(move |address: SocketAddr, _| {
+                            async move { address.port() != banned_port }
+                        }))
+                        .bind(([127, 0, 0, 1], server_port))
+                        .await
+                        .expect("server socket bind");
+
+                    let server::Connection { mut sender, .. } =
+                        server.accept().await.expect("connection accepted");
+
+                    task::spawn_local(async move {
+                        sender
+                            .send_reliable(0, b"Welcome")
+                            .await
+                            .expect("server sent packet");
+                        sender.keepalive(KeepaliveParameters::default()).await
+                    });
+
+                    loop {
+                        let _ = server.accept().await.expect("connection accepted");
+                    }
+                });
+
+                time::sleep(Duration::from_millis(5)).await;
+
+                let banned = Client::bind(([127, 0, 0, 1], banned_port))
+                    .await
+                    .expect("client bound");
+
+                let rejected = time::timeout(
+                    Duration::from_millis(500),
+                    banned.connect(([127, 0, 0, 1], server_port)),
+                )
+                .await;
+
+                assert!(rejected.is_err());
+
+                let client = Client::bind(([127, 0, 0, 1], client_port))
+                    .await
+                    .expect("client bound");
+
+                let client::Connection { mut receiver, .. } = client
+                    .connect(([127, 0, 0, 1], server_port))
+                    .await
+                    .expect("client connection");
+
+                let (channel, result) = receiver.recv().await.expect("client message receive");
+
+                assert_eq!(result, b"Welcome");
+                assert_eq!(channel, 0);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn dual_stack_test() {
+        let _ = env_logger::try_init();
+
+        let test_num = TEST_NUM_DISPENCER.fetch_add(1, Ordering::Relaxed);
+
+        let server_port = 30000 + test_num * 10;
+        let v4_client_addr: SocketAddr = ([127, 0, 0, 1], 30000 + test_num * 10 + 1).into();
+        let v6_client_addr: SocketAddr =
+            ([0, 0, 0, 0, 0, 0, 0, 1], 30000 + test_num * 10 + 2).into();
+
+        LocalSet::new()
+            .run_until(async move {
+                task::spawn_local(async move {
+                    // IPv4 clients must be seen with their plain addresses
+                    let mut server = ServerParameters::default()
+                        .with_connect_filter(ConnectFilter::new(move |address: SocketAddr, _| {
+                            async move { address == v4_client_addr || address == v6_client_addr }
+                        }))
+                        .bind(([0, 0, 0, 0, 0, 0, 0, 0], server_port))
+                        .await
+                        .expect("server socket bind");
+
+                    loop {
+                        let server::Connection { mut sender, .. } =
+                            server.accept().await.expect("connection accepted");
+
+                        task::spawn_local(async move {
+                            sender
+                                .send_reliable(0, b"Welcome")
+                                .await
+                                .expect("server sent packet");
+                            sender.keepalive(KeepaliveParameters::default()).await
+                        });
+                    }
+                });
+
+                time::sleep(Duration::from_millis(5)).await;
+
+                for (client_addr, server_addr) in [
+                    (
+                        v4_client_addr,
+                        SocketAddr::from(([127, 0, 0, 1], server_port)),
+                    ),
+                    (
+                        v6_client_addr,
+                        SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], server_port)),
+                    ),
+                ] {
+                    let client = Client::bind(client_addr).await.expect("client bound");
+
+                    let client::Connection { mut receiver, .. } =
+                        time::timeout(Duration::from_secs(1), client.connect(server_addr))
+                            .await
+                            .expect("connection accepted in time")
+                            .expect("client connection");
+
+                    let (channel, result) = receiver.recv().await.expect("client message receive");
+
+                    assert_eq!(result, b"Welcome");
+                    assert_eq!(channel, 0);
+                }
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn migration_test() {
+        let _ = env_logger::try_init();
+
+        let test_num = TEST_NUM_DISPENCER.fetch_add(1, Ordering::Relaxed);
+
+        let server_port = 30000 + test_num * 10;
+        let client_port = 30000 + test_num * 10 + 1;
+
+        let task: &_ = Box::leak(Box::new(RefCell::new(None)));
+        LocalSet::new()
+            .run_until(async move {
+                task::spawn_local(async move {
+                    let mut server = ServerParameters::default()
+                        .bind(([127, 0, 0, 1], server_port))
+                        .await
+                        .expect("server socket bind");
+
+                    loop {
+                        let server::Connection {
+                            sender: mut tx,
+                            receiver: mut rx,
+                            ..
+                        } = server.accept().await.expect("connection accepted");
+
+                        // Echoes the messages back
+                        *task.borrow_mut() = Some(task::spawn_local(async move {
+                            for _ in 0 .. 2 {
+                                let (channel, result) =
+                                    rx.recv().await.expect("server message receive");
+                                tx.send_reliable(channel, result.as_ref())
+                                    .await
+                                    .expect("server sent packet");
+                            }
+
+                            assert_eq!(rx.stats().migrations, 1);
+                        }));
+                    }
+                });
+
+                time::sleep(Duration::from_millis(5)).await;
+
+                let client::Connection {
+                    sender: mut tx,
+                    receiver: mut rx,
+                    ..
+                } = Client::bind(([127, 0, 0, 1], client_port))
+                    .await
+                    .expect("client bound")
+                    .connect(([127, 0, 0, 1], server_port))
+                    .await
+                    .expect("client connection");
+
+                tx.send_reliable(0, b"Before")
+                    .await
+                    .expect("client sent packet");
+
+                let (_, result) = rx.recv().await.expect("client message receive");
+                assert_eq!(result, b"Before");
+
+                let address = tx.rebind().await.expect("client rebound");
+                assert_ne!(address.port(), client_port);
+
+                match rx.recv().await {
+                    Err(client::Error::ConnectionMigrated { address: migrated }) => {
+                        assert_eq!(migrated, address)
+                    },
+                    other => panic!("expected migration, got {:?}", other),
+                }
+
+                // Same connection continues from the new address
+                tx.send_reliable(0, b"After")
+                    .await
+                    .expect("client sent packet");
+
+                let (_, result) = rx.recv().await.expect("client message receive");
+                assert_eq!(result, b"After");
+
+                assert_eq!(tx.stats().migrations, 1);
+
+                task.borrow_mut().take().unwrap().await.unwrap();
+            })
+            .await;
+    }
+
+    #[test]
+    fn canonical_address_test() {
+        let mapped: SocketAddr = "[::ffff:192.168.0.1]:12000".parse().unwrap();
+        let plain: SocketAddr = "192.168.0.1:12000".parse().unwrap();
+        let v6: SocketAddr = "[2001:db8::1]:12000".parse().unwrap();
+
+        assert_eq!(canonical_address(mapped), plain);
+        assert_eq!(canonical_address(plain), plain);
+        assert_eq!(canonical_address(v6), v6);
+
+        assert_eq!(socket_address(true, plain), mapped);
+        assert_eq!(socket_address(false, plain), plain);
+        assert_eq!(socket_address(true, v6), v6);
+    }
+
+    #[tokio::test]
+    async fn resume_test() {
+        let _ = env_logger::try_init();
+
+        let test_num = TEST_NUM_DISPENCER.fetch_add(1, Ordering::Relaxed);
+
+        let server_port = 30000 + test_num * 10;
+        let other_server_port = 30000 + test_num * 10 + 1;
+        let client_port = 30000 + test_num * 10 + 2;
+        let resumed_port = 30000 + test_num * 10 + 3;
+        let unknown_port = 30000 + test_num * 10 + 4;
+
+        LocalSet::new()
+            .run_until(async move {
+                task::spawn_local(async move {
+                    let mut server = ServerParameters::default()
+                        .bind(([127, 0, 0, 1], server_port))
+                        .await
+                        .expect("server socket bind");
+
+                    let first = server.accept().await.expect("connection accepted");
+                    assert!(!first.resumed);
+
+                    let server::Connection {
+                        mut sender,
+                        session,
+                        resumed,
+                        ..
+                    } = server.accept().await.expect("connection resumed");
+
+                    assert!(resumed);
+                    assert_eq!(session, first.session);
+
+                    task::spawn_local(async move {
+                        sender
+                            .send_reliable(0, b"Welcome back")
+                            .await
+                            .expect("server sent packet");
+                        sender.keepalive(KeepaliveParameters::default()).await
+                    });
+
+                    loop {
+                        let _ = server.accept().await.expect("connection accepted");
+                    }
+                });
+
+                task::spawn_local(async move {
+                    let mut server = ServerParameters::default()
+                        .bind(([127, 0, 0, 1], other_server_port))
+                        .await
+                        .expect("server socket bind");
+
+                    loop {
+                        let _ = server.accept().await.expect("connection accepted");
+                    }
+                });
+
+                time::sleep(Duration::from_millis(5)).await;
+
+                let client::Connection { resumption, .. } =
+                    Client::bind(([127, 0, 0, 1], client_port))
+                        .await
+                        .expect("client bound")
+                        .connect(([127, 0, 0, 1], server_port))
+                        .await
+                        .expect("client connection");
+
+                let unknown = Client::bind(([127, 0, 0, 1], unknown_port))
+                    .await
+                    .expect("client bound")
+                    .resume(([127, 0, 0, 1], other_server_port), &resumption)
+                    .await;
+
+                assert!(matches!(
+                    unknown,
+                    Err(client::Error::Handshake(HandshakeError::UnknownSession))
+                ));
+
+                let client::Connection { mut receiver, .. } =
+                    Client::bind(([127, 0, 0, 1], resumed_port))
+                        .await
+                        .expect("client bound")
+                        .resume(([127, 0, 0, 1], server_port), &resumption)
+                        .await
+                        .expect("client connection resumed");
+
+                let (_, result) = receiver.recv().await.expect("client message receive");
+                assert_eq!(result, b"Welcome back");
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn ping_test() {
+        let _ = env_logger::try_init();
+
+        let test_num = TEST_NUM_DISPENCER.fetch_add(1, Ordering::Relaxed);
+
+        let server_port = 30000 + test_num * 10;
+        let client_port = 30000 + test_num * 10 + 1;
+
+        LocalSet::new()
+            .run_until(async move {
+                task::spawn_local(async move {
+                    let mut server = ServerParameters::default()
+                        .bind(([127, 0, 0, 1], server_port))
+                        .await
+                        .expect("server socket bind");
+
+                    loop {
+                        let _ = server.accept().await.expect("connection accepted");
+                    }
+                });
+
+                time::sleep(Duration::from_millis(5)).await;
+
+                let client = Client::bind(([127, 0, 0, 1], client_port))
+                    .await
+                    .expect("client bound");
+
+                let rtt = time::timeout(
+                    Duration::from_secs(1),
+                    client.ping(([127, 0, 0, 1], server_port)),
+                )
+                .await
+                .expect("ping answered")
+                .expect("ping sent");
+
+                assert!(rtt < Duration::from_secs(1));
+
+                // The socket could still be used to connect
+                let _ = client
+                    .connect(([127, 0, 0, 1], server_port))
+                    .await
+                    .expect("client connection");
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn handshake_test() {
+        let _ = env_logger::try_init();
+
+        let test_num = TEST_NUM_DISPENCER.fetch_add(1, Ordering::Relaxed);
+
+        let server_port = 30000 + test_num * 10;
+        let client_port = 30000 + test_num * 10 + 1;
+        let lacking_port = 30000 + test_num * 10 + 2;
+        let demanding_port = 30000 + test_num * 10 + 3;
+        let old_port = 30000 + test_num * 10 + 4;
+
+        LocalSet::new()
+            .run_until(async move {
+                task::spawn_local(async move {
+                    let mut server = ServerParameters {
+                        features: Features(0b011),
+                        required_features: Features(0b001),
+                        ..Default::default()
+                    }
+                    .bind(([127, 0, 0, 1], server_port))
+                    .await
+                    .expect("server socket bind");
+
+                    let server::Connection {
+                        features,
+                        mut sender,
+                        ..
+                    } = server.accept().await.expect("connection accepted");
+
+                    assert_eq!(features, Features(0b001));
+
+                    task::spawn_local(async move {
+                        sender
+                            .send_reliable(0, b"Welcome")
+                            .await
+                            .expect("server sent packet");
+                        sender.keepalive(KeepaliveParameters::default()).await
+                    });
+
+                    loop {
+                        let _ = server.accept().await.expect("connection accepted");
+                    }
+                });
+
+                time::sleep(Duration::from_millis(5)).await;
+
+                let client::Connection {
+                    features,
+                    mut receiver,
+                    ..
+                } = client::ClientParameters {
+                    features: Features(0b101),
+                    ..Default::default()
+                }
+                .bind(([127, 0, 0, 1], client_port))
+                .await
+                .expect("client bound")
+                .connect(([127, 0, 0, 1], server_port))
+                .await
+                .expect("client connection");
+
+                assert_eq!(features, Features(0b001));
+
+                let (_, result) = receiver.recv().await.expect("client message receive");
+                assert_eq!(result, b"Welcome");
+
+                let lacking = client::ClientParameters {
+                    features: Features(0b100),
+                    ..Default::default()
+                }
+                .bind(([127, 0, 0, 1], lacking_port))
+                .await
+                .expect("client bound")
+                .connect(([127, 0, 0, 1], server_port))
+                .await;
+
+                assert!(matches!(
+                    lacking,
+                    Err(client::Error::Handshake(HandshakeError::MissingFeatures(
+                        Features(0b001)
+                    )))
+                ));
+
+                let demanding = client::ClientParameters {
+                    features: Features(0b1001),
+                    required_features: Features(0b1000),
+                    ..Default::default()
+                }
+                .bind(([127, 0, 0, 1], demanding_port))
+                .await
+                .expect("client bound")
+                .connect(([127, 0, 0, 1], server_port))
+                .await;
+
+                assert!(matches!(
+                    demanding,
+                    Err(client::Error::Handshake(HandshakeError::MissingFeatures(
+                        Features(0b1000)
+                    )))
+                ));
+
+                // Client without the version and the features in the CONNECT packet
+                let old = UdpSocket::bind(("127.0.0.1", old_port)).expect("client bound");
+                old.set_read_timeout(Some(Duration::from_millis(500)))
+                    .unwrap();
+
+                let mut packet = Vec::new();
+                packet.write_varint(NEW_CONNECTION_ID).unwrap();
+                packet.write_varint(Type::CONNECT).unwrap();
+                // Rejected before the key is used
+                packet.extend_from_slice(&[2; 33]);
+
+                old.send_to(&packet, ("127.0.0.1", server_port)).unwrap();
+
+                let (buffer, len) = task::spawn_blocking(move || {
+                    let mut buffer = [0; 512];
+                    old.recv(&mut buffer).map(|len| (buffer, len))
+                })
+                .await
+                .unwrap()
+                .expect("reject received");
+
+                let mut reply = &buffer[.. len];
+                assert_eq!(reply.read_varint::<usize>().unwrap(), SERVER_ID);
+                assert_eq!(reply.read_varint::<u8>().unwrap(), Type::REJECT);
+                assert_eq!(reply.read_varint::<u16>().unwrap(), PROTOCOL_VERSION);
+            })
+            .await;
+    }
+
     #[test]
     #[cfg(not(debug_assertions))]
     fn reliable_test_load() {
@@ -7,11 +7,12 @@
 //!     self,
 //!     FutureExt,
 //! };
-//! use std::time::Duration;
-//! use tokio::time;
-//! use voxbrix_protocol::server::{
-//!     Connection,
-//!     ServerParameters,
+//! use voxbrix_protocol::{
+//!     server::{
+//!         Connection,
+//!         ServerParameters,
+//!     },
+//!     KeepaliveParameters,
 //! };
 //!
 //! async fn example() {
@@ -34,15 +35,11 @@
 //!
 //!     let send_future = async {
 //!         sender.send_reliable(0, b"Hello Server!").await;
-//!         loop {
-//!             // Senders send no data passively by themselves and resending lost messages
-//!             // in reliable data transfer happens lazily, right before sending a new one.
-//!             // Therefore, it is highly recommended to send some kind of "ping" or "keepalive"
-//!             // messages periodically, so the lost packets could be retransmitted even if you
-//!             // do not send any meaningful data.
-//!             time::sleep(Duration::from_secs(1)).await;
-//!             sender.send_reliable(0, b"keepalive").await;
-//!         }
+//!         // Senders send no data passively by themselves and resending lost messages
+//!         // in reliable data transfer happens lazily, right before sending a new one.
+//!         // Polling the keepalive future retransmits the lost packets even if you
+//!         // do not send any meaningful data, and detects dead connections.
+//!         sender.keepalive(KeepaliveParameters::default()).await;
 //!     };
 //!
 //!     let server_future = async {
@@ -60,26 +57,51 @@ use crate::{
     seek_read,
     AsSlice,
     Channel,
+    Congestion,
+    CongestionParameters,
+    ConnectionStats,
+    Echo,
+    Features,
+    Header,
     Id,
+    Ipv6Mode,
+    KeepaliveParameters,
     Key,
+    Keys,
+    ProtocolViolation,
+    ReceiveTimer,
+    RejectReason,
+    ReplayWindow,
+    Secret,
     Sequence,
+    StatsCounter,
+    Token,
     Type,
     UnreliableBuffer,
     UnreliableBufferShard,
+    DROP_DISCONNECT_REASON,
+    ECHO_BUFFER,
     KEY_BUFFER,
     MAX_DATA_SIZE,
     MAX_PACKET_SIZE,
     MAX_SPLIT_DATA_SIZE,
-    MAX_SPLIT_PACKETS,
     NEW_CONNECTION_ID,
+    NONCE_SIZE,
+    PROTOCOL_VERSION,
+    PROTOCOL_VIOLATION_DISCONNECT_REASON,
     RELIABLE_QUEUE_LENGTH,
-    RELIABLE_RESEND_AFTER,
     SECRET_BUFFER,
     SERVER_ID,
+    TAG_SIZE,
+    TOKEN_BUFFER,
     UNRELIABLE_BUFFERS,
 };
 use chacha20poly1305::{
-    aead::KeyInit,
+    aead::{
+        AeadCore,
+        AeadInPlace,
+        KeyInit,
+    },
     ChaCha20Poly1305,
 };
 #[cfg(feature = "multi")]
@@ -89,7 +111,10 @@ use flume::{
     Sender as ChannelTx,
     TryRecvError as TryReceiveError,
 };
-use futures_lite::future::FutureExt;
+use futures_lite::future::{
+    Future,
+    FutureExt,
+};
 use integer_encoding::{
     VarIntReader,
     VarIntWriter,
@@ -109,13 +134,19 @@ use local_channel::{
     TryReceiveError,
 };
 use log::debug;
-use rand_core::OsRng;
+use rand_core::{
+    OsRng,
+    RngCore,
+};
 #[cfg(feature = "single")]
 use std::rc::Rc;
 #[cfg(feature = "multi")]
 use std::sync::Arc as Rc;
 use std::{
-    collections::VecDeque,
+    collections::{
+        BTreeMap,
+        VecDeque,
+    },
     fmt,
     io::{
         Cursor,
@@ -125,8 +156,12 @@ use std::{
     },
     mem,
     net::SocketAddr,
+    pin::Pin,
     slice,
-    time::Instant,
+    time::{
+        Duration,
+        Instant,
+    },
 };
 use tokio::{
     net::UdpSocket,
@@ -190,14 +225,19 @@ pub enum Error {
     Io(StdIoError),
     /// Returned on attempt to send reliable message with the `Server` dropped.
     ServerWasDropped,
-    /// Returned by the receiver in case the client dropped the connection handles.
-    Disconnect,
+    /// Returned by the receiver in case the client disconnected explicitly or dropped
+    /// the connection handles. In the latter case the `reason` is `DROP_DISCONNECT_REASON`.
+    Disconnect { reason: u8, payload: Vec<u8> },
     /// Happens if a sender attempts to send a packet on a non-existant connection.
     InvalidConnection,
-    /// Currently internal variant, should not be returned.
+    /// Returned by the keepalive in case nothing was received from the client within the
+    /// timeout.
     Timeout,
     /// Peer sent message that is too large.
     PeerMessageTooLarge,
+    /// Returned by the receiver in strict mode after the client exceeded the limit of
+    /// malformed packets and was disconnected, contains the last violation.
+    ProtocolViolation(ProtocolViolation),
 }
 
 impl fmt::Display for Error {
@@ -217,13 +257,14 @@ impl From<StdIoError> for Error {
 /// Represents a data slice received. Implements `AsMut<[u8]>` and `AsRef<[u8]>`.
 pub struct Packet {
     data: Data,
+    reliable: bool,
 }
 
-impl From<ReadBuffer> for Packet {
-    fn from(from: ReadBuffer) -> Self {
-        Packet {
-            data: Data::Single(from),
-        }
+impl Packet {
+    /// The packet was sent reliably. Reliable packets are acknowledged on arrival,
+    /// the peer will not send it again even if it is not handled.
+    pub fn is_reliable(&self) -> bool {
+        self.reliable
     }
 }
 
@@ -231,6 +272,7 @@ impl From<Vec<u8>> for Packet {
     fn from(from: Vec<u8>) -> Self {
         Packet {
             data: Data::Collection(from),
+            reliable: false,
         }
     }
 }
@@ -254,6 +296,8 @@ struct InBuffer {
     buffer: WriteBuffer,
     tag_start: usize,
     stop: usize,
+    // Canonical address the packet came from
+    address: SocketAddr,
 }
 
 enum Out {
@@ -266,6 +310,11 @@ enum Out {
         peer: Id,
         cipher: ChaCha20Poly1305,
     },
+    /// The client moved to the new address, the packets go there from now on.
+    Migrate {
+        peer: Id,
+        address: SocketAddr,
+    },
 }
 
 struct FeedbackSender {
@@ -336,16 +385,18 @@ impl Feedback {
 async fn stream_send_ack(
     shared: &Shared,
     feedback: &mut Feedback,
+    channel: Channel,
     sequence: Sequence,
 ) -> Result<(), Error> {
     let mut buffer = WriteBuffer::new();
 
     let (tag_start, stop) =
         crate::write_in_buffer(buffer.as_mut(), SERVER_ID, Type::ACKNOWLEDGE, |cursor| {
+            cursor.write_varint(channel).unwrap();
             cursor.write_varint(sequence).unwrap();
         });
 
-    crate::encode_in_buffer(buffer.as_mut(), &shared.cipher, tag_start, stop);
+    crate::encode_in_buffer(buffer.as_mut(), &shared.keys.current(), tag_start, stop);
 
     shared
         .transport_sender
@@ -357,21 +408,112 @@ async fn stream_send_ack(
         .map_err(|_| Error::ServerWasDropped)?;
 
     feedback.receive().await?;
+    shared.stats.sent(stop);
+
+    Ok(())
+}
+
+async fn stream_send_disconnect(
+    shared: &Shared,
+    feedback: &mut Feedback,
+    reason: u8,
+    payload: &[u8],
+) -> Result<(), Error> {
+    let mut buffer = WriteBuffer::new();
+
+    let stop = crate::disconnect_in_buffer(
+        buffer.as_mut(),
+        SERVER_ID,
+        &shared.keys.current(),
+        reason,
+        payload,
+    );
+
+    shared
+        .transport_sender
+        .send(Out::Buffer {
+            peer: shared.peer,
+            buffer: buffer.finish(0, stop),
+            result_tx: feedback.new_sender(),
+        })
+        .map_err(|_| Error::ServerWasDropped)?;
+
+    feedback.receive().await?;
+    shared.stats.sent(stop);
+
+    Ok(())
+}
+
+async fn stream_send_rekey(
+    shared: &Shared,
+    feedback: &mut Feedback,
+    cipher: &ChaCha20Poly1305,
+    key: &Key,
+) -> Result<(), Error> {
+    let mut buffer = WriteBuffer::new();
+
+    let (tag_start, stop) =
+        crate::write_in_buffer(buffer.as_mut(), SERVER_ID, Type::REKEY, |cursor| {
+            cursor.write_all(key).unwrap();
+        });
+
+    crate::encode_in_buffer(buffer.as_mut(), cipher, tag_start, stop);
+
+    shared
+        .transport_sender
+        .send(Out::Buffer {
+            peer: shared.peer,
+            buffer: buffer.finish(0, stop),
+            result_tx: feedback.new_sender(),
+        })
+        .map_err(|_| Error::ServerWasDropped)?;
+
+    feedback.receive().await?;
+    shared.stats.sent(stop);
+
+    Ok(())
+}
+
+async fn stream_send_signal(
+    shared: &Shared,
+    feedback: &mut Feedback,
+    packet_type: u8,
+) -> Result<(), Error> {
+    let mut buffer = WriteBuffer::new();
+
+    let (tag_start, _) = crate::write_in_buffer(buffer.as_mut(), SERVER_ID, packet_type, |_| {});
+
+    let stop = crate::tag_sign_in_buffer(buffer.as_mut(), &shared.keys.current(), tag_start);
+
+    shared
+        .transport_sender
+        .send(Out::Buffer {
+            peer: shared.peer,
+            buffer: buffer.finish(0, stop),
+            result_tx: feedback.new_sender(),
+        })
+        .map_err(|_| Error::ServerWasDropped)?;
+
+    feedback.receive().await?;
+    shared.stats.sent(stop);
 
     Ok(())
 }
 
 struct Shared {
     peer: Id,
-    cipher: ChaCha20Poly1305,
+    keys: Keys,
     transport_sender: ChannelTx<Out>,
+    receive_timer: ReceiveTimer,
+    stats: StatsCounter,
+    max_protocol_violations: Option<u64>,
 }
 
 impl Drop for Shared {
     fn drop(&mut self) {
         let _ = self.transport_sender.send(Out::DropClient {
             peer: self.peer,
-            cipher: self.cipher.clone(),
+            cipher: self.keys.current(),
         });
     }
 }
@@ -403,6 +545,36 @@ impl StreamSender {
         self.reliable.wait_complete().await
     }
 
+    /// Keep the connection alive. See `StreamReliableSender::keepalive()`.
+    pub async fn keepalive(&mut self, parameters: KeepaliveParameters) -> Result<(), Error> {
+        self.reliable.keepalive(parameters).await
+    }
+
+    /// Get network statistics of the connection.
+    pub fn stats(&self) -> ConnectionStats {
+        self.reliable.stats()
+    }
+
+    /// Disconnect the client, the `reason` and the `payload` are returned by the client's
+    /// receiver in `Error::Disconnect`. The payload is truncated to `MAX_DATA_SIZE`.
+    ///
+    /// The message is sent once and its delivery is not guaranteed. If it is lost,
+    /// the client will only notice the disconnect by keepalive timeout.
+    pub async fn disconnect(self, reason: u8, payload: &[u8]) -> Result<(), Error> {
+        let Self {
+            mut unreliable,
+            reliable: _,
+        } = self;
+
+        stream_send_disconnect(
+            &unreliable.shared,
+            &mut unreliable.feedback,
+            reason,
+            payload,
+        )
+        .await
+    }
+
     /// Split the `StreamSender` into `StreamUnreliableSender` and `StreamReliableSender` halves.
     pub fn split(self) -> (StreamUnreliableSender, StreamReliableSender) {
         let Self {
@@ -418,6 +590,7 @@ impl StreamSender {
 pub struct StreamUnreliableSender {
     shared: Rc<Shared>,
     unreliable_split_id: u16,
+    unreliable_sequence: u64,
     feedback: Feedback,
 }
 
@@ -431,8 +604,11 @@ impl StreamUnreliableSender {
     ) -> Result<(), Error> {
         let mut buffer = WriteBuffer::new();
 
+        self.unreliable_sequence += 1;
+
         let (tag_start, stop) =
             crate::write_in_buffer(buffer.as_mut(), SERVER_ID, packet_type, |cursor| {
+                cursor.write_varint(self.unreliable_sequence).unwrap();
                 cursor.write_varint(channel).unwrap();
                 if let Some(len_or_count) = len_or_count {
                     cursor.write_varint(self.unreliable_split_id).unwrap();
@@ -441,7 +617,12 @@ impl StreamUnreliableSender {
                 cursor.write_all(data).unwrap();
             });
 
-        crate::encode_in_buffer(buffer.as_mut(), &self.shared.cipher, tag_start, stop);
+        crate::encode_in_buffer(
+            buffer.as_mut(),
+            &self.shared.keys.current(),
+            tag_start,
+            stop,
+        );
 
         self.shared
             .transport_sender
@@ -453,6 +634,7 @@ impl StreamUnreliableSender {
             .map_err(|_| Error::ServerWasDropped)?;
 
         self.feedback.receive().await?;
+        self.shared.stats.sent(stop);
 
         Ok(())
     }
@@ -489,27 +671,48 @@ impl StreamUnreliableSender {
                 .await
         }
     }
+
+    /// Get network statistics of the connection.
+    pub fn stats(&self) -> ConnectionStats {
+        self.shared.stats.get()
+    }
 }
 
 enum PacketState {
     Done,
     Pending {
         sent_at: Instant,
+        // Unencrypted, the key may be replaced before the packet is acknowledged
         buffer: ReadBuffer,
+        tag_start: usize,
+        // Resent packets are not used for RTT measurement
+        resent: bool,
     },
 }
 
+/// Reliable messages of a channel, waiting for the acknowledgement.
+#[derive(Default)]
+struct SendQueue {
+    front_sequence: Sequence,
+    queue: VecDeque<PacketState>,
+}
+
 /// Reliable-sending part of the connection.
 pub struct StreamReliableSender {
     shared: Rc<Shared>,
-    queue_front_sequence: Sequence,
-    queue: VecDeque<PacketState>,
+    queues: BTreeMap<Channel, SendQueue>,
+    // Number of packets in all queues waiting for the acknowledgement
+    in_flight: usize,
+    congestion: Congestion,
     ack_receiver: ChannelRx<InBuffer>,
     feedback: Feedback,
+    last_ping: Option<Instant>,
 }
 
 impl StreamReliableSender {
     async fn send_buffer(&mut self, buffer: ReadBuffer) -> Result<(), Error> {
+        let len = buffer.as_ref().len();
+
         self.shared
             .transport_sender
             .send(Out::Buffer {
@@ -520,31 +723,46 @@ impl StreamReliableSender {
             .map_err(|_| Error::ServerWasDropped)?;
 
         self.feedback.receive().await?;
+        self.shared.stats.sent(len);
 
         Ok(())
     }
 
-    fn pack_data(&mut self, channel: Channel, data: &[u8], packet_type: u8) -> ReadBuffer {
+    /// Returns the unencrypted packet and the start of its tag.
+    fn pack_data(
+        &self,
+        channel: Channel,
+        sequence: Sequence,
+        data: &[u8],
+        packet_type: u8,
+    ) -> (ReadBuffer, usize) {
         let mut buffer = WriteBuffer::new();
 
         let (tag_start, stop) =
             crate::write_in_buffer(buffer.as_mut(), SERVER_ID, packet_type, |cursor| {
                 cursor.write_varint(channel).unwrap();
-                cursor
-                    .write_varint(
-                        self.queue_front_sequence
-                            .wrapping_add(self.queue.len() as u16),
-                    )
-                    .unwrap();
+                cursor.write_varint(sequence).unwrap();
                 cursor.write_all(data).unwrap();
             });
 
-        crate::encode_in_buffer(buffer.as_mut(), &self.shared.cipher, tag_start, stop);
+        (buffer.finish(0, stop), tag_start)
+    }
+
+    /// Encrypts a copy of the packed data with the current key.
+    fn encrypt(&self, buffer: &ReadBuffer, tag_start: usize) -> ReadBuffer {
+        let mut packet = WriteBuffer(Rc::new(*buffer.buffer));
 
-        buffer.finish(0, stop)
+        crate::encode_in_buffer(
+            packet.as_mut(),
+            &self.shared.keys.current(),
+            tag_start,
+            buffer.stop,
+        );
+
+        packet.finish(0, buffer.stop)
     }
 
-    async fn handle_acks_resend(&mut self, mut must_wait: bool) -> Result<(), Error> {
+    async fn handle_acks_resend(&mut self, mut wait: Option<Duration>) -> Result<(), Error> {
         loop {
             // Handling previous ACKs first
             let InBuffer {
@@ -552,11 +770,10 @@ impl StreamReliableSender {
                 mut buffer,
                 tag_start,
                 stop,
-            } = if must_wait {
-                must_wait = false;
-
+                address: _,
+            } = if let Some(wait) = wait.take() {
                 // TODO timeout retry limit?
-                let result = match time::timeout(RELIABLE_RESEND_AFTER, {
+                let result = match time::timeout(wait, {
                     #[cfg(feature = "single")]
                     {
                         self.ack_receiver.recv()
@@ -584,50 +801,110 @@ impl StreamReliableSender {
                 }
             };
 
-            let decrypted_start = match crate::decode_in_buffer(
-                &mut buffer.as_mut()[.. stop],
-                tag_start,
-                &self.shared.cipher,
-            ) {
-                Ok(s) => s,
+            let decrypted_start = match self
+                .shared
+                .keys
+                .decode(&mut buffer.as_mut()[.. stop], tag_start)
+            {
+                Ok((s, _)) => s,
                 Err(()) => continue,
             };
 
-            let mut read_cursor = Cursor::new(&buffer.as_ref()[decrypted_start .. stop]);
-            let ack: Sequence = seek_read!(read_cursor.read_varint(), "sequence");
+            self.shared.receive_timer.mark();
+            self.shared.stats.received(stop);
 
-            let index = ack.wrapping_sub(self.queue_front_sequence);
+            // Only counted here, the receiver enforces the limit of the strict mode
+            let (channel, ack) = match crate::read_header(
+                Type::ACKNOWLEDGE,
+                &buffer.as_ref()[decrypted_start .. stop],
+            ) {
+                Ok((Header::Acknowledge { channel, sequence }, _)) => (channel, sequence),
+                Ok(_) => continue,
+                Err(violation) => {
+                    debug!("protocol violation {:?} in acknowledgement", violation);
+                    self.shared.stats.violation(violation);
+                    continue;
+                },
+            };
+
+            let Some(queue) = self.queues.get_mut(&channel) else {
+                continue;
+            };
+
+            let index = ack.wrapping_sub(queue.front_sequence);
 
             if index < RELIABLE_QUEUE_LENGTH {
-                if let Some(queue_entry) = self.queue.get_mut(index as usize) {
+                if let Some(queue_entry) = queue.queue.get_mut(index as usize) {
+                    if let PacketState::Pending {
+                        sent_at, resent, ..
+                    } = queue_entry
+                    {
+                        if !*resent {
+                            self.shared.stats.rtt_sample(sent_at.elapsed());
+                            self.congestion.on_rtt_sample(sent_at.elapsed());
+                        }
+
+                        self.in_flight -= 1;
+                        self.congestion.on_ack();
+                    }
+
                     *queue_entry = PacketState::Done;
                 }
             }
         }
 
         // Getting rid of confirmed packets
-        while matches!(self.queue.front(), Some(PacketState::Done)) {
-            self.queue.pop_front();
-            self.queue_front_sequence = self.queue_front_sequence.wrapping_add(1);
+        for queue in self.queues.values_mut() {
+            while matches!(queue.queue.front(), Some(PacketState::Done)) {
+                queue.queue.pop_front();
+                queue.front_sequence = queue.front_sequence.wrapping_add(1);
+            }
         }
 
-        let mut queue = mem::take(&mut self.queue);
-
         // Lazily resending lost packages
-        for (sent_at, buffer) in queue.iter_mut().filter_map(|entry| {
-            match entry {
-                PacketState::Pending { sent_at, buffer } => Some((sent_at, buffer)),
-                PacketState::Done => None,
-            }
-        }) {
-            if sent_at.elapsed() > RELIABLE_RESEND_AFTER {
-                self.send_buffer(buffer.clone()).await?;
+        let retransmission_timeout = self.congestion.retransmission_timeout();
+        let lost = self
+            .queues
+            .iter()
+            .flat_map(|(channel, queue)| {
+                queue
+                    .queue
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(index, entry)| {
+                        match entry {
+                            PacketState::Pending {
+                                sent_at,
+                                buffer,
+                                tag_start,
+                                ..
+                            } if sent_at.elapsed() > retransmission_timeout => {
+                                Some((*channel, index, buffer.clone(), *tag_start))
+                            },
+                            _ => None,
+                        }
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        if !lost.is_empty() {
+            self.congestion.on_loss();
+        }
+
+        for (channel, index, buffer, tag_start) in lost {
+            let packet = self.encrypt(&buffer, tag_start);
+            self.send_buffer(packet).await?;
+            self.shared.stats.retransmit();
+
+            if let Some(PacketState::Pending {
+                sent_at, resent, ..
+            }) = self.queues.get_mut(&channel).unwrap().queue.get_mut(index)
+            {
                 *sent_at = Instant::now();
+                *resent = true;
             }
         }
 
-        self.queue = queue;
-
         Ok(())
     }
 
@@ -637,25 +914,36 @@ impl StreamReliableSender {
         data: &[u8],
         packet_type: u8,
     ) -> Result<(), Error> {
-        let mut must_wait = false;
+        let mut wait = None;
         loop {
-            self.handle_acks_resend(mem::replace(&mut must_wait, false))
-                .await?;
+            self.handle_acks_resend(wait.take()).await?;
+
+            let queue = self.queues.entry(channel).or_default();
 
-            if matches!(self.queue.front(), Some(PacketState::Pending { .. }))
-                && self.queue.len() >= RELIABLE_QUEUE_LENGTH as usize
+            if (matches!(queue.queue.front(), Some(PacketState::Pending { .. }))
+                && queue.queue.len() >= RELIABLE_QUEUE_LENGTH as usize)
+                || self.in_flight >= self.congestion.window()
             {
-                // Waiting list is full
-                must_wait = true;
+                // Waiting list is full or the congestion window is exhausted
+                wait = Some(self.congestion.retransmission_timeout());
                 continue;
             } else {
                 // Finally send our latest packet and add that to waiting list
-                let buffer = self.pack_data(channel, data, packet_type);
-                self.queue.push_back(PacketState::Pending {
-                    sent_at: Instant::now(),
-                    buffer: buffer.clone(),
-                });
-                self.send_buffer(buffer).await?;
+                let sequence = queue.front_sequence.wrapping_add(queue.queue.len() as u16);
+                let (buffer, tag_start) = self.pack_data(channel, sequence, data, packet_type);
+                let packet = self.encrypt(&buffer, tag_start);
+                self.queues
+                    .get_mut(&channel)
+                    .unwrap()
+                    .queue
+                    .push_back(PacketState::Pending {
+                        sent_at: Instant::now(),
+                        buffer,
+                        tag_start,
+                        resent: false,
+                    });
+                self.in_flight += 1;
+                self.send_buffer(packet).await?;
 
                 return Ok(());
             }
@@ -686,104 +974,207 @@ impl StreamReliableSender {
     /// Wait for all transmitted data to be delivered.
     /// Resends lost messages periodically internally.
     pub async fn wait_complete(&mut self) -> Result<(), Error> {
-        while !self.queue.is_empty() {
-            self.handle_acks_resend(true).await?;
+        while self.queues.values().any(|queue| !queue.queue.is_empty()) {
+            self.handle_acks_resend(Some(self.congestion.retransmission_timeout()))
+                .await?;
         }
 
         Ok(())
     }
+
+    /// Keep the connection alive: periodically send PING packets to the client and resend lost
+    /// messages. Returns only on error, `Error::Timeout` means that nothing was received from the
+    /// client within the timeout.
+    ///
+    /// The future is safe to drop, so it can be raced with waiting for new messages to send.
+    /// **The `StreamReceiver` must be polled for the keepalive to work.**
+    pub async fn keepalive(&mut self, parameters: KeepaliveParameters) -> Result<(), Error> {
+        loop {
+            if self.shared.receive_timer.elapsed() > parameters.timeout {
+                return Err(Error::Timeout);
+            }
+
+            let now = Instant::now();
+
+            let next_ping = match self.last_ping {
+                Some(last_ping) if now < last_ping + parameters.interval => {
+                    last_ping + parameters.interval
+                },
+                _ => {
+                    stream_send_signal(&self.shared, &mut self.feedback, Type::PING).await?;
+                    self.last_ping = Some(now);
+                    now + parameters.interval
+                },
+            };
+
+            let wait = next_ping
+                .saturating_duration_since(Instant::now())
+                .min(self.congestion.retransmission_timeout());
+
+            self.handle_acks_resend(Some(wait)).await?;
+        }
+    }
+
+    /// Get network statistics of the connection.
+    pub fn stats(&self) -> ConnectionStats {
+        self.shared.stats.get()
+    }
 }
 
 #[derive(Clone)]
 struct QueueEntry {
     buffer: ReadBuffer,
-    channel: Channel,
     is_split: bool,
 }
 
+/// Reliable messages of a channel, waiting to be received in order.
+struct ReceiveQueue {
+    sequence: Sequence,
+    queue: VecDeque<Option<QueueEntry>>,
+    split_buffer: Vec<u8>,
+    is_split: bool,
+}
+
+impl ReceiveQueue {
+    fn new() -> Self {
+        Self {
+            sequence: 0,
+            queue: vec![None; RELIABLE_QUEUE_LENGTH as usize].into(),
+            split_buffer: Vec::new(),
+            is_split: false,
+        }
+    }
+}
+
 /// Message-receiving part of the connection.
 pub struct StreamReceiver {
     shared: Rc<Shared>,
-    sequence: Sequence,
-    reliable_queue: VecDeque<Option<QueueEntry>>,
-    reliable_split_buffer: Vec<u8>,
-    reliable_split_channel: Option<Channel>,
+    // TODO limit the number of channels the peer can open?
+    reliable_queues: BTreeMap<Channel, ReceiveQueue>,
+    // Channel that may have the first message in the queue ready to be received
+    reliable_ready_channel: Option<Channel>,
     unreliable_split_buffers: VecDeque<UnreliableBuffer>,
+    unreliable_split_data: Vec<u8>,
+    replay_window: ReplayWindow,
+    // Request and answer of the last rekeying, to answer again if the answer was lost
+    last_rekey: Option<(Key, Key)>,
+    // Sequence of the last client migration
+    last_migration: u64,
+    received_single: Option<ReadBuffer>,
     transport_receiver: ChannelRx<InBuffer>,
     feedback: Feedback,
 }
 
+/// Where the data of the received message is located.
+enum Received {
+    Single {
+        buffer: ReadBuffer,
+        reliable: bool,
+    },
+    /// In the `split_buffer` of the channel queue.
+    ReliableSplit,
+    /// In the `unreliable_split_data`.
+    UnreliableSplit,
+}
+
 impl StreamReceiver {
     /// Receive a message. Returns a channel id and a `Packet`-represented byte slice in tuple on
     /// success.
     pub async fn recv(&mut self) -> Result<(Channel, Packet), Error> {
+        let (channel, received) = self.recv_inner().await?;
+
+        let packet = match received {
+            Received::Single { buffer, reliable } => {
+                Packet {
+                    data: Data::Single(buffer),
+                    reliable,
+                }
+            },
+            Received::ReliableSplit => {
+                Packet {
+                    data: Data::Collection(mem::take(
+                        &mut self.reliable_queues.get_mut(&channel).unwrap().split_buffer,
+                    )),
+                    reliable: true,
+                }
+            },
+            Received::UnreliableSplit => mem::take(&mut self.unreliable_split_data).into(),
+        };
+
+        Ok((channel, packet))
+    }
+
+    /// Receive a message without allocating. Returns a channel id and a byte slice in tuple on
+    /// success. The slice points into the internal buffers of the receiver and is valid until the
+    /// next receive call.
+    pub async fn recv_ref(&mut self) -> Result<(Channel, &[u8]), Error> {
+        let (channel, received) = self.recv_inner().await?;
+
+        let data = match received {
+            Received::Single { buffer, .. } => {
+                let buffer: &ReadBuffer = self.received_single.insert(buffer);
+                buffer.as_ref()
+            },
+            Received::ReliableSplit => self.reliable_queues[&channel].split_buffer.as_slice(),
+            Received::UnreliableSplit => self.unreliable_split_data.as_slice(),
+        };
+
+        Ok((channel, data))
+    }
+
+    async fn recv_inner(&mut self) -> Result<(Channel, Received), Error> {
+        self.received_single = None;
+
         loop {
-            // Firstly, we check the reliable message queue
+            // Firstly, we check the reliable message queue of the channel
             // in case we have messages ready, handle these first
-            if self
-                .reliable_queue
-                .front()
-                .and_then(|f| f.as_ref())
-                .is_some()
-            {
+            if let Some(channel) = self.reliable_ready_channel {
+                let queue = self.reliable_queues.get_mut(&channel).unwrap();
+
+                if queue.queue.front().and_then(|f| f.as_ref()).is_none() {
+                    self.reliable_ready_channel = None;
+                    continue;
+                }
+
                 let QueueEntry {
                     buffer: queue_buffer,
-                    channel,
                     is_split,
-                } = self.reliable_queue.pop_front().flatten().unwrap();
-
-                self.reliable_queue.push_back(None);
-                self.sequence = self.sequence.wrapping_add(1);
+                } = queue.queue.pop_front().flatten().unwrap();
+
+                queue.queue.push_back(None);
+                queue.sequence = queue.sequence.wrapping_add(1);
+
+                if !is_split && !queue.is_split {
+                    // Non-split packet arrived
+                    return Ok((
+                        channel,
+                        Received::Single {
+                            buffer: queue_buffer,
+                            reliable: true,
+                        },
+                    ));
+                }
 
-                if is_split {
-                    // Split started, if not started - cleanup & start,
-                    // if we already started - check the channel
-                    if self.reliable_split_channel.is_none() {
-                        self.reliable_split_channel = Some(channel);
-                        self.reliable_split_buffer.clear();
-                    } else if let Some(reliable_split_channel) = self.reliable_split_channel {
-                        if reliable_split_channel != channel {
-                            debug!("skipping mishappened packet with channel {}", channel);
-                            continue;
-                        }
-                    }
+                // Split started, if not started - cleanup & start
+                if !queue.is_split {
+                    queue.is_split = true;
+                    queue.split_buffer.clear();
+                }
 
-                    if self.reliable_split_buffer.len() + queue_buffer.as_ref().len()
-                        > MAX_SPLIT_DATA_SIZE
-                    {
-                        return Err(Error::PeerMessageTooLarge);
-                    }
+                if queue.split_buffer.len() + queue_buffer.as_ref().len() > MAX_SPLIT_DATA_SIZE {
+                    return Err(Error::PeerMessageTooLarge);
+                }
 
-                    self.reliable_split_buffer
-                        .extend_from_slice(queue_buffer.as_ref());
+                queue.split_buffer.extend_from_slice(queue_buffer.as_ref());
 
+                if is_split {
                     continue;
-                } else {
-                    let buf = if let Some(reliable_split_channel) = self.reliable_split_channel {
-                        // Split just completed, extending and returning
-                        if reliable_split_channel == channel {
-                            if self.reliable_split_buffer.len() + queue_buffer.as_ref().len()
-                                > MAX_SPLIT_DATA_SIZE
-                            {
-                                return Err(Error::PeerMessageTooLarge);
-                            }
-
-                            self.reliable_split_buffer
-                                .extend_from_slice(queue_buffer.as_ref());
+                }
 
-                            self.reliable_split_channel = None;
+                // Split just completed, returning
+                queue.is_split = false;
 
-                            mem::take(&mut self.reliable_split_buffer).into()
-                        } else {
-                            continue;
-                        }
-                    } else {
-                        // Non-split packet arrived
-                        queue_buffer.into()
-                    };
-
-                    return Ok((channel, buf));
-                }
+                return Ok((channel, Received::ReliableSplit));
             }
 
             let InBuffer {
@@ -791,6 +1182,7 @@ impl StreamReceiver {
                 buffer: mut in_buffer,
                 tag_start,
                 stop,
+                address,
             } = {
                 #[cfg(feature = "single")]
                 {
@@ -804,42 +1196,87 @@ impl StreamReceiver {
             .await
             .map_err(|_| Error::ServerWasDropped)?;
 
-            let start = match crate::decode_in_buffer(
-                &mut in_buffer.as_mut()[.. stop],
-                tag_start,
-                &self.shared.cipher,
-            ) {
+            let (start, previous_key) = match self
+                .shared
+                .keys
+                .decode(&mut in_buffer.as_mut()[.. stop], tag_start)
+            {
                 Ok(s) => s,
                 Err(()) => continue,
             };
 
+            self.shared.receive_timer.mark();
+            self.shared.stats.received(stop);
+
             let mut in_buffer = in_buffer.finish(start, stop);
 
-            let mut read_cursor = Cursor::new(in_buffer.as_ref());
+            let (header, data_start) = match crate::read_header(packet_type, in_buffer.as_ref()) {
+                Ok(h) => h,
+                Err(violation) => {
+                    debug!(
+                        "protocol violation {:?} in packet of type {}",
+                        violation, packet_type
+                    );
+
+                    let violations = self.shared.stats.violation(violation);
 
-            match packet_type {
-                Type::DISCONNECT => {
-                    return Err(Error::Disconnect);
+                    if self
+                        .shared
+                        .max_protocol_violations
+                        .is_some_and(|max| violations > max)
+                    {
+                        stream_send_disconnect(
+                            &self.shared,
+                            &mut self.feedback,
+                            PROTOCOL_VIOLATION_DISCONNECT_REASON,
+                            &[],
+                        )
+                        .await?;
+
+                        return Err(Error::ProtocolViolation(violation));
+                    }
+
+                    continue;
                 },
-                Type::UNRELIABLE => {
-                    let channel: Channel = seek_read!(read_cursor.read_varint(), "channel");
-                    in_buffer.start += read_cursor.position() as usize;
-                    return Ok((channel, in_buffer.into()));
+            };
+
+            in_buffer.start += data_start;
+
+            if let Some(sequence) = header.unreliable_sequence() {
+                if !self.replay_window.accept(sequence) {
+                    debug!("dropping replayed unreliable packet {}", sequence);
+                    self.shared.stats.unreliable_replayed();
+                    continue;
+                }
+            }
+
+            match header {
+                Header::Disconnect { reason } => {
+                    return Err(Error::Disconnect {
+                        reason,
+                        payload: in_buffer.as_ref().to_vec(),
+                    });
                 },
-                Type::UNRELIABLE_SPLIT_START => {
-                    let channel: Channel = seek_read!(read_cursor.read_varint(), "channel");
-                    let split_id: u16 = seek_read!(read_cursor.read_varint(), "split_id");
-                    let expected_packets: usize =
-                        seek_read!(read_cursor.read_varint(), "expected_packets");
-
-                    if expected_packets > MAX_SPLIT_PACKETS {
-                        log::debug!(
-                            "dropping packet with packet length {} more than maximum {}",
-                            expected_packets,
-                            MAX_SPLIT_PACKETS,
-                        );
-                        continue;
-                    }
+                Header::Ping => {
+                    stream_send_signal(&self.shared, &mut self.feedback, Type::PONG).await?;
+                },
+                Header::Unreliable { channel, .. } => {
+                    self.shared.stats.unreliable_received();
+                    return Ok((
+                        channel,
+                        Received::Single {
+                            buffer: in_buffer,
+                            reliable: false,
+                        },
+                    ));
+                },
+                Header::UnreliableSplitStart {
+                    channel,
+                    split_id,
+                    expected_packets,
+                    ..
+                } => {
+                    self.shared.stats.unreliable_received();
 
                     let mut split_buffer = if self.unreliable_split_buffers.len()
                         == UNRELIABLE_BUFFERS
@@ -847,6 +1284,9 @@ impl StreamReceiver {
                             && self.unreliable_split_buffers.back().unwrap().is_complete()
                     {
                         let mut b = self.unreliable_split_buffers.pop_back().unwrap();
+                        if !b.is_complete() {
+                            self.shared.stats.unreliable_dropped(b.complete_shards);
+                        }
                         let UnreliableBuffer {
                             split_id: b_split_id,
                             channel: b_channel,
@@ -868,7 +1308,6 @@ impl StreamReceiver {
                         }
                     };
 
-                    in_buffer.start += read_cursor.position() as usize;
                     let in_buffer: &[u8] = in_buffer.as_ref();
 
                     let shard = split_buffer.shards.get_mut(0).unwrap();
@@ -880,36 +1319,41 @@ impl StreamReceiver {
                     split_buffer.complete_shards += 1;
                     self.unreliable_split_buffers.push_front(split_buffer);
                 },
-                Type::UNRELIABLE_SPLIT => {
-                    let channel: Channel = seek_read!(read_cursor.read_varint(), "channel");
-                    let split_id: u16 = seek_read!(read_cursor.read_varint(), "split_id");
-                    let count: usize = seek_read!(read_cursor.read_varint(), "count");
+                Header::UnreliableSplit {
+                    channel,
+                    split_id,
+                    count,
+                    ..
+                } => {
+                    self.shared.stats.unreliable_received();
 
-                    in_buffer.start += read_cursor.position() as usize;
                     let in_buffer: &[u8] = in_buffer.as_ref();
 
                     let split_buffer = match self.unreliable_split_buffers.iter_mut().find(|b| {
                         b.split_id == split_id && b.channel == channel && !b.is_complete()
                     }) {
                         Some(b) => b,
-                        None => continue,
+                        None => {
+                            self.shared.stats.unreliable_dropped(1);
+                            continue;
+                        },
                     };
 
                     let shard = match split_buffer.shards.get_mut(count) {
                         Some(s) => s,
                         None => {
                             debug!("shard not found for count {}", count);
+                            self.shared.stats.unreliable_dropped(1);
                             continue;
                         },
                     };
 
                     if shard.written {
                         debug!("shard is already written for count {}", count);
+                        self.shared.stats.unreliable_dropped(1);
                         continue;
                     }
 
-                    let shard = split_buffer.shards.get_mut(count).unwrap();
-
                     shard.buffer[.. in_buffer.len()].copy_from_slice(in_buffer);
                     shard.length = in_buffer.len();
                     shard.written = true;
@@ -917,7 +1361,10 @@ impl StreamReceiver {
                     split_buffer.complete_shards += 1;
 
                     if split_buffer.is_complete() {
-                        let mut buf = Vec::with_capacity(MAX_DATA_SIZE * split_buffer.shards.len());
+                        let buf = &mut self.unreliable_split_data;
+
+                        buf.clear();
+                        buf.reserve(MAX_DATA_SIZE * split_buffer.shards.len());
 
                         for shard in split_buffer.shards.iter() {
                             buf.extend_from_slice(&shard.buffer[.. shard.length]);
@@ -926,35 +1373,104 @@ impl StreamReceiver {
                         // TODO: also check CRC and if it's incorrect restore buf length to
                         // MAX_PACKET_SIZE before continuing
 
-                        return Ok((channel, buf.into()));
+                        return Ok((channel, Received::UnreliableSplit));
                     }
                 },
-                Type::RELIABLE | Type::RELIABLE_SPLIT => {
-                    let channel: Channel = seek_read!(read_cursor.read_varint(), "channel");
-                    let sequence: Sequence = seek_read!(read_cursor.read_varint(), "sequence");
-
+                Header::Reliable {
+                    channel,
+                    sequence,
+                    is_split,
+                } => {
                     // TODO: do not answer if the sequence is not previous, but random?
-                    stream_send_ack(&self.shared, &mut self.feedback, sequence).await?;
+                    stream_send_ack(&self.shared, &mut self.feedback, channel, sequence).await?;
 
-                    // TODO verify correctness
-                    let index = sequence.wrapping_sub(self.sequence);
+                    let queue = self
+                        .reliable_queues
+                        .entry(channel)
+                        .or_insert_with(ReceiveQueue::new);
 
-                    in_buffer.start += read_cursor.position() as usize;
+                    // TODO verify correctness
+                    let index = sequence.wrapping_sub(queue.sequence);
 
                     if index < RELIABLE_QUEUE_LENGTH {
-                        let queue_place = self.reliable_queue.get_mut(index as usize).unwrap();
+                        let queue_place = queue.queue.get_mut(index as usize).unwrap();
 
                         *queue_place = Some(QueueEntry {
                             buffer: in_buffer,
-                            channel,
-                            is_split: packet_type == Type::RELIABLE_SPLIT,
+                            is_split,
                         });
+
+                        if index == 0 {
+                            self.reliable_ready_channel = Some(channel);
+                        }
                     }
                 },
-                _ => {},
+                Header::Rekey { key } => {
+                    match self.last_rekey {
+                        // Repeated request, the answer was lost and the client still uses
+                        // the previous key
+                        Some((request, answer)) if request == key && previous_key => {
+                            let Some(cipher) = self.shared.keys.previous() else {
+                                continue;
+                            };
+
+                            stream_send_rekey(&self.shared, &mut self.feedback, &cipher, &answer)
+                                .await?;
+                        },
+                        _ if previous_key => {
+                            debug!("dropping stale rekey request");
+                        },
+                        _ => {
+                            let keypair = EphemeralSecret::random(&mut OsRng);
+                            let answer: Key = EncodedPoint::from(keypair.public_key())
+                                .as_bytes()
+                                .try_into()
+                                .unwrap();
+
+                            // Checked by read_header()
+                            let peer_key = PublicKey::from_sec1_bytes(&key).unwrap();
+
+                            // The answer goes with the current key, the client does not have
+                            // the new one yet
+                            stream_send_rekey(
+                                &self.shared,
+                                &mut self.feedback,
+                                &self.shared.keys.current(),
+                                &answer,
+                            )
+                            .await?;
+
+                            self.shared.keys.rekey(
+                                &keypair.diffie_hellman(&peer_key),
+                                self.shared.stats.bytes(),
+                            );
+                            self.shared.stats.rekey();
+                            self.last_rekey = Some((key, answer));
+                        },
+                    }
+                },
+                Header::Migrate { sequence } => {
+                    // The older ones may be the captured packets replayed from elsewhere
+                    if sequence > self.last_migration {
+                        self.last_migration = sequence;
+                        self.shared.stats.migration();
+
+                        let _ = self.shared.transport_sender.send(Out::Migrate {
+                            peer: self.shared.peer,
+                            address,
+                        });
+                    }
+                },
+                // Acknowledgements are routed to the reliable sender
+                Header::Acknowledge { .. } | Header::Pong => {},
             }
         }
     }
+
+    /// Get network statistics of the connection.
+    pub fn stats(&self) -> ConnectionStats {
+        self.shared.stats.get()
+    }
 }
 
 struct Client {
@@ -1006,7 +1522,7 @@ impl Clients {
         }
 
         let idx = id - Self::ID_OFFSET;
-        let res = mem::replace(self.clients.get_mut(idx)?, None);
+        let res = self.clients.get_mut(idx)?.take();
 
         if res.is_some() {
             self.free_indices.push(idx);
@@ -1034,6 +1550,99 @@ pub struct Connection {
     pub sender: StreamSender,
     /// Receiver part.
     pub receiver: StreamReceiver,
+    /// Features supported by both the server and the client.
+    pub features: Features,
+    /// Identifier of the session, kept when the client resumes the connection.
+    pub session: u64,
+    /// The client resumed the lost connection of the `session`.
+    pub resumed: bool,
+}
+
+#[cfg(feature = "single")]
+type ConnectFilterFn = dyn FnMut(SocketAddr, Key) -> Pin<Box<dyn Future<Output = bool>>>;
+
+#[cfg(feature = "multi")]
+type ConnectFilterFn =
+    dyn FnMut(SocketAddr, Key) -> Pin<Box<dyn Future<Output = bool> + Send>> + Send;
+
+/// Callback deciding whether a new connection attempt should be accepted.
+///
+/// Receives the peer address and the one-time peer public key and resolves to `true`
+/// if the connection should be accepted. Rejected attempts are dropped silently
+/// before a client slot is allocated, which makes the filter suitable for IP bans
+/// and anti-flood protection.
+///
+/// **The filter is awaited inside `Server::accept()`, meaning that no packets
+/// are routed for the existing connections until it resolves. Keep it fast.**
+pub struct ConnectFilter(Box<ConnectFilterFn>);
+
+impl ConnectFilter {
+    #[cfg(feature = "single")]
+    pub fn new<F, R>(mut filter: F) -> Self
+    where
+        F: FnMut(SocketAddr, Key) -> R + 'static,
+        R: Future<Output = bool> + 'static,
+    {
+        Self(Box::new(move |address, key| Box::pin(filter(address, key))))
+    }
+
+    #[cfg(feature = "multi")]
+    pub fn new<F, R>(mut filter: F) -> Self
+    where
+        F: FnMut(SocketAddr, Key) -> R + Send + 'static,
+        R: Future<Output = bool> + Send + 'static,
+    {
+        Self(Box::new(move |address, key| Box::pin(filter(address, key))))
+    }
+
+    async fn check(&mut self, address: SocketAddr, key: Key) -> bool {
+        (self.0)(address, key).await
+    }
+}
+
+impl fmt::Debug for ConnectFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ConnectFilter")
+    }
+}
+
+fn seal_token(cipher: &ChaCha20Poly1305, session: u64, secret: &Secret) -> Token {
+    let mut token = TOKEN_BUFFER;
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let (token_nonce, rest) = token.split_at_mut(NONCE_SIZE);
+    let (sealed, tag) = rest.split_at_mut(rest.len() - TAG_SIZE);
+
+    token_nonce.copy_from_slice(&nonce);
+    sealed[.. mem::size_of::<u64>()].copy_from_slice(&session.to_le_bytes());
+    sealed[mem::size_of::<u64>() ..].copy_from_slice(secret);
+
+    let sealed_tag = cipher
+        .encrypt_in_place_detached(&nonce, &[], sealed)
+        .unwrap();
+
+    tag.copy_from_slice(&sealed_tag);
+
+    token
+}
+
+/// Returns the session and the secret of the connection the token was issued for.
+fn open_token(cipher: &ChaCha20Poly1305, token: &Token) -> Option<(u64, Secret)> {
+    let mut token = *token;
+
+    let (nonce, rest) = token.split_at_mut(NONCE_SIZE);
+    let (sealed, tag) = rest.split_at_mut(rest.len() - TAG_SIZE);
+
+    cipher
+        .decrypt_in_place_detached((&*nonce).into(), &[], sealed, (&*tag).into())
+        .ok()?;
+
+    let (session, secret) = sealed.split_at(mem::size_of::<u64>());
+
+    Some((
+        u64::from_le_bytes(session.try_into().unwrap()),
+        secret.try_into().unwrap(),
+    ))
 }
 
 /// Server parameters.
@@ -1041,26 +1650,61 @@ pub struct Connection {
 pub struct ServerParameters {
     /// Maximum number of simultaneous connections that the server can have.
     pub max_connections: usize,
+    /// Congestion control of the reliable messages sent to each client.
+    pub congestion: CongestionParameters,
+    /// Optional filter for the incoming connection attempts.
+    pub connect_filter: Option<ConnectFilter>,
+    /// Features the server supports.
+    pub features: Features,
+    /// Features the clients must support, the others are rejected.
+    pub required_features: Features,
+    /// Strict mode, the clients that sent more malformed packets than that are disconnected.
+    /// If `None`, the malformed packets are only dropped and counted in the stats.
+    pub max_protocol_violations: Option<u64>,
+    /// Whether the server bound to an IPv6 address also accepts the IPv4 clients.
+    pub ipv6_mode: Ipv6Mode,
 }
 
 impl Default for ServerParameters {
     fn default() -> Self {
         Self {
             max_connections: DEFAULT_MAX_CONNECTIONS,
+            congestion: CongestionParameters::default(),
+            connect_filter: None,
+            features: Features::NONE,
+            required_features: Features::NONE,
+            max_protocol_violations: None,
+            ipv6_mode: Ipv6Mode::default(),
         }
     }
 }
 
 impl ServerParameters {
+    /// Set the filter for the incoming connection attempts, see `ConnectFilter`.
+    pub fn with_connect_filter(mut self, filter: ConnectFilter) -> Self {
+        self.connect_filter = Some(filter);
+        self
+    }
+
     /// Bind the socket and produce a `Server` with the given parameters.
+    /// With the default `Ipv6Mode::DualStack`, binding to `[::]` accepts both IPv6 and IPv4
+    /// clients.
     pub async fn bind<A>(self, bind_address: A) -> Result<Server, StdIoError>
     where
         A: Into<SocketAddr>,
     {
-        let transport = UdpSocket::bind(bind_address.into()).await?;
+        let bind_address = bind_address.into();
+        let transport = crate::bind_socket(bind_address, self.ipv6_mode)?;
         let (out_queue_sender, out_queue) = new_channel();
         Ok(Server {
             clients: Clients::new(self.max_connections),
+            congestion: self.congestion,
+            connect_filter: self.connect_filter,
+            features: self.features,
+            required_features: self.required_features,
+            max_protocol_violations: self.max_protocol_violations,
+            token_cipher: ChaCha20Poly1305::new(&ChaCha20Poly1305::generate_key(&mut OsRng)),
+            is_ipv6_socket: bind_address.is_ipv6(),
             out_queue,
             out_queue_sender,
             receive_buffer: WriteBuffer::new(),
@@ -1071,6 +1715,15 @@ impl ServerParameters {
 
 pub struct Server {
     clients: Clients,
+    congestion: CongestionParameters,
+    connect_filter: Option<ConnectFilter>,
+    features: Features,
+    required_features: Features,
+    max_protocol_violations: Option<u64>,
+    // Seals the resumption tokens, which are only valid for this server instance
+    token_cipher: ChaCha20Poly1305,
+    // Client addresses are kept canonical and mapped back for sending
+    is_ipv6_socket: bool,
     out_queue: ChannelRx<Out>,
     out_queue_sender: ChannelTx<Out>,
     receive_buffer: WriteBuffer,
@@ -1078,6 +1731,19 @@ pub struct Server {
 }
 
 impl Server {
+    async fn reject(&mut self, address: SocketAddr, reason: u8, missing_features: Features) {
+        let mut write_cursor = Cursor::new(self.receive_buffer.as_mut_slice());
+
+        write_cursor.write_varint(SERVER_ID).unwrap();
+        write_cursor.write_varint(Type::REJECT).unwrap();
+        write_cursor.write_varint(PROTOCOL_VERSION).unwrap();
+        write_cursor.write_all(&[reason]).unwrap();
+        write_cursor.write_varint(missing_features.0).unwrap();
+
+        let address = crate::socket_address(self.is_ipv6_socket, address);
+        let _ = self.transport.send_to(write_cursor.slice(), address).await;
+    }
+
     /// Accept a new connection.
     ///
     /// **Internally, this method handles most of the message routing from and to connection
@@ -1108,7 +1774,8 @@ impl Server {
 
             match next? {
                 ServerPacket::In((len, addr)) => {
-                    let mut read_cursor = Cursor::new(self.receive_buffer.as_ref());
+                    let addr = crate::canonical_address(addr);
+                    let mut read_cursor = Cursor::new(&self.receive_buffer.as_ref()[.. len]);
                     let sender: usize = seek_read!(read_cursor.read_varint(), "sender");
 
                     let mut packet_type = Type::UNDEFINED;
@@ -1118,14 +1785,82 @@ impl Server {
                     );
 
                     match packet_type {
-                        Type::CONNECT => {
+                        Type::CONNECT | Type::RESUME => {
                             if sender != NEW_CONNECTION_ID {
                                 debug!("received non-connection message");
                                 continue;
                             }
 
+                            let resumed_session = if packet_type == Type::RESUME {
+                                let mut token = TOKEN_BUFFER;
+                                seek_read!(read_cursor.read_exact(&mut token), "token");
+                                let tag_start = read_cursor.position() as usize;
+
+                                let Some((session, resumed_secret)) =
+                                    open_token(&self.token_cipher, &token)
+                                else {
+                                    debug!("connection from {} rejected: unknown session", addr);
+                                    self.reject(addr, RejectReason::SESSION, Features::NONE)
+                                        .await;
+                                    continue;
+                                };
+
+                                let resumed_cipher =
+                                    ChaCha20Poly1305::new((&resumed_secret).into());
+
+                                let encrypted_start = seek_read!(
+                                    crate::decode_in_buffer(
+                                        &mut self.receive_buffer.as_mut_slice()[.. len],
+                                        tag_start,
+                                        &resumed_cipher,
+                                    ),
+                                    "resume"
+                                );
+
+                                read_cursor = Cursor::new(&self.receive_buffer.as_ref()[.. len]);
+                                read_cursor.set_position(encrypted_start as u64);
+
+                                Some(session)
+                            } else {
+                                None
+                            };
+
                             let mut peer_key = KEY_BUFFER;
                             seek_read!(read_cursor.read_exact(&mut peer_key), "peer key");
+
+                            // Clients before versioning send neither
+                            let version: u16 = read_cursor.read_varint().unwrap_or(0);
+                            let peer_features = Features(read_cursor.read_varint().unwrap_or(0));
+
+                            if let Some(filter) = &mut self.connect_filter {
+                                if !filter.check(addr, peer_key).await {
+                                    debug!("connection from {} rejected by filter", addr);
+                                    continue;
+                                }
+                            }
+
+                            let missing_features = self.required_features.difference(peer_features);
+
+                            if version != PROTOCOL_VERSION || missing_features != Features::NONE {
+                                debug!(
+                                    "connection from {} rejected: version {}, missing features \
+                                     {:?}",
+                                    addr, version, missing_features
+                                );
+
+                                let reason = if version != PROTOCOL_VERSION {
+                                    RejectReason::VERSION
+                                } else {
+                                    RejectReason::FEATURES
+                                };
+
+                                self.reject(addr, reason, missing_features).await;
+
+                                continue;
+                            }
+
+                            let features = self.features.intersection(peer_features);
+
                             let deciphered_peer_key = seek_read!(
                                 PublicKey::from_sec1_bytes(&peer_key),
                                 "deciphered peer key"
@@ -1139,7 +1874,8 @@ impl Server {
                                 .expand(&[], &mut secret)
                                 .unwrap();
 
-                            let cipher = ChaCha20Poly1305::new((&secret).into());
+                            let session = resumed_session.unwrap_or_else(|| OsRng.next_u64());
+                            let token = seal_token(&self.token_cipher, session, &secret);
 
                             let (in_queue_tx, in_queue_rx) = new_channel();
 
@@ -1170,10 +1906,16 @@ impl Server {
                             write_cursor.write_varint(Type::ACCEPT).unwrap();
                             write_cursor.write_all(&self_key).unwrap();
                             write_cursor.write_varint(id).unwrap();
+                            write_cursor.write_varint(PROTOCOL_VERSION).unwrap();
+                            write_cursor.write_varint(features.0).unwrap();
+                            write_cursor.write_all(&token).unwrap();
 
                             if self
                                 .transport
-                                .send_to(write_cursor.slice(), addr)
+                                .send_to(
+                                    write_cursor.slice(),
+                                    crate::socket_address(self.is_ipv6_socket, addr),
+                                )
                                 .await
                                 .is_err()
                             {
@@ -1183,8 +1925,11 @@ impl Server {
 
                             let shared = Shared {
                                 peer: id,
-                                cipher,
+                                keys: Keys::new(secret),
                                 transport_sender: self.out_queue_sender.clone(),
+                                receive_timer: ReceiveTimer::new(),
+                                stats: StatsCounter::default(),
+                                max_protocol_violations: self.max_protocol_violations,
                             };
 
                             let shared = Rc::new(shared);
@@ -1196,31 +1941,55 @@ impl Server {
                                     unreliable: StreamUnreliableSender {
                                         shared: shared.clone(),
                                         unreliable_split_id: 0,
+                                        unreliable_sequence: 0,
                                         feedback: Feedback::new(),
                                     },
                                     reliable: StreamReliableSender {
                                         shared: shared.clone(),
-                                        queue_front_sequence: 0,
-                                        queue: VecDeque::new(),
+                                        queues: BTreeMap::new(),
+                                        in_flight: 0,
+                                        congestion: Congestion::new(self.congestion),
                                         ack_receiver,
                                         feedback: Feedback::new(),
+                                        last_ping: None,
                                     },
                                 },
                                 receiver: StreamReceiver {
                                     shared,
-                                    sequence: 0,
-                                    reliable_queue: vec![None; RELIABLE_QUEUE_LENGTH as usize]
-                                        .into(),
-                                    reliable_split_buffer: Vec::new(),
-                                    reliable_split_channel: None,
+                                    reliable_queues: BTreeMap::new(),
+                                    reliable_ready_channel: None,
                                     unreliable_split_buffers: VecDeque::with_capacity(
                                         UNRELIABLE_BUFFERS,
                                     ),
+                                    unreliable_split_data: Vec::new(),
+                                    replay_window: ReplayWindow::new(),
+                                    last_rekey: None,
+                                    last_migration: 0,
+                                    received_single: None,
                                     transport_receiver: in_queue_rx,
                                     feedback: Feedback::new(),
                                 },
+                                features,
+                                session,
+                                resumed: resumed_session.is_some(),
                             });
                         },
+                        // Lets the clients measure the round trip time before connecting,
+                        // the answer is not larger than the ping, so it cannot amplify
+                        // the spoofed traffic
+                        Type::PING if sender == NEW_CONNECTION_ID => {
+                            let mut echo: Echo = ECHO_BUFFER;
+                            seek_read!(read_cursor.read_exact(&mut echo), "echo");
+
+                            let mut write_cursor = Cursor::new(self.receive_buffer.as_mut_slice());
+
+                            write_cursor.write_varint(SERVER_ID).unwrap();
+                            write_cursor.write_varint(Type::PONG).unwrap();
+                            write_cursor.write_all(&echo).unwrap();
+
+                            let address = crate::socket_address(self.is_ipv6_socket, addr);
+                            let _ = self.transport.send_to(write_cursor.slice(), address).await;
+                        },
                         Type::ACKNOWLEDGE => {
                             if let Some(client) = self.clients.get_mut(sender) {
                                 let tag_start = read_cursor.position() as usize;
@@ -1233,6 +2002,7 @@ impl Server {
                                     ),
                                     tag_start,
                                     stop: len,
+                                    address: addr,
                                 };
 
                                 let _ = client.ack_sender.send(data);
@@ -1250,6 +2020,7 @@ impl Server {
                                     ),
                                     tag_start,
                                     stop: len,
+                                    address: addr,
                                 };
 
                                 let _ = client.in_queue.send(data);
@@ -1274,7 +2045,10 @@ impl Server {
 
                             if let Err(err) = self
                                 .transport
-                                .send_to(buffer.as_ref(), client.address)
+                                .send_to(
+                                    buffer.as_ref(),
+                                    crate::socket_address(self.is_ipv6_socket, client.address),
+                                )
                                 .await
                             {
                                 let _ = result_tx.send(Err(err.into()));
@@ -1284,26 +2058,32 @@ impl Server {
                         },
                         Out::DropClient { peer, cipher } => {
                             if let Some(client) = self.clients.remove(peer) {
-                                let mut write_cursor =
-                                    Cursor::new(self.receive_buffer.as_mut_slice());
-
-                                write_cursor.write_varint(SERVER_ID).unwrap();
-                                write_cursor.write_varint(Type::DISCONNECT).unwrap();
-
-                                let tag_start = write_cursor.position();
-
-                                let len = crate::tag_sign_in_buffer(
+                                let len = crate::disconnect_in_buffer(
                                     self.receive_buffer.as_mut(),
+                                    SERVER_ID,
                                     &cipher,
-                                    tag_start as usize,
+                                    DROP_DISCONNECT_REASON,
+                                    &[],
                                 );
 
                                 let _ = self
                                     .transport
-                                    .send_to(&self.receive_buffer.as_ref()[.. len], client.address)
+                                    .send_to(
+                                        &self.receive_buffer.as_ref()[.. len],
+                                        crate::socket_address(self.is_ipv6_socket, client.address),
+                                    )
                                     .await;
                             }
                         },
+                        Out::Migrate { peer, address } => {
+                            if let Some(client) = self.clients.get_mut(peer) {
+                                debug!(
+                                    "client {} migrated from {} to {}",
+                                    peer, client.address, address
+                                );
+                                client.address = address;
+                            }
+                        },
                     }
                 },
             }
@@ -1,8 +1,12 @@
-pub const CHUNK_GENERATION_SCRIPT_LIST: &str = "assets/server/scripts/chunk_generation_list.json";
-pub const CHUNK_GENERATION_SCRIPT_DIR: &str = "assets/server/scripts/chunk_generation";
-pub const DIMENSION_KIND_GENERATION_MAP: &str = "assets/server/dimension_kind_generation_map.json";
-pub const DIMENSION_KIND_LIST: &str = "assets/server/dimension_kind_list.json";
-pub const SERVER_LOOP_SCRIPT_LIST: &str = "assets/server/scripts/server_loop_list.json";
-pub const SERVER_LOOP_SCRIPT_DIR: &str = "assets/server/scripts/server_loop";
-pub const ACTION_LIST: &str = "assets/server/action_list.json";
-pub const ACTION_SCRIPT_MAP: &str = "assets/server/action_script_map.json";
+// Relative to the pack roots:
+pub const DIMENSION_KIND_GENERATION_MAP: &str = "server/dimension_kind_generation_map.json";
+pub const CHUNK_GENERATION_PASS_LIST: &str = "server/chunk_generation_passes.json";
+pub const BIOME_LIST: &str = "server/biomes.json";
+pub const ACTION_SCRIPT_MAP: &str = "server/action_script_map.json";
+pub const ACTION_PERMISSION_MAP: &str = "server/action_permission_map.json";
+pub const EFFECT_SCRIPT_MAP: &str = "server/effect_script_map.json";
+pub const SPAWN_RULES: &str = "server/spawn_rules.json";
+pub const CHUNK_GENERATION_SCRIPT_LIST: &str = "server/scripts/chunk_generation_list.json";
+pub const CHUNK_GENERATION_SCRIPT_DIR: &str = "server/scripts/chunk_generation";
+pub const SERVER_LOOP_SCRIPT_LIST: &str = "server/scripts/server_loop_list.json";
+pub const SERVER_LOOP_SCRIPT_DIR: &str = "server/scripts/server_loop";
@@ -3,24 +3,23 @@ use crate::{
         ClientEvent,
         SendData,
     },
+    config::Config,
     entity::player::Player,
+    metrics::Metrics,
     server_loop::ServerEvent,
     storage::{
         player::PlayerProfile,
         IntoData,
         IntoDataSized,
     },
+    system::asset_sync::AssetSyncSystem,
     BASE_CHANNEL,
     CLIENT_CONNECTION_TIMEOUT,
-    PLAYER_CHUNK_VIEW_RADIUS,
     PLAYER_TABLE,
     USERNAME_TABLE,
 };
 use futures_lite::{
-    future::{
-        self,
-        FutureExt,
-    },
+    future::FutureExt,
     stream::{
         self,
         StreamExt,
@@ -41,7 +40,10 @@ use redb::{
     Database,
     ReadableTable,
 };
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::Duration,
+};
 use tokio::{
     task,
     time,
@@ -50,14 +52,17 @@ use voxbrix_common::{
     async_ext::StreamExt as _,
     messages::{
         client::{
+            AssetFile,
             InitData,
             InitResponse,
             LoginFailure,
             LoginResult,
             RegisterFailure,
             RegisterResult,
+            ResumeResult,
         },
         server::{
+            AssetRequest,
             InitRequest,
             LoginRequest,
             RegisterRequest,
@@ -71,8 +76,13 @@ use voxbrix_protocol::{
         Packet,
     },
     Channel,
+    ConnectionStats,
+    KeepaliveParameters,
 };
 
+/// Time to send all the requested asset files.
+const ASSET_TRANSFER_TIMEOUT: Duration = Duration::from_secs(60);
+
 enum LoopEvent {
     ServerLoop(ClientEvent),
     PeerMessage { channel: usize, data: Packet },
@@ -85,12 +95,16 @@ pub enum Error {
     InitializationTimeout,
     FailedRegistration,
     FailedLogin,
+    FailedResume,
     SendError,
     ReceiveError,
 }
 
 pub struct ClientLoop {
+    pub config: Arc<Config>,
     pub database: Arc<Database>,
+    pub asset_sync_system: Arc<AssetSyncSystem>,
+    pub metrics: Arc<Metrics>,
     pub event_tx: Sender<ServerEvent>,
     pub connection: Connection,
     pub session_id: u64,
@@ -101,7 +115,10 @@ impl ClientLoop {
         let mut buffer = Vec::new();
 
         let Self {
+            config,
             database,
+            asset_sync_system,
+            metrics,
             event_tx,
             connection,
             session_id,
@@ -112,236 +129,333 @@ impl ClientLoop {
             peer_key,
             sender: tx,
             receiver: mut rx,
+            features: _,
+            session: connection_session,
+            resumed,
         } = connection;
 
         let (mut unreliable_tx, mut reliable_tx) = tx.split();
 
         let mut packer = Packer::new();
 
-        // Lookup for the player in the database,
-        // if there's none - register,
-        // if the password is not correct - send error
-        let request = time::timeout(CLIENT_CONNECTION_TIMEOUT, async {
-            rx.recv().await.map_err(|_| Error::ReceiveError)
-        })
-        .await
-        .map_err(|_| Error::InitializationTimeout)?
-        .and_then(|(_channel, data)| {
-            packer
-                .unpack::<InitRequest>(data.as_ref())
-                .map_err(|_| Error::UnexpectedMessage)
-        })?;
-
-        // TODO: read from config
-        let private_key = SigningKey::from_bytes((&[3; 32]).into()).unwrap();
-        let public_key = private_key
-            .verifying_key()
-            .to_encoded_point(true)
-            .as_bytes()
-            .try_into()
-            .unwrap();
-
-        let key_signature: Signature = private_key.sign(&self_key);
-
-        packer.pack(
-            &InitResponse {
-                public_key,
-                key_signature: key_signature.to_bytes().into(),
-            },
-            &mut buffer,
-        );
+        let (client_tx, server_rx) = flume::unbounded();
 
-        time::timeout(CLIENT_CONNECTION_TIMEOUT, async {
-            reliable_tx
-                .send_reliable(BASE_CHANNEL, &buffer)
-                .await
-                .map_err(|_| Error::SendError)
-        })
-        .await
-        .map_err(|_| Error::InitializationTimeout)??;
-
-        let player = match request {
-            InitRequest::Login => {
-                let LoginRequest {
-                    username,
-                    key_signature,
-                } = time::timeout(CLIENT_CONNECTION_TIMEOUT, async {
-                    rx.recv().await.map_err(|_| Error::ReceiveError)
-                })
-                .await
-                .map_err(|_| Error::InitializationTimeout)?
-                .and_then(|(_channel, data)| {
-                    packer
-                        .unpack::<LoginRequest>(data.as_ref())
-                        .map_err(|_| Error::UnexpectedMessage)
-                })?;
-
-                let player_res = task::spawn_blocking(move || {
-                    let mut packer = Packer::new();
-
-                    let db_read = database.begin_read().expect("database write");
-
-                    let username_table = db_read
-                        .open_table(USERNAME_TABLE)
-                        .expect("database table open");
-
-                    let player_table = db_read
-                        .open_table(PLAYER_TABLE)
-                        .expect("database table open");
-
-                    let player_id = username_table
-                        .get(username.as_str())
-                        .expect("database read")
-                        .map(|bytes| bytes.value().into_inner())
-                        .ok_or(LoginFailure::IncorrectCredentials)?;
-
-                    let player = player_table
-                        .get(player_id.into_data_sized())
-                        .expect("database read")
-                        .map(|bytes| bytes.value().into_inner(&mut packer))
-                        .ok_or(LoginFailure::IncorrectCredentials)?;
-
-                    let public_key =
-                        VerifyingKey::from_sec1_bytes(&player.public_key).map_err(|_| {
-                            warn!("client login: unable to parse client key in the database");
-                            LoginFailure::IncorrectCredentials
-                        })?;
-
-                    let signature =
-                        Signature::from_bytes((&key_signature).into()).map_err(|_| {
-                            warn!("client login: incorrect key signature format");
-                            LoginFailure::IncorrectCredentials
-                        })?;
-
-                    public_key
-                        .verify(&peer_key, &signature)
-                        .map_err(|_| LoginFailure::IncorrectCredentials)?;
-
-                    Ok(player_id)
-                })
-                .await
+        let (player, actor) = if resumed {
+            // The player is still in the world, the login and the assets are skipped
+            let _ = event_tx.send(ServerEvent::ResumePlayer {
+                connection_session,
+                client_tx,
+                session_id,
+            });
+
+            match server_rx.recv_async().await {
+                Ok(ClientEvent::AssignResumedPlayer { player, actor }) => (player, actor),
+                Err(_) => {
+                    packer.pack(&ResumeResult::Failure, &mut buffer);
+                    let _ = time::timeout(CLIENT_CONNECTION_TIMEOUT, async {
+                        reliable_tx
+                            .send_reliable(BASE_CHANNEL, &buffer)
+                            .await
+                            .map_err(|_| Error::SendError)
+                    })
+                    .await;
+
+                    return Err(Error::FailedResume);
+                },
+                _ => panic!("client_loop: incorrect answer to ResumePlayer"),
+            }
+        } else {
+            // Lookup for the player in the database,
+            // if there's none - register,
+            // if the password is not correct - send error
+            let request = time::timeout(CLIENT_CONNECTION_TIMEOUT, async {
+                rx.recv().await.map_err(|_| Error::ReceiveError)
+            })
+            .await
+            .map_err(|_| Error::InitializationTimeout)?
+            .and_then(|(_channel, data)| {
+                packer
+                    .unpack::<InitRequest>(data.as_ref())
+                    .map_err(|_| Error::UnexpectedMessage)
+            })?;
+
+            // TODO: read from config
+            let private_key = SigningKey::from_bytes((&[3; 32]).into()).unwrap();
+            let public_key = private_key
+                .verifying_key()
+                .to_encoded_point(true)
+                .as_bytes()
+                .try_into()
                 .unwrap();
 
-                match player_res {
-                    Ok(p) => p,
-                    Err(failure) => {
-                        packer.pack(&LoginResult::Failure(failure), &mut buffer);
-                        let _ = time::timeout(CLIENT_CONNECTION_TIMEOUT, async {
-                            reliable_tx
-                                .send_reliable(BASE_CHANNEL, &buffer)
-                                .await
-                                .map_err(|_| Error::SendError)
-                        })
-                        .await;
+            let key_signature: Signature = private_key.sign(&self_key);
 
-                        return Err(Error::FailedLogin);
-                    },
-                }
-            },
-            InitRequest::Register => {
-                let RegisterRequest {
-                    username,
+            packer.pack(
+                &InitResponse {
                     public_key,
-                } = time::timeout(CLIENT_CONNECTION_TIMEOUT, async {
-                    rx.recv().await.map_err(|_| Error::ReceiveError)
-                })
-                .await
-                .map_err(|_| Error::InitializationTimeout)?
-                .and_then(|(_channel, data)| {
-                    packer
-                        .unpack::<RegisterRequest>(data.as_ref())
-                        .map_err(|_| Error::UnexpectedMessage)
-                })?;
-
-                let player_res = task::spawn_blocking(move || {
-                    let mut packer = Packer::new();
-
-                    let db_write = database.begin_write().expect("database write");
-                    let player = {
-                        let mut username_table = db_write
+                    key_signature: key_signature.to_bytes().into(),
+                },
+                &mut buffer,
+            );
+
+            time::timeout(CLIENT_CONNECTION_TIMEOUT, async {
+                reliable_tx
+                    .send_reliable(BASE_CHANNEL, &buffer)
+                    .await
+                    .map_err(|_| Error::SendError)
+            })
+            .await
+            .map_err(|_| Error::InitializationTimeout)??;
+
+            let (player, username, role, state) = match request {
+                InitRequest::Login => {
+                    let LoginRequest {
+                        username,
+                        key_signature,
+                    } = time::timeout(CLIENT_CONNECTION_TIMEOUT, async {
+                        rx.recv().await.map_err(|_| Error::ReceiveError)
+                    })
+                    .await
+                    .map_err(|_| Error::InitializationTimeout)?
+                    .and_then(|(_channel, data)| {
+                        packer
+                            .unpack::<LoginRequest>(data.as_ref())
+                            .map_err(|_| Error::UnexpectedMessage)
+                    })?;
+
+                    let player_res = task::spawn_blocking(move || {
+                        let mut packer = Packer::new();
+
+                        let db_read = database.begin_read().expect("database write");
+
+                        let username_table = db_read
                             .open_table(USERNAME_TABLE)
                             .expect("database table open");
 
-                        if username_table
-                            .get(username.as_str())
-                            .expect("database read")
-                            .is_some()
-                        {
-                            return Err(RegisterFailure::UsernameTaken);
-                        }
-
-                        let mut player_table = db_write
+                        let player_table = db_read
                             .open_table(PLAYER_TABLE)
                             .expect("database table open");
 
+                        let player_id = username_table
+                            .get(username.as_str())
+                            .expect("database read")
+                            .map(|bytes| bytes.value().into_inner())
+                            .ok_or(LoginFailure::IncorrectCredentials)?;
+
                         let player = player_table
-                            .iter()
+                            .get(player_id.into_data_sized())
                             .expect("database read")
-                            .next_back()
-                            .transpose()
-                            .expect("database iteration")
-                            .map(|(data, _)| {
-                                let player = data.value().into_inner();
-                                // TODO: some kind of wrapping?
-                                Player(player.0.checked_add(1).unwrap())
+                            .map(|bytes| bytes.value().into_inner(&mut packer))
+                            .ok_or(LoginFailure::IncorrectCredentials)?;
+
+                        let public_key = VerifyingKey::from_sec1_bytes(&player.public_key)
+                            .map_err(|_| {
+                                warn!("client login: unable to parse client key in the database");
+                                LoginFailure::IncorrectCredentials
+                            })?;
+
+                        let signature =
+                            Signature::from_bytes((&key_signature).into()).map_err(|_| {
+                                warn!("client login: incorrect key signature format");
+                                LoginFailure::IncorrectCredentials
+                            })?;
+
+                        public_key
+                            .verify(&peer_key, &signature)
+                            .map_err(|_| LoginFailure::IncorrectCredentials)?;
+
+                        Ok((player_id, player.username, player.role, player.state))
+                    })
+                    .await
+                    .unwrap();
+
+                    match player_res {
+                        Ok(p) => p,
+                        Err(failure) => {
+                            packer.pack(&LoginResult::Failure(failure), &mut buffer);
+                            let _ = time::timeout(CLIENT_CONNECTION_TIMEOUT, async {
+                                reliable_tx
+                                    .send_reliable(BASE_CHANNEL, &buffer)
+                                    .await
+                                    .map_err(|_| Error::SendError)
                             })
-                            .unwrap_or(Player(0));
-
-                        username_table
-                            .insert(username.as_str(), player.into_data_sized())
-                            .expect("database write");
-
-                        player_table
-                            .insert(
-                                player.into_data_sized(),
-                                PlayerProfile {
-                                    username,
-                                    public_key,
-                                }
-                                .into_data(&mut packer),
-                            )
-                            .expect("database write");
-
-                        player
-                    };
-                    db_write.commit().expect("database commit");
-
-                    Ok(player)
-                })
-                .await
-                .unwrap();
+                            .await;
 
-                match player_res {
-                    Ok(p) => p,
-                    Err(failure) => {
-                        packer.pack(&RegisterResult::Failure(failure), &mut buffer);
-                        let _ = time::timeout(CLIENT_CONNECTION_TIMEOUT, async {
-                            reliable_tx
-                                .send_reliable(BASE_CHANNEL, &buffer)
-                                .await
-                                .map_err(|_| Error::SendError)
-                        })
-                        .await
-                        .map_err(|_| Error::InitializationTimeout)?;
+                            return Err(Error::FailedLogin);
+                        },
+                    }
+                },
+                InitRequest::Register => {
+                    let RegisterRequest {
+                        username,
+                        public_key,
+                    } = time::timeout(CLIENT_CONNECTION_TIMEOUT, async {
+                        rx.recv().await.map_err(|_| Error::ReceiveError)
+                    })
+                    .await
+                    .map_err(|_| Error::InitializationTimeout)?
+                    .and_then(|(_channel, data)| {
+                        packer
+                            .unpack::<RegisterRequest>(data.as_ref())
+                            .map_err(|_| Error::UnexpectedMessage)
+                    })?;
 
-                        return Err(Error::FailedRegistration);
-                    },
-                }
-            },
-        };
+                    let role = config.default_role;
+
+                    let player_res = task::spawn_blocking(move || {
+                        let mut packer = Packer::new();
+
+                        let db_write = database.begin_write().expect("database write");
+                        let player = {
+                            let mut username_table = db_write
+                                .open_table(USERNAME_TABLE)
+                                .expect("database table open");
+
+                            if username_table
+                                .get(username.as_str())
+                                .expect("database read")
+                                .is_some()
+                            {
+                                return Err(RegisterFailure::UsernameTaken);
+                            }
+
+                            let mut player_table = db_write
+                                .open_table(PLAYER_TABLE)
+                                .expect("database table open");
+
+                            let player = player_table
+                                .iter()
+                                .expect("database read")
+                                .next_back()
+                                .transpose()
+                                .expect("database iteration")
+                                .map(|(data, _)| {
+                                    let player = data.value().into_inner();
+                                    // TODO: some kind of wrapping?
+                                    Player(player.0.checked_add(1).unwrap())
+                                })
+                                .unwrap_or(Player(0));
+
+                            username_table
+                                .insert(username.as_str(), player.into_data_sized())
+                                .expect("database write");
+
+                            player_table
+                                .insert(
+                                    player.into_data_sized(),
+                                    PlayerProfile {
+                                        username: username.clone(),
+                                        public_key,
+                                        role,
+                                        state: None,
+                                    }
+                                    .into_data(&mut packer),
+                                )
+                                .expect("database write");
+
+                            player
+                        };
+                        db_write.commit().expect("database commit");
+
+                        Ok((player, username, role, None))
+                    })
+                    .await
+                    .unwrap();
+
+                    match player_res {
+                        Ok(p) => p,
+                        Err(failure) => {
+                            packer.pack(&RegisterResult::Failure(failure), &mut buffer);
+                            let _ = time::timeout(CLIENT_CONNECTION_TIMEOUT, async {
+                                reliable_tx
+                                    .send_reliable(BASE_CHANNEL, &buffer)
+                                    .await
+                                    .map_err(|_| Error::SendError)
+                            })
+                            .await
+                            .map_err(|_| Error::InitializationTimeout)?;
 
-        let (client_tx, server_rx) = flume::unbounded();
+                            return Err(Error::FailedRegistration);
+                        },
+                    }
+                },
+            };
+
+            // Credentials are correct, the client synchronizes the assets before joining
+            let manifest = asset_sync_system.manifest().clone();
+            match request {
+                InitRequest::Login => packer.pack(&LoginResult::Success(manifest), &mut buffer),
+                InitRequest::Register => {
+                    packer.pack(&RegisterResult::Success(manifest), &mut buffer)
+                },
+            }
 
-        let _ = event_tx.send(ServerEvent::AddPlayer {
-            player,
-            client_tx,
-            session_id,
-        });
+            time::timeout(CLIENT_CONNECTION_TIMEOUT, async {
+                reliable_tx
+                    .send_reliable(BASE_CHANNEL, &buffer)
+                    .await
+                    .map_err(|_| Error::SendError)
+            })
+            .await
+            .map_err(|_| Error::InitializationTimeout)??;
+
+            let AssetRequest { files } = time::timeout(CLIENT_CONNECTION_TIMEOUT, async {
+                rx.recv().await.map_err(|_| Error::ReceiveError)
+            })
+            .await
+            .map_err(|_| Error::InitializationTimeout)?
+            .and_then(|(_channel, data)| {
+                packer
+                    .unpack::<AssetRequest>(data.as_ref())
+                    .map_err(|_| Error::UnexpectedMessage)
+            })?;
+
+            time::timeout(
+                ASSET_TRANSFER_TIMEOUT,
+                async {
+                    for index in files {
+                        let data = asset_sync_system
+                            .file(index)
+                            .ok_or(Error::UnexpectedMessage)?
+                            .to_vec();
+
+                        packer.pack(&AssetFile { index, data }, &mut buffer);
+
+                        reliable_tx
+                            .send_reliable(BASE_CHANNEL, &buffer)
+                            .await
+                            .map_err(|_| Error::SendError)?;
+                    }
 
-        let actor = match server_rx.recv_async().await {
-            Ok(ClientEvent::AssignActor { actor }) => actor,
-            Err(_) => return Ok(()),
-            _ => panic!("client_loop: incorrect answer to AddPlayer"),
+                    Ok::<_, Error>(())
+                }
+                // Receiver must be polled for the sender to get the acknowledgements,
+                // the client sends nothing until it gets all the files
+                .or(async {
+                    match rx.recv().await {
+                        Ok(_) => Err(Error::UnexpectedMessage),
+                        Err(_) => Err(Error::ReceiveError),
+                    }
+                }),
+            )
+            .await
+            .map_err(|_| Error::InitializationTimeout)??;
+
+            let _ = event_tx.send(ServerEvent::AddPlayer {
+                player,
+                username,
+                role,
+                state,
+                client_tx,
+                session_id,
+                connection_session,
+            });
+
+            match server_rx.recv_async().await {
+                Ok(ClientEvent::AssignActor { actor }) => (player, actor),
+                Err(_) => return Ok(()),
+                _ => panic!("client_loop: incorrect answer to AddPlayer"),
+            }
         };
 
         let (unreliable_loop_tx, mut unreliable_loop_rx) =
@@ -366,11 +480,16 @@ impl ClientLoop {
             loop {
                 let msg = (async { Ok(reliable_loop_rx.recv().await) })
                     .or(async {
-                        reliable_tx.wait_complete().await.map_err(|err| {
-                            warn!("client_loop: wait_complete error {:?}", err);
-                            Error::SendError
-                        })?;
-                        future::pending::<()>().await;
+                        reliable_tx
+                            .keepalive(KeepaliveParameters {
+                                timeout: CLIENT_CONNECTION_TIMEOUT,
+                                ..Default::default()
+                            })
+                            .await
+                            .map_err(|err| {
+                                warn!("client_loop: keepalive error {:?}", err);
+                                Error::SendError
+                            })?;
                         unreachable!();
                     })
                     .await?;
@@ -390,20 +509,28 @@ impl ClientLoop {
             }
         });
 
-        let recv_stream = stream::unfold(rx, |mut rx| {
-            async move {
-                let value = rx
-                    .recv()
-                    .await
-                    .map(|(channel, data)| LoopEvent::PeerMessage { channel, data })
-                    .map_err(|err| {
-                        warn!("client_loop: connection interrupted: {:?}", err);
-                        Error::ReceiveError
-                    });
-
-                Some((value, rx))
-            }
-        });
+        let recv_stream = stream::unfold(
+            (rx, ConnectionStats::default(), metrics),
+            |(mut rx, previous_stats, metrics)| {
+                async move {
+                    let value = rx
+                        .recv()
+                        .await
+                        .map(|(channel, data)| LoopEvent::PeerMessage { channel, data })
+                        .map_err(|err| {
+                            warn!("client_loop: connection interrupted: {:?}", err);
+                            Error::ReceiveError
+                        });
+
+                    // The stats are shared by the connection halves,
+                    // so the receiver has the traffic of the senders too
+                    let stats = rx.stats();
+                    metrics.connection_traffic(&previous_stats, &stats);
+
+                    Some((value, (rx, stats, metrics)))
+                }
+            },
+        );
 
         let mut events = Box::pin(
             server_rx
@@ -414,19 +541,16 @@ impl ClientLoop {
                 .rr_ff(unrel_send_task),
         );
 
-        let init_data_response = match request {
-            InitRequest::Login => {
-                packer.pack_to_vec(&LoginResult::Success(InitData {
-                    actor,
-                    player_chunk_view_radius: PLAYER_CHUNK_VIEW_RADIUS,
-                }))
-            },
-            InitRequest::Register => {
-                packer.pack_to_vec(&RegisterResult::Success(InitData {
-                    actor,
-                    player_chunk_view_radius: PLAYER_CHUNK_VIEW_RADIUS,
-                }))
-            },
+        let init_data = InitData {
+            actor,
+            player_chunk_view_radius: config.player_chunk_view_radius,
+            process_interval_ms: config.process_interval_ms,
+        };
+
+        let init_data_response = if resumed {
+            packer.pack_to_vec(&ResumeResult::Success(init_data))
+        } else {
+            packer.pack_to_vec(&init_data)
         };
 
         // Finalize successful connection
@@ -456,6 +580,7 @@ impl ClientLoop {
                         .send(ServerEvent::PlayerEvent {
                             player,
                             channel,
+                            reliable: data.is_reliable(),
                             data,
                             session_id,
                         })
@@ -4,4 +4,5 @@ pub mod actor_class;
 pub mod block;
 pub mod block_class;
 pub mod chunk;
+pub mod effect;
 pub mod player;
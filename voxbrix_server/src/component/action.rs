@@ -1 +1,2 @@
+pub mod permission;
 pub mod script;
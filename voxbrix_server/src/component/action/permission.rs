@@ -0,0 +1,47 @@
+use crate::component::player::role::Permission;
+use anyhow::{
+    Context,
+    Error,
+};
+use nohash_hasher::IntMap;
+use voxbrix_common::{
+    entity::action::Action,
+    LabelMap,
+};
+
+/// Permission the player must have to perform the action.
+/// Actions not in the component are allowed to everyone.
+pub struct PermissionActionComponent(IntMap<Action, Permission>);
+
+impl PermissionActionComponent {
+    pub fn new<'a>(
+        action_permission_pairs: impl Iterator<Item = (&'a str, &'a str)>,
+        action_label_map: &LabelMap<Action>,
+    ) -> Result<Self, Error> {
+        let lookup = |action_label, permission_label| -> Result<_, Error> {
+            let action = action_label_map
+                .get(action_label)
+                .ok_or_else(|| Error::msg("action is undefined"))?;
+            let permission = Permission::from_label(permission_label)
+                .ok_or_else(|| Error::msg("permission is undefined"))?;
+
+            Ok((action, permission))
+        };
+        let inner = action_permission_pairs
+            .map(|(action_label, permission_label)| {
+                lookup(action_label, permission_label).with_context(|| {
+                    format!(
+                        "while processing action-permission pair(\"{}\": \"{}\")",
+                        action_label, permission_label,
+                    )
+                })
+            })
+            .collect::<Result<IntMap<_, _>, Error>>()?;
+
+        Ok(Self(inner))
+    }
+
+    pub fn get(&self, action: &Action) -> Option<&Permission> {
+        self.0.get(action)
+    }
+}
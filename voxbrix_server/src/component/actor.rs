@@ -26,9 +26,14 @@ use voxbrix_common::{
 
 pub mod chunk_activation;
 pub mod class;
+pub mod effect;
+pub mod goal;
+pub mod health;
+pub mod inventory;
 pub mod orientation;
 pub mod player;
 pub mod position;
+pub mod projectile;
 pub mod velocity;
 
 enum LoadedData {
@@ -227,6 +232,33 @@ where
         self.packer = Some(packer);
     }
 
+    fn drop_outdated_changes(&mut self, snapshot: Snapshot) {
+        if snapshot.0 > self.last_packed_snapshot.0 {
+            self.changes
+                .retain(move |_, past_snapshot| snapshot.0 - past_snapshot.0 <= MAX_SNAPSHOT_DIFF);
+
+            self.last_packed_snapshot = snapshot;
+        }
+    }
+
+    /// Actors with the changes the client has not received yet and the actors
+    /// that need the full update.
+    fn changed_actors<'b>(
+        &'b self,
+        client_last_snapshot: Snapshot,
+        player_actor: Option<&'b Actor>,
+        actors_full_update: &'b IntSet<Actor>,
+        actors_partial_update: &'b IntSet<Actor>,
+    ) -> impl Iterator<Item = &'b Actor> {
+        actors_partial_update
+            .iter()
+            .filter_map(|actor| self.changes.get_key_value(actor))
+            .filter(move |(_, past_snapshot)| past_snapshot.0 > client_last_snapshot.0)
+            .map(|(actor, _)| actor)
+            .chain(actors_full_update.iter())
+            .filter(move |actor| Some(*actor) != player_actor)
+    }
+
     pub fn pack_changes(
         &mut self,
         state: &mut StatePacker,
@@ -236,37 +268,85 @@ where
         actors_full_update: &IntSet<Actor>,
         actors_partial_update: &IntSet<Actor>,
     ) {
-        if snapshot.0 > self.last_packed_snapshot.0 {
-            self.changes
-                .retain(move |_, past_snapshot| snapshot.0 - past_snapshot.0 <= MAX_SNAPSHOT_DIFF);
-
-            self.last_packed_snapshot = snapshot;
-        }
+        self.drop_outdated_changes(snapshot);
 
         let mut packer = self.packer.take().unwrap();
 
-        let changed_actors_iter = actors_partial_update
+        let iter = self
+            .changed_actors(
+                client_last_snapshot,
+                player_actor,
+                actors_full_update,
+                actors_partial_update,
+            )
+            .map(|actor| (*actor, self.storage.get(actor)));
+
+        let buffer = state.get_component_buffer(self.state_component);
+
+        packer = packer.load_changes(iter).pack(buffer);
+
+        self.packer = Some(packer);
+    }
+
+    /// Same as `pack_full`, but the values are converted into the form the clients receive.
+    pub fn pack_full_with<P>(
+        &mut self,
+        state: &mut StatePacker,
+        player_actor: Option<&Actor>,
+        actors_full_update: &IntSet<Actor>,
+        convert: impl Fn(&T) -> P,
+    ) where
+        P: Serialize,
+    {
+        let values = actors_full_update
+            .iter()
+            .filter(|actor| Some(*actor) != player_actor)
+            .filter_map(|actor| Some((*actor, convert(self.storage.get(actor)?))))
+            .collect::<Vec<_>>();
+
+        let full = values
             .iter()
-            .filter_map(|actor| self.changes.get_key_value(actor))
-            .filter(|(_, past_snapshot)| past_snapshot.0 > client_last_snapshot.0)
-            .map(|(actor, _)| actor)
-            .chain(actors_full_update.iter());
+            .map(|(actor, value)| (*actor, value))
+            .collect::<Vec<_>>();
 
         let buffer = state.get_component_buffer(self.state_component);
 
-        if let Some(player_actor) = player_actor {
-            let iter = changed_actors_iter
-                .filter(|actor| actor != &player_actor)
-                .map(|actor| (*actor, self.storage.get(actor)));
+        pack::encode_into(&ActorStatePack::Full(&full), buffer);
+    }
 
-            packer = packer.load_changes(iter).pack(buffer);
-        } else {
-            let iter = changed_actors_iter.map(|actor| (*actor, self.storage.get(actor)));
+    /// Same as `pack_changes`, but the values are converted into the form the clients receive.
+    pub fn pack_changes_with<P>(
+        &mut self,
+        state: &mut StatePacker,
+        snapshot: Snapshot,
+        client_last_snapshot: Snapshot,
+        player_actor: Option<&Actor>,
+        actors_full_update: &IntSet<Actor>,
+        actors_partial_update: &IntSet<Actor>,
+        convert: impl Fn(&T) -> P,
+    ) where
+        P: Serialize,
+    {
+        self.drop_outdated_changes(snapshot);
+
+        let values = self
+            .changed_actors(
+                client_last_snapshot,
+                player_actor,
+                actors_full_update,
+                actors_partial_update,
+            )
+            .map(|actor| (*actor, self.storage.get(actor).map(&convert)))
+            .collect::<Vec<_>>();
+
+        let changes = values
+            .iter()
+            .map(|(actor, value)| (*actor, value.as_ref()))
+            .collect::<Vec<_>>();
 
-            packer = packer.load_changes(iter).pack(buffer);
-        }
+        let buffer = state.get_component_buffer(self.state_component);
 
-        self.packer = Some(packer);
+        pack::encode_into(&ActorStatePack::Change(&changes), buffer);
     }
 
     pub fn insert(&mut self, i: Actor, new: T, snapshot: Snapshot) -> Option<T> {
@@ -286,6 +366,10 @@ where
     // })
     // }
 
+    pub fn get(&self, i: &Actor) -> Option<&T> {
+        self.storage.get(i)
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = (Actor, &T)> {
         self.storage.iter().map(|(k, v)| (*k, v))
     }
@@ -316,6 +400,10 @@ impl<T> ActorComponent<T> {
         self.storage.get(i)
     }
 
+    pub fn get_mut(&mut self, i: &Actor) -> Option<&mut T> {
+        self.storage.get_mut(i)
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = (Actor, &T)> {
         self.storage.iter().map(|(&a, t)| (a, t))
     }
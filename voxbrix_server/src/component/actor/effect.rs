@@ -0,0 +1,4 @@
+use crate::component::actor::ActorComponentPackable;
+use voxbrix_common::component::actor::effect::ActiveEffects;
+
+pub type EffectActorComponent = ActorComponentPackable<ActiveEffects>;
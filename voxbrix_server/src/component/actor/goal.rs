@@ -0,0 +1,20 @@
+use crate::{
+    component::actor::ActorComponent,
+    system::pathfinding::NodeCoords,
+};
+use voxbrix_common::entity::{
+    chunk::Chunk,
+    snapshot::Snapshot,
+};
+
+pub type GoalActorComponent = ActorComponent<Goal>;
+
+/// Path the actor is currently walking.
+pub struct Goal {
+    /// Chunk the path nodes are relative to.
+    pub origin: Chunk,
+    /// Remaining nodes, the next one is last.
+    pub path: Vec<NodeCoords>,
+    /// Snapshot on which the goal is chosen again.
+    pub expires_at: Snapshot,
+}
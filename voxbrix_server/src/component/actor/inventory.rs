@@ -0,0 +1,4 @@
+use crate::component::actor::ActorComponentPackable;
+use voxbrix_common::component::actor::inventory::Inventory;
+
+pub type InventoryActorComponent = ActorComponentPackable<Inventory>;
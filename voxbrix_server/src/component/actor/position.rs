@@ -11,7 +11,11 @@ use std::{
     ops::Deref,
 };
 use voxbrix_common::{
-    component::actor::position::Position,
+    component::actor::position::{
+        Position,
+        PositionPacked,
+        POSITION_BASELINE_SNAPSHOTS,
+    },
     entity::{
         actor::Actor,
         chunk::Chunk,
@@ -33,7 +37,6 @@ pub struct Writable<'a, T> {
     actor: Actor,
     snapshot: Snapshot,
     changes: &'a mut IntMap<Actor, Snapshot>,
-    chunk_changes: &'a mut VecDeque<ActorChunkChange>,
     chunk_actor_component: &'a mut BTreeSet<(Chunk, Actor)>,
     data: &'a mut T,
 }
@@ -44,7 +47,6 @@ impl Writable<'_, Position> {
         let Self {
             snapshot,
             changes,
-            chunk_changes,
             chunk_actor_component,
             actor,
             data,
@@ -52,12 +54,6 @@ impl Writable<'_, Position> {
 
         if value != **data {
             if value.chunk != data.chunk {
-                chunk_changes.push_back(ActorChunkChange {
-                    snapshot: *snapshot,
-                    actor: *actor,
-                    previous_chunk: Some(data.chunk),
-                });
-
                 chunk_actor_component.remove(&(data.chunk, *actor));
                 chunk_actor_component.insert((value.chunk, *actor));
             }
@@ -77,12 +73,6 @@ impl<'a, T> Deref for Writable<'a, T> {
     }
 }
 
-struct ActorChunkChange {
-    snapshot: Snapshot,
-    actor: Actor,
-    previous_chunk: Option<Chunk>,
-}
-
 /// Special container for the position component.
 /// There are some specifics related to position being main factor to
 /// determine if and how (complete/change-only) the Actor should be sent to the client.
@@ -90,9 +80,12 @@ pub struct PositionActorComponent {
     state_component: StateComponent,
     last_packed_snapshot: Snapshot,
     changes: IntMap<Actor, Snapshot>,
-    chunk_changes: VecDeque<ActorChunkChange>,
-    packer: Option<ActorComponentPacker<'static, Position>>,
+    packer: Option<ActorComponentPacker<'static, PositionPacked>>,
+    /// Positions packed for the client being processed.
+    packed: Vec<(Actor, Option<PositionPacked>)>,
     storage: IntMap<Actor, Position>,
+    /// Positions the clients have received with the recent snapshots.
+    history: IntMap<Actor, VecDeque<(Snapshot, Position)>>,
     chunk_actor_component: BTreeSet<(Chunk, Actor)>,
     /// Actors that must have all components packed.
     /// Filled on packing this component.
@@ -141,46 +134,99 @@ impl PositionActorComponent {
             state_component,
             last_packed_snapshot: Snapshot(0),
             changes: IntMap::default(),
-            chunk_changes: VecDeque::new(),
             packer: Some(ActorComponentPacker::new()),
+            packed: Vec::new(),
             storage: IntMap::default(),
+            history: IntMap::default(),
             chunk_actor_component: BTreeSet::new(),
             actors_full_update: IntSet::default(),
             actors_partial_update: IntSet::default(),
         }
     }
 
-    /// Packs all the data for the actors in the chunks.
+    /// Actors currently positioned in the chunk.
+    pub fn chunk_actors(&self, chunk: &Chunk) -> impl Iterator<Item = Actor> + '_ {
+        self.chunk_actor_component
+            .range((*chunk, Actor::MIN) ..= (*chunk, Actor::MAX))
+            .map(|(_, actor)| *actor)
+    }
+
+    /// Records the positions the clients receive with the snapshot, used as the delta baselines,
+    /// and drops the outdated changes. Done once per snapshot.
+    fn start_snapshot(&mut self, snapshot: Snapshot) {
+        if snapshot.0 <= self.last_packed_snapshot.0 {
+            return;
+        }
+
+        let Self {
+            last_packed_snapshot,
+            changes,
+            storage,
+            history,
+            ..
+        } = self;
+
+        // Changes made after the last packing are included as well
+        for (actor, _) in changes
+            .iter()
+            .filter(|(_, change_snapshot)| change_snapshot.0 >= last_packed_snapshot.0)
+        {
+            let Some(position) = storage.get(actor) else {
+                history.remove(actor);
+                continue;
+            };
+
+            let actor_history = history.entry(*actor).or_default();
+
+            if actor_history.back().map(|(_, last)| last) == Some(position) {
+                continue;
+            }
+
+            actor_history.push_back((snapshot, *position));
+
+            // The latest position before the oldest allowed baseline is still needed
+            while actor_history.get(1).is_some_and(|(past_snapshot, _)| {
+                snapshot.0 - past_snapshot.0 >= POSITION_BASELINE_SNAPSHOTS
+            }) {
+                actor_history.pop_front();
+            }
+        }
+
+        changes
+            .retain(move |_, change_snapshot| snapshot.0 - change_snapshot.0 <= MAX_SNAPSHOT_DIFF);
+
+        *last_packed_snapshot = snapshot;
+    }
+
+    /// Packs all the data for the actors.
     /// Saves the list of actors, so other components could do the same.
     pub fn pack_full(
         &mut self,
         state: &mut StatePacker,
+        snapshot: Snapshot,
         player_actor: &Actor,
         // Those will have to have all components packed:
-        full_update_chunks: impl Iterator<Item = Chunk>,
+        full_update_actors: impl Iterator<Item = Actor>,
     ) {
+        self.start_snapshot(snapshot);
+
         self.actors_full_update.clear();
         self.actors_partial_update.clear();
 
-        self.actors_full_update
-            .extend(full_update_chunks.flat_map(|chunk| {
-                // Actors on chunks that were freshly loaded.
-                // TODO?: currently includes Actors that were already loaded in the intersection
-                // of the old chunks and new chunks (persisted chunks) and move simultaniously with
-                // the player to the new (unloaded before) chunks loaded by the player.
-                // This could lead to sending redundant data about these Actors, but this
-                // is a complex edge case that (with removing moved-away-from-view actors in client) could
-                // introduce subtle but serious bugs if we exclude those Actors.
-                self.chunk_actor_component
-                    .range((chunk, Actor::MIN) ..= (chunk, Actor::MAX))
-                    .map(|(_, actor)| (*actor))
-            }));
+        self.actors_full_update.extend(full_update_actors);
+
+        self.packed.clear();
+        self.packed.extend(
+            self.actors_full_update
+                .iter()
+                .filter(|actor| *actor != player_actor)
+                .filter_map(|actor| Some((*actor, Some(self.storage.get(actor)?.pack_absolute())))),
+        );
 
         let change_iter = self
-            .actors_full_update
+            .packed
             .iter()
-            .filter(|actor| *actor != player_actor)
-            .filter_map(|actor| Some((*actor, self.storage.get(actor)?)));
+            .filter_map(|(actor, packed)| Some((*actor, packed.as_ref()?)));
 
         let mut packer = self.packer.take().unwrap();
 
@@ -191,131 +237,84 @@ impl PositionActorComponent {
         self.packer = Some(packer);
     }
 
+    /// Positions of the actors the client already has are sent as the deltas
+    /// against the `last_server_snapshot` if it is recent enough.
     pub fn pack_changes(
         &mut self,
         state: &mut StatePacker,
         snapshot: Snapshot,
         last_server_snapshot: Snapshot,
         player_actor: &Actor,
-        // Checks should include:
-        //     The Actor is within the territory that persists within player's view field
-        //         while player moves from the old position (during last_server_snapshot)
-        //         to the current (during snapshot) one.
-        //         None argument considered to be "out", so must return `false`.
-        is_within_intersection: impl Fn(Option<&Chunk>) -> bool,
-        // Those will have to have all components packed (new chunks):
-        full_update_chunks: impl Iterator<Item = Chunk>,
-        // Those must have changes packed (new/old intersection chunks):
-        partial_update_chunks: impl Iterator<Item = Chunk>,
+        // Those will have to have all components packed (entered the player's view):
+        full_update_actors: impl Iterator<Item = Actor>,
+        // Those must have changes packed (already known to the player):
+        partial_update_actors: impl Iterator<Item = Actor>,
     ) {
-        if snapshot.0 > self.last_packed_snapshot.0 {
-            self.changes.retain(move |_, change_snapshot| {
-                snapshot.0 - change_snapshot.0 <= MAX_SNAPSHOT_DIFF
-            });
-
-            while self.chunk_changes.front().is_some()
-                && snapshot.0 - self.chunk_changes.front().unwrap().snapshot.0 > MAX_SNAPSHOT_DIFF
-            {
-                self.chunk_changes.pop_front();
-            }
-
-            self.last_packed_snapshot = snapshot;
-        }
+        self.start_snapshot(snapshot);
 
         self.actors_full_update.clear();
         self.actors_partial_update.clear();
 
-        self.actors_full_update.extend(
-            full_update_chunks
-                .flat_map(|chunk| {
-                    // Actors on chunks that were freshly loaded.
-                    // TODO?: currently includes Actors that were already loaded in the intersection
-                    // of the old chunks and new chunks (persisted chunks) and move simultaniously with
-                    // the player to the new (unloaded before) chunks loaded by the player.
-                    // This could lead to sending redundant data about these Actors, but this
-                    // is a complex edge case that (with removing moved-away-from-view actors in client) could
-                    // introduce subtle but serious bugs if we exclude those Actors.
-                    self.chunk_actor_component
-                        .range((chunk, Actor::MIN) ..= (chunk, Actor::MAX))
-                        .map(|(_, actor)| (*actor))
-                })
-                .chain(
-                    self.chunk_changes
-                        .iter()
-                        .filter(
-                            |ActorChunkChange {
-                                 snapshot,
-                                 actor,
-                                 previous_chunk,
-                             }| {
-                                // Actors that moved into the intersection (persisted chunks).
-                                //
-                                if last_server_snapshot >= *snapshot {
-                                    // The player already knows about those changes.
-                                    return false;
-                                }
-
-                                if is_within_intersection(previous_chunk.as_ref()) {
-                                    // We only consider Actor moved in.
-                                    return false;
-                                }
-
-                                // Current Actor's chunk:
-                                is_within_intersection(
-                                    self.storage.get(&actor).map(|pos| pos.chunk).as_ref(),
-                                )
-                            },
-                        )
-                        .map(|c| c.actor),
-                ),
+        self.actors_full_update.extend(full_update_actors);
+
+        self.actors_partial_update
+            .extend(partial_update_actors.filter(|actor| !self.actors_full_update.contains(actor)));
+
+        let Self {
+            changes,
+            storage,
+            history,
+            actors_full_update,
+            actors_partial_update,
+            packed,
+            ..
+        } = self;
+
+        let baseline_snapshot = Some(last_server_snapshot)
+            .filter(|baseline| snapshot.0 - baseline.0 < POSITION_BASELINE_SNAPSHOTS);
+
+        let baseline = |actor: &Actor| {
+            let baseline_snapshot = baseline_snapshot?;
+
+            history
+                .get(actor)?
+                .iter()
+                .rev()
+                .find(|(past_snapshot, _)| *past_snapshot <= baseline_snapshot)
+                .map(|(_, position)| position)
+        };
+
+        packed.clear();
+
+        packed.extend(
+            actors_partial_update
+                .iter()
+                .filter_map(|actor| changes.get_key_value(&actor))
+                .filter(move |(_, change_snapshot)| change_snapshot.0 > last_server_snapshot.0)
+                .map(|(actor, _)| actor)
+                .filter(|actor| *actor != player_actor)
+                .map(|actor| {
+                    let packed = storage.get(actor).map(|position| {
+                        match baseline(actor) {
+                            Some(baseline) => position.pack_delta(baseline),
+                            None => position.pack_absolute(),
+                        }
+                    });
+
+                    (*actor, packed)
+                }),
         );
 
-        // Actors that moved out of the intersection (persisted chunks).
-        let actors_moved_away = self
-            .chunk_changes
-            .iter()
-            .filter(
-                |ActorChunkChange {
-                     snapshot,
-                     actor,
-                     previous_chunk,
-                 }| {
-                    if last_server_snapshot >= *snapshot {
-                        // The player already knows about those changes.
-                        return false;
-                    }
-
-                    if !is_within_intersection(previous_chunk.as_ref()) {
-                        // We only consider Actor moved out.
-                        return false;
-                    }
-
-                    // Current Actor's chunk:
-                    !is_within_intersection(self.storage.get(&actor).map(|pos| pos.chunk).as_ref())
-                },
-            )
-            .map(|c| c.actor);
-
-        self.actors_partial_update.extend(
-            partial_update_chunks
-                .flat_map(|chunk| {
-                    self.chunk_actor_component
-                        .range((chunk, Actor::MIN) ..= (chunk, Actor::MAX))
-                        .map(|(_, actor)| (*actor))
-                })
-                .chain(actors_moved_away)
-                .filter(|actor| !self.actors_full_update.contains(actor)),
+        packed.extend(
+            actors_full_update
+                .iter()
+                .filter(|actor| *actor != player_actor)
+                .map(|actor| (*actor, storage.get(actor).map(Position::pack_absolute))),
         );
 
-        let change_iter = self
-            .actors_partial_update
+        let change_iter = packed
             .iter()
-            .filter_map(|actor| self.changes.get_key_value(&actor))
-            .filter(move |(_, change_snapshot)| change_snapshot.0 > last_server_snapshot.0)
-            .map(|(actor, _)| actor)
-            .chain(self.actors_full_update.iter())
-            .filter(|actor| *actor != player_actor)
-            .map(|actor| (*actor, self.storage.get(actor)));
+            .map(|(actor, packed)| (*actor, packed.as_ref()));
 
         let mut packer = self.packer.take().unwrap();
 
@@ -342,25 +341,17 @@ impl PositionActorComponent {
         let prev_value = self.storage.insert(actor, value);
         let value = self.storage.get(&actor).unwrap();
 
-        let (changed, chunk_changed, previous_chunk) = match prev_value {
-            Some(prev_value) => {
-                (
-                    &prev_value != value,
-                    prev_value.chunk != value.chunk,
-                    Some(prev_value.chunk),
-                )
-            },
-            None => (true, true, None),
+        let (changed, previous_chunk) = match prev_value {
+            Some(prev_value) => (&prev_value != value, Some(prev_value.chunk)),
+            None => (true, None),
         };
 
         if changed {
             self.changes.insert(actor, snapshot);
-            if chunk_changed {
-                self.chunk_changes.push_back(ActorChunkChange {
-                    snapshot,
-                    actor,
-                    previous_chunk,
-                });
+            if previous_chunk != Some(value.chunk) {
+                if let Some(previous_chunk) = previous_chunk {
+                    self.chunk_actor_component.remove(&(previous_chunk, actor));
+                }
 
                 self.chunk_actor_component.insert((value.chunk, actor));
             }
@@ -376,7 +367,6 @@ impl PositionActorComponent {
             actor: *i,
             snapshot,
             changes: &mut self.changes,
-            chunk_changes: &mut self.chunk_changes,
             chunk_actor_component: &mut self.chunk_actor_component,
             data: self.storage.get_mut(i)?,
         })
@@ -384,12 +374,6 @@ impl PositionActorComponent {
 
     pub fn remove(&mut self, actor: &Actor, snapshot: Snapshot) {
         if let Some(value) = self.storage.remove(actor) {
-            self.chunk_changes.push_back(ActorChunkChange {
-                snapshot,
-                actor: *actor,
-                previous_chunk: Some(value.chunk),
-            });
-
             self.chunk_actor_component.remove(&(value.chunk, *actor));
 
             self.changes.insert(*actor, snapshot);
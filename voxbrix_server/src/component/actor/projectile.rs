@@ -0,0 +1,12 @@
+use crate::component::actor::ActorComponent;
+use voxbrix_common::entity::action::Action;
+
+pub struct Projectile {
+    /// Downward acceleration, blocks per second squared.
+    pub gravity: f32,
+    /// Action the script of which is run when the projectile hits a block.
+    pub hit_action: Action,
+}
+
+// Actors moved by the projectile physics instead of the regular collision.
+pub type ProjectileActorComponent = ActorComponent<Projectile>;
@@ -11,6 +11,8 @@ use voxbrix_common::{
     system::actor_class_loading::LoadActorClassComponent,
 };
 
+pub mod behavior;
+pub mod health;
 pub mod model;
 
 /// Works as both Actor component and ActorClass component.
@@ -0,0 +1,24 @@
+use serde::Deserialize;
+use voxbrix_common::component::actor_class::ActorClassComponent;
+
+pub type BehaviorActorClassComponent = ActorClassComponent<Behavior>;
+
+/// How the non-player actors of the class choose where to walk.
+/// `speed` is in blocks per second.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(tag = "type")]
+pub enum Behavior {
+    /// Walk to random blocks within the `radius`.
+    Wander { radius: i32, speed: f32 },
+    /// Walk to the closest player within the `radius`, stand still if there is none.
+    FollowPlayer { radius: i32, speed: f32 },
+}
+
+impl Behavior {
+    pub fn speed(&self) -> f32 {
+        match self {
+            Self::Wander { speed, .. } => *speed,
+            Self::FollowPlayer { speed, .. } => *speed,
+        }
+    }
+}
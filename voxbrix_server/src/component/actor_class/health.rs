@@ -0,0 +1,4 @@
+use voxbrix_common::component::actor_class::ActorClassComponent;
+
+/// Maximum health the spawned actors of the class start with.
+pub type HealthActorClassComponent = ActorClassComponent<u32>;
@@ -1,4 +1,6 @@
+pub mod block_entity;
 pub mod class;
+pub mod metadata;
 
 use ahash::{
     AHashMap,
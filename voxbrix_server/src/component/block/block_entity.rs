@@ -0,0 +1,103 @@
+use crate::storage::TypeName;
+use ahash::{
+    AHashMap,
+    AHashSet,
+};
+use voxbrix_common::{
+    component::block::block_entity::{
+        BlockEntity,
+        ChunkBlockEntities,
+    },
+    entity::{
+        block::Block,
+        chunk::Chunk,
+    },
+};
+
+impl TypeName for ChunkBlockEntities {
+    const NAME: &'static str = "ChunkBlockEntities";
+}
+
+/// Block entities of the loaded chunks.
+/// Changes are tracked per block, to be sent to the players viewing the chunk,
+/// and per chunk, to be saved.
+pub struct BlockEntityBlockComponent {
+    changed_blocks: AHashMap<Chunk, AHashSet<Block>>,
+    data: AHashMap<Chunk, ChunkBlockEntities>,
+}
+
+impl BlockEntityBlockComponent {
+    pub fn new() -> Self {
+        Self {
+            changed_blocks: AHashMap::new(),
+            data: AHashMap::new(),
+        }
+    }
+
+    /// Inserting the whole chunk is not tracked
+    pub fn insert_chunk(&mut self, chunk: Chunk, block_entities: ChunkBlockEntities) {
+        self.data.insert(chunk, block_entities);
+    }
+
+    /// Removing the whole chunk is not tracked
+    pub fn remove_chunk(&mut self, chunk: &Chunk) {
+        self.changed_blocks.remove(chunk);
+        self.data.remove(chunk);
+    }
+
+    pub fn get_chunk(&self, chunk: &Chunk) -> Option<&ChunkBlockEntities> {
+        self.data.get(chunk)
+    }
+
+    pub fn get(&self, chunk: &Chunk, block: Block) -> Option<&BlockEntity> {
+        self.data.get(chunk)?.get(block)
+    }
+
+    /// Sets the block entity of the block, `None` removes it.
+    /// Returns `false` if the chunk is not loaded.
+    pub fn set(&mut self, chunk: &Chunk, block: Block, block_entity: Option<BlockEntity>) -> bool {
+        let Some(chunk_block_entities) = self.data.get_mut(chunk) else {
+            return false;
+        };
+
+        let changed = match block_entity {
+            Some(block_entity) => {
+                chunk_block_entities.0.insert(block, block_entity.clone()) != Some(block_entity)
+            },
+            None => chunk_block_entities.0.remove(&block).is_some(),
+        };
+
+        if changed {
+            self.changed_blocks.entry(*chunk).or_default().insert(block);
+        }
+
+        true
+    }
+
+    /// Chunks with the block entities changed since the last `clear_changes`.
+    pub fn changed_chunks(&self) -> impl Iterator<Item = (&Chunk, &ChunkBlockEntities)> {
+        self.changed_blocks
+            .keys()
+            .filter_map(|chunk| Some((chunk, self.data.get(chunk)?)))
+    }
+
+    /// Block entity changes since the last `clear_changes`,
+    /// `None` for the removed ones.
+    pub fn changes(&self) -> impl Iterator<Item = (Chunk, Block, Option<&BlockEntity>)> {
+        self.changed_blocks.iter().flat_map(move |(chunk, blocks)| {
+            let chunk_block_entities = self.data.get(chunk);
+
+            blocks.iter().map(move |block| {
+                (
+                    *chunk,
+                    *block,
+                    chunk_block_entities.and_then(|entities| entities.get(*block)),
+                )
+            })
+        })
+    }
+
+    pub fn clear_changes(&mut self) {
+        self.changed_blocks.clear();
+    }
+}
@@ -0,0 +1,95 @@
+use crate::storage::TypeName;
+use ahash::{
+    AHashMap,
+    AHashSet,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::collections::BTreeMap;
+use voxbrix_common::{
+    entity::{
+        block::Block,
+        chunk::Chunk,
+    },
+    pack::Pack,
+};
+
+/// Script-defined data of the blocks in a chunk, like chest contents or sign text.
+/// Only the blocks that have the metadata are stored.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct ChunkMetadata(BTreeMap<Block, Vec<u8>>);
+
+impl ChunkMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Pack for ChunkMetadata {
+    const DEFAULT_COMPRESSED: bool = true;
+}
+
+impl TypeName for ChunkMetadata {
+    const NAME: &'static str = "ChunkMetadata";
+}
+
+pub struct MetadataBlockComponent {
+    changed_chunks: AHashSet<Chunk>,
+    data: AHashMap<Chunk, ChunkMetadata>,
+}
+
+impl MetadataBlockComponent {
+    pub fn new() -> Self {
+        Self {
+            changed_chunks: AHashSet::new(),
+            data: AHashMap::new(),
+        }
+    }
+
+    /// Inserting the whole chunk is not tracked
+    pub fn insert_chunk(&mut self, chunk: Chunk, metadata: ChunkMetadata) {
+        self.data.insert(chunk, metadata);
+    }
+
+    /// Removing the whole chunk is not tracked
+    pub fn remove_chunk(&mut self, chunk: &Chunk) {
+        self.changed_chunks.remove(chunk);
+        self.data.remove(chunk);
+    }
+
+    pub fn get(&self, chunk: &Chunk, block: Block) -> Option<&[u8]> {
+        self.data.get(chunk)?.0.get(&block).map(|m| m.as_slice())
+    }
+
+    /// Sets the metadata of the block, `None` removes it.
+    /// Returns `false` if the chunk is not loaded.
+    pub fn set(&mut self, chunk: &Chunk, block: Block, metadata: Option<Vec<u8>>) -> bool {
+        let Some(chunk_metadata) = self.data.get_mut(chunk) else {
+            return false;
+        };
+
+        let changed = match metadata {
+            Some(metadata) => {
+                chunk_metadata.0.insert(block, metadata);
+                true
+            },
+            None => chunk_metadata.0.remove(&block).is_some(),
+        };
+
+        if changed {
+            self.changed_chunks.insert(*chunk);
+        }
+
+        true
+    }
+
+    /// Chunks with the metadata changed since the last call.
+    pub fn take_changes(&mut self) -> Vec<(Chunk, ChunkMetadata)> {
+        self.changed_chunks
+            .drain()
+            .filter_map(|chunk| Some((chunk, self.data.get(&chunk)?.clone())))
+            .collect()
+    }
+}
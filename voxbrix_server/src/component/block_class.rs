@@ -1,3 +1,6 @@
+pub mod neighbor_changed;
+pub mod random_tick;
+
 use crate::storage::TypeName;
 use voxbrix_common::{
     component::block::BlocksVec,
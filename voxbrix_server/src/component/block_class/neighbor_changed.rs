@@ -0,0 +1,7 @@
+use voxbrix_common::{
+    component::block_class::BlockClassComponent,
+    entity::script::Script,
+};
+
+/// Script invoked when a neighbor block of the class changes its class.
+pub type NeighborChangedBlockClassComponent = BlockClassComponent<Script>;
@@ -0,0 +1,7 @@
+use voxbrix_common::{
+    component::block_class::BlockClassComponent,
+    entity::script::Script,
+};
+
+/// Script invoked for the randomly selected blocks of the class.
+pub type RandomTickBlockClassComponent = BlockClassComponent<Script>;
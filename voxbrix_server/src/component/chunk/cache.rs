@@ -13,6 +13,11 @@ impl ChunkCache {
     pub fn into_inner(self) -> Arc<Vec<u8>> {
         self.0
     }
+
+    /// Size of the encoded message in bytes.
+    pub fn size(&self) -> usize {
+        self.0.len()
+    }
 }
 
 impl From<Arc<Vec<u8>>> for ChunkCache {
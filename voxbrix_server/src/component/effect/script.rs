@@ -0,0 +1,50 @@
+use anyhow::{
+    Context,
+    Error,
+};
+use nohash_hasher::IntMap;
+use voxbrix_common::{
+    entity::{
+        effect::Effect,
+        script::Script,
+    },
+    LabelMap,
+};
+
+/// Scripts run on the periodic ticks of the effects.
+pub struct ScriptEffectComponent(IntMap<Effect, Script>);
+
+impl ScriptEffectComponent {
+    pub fn new<'a>(
+        effect_script_pairs: impl Iterator<Item = (&'a str, &'a str)>,
+        effect_label_map: &LabelMap<Effect>,
+        script_label_map: &LabelMap<Script>,
+    ) -> Result<Self, Error> {
+        let lookup = |effect_label, script_label| -> Result<_, Error> {
+            let effect = effect_label_map
+                .get(effect_label)
+                .ok_or_else(|| Error::msg("effect is undefined"))?;
+            let script = script_label_map
+                .get(script_label)
+                .ok_or_else(|| Error::msg("script is undefined"))?;
+
+            Ok((effect, script))
+        };
+        let inner = effect_script_pairs
+            .map(|(effect_label, script_label)| {
+                lookup(effect_label, script_label).with_context(|| {
+                    format!(
+                        "while processing effect-script pair(\"{}\": \"{}\")",
+                        effect_label, script_label,
+                    )
+                })
+            })
+            .collect::<Result<IntMap<_, _>, Error>>()?;
+
+        Ok(Self(inner))
+    }
+
+    pub fn get(&self, effect: &Effect) -> Option<&Script> {
+        self.0.get(effect)
+    }
+}
@@ -3,9 +3,14 @@ use nohash_hasher::IntMap;
 
 pub mod actions_packer;
 pub mod actor;
+pub mod actor_relevancy;
+pub mod chunk_stream;
 pub mod chunk_update;
 pub mod chunk_view;
 pub mod client;
+pub mod position_correction;
+pub mod role;
+pub mod username;
 
 pub struct PlayerComponent<T> {
     data: IntMap<Player, T>,
@@ -26,6 +31,14 @@ impl<T> PlayerComponent<T> {
         self.data.get_mut(player)
     }
 
+    pub fn get_mut_or_insert_with(
+        &mut self,
+        player: Player,
+        value_fn: impl FnOnce() -> T,
+    ) -> &mut T {
+        self.data.entry(player).or_insert_with(value_fn)
+    }
+
     pub fn insert(&mut self, player: Player, value: T) -> Option<T> {
         self.data.insert(player, value)
     }
@@ -38,6 +51,10 @@ impl<T> PlayerComponent<T> {
         self.data.iter()
     }
 
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&Player, &mut T)> {
+        self.data.iter_mut()
+    }
+
     pub fn drain(&mut self) -> impl Iterator<Item = (Player, T)> + '_ {
         self.data.drain()
     }
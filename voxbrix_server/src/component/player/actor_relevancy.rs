@@ -0,0 +1,87 @@
+use crate::component::player::PlayerComponent;
+use nohash_hasher::IntMap;
+use voxbrix_common::entity::{
+    actor::Actor,
+    snapshot::Snapshot,
+};
+
+/// Extra chunks around the view radius the actors have to move past to stop being relevant.
+pub const HYSTERESIS_CHUNKS: i32 = 1;
+
+// Actors the player receives the updates of.
+pub type ActorRelevancyPlayerComponent = PlayerComponent<ActorRelevancy>;
+
+/// Actors become relevant within the chunk view radius and stay relevant
+/// until they are further than the radius plus `HYSTERESIS_CHUNKS`,
+/// so that the actors on the edge do not enter and leave the view on every step.
+pub struct ActorRelevancy {
+    /// Relevant actors with the snapshot they have entered on.
+    entered: IntMap<Actor, Snapshot>,
+    /// Actors that are no longer relevant with the snapshot they have left on,
+    /// kept until the client acknowledges the snapshot.
+    left: IntMap<Actor, Snapshot>,
+}
+
+impl ActorRelevancy {
+    pub fn new() -> Self {
+        Self {
+            entered: IntMap::default(),
+            left: IntMap::default(),
+        }
+    }
+
+    /// `entering` are the actors within the view radius,
+    /// `is_relevant` tells if the already relevant actor is still within the hysteresis margin.
+    pub fn update(
+        &mut self,
+        snapshot: Snapshot,
+        last_server_snapshot: Snapshot,
+        entering: impl Iterator<Item = Actor>,
+        is_relevant: impl Fn(&Actor) -> bool,
+    ) {
+        let Self { entered, left } = self;
+
+        for actor in entering {
+            entered.entry(actor).or_insert(snapshot);
+            left.remove(&actor);
+        }
+
+        entered.retain(|actor, _| {
+            let retain = is_relevant(actor);
+            if !retain {
+                left.insert(*actor, snapshot);
+            }
+            retain
+        });
+
+        left.retain(|_, left_snapshot| left_snapshot.0 > last_server_snapshot.0);
+    }
+
+    /// Relevant actors the client may not know about yet, they need all components sent.
+    pub fn full_update(&self, last_server_snapshot: Snapshot) -> impl Iterator<Item = Actor> + '_ {
+        self.entered
+            .iter()
+            .filter(move |(_, entered_snapshot)| entered_snapshot.0 > last_server_snapshot.0)
+            .map(|(actor, _)| *actor)
+    }
+
+    /// Relevant actors the client already has, they only need changes sent.
+    pub fn partial_update(
+        &self,
+        last_server_snapshot: Snapshot,
+    ) -> impl Iterator<Item = Actor> + '_ {
+        self.entered
+            .iter()
+            .filter(move |(_, entered_snapshot)| entered_snapshot.0 <= last_server_snapshot.0)
+            .map(|(actor, _)| *actor)
+    }
+
+    pub fn all(&self) -> impl Iterator<Item = Actor> + '_ {
+        self.entered.keys().copied()
+    }
+
+    /// Actors the client has to remove.
+    pub fn left(&self) -> impl Iterator<Item = Actor> + '_ {
+        self.left.keys().copied()
+    }
+}
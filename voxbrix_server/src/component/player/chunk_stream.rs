@@ -0,0 +1,69 @@
+use crate::component::player::PlayerComponent;
+use ahash::AHashSet;
+use voxbrix_common::{
+    entity::chunk::{
+        Chunk,
+        ChunkRadius,
+    },
+    math::Vec3F32,
+};
+
+// Chunks waiting to be sent to the player.
+pub type ChunkStreamPlayerComponent = PlayerComponent<ChunkStream>;
+
+/// Chunks are sent by priority within the per-tick byte budget,
+/// the rest stay queued for the next ticks.
+pub struct ChunkStream {
+    pending: AHashSet<Chunk>,
+}
+
+impl ChunkStream {
+    pub fn new() -> Self {
+        Self {
+            pending: AHashSet::new(),
+        }
+    }
+
+    pub fn enqueue(&mut self, chunk: Chunk) {
+        self.pending.insert(chunk);
+    }
+
+    pub fn remove(&mut self, chunk: &Chunk) {
+        self.pending.remove(chunk);
+    }
+
+    /// Cancels the chunks the player has moved away from.
+    pub fn retain_within(&mut self, radius: &ChunkRadius) {
+        self.pending.retain(|chunk| radius.is_within(chunk));
+    }
+
+    /// Pending chunks, the most important first.
+    /// The distance to the chunks behind the player counts up to twice as much
+    /// as the distance to the ones in the view direction.
+    pub fn prioritized(&self, center: &Chunk, forward: Vec3F32) -> Vec<Chunk> {
+        let forward = forward.normalize_or_zero();
+
+        let mut chunks = self
+            .pending
+            .iter()
+            .map(|chunk| {
+                let offset = Vec3F32::from(
+                    [0, 1, 2].map(|i| chunk.position[i].saturating_sub(center.position[i]) as f32),
+                );
+
+                let distance = offset.length();
+                let alignment = if distance > 0.0 {
+                    forward.dot(offset / distance)
+                } else {
+                    1.0
+                };
+
+                (*chunk, distance * (1.5 - 0.5 * alignment))
+            })
+            .collect::<Vec<_>>();
+
+        chunks.sort_unstable_by(|(_, priority1), (_, priority2)| priority1.total_cmp(priority2));
+
+        chunks.into_iter().map(|(chunk, _)| chunk).collect()
+    }
+}
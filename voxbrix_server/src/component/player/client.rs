@@ -1,9 +1,14 @@
-use crate::component::player::PlayerComponent;
+use crate::{
+    component::player::PlayerComponent,
+    entity::player::Player,
+};
 use flume::Sender;
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::Instant,
+};
 use voxbrix_common::entity::{
     actor::Actor,
-    chunk::Chunk,
     snapshot::Snapshot,
 };
 use voxbrix_protocol::Channel;
@@ -27,6 +32,7 @@ impl SendData {
 // Client loop input
 pub enum ClientEvent {
     AssignActor { actor: Actor },
+    AssignResumedPlayer { player: Player, actor: Actor },
     SendDataUnreliable { channel: Channel, data: SendData },
     SendDataReliable { channel: Channel, data: SendData },
 }
@@ -37,6 +43,9 @@ pub struct Client {
     pub last_server_snapshot: Snapshot,
     // The last client snapshot received from the client
     pub last_client_snapshot: Snapshot,
-    pub last_confirmed_chunk: Option<Chunk>,
     pub session_id: u64,
+    /// Session of the protocol connection, kept by the resumed connections.
+    pub connection_session: u64,
+    /// Time the client loop has gone, the player waits for the client to resume the connection.
+    pub detached_at: Option<Instant>,
 }
@@ -0,0 +1,5 @@
+use crate::component::player::PlayerComponent;
+use voxbrix_common::messages::client::PositionCorrection;
+
+// Corrections the client has not acknowledged yet.
+pub type PositionCorrectionPlayerComponent = PlayerComponent<PositionCorrection>;
@@ -0,0 +1,62 @@
+use crate::component::player::PlayerComponent;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Actions a player could be allowed to perform.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    PlaceBlock,
+    RemoveBlock,
+    /// Server administration, e.g. changing roles of other players.
+    Administrate,
+}
+
+impl Permission {
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "place_block" => Some(Self::PlaceBlock),
+            "remove_block" => Some(Self::RemoveBlock),
+            "administrate" => Some(Self::Administrate),
+            _ => None,
+        }
+    }
+}
+
+impl From<server_loop_api::Permission> for Permission {
+    fn from(value: server_loop_api::Permission) -> Self {
+        match value {
+            server_loop_api::Permission::PlaceBlock => Self::PlaceBlock,
+            server_loop_api::Permission::RemoveBlock => Self::RemoveBlock,
+            server_loop_api::Permission::Administrate => Self::Administrate,
+        }
+    }
+}
+
+/// Role of the player, saved in the player profile.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Can only look around.
+    Guest,
+    /// Can modify the world.
+    Player,
+    /// Can do anything.
+    Admin,
+}
+
+impl Role {
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        match self {
+            Self::Guest => false,
+            Self::Player => {
+                matches!(permission, Permission::PlaceBlock | Permission::RemoveBlock)
+            },
+            Self::Admin => true,
+        }
+    }
+}
+
+pub type RolePlayerComponent = PlayerComponent<Role>;
@@ -0,0 +1,3 @@
+use crate::component::player::PlayerComponent;
+
+pub type UsernamePlayerComponent = PlayerComponent<String>;
@@ -0,0 +1,238 @@
+//! Server settings, loaded from the `server.toml` file at startup.
+//! Any of the fields could be omitted, the default value is used in that case.
+use crate::{
+    component::player::role::Role,
+    system::rate_limit::RateLimits,
+};
+use anyhow::{
+    Context,
+    Result,
+};
+use log::info;
+use serde::Deserialize;
+use std::{
+    fs,
+    io::ErrorKind as IoErrorKind,
+    net::{
+        Ipv6Addr,
+        SocketAddr,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::Duration,
+};
+use voxbrix_common::{
+    assets::BASE_PACK_PATH,
+    script_registry::{
+        ScriptFailurePolicy,
+        ScriptLimits,
+    },
+};
+use voxbrix_protocol::server::DEFAULT_MAX_CONNECTIONS;
+
+pub const DEFAULT_CONFIG_PATH: &str = "server.toml";
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkStorageKind {
+    /// Chunks are stored in the database.
+    Database,
+    /// Chunks are stored in the region files in `region_directory`.
+    Region,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Name of the server shown to the players in the menu.
+    pub server_name: String,
+    /// Address to accept the client connections on.
+    /// The default IPv6 one also accepts the IPv4 clients, unless `ipv6_only` is set.
+    /// Hosts without IPv6 need an IPv4 address here, e.g. `0.0.0.0:12000`.
+    pub bind_address: SocketAddr,
+    /// Accept only the IPv6 clients on the IPv6 `bind_address`.
+    pub ipv6_only: bool,
+    /// Maximum number of simultaneously connected clients.
+    pub max_connections: usize,
+    /// Interval of broadcasting the server to the clients in the local network,
+    /// in milliseconds. The server is not announced if not set.
+    pub lan_beacon_interval_ms: Option<u64>,
+    /// Clients that sent more malformed packets than that are disconnected.
+    /// If not set, the malformed packets are only dropped.
+    pub max_protocol_violations: Option<u64>,
+    /// Radius of chunks around a player that are loaded and sent to the client.
+    pub player_chunk_view_radius: i32,
+    /// Maximum speed of the players, in blocks per second.
+    /// The positions the clients send are not checked if not set.
+    pub player_max_speed: Option<f32>,
+    /// Players that made more invalid moves than that are kicked.
+    /// If not set, the invalid moves are only corrected.
+    pub max_movement_violations: Option<u64>,
+    /// New actions accepted from a player every tick, the rest are dropped.
+    /// Not limited if not set.
+    pub max_actions_per_tick: Option<usize>,
+    /// Bytes of the messages accepted from a player every tick, the rest are dropped.
+    /// A reliable message over the limit cannot be dropped and disconnects the player.
+    /// Not limited if not set.
+    pub max_received_bytes_per_tick: Option<usize>,
+    /// Players with more actions and messages dropped by the limits above are disconnected,
+    /// one drop is forgiven every tick. If not set, the excess is only dropped.
+    pub max_rate_limit_drops: Option<u64>,
+    /// Interval of the server loop processing, in milliseconds.
+    /// Each tick simulates exactly this much time.
+    pub process_interval_ms: u64,
+    /// Maximum number of ticks run at once to catch up after the slow ones,
+    /// the rest of the missed ticks are dropped.
+    pub max_catch_up_ticks: u32,
+    /// Amount of the chunk data sent to a single player every tick, in bytes.
+    /// The rest of the chunks wait for the next ticks, the nearest ones are sent first.
+    pub chunk_stream_bytes_per_tick: usize,
+    pub database_path: PathBuf,
+    pub chunk_storage: ChunkStorageKind,
+    pub region_directory: PathBuf,
+    /// Interval of flushing the chunk writes to the storage, in milliseconds.
+    /// The writes of the same chunk within it are saved once.
+    pub storage_flush_interval_ms: u64,
+    /// Number of the chunks waiting to be saved that triggers the flush before the interval ends.
+    pub storage_flush_chunks: usize,
+    /// Directory with the base assets, including the scripts.
+    pub asset_directory: PathBuf,
+    /// Directories of the mod packs loaded on top of the base assets,
+    /// each one with a `pack.json` naming the pack and its dependencies.
+    pub pack_directories: Vec<PathBuf>,
+    /// Radius of chunks around a player that receive the player's local chat messages.
+    pub chat_local_radius: i32,
+    /// Role given to the newly registered players.
+    pub default_role: Role,
+    /// Number of random blocks in each active chunk updated by the block class scripts every tick.
+    pub random_ticks_per_chunk: usize,
+    /// Interval of the actor spawning and despawning, in ticks.
+    pub spawn_interval_ticks: u64,
+    /// Radius of chunks around the player spawn position that stay active without players.
+    /// Spawn chunks are activated by players only if not set.
+    pub spawn_chunk_radius: Option<i32>,
+    /// Seed of the newly created world, random if not set.
+    /// The existing worlds keep the seed they were created with.
+    pub world_seed: Option<u64>,
+    /// Length of the day and night cycle, in ticks.
+    pub day_length_ticks: u64,
+    /// Interval of checking the server loop script modules for changes, in milliseconds.
+    /// The changed modules are reloaded without restarting the server.
+    /// Reloading is disabled if not set.
+    pub script_reload_interval_ms: Option<u64>,
+    /// Fuel of a single server loop script run, roughly the number of wasm instructions.
+    /// Runs are not limited if not set.
+    pub script_fuel: Option<u64>,
+    /// Maximum memory size of each server loop script, in bytes.
+    pub script_max_memory_bytes: Option<usize>,
+    /// What happens to the script that ran out of fuel or memory or trapped otherwise.
+    pub script_failure_policy: ScriptFailurePolicy,
+    /// Interval of saving the states of the players in the world, in ticks.
+    /// The states are also saved when the players leave.
+    /// Periodic saving is disabled if zero.
+    pub player_save_interval_ticks: u64,
+    /// Time the player of the lost connection stays in the world, in seconds.
+    /// The client resuming the connection within it gets the same actor back.
+    pub session_grace_period_secs: u64,
+    /// File to record the server loop events to, for `voxbrix_server replay <file>`.
+    /// Recording is disabled if not set.
+    pub replay_record_path: Option<PathBuf>,
+    /// File to write the metrics to in the Prometheus text format.
+    /// Metrics are not written if not set.
+    pub metrics_path: Option<PathBuf>,
+    /// Interval of writing the metrics, in milliseconds.
+    pub metrics_interval_ms: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            server_name: "Voxbrix server".to_owned(),
+            bind_address: (Ipv6Addr::UNSPECIFIED, 12000).into(),
+            ipv6_only: false,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            lan_beacon_interval_ms: Some(2000),
+            max_protocol_violations: Some(32),
+            player_chunk_view_radius: 8,
+            player_max_speed: Some(15.0),
+            max_movement_violations: None,
+            max_actions_per_tick: Some(16),
+            max_received_bytes_per_tick: Some(64 * 1024),
+            max_rate_limit_drops: Some(256),
+            process_interval_ms: 50,
+            max_catch_up_ticks: 4,
+            chunk_stream_bytes_per_tick: 256 * 1024,
+            database_path: "/tmp/voxbrix.db".into(),
+            chunk_storage: ChunkStorageKind::Database,
+            region_directory: "/tmp/voxbrix_regions".into(),
+            storage_flush_interval_ms: 1000,
+            storage_flush_chunks: 256,
+            asset_directory: BASE_PACK_PATH.into(),
+            pack_directories: Vec::new(),
+            chat_local_radius: 4,
+            default_role: Role::Player,
+            random_ticks_per_chunk: 3,
+            spawn_interval_ticks: 100,
+            spawn_chunk_radius: Some(2),
+            world_seed: None,
+            day_length_ticks: 24000,
+            script_reload_interval_ms: None,
+            script_fuel: None,
+            script_max_memory_bytes: None,
+            script_failure_policy: ScriptFailurePolicy::Skip,
+            player_save_interval_ticks: 1200,
+            session_grace_period_secs: 60,
+            replay_record_path: None,
+            metrics_path: None,
+            metrics_interval_ms: 10000,
+        }
+    }
+}
+
+impl Config {
+    /// Load the settings from the file, or use the defaults if the file does not exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = match fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == IoErrorKind::NotFound => {
+                info!("config {:?} not found, using defaults", path);
+                return Ok(Self::default());
+            },
+            Err(err) => {
+                return Err(err).with_context(|| format!("unable to read config {:?}", path));
+            },
+        };
+
+        toml::from_str(&data).with_context(|| format!("unable to parse config {:?}", path))
+    }
+
+    pub fn script_limits(&self) -> ScriptLimits {
+        ScriptLimits {
+            fuel: self.script_fuel,
+            max_memory_bytes: self.script_max_memory_bytes,
+            failure_policy: self.script_failure_policy,
+        }
+    }
+
+    pub fn rate_limits(&self) -> RateLimits {
+        RateLimits {
+            actions_per_tick: self.max_actions_per_tick,
+            bytes_per_tick: self.max_received_bytes_per_tick,
+            max_dropped: self.max_rate_limit_drops,
+        }
+    }
+
+    pub fn process_interval(&self) -> Duration {
+        Duration::from_millis(self.process_interval_ms)
+    }
+
+    pub fn storage_flush_interval(&self) -> Duration {
+        Duration::from_millis(self.storage_flush_interval_ms)
+    }
+
+    pub fn session_grace_period(&self) -> Duration {
+        Duration::from_secs(self.session_grace_period_secs)
+    }
+}
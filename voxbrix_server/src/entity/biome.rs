@@ -0,0 +1,14 @@
+use voxbrix_common::AsFromUsize;
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Debug)]
+pub struct Biome(pub u64);
+
+impl AsFromUsize for Biome {
+    fn as_usize(&self) -> usize {
+        self.0.try_into().unwrap()
+    }
+
+    fn from_usize(i: usize) -> Self {
+        Self(i.try_into().unwrap())
+    }
+}
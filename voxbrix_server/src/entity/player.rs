@@ -18,6 +18,18 @@ impl std::hash::Hash for Player {
 
 impl nohash_hasher::IsEnabled for Player {}
 
+impl From<server_loop_api::Player> for Player {
+    fn from(value: server_loop_api::Player) -> Self {
+        Self(value.0)
+    }
+}
+
+impl From<Player> for server_loop_api::Player {
+    fn from(value: Player) -> Self {
+        Self(value.0)
+    }
+}
+
 impl TypeName for Player {
     const NAME: &'static str = "Player";
 }
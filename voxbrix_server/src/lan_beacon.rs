@@ -0,0 +1,83 @@
+//! Announces the server to the clients in the local network.
+//!
+//! The `LanBeacon` is broadcast to `LAN_BEACON_PORT` every interval, the clients list the servers
+//! they hear from in the menu. Only IPv4 networks are reached.
+use crate::metrics::Metrics;
+use log::{
+    error,
+    info,
+};
+use std::{
+    net::{
+        Ipv4Addr,
+        SocketAddr,
+    },
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    net::UdpSocket,
+    time,
+};
+use voxbrix_common::{
+    messages::{
+        LanBeacon,
+        LAN_BEACON_PORT,
+    },
+    pack::Packer,
+};
+use voxbrix_protocol::PROTOCOL_VERSION;
+
+/// Broadcasts the beacon every interval, never returns.
+/// The player count is the one of the last server loop tick.
+pub async fn broadcast_periodically(
+    name: String,
+    bind_address: SocketAddr,
+    max_players: usize,
+    metrics: Arc<Metrics>,
+    interval: Duration,
+) {
+    let socket = match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await {
+        Ok(socket) => socket,
+        Err(err) => {
+            error!("unable to bind the LAN beacon socket: {:?}", err);
+            return;
+        },
+    };
+
+    if let Err(err) = socket.set_broadcast(true) {
+        error!("unable to enable the LAN beacon broadcast: {:?}", err);
+        return;
+    }
+
+    info!("broadcasting the LAN beacon to port {}", LAN_BEACON_PORT);
+
+    let mut packer = Packer::new();
+    let mut interval = time::interval(interval);
+    let mut is_failing = false;
+
+    loop {
+        interval.tick().await;
+
+        let beacon = packer.pack_to_vec(&LanBeacon {
+            name: name.clone(),
+            port: bind_address.port(),
+            players: metrics.players().try_into().unwrap_or(u32::MAX),
+            max_players: max_players.try_into().unwrap_or(u32::MAX),
+            protocol_version: PROTOCOL_VERSION,
+        });
+
+        match socket
+            .send_to(&beacon, (Ipv4Addr::BROADCAST, LAN_BEACON_PORT))
+            .await
+        {
+            Ok(_) => is_failing = false,
+            // Network could be down for a while, logged once
+            Err(err) if !is_failing => {
+                is_failing = true;
+                error!("unable to broadcast the LAN beacon: {:?}", err);
+            },
+            Err(_) => {},
+        }
+    }
+}
@@ -1,84 +1,177 @@
 use crate::{
+    component::block::metadata::ChunkMetadata,
+    config::{
+        ChunkStorageKind,
+        Config,
+        DEFAULT_CONFIG_PATH,
+    },
     entity::player::Player,
+    metrics::Metrics,
     storage::{
+        archive,
+        label::LabelOrder,
+        migrations,
         player::PlayerProfile,
+        region::RegionStorage,
+        world,
+        BlockEntityStorage,
+        ChunkStorage,
         Data,
         DataSized,
+        MetadataStorage,
+        StructurePlacements,
+        StructureStorage,
     },
+    system::asset_sync::AssetSyncSystem,
+};
+use anyhow::{
+    Error,
+    Result,
 };
-use anyhow::Result;
 use client_loop::ClientLoop;
 use log::{
     error,
+    info,
     warn,
 };
+use pregen::PregenArea;
 use redb::{
     Database,
     TableDefinition,
 };
 use server_loop::{
+    Replay,
     ServerEvent,
     ServerLoop,
 };
 use std::{
     env,
+    path::Path,
     sync::Arc,
     time::Duration,
 };
 use tokio::{
     runtime::Builder as RuntimeBuilder,
+    signal,
     task::{
         self,
         LocalSet,
     },
 };
 use voxbrix_common::{
-    component::block::BlocksVec,
+    component::block::{
+        block_entity::ChunkBlockEntities,
+        BlocksVec,
+    },
     entity::{
         block_class::BlockClass,
         chunk::Chunk,
     },
+    messages::features,
+    system::pack_loading::PackSet,
 };
 use voxbrix_protocol::{
     server::ServerParameters,
     Channel,
+    Features,
+    Ipv6Mode,
 };
 
 const BASE_CHANNEL: Channel = 0;
-const PLAYER_CHUNK_VIEW_RADIUS: i32 = 8;
-const PROCESS_INTERVAL: Duration = Duration::from_millis(50);
 const CLIENT_CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
 const BLOCK_CLASS_TABLE: TableDefinition<DataSized<Chunk>, Data<BlocksVec<BlockClass>>> =
     TableDefinition::new("block_class");
+const BLOCK_METADATA_TABLE: TableDefinition<DataSized<Chunk>, Data<ChunkMetadata>> =
+    TableDefinition::new("block_metadata");
+const BLOCK_ENTITY_TABLE: TableDefinition<DataSized<Chunk>, Data<ChunkBlockEntities>> =
+    TableDefinition::new("block_entity");
 const PLAYER_TABLE: TableDefinition<DataSized<Player>, Data<PlayerProfile>> =
     TableDefinition::new("player");
 const USERNAME_TABLE: TableDefinition<&str, DataSized<Player>> = TableDefinition::new("username");
+const STRUCTURE_PLACEMENT_TABLE: TableDefinition<DataSized<Chunk>, Data<StructurePlacements>> =
+    TableDefinition::new("structure_placement");
+const WORLD_TABLE: TableDefinition<&str, u64> = TableDefinition::new("world");
+const LABEL_TABLE: TableDefinition<&str, Data<LabelOrder>> = TableDefinition::new("label");
+const SCHEMA_TABLE: TableDefinition<&str, u64> = TableDefinition::new("schema");
 
 mod assets;
 mod client_loop;
 mod component;
+mod config;
 mod entity;
+mod lan_beacon;
+mod metrics;
+mod pregen;
 mod server_loop;
 mod storage;
 mod system;
+mod world_rng;
 
 fn main() -> Result<()> {
     env_logger::init();
-    let database = Arc::new(Database::create("/tmp/voxbrix.db")?);
-
-    let write_tx = database.begin_write()?;
-    {
-        // Initialize all tables
-        write_tx.open_table(USERNAME_TABLE)?;
-        write_tx.open_table(PLAYER_TABLE)?;
-        write_tx.open_table(BLOCK_CLASS_TABLE)?;
+
+    let config_path = env::var("VOXBRIX_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_owned());
+    let config = Arc::new(Config::load(Path::new(&config_path))?);
+
+    let database = Arc::new(Database::create(&config.database_path)?);
+
+    migrations::run(&database)?;
+
+    let chunk_storage = match config.chunk_storage {
+        ChunkStorageKind::Database => ChunkStorage::Database(database.clone()),
+        ChunkStorageKind::Region => {
+            ChunkStorage::Region(Arc::new(RegionStorage::open(&config.region_directory)?))
+        },
+    };
+
+    let metadata_storage = MetadataStorage::new(database.clone());
+
+    let block_entity_storage = BlockEntityStorage::new(database.clone());
+
+    let structure_storage = StructureStorage::new(database.clone());
+
+    // `export <file>` writes the world into the archive and exits,
+    // `import <file>` replaces the world with the archive contents before starting the server,
+    // `reseed <seed>` replaces the world seed and exits,
+    // `replay <file>` runs the recorded server loop events instead of accepting the clients,
+    // `pregen <dimension kind> <radius> <x> <y> <z> ...` generates the chunks and exits
+    let mut replay = None;
+    let mut pregen_area = None;
+    let mut args = env::args().skip(1);
+    match (args.next().as_deref(), args.next()) {
+        (Some("export"), Some(path)) => {
+            archive::export(&database, &chunk_storage, Path::new(&path))?;
+            return Ok(());
+        },
+        (Some("import"), Some(path)) => {
+            archive::import(&database, &chunk_storage, Path::new(&path))?;
+        },
+        (Some("reseed"), Some(seed)) => {
+            let seed = seed
+                .parse::<u64>()
+                .map_err(|_| Error::msg(format!("invalid world seed \"{}\"", seed)))?;
+            world::set_seed(&database, seed)?;
+            return Ok(());
+        },
+        (Some("replay"), Some(path)) => {
+            replay = Some(Replay::open(Path::new(&path))?);
+        },
+        (Some("pregen"), Some(dimension_kind)) => {
+            pregen_area = Some(PregenArea::parse(dimension_kind, args.by_ref())?);
+        },
+        (None, _) => {},
+        _ => {
+            return Err(Error::msg(
+                "usage: voxbrix_server [export <file> | import <file> | reseed <seed> | replay \
+                 <file> | pregen <dimension kind> <radius> <x> <y> <z> ...]",
+            ));
+        },
     }
-    write_tx.commit()?;
 
-    let port = env::var("VOXBRIX_PORT")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(12000);
+    let world_seed = match &replay {
+        Some(replay) => replay.header().world_seed,
+        None => world::load_or_create_seed(&database, config.world_seed)?,
+    };
 
     let rt = RuntimeBuilder::new_current_thread()
         .enable_io()
@@ -87,15 +180,89 @@ fn main() -> Result<()> {
         .expect("unable to build runtime");
 
     rt.block_on(LocalSet::new().run_until(async move {
+        let packs = PackSet::load(
+            config.asset_directory.clone(),
+            config.pack_directories.clone(),
+        )
+        .await?;
+
+        for pack in packs.packs() {
+            info!("loading pack \"{}\" from {:?}", pack.label, pack.root);
+        }
+
+        if let Some(area) = pregen_area {
+            return pregen::run(
+                area,
+                database,
+                packs,
+                chunk_storage,
+                structure_storage,
+                world_seed,
+            )
+            .await;
+        }
+
+        let asset_sync_system =
+            Arc::new(AssetSyncSystem::load(database.clone(), packs.clone()).await?);
+
         let (event_tx, event_rx) = local_channel::mpsc::channel();
 
-        {
-            let server = ServerParameters::default()
-                .bind(([0, 0, 0, 0], port))
-                .await?;
+        let metrics = Arc::new(Metrics::new());
+
+        if let Some(path) = config.metrics_path.clone() {
+            task::spawn_local(
+                metrics
+                    .clone()
+                    .write_periodically(path, Duration::from_millis(config.metrics_interval_ms)),
+            );
+        }
+
+        // Replayed clients are not connected
+        if replay.is_none() {
+            if let Some(interval_ms) = config.lan_beacon_interval_ms {
+                task::spawn_local(lan_beacon::broadcast_periodically(
+                    config.server_name.clone(),
+                    config.bind_address,
+                    config.max_connections,
+                    metrics.clone(),
+                    Duration::from_millis(interval_ms),
+                ));
+            }
+
+            {
+                let event_tx = event_tx.clone();
 
+                task::spawn_local(async move {
+                    match signal::ctrl_c().await {
+                        Ok(()) => {
+                            let _ = event_tx.send(ServerEvent::Shutdown);
+                        },
+                        Err(err) => {
+                            error!("unable to listen for the shutdown signal: {:?}", err);
+                        },
+                    }
+                });
+            }
+
+            let server = ServerParameters {
+                max_connections: config.max_connections,
+                features: Features(features::SUPPORTED),
+                required_features: Features(features::REQUIRED),
+                max_protocol_violations: config.max_protocol_violations,
+                ipv6_mode: if config.ipv6_only {
+                    Ipv6Mode::Only
+                } else {
+                    Ipv6Mode::DualStack
+                },
+                ..Default::default()
+            }
+            .bind(config.bind_address)
+            .await?;
+
+            let config = config.clone();
             let database = database.clone();
             let event_tx = event_tx.clone();
+            let metrics = metrics.clone();
 
             task::spawn_local(async move {
                 let mut server = server;
@@ -110,12 +277,20 @@ fn main() -> Result<()> {
 
                     match server.accept().await {
                         Ok(connection) => {
+                            let config = config.clone();
                             let database = database.clone();
+                            let asset_sync_system = asset_sync_system.clone();
+                            let metrics = metrics.clone();
                             let event_tx = event_tx.clone();
 
                             task::spawn_local(async move {
+                                metrics.connection_opened();
+
                                 let result = ClientLoop {
+                                    config,
                                     database,
+                                    asset_sync_system,
+                                    metrics: metrics.clone(),
                                     event_tx,
                                     connection,
                                     session_id,
@@ -123,6 +298,8 @@ fn main() -> Result<()> {
                                 .run()
                                 .await;
 
+                                metrics.connection_closed();
+
                                 match result {
                                     Ok(_) => {
                                         warn!("client loop exited");
@@ -143,7 +320,21 @@ fn main() -> Result<()> {
             });
         }
 
-        ServerLoop { database, event_rx }.run().await;
+        ServerLoop {
+            config,
+            database,
+            packs,
+            chunk_storage,
+            metadata_storage,
+            block_entity_storage,
+            structure_storage,
+            world_seed,
+            event_rx,
+            replay,
+            metrics,
+        }
+        .run()
+        .await;
 
         Ok(())
     }))
@@ -0,0 +1,307 @@
+//! Server metrics in the Prometheus text format.
+//!
+//! The metrics are periodically written into a file, which could be picked up e.g. by the
+//! textfile collector of the Prometheus node exporter.
+use anyhow::{
+    Context,
+    Result,
+};
+use log::error;
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{
+            AtomicU64,
+            Ordering,
+        },
+        Arc,
+        Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    task,
+    time,
+};
+use voxbrix_protocol::ConnectionStats;
+
+/// Number of the latest ticks the duration quantiles are calculated over.
+const TICK_WINDOW: usize = 1200;
+const TICK_QUANTILES: [f64; 4] = [0.5, 0.9, 0.99, 1.0];
+
+/// Counters of the server, shared between the server loop and the client loops.
+#[derive(Default)]
+pub struct Metrics {
+    ticks: AtomicU64,
+    dropped_ticks: AtomicU64,
+    /// Durations of the latest ticks, in microseconds.
+    tick_window: Mutex<Vec<u64>>,
+    tick_time_us: AtomicU64,
+    script_time_us: AtomicU64,
+    active_chunks: AtomicU64,
+    players: AtomicU64,
+    connections: AtomicU64,
+    packets_sent: AtomicU64,
+    packets_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    retransmits: AtomicU64,
+    /// Invalid moves by the username of the player.
+    movement_violations: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Should be called after every server loop tick.
+    pub fn tick(
+        &self,
+        duration: Duration,
+        script_time: Duration,
+        active_chunks: usize,
+        players: usize,
+    ) {
+        let duration_us = duration.as_micros() as u64;
+
+        let ticks = self.ticks.fetch_add(1, Ordering::Relaxed);
+        self.tick_time_us.fetch_add(duration_us, Ordering::Relaxed);
+        self.script_time_us
+            .fetch_add(script_time.as_micros() as u64, Ordering::Relaxed);
+        self.active_chunks
+            .store(active_chunks as u64, Ordering::Relaxed);
+        self.players.store(players as u64, Ordering::Relaxed);
+
+        let mut window = self.tick_window.lock().unwrap();
+
+        if window.len() < TICK_WINDOW {
+            window.push(duration_us);
+        } else {
+            window[ticks as usize % TICK_WINDOW] = duration_us;
+        }
+    }
+
+    /// Players in the world at the last tick.
+    pub fn players(&self) -> u64 {
+        self.players.load(Ordering::Relaxed)
+    }
+
+    /// Ticks missed and not caught up with.
+    pub fn dropped_ticks(&self, count: u32) {
+        self.dropped_ticks
+            .fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn connection_opened(&self) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_closed(&self) {
+        self.connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Adds the traffic of the connection since the `previous` stats.
+    pub fn connection_traffic(&self, previous: &ConnectionStats, current: &ConnectionStats) {
+        let add = |counter: &AtomicU64, previous: u64, current: u64| {
+            counter.fetch_add(current.saturating_sub(previous), Ordering::Relaxed);
+        };
+
+        add(
+            &self.packets_sent,
+            previous.packets_sent,
+            current.packets_sent,
+        );
+        add(
+            &self.packets_received,
+            previous.packets_received,
+            current.packets_received,
+        );
+        add(&self.bytes_sent, previous.bytes_sent, current.bytes_sent);
+        add(
+            &self.bytes_received,
+            previous.bytes_received,
+            current.bytes_received,
+        );
+        add(&self.retransmits, previous.retransmits, current.retransmits);
+    }
+
+    /// The position the player has sent is corrected by the server.
+    pub fn movement_violation(&self, username: &str) {
+        let mut violations = self.movement_violations.lock().unwrap();
+
+        match violations.get_mut(username) {
+            Some(count) => *count += 1,
+            None => {
+                violations.insert(username.to_owned(), 1);
+            },
+        }
+    }
+
+    /// Metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+
+        let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+            let _ = writeln!(output, "# HELP voxbrix_{} {}", name, help);
+            let _ = writeln!(output, "# TYPE voxbrix_{} {}", name, kind);
+            let _ = write!(output, "{}", value);
+        };
+
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let seconds = |us: u64| us as f64 / 1_000_000.0;
+
+        let mut window = self.tick_window.lock().unwrap().clone();
+        window.sort_unstable();
+
+        let mut tick_duration = String::new();
+
+        if !window.is_empty() {
+            for quantile in TICK_QUANTILES {
+                let index = ((window.len() - 1) as f64 * quantile).round() as usize;
+                let _ = writeln!(
+                    tick_duration,
+                    "voxbrix_tick_duration_seconds{{quantile=\"{}\"}} {}",
+                    quantile,
+                    seconds(window[index])
+                );
+            }
+        }
+
+        let _ = writeln!(
+            tick_duration,
+            "voxbrix_tick_duration_seconds_sum {}",
+            seconds(load(&self.tick_time_us))
+        );
+        let _ = writeln!(
+            tick_duration,
+            "voxbrix_tick_duration_seconds_count {}",
+            load(&self.ticks)
+        );
+
+        metric(
+            "tick_duration_seconds",
+            "summary",
+            "Duration of the server loop ticks.",
+            tick_duration,
+        );
+
+        let mut simple = |name: &str, kind: &str, help: &str, value: String| {
+            metric(name, kind, help, format!("voxbrix_{} {}\n", name, value));
+        };
+
+        simple(
+            "dropped_ticks_total",
+            "counter",
+            "Server loop ticks skipped because the server could not catch up.",
+            load(&self.dropped_ticks).to_string(),
+        );
+        simple(
+            "script_time_seconds_total",
+            "counter",
+            "Time spent running the server loop scripts.",
+            seconds(load(&self.script_time_us)).to_string(),
+        );
+        simple(
+            "active_chunks",
+            "gauge",
+            "Chunks active at the last tick.",
+            load(&self.active_chunks).to_string(),
+        );
+        simple(
+            "players",
+            "gauge",
+            "Players in the world at the last tick.",
+            load(&self.players).to_string(),
+        );
+        simple(
+            "connections",
+            "gauge",
+            "Open client connections.",
+            load(&self.connections).to_string(),
+        );
+        simple(
+            "packets_sent_total",
+            "counter",
+            "Packets sent to the clients.",
+            load(&self.packets_sent).to_string(),
+        );
+        simple(
+            "packets_received_total",
+            "counter",
+            "Authenticated packets received from the clients.",
+            load(&self.packets_received).to_string(),
+        );
+        simple(
+            "bytes_sent_total",
+            "counter",
+            "Bytes sent to the clients.",
+            load(&self.bytes_sent).to_string(),
+        );
+        simple(
+            "bytes_received_total",
+            "counter",
+            "Bytes of the authenticated packets received from the clients.",
+            load(&self.bytes_received).to_string(),
+        );
+        simple(
+            "retransmits_total",
+            "counter",
+            "Reliable packets sent again to the clients.",
+            load(&self.retransmits).to_string(),
+        );
+
+        let mut movement_violations = String::new();
+
+        for (username, count) in self.movement_violations.lock().unwrap().iter() {
+            let _ = writeln!(
+                movement_violations,
+                "voxbrix_movement_violations_total{{player=\"{}\"}} {}",
+                username.replace('\\', "\\\\").replace('"', "\\\""),
+                count
+            );
+        }
+
+        metric(
+            "movement_violations_total",
+            "counter",
+            "Player moves corrected by the server, by the player.",
+            movement_violations,
+        );
+
+        output
+    }
+
+    /// Writes the metrics into the file every interval, never returns.
+    /// The file is replaced at once, so the readers never see it partially written.
+    pub async fn write_periodically(self: Arc<Self>, path: PathBuf, interval: Duration) {
+        let mut interval = time::interval(interval);
+
+        loop {
+            interval.tick().await;
+
+            let output = self.render();
+            let path = path.clone();
+
+            let result = task::spawn_blocking(move || -> Result<()> {
+                let temp_path = path.with_extension("tmp");
+
+                fs::write(&temp_path, output)
+                    .with_context(|| format!("unable to write {:?}", temp_path))?;
+                fs::rename(&temp_path, &path)
+                    .with_context(|| format!("unable to replace {:?}", path))?;
+
+                Ok(())
+            })
+            .await
+            .unwrap();
+
+            if let Err(err) = result {
+                error!("unable to write metrics: {:?}", err);
+            }
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! World pre-generation, run with `voxbrix_server pregen <dimension kind> <radius> <x> <y> <z>`.
+//!
+//! Generates the chunks within the radius around the chunk, or around the path through several
+//! chunks if more coordinates follow, and saves them as the server would. The chunks closer to
+//! the first one are generated first. Chunks already in the storage are skipped, so the run
+//! interrupted with Ctrl-C continues where it has stopped the next time.
+use crate::{
+    storage::{
+        label,
+        ChunkStorage,
+        StructureStorage,
+    },
+    system::{
+        biome::BiomeRegistry,
+        chunk_generation::ChunkGenerator,
+    },
+};
+use ahash::AHashSet;
+use anyhow::{
+    Error,
+    Result,
+};
+use log::{
+    error,
+    info,
+};
+use rayon::prelude::*;
+use redb::Database;
+use std::sync::{
+    atomic::{
+        AtomicBool,
+        AtomicUsize,
+        Ordering,
+    },
+    Arc,
+};
+use tokio::{
+    signal,
+    task,
+};
+use voxbrix_common::{
+    assets::{
+        BLOCK_CLASS_LIST_PATH,
+        DIMENSION_KIND_LIST_PATH,
+    },
+    entity::chunk::{
+        Chunk,
+        Dimension,
+    },
+    pack::Packer,
+    system::pack_loading::PackSet,
+};
+
+pub const USAGE: &str = "pregen <dimension kind> <radius> <x> <y> <z> [<x> <y> <z> ...]";
+
+/// Chunks to generate, in the chunk coordinates.
+pub struct PregenArea {
+    dimension_kind: String,
+    radius: i32,
+    /// The area is the path through the points, a single point is fine.
+    points: Vec<[i32; 3]>,
+}
+
+impl PregenArea {
+    pub fn parse(dimension_kind: String, args: impl Iterator<Item = String>) -> Result<Self> {
+        let usage = || Error::msg(format!("usage: voxbrix_server {}", USAGE));
+
+        let mut args = args
+            .map(|arg| arg.parse::<i32>().map_err(|_| usage()))
+            .collect::<Result<Vec<_>>>()?;
+
+        if args.is_empty() {
+            return Err(usage());
+        }
+
+        let radius = args.remove(0);
+
+        if radius < 0 || args.is_empty() || args.len() % 3 != 0 {
+            return Err(usage());
+        }
+
+        let points = args
+            .chunks_exact(3)
+            .map(|coords| [coords[0], coords[1], coords[2]])
+            .collect();
+
+        Ok(Self {
+            dimension_kind,
+            radius,
+            points,
+        })
+    }
+
+    /// Ordered by the distance to the first point.
+    fn chunks(&self, dimension: Dimension) -> Vec<Chunk> {
+        let mut centers = AHashSet::new();
+
+        centers.insert(self.points[0]);
+
+        for segment in self.points.windows(2) {
+            let [start, end] = [segment[0], segment[1]];
+
+            let steps = (0 .. 3)
+                .map(|axis| (end[axis] - start[axis]).abs())
+                .max()
+                .unwrap();
+
+            for step in 1 ..= steps {
+                let ratio = step as f64 / steps as f64;
+
+                centers.insert([0, 1, 2].map(|axis| {
+                    start[axis] + ((end[axis] - start[axis]) as f64 * ratio).round() as i32
+                }));
+            }
+        }
+
+        let chunks = centers
+            .into_iter()
+            .flat_map(|position| {
+                Chunk {
+                    position,
+                    dimension,
+                }
+                .radius(self.radius)
+                .into_iter_simple()
+            })
+            .collect::<AHashSet<_>>();
+
+        let origin = self.points[0];
+
+        let mut chunks = chunks.into_iter().collect::<Vec<_>>();
+
+        chunks.sort_unstable_by_key(|chunk| {
+            let distance: i64 = (0 .. 3)
+                .map(|axis| (chunk.position[axis] as i64 - origin[axis] as i64).pow(2))
+                .sum();
+
+            (distance, chunk.position)
+        });
+
+        chunks
+    }
+}
+
+pub async fn run(
+    area: PregenArea,
+    database: Arc<Database>,
+    packs: PackSet,
+    chunk_storage: ChunkStorage,
+    structure_storage: StructureStorage,
+    world_seed: u64,
+) -> Result<()> {
+    let block_class_label_map = label::load_stable_list(
+        database.clone(),
+        &packs,
+        "block_class",
+        BLOCK_CLASS_LIST_PATH,
+    )
+    .await?
+    .into_label_map();
+
+    let dimension_kind_label_map =
+        label::load_stable_list(database, &packs, "dimension_kind", DIMENSION_KIND_LIST_PATH)
+            .await?
+            .into_label_map();
+
+    let kind = dimension_kind_label_map
+        .get(&area.dimension_kind)
+        .ok_or_else(|| {
+            Error::msg(format!(
+                "dimension kind \"{}\" is undefined",
+                area.dimension_kind
+            ))
+        })?;
+
+    let biome_registry = BiomeRegistry::load(packs.clone(), block_class_label_map.clone()).await?;
+
+    let generator = ChunkGenerator::load(
+        chunk_storage.clone(),
+        structure_storage,
+        world_seed,
+        packs,
+        block_class_label_map,
+        dimension_kind_label_map,
+        Arc::new(biome_registry),
+    )
+    .await;
+
+    let chunks = area.chunks(Dimension { kind, phase: 0 });
+
+    info!("pre-generating {} chunks", chunks.len());
+
+    let aborted = Arc::new(AtomicBool::new(false));
+
+    {
+        let aborted = aborted.clone();
+
+        task::spawn_local(async move {
+            match signal::ctrl_c().await {
+                Ok(()) => {
+                    info!("aborting pre-generation, the chunks in progress will be finished");
+                    aborted.store(true, Ordering::Relaxed);
+                },
+                Err(err) => {
+                    error!("unable to listen for the abort signal: {:?}", err);
+                },
+            }
+        });
+    }
+
+    let worker_aborted = aborted.clone();
+
+    let (generated, skipped) = task::spawn_blocking(move || {
+        let aborted = worker_aborted;
+        let total = chunks.len();
+        let done = AtomicUsize::new(0);
+        let generated = AtomicUsize::new(0);
+        let skipped = AtomicUsize::new(0);
+
+        chunks
+            .par_iter()
+            .for_each_init(Packer::new, |packer, chunk| {
+                if aborted.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                if chunk_storage.load(*chunk, packer).is_some() {
+                    skipped.fetch_add(1, Ordering::Relaxed);
+                } else {
+                    // Placements into the chunks already generated are applied on their activation
+                    generator.generate(*chunk, packer, |_| {});
+                    generated.fetch_add(1, Ordering::Relaxed);
+                }
+
+                let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+
+                if done * 100 / total != (done - 1) * 100 / total {
+                    info!(
+                        "pre-generation {}% done, {} of {} chunks",
+                        done * 100 / total,
+                        done,
+                        total
+                    );
+                }
+            });
+
+        (generated.into_inner(), skipped.into_inner())
+    })
+    .await
+    .unwrap();
+
+    info!(
+        "pre-generation {}, {} chunks generated, {} already existed",
+        if aborted.load(Ordering::Relaxed) {
+            "aborted"
+        } else {
+            "finished"
+        },
+        generated,
+        skipped
+    );
+
+    Ok(())
+}
@@ -1,71 +1,164 @@
 use crate::{
     assets::{
-        ACTION_LIST,
+        ACTION_PERMISSION_MAP,
         ACTION_SCRIPT_MAP,
-        DIMENSION_KIND_LIST,
+        EFFECT_SCRIPT_MAP,
         SERVER_LOOP_SCRIPT_DIR,
         SERVER_LOOP_SCRIPT_LIST,
+        SPAWN_RULES,
     },
     component::{
-        action::script::ScriptActionComponent,
+        action::{
+            permission::PermissionActionComponent,
+            script::ScriptActionComponent,
+        },
         actor::{
             chunk_activation::ChunkActivationActorComponent,
             class::ClassActorComponent,
+            effect::EffectActorComponent,
+            goal::GoalActorComponent,
+            health::HealthActorComponent,
+            inventory::InventoryActorComponent,
             orientation::OrientationActorComponent,
             player::PlayerActorComponent,
             position::PositionActorComponent,
+            projectile::ProjectileActorComponent,
             velocity::VelocityActorComponent,
         },
-        actor_class::model::ModelActorClassComponent,
-        block::class::ClassBlockComponent,
+        actor_class::{
+            behavior::{
+                Behavior,
+                BehaviorActorClassComponent,
+            },
+            health::HealthActorClassComponent,
+            model::ModelActorClassComponent,
+        },
+        block::{
+            block_entity::BlockEntityBlockComponent,
+            class::ClassBlockComponent,
+            metadata::{
+                ChunkMetadata,
+                MetadataBlockComponent,
+            },
+        },
+        block_class::{
+            neighbor_changed::NeighborChangedBlockClassComponent,
+            random_tick::RandomTickBlockClassComponent,
+        },
         chunk::{
             cache::CacheChunkComponent,
-            status::StatusChunkComponent,
+            status::{
+                ChunkStatus,
+                StatusChunkComponent,
+            },
         },
+        effect::script::ScriptEffectComponent,
         player::{
             actions_packer::ActionsPackerPlayerComponent,
             actor::ActorPlayerComponent,
+            actor_relevancy::ActorRelevancyPlayerComponent,
+            chunk_stream::ChunkStreamPlayerComponent,
             chunk_update::ChunkUpdatePlayerComponent,
             chunk_view::ChunkViewPlayerComponent,
             client::{
                 ClientEvent,
                 ClientPlayerComponent,
             },
+            position_correction::PositionCorrectionPlayerComponent,
+            role::{
+                Role,
+                RolePlayerComponent,
+            },
+            username::UsernamePlayerComponent,
         },
     },
+    config::Config,
     entity::{
         actor::ActorRegistry,
         player::Player,
     },
-    storage::StorageThread,
+    metrics::Metrics,
+    storage::{
+        label,
+        player::PlayerState,
+        world,
+        BlockEntityStorage,
+        ChunkStorage,
+        MetadataStorage,
+        StorageThread,
+        StructureStorage,
+    },
     system::{
-        chunk_activation::ChunkActivationSystem,
+        actor_ai::ActorAiSystem,
+        actor_transfer::ActorTransferSystem,
+        biome::BiomeRegistry,
+        chunk_activation::{
+            ChunkActivationSystem,
+            Ticket,
+            TicketSource,
+            MAX_TICKET_RADIUS,
+        },
         chunk_generation::ChunkGenerationSystem,
+        client_script_dispatch::ClientScriptDispatchSystem,
+        damage::DamageSystem,
+        effect::EffectSystem,
+        fluid::FluidSystem,
         map_loading::Map,
+        movement_validation::MovementValidationSystem,
+        neighbor_update::NeighborUpdateSystem,
         position::PositionSystem,
+        projectile::ProjectileSystem,
+        random_tick::RandomTickSystem,
+        rate_limit::RateLimitSystem,
+        script_reload::{
+            self,
+            ScriptReloadSystem,
+        },
+        script_schedule::ScriptScheduleSystem,
+        spawn::SpawnSystem,
     },
+    world_rng::WorldRng,
     BASE_CHANNEL,
-    PROCESS_INTERVAL,
 };
+use admin_command::AdminCommand;
+use ahash::AHashSet;
 use data::{
     EntityRemoveQueue,
     SharedData,
+    SPAWN_POSITION,
 };
 use flume::Sender as SharedSender;
 use futures_lite::stream::{
     self,
+    Stream,
     StreamExt,
 };
 use local_channel::mpsc::Receiver;
+use log::{
+    error,
+    info,
+    warn,
+};
 use player_event::PlayerEvent;
 use process::Process;
+use profiler::{
+    Profiler,
+    Section,
+};
 use redb::Database;
+use replay::ReplayRecorder;
 use std::{
+    pin::Pin,
     sync::Arc,
-    time::Instant,
+    time::{
+        Duration,
+        Instant,
+    },
 };
+use timestep::FixedTimestep;
 use tokio::{
     runtime::Handle,
+    task,
     time::{
         self,
         MissedTickBehavior,
@@ -73,16 +166,36 @@ use tokio::{
 };
 use voxbrix_common::{
     assets::{
+        ACTION_LIST_PATH,
+        ACTOR_CLASS_LIST_PATH,
         ACTOR_MODEL_LIST_PATH,
+        BLOCK_CLASS_LIST_PATH,
+        DIMENSION_KIND_LIST_PATH,
+        EFFECT_LIST_PATH,
+        ITEM_CLASS_LIST_PATH,
         STATE_COMPONENTS_PATH,
     },
-    component::block_class::collision::{
-        Collision,
-        CollisionBlockClassComponent,
+    component::{
+        actor_class::collider::{
+            Collider,
+            ColliderActorClassComponent,
+        },
+        block::block_entity::ChunkBlockEntities,
+        block_class::{
+            collision::{
+                Collision,
+                CollisionBlockClassComponent,
+            },
+            fluid::{
+                Fluid,
+                FluidBlockClassComponent,
+            },
+        },
     },
     compute,
     entity::{
         chunk::Chunk,
+        script::Script,
         snapshot::Snapshot,
     },
     messages::{
@@ -90,13 +203,15 @@ use voxbrix_common::{
         ActionsUnpacker,
         StatePacker,
         StateUnpacker,
+        ADMIN_CHANNEL,
     },
     pack::Packer,
     script_registry::ScriptRegistryBuilder,
     system::{
         actor_class_loading::ActorClassLoadingSystem,
         block_class_loading::BlockClassLoadingSystem,
-        list_loading::List,
+        pack_loading::PackSet,
+        recipe_loading::RecipeRegistry,
     },
     ChunkData,
 };
@@ -104,57 +219,148 @@ use voxbrix_protocol::{
     server::Packet,
     Channel,
 };
+use wasmtime::Module;
 
+mod admin_command;
 mod data;
 mod player_event;
 mod process;
+mod profiler;
+mod replay;
+mod timestep;
+
+pub use replay::{
+    Header as ReplayHeader,
+    Replay,
+};
 
 pub enum SharedEvent {
     ChunkLoaded {
         data: ChunkData,
+        metadata: ChunkMetadata,
+        block_entities: ChunkBlockEntities,
         data_encoded: Arc<Vec<u8>>,
     },
     ChunkGeneration(Chunk),
+    /// Structure placements have been queued for the chunk.
+    StructureQueued(Chunk),
+    /// Script modules have been recompiled after their files had changed.
+    ScriptsReloaded(Vec<(Script, Module)>),
 }
 
 // Server loop input
 pub enum ServerEvent {
     Process,
+    /// Processing of the replay, with the recorded time since the previous one.
+    ReplayedProcess {
+        elapsed: Duration,
+    },
     AddPlayer {
         player: Player,
+        username: String,
+        role: Role,
+        /// Saved state of the player actor, empty for the new players.
+        state: Option<PlayerState>,
+        client_tx: SharedSender<ClientEvent>,
+        session_id: u64,
+        connection_session: u64,
+    },
+    /// Client resumed the protocol connection of the player, the player is looked up by it.
+    ResumePlayer {
+        connection_session: u64,
         client_tx: SharedSender<ClientEvent>,
         session_id: u64,
     },
     PlayerEvent {
         player: Player,
         channel: Channel,
+        /// Sent reliably, the client does not send it again.
+        reliable: bool,
         data: Packet,
         session_id: u64,
     },
     SharedEvent(SharedEvent),
     ServerConnectionClosed,
+    /// Server is stopping, the loop exits and the storage flushes the pending writes.
+    Shutdown,
 }
 
 pub struct ServerLoop {
+    pub config: Arc<Config>,
     pub database: Arc<Database>,
+    pub packs: PackSet,
+    pub chunk_storage: ChunkStorage,
+    pub metadata_storage: MetadataStorage,
+    pub block_entity_storage: BlockEntityStorage,
+    pub structure_storage: StructureStorage,
+    pub world_seed: u64,
     pub event_rx: Receiver<ServerEvent>,
+    /// Recorded events to be handled instead of the `event_rx` ones.
+    pub replay: Option<Replay>,
+    pub metrics: Arc<Metrics>,
 }
 
 impl ServerLoop {
     pub async fn run(self) {
-        let Self { database, event_rx } = self;
+        let Self {
+            config,
+            database,
+            packs,
+            chunk_storage,
+            metadata_storage,
+            block_entity_storage,
+            structure_storage,
+            world_seed,
+            event_rx,
+            replay,
+            metrics,
+        } = self;
+
+        // The replay is not recorded again
+        let recorder = match (&config.replay_record_path, &replay) {
+            (Some(path), None) => {
+                let recorder = ReplayRecorder::create(path, ReplayHeader { world_seed })
+                    .expect("creating replay");
+
+                info!("recording replay to {:?}", path);
+
+                Some(recorder)
+            },
+            _ => None,
+        };
 
         let (shared_event_tx, shared_event_rx) = flume::unbounded();
 
-        let actor_class_loading_system = ActorClassLoadingSystem::load_data()
-            .await
-            .expect("loading actor classes");
+        let actor_class_list = label::load_stable_list(
+            database.clone(),
+            &packs,
+            "actor_class",
+            ACTOR_CLASS_LIST_PATH,
+        )
+        .await
+        .expect("loading actor class list");
 
-        let block_class_loading_system = BlockClassLoadingSystem::load_data()
-            .await
-            .expect("loading block classes");
+        let actor_class_loading_system =
+            ActorClassLoadingSystem::load_data(packs.clone(), actor_class_list)
+                .await
+                .expect("loading actor classes");
+
+        let block_class_list = label::load_stable_list(
+            database.clone(),
+            &packs,
+            "block_class",
+            BLOCK_CLASS_LIST_PATH,
+        )
+        .await
+        .expect("loading block class list");
+
+        let block_class_loading_system =
+            BlockClassLoadingSystem::load_data(packs.clone(), block_class_list)
+                .await
+                .expect("loading block classes");
 
-        let state_components_label_map = List::load(STATE_COMPONENTS_PATH)
+        let state_components_label_map = packs
+            .load_list(STATE_COMPONENTS_PATH)
             .await
             .expect("state component list not found")
             .into_label_map();
@@ -168,6 +374,13 @@ impl ServerLoop {
         let orientation_ac = OrientationActorComponent::new(
             state_components_label_map.get("actor_orientation").unwrap(),
         );
+        let inventory_ac = InventoryActorComponent::new(
+            state_components_label_map.get("actor_inventory").unwrap(),
+        );
+        let health_ac =
+            HealthActorComponent::new(state_components_label_map.get("actor_health").unwrap());
+        let effect_ac =
+            EffectActorComponent::new(state_components_label_map.get("actor_effects").unwrap());
         let player_ac = PlayerActorComponent::new();
         let chunk_activation_ac = ChunkActivationActorComponent::new();
 
@@ -182,7 +395,8 @@ impl ServerLoop {
 
         let position_system = PositionSystem::new();
 
-        let actor_model_label_map = List::load(ACTOR_MODEL_LIST_PATH)
+        let actor_model_label_map = packs
+            .load_list(ACTOR_MODEL_LIST_PATH)
             .await
             .expect("loading actor model label map")
             .into_label_map();
@@ -195,35 +409,139 @@ impl ServerLoop {
             })
             .expect("unable to load model actor class component");
 
+        let mut collider_acc = ColliderActorClassComponent::new();
+
+        actor_class_loading_system
+            .load_component("collider", &mut collider_acc, |desc: Collider| Ok(desc))
+            .expect("unable to load collider actor class component");
+
+        let mut behavior_acc = BehaviorActorClassComponent::new();
+
+        actor_class_loading_system
+            .load_component("behavior", &mut behavior_acc, |desc: Behavior| Ok(desc))
+            .expect("unable to load behavior actor class component");
+
+        let mut health_acc = HealthActorClassComponent::new();
+
+        actor_class_loading_system
+            .load_component("health", &mut health_acc, |desc: u32| Ok(desc))
+            .expect("unable to load health actor class component");
+
         let actor_class_label_map = actor_class_loading_system.into_label_map();
 
         block_class_loading_system
             .load_component("collision", &mut collision_bcc, |desc: Collision| Ok(desc))
             .expect("unable to load collision block class component");
 
-        let block_class_label_map = block_class_loading_system.into_label_map();
+        let mut fluid_bcc = FluidBlockClassComponent::new();
 
-        // TODO
-        let action_label_map = List::load(ACTION_LIST)
+        block_class_loading_system
+            .load_component("fluid", &mut fluid_bcc, |desc: Fluid| Ok(desc))
+            .expect("unable to load fluid block class component");
+
+        let item_class_label_map =
+            label::load_stable_list(database.clone(), &packs, "item_class", ITEM_CLASS_LIST_PATH)
+                .await
+                .expect("loading item class label map")
+                .into_label_map();
+
+        let recipe_registry = RecipeRegistry::load(packs.clone(), item_class_label_map.clone())
             .await
-            .expect("loading actor model label map")
+            .expect("loading recipes");
+
+        // The list is shared with the clients, the actions they send are of the same labels
+        let action_label_map = packs
+            .load_list(ACTION_LIST_PATH)
+            .await
+            .expect("loading action label map")
             .into_label_map();
 
         let mut engine_config = wasmtime::Config::new();
 
         engine_config
             .wasm_multi_value(false)
-            .wasm_multi_memory(false);
+            .wasm_multi_memory(false)
+            .consume_fuel(config.script_fuel.is_some());
 
         let engine = wasmtime::Engine::new(&engine_config).expect("wasm engine failed to start");
 
-        let script_registry = data::setup_script_registry(
-            ScriptRegistryBuilder::load(engine, SERVER_LOOP_SCRIPT_LIST, SERVER_LOOP_SCRIPT_DIR)
-                .await
-                .expect("failed to load scripts"),
-        );
+        let mut script_registry_builder = ScriptRegistryBuilder::load_from_packs(
+            engine,
+            packs.clone(),
+            SERVER_LOOP_SCRIPT_LIST,
+            SERVER_LOOP_SCRIPT_DIR,
+        )
+        .await
+        .expect("failed to load scripts");
+
+        script_registry_builder.set_limits(config.script_limits());
+
+        let script_registry = data::setup_script_registry(script_registry_builder);
+
+        // Scripts would fail when run with the actions they refer to missing
+        {
+            let module_paths = script_registry.module_paths().to_vec();
+            let action_label_map = action_label_map.clone();
+
+            task::spawn_blocking(move || {
+                for path in module_paths.iter() {
+                    script_reload::check_action_labels(path, &action_label_map)?;
+                }
+
+                Ok::<_, anyhow::Error>(())
+            })
+            .await
+            .unwrap()
+            .expect("failed to load scripts");
+        }
+
+        let script_label_map = script_registry.script_label_map().clone();
+
+        if let Some(interval_ms) = config.script_reload_interval_ms {
+            let shared_event_tx = shared_event_tx.clone();
+
+            ScriptReloadSystem::spawn(
+                script_registry.engine().clone(),
+                script_registry.module_paths().to_vec(),
+                action_label_map.clone(),
+                Duration::from_millis(interval_ms),
+                move |modules| {
+                    let _ = shared_event_tx.send(SharedEvent::ScriptsReloaded(modules));
+                },
+            );
+        }
+
+        let mut random_tick_bcc = RandomTickBlockClassComponent::new();
+        let mut neighbor_changed_bcc = NeighborChangedBlockClassComponent::new();
+
+        block_class_loading_system
+            .load_component("random_tick", &mut random_tick_bcc, |desc: String| {
+                script_label_map.get(&desc).ok_or_else(|| {
+                    anyhow::Error::msg(format!("script \"{}\" not found in the script list", desc))
+                })
+            })
+            .expect("unable to load random tick block class component");
+
+        block_class_loading_system
+            .load_component(
+                "neighbor_changed",
+                &mut neighbor_changed_bcc,
+                |desc: String| {
+                    script_label_map.get(&desc).ok_or_else(|| {
+                        anyhow::Error::msg(format!(
+                            "script \"{}\" not found in the script list",
+                            desc
+                        ))
+                    })
+                },
+            )
+            .expect("unable to load neighbor changed block class component");
 
-        let action_script_map = Map::load(ACTION_SCRIPT_MAP)
+        let block_class_label_map = block_class_loading_system.into_label_map();
+
+        let fluid_system = FluidSystem::new(&fluid_bcc, &block_class_label_map);
+
+        let action_script_map = Map::load(packs.clone(), ACTION_SCRIPT_MAP)
             .await
             .expect("failed to load action-script map");
 
@@ -234,16 +552,65 @@ impl ServerLoop {
         )
         .expect("failed to map actions to scripts");
 
-        let dimension_kind_label_map = List::load(DIMENSION_KIND_LIST)
+        let action_permission_map = Map::load(packs.clone(), ACTION_PERMISSION_MAP)
             .await
-            .expect("loading dimension kind label map")
-            .into_label_map();
+            .expect("failed to load action-permission map");
+
+        let permission_action_component =
+            PermissionActionComponent::new(action_permission_map.iter(), &action_label_map)
+                .expect("failed to map actions to permissions");
+
+        let effect_label_map =
+            label::load_stable_list(database.clone(), &packs, "effect", EFFECT_LIST_PATH)
+                .await
+                .expect("loading effect label map")
+                .into_label_map();
+
+        let effect_script_map = Map::load(packs.clone(), EFFECT_SCRIPT_MAP)
+            .await
+            .expect("failed to load effect-script map");
+
+        let script_effect_component = ScriptEffectComponent::new(
+            effect_script_map.iter(),
+            &effect_label_map,
+            script_registry.script_label_map(),
+        )
+        .expect("failed to map effects to scripts");
+
+        let dimension_kind_label_map = label::load_stable_list(
+            database.clone(),
+            &packs,
+            "dimension_kind",
+            DIMENSION_KIND_LIST_PATH,
+        )
+        .await
+        .expect("loading dimension kind label map")
+        .into_label_map();
+
+        let spawn_system = SpawnSystem::load(
+            packs.clone(),
+            SPAWN_RULES,
+            dimension_kind_label_map.clone(),
+            actor_class_label_map.clone(),
+            block_class_label_map.clone(),
+        )
+        .await
+        .expect("loading spawn rules");
+
+        let biome_registry = BiomeRegistry::load(packs.clone(), block_class_label_map.clone())
+            .await
+            .expect("loading biomes");
 
         let shared_event_tx_clone = shared_event_tx.clone();
+        let shared_event_tx_structure = shared_event_tx.clone();
         let chunk_generation_system = ChunkGenerationSystem::new(
-            database.clone(),
+            chunk_storage.clone(),
+            structure_storage.clone(),
+            world_seed,
+            packs.clone(),
             block_class_label_map.clone(),
-            dimension_kind_label_map,
+            dimension_kind_label_map.clone(),
+            Arc::new(biome_registry),
             move |chunk, block_classes, packer| {
                 let data = ChunkData {
                     chunk,
@@ -253,26 +620,81 @@ impl ServerLoop {
                 let data_encoded =
                     Arc::new(packer.pack_to_vec(&ClientAccept::ChunkData(data.clone())));
 
-                let _ = shared_event_tx_clone.send(SharedEvent::ChunkLoaded { data, data_encoded });
+                let _ = shared_event_tx_clone.send(SharedEvent::ChunkLoaded {
+                    data,
+                    metadata: ChunkMetadata::default(),
+                    block_entities: ChunkBlockEntities::default(),
+                    data_encoded,
+                });
+            },
+            move |chunk| {
+                let _ = shared_event_tx_structure.send(SharedEvent::StructureQueued(chunk));
             },
         )
         .await;
 
-        let mut send_status_interval = time::interval(PROCESS_INTERVAL);
+        let mut send_status_interval = time::interval(config.process_interval());
         send_status_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
-        let mut stream = stream::poll_fn(|cx| {
-            send_status_interval
-                .poll_tick(cx)
-                .map(|_| Some(ServerEvent::Process))
-        })
-        .or(event_rx)
-        .or(shared_event_rx.stream().map(ServerEvent::SharedEvent));
+        let mut timestep = FixedTimestep::new(config.process_interval(), config.max_catch_up_ticks);
+
+        let mut stream: Pin<Box<dyn Stream<Item = ServerEvent> + '_>> = match replay {
+            Some(replay) => Box::pin(replay.into_stream(shared_event_rx)),
+            None => {
+                Box::pin(
+                    stream::poll_fn(|cx| {
+                        send_status_interval
+                            .poll_tick(cx)
+                            .map(|_| Some(ServerEvent::Process))
+                    })
+                    .or(event_rx)
+                    .or(shared_event_rx.stream().map(ServerEvent::SharedEvent)),
+                )
+            },
+        };
 
-        let storage = StorageThread::new();
+        let storage = StorageThread::new(
+            chunk_storage.clone(),
+            metadata_storage.clone(),
+            block_entity_storage.clone(),
+            config.storage_flush_interval(),
+            config.storage_flush_chunks,
+        );
+
+        let world_time = world::load_time(&database).expect("loading world time");
+
+        let movement_validation_system =
+            MovementValidationSystem::new(config.player_max_speed, config.process_interval());
+        let rate_limit_system = RateLimitSystem::new(config.rate_limits());
+
+        let mut chunk_activation_system = ChunkActivationSystem::new();
+
+        if let Some(radius) = config.spawn_chunk_radius {
+            let ticket = Ticket {
+                source: TicketSource::SpawnAnchor,
+                center: SPAWN_POSITION.chunk,
+                radius,
+                expires_at: None,
+            };
+
+            if chunk_activation_system.add_ticket(ticket).is_none() {
+                warn!(
+                    "spawn chunk radius {} is out of 0..={}, spawn chunks are not anchored",
+                    radius, MAX_TICKET_RADIUS
+                );
+            }
+        }
 
         let mut shared_data = SharedData {
+            config,
             database,
+            chunk_storage,
+            metadata_storage,
+            block_entity_storage,
+            structure_storage,
+            world_seed,
+            world_time,
+            structure_loading_chunks: AHashSet::new(),
             shared_event_tx,
             packer: Packer::new(),
             actor_registry: ActorRegistry::new(),
@@ -281,38 +703,78 @@ impl ServerLoop {
             actions_packer_pc: ActionsPackerPlayerComponent::new(),
             actor_pc: ActorPlayerComponent::new(),
             chunk_update_pc: ChunkUpdatePlayerComponent::new(),
+            chunk_stream_pc: ChunkStreamPlayerComponent::new(),
+            actor_relevancy_pc: ActorRelevancyPlayerComponent::new(),
             chunk_view_pc: ChunkViewPlayerComponent::new(),
+            role_pc: RolePlayerComponent::new(),
+            username_pc: UsernamePlayerComponent::new(),
+            position_correction_pc: PositionCorrectionPlayerComponent::new(),
 
             class_ac,
             position_ac,
             velocity_ac,
             orientation_ac,
+            inventory_ac,
+            health_ac,
+            effect_ac,
             player_ac,
             chunk_activation_ac,
+            projectile_ac: ProjectileActorComponent::new(),
+            goal_ac: GoalActorComponent::new(),
 
             model_acc,
+            collider_acc,
+            behavior_acc,
+            health_acc,
 
             class_bc,
+            metadata_bc: MetadataBlockComponent::new(),
+            block_entity_bc: BlockEntityBlockComponent::new(),
 
             collision_bcc,
+            fluid_bcc,
+            random_tick_bcc,
+            neighbor_changed_bcc,
 
             status_cc,
             cache_cc,
 
             actor_class_label_map,
             block_class_label_map,
+            item_class_label_map,
+            effect_label_map,
+            action_label_map,
+            recipe_registry,
+            script_label_map,
 
             position_system,
-            chunk_activation_system: ChunkActivationSystem::new(),
+            movement_validation_system,
+            rate_limit_system,
+            actor_ai_system: ActorAiSystem::new(),
+            spawn_system,
+            dimension_kind_label_map: dimension_kind_label_map.clone(),
+            actor_transfer_system: ActorTransferSystem::new(dimension_kind_label_map),
+            client_script_dispatch_system: ClientScriptDispatchSystem::new(),
+            projectile_system: ProjectileSystem::new(),
+            random_tick_system: RandomTickSystem::new(),
+            damage_system: DamageSystem::new(),
+            effect_system: EffectSystem::new(),
+            neighbor_update_system: NeighborUpdateSystem::new(),
+            fluid_system,
+            chunk_activation_system,
             chunk_generation_system,
+            script_schedule_system: ScriptScheduleSystem::new(),
 
             script_registry,
 
             script_action_component,
+            permission_action_component,
+            script_effect_component,
 
             storage,
 
             snapshot: Snapshot(1),
+            world_rng: WorldRng::new(world_seed, Snapshot(1)),
 
             state_packer: StatePacker::new(),
             state_unpacker: StateUnpacker::new(),
@@ -320,6 +782,9 @@ impl ServerLoop {
 
             last_process_time: Instant::now(),
 
+            profiler: Profiler::new(),
+            metrics: metrics.clone(),
+
             remove_queue: EntityRemoveQueue::new(),
         };
 
@@ -327,56 +792,219 @@ impl ServerLoop {
             shared_data.remove_entities();
 
             match event {
-                ServerEvent::Process => {
-                    let rt_handle = Handle::current();
-                    compute!((shared_data) Process {
-                        shared_data: &mut shared_data,
-                        rt_handle,
-                    }.run());
+                ServerEvent::Process | ServerEvent::ReplayedProcess { .. } => {
+                    // Every recorded process is a single tick
+                    let ticks = match event {
+                        ServerEvent::ReplayedProcess { .. } => 1,
+                        _ => {
+                            let due = timestep.advance(Instant::now());
+
+                            if due.dropped > 0 {
+                                metrics.dropped_ticks(due.dropped);
+                            }
+
+                            due.run
+                        },
+                    };
+
+                    for tick in 0 .. ticks {
+                        if tick > 0 {
+                            shared_data.remove_entities();
+                        }
+
+                        let now =
+                            match event {
+                                ServerEvent::ReplayedProcess { elapsed } => {
+                                    shared_data.last_process_time + elapsed
+                                },
+                                _ => {
+                                    let now = Instant::now();
+
+                                    if let Some(recorder) = &recorder {
+                                        recorder.record_process(now.saturating_duration_since(
+                                            shared_data.last_process_time,
+                                        ));
+                                    }
+
+                                    now
+                                },
+                            };
+
+                        let started = Instant::now();
+
+                        let rt_handle = Handle::current();
+                        compute!((shared_data) Process {
+                            shared_data: &mut shared_data,
+                            rt_handle,
+                            now,
+                        }.run());
+
+                        let active_chunks = shared_data
+                            .status_cc
+                            .iter()
+                            .filter(|(_, status)| **status == ChunkStatus::Active)
+                            .count();
+
+                        let duration = started.elapsed();
+                        let script_time = shared_data.script_registry.take_run_time();
+
+                        metrics.tick(
+                            duration,
+                            script_time,
+                            active_chunks,
+                            shared_data.client_pc.iter().count(),
+                        );
+
+                        shared_data.profiler.record(Section::Scripts, script_time);
+                        shared_data.profiler.finish_frame(duration);
+                    }
                 },
                 ServerEvent::AddPlayer {
                     player,
+                    username,
+                    role,
+                    state,
                     client_tx,
                     session_id,
+                    connection_session,
                 } => {
+                    if let Some(recorder) = &recorder {
+                        recorder.record_add_player(
+                            player,
+                            &username,
+                            role,
+                            state.as_ref(),
+                            session_id,
+                            connection_session,
+                        );
+                    }
+
+                    // The player still in the world has the newer state than the saved one
+                    let state = shared_data.player_state(&player).or(state);
+
                     shared_data.remove_player(&player);
-                    shared_data.add_player(player, client_tx, session_id);
+                    shared_data.add_player(
+                        player,
+                        username,
+                        role,
+                        state,
+                        client_tx,
+                        session_id,
+                        connection_session,
+                    );
+                },
+                ServerEvent::ResumePlayer {
+                    connection_session,
+                    client_tx,
+                    session_id,
+                } => {
+                    if let Some(recorder) = &recorder {
+                        recorder.record_resume_player(connection_session, session_id);
+                    }
+
+                    shared_data.resume_player(connection_session, client_tx, session_id);
                 },
                 ServerEvent::PlayerEvent {
                     player,
                     channel,
+                    reliable,
                     data,
                     session_id,
                 } => {
+                    if let Some(recorder) = &recorder {
+                        recorder.record_player_event(
+                            player,
+                            channel,
+                            reliable,
+                            data.as_ref(),
+                            session_id,
+                        );
+                    }
+
                     // Filter out outdated messages
-                    // and other channels
-                    if shared_data
+                    if !shared_data
                         .client_pc
                         .get(&player)
                         .map(|c| c.session_id == session_id)
                         .unwrap_or(false)
-                        && channel == BASE_CHANNEL
                     {
-                        PlayerEvent {
-                            shared_data: &mut shared_data,
-                            player,
-                            data,
+                        continue;
+                    }
+
+                    if !shared_data.rate_limit_system.message(
+                        &player,
+                        shared_data.snapshot,
+                        data.as_ref().len(),
+                        reliable,
+                    ) {
+                        if shared_data.rate_limit_system.is_exceeded(&player) {
+                            shared_data.remove_queue.remove_player(&player);
                         }
-                        .run();
+
+                        continue;
+                    }
+
+                    match channel {
+                        BASE_CHANNEL => {
+                            PlayerEvent {
+                                shared_data: &mut shared_data,
+                                player,
+                                data,
+                            }
+                            .run();
+                        },
+                        ADMIN_CHANNEL => {
+                            AdminCommand {
+                                shared_data: &mut shared_data,
+                                player,
+                                data,
+                            }
+                            .run();
+                        },
+                        _ => {},
                     }
                 },
                 ServerEvent::SharedEvent(event) => {
+                    if let Some(recorder) = &recorder {
+                        recorder.record_shared_event(&event);
+                    }
+
                     match event {
                         SharedEvent::ChunkLoaded {
                             data: chunk_data,
+                            metadata,
+                            block_entities,
                             data_encoded,
-                        } => shared_data.chunk_loaded(chunk_data, data_encoded),
+                        } => {
+                            shared_data.chunk_loaded(
+                                chunk_data,
+                                metadata,
+                                block_entities,
+                                data_encoded,
+                            )
+                        },
                         SharedEvent::ChunkGeneration(chunk) => {
                             shared_data.chunk_generation_system.generate_chunk(chunk);
                         },
+                        SharedEvent::StructureQueued(chunk) => {
+                            shared_data.structure_queued(chunk);
+                        },
+                        SharedEvent::ScriptsReloaded(modules) => {
+                            // Events are handled between the ticks,
+                            // so no script is running at the moment
+                            match shared_data.script_registry.replace_modules(modules) {
+                                Ok(()) => info!("scripts reloaded"),
+                                Err(err) => error!("unable to reload scripts: {:?}", err),
+                            }
+                        },
                     }
                 },
                 ServerEvent::ServerConnectionClosed => return,
+                ServerEvent::Shutdown => {
+                    info!("shutting down");
+                    shared_data.save_players();
+                    return;
+                },
             }
         }
     }
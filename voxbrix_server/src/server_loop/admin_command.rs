@@ -0,0 +1,431 @@
+use crate::{
+    component::{
+        chunk::status::ChunkStatus,
+        player::{
+            client::{
+                ClientEvent,
+                SendData,
+            },
+            role::Permission,
+        },
+    },
+    entity::player::Player,
+    server_loop::{
+        data::SharedData,
+        SharedEvent,
+    },
+    system::{
+        bulk_edit,
+        chunk_activation::{
+            Ticket,
+            TicketId,
+            TicketSource,
+        },
+        script_reload::ScriptReloadSystem,
+    },
+};
+use log::{
+    debug,
+    info,
+};
+use voxbrix_common::{
+    component::actor::position::Position,
+    entity::{
+        block::Block,
+        chunk::Chunk,
+        snapshot::Snapshot,
+    },
+    math::Vec3F32,
+    messages::{
+        client::AdminResponse,
+        server::AdminCommand as AdminCommandMessage,
+        ADMIN_CHANNEL,
+    },
+};
+use voxbrix_protocol::server::Packet;
+
+const USAGE: &str = "commands: kick <player>, teleport <player> <x> <y> <z>, give <player> <item> \
+                     [amount], setblock <x> <y> <z> <block>, fill <x1> <y1> <z1> <x2> <y2> <z2> \
+                     <block>, forceload <x> <y> <z> <radius> [ticks], unforceload <ticket>, \
+                     tickets, time [set <ticks>], save-all, reload-scripts, profile";
+
+/// Command sent by a player over the admin channel.
+/// The player must have the `Administrate` permission.
+pub struct AdminCommand<'a> {
+    pub shared_data: &'a mut SharedData,
+    pub player: Player,
+    pub data: Packet,
+}
+
+impl AdminCommand<'_> {
+    pub fn run(self) {
+        let Self {
+            shared_data: sd,
+            player,
+            data,
+        } = self;
+
+        let Ok(AdminCommandMessage { command }) = sd.packer.unpack(data.as_ref()) else {
+            debug!("server_loop: unable to parse admin command of {:?}", player);
+            return;
+        };
+
+        let result = if sd
+            .role_pc
+            .get(&player)
+            .is_some_and(|role| role.has_permission(Permission::Administrate))
+        {
+            let args = command.split_whitespace().collect::<Vec<_>>();
+
+            match args.as_slice() {
+                ["kick", username] => kick(sd, username),
+                ["teleport", username, x, y, z] => teleport(sd, username, [*x, *y, *z]),
+                ["give", username, item] => give(sd, username, item, "1"),
+                ["give", username, item, amount] => give(sd, username, item, amount),
+                ["setblock", x, y, z, block] => set_block(sd, &player, [*x, *y, *z], block),
+                ["fill", x1, y1, z1, x2, y2, z2, block] => {
+                    fill(sd, &player, [*x1, *y1, *z1], [*x2, *y2, *z2], block)
+                },
+                ["forceload", x, y, z, radius] => {
+                    forceload(sd, &player, [*x, *y, *z], radius, None)
+                },
+                ["forceload", x, y, z, radius, ticks] => {
+                    forceload(sd, &player, [*x, *y, *z], radius, Some(ticks))
+                },
+                ["unforceload", ticket] => unforceload(sd, ticket),
+                ["tickets"] => Ok(tickets(sd)),
+                ["time"] => Ok(time(sd)),
+                ["time", "set", value] => set_time(sd, value),
+                ["save-all"] => Ok(save_all(sd)),
+                ["reload-scripts"] => Ok(reload_scripts(sd)),
+                ["profile"] => Ok(sd.profiler.dump()),
+                _ => Err(USAGE.to_owned()),
+            }
+        } else {
+            Err("permission denied".to_owned())
+        };
+
+        if let Ok(text) = &result {
+            info!(
+                "player {:?} ran admin command \"{}\": {}",
+                player, command, text
+            );
+        }
+
+        let response = match result {
+            Ok(text) => {
+                AdminResponse {
+                    success: true,
+                    text,
+                }
+            },
+            Err(text) => {
+                AdminResponse {
+                    success: false,
+                    text,
+                }
+            },
+        };
+
+        let Some(client) = sd.client_pc.get(&player) else {
+            return;
+        };
+
+        if client
+            .tx
+            .send(ClientEvent::SendDataReliable {
+                channel: ADMIN_CHANNEL,
+                data: SendData::Owned(sd.packer.pack_to_vec(&response)),
+            })
+            .is_err()
+        {
+            sd.remove_queue.remove_player(&player);
+        }
+    }
+}
+
+fn find_player(sd: &SharedData, username: &str) -> Result<Player, String> {
+    sd.username_pc
+        .iter()
+        .find(|(_, name)| name.as_str() == username)
+        .map(|(player, _)| *player)
+        .ok_or_else(|| format!("player \"{}\" is not online", username))
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("incorrect number \"{}\"", value))
+}
+
+/// Chunk and block by the global block coordinates in the dimension of the player.
+fn global_block(
+    sd: &SharedData,
+    player: &Player,
+    coords: [&str; 3],
+) -> Result<(Chunk, Block), String> {
+    let dimension = sd
+        .actor_pc
+        .get(player)
+        .and_then(|actor| sd.position_ac.get(actor))
+        .map(|position| position.chunk.dimension)
+        .ok_or_else(|| "player has no position".to_owned())?;
+
+    let [x, y, z] = coords;
+
+    let origin = Chunk {
+        position: [0, 0, 0],
+        dimension,
+    };
+
+    Block::from_chunk_offset(origin, [parse(x)?, parse(y)?, parse(z)?])
+        .ok_or_else(|| "coordinates are out of the world".to_owned())
+}
+
+fn kick(sd: &mut SharedData, username: &str) -> Result<String, String> {
+    let target = find_player(sd, username)?;
+
+    // Dropping the channel closes the client loop
+    sd.remove_player(&target);
+
+    Ok(format!("kicked \"{}\"", username))
+}
+
+fn teleport(sd: &mut SharedData, username: &str, coords: [&str; 3]) -> Result<String, String> {
+    let target = find_player(sd, username)?;
+    let (chunk, block) = global_block(sd, &target, coords)?;
+
+    let actor = *sd
+        .actor_pc
+        .get(&target)
+        .ok_or_else(|| "player has no actor".to_owned())?;
+
+    let [x, y, z] = block.into_coords();
+
+    // Standing in the middle of the block
+    let position = Position {
+        chunk,
+        offset: Vec3F32::new(x as f32 + 0.5, y as f32 + 0.5, z as f32),
+    };
+
+    sd.transfer_actor(actor, position);
+
+    Ok(format!("teleported \"{}\"", username))
+}
+
+fn give(sd: &mut SharedData, username: &str, item: &str, amount: &str) -> Result<String, String> {
+    let target = find_player(sd, username)?;
+    let amount = parse::<u32>(amount)?;
+
+    let item_class = sd
+        .item_class_label_map
+        .get(item)
+        .ok_or_else(|| format!("item class \"{}\" is undefined", item))?;
+
+    let actor = *sd
+        .actor_pc
+        .get(&target)
+        .ok_or_else(|| "player has no actor".to_owned())?;
+
+    let mut inventory = sd
+        .inventory_ac
+        .get(&actor)
+        .cloned()
+        .ok_or_else(|| "player has no inventory".to_owned())?;
+
+    let left = inventory.add(item_class, amount);
+
+    sd.inventory_ac.insert(actor, inventory, sd.snapshot);
+
+    Ok(format!(
+        "gave {} \"{}\" to \"{}\"",
+        amount - left,
+        item,
+        username
+    ))
+}
+
+fn set_block(
+    sd: &mut SharedData,
+    player: &Player,
+    coords: [&str; 3],
+    block_class: &str,
+) -> Result<String, String> {
+    let (chunk, block) = global_block(sd, player, coords)?;
+
+    let block_class = sd
+        .block_class_label_map
+        .get(block_class)
+        .ok_or_else(|| format!("block class \"{}\" is undefined", block_class))?;
+
+    let Some(mut classes) = sd.class_bc.get_mut_chunk(&chunk) else {
+        return Err("chunk is not loaded".to_owned());
+    };
+
+    classes.set(block, block_class);
+
+    // Metadata and the block entity belong to the replaced block
+    sd.metadata_bc.set(&chunk, block, None);
+    sd.block_entity_bc.set(&chunk, block, None);
+
+    Ok("block set".to_owned())
+}
+
+fn fill(
+    sd: &mut SharedData,
+    player: &Player,
+    coords_a: [&str; 3],
+    coords_b: [&str; 3],
+    block_class: &str,
+) -> Result<String, String> {
+    let corner_a = global_block(sd, player, coords_a)?;
+    let corner_b = global_block(sd, player, coords_b)?;
+
+    let block_class = sd
+        .block_class_label_map
+        .get(block_class)
+        .ok_or_else(|| format!("block class \"{}\" is undefined", block_class))?;
+
+    let changed = bulk_edit::fill_region(
+        &mut sd.class_bc,
+        &mut sd.metadata_bc,
+        &mut sd.block_entity_bc,
+        corner_a,
+        corner_b,
+        block_class,
+    )
+    .map_err(|err| err.to_string())?;
+
+    Ok(format!("{} blocks changed", changed))
+}
+
+/// Keeps the chunks around the block active, for `ticks` or until removed.
+fn forceload(
+    sd: &mut SharedData,
+    player: &Player,
+    coords: [&str; 3],
+    radius: &str,
+    ticks: Option<&str>,
+) -> Result<String, String> {
+    let (chunk, _) = global_block(sd, player, coords)?;
+    let radius = parse(radius)?;
+
+    let expires_at = match ticks {
+        Some(ticks) => Some(Snapshot(sd.snapshot.0 + parse::<u64>(ticks)?)),
+        None => None,
+    };
+
+    let ticket = Ticket {
+        source: TicketSource::Admin,
+        center: chunk,
+        radius,
+        expires_at,
+    };
+
+    let id = sd
+        .chunk_activation_system
+        .add_ticket(ticket)
+        .ok_or_else(|| format!("radius {} is out of range", radius))?;
+
+    Ok(format!("added ticket {}", id.0))
+}
+
+fn unforceload(sd: &mut SharedData, ticket: &str) -> Result<String, String> {
+    let id = TicketId(parse(ticket)?);
+
+    if !sd.chunk_activation_system.remove_ticket(id) {
+        return Err(format!("ticket {} does not exist", id.0));
+    }
+
+    Ok(format!("removed ticket {}", id.0))
+}
+
+fn tickets(sd: &SharedData) -> String {
+    let mut tickets = sd.chunk_activation_system.tickets().collect::<Vec<_>>();
+
+    tickets.sort_unstable_by_key(|(id, _)| id.0);
+
+    let mut text = format!("{} tickets", tickets.len());
+
+    for (id, ticket) in tickets {
+        text.push_str(&format!(
+            "\n{}: {:?} chunk {:?} in {:?} radius {}",
+            id.0, ticket.source, ticket.center.position, ticket.center.dimension, ticket.radius
+        ));
+
+        if let Some(expires_at) = ticket.expires_at {
+            text.push_str(&format!(
+                ", {} ticks left",
+                expires_at.0.saturating_sub(sd.snapshot.0)
+            ));
+        }
+    }
+
+    text
+}
+
+fn time(sd: &SharedData) -> String {
+    format!(
+        "world time is {}, day {} tick {}",
+        sd.world_time,
+        sd.world_time / sd.config.day_length_ticks.max(1),
+        sd.world_time % sd.config.day_length_ticks.max(1),
+    )
+}
+
+/// The time of the day changes for everyone at once.
+fn set_time(sd: &mut SharedData, value: &str) -> Result<String, String> {
+    sd.world_time = parse(value)?;
+
+    let players = sd
+        .client_pc
+        .iter()
+        .map(|(player, _)| *player)
+        .collect::<Vec<_>>();
+
+    for player in players {
+        sd.send_world_time(&player);
+    }
+
+    Ok(format!("world time set to {}", sd.world_time))
+}
+
+/// Block classes are saved every tick they change,
+/// this only writes all the active chunks once again.
+fn save_all(sd: &mut SharedData) -> String {
+    let mut amount = 0;
+
+    for (chunk, status) in sd.status_cc.iter() {
+        if *status != ChunkStatus::Active {
+            continue;
+        }
+
+        let Some(block_classes) = sd.class_bc.get_chunk(chunk) else {
+            continue;
+        };
+
+        sd.storage.save_block_classes(*chunk, block_classes.clone());
+
+        amount += 1;
+    }
+
+    sd.storage.flush();
+
+    format!("saving {} chunks", amount)
+}
+
+/// Recompiles all the script modules, they are swapped in once compiled.
+fn reload_scripts(sd: &mut SharedData) -> String {
+    let shared_event_tx = sd.shared_event_tx.clone();
+
+    ScriptReloadSystem::reload_all(
+        sd.script_registry.engine().clone(),
+        sd.script_registry.module_paths().to_vec(),
+        sd.action_label_map.clone(),
+        move |modules| {
+            let _ = shared_event_tx.send(SharedEvent::ScriptsReloaded(modules));
+        },
+    );
+
+    "reloading scripts".to_owned()
+}
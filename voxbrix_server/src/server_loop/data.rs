@@ -1,19 +1,42 @@
 use crate::{
     component::{
-        action::script::ScriptActionComponent,
+        action::{
+            permission::PermissionActionComponent,
+            script::ScriptActionComponent,
+        },
         actor::{
             chunk_activation::{
                 ActorChunkActivation,
                 ChunkActivationActorComponent,
             },
             class::ClassActorComponent,
+            effect::EffectActorComponent,
+            goal::GoalActorComponent,
+            health::HealthActorComponent,
+            inventory::InventoryActorComponent,
             orientation::OrientationActorComponent,
             player::PlayerActorComponent,
             position::PositionActorComponent,
+            projectile::ProjectileActorComponent,
             velocity::VelocityActorComponent,
         },
-        actor_class::model::ModelActorClassComponent,
-        block::class::ClassBlockComponent,
+        actor_class::{
+            behavior::BehaviorActorClassComponent,
+            health::HealthActorClassComponent,
+            model::ModelActorClassComponent,
+        },
+        block::{
+            block_entity::BlockEntityBlockComponent,
+            class::ClassBlockComponent,
+            metadata::{
+                ChunkMetadata,
+                MetadataBlockComponent,
+            },
+        },
+        block_class::{
+            neighbor_changed::NeighborChangedBlockClassComponent,
+            random_tick::RandomTickBlockClassComponent,
+        },
         chunk::{
             cache::CacheChunkComponent,
             status::{
@@ -21,10 +44,19 @@ use crate::{
                 StatusChunkComponent,
             },
         },
+        effect::script::ScriptEffectComponent,
         player::{
             actions_packer::ActionsPackerPlayerComponent,
             actor::ActorPlayerComponent,
-            chunk_update::ChunkUpdatePlayerComponent,
+            actor_relevancy::ActorRelevancyPlayerComponent,
+            chunk_stream::{
+                ChunkStream,
+                ChunkStreamPlayerComponent,
+            },
+            chunk_update::{
+                ChunkUpdate,
+                ChunkUpdatePlayerComponent,
+            },
             chunk_view::{
                 ChunkView,
                 ChunkViewPlayerComponent,
@@ -35,31 +67,99 @@ use crate::{
                 ClientPlayerComponent,
                 SendData,
             },
+            position_correction::PositionCorrectionPlayerComponent,
+            role::{
+                Role,
+                RolePlayerComponent,
+            },
+            username::UsernamePlayerComponent,
         },
     },
+    config::Config,
     entity::{
         actor::ActorRegistry,
         player::Player,
     },
-    server_loop::SharedEvent,
-    storage::StorageThread,
+    metrics::Metrics,
+    server_loop::{
+        profiler::Profiler,
+        SharedEvent,
+    },
+    storage::{
+        player::{
+            self,
+            PlayerState,
+        },
+        BlockEntityStorage,
+        ChunkStorage,
+        MetadataStorage,
+        StorageThread,
+        StructureStorage,
+    },
     system::{
-        chunk_activation::ChunkActivationSystem,
+        actor_ai::ActorAiSystem,
+        actor_transfer::ActorTransferSystem,
+        bulk_edit,
+        chunk_activation::{
+            ChunkActivationSystem,
+            Ticket,
+            TicketId,
+            TicketSource,
+        },
         chunk_generation::ChunkGenerationSystem,
+        client_script_dispatch::ClientScriptDispatchSystem,
+        damage::DamageSystem,
+        effect::EffectSystem,
+        fluid::FluidSystem,
+        movement_validation::MovementValidationSystem,
+        neighbor_update::NeighborUpdateSystem,
         position::PositionSystem,
+        projectile::ProjectileSystem,
+        random_tick::RandomTickSystem,
+        rate_limit::RateLimitSystem,
+        script_schedule::ScriptScheduleSystem,
+        spawn::SpawnSystem,
     },
+    world_rng::WorldRng,
     BASE_CHANNEL,
-    PLAYER_CHUNK_VIEW_RADIUS,
 };
+use ahash::AHashSet;
 use flume::Sender;
-use log::debug;
+use log::{
+    debug,
+    warn,
+};
 use nohash_hasher::IntSet;
 use redb::Database;
 use server_loop_api::{
     ActionInput,
+    ActorEffectRequest,
+    ActorHealth,
+    AddChunkTicketRequest,
+    ApplyEffectRequest,
+    BlockFilter,
+    CastBoxRequest,
+    CastBoxResponse,
+    CountItemsRequest,
+    DamageActorRequest,
+    DispatchClientScriptRequest,
+    FillRegionRequest,
+    GetBlockEntityRequest,
+    GetBlockMetadataRequest,
+    GetRecipeResponse,
     GetTargetBlockRequest,
     GetTargetBlockResponse,
+    ItemsRequest,
+    OverlapSphereRequest,
+    PlayerHasPermissionRequest,
+    RaycastRequest,
+    RaycastResponse,
+    RecipeIngredient,
+    ScheduleScriptRequest,
+    SetBlockEntityRequest,
+    SetBlockMetadataRequest,
     SetClassOfBlockRequest,
+    TransferActorRequest,
 };
 use std::{
     mem,
@@ -68,18 +168,60 @@ use std::{
 };
 use voxbrix_common::{
     component::{
-        actor::position::Position,
-        block_class::collision::CollisionBlockClassComponent,
+        actor::{
+            effect::{
+                ActiveEffects,
+                EffectInstance,
+            },
+            health::{
+                Health,
+                PLAYER_MAX_HEALTH,
+            },
+            inventory::{
+                Inventory,
+                PLAYER_INVENTORY_SIZE,
+            },
+            position::Position,
+            velocity::Velocity,
+        },
+        actor_class::collider::ColliderActorClassComponent,
+        block::block_entity::{
+            BlockEntity,
+            ChunkBlockEntities,
+        },
+        block_class::{
+            collision::CollisionBlockClassComponent,
+            fluid::FluidBlockClassComponent,
+        },
     },
     entity::{
+        action::Action,
         actor::Actor,
         actor_class::ActorClass,
-        block::BLOCKS_IN_CHUNK_EDGE,
+        block::{
+            Block,
+            BLOCKS_IN_CHUNK_EDGE,
+        },
         block_class::BlockClass,
-        chunk::Chunk,
+        chunk::{
+            Chunk,
+            Dimension,
+            DimensionKind,
+        },
+        effect::Effect,
+        item_class::ItemClass,
+        script::Script,
         snapshot::Snapshot,
     },
+    math::{
+        query,
+        Vec3F32,
+    },
     messages::{
+        client::{
+            ClientAccept,
+            PositionCorrection,
+        },
         ActionsPacker,
         ActionsUnpacker,
         StatePacker,
@@ -94,13 +236,36 @@ use voxbrix_common::{
         ScriptData,
         ScriptRegistry,
         ScriptRegistryBuilder,
+        SendMutPtr,
+        SendPtr,
+    },
+    system::{
+        position,
+        recipe_loading::{
+            Ingredient,
+            RecipeRegistry,
+        },
     },
-    system::position,
     ChunkData,
     LabelMap,
 };
 use wasmtime::Caller;
 
+/// Gap left between the spawned actor and the block beneath.
+const SPAWN_GAP: f32 = 0.01;
+
+/// Where the players respawn, same as the initial position of the client.
+pub const SPAWN_POSITION: Position = Position {
+    chunk: Chunk {
+        position: [0, 0, 0],
+        dimension: Dimension {
+            kind: DimensionKind(0),
+            phase: 0,
+        },
+    },
+    offset: Vec3F32::new(0.0, 0.0, 4.0),
+};
+
 pub struct EntityRemoveQueue(Option<EntityRemoveQueueInner>);
 
 struct EntityRemoveQueueInner {
@@ -118,6 +283,11 @@ impl EntityRemoveQueueInner {
         })
     }
 
+    fn remove_actor(&mut self, actor: &Actor) {
+        self.actors.insert(*actor);
+        self.is_not_empty = true;
+    }
+
     fn remove_player(&mut self, player: &Player) {
         self.players.insert(*player);
         self.is_not_empty = true;
@@ -129,6 +299,13 @@ impl EntityRemoveQueue {
         Self(EntityRemoveQueueInner::new())
     }
 
+    pub fn remove_actor(&mut self, actor: &Actor) {
+        self.0
+            .as_mut()
+            .expect("EntityRemoveQueue is taken")
+            .remove_actor(actor)
+    }
+
     pub fn remove_player(&mut self, player: &Player) {
         self.0
             .as_mut()
@@ -145,36 +322,6 @@ impl EntityRemoveQueue {
     }
 }
 
-pub struct SendMutPtr<T>(*mut T);
-unsafe impl<T> Send for SendMutPtr<T> where T: Send {}
-
-impl<T> SendMutPtr<T> {
-    pub fn new(value: &mut T) -> Self {
-        Self(value)
-    }
-
-    pub unsafe fn get<'a>(&'a self) -> &'a T {
-        &*self.0
-    }
-
-    pub unsafe fn get_mut<'a>(&'a mut self) -> &'a mut T {
-        &mut *self.0
-    }
-}
-
-pub struct SendPtr<T>(*const T);
-unsafe impl<T> Send for SendPtr<T> where T: Sync {}
-
-impl<T> SendPtr<T> {
-    pub fn new(value: &T) -> Self {
-        Self(value)
-    }
-
-    pub unsafe fn get<'a>(&'a self) -> &'a T {
-        unsafe { &*self.0 }
-    }
-}
-
 /// For safely retrieving the data:
 /// 1. Make sure that the pointers (SendPtr and SendMutPtr) do not live after the wrapped function
 ///    returns. Should be easy: do NOT replace SendPtr- and SendMutPtr-typed fields of the
@@ -182,13 +329,33 @@ impl<T> SendPtr<T> {
 /// 2. Pointers created must not violate borrowing rules.
 pub struct ScriptSharedData {
     pub snapshot: Snapshot,
+    pub world_seed: u64,
     pub actor_pc: SendPtr<ActorPlayerComponent>,
     pub actions_packer_pc: SendMutPtr<ActionsPackerPlayerComponent>,
     pub chunk_view_pc: SendPtr<ChunkViewPlayerComponent>,
+    pub role_pc: SendPtr<RolePlayerComponent>,
+    pub player_ac: SendPtr<PlayerActorComponent>,
     pub position_ac: SendPtr<PositionActorComponent>,
     pub block_class_label_map: SendPtr<LabelMap<BlockClass>>,
     pub class_bc: SendMutPtr<ClassBlockComponent>,
+    pub metadata_bc: SendMutPtr<MetadataBlockComponent>,
+    pub block_entity_bc: SendMutPtr<BlockEntityBlockComponent>,
     pub collision_bcc: SendPtr<CollisionBlockClassComponent>,
+    pub script_label_map: SendPtr<LabelMap<Script>>,
+    pub script_schedule_system: SendMutPtr<ScriptScheduleSystem>,
+    pub inventory_ac: SendMutPtr<InventoryActorComponent>,
+    pub item_class_label_map: SendPtr<LabelMap<ItemClass>>,
+    pub recipe_registry: SendPtr<RecipeRegistry>,
+    pub health_ac: SendMutPtr<HealthActorComponent>,
+    pub damage_system: SendMutPtr<DamageSystem>,
+    pub effect_ac: SendMutPtr<EffectActorComponent>,
+    pub effect_label_map: SendPtr<LabelMap<Effect>>,
+    pub action_label_map: SendPtr<LabelMap<Action>>,
+    pub dimension_kind_label_map: SendPtr<LabelMap<DimensionKind>>,
+    pub actor_transfer_system: SendMutPtr<ActorTransferSystem>,
+    pub client_script_dispatch_system: SendMutPtr<ClientScriptDispatchSystem>,
+    pub world_rng: SendMutPtr<WorldRng>,
+    pub chunk_activation_system: SendMutPtr<ChunkActivationSystem>,
 }
 
 // Try to make unsafe blocks only output owned types.
@@ -223,6 +390,20 @@ pub fn setup_script_registry(
 
     registry.func_wrap("env", "get_blocks_in_chunk_edge", get_blocks_in_chunk_edge);
 
+    fn get_world_seed(caller: Caller<ScriptData<ScriptSharedData>>) -> u64 {
+        caller.data().shared().world_seed
+    }
+
+    registry.func_wrap("env", "get_world_seed", get_world_seed);
+
+    fn get_random(mut caller: Caller<ScriptData<ScriptSharedData>>) -> u64 {
+        let world_rng = unsafe { caller.data_mut().shared_mut().world_rng.get_mut() };
+
+        world_rng.next_u64()
+    }
+
+    registry.func_wrap("env", "get_random", get_random);
+
     fn get_target_block(
         mut caller: Caller<ScriptData<ScriptSharedData>>,
         buf_ptr: u32,
@@ -269,6 +450,146 @@ pub fn setup_script_registry(
 
     registry.func_wrap("env", "get_target_block", get_target_block);
 
+    fn query_filter<'a>(
+        filter: BlockFilter,
+        class_bc: &'a ClassBlockComponent,
+        collision_bcc: &'a CollisionBlockClassComponent,
+    ) -> impl FnMut(Chunk, Block) -> bool + 'a {
+        let classes = match filter {
+            BlockFilter::Solid => None,
+            BlockFilter::Classes(classes) => {
+                Some(
+                    classes
+                        .into_iter()
+                        .map(BlockClass::from)
+                        .collect::<Vec<_>>(),
+                )
+            },
+        };
+
+        move |chunk, block| {
+            let Some(class) = class_bc.get_chunk(&chunk).map(|blocks| blocks.get(block)) else {
+                return false;
+            };
+
+            match &classes {
+                None => collision_bcc.get(class).is_some(),
+                Some(classes) => classes.contains(class),
+            }
+        }
+    }
+
+    fn raycast(mut caller: Caller<ScriptData<ScriptSharedData>>, buf_ptr: u32, buf_len: u32) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (command, _) =
+            pack::decode_from_slice::<RaycastRequest>(bytes).expect("invalid argument");
+
+        let sd = caller.data().shared();
+        let class_bc = unsafe { sd.class_bc.get() };
+        let collision_bcc = unsafe { sd.collision_bcc.get() };
+
+        let response = query::raycast(
+            &Position {
+                chunk: command.chunk.into(),
+                offset: command.offset.into(),
+            },
+            command.direction.into(),
+            command.max_distance,
+            query_filter(command.filter, class_bc, collision_bcc),
+        )
+        .map(|hit| {
+            RaycastResponse {
+                time: hit.time,
+                chunk: hit.chunk.into(),
+                block: hit.block.into(),
+                side: hit.side as u8,
+            }
+        });
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "raycast", raycast);
+
+    fn cast_box(mut caller: Caller<ScriptData<ScriptSharedData>>, buf_ptr: u32, buf_len: u32) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (command, _) =
+            pack::decode_from_slice::<CastBoxRequest>(bytes).expect("invalid argument");
+
+        let sd = caller.data().shared();
+        let class_bc = unsafe { sd.class_bc.get() };
+        let collision_bcc = unsafe { sd.collision_bcc.get() };
+
+        let response = query::cast_box(
+            &Position {
+                chunk: command.chunk.into(),
+                offset: command.offset.into(),
+            },
+            &command.radius,
+            command.movement.into(),
+            query_filter(command.filter, class_bc, collision_bcc),
+        )
+        .map(|hit| {
+            CastBoxResponse {
+                time: hit.time,
+                normal: hit.normal.into(),
+                chunk: hit.chunk.into(),
+                block: hit.block.into(),
+            }
+        });
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "cast_box", cast_box);
+
+    fn overlap_sphere(
+        mut caller: Caller<ScriptData<ScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (command, _) =
+            pack::decode_from_slice::<OverlapSphereRequest>(bytes).expect("invalid argument");
+
+        let sd = caller.data().shared();
+        let class_bc = unsafe { sd.class_bc.get() };
+        let collision_bcc = unsafe { sd.collision_bcc.get() };
+
+        let response = query::overlap_sphere(
+            &Position {
+                chunk: command.chunk.into(),
+                offset: command.offset.into(),
+            },
+            command.radius,
+            query_filter(command.filter, class_bc, collision_bcc),
+        )
+        .into_iter()
+        .map(|(chunk, block)| {
+            (
+                server_loop_api::Chunk::from(chunk),
+                server_loop_api::Block::from(block),
+            )
+        })
+        .collect::<Vec<_>>();
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "overlap_sphere", overlap_sphere);
+
     fn set_class_of_block(
         mut caller: Caller<ScriptData<ScriptSharedData>>,
         buf_ptr: u32,
@@ -284,18 +605,61 @@ pub fn setup_script_registry(
 
         let sd = caller.data_mut().shared_mut();
         let class_bc = unsafe { sd.class_bc.get_mut() };
+        let metadata_bc = unsafe { sd.metadata_bc.get_mut() };
+        let block_entity_bc = unsafe { sd.block_entity_bc.get_mut() };
+
+        let chunk = command.chunk.into();
+        let block = command.block.into();
 
-        let Some(mut classes) = class_bc.get_mut_chunk(&command.chunk.into()) else {
+        let Some(mut classes) = class_bc.get_mut_chunk(&chunk) else {
             debug!("changing non-existant chunk");
             return;
         };
 
-        classes.set(command.block.into(), command.block_class.into());
+        classes.set(block, command.block_class.into());
+
+        // Metadata and the block entity belong to the replaced block
+        metadata_bc.set(&chunk, block, None);
+        block_entity_bc.set(&chunk, block, None);
     }
 
     registry.func_wrap("env", "set_class_of_block", set_class_of_block);
 
-    fn get_block_class_by_label(
+    fn fill_region(mut caller: Caller<ScriptData<ScriptSharedData>>, buf_ptr: u32, buf_len: u32) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (command, _) =
+            pack::decode_from_slice::<FillRegionRequest>(bytes).expect("invalid argument");
+
+        let sd = caller.data_mut().shared_mut();
+        let class_bc = unsafe { sd.class_bc.get_mut() };
+        let metadata_bc = unsafe { sd.metadata_bc.get_mut() };
+        let block_entity_bc = unsafe { sd.block_entity_bc.get_mut() };
+
+        let response = match bulk_edit::fill_region(
+            class_bc,
+            metadata_bc,
+            block_entity_bc,
+            (command.chunk_a.into(), command.block_a.into()),
+            (command.chunk_b.into(), command.block_b.into()),
+            command.block_class.into(),
+        ) {
+            Ok(changed) => Some(changed as u64),
+            Err(err) => {
+                debug!("unable to fill region: {}", err);
+                None
+            },
+        };
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "fill_region", fill_region);
+
+    fn get_block_metadata(
         mut caller: Caller<ScriptData<ScriptSharedData>>,
         buf_ptr: u32,
         buf_len: u32,
@@ -305,19 +669,22 @@ pub fn setup_script_registry(
         let memory = caller.data().memory();
         let bytes = &memory.data(&caller)[ptr .. ptr + len];
 
-        let (label, _) = pack::decode_from_slice::<&str>(bytes).expect("invalid argument");
+        let (command, _) =
+            pack::decode_from_slice::<GetBlockMetadataRequest>(bytes).expect("invalid argument");
 
         let sd = caller.data().shared();
-        let block_class_label_map = unsafe { sd.block_class_label_map.get() };
+        let metadata_bc = unsafe { sd.metadata_bc.get() };
 
-        let response = block_class_label_map.get(label);
+        let response = metadata_bc
+            .get(&command.chunk.into(), command.block.into())
+            .map(|metadata| metadata.to_vec());
 
         script_registry::write_script_buffer(&mut caller, response);
     }
 
-    registry.func_wrap("env", "get_block_class_by_label", get_block_class_by_label);
+    registry.func_wrap("env", "get_block_metadata", get_block_metadata);
 
-    fn broadcast_action_local(
+    fn set_block_metadata(
         mut caller: Caller<ScriptData<ScriptSharedData>>,
         buf_ptr: u32,
         buf_len: u32,
@@ -325,94 +692,787 @@ pub fn setup_script_registry(
         let ptr = buf_ptr as usize;
         let len = buf_len as usize;
         let memory = caller.data().memory();
-        let mut bytes = mem::take(caller.data_mut().buffer());
-        bytes.extend_from_slice(&memory.data(&caller)[ptr .. ptr + len]);
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (command, _) =
+            pack::decode_from_slice::<SetBlockMetadataRequest>(bytes).expect("invalid argument");
 
         let sd = caller.data_mut().shared_mut();
-        let actor_pc = unsafe { sd.actor_pc.get() };
-        let actions_packer_pc = unsafe { sd.actions_packer_pc.get_mut() };
-        let position_ac = unsafe { sd.position_ac.get() };
-        let chunk_view_pc = unsafe { sd.chunk_view_pc.get() };
+        let metadata_bc = unsafe { sd.metadata_bc.get_mut() };
 
-        // TODO Instead of option, in the future we should have either actor or "acting position"
-        // directly as an enum.
-        let input: ActionInput = pack::decode_from_slice(&bytes)
-            .expect("unable to decode action data")
-            .0;
+        if !metadata_bc.set(
+            &command.chunk.into(),
+            command.block.into(),
+            command.metadata,
+        ) {
+            debug!("changing non-existant chunk");
+        }
+    }
 
-        let action_actor: Option<Actor> = input.actor.map(Into::into);
+    registry.func_wrap("env", "set_block_metadata", set_block_metadata);
 
-        let acting_position = position_ac
-            .get(&action_actor.expect("actor was not passed by the script"))
-            .expect("acting actor has no position");
+    fn get_block_entity(
+        mut caller: Caller<ScriptData<ScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
 
-        for player in chunk_view_pc.iter().filter_map(|(player, chunk_view)| {
-            let position = position_ac.get(actor_pc.get(&player)?)?;
+        let (command, _) =
+            pack::decode_from_slice::<GetBlockEntityRequest>(bytes).expect("invalid argument");
 
-            position
-                .chunk
-                .radius(chunk_view.radius)
-                .is_within(&acting_position.chunk)
-                .then_some(())?;
+        let sd = caller.data().shared();
+        let block_entity_bc = unsafe { sd.block_entity_bc.get() };
 
-            Some(player)
-        }) {
-            actions_packer_pc
-                .get_mut(&player)
-                .expect("no action packer found for a player")
-                .add_action(input.action.into(), sd.snapshot, (action_actor, input.data));
-        }
+        let response = block_entity_bc
+            .get(&command.chunk.into(), command.block.into())
+            .map(|block_entity| {
+                server_loop_api::BlockEntity {
+                    kind: block_entity.kind.clone(),
+                    data: block_entity.data.clone(),
+                }
+            });
 
-        *caller.data_mut().buffer() = bytes;
+        script_registry::write_script_buffer(&mut caller, response);
     }
 
-    registry.func_wrap("env", "broadcast_action_local", broadcast_action_local);
+    registry.func_wrap("env", "get_block_entity", get_block_entity);
 
-    registry.build()
-}
+    fn set_block_entity(
+        mut caller: Caller<ScriptData<ScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
 
-/// All components and systems the loop has.
-pub struct SharedData {
-    pub database: Arc<Database>,
-    pub shared_event_tx: Sender<SharedEvent>,
-    pub packer: Packer,
-    pub actor_registry: ActorRegistry,
+        let (command, _) =
+            pack::decode_from_slice::<SetBlockEntityRequest>(bytes).expect("invalid argument");
 
-    pub client_pc: ClientPlayerComponent,
-    pub actor_pc: ActorPlayerComponent,
-    pub chunk_update_pc: ChunkUpdatePlayerComponent,
-    pub chunk_view_pc: ChunkViewPlayerComponent,
-    pub actions_packer_pc: ActionsPackerPlayerComponent,
+        let sd = caller.data_mut().shared_mut();
+        let block_entity_bc = unsafe { sd.block_entity_bc.get_mut() };
 
-    pub class_ac: ClassActorComponent,
-    pub position_ac: PositionActorComponent,
-    pub velocity_ac: VelocityActorComponent,
-    pub orientation_ac: OrientationActorComponent,
-    pub player_ac: PlayerActorComponent,
-    pub chunk_activation_ac: ChunkActivationActorComponent,
+        let block_entity = command.block_entity.map(|block_entity| {
+            BlockEntity {
+                kind: block_entity.kind,
+                data: block_entity.data,
+            }
+        });
 
-    pub model_acc: ModelActorClassComponent,
+        if !block_entity_bc.set(&command.chunk.into(), command.block.into(), block_entity) {
+            debug!("changing non-existant chunk");
+        }
+    }
 
-    pub class_bc: ClassBlockComponent,
-    pub collision_bcc: CollisionBlockClassComponent,
+    registry.func_wrap("env", "set_block_entity", set_block_entity);
+
+    fn schedule_script(
+        mut caller: Caller<ScriptData<ScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let mut bytes = mem::take(caller.data_mut().buffer());
+        bytes.clear();
+        bytes.extend_from_slice(&memory.data(&caller)[ptr .. ptr + len]);
+
+        let sd = caller.data_mut().shared_mut();
+        let script_label_map = unsafe { sd.script_label_map.get() };
+        let script_schedule_system = unsafe { sd.script_schedule_system.get_mut() };
+
+        let (request, _) =
+            pack::decode_from_slice::<ScheduleScriptRequest>(&bytes).expect("invalid argument");
+
+        match script_label_map.get(request.script) {
+            Some(script) => {
+                script_schedule_system.schedule(
+                    script,
+                    request.delay_ticks,
+                    request.payload.to_vec(),
+                );
+            },
+            None => warn!("scheduled script \"{}\" not found", request.script),
+        }
+
+        *caller.data_mut().buffer() = bytes;
+    }
+
+    registry.func_wrap("env", "schedule_script", schedule_script);
+
+    fn get_block_class_by_label(
+        mut caller: Caller<ScriptData<ScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (label, _) = pack::decode_from_slice::<&str>(bytes).expect("invalid argument");
+
+        let sd = caller.data().shared();
+        let block_class_label_map = unsafe { sd.block_class_label_map.get() };
+
+        let response = block_class_label_map.get(label);
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "get_block_class_by_label", get_block_class_by_label);
+
+    fn broadcast_action_local(
+        mut caller: Caller<ScriptData<ScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let mut bytes = mem::take(caller.data_mut().buffer());
+        bytes.extend_from_slice(&memory.data(&caller)[ptr .. ptr + len]);
+
+        let sd = caller.data_mut().shared_mut();
+        let actor_pc = unsafe { sd.actor_pc.get() };
+        let actions_packer_pc = unsafe { sd.actions_packer_pc.get_mut() };
+        let position_ac = unsafe { sd.position_ac.get() };
+        let chunk_view_pc = unsafe { sd.chunk_view_pc.get() };
+
+        // TODO Instead of option, in the future we should have either actor or "acting position"
+        // directly as an enum.
+        let input: ActionInput = pack::decode_from_slice(&bytes)
+            .expect("unable to decode action data")
+            .0;
+
+        let action_actor: Option<Actor> = input.actor.map(Into::into);
+
+        let acting_position = position_ac
+            .get(&action_actor.expect("actor was not passed by the script"))
+            .expect("acting actor has no position");
+
+        for player in chunk_view_pc.iter().filter_map(|(player, chunk_view)| {
+            let position = position_ac.get(actor_pc.get(&player)?)?;
+
+            position
+                .chunk
+                .radius(chunk_view.radius)
+                .is_within(&acting_position.chunk)
+                .then_some(())?;
+
+            Some(player)
+        }) {
+            actions_packer_pc
+                .get_mut(&player)
+                .expect("no action packer found for a player")
+                .add_action(input.action.into(), sd.snapshot, (action_actor, input.data));
+        }
+
+        *caller.data_mut().buffer() = bytes;
+    }
+
+    registry.func_wrap("env", "broadcast_action_local", broadcast_action_local);
+
+    fn get_player_of_actor(
+        mut caller: Caller<ScriptData<ScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (actor, _) =
+            pack::decode_from_slice::<server_loop_api::Actor>(bytes).expect("invalid argument");
+
+        let sd = caller.data().shared();
+        let player_ac = unsafe { sd.player_ac.get() };
+
+        let response: Option<server_loop_api::Player> =
+            player_ac.get(&actor.into()).map(|player| (*player).into());
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "get_player_of_actor", get_player_of_actor);
+
+    fn player_has_permission(
+        mut caller: Caller<ScriptData<ScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (request, _) =
+            pack::decode_from_slice::<PlayerHasPermissionRequest>(bytes).expect("invalid argument");
+
+        let sd = caller.data().shared();
+        let role_pc = unsafe { sd.role_pc.get() };
+
+        let response = role_pc
+            .get(&request.player.into())
+            .is_some_and(|role| role.has_permission(request.permission.into()));
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "player_has_permission", player_has_permission);
+
+    fn get_item_class_by_label(
+        mut caller: Caller<ScriptData<ScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (label, _) = pack::decode_from_slice::<&str>(bytes).expect("invalid argument");
+
+        let sd = caller.data().shared();
+        let item_class_label_map = unsafe { sd.item_class_label_map.get() };
+
+        let response = item_class_label_map.get(label);
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "get_item_class_by_label", get_item_class_by_label);
+
+    fn add_items(mut caller: Caller<ScriptData<ScriptSharedData>>, buf_ptr: u32, buf_len: u32) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (request, _) =
+            pack::decode_from_slice::<ItemsRequest>(bytes).expect("invalid argument");
+
+        let sd = caller.data_mut().shared_mut();
+        let snapshot = sd.snapshot;
+        let inventory_ac = unsafe { sd.inventory_ac.get_mut() };
+
+        let actor = request.actor.into();
+
+        let response = match inventory_ac.get(&actor) {
+            Some(inventory) => {
+                let mut inventory = inventory.clone();
+                let left = inventory.add(request.item_class.into(), request.amount);
+                inventory_ac.insert(actor, inventory, snapshot);
+                left
+            },
+            None => request.amount,
+        };
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "add_items", add_items);
+
+    fn remove_items(mut caller: Caller<ScriptData<ScriptSharedData>>, buf_ptr: u32, buf_len: u32) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (request, _) =
+            pack::decode_from_slice::<ItemsRequest>(bytes).expect("invalid argument");
+
+        let sd = caller.data_mut().shared_mut();
+        let snapshot = sd.snapshot;
+        let inventory_ac = unsafe { sd.inventory_ac.get_mut() };
+
+        let actor = request.actor.into();
+
+        let response = match inventory_ac.get(&actor) {
+            Some(inventory) => {
+                let mut inventory = inventory.clone();
+                let removed = inventory.remove(request.item_class.into(), request.amount);
+                inventory_ac.insert(actor, inventory, snapshot);
+                removed
+            },
+            None => false,
+        };
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "remove_items", remove_items);
+
+    fn count_items(mut caller: Caller<ScriptData<ScriptSharedData>>, buf_ptr: u32, buf_len: u32) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (request, _) =
+            pack::decode_from_slice::<CountItemsRequest>(bytes).expect("invalid argument");
+
+        let sd = caller.data().shared();
+        let inventory_ac = unsafe { sd.inventory_ac.get() };
+
+        let response = inventory_ac
+            .get(&request.actor.into())
+            .map(|inventory| inventory.count(request.item_class.into()))
+            .unwrap_or(0);
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "count_items", count_items);
+
+    fn get_recipe_by_label(
+        mut caller: Caller<ScriptData<ScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (label, _) = pack::decode_from_slice::<&str>(bytes).expect("invalid argument");
+
+        let sd = caller.data().shared();
+        let recipe_registry = unsafe { sd.recipe_registry.get() };
+
+        let convert = |ingredients: &[Ingredient]| {
+            ingredients
+                .iter()
+                .map(|ingredient| {
+                    RecipeIngredient {
+                        item_class: ingredient.item_class.into(),
+                        amount: ingredient.amount,
+                    }
+                })
+                .collect()
+        };
+
+        let response = recipe_registry.label_map().get(label).and_then(|recipe| {
+            let data = recipe_registry.get(&recipe)?;
+
+            Some(GetRecipeResponse {
+                recipe: recipe.into(),
+                inputs: convert(&data.inputs),
+                outputs: convert(&data.outputs),
+            })
+        });
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "get_recipe_by_label", get_recipe_by_label);
+
+    fn damage_actor(mut caller: Caller<ScriptData<ScriptSharedData>>, buf_ptr: u32, buf_len: u32) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (request, _) =
+            pack::decode_from_slice::<DamageActorRequest>(bytes).expect("invalid argument");
+
+        let sd = caller.data_mut().shared_mut();
+        let snapshot = sd.snapshot;
+        let health_ac = unsafe { sd.health_ac.get_mut() };
+        let damage_system = unsafe { sd.damage_system.get_mut() };
+
+        let response = damage_system.damage(
+            health_ac,
+            snapshot,
+            request.actor.into(),
+            request.amount,
+            request.source.map(|actor| actor.into()),
+        );
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "damage_actor", damage_actor);
+
+    fn get_actor_health(
+        mut caller: Caller<ScriptData<ScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (actor, _) =
+            pack::decode_from_slice::<server_loop_api::Actor>(bytes).expect("invalid argument");
+
+        let sd = caller.data().shared();
+        let health_ac = unsafe { sd.health_ac.get() };
+
+        let response = health_ac.get(&actor.into()).map(|health| {
+            ActorHealth {
+                current: health.current,
+                max: health.max,
+            }
+        });
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "get_actor_health", get_actor_health);
+
+    fn get_effect_by_label(
+        mut caller: Caller<ScriptData<ScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (label, _) = pack::decode_from_slice::<&str>(bytes).expect("invalid argument");
+
+        let sd = caller.data().shared();
+        let effect_label_map = unsafe { sd.effect_label_map.get() };
+
+        let response = effect_label_map.get(label);
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "get_effect_by_label", get_effect_by_label);
+
+    fn get_action_by_label(
+        mut caller: Caller<ScriptData<ScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (label, _) = pack::decode_from_slice::<&str>(bytes).expect("invalid argument");
+
+        let sd = caller.data().shared();
+        let action_label_map = unsafe { sd.action_label_map.get() };
+
+        let response = action_label_map.get(label);
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "get_action_by_label", get_action_by_label);
+
+    fn apply_effect(mut caller: Caller<ScriptData<ScriptSharedData>>, buf_ptr: u32, buf_len: u32) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (request, _) =
+            pack::decode_from_slice::<ApplyEffectRequest>(bytes).expect("invalid argument");
+
+        let sd = caller.data_mut().shared_mut();
+        let snapshot = sd.snapshot;
+        let effect_ac = unsafe { sd.effect_ac.get_mut() };
+
+        let actor = request.actor.into();
+
+        let mut effects = effect_ac
+            .get(&actor)
+            .cloned()
+            .unwrap_or_else(ActiveEffects::new);
+
+        effects.insert(
+            request.effect.into(),
+            EffectInstance {
+                magnitude: request.magnitude,
+                expires_at: Snapshot(snapshot.0.saturating_add(request.duration_ticks)),
+                tick_period: request.tick_period,
+            },
+        );
+
+        effect_ac.insert(actor, effects, snapshot);
+    }
+
+    registry.func_wrap("env", "apply_effect", apply_effect);
+
+    fn remove_effect(mut caller: Caller<ScriptData<ScriptSharedData>>, buf_ptr: u32, buf_len: u32) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (request, _) =
+            pack::decode_from_slice::<ActorEffectRequest>(bytes).expect("invalid argument");
+
+        let sd = caller.data_mut().shared_mut();
+        let snapshot = sd.snapshot;
+        let effect_ac = unsafe { sd.effect_ac.get_mut() };
+
+        let actor = request.actor.into();
+
+        let response = match effect_ac.get(&actor) {
+            Some(effects) if effects.get(&request.effect.into()).is_some() => {
+                let mut effects = effects.clone();
+                effects.remove(&request.effect.into());
+
+                if effects.is_empty() {
+                    effect_ac.remove(&actor, snapshot);
+                } else {
+                    effect_ac.insert(actor, effects, snapshot);
+                }
+
+                true
+            },
+            _ => false,
+        };
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "remove_effect", remove_effect);
+
+    fn get_dimension_kind_by_label(
+        mut caller: Caller<ScriptData<ScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (label, _) = pack::decode_from_slice::<&str>(bytes).expect("invalid argument");
+
+        let sd = caller.data().shared();
+        let dimension_kind_label_map = unsafe { sd.dimension_kind_label_map.get() };
+
+        let response = dimension_kind_label_map
+            .get(label)
+            .map(server_loop_api::DimensionKind::from);
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap(
+        "env",
+        "get_dimension_kind_by_label",
+        get_dimension_kind_by_label,
+    );
+
+    fn transfer_actor(
+        mut caller: Caller<ScriptData<ScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (request, _) =
+            pack::decode_from_slice::<TransferActorRequest>(bytes).expect("invalid argument");
+
+        let sd = caller.data_mut().shared_mut();
+        let actor_transfer_system = unsafe { sd.actor_transfer_system.get_mut() };
+
+        let response = actor_transfer_system.transfer(
+            request.actor.into(),
+            Position {
+                chunk: request.chunk.into(),
+                offset: request.offset.into(),
+            },
+        );
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "transfer_actor", transfer_actor);
+
+    fn add_chunk_ticket(
+        mut caller: Caller<ScriptData<ScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (request, _) =
+            pack::decode_from_slice::<AddChunkTicketRequest>(bytes).expect("invalid argument");
+
+        let sd = caller.data_mut().shared_mut();
+        let snapshot = sd.snapshot;
+        let chunk_activation_system = unsafe { sd.chunk_activation_system.get_mut() };
+
+        let response = chunk_activation_system
+            .add_ticket(Ticket {
+                source: TicketSource::Script,
+                center: request.chunk.into(),
+                radius: request.radius,
+                expires_at: request.ticks.map(|ticks| Snapshot(snapshot.0 + ticks)),
+            })
+            .map(|id| id.0);
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "add_chunk_ticket", add_chunk_ticket);
+
+    fn remove_chunk_ticket(
+        mut caller: Caller<ScriptData<ScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (id, _) = pack::decode_from_slice::<u64>(bytes).expect("invalid argument");
+
+        let sd = caller.data_mut().shared_mut();
+        let chunk_activation_system = unsafe { sd.chunk_activation_system.get_mut() };
+
+        let response = chunk_activation_system.remove_ticket(TicketId(id));
+
+        script_registry::write_script_buffer(&mut caller, response);
+    }
+
+    registry.func_wrap("env", "remove_chunk_ticket", remove_chunk_ticket);
+
+    fn dispatch_client_script(
+        mut caller: Caller<ScriptData<ScriptSharedData>>,
+        buf_ptr: u32,
+        buf_len: u32,
+    ) {
+        let ptr = buf_ptr as usize;
+        let len = buf_len as usize;
+        let memory = caller.data().memory();
+        let bytes = &memory.data(&caller)[ptr .. ptr + len];
+
+        let (request, _) = pack::decode_from_slice::<DispatchClientScriptRequest>(bytes)
+            .expect("invalid argument");
+
+        let player = request.player.into();
+        let script = request.script.to_owned();
+        let payload = request.payload.to_vec();
+
+        let sd = caller.data_mut().shared_mut();
+        let client_script_dispatch_system = unsafe { sd.client_script_dispatch_system.get_mut() };
+
+        client_script_dispatch_system.dispatch(player, script, payload);
+    }
+
+    registry.func_wrap("env", "dispatch_client_script", dispatch_client_script);
+
+    registry.build()
+}
+
+/// All components and systems the loop has.
+pub struct SharedData {
+    pub config: Arc<Config>,
+    pub database: Arc<Database>,
+    pub chunk_storage: ChunkStorage,
+    pub metadata_storage: MetadataStorage,
+    pub block_entity_storage: BlockEntityStorage,
+    pub structure_storage: StructureStorage,
+    pub world_seed: u64,
+    /// Ticks since the world creation, the time of the day is derived from it.
+    pub world_time: u64,
+    /// Loading chunks that have got structure placements queued meanwhile.
+    pub structure_loading_chunks: AHashSet<Chunk>,
+    pub shared_event_tx: Sender<SharedEvent>,
+    pub packer: Packer,
+    pub actor_registry: ActorRegistry,
+
+    pub client_pc: ClientPlayerComponent,
+    pub actor_pc: ActorPlayerComponent,
+    pub chunk_update_pc: ChunkUpdatePlayerComponent,
+    pub chunk_stream_pc: ChunkStreamPlayerComponent,
+    pub actor_relevancy_pc: ActorRelevancyPlayerComponent,
+    pub chunk_view_pc: ChunkViewPlayerComponent,
+    pub actions_packer_pc: ActionsPackerPlayerComponent,
+    pub role_pc: RolePlayerComponent,
+    pub username_pc: UsernamePlayerComponent,
+    pub position_correction_pc: PositionCorrectionPlayerComponent,
+
+    pub class_ac: ClassActorComponent,
+    pub position_ac: PositionActorComponent,
+    pub velocity_ac: VelocityActorComponent,
+    pub orientation_ac: OrientationActorComponent,
+    pub inventory_ac: InventoryActorComponent,
+    pub health_ac: HealthActorComponent,
+    pub effect_ac: EffectActorComponent,
+    pub player_ac: PlayerActorComponent,
+    pub chunk_activation_ac: ChunkActivationActorComponent,
+    pub projectile_ac: ProjectileActorComponent,
+    pub goal_ac: GoalActorComponent,
+
+    pub model_acc: ModelActorClassComponent,
+    pub collider_acc: ColliderActorClassComponent,
+    pub behavior_acc: BehaviorActorClassComponent,
+    pub health_acc: HealthActorClassComponent,
+
+    pub class_bc: ClassBlockComponent,
+    pub metadata_bc: MetadataBlockComponent,
+    pub block_entity_bc: BlockEntityBlockComponent,
+    pub collision_bcc: CollisionBlockClassComponent,
+    pub fluid_bcc: FluidBlockClassComponent,
+    pub random_tick_bcc: RandomTickBlockClassComponent,
+    pub neighbor_changed_bcc: NeighborChangedBlockClassComponent,
 
     pub status_cc: StatusChunkComponent,
     pub cache_cc: CacheChunkComponent,
 
     pub actor_class_label_map: LabelMap<ActorClass>,
     pub block_class_label_map: LabelMap<BlockClass>,
+    pub item_class_label_map: LabelMap<ItemClass>,
+    pub effect_label_map: LabelMap<Effect>,
+    pub action_label_map: LabelMap<Action>,
+    pub recipe_registry: RecipeRegistry,
+    pub script_label_map: LabelMap<Script>,
 
     pub position_system: PositionSystem,
+    pub movement_validation_system: MovementValidationSystem,
+    pub rate_limit_system: RateLimitSystem,
+    pub actor_ai_system: ActorAiSystem,
+    pub spawn_system: SpawnSystem,
+    pub dimension_kind_label_map: LabelMap<DimensionKind>,
+    pub actor_transfer_system: ActorTransferSystem,
+    pub client_script_dispatch_system: ClientScriptDispatchSystem,
+    pub projectile_system: ProjectileSystem,
+    pub random_tick_system: RandomTickSystem,
+    pub damage_system: DamageSystem,
+    pub effect_system: EffectSystem,
+    pub neighbor_update_system: NeighborUpdateSystem,
+    pub fluid_system: FluidSystem,
     pub chunk_activation_system: ChunkActivationSystem,
     pub chunk_generation_system: ChunkGenerationSystem,
+    pub script_schedule_system: ScriptScheduleSystem,
 
     pub script_registry: ScriptRegistry<ScriptSharedData>,
 
     pub script_action_component: ScriptActionComponent,
+    pub permission_action_component: PermissionActionComponent,
+    pub script_effect_component: ScriptEffectComponent,
 
     pub storage: StorageThread,
 
     pub snapshot: Snapshot,
+    pub world_rng: WorldRng,
 
     pub state_packer: StatePacker,
     pub state_unpacker: StateUnpacker,
@@ -420,6 +1480,9 @@ pub struct SharedData {
 
     pub last_process_time: Instant,
 
+    pub profiler: Profiler,
+    pub metrics: Arc<Metrics>,
+
     pub remove_queue: EntityRemoveQueue,
 }
 
@@ -432,7 +1495,16 @@ impl SharedData {
                 self.remove_actor(&actor);
             }
             for player in remove_queue.players.drain() {
-                self.remove_player(&player);
+                match self.client_pc.get_mut(&player) {
+                    // Players of the lost connections wait for the client to resume
+                    Some(client)
+                        if client.tx.is_disconnected()
+                            && !self.config.session_grace_period().is_zero() =>
+                    {
+                        client.detached_at.get_or_insert_with(Instant::now);
+                    },
+                    _ => self.remove_player(&player),
+                }
             }
 
             remove_queue.is_not_empty = false;
@@ -446,8 +1518,13 @@ impl SharedData {
         self.position_ac.remove(actor, self.snapshot);
         self.velocity_ac.remove(actor, self.snapshot);
         self.orientation_ac.remove(actor, self.snapshot);
+        self.inventory_ac.remove(actor, self.snapshot);
+        self.health_ac.remove(actor, self.snapshot);
+        self.effect_ac.remove(actor, self.snapshot);
         self.player_ac.remove(actor);
         self.chunk_activation_ac.remove(actor);
+        self.projectile_ac.remove(actor);
+        self.goal_ac.remove(actor);
         self.actor_registry.remove(actor);
     }
 
@@ -460,6 +1537,8 @@ impl SharedData {
             if !retain {
                 self.cache_cc.remove(chunk);
                 self.class_bc.remove_chunk(chunk);
+                self.metadata_bc.remove_chunk(chunk);
+                self.block_entity_bc.remove_chunk(chunk);
             }
 
             retain
@@ -467,16 +1546,96 @@ impl SharedData {
     }
 
     pub fn remove_player(&mut self, player: &Player) {
+        if let Some(state) = self.player_state(player) {
+            self.save_player_states(vec![(*player, state)]);
+        }
+
         self.client_pc.remove(&player);
         self.chunk_update_pc.remove(&player);
+        self.chunk_stream_pc.remove(&player);
+        self.actor_relevancy_pc.remove(&player);
         self.chunk_view_pc.remove(&player);
         self.actions_packer_pc.remove(&player);
+        self.role_pc.remove(&player);
+        self.username_pc.remove(&player);
+        self.position_correction_pc.remove(&player);
+        self.movement_validation_system.remove_player(&player);
+        self.rate_limit_system.remove_player(&player);
         if let Some(actor) = self.actor_pc.remove(&player) {
             self.remove_actor(&actor);
         }
     }
 
-    pub fn add_player(&mut self, player: Player, tx: Sender<ClientEvent>, session_id: u64) {
+    /// Removes the detached players the clients have not resumed within the grace period.
+    pub fn remove_detached_players(&mut self, now: Instant) {
+        let grace_period = self.config.session_grace_period();
+
+        let expired = self
+            .client_pc
+            .iter()
+            .filter(|(_, client)| {
+                client
+                    .detached_at
+                    .map(|detached_at| now.saturating_duration_since(detached_at) >= grace_period)
+                    .unwrap_or(false)
+            })
+            .map(|(player, _)| *player)
+            .collect::<Vec<_>>();
+
+        for player in expired {
+            self.remove_player(&player);
+        }
+    }
+
+    /// State of the player actor to be kept between the sessions.
+    pub fn player_state(&self, player: &Player) -> Option<PlayerState> {
+        let actor = self.actor_pc.get(player)?;
+
+        Some(PlayerState {
+            position: *self.position_ac.get(actor)?,
+            orientation: self.orientation_ac.get(actor).copied(),
+            inventory: self.inventory_ac.get(actor)?.clone(),
+        })
+    }
+
+    fn save_player_states(&self, states: Vec<(Player, PlayerState)>) {
+        if states.is_empty() {
+            return;
+        }
+
+        let database = self.database.clone();
+
+        self.storage.execute(move || {
+            let mut packer = Packer::new();
+
+            if let Err(err) = player::save_states(&database, &states, &mut packer) {
+                warn!("unable to save player states: {:?}", err);
+            }
+        });
+    }
+
+    /// Saves the states of all players in the world.
+    pub fn save_players(&self) {
+        let states = self
+            .actor_pc
+            .iter()
+            .filter_map(|(player, _)| Some((*player, self.player_state(player)?)))
+            .collect();
+
+        self.save_player_states(states);
+    }
+
+    /// Adds the player actor, restoring the saved `state` if there is one.
+    pub fn add_player(
+        &mut self,
+        player: Player,
+        username: String,
+        role: Role,
+        state: Option<PlayerState>,
+        tx: Sender<ClientEvent>,
+        session_id: u64,
+        connection_session: u64,
+    ) {
         let tx_init = tx.clone();
         let actor = self.actor_registry.add();
 
@@ -486,12 +1645,31 @@ impl SharedData {
             self.snapshot,
         );
 
+        let (position, orientation, inventory) = match state {
+            Some(PlayerState {
+                position,
+                orientation,
+                inventory,
+            }) => (Some(position), orientation, inventory),
+            None => (None, None, Inventory::new(PLAYER_INVENTORY_SIZE)),
+        };
+
+        self.inventory_ac.insert(actor, inventory, self.snapshot);
+
+        if let Some(orientation) = orientation {
+            self.orientation_ac
+                .insert(actor, orientation, self.snapshot);
+        }
+
+        self.health_ac
+            .insert(actor, Health::new(PLAYER_MAX_HEALTH), self.snapshot);
+
         self.player_ac.insert(actor, player);
 
         self.chunk_activation_ac.insert(
             actor,
             ActorChunkActivation {
-                radius: PLAYER_CHUNK_VIEW_RADIUS,
+                radius: self.config.player_chunk_view_radius,
             },
         );
 
@@ -501,8 +1679,9 @@ impl SharedData {
                 tx,
                 last_server_snapshot: Snapshot(0),
                 last_client_snapshot: Snapshot(0),
-                last_confirmed_chunk: None,
                 session_id,
+                connection_session,
+                detached_at: None,
             },
         );
 
@@ -511,18 +1690,247 @@ impl SharedData {
         self.chunk_view_pc.insert(
             player,
             ChunkView {
-                radius: PLAYER_CHUNK_VIEW_RADIUS,
+                radius: self.config.player_chunk_view_radius,
             },
         );
 
         self.actions_packer_pc.insert(player, ActionsPacker::new());
 
+        self.role_pc.insert(player, role);
+
+        self.username_pc.insert(player, username);
+
+        // Client starts at the saved position once it gets the correction,
+        // the chunks around are sent right away
+        if let Some(position) = position {
+            self.correct_player_position(&player, position);
+            self.chunk_update_pc.insert(
+                player,
+                ChunkUpdate {
+                    previous_view: None,
+                },
+            );
+        }
+
         if tx_init.send(ClientEvent::AssignActor { actor }).is_err() {
             self.remove_player(&player);
+            return;
+        }
+
+        self.send_world_time(&player);
+    }
+
+    /// Sends the current world time to the player.
+    pub fn send_world_time(&mut self, player: &Player) {
+        let Some(client) = self.client_pc.get(player) else {
+            return;
+        };
+
+        let data = self.packer.pack_to_vec(&ClientAccept::WorldTime {
+            time: self.world_time,
+            day_length: self.config.day_length_ticks,
+        });
+
+        if client
+            .tx
+            .send(ClientEvent::SendDataReliable {
+                channel: BASE_CHANNEL,
+                data: SendData::Owned(data),
+            })
+            .is_err()
+        {
+            self.remove_queue.remove_player(player);
+        }
+    }
+
+    /// Attaches the new client loop to the player of the resumed connection.
+    /// The client loop gets the actor of the player, or the closed channel
+    /// if the player has already been removed.
+    pub fn resume_player(
+        &mut self,
+        connection_session: u64,
+        tx: Sender<ClientEvent>,
+        session_id: u64,
+    ) {
+        let Some(player) = self
+            .client_pc
+            .iter()
+            .find(|(_, client)| client.connection_session == connection_session)
+            .map(|(player, _)| *player)
+        else {
+            return;
+        };
+
+        let Some(actor) = self.actor_pc.get(&player).copied() else {
+            return;
+        };
+
+        if tx
+            .send(ClientEvent::AssignResumedPlayer { player, actor })
+            .is_err()
+        {
+            return;
+        }
+
+        // Client gets the full state and the chunks around again,
+        // the messages of the old client loop are filtered out by the session id
+        self.client_pc.insert(
+            player,
+            Client {
+                tx,
+                last_server_snapshot: Snapshot(0),
+                last_client_snapshot: Snapshot(0),
+                session_id,
+                connection_session,
+                detached_at: None,
+            },
+        );
+
+        self.send_world_time(&player);
+    }
+
+    /// Override the position of the player with the one set by the server.
+    /// Positions the client sends are ignored until it receives the correction.
+    pub fn correct_player_position(&mut self, player: &Player, position: Position) {
+        let Some((actor, client)) = self.actor_pc.get(player).zip(self.client_pc.get(player))
+        else {
+            return;
+        };
+
+        self.position_ac.insert(*actor, position, self.snapshot);
+
+        self.position_correction_pc.insert(
+            *player,
+            PositionCorrection {
+                snapshot: self.snapshot,
+                last_client_snapshot: client.last_client_snapshot,
+                position,
+            },
+        );
+    }
+
+    /// Add a non-player actor of the class standing in the block.
+    pub fn spawn_actor(&mut self, actor_class: ActorClass, chunk: Chunk, block: Block) -> Actor {
+        let actor = self.actor_registry.add();
+
+        let half_height = self
+            .collider_acc
+            .get(&actor_class)
+            .map(|collider| collider.radius[2])
+            .unwrap_or(0.0);
+
+        let [x, y, z] = block.into_coords();
+
+        let position = Position {
+            chunk,
+            offset: Vec3F32::new(
+                x as f32 + 0.5,
+                y as f32 + 0.5,
+                z as f32 + half_height + SPAWN_GAP,
+            ),
+        };
+
+        self.class_ac.insert(actor, actor_class, self.snapshot);
+        self.position_ac.insert(actor, position, self.snapshot);
+        self.velocity_ac.insert(
+            actor,
+            Velocity {
+                vector: Vec3F32::ZERO,
+            },
+            self.snapshot,
+        );
+
+        if let Some(max_health) = self.health_acc.get(&actor_class) {
+            self.health_ac
+                .insert(actor, Health::new(*max_health), self.snapshot);
+        }
+
+        actor
+    }
+
+    /// Restore the health of the player actor and move it to the spawn position.
+    pub fn respawn_player(&mut self, player: &Player) {
+        let Some(actor) = self.actor_pc.get(player).copied() else {
+            return;
+        };
+
+        if let Some(health) = self.health_ac.get(&actor) {
+            let health = Health::new(health.max);
+            self.health_ac.insert(actor, health, self.snapshot);
+        }
+
+        self.transfer_actor(actor, SPAWN_POSITION);
+    }
+
+    /// Move the actor to the position, notifying the player client if the dimension changes.
+    pub fn transfer_actor(&mut self, actor: Actor, position: Position) {
+        let previous_dimension = self
+            .position_ac
+            .get(&actor)
+            .map(|position| position.chunk.dimension);
+
+        let Some(player) = self.player_ac.get(&actor).copied() else {
+            self.position_ac.insert(actor, position, self.snapshot);
+            // The path is relative to the previous position
+            self.goal_ac.remove(&actor);
+            return;
+        };
+
+        self.correct_player_position(&player, position);
+
+        if previous_dimension == Some(position.chunk.dimension) {
+            return;
+        }
+
+        let Some(client) = self.client_pc.get(&player) else {
+            return;
+        };
+
+        let data = ClientAccept::DimensionChange {
+            dimension: position.chunk.dimension,
+        };
+
+        if client
+            .tx
+            .send(ClientEvent::SendDataReliable {
+                channel: BASE_CHANNEL,
+                data: SendData::Owned(self.packer.pack_to_vec(&data)),
+            })
+            .is_err()
+        {
+            self.remove_queue.remove_player(&player);
+        }
+    }
+
+    /// Structure placements have been queued for the chunk by the generation of its neighbor.
+    /// The chunks that are not active get the placements once activated.
+    pub fn structure_queued(&mut self, chunk: Chunk) {
+        match self.status_cc.get(&chunk) {
+            Some(ChunkStatus::Active) => self.apply_structure_placements(chunk),
+            Some(ChunkStatus::Loading) => {
+                self.structure_loading_chunks.insert(chunk);
+            },
+            None => {},
         }
     }
 
-    pub fn chunk_loaded(&mut self, chunk_data: ChunkData, data_encoded: Arc<Vec<u8>>) {
+    fn apply_structure_placements(&mut self, chunk: Chunk) {
+        let Some(mut block_classes) = self.class_bc.get_mut_chunk(&chunk) else {
+            return;
+        };
+
+        for (block, block_class) in self.structure_storage.take(chunk, &mut self.packer) {
+            block_classes.set(block, block_class);
+        }
+    }
+
+    pub fn chunk_loaded(
+        &mut self,
+        chunk_data: ChunkData,
+        metadata: ChunkMetadata,
+        block_entities: ChunkBlockEntities,
+        data_encoded: Arc<Vec<u8>>,
+    ) {
         match self.status_cc.get_mut(&chunk_data.chunk) {
             Some(status) if *status == ChunkStatus::Loading => {
                 *status = ChunkStatus::Active;
@@ -532,31 +1940,39 @@ impl SharedData {
 
         self.class_bc
             .insert_chunk(chunk_data.chunk, chunk_data.block_classes);
-        self.cache_cc
-            .insert(chunk_data.chunk, data_encoded.clone().into());
+        self.metadata_bc.insert_chunk(chunk_data.chunk, metadata);
+
+        self.block_entity_bc
+            .insert_chunk(chunk_data.chunk, block_entities);
+
+        self.cache_cc.insert(chunk_data.chunk, data_encoded.into());
 
         let chunk = chunk_data.chunk;
 
-        for (player, client) in self.actor_pc.iter().filter_map(|(player, actor)| {
-            let position = self.position_ac.get(actor)?;
-            let chunk_ticket = self.chunk_activation_ac.get(actor)?;
+        // Sent with the block entities by the chunk streaming of the players viewing the chunk
+        let viewing_players = self
+            .actor_pc
+            .iter()
+            .filter(|(player, actor)| {
+                self.position_ac
+                    .get(actor)
+                    .zip(self.chunk_view_pc.get(player))
+                    .is_some_and(|(position, view)| {
+                        position.chunk.radius(view.radius).is_within(&chunk)
+                    })
+            })
+            .map(|(player, _)| *player)
+            .collect::<Vec<_>>();
+
+        for player in viewing_players {
+            self.chunk_stream_pc
+                .get_mut_or_insert_with(player, ChunkStream::new)
+                .enqueue(chunk);
+        }
 
-            if position.chunk.radius(chunk_ticket.radius).is_within(&chunk) {
-                Some((player, self.client_pc.get(player)?))
-            } else {
-                None
-            }
-        }) {
-            if client
-                .tx
-                .send(ClientEvent::SendDataReliable {
-                    channel: BASE_CHANNEL,
-                    data: SendData::Arc(data_encoded.clone()),
-                })
-                .is_err()
-            {
-                self.remove_queue.remove_player(player);
-            }
+        // The placements could have been queued after the chunk was read from the storage
+        if self.structure_loading_chunks.remove(&chunk) {
+            self.apply_structure_placements(chunk);
         }
     }
 }
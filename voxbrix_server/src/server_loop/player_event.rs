@@ -1,22 +1,51 @@
 use crate::{
-    component::player::chunk_update::{
-        ChunkUpdate,
-        FullChunkView,
+    component::{
+        chunk::status::ChunkStatus,
+        player::{
+            chunk_update::{
+                ChunkUpdate,
+                FullChunkView,
+            },
+            client::{
+                ClientEvent,
+                SendData,
+            },
+        },
     },
     entity::player::Player,
-    server_loop::data::{
-        ScriptSharedData,
-        SendMutPtr,
-        SendPtr,
-        SharedData,
+    server_loop::{
+        data::{
+            ScriptSharedData,
+            SharedData,
+        },
+        profiler::Section,
     },
+    BASE_CHANNEL,
 };
 use log::{
     debug,
+    info,
     warn,
 };
 use server_loop_api::ActionInput;
-use voxbrix_common::messages::server::ServerAccept;
+use std::{
+    sync::Arc,
+    time::Instant,
+};
+use voxbrix_common::{
+    messages::{
+        client::ClientAccept,
+        server::{
+            ChatScope,
+            ServerAccept,
+            MAX_CHAT_MESSAGE_LENGTH,
+        },
+    },
+    script_registry::{
+        SendMutPtr,
+        SendPtr,
+    },
+};
 use voxbrix_protocol::server::Packet;
 
 pub struct PlayerEvent<'a> {
@@ -51,6 +80,8 @@ impl PlayerEvent<'_> {
                 state,
                 actions,
             } => {
+                let started = Instant::now();
+
                 let state = match sd.state_unpacker.unpack_state(state) {
                     Ok(v) => v,
                     Err(_) => {
@@ -62,7 +93,7 @@ impl PlayerEvent<'_> {
                 // TODO: there could be sequential messaged on the wire that have the same state
                 // changes duplicated. Make sure that those do not duplicate in the components.
                 let actor = match sd.actor_pc.get(&player) {
-                    Some(a) => a,
+                    Some(a) => *a,
                     None => return,
                 };
 
@@ -80,45 +111,103 @@ impl PlayerEvent<'_> {
                 client.last_server_snapshot = last_server_snapshot;
                 client.last_client_snapshot = last_client_snapshot;
 
-                sd.velocity_ac.unpack_player(actor, &state, sd.snapshot);
-                sd.orientation_ac.unpack_player(actor, &state, sd.snapshot);
+                sd.velocity_ac.unpack_player(&actor, &state, sd.snapshot);
+                sd.orientation_ac.unpack_player(&actor, &state, sd.snapshot);
 
-                sd.position_ac.unpack_player_with(
-                    actor,
-                    &state,
-                    sd.snapshot,
-                    |old_value, new_value| {
-                        let chunk = match new_value {
-                            Some(v) => v.chunk,
-                            None => return,
-                        };
+                // Positions sent before the client received the correction are outdated
+                let correction_pending = match sd.position_correction_pc.get(&player) {
+                    Some(correction) if last_server_snapshot >= correction.snapshot => {
+                        sd.position_correction_pc.remove(&player);
+                        false
+                    },
+                    Some(_) => true,
+                    None => false,
+                };
 
-                        sd.client_pc.get_mut(&player).unwrap().last_confirmed_chunk = Some(chunk);
+                // Players cannot move into the chunks that are not loaded on the server.
+                // The correction is sent once the message is no longer borrowed from the packer.
+                let rejected_position = if !correction_pending {
+                    sd.position_ac.unpack_player_with(
+                        &actor,
+                        &state,
+                        sd.snapshot,
+                        |old_value, new_value| {
+                            let new_value = new_value?;
+                            let mut chunk = new_value.chunk;
+                            let mut corrected = None;
 
-                        if old_value.is_none()
-                            || old_value.is_some() && old_value.unwrap().chunk != chunk
-                        {
-                            let prev_view_radius = match sd.chunk_view_pc.get(&player) {
-                                Some(r) => r.radius,
-                                None => return,
-                            };
-
-                            let previous_view = old_value.map(|old_pos| {
-                                FullChunkView {
-                                    chunk: old_pos.chunk,
-                                    radius: prev_view_radius,
+                            if let Some(old_value) = old_value {
+                                if old_value.chunk != chunk
+                                    && sd.status_cc.get(&chunk) != Some(&ChunkStatus::Active)
+                                {
+                                    return Some(*old_value);
                                 }
-                            });
 
-                            if sd.chunk_update_pc.get(&player).is_some() {
-                                return;
-                            } else {
-                                sd.chunk_update_pc
-                                    .insert(player, ChunkUpdate { previous_view });
+                                let collider = sd
+                                    .class_ac
+                                    .get(&actor)
+                                    .and_then(|class| sd.collider_acc.get(class))
+                                    .copied()
+                                    .unwrap_or_default();
+
+                                corrected = sd.movement_validation_system.validate(
+                                    &player,
+                                    sd.snapshot,
+                                    old_value,
+                                    new_value,
+                                    &collider,
+                                    &sd.class_bc,
+                                    &sd.collision_bcc,
+                                );
+
+                                if let Some(corrected) = corrected {
+                                    chunk = corrected.chunk;
+
+                                    if let Some(username) = sd.username_pc.get(&player) {
+                                        sd.metrics.movement_violation(username);
+                                    }
+                                }
                             }
-                        }
-                    },
-                );
+
+                            if old_value.is_none()
+                                || old_value.is_some() && old_value.unwrap().chunk != chunk
+                            {
+                                let prev_view_radius = sd.chunk_view_pc.get(&player)?.radius;
+
+                                let previous_view = old_value.map(|old_pos| {
+                                    FullChunkView {
+                                        chunk: old_pos.chunk,
+                                        radius: prev_view_radius,
+                                    }
+                                });
+
+                                if sd.chunk_update_pc.get(&player).is_none() {
+                                    sd.chunk_update_pc
+                                        .insert(player, ChunkUpdate { previous_view });
+                                }
+                            }
+
+                            corrected
+                        },
+                    )
+                } else {
+                    None
+                };
+
+                if sd
+                    .config
+                    .max_movement_violations
+                    .is_some_and(|max_violations| {
+                        sd.movement_validation_system.violations(&player) > max_violations
+                    })
+                {
+                    info!("kicking player {:?} for invalid movement", player);
+                    sd.remove_queue.remove_player(&player);
+                    return;
+                }
+
+                sd.profiler.record(Section::StateUnpack, started.elapsed());
+                let started = Instant::now();
 
                 // Pruning confirmed Server -> Client actions.
                 sd.actions_packer_pc
@@ -140,6 +229,29 @@ impl PlayerEvent<'_> {
                     .iter()
                     .filter(|(_, snapshot, _)| *snapshot > previous_last_client_snapshot)
                 {
+                    if !sd.rate_limit_system.action(&player, sd.snapshot) {
+                        if sd.rate_limit_system.is_exceeded(&player) {
+                            sd.remove_queue.remove_player(&player);
+                            return;
+                        }
+
+                        continue;
+                    }
+
+                    if let Some(permission) = sd.permission_action_component.get(action) {
+                        if !sd
+                            .role_pc
+                            .get(&player)
+                            .is_some_and(|role| role.has_permission(*permission))
+                        {
+                            debug!(
+                                "player {:?} has no permission {:?} for action {:?}",
+                                player, permission, action
+                            );
+                            continue;
+                        }
+                    }
+
                     let Some(script) = sd.script_action_component.get(action) else {
                         warn!("script for \"{:?}\" not found", action);
                         continue;
@@ -147,13 +259,35 @@ impl PlayerEvent<'_> {
 
                     let script_data = ScriptSharedData {
                         snapshot: sd.snapshot,
+                        world_seed: sd.world_seed,
                         actor_pc: SendPtr::new(&sd.actor_pc),
                         actions_packer_pc: SendMutPtr::new(&mut sd.actions_packer_pc),
                         chunk_view_pc: SendPtr::new(&sd.chunk_view_pc),
+                        role_pc: SendPtr::new(&sd.role_pc),
+                        player_ac: SendPtr::new(&sd.player_ac),
                         position_ac: SendPtr::new(&sd.position_ac),
                         block_class_label_map: SendPtr::new(&sd.block_class_label_map),
                         class_bc: SendMutPtr::new(&mut sd.class_bc),
+                        metadata_bc: SendMutPtr::new(&mut sd.metadata_bc),
+                        block_entity_bc: SendMutPtr::new(&mut sd.block_entity_bc),
                         collision_bcc: SendPtr::new(&sd.collision_bcc),
+                        script_label_map: SendPtr::new(&sd.script_label_map),
+                        script_schedule_system: SendMutPtr::new(&mut sd.script_schedule_system),
+                        inventory_ac: SendMutPtr::new(&mut sd.inventory_ac),
+                        item_class_label_map: SendPtr::new(&sd.item_class_label_map),
+                        recipe_registry: SendPtr::new(&sd.recipe_registry),
+                        health_ac: SendMutPtr::new(&mut sd.health_ac),
+                        damage_system: SendMutPtr::new(&mut sd.damage_system),
+                        effect_ac: SendMutPtr::new(&mut sd.effect_ac),
+                        effect_label_map: SendPtr::new(&sd.effect_label_map),
+                        action_label_map: SendPtr::new(&sd.action_label_map),
+                        dimension_kind_label_map: SendPtr::new(&sd.dimension_kind_label_map),
+                        actor_transfer_system: SendMutPtr::new(&mut sd.actor_transfer_system),
+                        client_script_dispatch_system: SendMutPtr::new(
+                            &mut sd.client_script_dispatch_system,
+                        ),
+                        world_rng: SendMutPtr::new(&mut sd.world_rng),
+                        chunk_activation_system: SendMutPtr::new(&mut sd.chunk_activation_system),
                     };
 
                     sd.script_registry.run_script(
@@ -161,11 +295,112 @@ impl PlayerEvent<'_> {
                         script_data,
                         ActionInput {
                             action: (*action).into(),
-                            actor: Some((*actor).into()),
+                            actor: Some(actor.into()),
                             data,
                         },
                     );
                 }
+
+                drop(state);
+                drop(actions);
+
+                if let Some(position) = rejected_position {
+                    sd.correct_player_position(&player, position);
+                }
+
+                sd.profiler.record(Section::Actions, started.elapsed());
+            },
+            ServerAccept::ChatMessage { scope, text } => {
+                if text.len() > MAX_CHAT_MESSAGE_LENGTH {
+                    debug!("player {:?} sent too long chat message", player);
+                    return;
+                }
+
+                let Some(sender) = sd.username_pc.get(&player) else {
+                    return;
+                };
+
+                let sender_chunk = sd
+                    .actor_pc
+                    .get(&player)
+                    .and_then(|actor| sd.position_ac.get(actor))
+                    .map(|position| position.chunk);
+
+                let message = Arc::new(sd.packer.pack_to_vec(&ClientAccept::ChatMessage {
+                    sender: sender.clone(),
+                    text,
+                }));
+
+                for (receiver, client) in sd.client_pc.iter() {
+                    let in_scope = match scope {
+                        ChatScope::Global => true,
+                        ChatScope::Local => {
+                            sender_chunk
+                                .zip(
+                                    sd.actor_pc
+                                        .get(receiver)
+                                        .and_then(|actor| sd.position_ac.get(actor)),
+                                )
+                                .is_some_and(|(sender_chunk, position)| {
+                                    sender_chunk
+                                        .radius(sd.config.chat_local_radius)
+                                        .is_within(&position.chunk)
+                                })
+                        },
+                    };
+
+                    if !in_scope {
+                        continue;
+                    }
+
+                    if client
+                        .tx
+                        .send(ClientEvent::SendDataReliable {
+                            channel: BASE_CHANNEL,
+                            data: SendData::Arc(message.clone()),
+                        })
+                        .is_err()
+                    {
+                        sd.remove_queue.remove_player(receiver);
+                    }
+                }
+            },
+            ServerAccept::MoveItem { from, to } => {
+                let Some(actor) = sd.actor_pc.get(&player).copied() else {
+                    return;
+                };
+
+                let Some(mut inventory) = sd.inventory_ac.get(&actor).cloned() else {
+                    return;
+                };
+
+                if !inventory.move_stack(from as usize, to as usize) {
+                    debug!("player {:?} moved item out of inventory bounds", player);
+                    return;
+                }
+
+                sd.inventory_ac.insert(actor, inventory, sd.snapshot);
+            },
+            ServerAccept::Craft { recipe } => {
+                let Some(recipe_data) = sd.recipe_registry.get(&recipe) else {
+                    debug!("player {:?} requested unknown recipe {:?}", player, recipe);
+                    return;
+                };
+
+                let Some(actor) = sd.actor_pc.get(&player).copied() else {
+                    return;
+                };
+
+                let Some(mut inventory) = sd.inventory_ac.get(&actor).cloned() else {
+                    return;
+                };
+
+                if !recipe_data.craft(&mut inventory) {
+                    debug!("player {:?} is unable to craft {:?}", player, recipe);
+                    return;
+                }
+
+                sd.inventory_ac.insert(actor, inventory, sd.snapshot);
             },
         }
     }
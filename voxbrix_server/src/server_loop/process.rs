@@ -1,22 +1,40 @@
 use crate::{
     component::{
         chunk::cache::ChunkCache,
-        player::client::{
-            ClientEvent,
-            SendData,
+        player::{
+            actor_relevancy::{
+                self,
+                ActorRelevancy,
+            },
+            chunk_stream::ChunkStream,
+            client::{
+                ClientEvent,
+                SendData,
+            },
         },
     },
     server_loop::{
-        data::SharedData,
+        data::{
+            ScriptSharedData,
+            SharedData,
+        },
+        profiler::Section,
         SharedEvent,
     },
-    storage::{
-        IntoData,
-        IntoDataSized,
-    },
+    storage::world,
     system::chunk_activation::ChunkActivationOutcome,
     BASE_CHANNEL,
-    BLOCK_CLASS_TABLE,
+};
+use ahash::AHashSet;
+use log::warn;
+use server_loop_api::{
+    ActionInput,
+    DeathInput,
+    EffectTickInput,
+    NeighborChangedInput,
+    ProjectileHit,
+    RandomTickInput,
+    ScheduledInput,
 };
 use std::{
     sync::Arc,
@@ -24,24 +42,36 @@ use std::{
 };
 use tokio::runtime::Handle;
 use voxbrix_common::{
-    entity::{
-        chunk::Chunk,
-        snapshot::{
-            Snapshot,
-            MAX_SNAPSHOT_DIFF,
-        },
+    component::actor::orientation::Orientation,
+    entity::snapshot::{
+        Snapshot,
+        MAX_SNAPSHOT_DIFF,
     },
+    math::Vec3F32,
     messages::client::{
         ChunkChanges,
         ClientAccept,
     },
-    pack::Packer,
+    pack,
+    script_registry::{
+        SendMutPtr,
+        SendPtr,
+    },
     ChunkData,
 };
 
+/// Label of the optional script run on the actor deaths.
+const ACTOR_DEATH_SCRIPT: &str = "actor_death";
+/// Approximate size of a single block change in the `ChunkChanges`, in bytes.
+const BLOCK_CHANGE_SIZE_ESTIMATE: usize = 4;
+/// Interval of sending the world time to the players and saving it, in ticks.
+const WORLD_TIME_SYNC_INTERVAL_TICKS: u64 = 200;
+
 pub struct Process<'a> {
     pub shared_data: &'a mut SharedData,
     pub rt_handle: Handle,
+    /// Time of the processing, recorded one in the replay.
+    pub now: Instant,
 }
 
 impl Process<'_> {
@@ -49,36 +79,117 @@ impl Process<'_> {
         let Self {
             shared_data: sd,
             rt_handle,
+            now,
         } = self;
 
-        let now = Instant::now();
-        let elapsed = now.saturating_duration_since(sd.last_process_time);
+        // Fixed timestep, the server loop catches up with the missed ticks
+        let elapsed = sd.config.process_interval();
         sd.last_process_time = now;
 
-        // Sending chunks to players
-        for (player, client, prev_radius, curr_radius) in
+        sd.remove_detached_players(now);
+
+        sd.world_time = sd.world_time.wrapping_add(1);
+
+        if sd.world_time % WORLD_TIME_SYNC_INTERVAL_TICKS == 0 {
+            let players = sd
+                .client_pc
+                .iter()
+                .map(|(player, _)| *player)
+                .collect::<Vec<_>>();
+
+            for player in players {
+                sd.send_world_time(&player);
+            }
+
+            let database = sd.database.clone();
+            let world_time = sd.world_time;
+
+            sd.storage.execute(move || {
+                if let Err(err) = world::save_time(&database, world_time) {
+                    warn!("unable to save world time: {:?}", err);
+                }
+            });
+        }
+
+        let player_save_interval = sd.config.player_save_interval_ticks;
+
+        if player_save_interval != 0 && sd.world_time % player_save_interval == 0 {
+            sd.save_players();
+        }
+
+        let started = Instant::now();
+
+        // Queueing the chunks that came into the view of the players
+        for (player, prev_radius, curr_radius) in
             sd.chunk_update_pc
                 .drain()
                 .filter_map(|(player, prev_view)| {
                     let actor = sd.actor_pc.get(&player)?;
-                    let client = sd.client_pc.get(&player)?;
                     let position = sd.position_ac.get(&actor)?;
                     let curr_view = sd.chunk_view_pc.get(&player)?;
                     let curr_radius = position.chunk.radius(curr_view.radius);
                     let prev_radius = prev_view.previous_view.map(|v| v.chunk.radius(v.radius));
 
-                    Some((player, client, prev_radius, curr_radius))
+                    Some((player, prev_radius, curr_radius))
                 })
         {
-            for chunk_data in curr_radius.into_iter_expanding().filter_map(|chunk| {
-                if let Some(prev_radius) = &prev_radius {
-                    if prev_radius.is_within(&chunk) {
-                        return None;
-                    }
-                }
+            let stream = sd
+                .chunk_stream_pc
+                .get_mut_or_insert_with(player, ChunkStream::new);
 
-                sd.cache_cc.get(&chunk)
+            // Chunks that are still loading are queued once they are loaded
+            for chunk in curr_radius.into_iter_simple().filter(|chunk| {
+                !prev_radius
+                    .as_ref()
+                    .is_some_and(|prev_radius| prev_radius.is_within(chunk))
+                    && sd.cache_cc.get(chunk).is_some()
             }) {
+                stream.enqueue(chunk);
+            }
+        }
+
+        // Sending the queued chunks to the players, the most important ones first,
+        // within the byte budget
+        for (player, stream) in sd.chunk_stream_pc.iter_mut() {
+            let Some((client, position, curr_radius)) = sd.actor_pc.get(player).and_then(|actor| {
+                let client = sd.client_pc.get(player)?;
+                let position = sd.position_ac.get(actor)?;
+                let curr_view = sd.chunk_view_pc.get(player)?;
+                let curr_radius = position.chunk.radius(curr_view.radius);
+
+                Some((client, position, curr_radius))
+            }) else {
+                continue;
+            };
+
+            stream.retain_within(&curr_radius);
+
+            let forward = sd
+                .actor_pc
+                .get(player)
+                .and_then(|actor| sd.orientation_ac.get(actor))
+                .map(|orientation| orientation.forward())
+                .unwrap_or(Vec3F32::ZERO);
+
+            let mut budget = sd.config.chunk_stream_bytes_per_tick;
+            let mut sent_any = false;
+
+            for chunk in stream.prioritized(&position.chunk, forward) {
+                let Some(chunk_data) = sd.cache_cc.get(&chunk) else {
+                    // Unloaded, will be queued again when loaded
+                    stream.remove(&chunk);
+                    continue;
+                };
+
+                // At least one chunk per tick, even if it is larger than the budget
+                if sent_any && chunk_data.size() > budget {
+                    break;
+                }
+
+                budget = budget.saturating_sub(chunk_data.size());
+                sent_any = true;
+                stream.remove(&chunk);
+
                 if client
                     .tx
                     .send(ClientEvent::SendDataReliable {
@@ -87,7 +198,30 @@ impl Process<'_> {
                     })
                     .is_err()
                 {
-                    sd.remove_queue.remove_player(&player);
+                    sd.remove_queue.remove_player(player);
+                }
+
+                // Block entities follow the chunk data on the same reliable channel
+                if let Some(block_entities) = sd
+                    .block_entity_bc
+                    .get_chunk(&chunk)
+                    .filter(|block_entities| !block_entities.is_empty())
+                {
+                    let data = ClientAccept::ChunkBlockEntities {
+                        chunk,
+                        block_entities: block_entities.clone(),
+                    };
+
+                    if client
+                        .tx
+                        .send(ClientEvent::SendDataReliable {
+                            channel: BASE_CHANNEL,
+                            data: SendData::Owned(sd.packer.pack_to_vec(&data)),
+                        })
+                        .is_err()
+                    {
+                        sd.remove_queue.remove_player(player);
+                    }
                 }
             }
         }
@@ -110,25 +244,67 @@ impl Process<'_> {
                 _ => panic!(),
             };
 
-            let database = sd.database.clone();
+            sd.storage
+                .save_block_classes(*chunk_changes.chunk, blocks_cache);
+        }
 
-            let chunk_db = *chunk_changes.chunk;
+        for (chunk, metadata) in sd.metadata_bc.take_changes() {
+            sd.storage.save_metadata(chunk, metadata);
+        }
 
-            sd.storage.execute(move || {
-                let chunk_db = chunk_db.into_data_sized();
-                let mut packer = Packer::new();
-                let db_write = database.begin_write().unwrap();
-                {
-                    let mut table = db_write.open_table(BLOCK_CLASS_TABLE).unwrap();
+        for (chunk, block_entities) in sd.block_entity_bc.changed_chunks() {
+            sd.storage
+                .save_block_entities(*chunk, block_entities.clone());
+        }
 
-                    table
-                        .insert(chunk_db, blocks_cache.into_data(&mut packer))
-                        .expect("server_loop: database write");
-                }
-                db_write.commit().unwrap();
-            });
+        // Sending block entity changes to the players viewing the chunks
+        for (player, client, curr_radius) in sd.actor_pc.iter().filter_map(|(player, actor)| {
+            let client = sd.client_pc.get(&player)?;
+            let position = sd.position_ac.get(&actor)?;
+            let curr_view = sd.chunk_view_pc.get(&player)?;
+            let curr_radius = position.chunk.radius(curr_view.radius);
+
+            Some((player, client, curr_radius))
+        }) {
+            let changes = sd
+                .block_entity_bc
+                .changes()
+                .filter(|(chunk, _, _)| curr_radius.is_within(chunk))
+                .map(|(chunk, block, block_entity)| (chunk, block, block_entity.cloned()))
+                .collect::<Vec<_>>();
+
+            if changes.is_empty() {
+                continue;
+            }
+
+            let data = ClientAccept::BlockEntityChanges(changes);
+            if client
+                .tx
+                .send(ClientEvent::SendDataReliable {
+                    channel: BASE_CHANNEL,
+                    data: SendData::Owned(sd.packer.pack_to_vec(&data)),
+                })
+                .is_err()
+            {
+                sd.remove_queue.remove_player(&player);
+            }
         }
 
+        sd.block_entity_bc.clear_changes();
+
+        // Chunks changed so much that their whole data is smaller than the list of the changes,
+        // e.g. after a region fill. The cache already has the new data of the changed chunks
+        let full_chunks = sd
+            .class_bc
+            .changed_chunks()
+            .filter(|change| {
+                sd.cache_cc.get(change.chunk).is_some_and(|cache| {
+                    change.changes().len() * BLOCK_CHANGE_SIZE_ESTIMATE > cache.size()
+                })
+            })
+            .map(|change| *change.chunk)
+            .collect::<AHashSet<_>>();
+
         let mut change_buffer = Vec::new();
 
         // Sending block class changes to players
@@ -140,10 +316,9 @@ impl Process<'_> {
 
             Some((player, client, curr_radius))
         }) {
-            let chunk_iter = sd
-                .class_bc
-                .changed_chunks()
-                .filter(|change| curr_radius.is_within(change.chunk));
+            let chunk_iter = sd.class_bc.changed_chunks().filter(|change| {
+                curr_radius.is_within(change.chunk) && !full_chunks.contains(change.chunk)
+            });
 
             let chunk_amount = chunk_iter.clone().count();
 
@@ -173,24 +348,454 @@ impl Process<'_> {
             {
                 sd.remove_queue.remove_player(&player);
             }
+
+            for chunk_data in full_chunks
+                .iter()
+                .filter(|chunk| curr_radius.is_within(chunk))
+                .filter_map(|chunk| sd.cache_cc.get(chunk))
+            {
+                if client
+                    .tx
+                    .send(ClientEvent::SendDataReliable {
+                        channel: BASE_CHANNEL,
+                        data: SendData::Arc(chunk_data.clone().into_inner()),
+                    })
+                    .is_err()
+                {
+                    sd.remove_queue.remove_player(&player);
+                }
+            }
         }
 
+        sd.profiler.record(Section::Send, started.elapsed());
+
+        sd.neighbor_update_system
+            .process(&sd.class_bc, &sd.neighbor_changed_bcc);
+        sd.fluid_system
+            .schedule_changes(&sd.class_bc, &sd.fluid_bcc);
+
         sd.class_bc.clear_changes();
 
         sd.chunk_activation_system.clear();
+        sd.chunk_activation_system.expire_tickets(sd.snapshot);
         sd.chunk_activation_system
             .actor_activations(&sd.chunk_activation_ac, &sd.position_ac);
+        sd.chunk_activation_system.ticket_activations();
+
+        if sd.snapshot.0 % sd.config.spawn_interval_ticks.max(1) == 0 {
+            sd.spawn_system.process(
+                &mut sd.world_rng,
+                &sd.status_cc,
+                &sd.chunk_activation_system,
+                &sd.class_bc,
+                &sd.collision_bcc,
+                &sd.class_ac,
+                &sd.player_ac,
+                &sd.position_ac,
+            );
+
+            for actor in sd.spawn_system.take_despawns() {
+                sd.remove_queue.remove_actor(&actor);
+            }
+
+            for spawn in sd.spawn_system.take_spawns() {
+                sd.spawn_actor(spawn.actor_class, spawn.chunk, spawn.block);
+            }
+        }
+
+        sd.actor_ai_system.process(
+            sd.snapshot,
+            &mut sd.world_rng,
+            &sd.class_bc,
+            &sd.collision_bcc,
+            &sd.class_ac,
+            &sd.collider_acc,
+            &sd.behavior_acc,
+            &sd.player_ac,
+            &sd.position_ac,
+            &mut sd.velocity_ac,
+            &mut sd.goal_ac,
+        );
 
         sd.position_system.process(
             elapsed,
             &sd.class_bc,
             &sd.collision_bcc,
+            &sd.fluid_bcc,
+            &sd.class_ac,
+            &sd.collider_acc,
             &mut sd.position_ac,
             &sd.velocity_ac,
             &sd.player_ac,
+            &sd.projectile_ac,
             sd.snapshot,
         );
 
+        sd.projectile_system.process(
+            elapsed,
+            &sd.class_bc,
+            &sd.collision_bcc,
+            &sd.projectile_ac,
+            &mut sd.position_ac,
+            &mut sd.velocity_ac,
+            sd.snapshot,
+        );
+
+        for (actor, hit) in sd.projectile_system.take_hits() {
+            // The projectile stays where it has hit the block,
+            // the script decides what happens next
+            let Some(projectile) = sd.projectile_ac.remove(&actor) else {
+                continue;
+            };
+
+            sd.velocity_ac.remove(&actor, sd.snapshot);
+
+            let Some(script) = sd.script_action_component.get(&projectile.hit_action) else {
+                warn!("script for \"{:?}\" not found", projectile.hit_action);
+                continue;
+            };
+
+            let mut data = Vec::new();
+            pack::encode_into(
+                &ProjectileHit {
+                    chunk: hit.chunk.into(),
+                    block: hit.block.into(),
+                    side: hit.side as u8,
+                },
+                &mut data,
+            );
+
+            let script_data = ScriptSharedData {
+                snapshot: sd.snapshot,
+                world_seed: sd.world_seed,
+                actor_pc: SendPtr::new(&sd.actor_pc),
+                actions_packer_pc: SendMutPtr::new(&mut sd.actions_packer_pc),
+                chunk_view_pc: SendPtr::new(&sd.chunk_view_pc),
+                role_pc: SendPtr::new(&sd.role_pc),
+                player_ac: SendPtr::new(&sd.player_ac),
+                position_ac: SendPtr::new(&sd.position_ac),
+                block_class_label_map: SendPtr::new(&sd.block_class_label_map),
+                class_bc: SendMutPtr::new(&mut sd.class_bc),
+                metadata_bc: SendMutPtr::new(&mut sd.metadata_bc),
+                block_entity_bc: SendMutPtr::new(&mut sd.block_entity_bc),
+                collision_bcc: SendPtr::new(&sd.collision_bcc),
+                script_label_map: SendPtr::new(&sd.script_label_map),
+                script_schedule_system: SendMutPtr::new(&mut sd.script_schedule_system),
+                inventory_ac: SendMutPtr::new(&mut sd.inventory_ac),
+                item_class_label_map: SendPtr::new(&sd.item_class_label_map),
+                recipe_registry: SendPtr::new(&sd.recipe_registry),
+                health_ac: SendMutPtr::new(&mut sd.health_ac),
+                damage_system: SendMutPtr::new(&mut sd.damage_system),
+                effect_ac: SendMutPtr::new(&mut sd.effect_ac),
+                effect_label_map: SendPtr::new(&sd.effect_label_map),
+                action_label_map: SendPtr::new(&sd.action_label_map),
+                dimension_kind_label_map: SendPtr::new(&sd.dimension_kind_label_map),
+                actor_transfer_system: SendMutPtr::new(&mut sd.actor_transfer_system),
+                client_script_dispatch_system: SendMutPtr::new(
+                    &mut sd.client_script_dispatch_system,
+                ),
+                world_rng: SendMutPtr::new(&mut sd.world_rng),
+                chunk_activation_system: SendMutPtr::new(&mut sd.chunk_activation_system),
+            };
+
+            sd.script_registry.run_script(
+                script,
+                script_data,
+                ActionInput {
+                    action: projectile.hit_action.into(),
+                    actor: Some(actor.into()),
+                    data: &data,
+                },
+            );
+        }
+
+        for scheduled in sd.script_schedule_system.next_tick() {
+            let script_data = ScriptSharedData {
+                snapshot: sd.snapshot,
+                world_seed: sd.world_seed,
+                actor_pc: SendPtr::new(&sd.actor_pc),
+                actions_packer_pc: SendMutPtr::new(&mut sd.actions_packer_pc),
+                chunk_view_pc: SendPtr::new(&sd.chunk_view_pc),
+                role_pc: SendPtr::new(&sd.role_pc),
+                player_ac: SendPtr::new(&sd.player_ac),
+                position_ac: SendPtr::new(&sd.position_ac),
+                block_class_label_map: SendPtr::new(&sd.block_class_label_map),
+                class_bc: SendMutPtr::new(&mut sd.class_bc),
+                metadata_bc: SendMutPtr::new(&mut sd.metadata_bc),
+                block_entity_bc: SendMutPtr::new(&mut sd.block_entity_bc),
+                collision_bcc: SendPtr::new(&sd.collision_bcc),
+                script_label_map: SendPtr::new(&sd.script_label_map),
+                script_schedule_system: SendMutPtr::new(&mut sd.script_schedule_system),
+                inventory_ac: SendMutPtr::new(&mut sd.inventory_ac),
+                item_class_label_map: SendPtr::new(&sd.item_class_label_map),
+                recipe_registry: SendPtr::new(&sd.recipe_registry),
+                health_ac: SendMutPtr::new(&mut sd.health_ac),
+                damage_system: SendMutPtr::new(&mut sd.damage_system),
+                effect_ac: SendMutPtr::new(&mut sd.effect_ac),
+                effect_label_map: SendPtr::new(&sd.effect_label_map),
+                action_label_map: SendPtr::new(&sd.action_label_map),
+                dimension_kind_label_map: SendPtr::new(&sd.dimension_kind_label_map),
+                actor_transfer_system: SendMutPtr::new(&mut sd.actor_transfer_system),
+                client_script_dispatch_system: SendMutPtr::new(
+                    &mut sd.client_script_dispatch_system,
+                ),
+                world_rng: SendMutPtr::new(&mut sd.world_rng),
+                chunk_activation_system: SendMutPtr::new(&mut sd.chunk_activation_system),
+            };
+
+            sd.script_registry.run_script(
+                &scheduled.script,
+                script_data,
+                ScheduledInput {
+                    payload: &scheduled.payload,
+                },
+            );
+        }
+
+        sd.fluid_system
+            .process(&mut sd.class_bc, &sd.collision_bcc, &sd.fluid_bcc);
+
+        sd.random_tick_system.process(
+            &mut sd.world_rng,
+            sd.config.random_ticks_per_chunk,
+            &sd.status_cc,
+            &sd.class_bc,
+            &sd.random_tick_bcc,
+        );
+
+        for tick in sd.random_tick_system.take_ticks() {
+            let script_data = ScriptSharedData {
+                snapshot: sd.snapshot,
+                world_seed: sd.world_seed,
+                actor_pc: SendPtr::new(&sd.actor_pc),
+                actions_packer_pc: SendMutPtr::new(&mut sd.actions_packer_pc),
+                chunk_view_pc: SendPtr::new(&sd.chunk_view_pc),
+                role_pc: SendPtr::new(&sd.role_pc),
+                player_ac: SendPtr::new(&sd.player_ac),
+                position_ac: SendPtr::new(&sd.position_ac),
+                block_class_label_map: SendPtr::new(&sd.block_class_label_map),
+                class_bc: SendMutPtr::new(&mut sd.class_bc),
+                metadata_bc: SendMutPtr::new(&mut sd.metadata_bc),
+                block_entity_bc: SendMutPtr::new(&mut sd.block_entity_bc),
+                collision_bcc: SendPtr::new(&sd.collision_bcc),
+                script_label_map: SendPtr::new(&sd.script_label_map),
+                script_schedule_system: SendMutPtr::new(&mut sd.script_schedule_system),
+                inventory_ac: SendMutPtr::new(&mut sd.inventory_ac),
+                item_class_label_map: SendPtr::new(&sd.item_class_label_map),
+                recipe_registry: SendPtr::new(&sd.recipe_registry),
+                health_ac: SendMutPtr::new(&mut sd.health_ac),
+                damage_system: SendMutPtr::new(&mut sd.damage_system),
+                effect_ac: SendMutPtr::new(&mut sd.effect_ac),
+                effect_label_map: SendPtr::new(&sd.effect_label_map),
+                action_label_map: SendPtr::new(&sd.action_label_map),
+                dimension_kind_label_map: SendPtr::new(&sd.dimension_kind_label_map),
+                actor_transfer_system: SendMutPtr::new(&mut sd.actor_transfer_system),
+                client_script_dispatch_system: SendMutPtr::new(
+                    &mut sd.client_script_dispatch_system,
+                ),
+                world_rng: SendMutPtr::new(&mut sd.world_rng),
+                chunk_activation_system: SendMutPtr::new(&mut sd.chunk_activation_system),
+            };
+
+            sd.script_registry.run_script(
+                &tick.script,
+                script_data,
+                RandomTickInput {
+                    chunk: tick.chunk.into(),
+                    block: tick.block.into(),
+                    block_class: tick.block_class.into(),
+                },
+            );
+        }
+
+        // Changes made by the handlers notify their neighbors on the next tick
+        for update in sd.neighbor_update_system.take_updates() {
+            let script_data = ScriptSharedData {
+                snapshot: sd.snapshot,
+                world_seed: sd.world_seed,
+                actor_pc: SendPtr::new(&sd.actor_pc),
+                actions_packer_pc: SendMutPtr::new(&mut sd.actions_packer_pc),
+                chunk_view_pc: SendPtr::new(&sd.chunk_view_pc),
+                role_pc: SendPtr::new(&sd.role_pc),
+                player_ac: SendPtr::new(&sd.player_ac),
+                position_ac: SendPtr::new(&sd.position_ac),
+                block_class_label_map: SendPtr::new(&sd.block_class_label_map),
+                class_bc: SendMutPtr::new(&mut sd.class_bc),
+                metadata_bc: SendMutPtr::new(&mut sd.metadata_bc),
+                block_entity_bc: SendMutPtr::new(&mut sd.block_entity_bc),
+                collision_bcc: SendPtr::new(&sd.collision_bcc),
+                script_label_map: SendPtr::new(&sd.script_label_map),
+                script_schedule_system: SendMutPtr::new(&mut sd.script_schedule_system),
+                inventory_ac: SendMutPtr::new(&mut sd.inventory_ac),
+                item_class_label_map: SendPtr::new(&sd.item_class_label_map),
+                recipe_registry: SendPtr::new(&sd.recipe_registry),
+                health_ac: SendMutPtr::new(&mut sd.health_ac),
+                damage_system: SendMutPtr::new(&mut sd.damage_system),
+                effect_ac: SendMutPtr::new(&mut sd.effect_ac),
+                effect_label_map: SendPtr::new(&sd.effect_label_map),
+                action_label_map: SendPtr::new(&sd.action_label_map),
+                dimension_kind_label_map: SendPtr::new(&sd.dimension_kind_label_map),
+                actor_transfer_system: SendMutPtr::new(&mut sd.actor_transfer_system),
+                client_script_dispatch_system: SendMutPtr::new(
+                    &mut sd.client_script_dispatch_system,
+                ),
+                world_rng: SendMutPtr::new(&mut sd.world_rng),
+                chunk_activation_system: SendMutPtr::new(&mut sd.chunk_activation_system),
+            };
+
+            sd.script_registry.run_script(
+                &update.script,
+                script_data,
+                NeighborChangedInput {
+                    chunk: update.chunk.into(),
+                    block: update.block.into(),
+                    block_class: update.block_class.into(),
+                    changed_chunk: update.changed_chunk.into(),
+                    changed_block: update.changed_block.into(),
+                },
+            );
+        }
+
+        sd.effect_system.process(&mut sd.effect_ac, sd.snapshot);
+
+        for tick in sd.effect_system.take_ticks() {
+            let Some(script) = sd.script_effect_component.get(&tick.effect).copied() else {
+                continue;
+            };
+
+            let script_data = ScriptSharedData {
+                snapshot: sd.snapshot,
+                world_seed: sd.world_seed,
+                actor_pc: SendPtr::new(&sd.actor_pc),
+                actions_packer_pc: SendMutPtr::new(&mut sd.actions_packer_pc),
+                chunk_view_pc: SendPtr::new(&sd.chunk_view_pc),
+                role_pc: SendPtr::new(&sd.role_pc),
+                player_ac: SendPtr::new(&sd.player_ac),
+                position_ac: SendPtr::new(&sd.position_ac),
+                block_class_label_map: SendPtr::new(&sd.block_class_label_map),
+                class_bc: SendMutPtr::new(&mut sd.class_bc),
+                metadata_bc: SendMutPtr::new(&mut sd.metadata_bc),
+                block_entity_bc: SendMutPtr::new(&mut sd.block_entity_bc),
+                collision_bcc: SendPtr::new(&sd.collision_bcc),
+                script_label_map: SendPtr::new(&sd.script_label_map),
+                script_schedule_system: SendMutPtr::new(&mut sd.script_schedule_system),
+                inventory_ac: SendMutPtr::new(&mut sd.inventory_ac),
+                item_class_label_map: SendPtr::new(&sd.item_class_label_map),
+                recipe_registry: SendPtr::new(&sd.recipe_registry),
+                health_ac: SendMutPtr::new(&mut sd.health_ac),
+                damage_system: SendMutPtr::new(&mut sd.damage_system),
+                effect_ac: SendMutPtr::new(&mut sd.effect_ac),
+                effect_label_map: SendPtr::new(&sd.effect_label_map),
+                action_label_map: SendPtr::new(&sd.action_label_map),
+                dimension_kind_label_map: SendPtr::new(&sd.dimension_kind_label_map),
+                actor_transfer_system: SendMutPtr::new(&mut sd.actor_transfer_system),
+                client_script_dispatch_system: SendMutPtr::new(
+                    &mut sd.client_script_dispatch_system,
+                ),
+                world_rng: SendMutPtr::new(&mut sd.world_rng),
+                chunk_activation_system: SendMutPtr::new(&mut sd.chunk_activation_system),
+            };
+
+            sd.script_registry.run_script(
+                &script,
+                script_data,
+                EffectTickInput {
+                    actor: tick.actor.into(),
+                    effect: tick.effect.into(),
+                    magnitude: tick.magnitude,
+                    remaining_ticks: tick.remaining_ticks,
+                },
+            );
+        }
+
+        for death in sd.damage_system.take_deaths() {
+            if let Some(script) = sd.script_label_map.get(ACTOR_DEATH_SCRIPT) {
+                let script_data = ScriptSharedData {
+                    snapshot: sd.snapshot,
+                    world_seed: sd.world_seed,
+                    actor_pc: SendPtr::new(&sd.actor_pc),
+                    actions_packer_pc: SendMutPtr::new(&mut sd.actions_packer_pc),
+                    chunk_view_pc: SendPtr::new(&sd.chunk_view_pc),
+                    role_pc: SendPtr::new(&sd.role_pc),
+                    player_ac: SendPtr::new(&sd.player_ac),
+                    position_ac: SendPtr::new(&sd.position_ac),
+                    block_class_label_map: SendPtr::new(&sd.block_class_label_map),
+                    class_bc: SendMutPtr::new(&mut sd.class_bc),
+                    metadata_bc: SendMutPtr::new(&mut sd.metadata_bc),
+                    block_entity_bc: SendMutPtr::new(&mut sd.block_entity_bc),
+                    collision_bcc: SendPtr::new(&sd.collision_bcc),
+                    script_label_map: SendPtr::new(&sd.script_label_map),
+                    script_schedule_system: SendMutPtr::new(&mut sd.script_schedule_system),
+                    inventory_ac: SendMutPtr::new(&mut sd.inventory_ac),
+                    item_class_label_map: SendPtr::new(&sd.item_class_label_map),
+                    recipe_registry: SendPtr::new(&sd.recipe_registry),
+                    health_ac: SendMutPtr::new(&mut sd.health_ac),
+                    damage_system: SendMutPtr::new(&mut sd.damage_system),
+                    effect_ac: SendMutPtr::new(&mut sd.effect_ac),
+                    effect_label_map: SendPtr::new(&sd.effect_label_map),
+                    action_label_map: SendPtr::new(&sd.action_label_map),
+                    dimension_kind_label_map: SendPtr::new(&sd.dimension_kind_label_map),
+                    actor_transfer_system: SendMutPtr::new(&mut sd.actor_transfer_system),
+                    client_script_dispatch_system: SendMutPtr::new(
+                        &mut sd.client_script_dispatch_system,
+                    ),
+                    world_rng: SendMutPtr::new(&mut sd.world_rng),
+                    chunk_activation_system: SendMutPtr::new(&mut sd.chunk_activation_system),
+                };
+
+                sd.script_registry.run_script(
+                    &script,
+                    script_data,
+                    DeathInput {
+                        actor: death.actor.into(),
+                        source: death.source.map(|actor| actor.into()),
+                    },
+                );
+            }
+
+            // The death script could have restored the health
+            if !sd
+                .health_ac
+                .get(&death.actor)
+                .is_some_and(|health| health.is_dead())
+            {
+                continue;
+            }
+
+            match sd.player_ac.get(&death.actor).copied() {
+                Some(player) => sd.respawn_player(&player),
+                None => sd.remove_queue.remove_actor(&death.actor),
+            }
+        }
+
+        for transfer in sd.actor_transfer_system.take_transfers() {
+            sd.transfer_actor(transfer.actor, transfer.position);
+        }
+
+        for dispatch in sd.client_script_dispatch_system.take_dispatches() {
+            let Some(client) = sd.client_pc.get(&dispatch.player) else {
+                continue;
+            };
+
+            let data = ClientAccept::ScriptDispatch {
+                script: dispatch.script,
+                payload: dispatch.payload,
+            };
+
+            if client
+                .tx
+                .send(ClientEvent::SendDataReliable {
+                    channel: BASE_CHANNEL,
+                    data: SendData::Owned(sd.packer.pack_to_vec(&data)),
+                })
+                .is_err()
+            {
+                sd.remove_queue.remove_player(&dispatch.player);
+            }
+        }
+
+        let started = Instant::now();
+
         for (player, player_actor, client) in sd
             .actor_pc
             .iter()
@@ -218,44 +823,39 @@ impl Process<'_> {
             };
 
             let chunk_radius = position_chunk.radius(chunk_view_radius);
+            let relevancy_radius =
+                position_chunk.radius(chunk_view_radius + actor_relevancy::HYSTERESIS_CHUNKS);
 
-            let client_is_outdated = client.last_server_snapshot == Snapshot(0)
-                || sd.snapshot.0 - client.last_server_snapshot.0 > MAX_SNAPSHOT_DIFF;
-
-            if let Some(previous_chunk_radius) = client
-                .last_confirmed_chunk
-                // Enforces full update for the outdated clients
-                .filter(|_| !client_is_outdated)
-                // TODO Should be `previous_view` if the view is runtime-variable.
-                .map(|c| c.radius(chunk_view_radius))
-            {
-                let chunk_within_intersection = |chunk: Option<&Chunk>| -> bool {
-                    let chunk = match chunk {
-                        Some(v) => v,
-                        None => return false,
-                    };
-
-                    previous_chunk_radius.is_within(chunk) && chunk_radius.is_within(chunk)
-                };
+            let relevancy = sd
+                .actor_relevancy_pc
+                .get_mut_or_insert_with(*player, ActorRelevancy::new);
 
-                // TODO optimize?
-                let new_chunks = chunk_radius
+            // TODO optimize?
+            relevancy.update(
+                sd.snapshot,
+                client.last_server_snapshot,
+                chunk_radius
                     .into_iter_simple()
-                    .filter(|c| !previous_chunk_radius.is_within(c));
+                    .flat_map(|chunk| sd.position_ac.chunk_actors(&chunk)),
+                |actor| {
+                    sd.position_ac
+                        .get(actor)
+                        .is_some_and(|position| relevancy_radius.is_within(&position.chunk))
+                },
+            );
 
-                // TODO optimize?
-                let intersection_chunks = chunk_radius
-                    .into_iter_simple()
-                    .filter(|c| previous_chunk_radius.is_within(c));
+            let client_is_outdated = client.last_server_snapshot == Snapshot(0)
+                || sd.snapshot.0 - client.last_server_snapshot.0 > MAX_SNAPSHOT_DIFF;
 
+            // Enforces full update for the outdated clients
+            if !client_is_outdated {
                 sd.position_ac.pack_changes(
                     &mut sd.state_packer,
                     sd.snapshot,
                     client.last_server_snapshot,
                     player_actor,
-                    chunk_within_intersection,
-                    new_chunks,
-                    intersection_chunks,
+                    relevancy.full_update(client.last_server_snapshot),
+                    relevancy.partial_update(client.last_server_snapshot),
                 );
 
                 // Server-controlled components, we pass `None` instead of `player_actor`.
@@ -289,20 +889,49 @@ impl Process<'_> {
                     sd.position_ac.actors_partial_update(),
                 );
 
-                sd.orientation_ac.pack_changes(
+                sd.orientation_ac.pack_changes_with(
                     &mut sd.state_packer,
                     sd.snapshot,
                     client.last_server_snapshot,
                     Some(player_actor),
                     sd.position_ac.actors_full_update(),
                     sd.position_ac.actors_partial_update(),
+                    Orientation::pack,
                 );
-            } else {
-                // TODO optimize?
-                let new_chunks = chunk_radius.into_iter_simple();
 
-                sd.position_ac
-                    .pack_full(&mut sd.state_packer, player_actor, new_chunks);
+                sd.inventory_ac.pack_changes(
+                    &mut sd.state_packer,
+                    sd.snapshot,
+                    client.last_server_snapshot,
+                    None,
+                    sd.position_ac.actors_full_update(),
+                    sd.position_ac.actors_partial_update(),
+                );
+
+                sd.health_ac.pack_changes(
+                    &mut sd.state_packer,
+                    sd.snapshot,
+                    client.last_server_snapshot,
+                    None,
+                    sd.position_ac.actors_full_update(),
+                    sd.position_ac.actors_partial_update(),
+                );
+
+                sd.effect_ac.pack_changes(
+                    &mut sd.state_packer,
+                    sd.snapshot,
+                    client.last_server_snapshot,
+                    None,
+                    sd.position_ac.actors_full_update(),
+                    sd.position_ac.actors_partial_update(),
+                );
+            } else {
+                sd.position_ac.pack_full(
+                    &mut sd.state_packer,
+                    sd.snapshot,
+                    player_actor,
+                    relevancy.all(),
+                );
 
                 // Server-controlled components, we pass `None` instead of `player_actor`.
                 // These components will not filter out player's own components.
@@ -326,10 +955,29 @@ impl Process<'_> {
                     sd.position_ac.actors_full_update(),
                 );
 
-                sd.orientation_ac.pack_full(
+                sd.orientation_ac.pack_full_with(
                     &mut sd.state_packer,
                     Some(player_actor),
                     sd.position_ac.actors_full_update(),
+                    Orientation::pack,
+                );
+
+                sd.inventory_ac.pack_full(
+                    &mut sd.state_packer,
+                    None,
+                    sd.position_ac.actors_full_update(),
+                );
+
+                sd.health_ac.pack_full(
+                    &mut sd.state_packer,
+                    None,
+                    sd.position_ac.actors_full_update(),
+                );
+
+                sd.effect_ac.pack_full(
+                    &mut sd.state_packer,
+                    None,
+                    sd.position_ac.actors_full_update(),
                 );
             }
 
@@ -343,8 +991,11 @@ impl Process<'_> {
             let data = sd.packer.pack_to_vec(&ClientAccept::State {
                 snapshot: sd.snapshot,
                 last_client_snapshot: client.last_client_snapshot,
+                last_server_snapshot: client.last_server_snapshot,
                 state,
                 actions,
+                position_correction: sd.position_correction_pc.get(player).copied(),
+                actors_left: relevancy.left().collect(),
             });
 
             if client
@@ -359,14 +1010,25 @@ impl Process<'_> {
             }
         }
 
+        sd.profiler.record(Section::Send, started.elapsed());
+        let started = Instant::now();
+
         let shared_event_tx = sd.shared_event_tx.clone();
 
         sd.chunk_activation_system.activate(
-            &mut sd.database,
+            &sd.chunk_storage,
+            &sd.metadata_storage,
+            &sd.block_entity_storage,
+            &sd.structure_storage,
+            sd.storage.pending_writes(),
             &mut sd.status_cc,
             move |chunk, activation_outcome, packer| {
                 match activation_outcome {
-                    ChunkActivationOutcome::ChunkActivated(block_classes) => {
+                    ChunkActivationOutcome::ChunkActivated(
+                        block_classes,
+                        metadata,
+                        block_entities,
+                    ) => {
                         let data = ChunkData {
                             chunk,
                             block_classes,
@@ -375,8 +1037,12 @@ impl Process<'_> {
                         let data_encoded =
                             Arc::new(packer.pack_to_vec(&ClientAccept::ChunkData(data.clone())));
 
-                        let _ =
-                            shared_event_tx.send(SharedEvent::ChunkLoaded { data, data_encoded });
+                        let _ = shared_event_tx.send(SharedEvent::ChunkLoaded {
+                            data,
+                            metadata,
+                            block_entities,
+                            data_encoded,
+                        });
                     },
                     ChunkActivationOutcome::ChunkNeedsGeneration => {
                         let _ = shared_event_tx.send(SharedEvent::ChunkGeneration(chunk));
@@ -388,6 +1054,10 @@ impl Process<'_> {
 
         sd.prune_chunks();
 
+        sd.profiler
+            .record(Section::ChunkActivation, started.elapsed());
+
         sd.snapshot = sd.snapshot.next();
+        sd.world_rng.start_snapshot(sd.snapshot);
     }
 }
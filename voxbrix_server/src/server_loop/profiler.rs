@@ -0,0 +1,129 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    time::Duration,
+};
+
+/// Number of the latest ticks kept, 10 seconds of the default 50ms ticks.
+const FRAME_WINDOW: usize = 200;
+
+/// Measured parts of the server loop.
+#[derive(Clone, Copy, Debug)]
+pub enum Section {
+    StateUnpack,
+    Actions,
+    Scripts,
+    ChunkActivation,
+    Send,
+}
+
+impl Section {
+    const ALL: [Section; 5] = [
+        Section::StateUnpack,
+        Section::Actions,
+        Section::Scripts,
+        Section::ChunkActivation,
+        Section::Send,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Section::StateUnpack => "state_unpack",
+            Section::Actions => "actions",
+            Section::Scripts => "scripts",
+            Section::ChunkActivation => "chunk_activation",
+            Section::Send => "send",
+        }
+    }
+}
+
+/// Durations of the sections within a single tick.
+#[derive(Clone, Copy, Default)]
+struct Frame {
+    total: Duration,
+    sections: [Duration; Section::ALL.len()],
+}
+
+/// Time spent in the sections of the server loop over the latest ticks.
+///
+/// The player events are handled between the ticks, their sections go into the next tick.
+/// The sections may overlap, e.g. the scripts run by the actions are counted in both.
+pub struct Profiler {
+    current: Frame,
+    frames: VecDeque<Frame>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            current: Frame::default(),
+            frames: VecDeque::with_capacity(FRAME_WINDOW),
+        }
+    }
+
+    pub fn record(&mut self, section: Section, duration: Duration) {
+        self.current.sections[section as usize] += duration;
+    }
+
+    /// Should be called after every server loop tick.
+    pub fn finish_frame(&mut self, total: Duration) {
+        let mut frame = std::mem::take(&mut self.current);
+        frame.total = total;
+
+        if self.frames.len() == FRAME_WINDOW {
+            self.frames.pop_front();
+        }
+
+        self.frames.push_back(frame);
+    }
+
+    /// Average and maximum durations of the sections and the breakdown of the slowest tick.
+    pub fn dump(&self) -> String {
+        let Some(slowest) = self.frames.iter().max_by_key(|frame| frame.total) else {
+            return "no ticks profiled yet".to_owned();
+        };
+
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let count = self.frames.len() as u32;
+        let total = self
+            .frames
+            .iter()
+            .map(|frame| frame.total)
+            .sum::<Duration>();
+
+        let mut output = format!(
+            "last {} ticks: total avg {:.2}ms max {:.2}ms",
+            count,
+            ms(total / count),
+            ms(slowest.total),
+        );
+
+        for section in Section::ALL {
+            let durations = self
+                .frames
+                .iter()
+                .map(|frame| frame.sections[section as usize]);
+
+            let _ = write!(
+                output,
+                ", {} avg {:.2}ms max {:.2}ms",
+                section.name(),
+                ms(durations.clone().sum::<Duration>() / count),
+                ms(durations.max().unwrap_or_default()),
+            );
+        }
+
+        let _ = write!(output, "; slowest tick {:.2}ms:", ms(slowest.total));
+
+        for section in Section::ALL {
+            let _ = write!(
+                output,
+                " {} {:.2}ms",
+                section.name(),
+                ms(slowest.sections[section as usize])
+            );
+        }
+
+        output
+    }
+}
@@ -0,0 +1,417 @@
+//! Recording of the server loop input for debugging.
+//!
+//! The replay file starts with `MAGIC`, the format version and the `Header`, followed by
+//! postcard-encoded `Record`s until the end of the file. The records are the events
+//! of the server loop that change the world, in the order they were handled, so feeding them
+//! back reproduces the ticks of the recorded session. Chunk loads finish in the background
+//! at an unpredictable time, so the replay holds them back until the point they were handled at.
+//!
+//! The replay must run against a copy of the world as it was when the recording started.
+use crate::{
+    component::player::{
+        client::ClientEvent,
+        role::Role,
+    },
+    entity::player::Player,
+    server_loop::{
+        ServerEvent,
+        SharedEvent,
+    },
+    storage::player::PlayerState,
+};
+use anyhow::{
+    Context,
+    Error,
+    Result,
+};
+use flume::{
+    Receiver,
+    Sender,
+};
+use futures_lite::stream::{
+    self,
+    Stream,
+};
+use log::{
+    error,
+    info,
+    warn,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::VecDeque,
+    fs::{
+        self,
+        File,
+    },
+    io::{
+        BufWriter,
+        Write,
+    },
+    path::Path,
+    thread,
+    time::Duration,
+};
+use tokio::{
+    task,
+    time,
+};
+use voxbrix_common::{
+    entity::chunk::Chunk,
+    pack,
+};
+use voxbrix_protocol::Channel;
+
+const MAGIC: &[u8] = b"VOXBRIX-REPLAY";
+const VERSION: u32 = 4;
+
+/// Time to wait for the chunk load the recording has at the current point.
+/// The replayed world differs from the recorded one if it runs out.
+const CHUNK_LOAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Header {
+    pub world_seed: u64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+enum Record {
+    Process {
+        /// Time passed since the previous processing, in microseconds.
+        elapsed_us: u64,
+    },
+    AddPlayer {
+        player: Player,
+        username: String,
+        role: Role,
+        state: Option<PlayerState>,
+        session_id: u64,
+        connection_session: u64,
+    },
+    ResumePlayer {
+        connection_session: u64,
+        session_id: u64,
+    },
+    PlayerEvent {
+        player: Player,
+        channel: Channel,
+        reliable: bool,
+        data: Vec<u8>,
+        session_id: u64,
+    },
+    ChunkLoaded(Chunk),
+    StructureQueued(Chunk),
+}
+
+impl Record {
+    /// Record of the shared event that has to be handled at the same point in the replay.
+    fn of_shared_event(event: &SharedEvent) -> Option<Self> {
+        match event {
+            SharedEvent::ChunkLoaded { data, .. } => Some(Self::ChunkLoaded(data.chunk)),
+            SharedEvent::StructureQueued(chunk) => Some(Self::StructureQueued(*chunk)),
+            SharedEvent::ChunkGeneration(_) | SharedEvent::ScriptsReloaded(_) => None,
+        }
+    }
+}
+
+/// Appends the server loop events to the replay file.
+/// The file is written in a separate thread.
+pub struct ReplayRecorder {
+    tx: Sender<Vec<u8>>,
+}
+
+impl ReplayRecorder {
+    pub fn create(path: &Path, header: Header) -> Result<Self> {
+        let mut writer = BufWriter::new(
+            File::create(path).with_context(|| format!("unable to create replay {:?}", path))?,
+        );
+        let mut buffer = Vec::new();
+
+        writer.write_all(MAGIC)?;
+        pack::encode_into(&VERSION, &mut buffer);
+        writer.write_all(&buffer)?;
+        pack::encode_into(&header, &mut buffer);
+        writer.write_all(&buffer)?;
+
+        let (tx, rx) = flume::unbounded::<Vec<u8>>();
+
+        thread::spawn(move || {
+            let result = (|| {
+                while let Ok(record) = rx.recv() {
+                    writer.write_all(&record)?;
+
+                    if rx.is_empty() {
+                        writer.flush()?;
+                    }
+                }
+
+                writer.flush()
+            })();
+
+            if let Err(err) = result {
+                error!("unable to write replay: {:?}", err);
+            }
+        });
+
+        Ok(Self { tx })
+    }
+
+    fn record(&self, record: &Record) {
+        let mut buffer = Vec::new();
+        pack::encode_into(record, &mut buffer);
+        let _ = self.tx.send(buffer);
+    }
+
+    pub fn record_process(&self, elapsed: Duration) {
+        self.record(&Record::Process {
+            elapsed_us: elapsed.as_micros() as u64,
+        });
+    }
+
+    pub fn record_add_player(
+        &self,
+        player: Player,
+        username: &str,
+        role: Role,
+        state: Option<&PlayerState>,
+        session_id: u64,
+        connection_session: u64,
+    ) {
+        self.record(&Record::AddPlayer {
+            player,
+            username: username.to_owned(),
+            role,
+            state: state.cloned(),
+            session_id,
+            connection_session,
+        });
+    }
+
+    pub fn record_resume_player(&self, connection_session: u64, session_id: u64) {
+        self.record(&Record::ResumePlayer {
+            connection_session,
+            session_id,
+        });
+    }
+
+    pub fn record_player_event(
+        &self,
+        player: Player,
+        channel: Channel,
+        reliable: bool,
+        data: &[u8],
+        session_id: u64,
+    ) {
+        self.record(&Record::PlayerEvent {
+            player,
+            channel,
+            reliable,
+            data: data.to_vec(),
+            session_id,
+        });
+    }
+
+    pub fn record_shared_event(&self, event: &SharedEvent) {
+        if let Some(record) = Record::of_shared_event(event) {
+            self.record(&record);
+        }
+    }
+}
+
+/// Recorded server loop events to be fed back instead of the ones of the connected clients.
+pub struct Replay {
+    header: Header,
+    records: VecDeque<Record>,
+}
+
+impl Replay {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = fs::read(path).with_context(|| format!("unable to read replay {:?}", path))?;
+
+        let input = file
+            .strip_prefix(MAGIC)
+            .ok_or_else(|| Error::msg("not a replay file"))?;
+
+        let (version, read) = pack::decode_from_slice::<u32>(input)
+            .ok_or_else(|| Error::msg("corrupted replay header"))?;
+
+        if version != VERSION {
+            return Err(Error::msg(format!(
+                "unsupported replay version {}",
+                version
+            )));
+        }
+
+        let mut input = &input[read ..];
+
+        let (header, read) = pack::decode_from_slice::<Header>(input)
+            .ok_or_else(|| Error::msg("corrupted replay header"))?;
+
+        input = &input[read ..];
+
+        let mut records = VecDeque::new();
+
+        while !input.is_empty() {
+            // The recording could be cut short by the server crash
+            let Some((record, read)) = pack::decode_from_slice::<Record>(input) else {
+                warn!("replay is truncated after {} records", records.len());
+                break;
+            };
+
+            input = &input[read ..];
+
+            records.push_back(record);
+        }
+
+        Ok(Self { header, records })
+    }
+
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    /// Server loop events of the recording, the stream ends with the recording.
+    /// The shared events the recording has are held back until their point of the recording,
+    /// the rest are passed as they come.
+    pub fn into_stream(
+        self,
+        shared_event_rx: Receiver<SharedEvent>,
+    ) -> impl Stream<Item = ServerEvent> {
+        let state = ReplayState {
+            records: self.records,
+            shared_event_rx,
+            pending: Vec::new(),
+        };
+
+        stream::unfold(state, |mut state| {
+            async move {
+                let event = state.next_event().await?;
+                Some((event, state))
+            }
+        })
+    }
+}
+
+struct ReplayState {
+    records: VecDeque<Record>,
+    shared_event_rx: Receiver<SharedEvent>,
+    pending: Vec<(Record, SharedEvent)>,
+}
+
+impl ReplayState {
+    /// Replayed clients get nothing, their channels are drained to keep them open.
+    fn client_tx() -> Sender<ClientEvent> {
+        let (tx, rx) = flume::unbounded();
+
+        task::spawn_local(async move { while rx.recv_async().await.is_ok() {} });
+
+        tx
+    }
+
+    /// Shared event to be passed right away, the held back ones are put aside.
+    fn pass_shared_event(&mut self, event: SharedEvent) -> Option<SharedEvent> {
+        match Record::of_shared_event(&event) {
+            Some(record) => {
+                self.pending.push((record, event));
+                None
+            },
+            None => Some(event),
+        }
+    }
+
+    async fn next_event(&mut self) -> Option<ServerEvent> {
+        'records: loop {
+            while let Ok(event) = self.shared_event_rx.try_recv() {
+                if let Some(event) = self.pass_shared_event(event) {
+                    return Some(ServerEvent::SharedEvent(event));
+                }
+            }
+
+            let Some(record) = self.records.pop_front() else {
+                info!("replay finished");
+                return None;
+            };
+
+            let event = match record {
+                Record::Process { elapsed_us } => {
+                    ServerEvent::ReplayedProcess {
+                        elapsed: Duration::from_micros(elapsed_us),
+                    }
+                },
+                Record::AddPlayer {
+                    player,
+                    username,
+                    role,
+                    state,
+                    session_id,
+                    connection_session,
+                } => {
+                    ServerEvent::AddPlayer {
+                        player,
+                        username,
+                        role,
+                        state,
+                        client_tx: Self::client_tx(),
+                        session_id,
+                        connection_session,
+                    }
+                },
+                Record::ResumePlayer {
+                    connection_session,
+                    session_id,
+                } => {
+                    ServerEvent::ResumePlayer {
+                        connection_session,
+                        client_tx: Self::client_tx(),
+                        session_id,
+                    }
+                },
+                Record::PlayerEvent {
+                    player,
+                    channel,
+                    reliable,
+                    data,
+                    session_id,
+                } => {
+                    ServerEvent::PlayerEvent {
+                        player,
+                        channel,
+                        reliable,
+                        data: data.into(),
+                        session_id,
+                    }
+                },
+                record @ (Record::ChunkLoaded(_) | Record::StructureQueued(_)) => {
+                    loop {
+                        if let Some(index) = self.pending.iter().position(|(r, _)| *r == record) {
+                            let (_, event) = self.pending.remove(index);
+                            break ServerEvent::SharedEvent(event);
+                        }
+
+                        match time::timeout(CHUNK_LOAD_TIMEOUT, self.shared_event_rx.recv_async())
+                            .await
+                        {
+                            Ok(Ok(event)) => {
+                                if let Some(event) = self.pass_shared_event(event) {
+                                    // Still waiting for the recorded one
+                                    self.records.push_front(record);
+                                    return Some(ServerEvent::SharedEvent(event));
+                                }
+                            },
+                            Ok(Err(_)) => return None,
+                            Err(_) => {
+                                warn!("replay: {:?} has not happened, skipping", record);
+                                continue 'records;
+                            },
+                        }
+                    }
+                },
+            };
+
+            return Some(event);
+        }
+    }
+}
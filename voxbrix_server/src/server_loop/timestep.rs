@@ -0,0 +1,51 @@
+use std::time::{
+    Duration,
+    Instant,
+};
+
+/// Ticks due after advancing the `FixedTimestep`.
+pub struct DueTicks {
+    pub run: u32,
+    pub dropped: u32,
+}
+
+/// Accumulates the real time and tells how many fixed length ticks are due.
+/// After the slow ticks a limited number of the missed ones is caught up,
+/// the rest are dropped, so that the server does not fall further behind.
+pub struct FixedTimestep {
+    step: Duration,
+    max_catch_up_ticks: u32,
+    accumulated: Duration,
+    last_advance: Instant,
+}
+
+impl FixedTimestep {
+    pub fn new(step: Duration, max_catch_up_ticks: u32) -> Self {
+        Self {
+            step,
+            max_catch_up_ticks: max_catch_up_ticks.max(1),
+            accumulated: Duration::ZERO,
+            last_advance: Instant::now(),
+        }
+    }
+
+    pub fn advance(&mut self, now: Instant) -> DueTicks {
+        self.accumulated += now.saturating_duration_since(self.last_advance);
+        self.last_advance = now;
+
+        let due: u32 = (self.accumulated.as_nanos() / self.step.as_nanos().max(1))
+            .try_into()
+            .unwrap_or(u32::MAX);
+
+        let run = due.min(self.max_catch_up_ticks);
+
+        self.accumulated = self
+            .accumulated
+            .saturating_sub(self.step.saturating_mul(due));
+
+        DueTicks {
+            run,
+            dropped: due - run,
+        }
+    }
+}
@@ -1,8 +1,24 @@
-use flume::Sender;
+use crate::{
+    component::block::metadata::ChunkMetadata,
+    BLOCK_CLASS_TABLE,
+    BLOCK_ENTITY_TABLE,
+    BLOCK_METADATA_TABLE,
+    STRUCTURE_PLACEMENT_TABLE,
+};
+use ahash::AHashMap;
+use anyhow::Result;
+use flume::{
+    RecvTimeoutError,
+    Sender,
+};
+use log::error;
 use redb::{
+    Database,
     Key,
+    ReadableTable,
     Value,
 };
+use region::RegionStorage;
 use serde::{
     Deserialize,
     Serialize,
@@ -11,34 +27,577 @@ use std::{
     cmp::Ordering,
     fmt::Debug,
     marker::PhantomData,
-    thread,
+    sync::{
+        Arc,
+        Mutex,
+    },
+    thread::{
+        self,
+        JoinHandle,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
 };
-use voxbrix_common::pack::{
-    Pack,
-    Packer,
+use voxbrix_common::{
+    component::block::{
+        block_entity::ChunkBlockEntities,
+        BlocksVec,
+    },
+    entity::{
+        block::Block,
+        block_class::BlockClass,
+        chunk::Chunk,
+    },
+    pack::{
+        Pack,
+        Packer,
+    },
 };
 
+pub mod archive;
+pub mod label;
+pub mod migrations;
+pub mod region;
+pub mod world;
+
+/// Latest unsaved data of a chunk.
+#[derive(Clone, Default)]
+pub struct PendingChunkWrite {
+    pub block_classes: Option<Arc<BlocksVec<BlockClass>>>,
+    pub metadata: Option<Arc<ChunkMetadata>>,
+    pub block_entities: Option<Arc<ChunkBlockEntities>>,
+    /// Flushed writes are only removed if the chunk has not been written again since.
+    version: u64,
+}
+
+#[derive(Default)]
+struct PendingWritesInner {
+    chunks: AHashMap<Chunk, PendingChunkWrite>,
+    next_version: u64,
+}
+
+/// Chunk writes waiting to be flushed to the storage.
+/// The chunk loading must check them first, the storage may have the outdated data.
+#[derive(Clone, Default)]
+pub struct PendingWrites(Arc<Mutex<PendingWritesInner>>);
+
+impl PendingWrites {
+    /// Returns the number of the chunks waiting to be flushed.
+    fn update(&self, chunk: Chunk, update: impl FnOnce(&mut PendingChunkWrite)) -> usize {
+        let mut inner = self.0.lock().unwrap();
+        let version = inner.next_version;
+        inner.next_version += 1;
+
+        let write = inner.chunks.entry(chunk).or_default();
+        update(write);
+        write.version = version;
+
+        inner.chunks.len()
+    }
+
+    pub fn save_block_classes(&self, chunk: Chunk, block_classes: BlocksVec<BlockClass>) -> usize {
+        self.update(chunk, |write| {
+            write.block_classes = Some(Arc::new(block_classes))
+        })
+    }
+
+    pub fn save_metadata(&self, chunk: Chunk, metadata: ChunkMetadata) -> usize {
+        self.update(chunk, |write| write.metadata = Some(Arc::new(metadata)))
+    }
+
+    pub fn save_block_entities(&self, chunk: Chunk, block_entities: ChunkBlockEntities) -> usize {
+        self.update(chunk, |write| {
+            write.block_entities = Some(Arc::new(block_entities))
+        })
+    }
+
+    pub fn get(&self, chunk: &Chunk) -> Option<PendingChunkWrite> {
+        self.0.lock().unwrap().chunks.get(chunk).cloned()
+    }
+
+    fn snapshot(&self) -> Vec<(Chunk, PendingChunkWrite)> {
+        self.0
+            .lock()
+            .unwrap()
+            .chunks
+            .iter()
+            .map(|(chunk, write)| (*chunk, write.clone()))
+            .collect()
+    }
+
+    fn remove_flushed(&self, flushed: &[(Chunk, PendingChunkWrite)]) {
+        let mut inner = self.0.lock().unwrap();
+
+        for (chunk, write) in flushed {
+            if inner
+                .chunks
+                .get(chunk)
+                .is_some_and(|pending| pending.version == write.version)
+            {
+                inner.chunks.remove(chunk);
+            }
+        }
+    }
+}
+
+enum StorageTask {
+    Execute(Box<dyn FnMut() + Send>),
+    Flush,
+}
+
+/// Background thread of the storage writes.
+/// Chunk writes are coalesced and flushed in batches every interval or once enough chunks
+/// are waiting, the rest of the writes are executed in order.
+/// Everything left is flushed when the thread is dropped.
 pub struct StorageThread {
-    tx: Sender<Box<dyn FnMut() + Send>>,
+    tx: Option<Sender<StorageTask>>,
+    pending: PendingWrites,
+    flush_chunks: usize,
+    thread: Option<JoinHandle<()>>,
 }
 
 impl StorageThread {
-    pub fn new() -> Self {
-        let (tx, rx) = flume::unbounded::<Box<dyn FnMut() + Send>>();
-        thread::spawn(move || {
-            while let Ok(mut task) = rx.recv() {
-                task();
-            }
-        });
+    /// `flush_chunks` is the number of the waiting chunks that triggers the flush
+    /// before the `flush_interval` ends.
+    pub fn new(
+        chunk_storage: ChunkStorage,
+        metadata_storage: MetadataStorage,
+        block_entity_storage: BlockEntityStorage,
+        flush_interval: Duration,
+        flush_chunks: usize,
+    ) -> Self {
+        let (tx, rx) = flume::unbounded::<StorageTask>();
+        let pending = PendingWrites::default();
+
+        let thread = {
+            let pending = pending.clone();
+
+            thread::spawn(move || {
+                let mut packer = Packer::new();
+                let mut last_flush = Instant::now();
+
+                let mut flush = |last_flush: &mut Instant| {
+                    let writes = pending.snapshot();
+
+                    if !writes.is_empty() {
+                        chunk_storage.save_batch(
+                            writes.iter().filter_map(|(chunk, write)| {
+                                Some((*chunk, write.block_classes.as_deref()?))
+                            }),
+                            &mut packer,
+                        );
+
+                        metadata_storage.save_batch(
+                            writes.iter().filter_map(|(chunk, write)| {
+                                Some((*chunk, write.metadata.as_deref()?))
+                            }),
+                            &mut packer,
+                        );
+
+                        block_entity_storage.save_batch(
+                            writes.iter().filter_map(|(chunk, write)| {
+                                Some((*chunk, write.block_entities.as_deref()?))
+                            }),
+                            &mut packer,
+                        );
+
+                        pending.remove_flushed(&writes);
+                    }
+
+                    *last_flush = Instant::now();
+                };
+
+                loop {
+                    let timeout = flush_interval.saturating_sub(last_flush.elapsed());
+
+                    match rx.recv_timeout(timeout) {
+                        Ok(StorageTask::Execute(mut task)) => task(),
+                        Ok(StorageTask::Flush) => flush(&mut last_flush),
+                        Err(RecvTimeoutError::Timeout) => flush(&mut last_flush),
+                        Err(RecvTimeoutError::Disconnected) => {
+                            flush(&mut last_flush);
+                            return;
+                        },
+                    }
+
+                    if last_flush.elapsed() >= flush_interval {
+                        flush(&mut last_flush);
+                    }
+                }
+            })
+        };
 
-        Self { tx }
+        Self {
+            tx: Some(tx),
+            pending,
+            flush_chunks,
+            thread: Some(thread),
+        }
     }
 
     pub fn execute<F>(&self, task: F)
     where
         F: 'static + FnMut() + Send,
     {
-        let _ = self.tx.send(Box::new(task));
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(StorageTask::Execute(Box::new(task)));
+        }
+    }
+
+    /// Writes waiting to be flushed, must be checked by the chunk loading.
+    pub fn pending_writes(&self) -> &PendingWrites {
+        &self.pending
+    }
+
+    /// Flushes the waiting writes without waiting for the interval.
+    pub fn flush(&self) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(StorageTask::Flush);
+        }
+    }
+
+    fn flush_if_full(&self, pending_chunks: usize) {
+        if pending_chunks == self.flush_chunks {
+            self.flush();
+        }
+    }
+
+    pub fn save_block_classes(&self, chunk: Chunk, block_classes: BlocksVec<BlockClass>) {
+        self.flush_if_full(self.pending.save_block_classes(chunk, block_classes));
+    }
+
+    pub fn save_metadata(&self, chunk: Chunk, metadata: ChunkMetadata) {
+        self.flush_if_full(self.pending.save_metadata(chunk, metadata));
+    }
+
+    pub fn save_block_entities(&self, chunk: Chunk, block_entities: ChunkBlockEntities) {
+        self.flush_if_full(self.pending.save_block_entities(chunk, block_entities));
+    }
+}
+
+impl Drop for StorageThread {
+    fn drop(&mut self) {
+        // The thread flushes the rest of the writes once the channel is closed
+        self.tx.take();
+
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("storage thread has panicked, the last writes may be lost");
+            }
+        }
+    }
+}
+
+/// Persistent storage of the chunk block classes.
+#[derive(Clone)]
+pub enum ChunkStorage {
+    /// Each chunk is a separate row in the `BLOCK_CLASS_TABLE`.
+    Database(Arc<Database>),
+    /// Chunks are packed into region files.
+    Region(Arc<RegionStorage>),
+}
+
+impl ChunkStorage {
+    /// Load the chunk block classes, `None` if the chunk was never saved.
+    pub fn load(&self, chunk: Chunk, packer: &mut Packer) -> Option<BlocksVec<BlockClass>> {
+        match self {
+            Self::Database(database) => {
+                let db_read = database.begin_read().unwrap();
+                let table = db_read
+                    .open_table(BLOCK_CLASS_TABLE)
+                    .expect("chunk storage: database read");
+
+                table
+                    .get(chunk.into_data_sized())
+                    .unwrap()
+                    .map(|bytes| bytes.value().into_inner(packer))
+            },
+            Self::Region(region) => {
+                region
+                    .load(&chunk)
+                    .expect("chunk storage: region read")
+                    .map(|bytes| {
+                        packer
+                            .unpack(&bytes)
+                            .expect("chunk storage: corrupted region data")
+                    })
+            },
+        }
+    }
+
+    /// Save the chunk block classes.
+    pub fn save(&self, chunk: Chunk, block_classes: &BlocksVec<BlockClass>, packer: &mut Packer) {
+        self.save_batch([(chunk, block_classes)], packer);
+    }
+
+    /// Save the block classes of multiple chunks, in a single transaction for the database.
+    pub fn save_batch<'a>(
+        &self,
+        chunks: impl IntoIterator<Item = (Chunk, &'a BlocksVec<BlockClass>)>,
+        packer: &mut Packer,
+    ) {
+        match self {
+            Self::Database(database) => {
+                let db_write = database.begin_write().unwrap();
+                {
+                    let mut table = db_write.open_table(BLOCK_CLASS_TABLE).unwrap();
+
+                    for (chunk, block_classes) in chunks {
+                        table
+                            .insert(chunk.into_data_sized(), block_classes.into_data(packer))
+                            .expect("chunk storage: database write");
+                    }
+                }
+                db_write.commit().unwrap();
+            },
+            Self::Region(region) => {
+                for (chunk, block_classes) in chunks {
+                    region
+                        .save(&chunk, &packer.pack_to_vec(block_classes))
+                        .expect("chunk storage: region write");
+                }
+            },
+        }
+    }
+
+    /// Call `f` for every saved chunk with its packed block classes.
+    pub fn for_each_packed(&self, mut f: impl FnMut(Chunk, &[u8]) -> Result<()>) -> Result<()> {
+        match self {
+            Self::Database(database) => {
+                let db_read = database.begin_read()?;
+                let table = db_read.open_table(BLOCK_CLASS_TABLE)?;
+
+                for entry in table.iter()? {
+                    let (chunk, block_classes) = entry?;
+                    f(chunk.value().into_inner(), block_classes.value().as_ref())?;
+                }
+
+                Ok(())
+            },
+            Self::Region(region) => region.for_each(f),
+        }
+    }
+
+    /// Save chunks with already packed block classes.
+    pub fn save_packed<'a>(
+        &self,
+        chunks: impl IntoIterator<Item = (Chunk, &'a [u8])>,
+    ) -> Result<()> {
+        match self {
+            Self::Database(database) => {
+                let db_write = database.begin_write()?;
+                {
+                    let mut table = db_write.open_table(BLOCK_CLASS_TABLE)?;
+
+                    for (chunk, block_classes) in chunks {
+                        table.insert(
+                            chunk.into_data_sized(),
+                            Data::<BlocksVec<BlockClass>>::new_shared(block_classes),
+                        )?;
+                    }
+                }
+                db_write.commit()?;
+            },
+            Self::Region(region) => {
+                for (chunk, block_classes) in chunks {
+                    region.save(&chunk, block_classes)?;
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Remove all saved chunks.
+    pub fn clear(&self) -> Result<()> {
+        match self {
+            Self::Database(database) => {
+                let db_write = database.begin_write()?;
+                db_write.delete_table(BLOCK_CLASS_TABLE)?;
+                db_write.open_table(BLOCK_CLASS_TABLE)?;
+                db_write.commit()?;
+            },
+            Self::Region(region) => region.clear()?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Persistent storage of the block metadata.
+/// Kept in the database regardless of the chunk storage kind, as the metadata is sparse.
+#[derive(Clone)]
+pub struct MetadataStorage(Arc<Database>);
+
+impl MetadataStorage {
+    pub fn new(database: Arc<Database>) -> Self {
+        Self(database)
+    }
+
+    /// Load the chunk block metadata, empty if the chunk has none.
+    pub fn load(&self, chunk: Chunk, packer: &mut Packer) -> ChunkMetadata {
+        let db_read = self.0.begin_read().unwrap();
+        let table = db_read
+            .open_table(BLOCK_METADATA_TABLE)
+            .expect("metadata storage: database read");
+
+        table
+            .get(chunk.into_data_sized())
+            .unwrap()
+            .map(|bytes| bytes.value().into_inner(packer))
+            .unwrap_or_default()
+    }
+
+    /// Save the block metadata of multiple chunks in a single transaction,
+    /// removing the empty ones.
+    pub fn save_batch<'a>(
+        &self,
+        chunks: impl IntoIterator<Item = (Chunk, &'a ChunkMetadata)>,
+        packer: &mut Packer,
+    ) {
+        let db_write = self.0.begin_write().unwrap();
+        {
+            let mut table = db_write.open_table(BLOCK_METADATA_TABLE).unwrap();
+
+            for (chunk, metadata) in chunks {
+                if metadata.is_empty() {
+                    table
+                        .remove(chunk.into_data_sized())
+                        .expect("metadata storage: database write");
+                } else {
+                    table
+                        .insert(chunk.into_data_sized(), metadata.into_data(packer))
+                        .expect("metadata storage: database write");
+                }
+            }
+        }
+        db_write.commit().unwrap();
+    }
+}
+
+/// Persistent storage of the block entities, kept in the database like the metadata.
+#[derive(Clone)]
+pub struct BlockEntityStorage(Arc<Database>);
+
+impl BlockEntityStorage {
+    pub fn new(database: Arc<Database>) -> Self {
+        Self(database)
+    }
+
+    /// Load the chunk block entities, empty if the chunk has none.
+    pub fn load(&self, chunk: Chunk, packer: &mut Packer) -> ChunkBlockEntities {
+        let db_read = self.0.begin_read().unwrap();
+        let table = db_read
+            .open_table(BLOCK_ENTITY_TABLE)
+            .expect("block entity storage: database read");
+
+        table
+            .get(chunk.into_data_sized())
+            .unwrap()
+            .map(|bytes| bytes.value().into_inner(packer))
+            .unwrap_or_default()
+    }
+
+    /// Save the block entities of multiple chunks in a single transaction,
+    /// removing the empty ones.
+    pub fn save_batch<'a>(
+        &self,
+        chunks: impl IntoIterator<Item = (Chunk, &'a ChunkBlockEntities)>,
+        packer: &mut Packer,
+    ) {
+        let db_write = self.0.begin_write().unwrap();
+        {
+            let mut table = db_write.open_table(BLOCK_ENTITY_TABLE).unwrap();
+
+            for (chunk, block_entities) in chunks {
+                if block_entities.is_empty() {
+                    table
+                        .remove(chunk.into_data_sized())
+                        .expect("block entity storage: database write");
+                } else {
+                    table
+                        .insert(chunk.into_data_sized(), block_entities.into_data(packer))
+                        .expect("block entity storage: database write");
+                }
+            }
+        }
+        db_write.commit().unwrap();
+    }
+}
+
+/// Structure blocks placed into a chunk by the generation of another chunk.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct StructurePlacements(pub Vec<(Block, BlockClass)>);
+
+impl Pack for StructurePlacements {
+    const DEFAULT_COMPRESSED: bool = true;
+}
+
+impl TypeName for StructurePlacements {
+    const NAME: &'static str = "StructurePlacements";
+}
+
+/// Structure placements waiting for their chunks to be generated or loaded.
+#[derive(Clone)]
+pub struct StructureStorage(Arc<Database>);
+
+impl StructureStorage {
+    pub fn new(database: Arc<Database>) -> Self {
+        Self(database)
+    }
+
+    /// Queue the placements after the ones already queued for the chunk.
+    pub fn add(&self, chunk: Chunk, placements: &[(Block, BlockClass)], packer: &mut Packer) {
+        let db_write = self.0.begin_write().unwrap();
+        {
+            let mut table = db_write.open_table(STRUCTURE_PLACEMENT_TABLE).unwrap();
+
+            let mut queued = table
+                .get(chunk.into_data_sized())
+                .unwrap()
+                .map(|bytes| bytes.value().into_inner(packer))
+                .unwrap_or_default();
+
+            queued.0.extend_from_slice(placements);
+
+            table
+                .insert(chunk.into_data_sized(), queued.into_data(packer))
+                .expect("structure storage: database write");
+        }
+        db_write.commit().unwrap();
+    }
+
+    /// Remove and return the placements queued for the chunk.
+    pub fn take(&self, chunk: Chunk, packer: &mut Packer) -> Vec<(Block, BlockClass)> {
+        // Most of the chunks have nothing queued, avoiding the write transaction for these
+        let is_queued = {
+            let db_read = self.0.begin_read().unwrap();
+            let table = db_read
+                .open_table(STRUCTURE_PLACEMENT_TABLE)
+                .expect("structure storage: database read");
+
+            table.get(chunk.into_data_sized()).unwrap().is_some()
+        };
+
+        if !is_queued {
+            return Vec::new();
+        }
+
+        let db_write = self.0.begin_write().unwrap();
+        let placements = {
+            let mut table = db_write.open_table(STRUCTURE_PLACEMENT_TABLE).unwrap();
+
+            table
+                .remove(chunk.into_data_sized())
+                .expect("structure storage: database write")
+                .map(|bytes| bytes.value().into_inner(packer))
+                .unwrap_or_default()
+        };
+        db_write.commit().unwrap();
+
+        placements.0
     }
 }
 
@@ -185,6 +744,12 @@ impl<T> Data<'static, T> {
     }
 }
 
+impl<T> AsRef<[u8]> for Data<'_, T> {
+    fn as_ref(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+}
+
 impl<'a, T> Data<'a, T>
 where
     T: Pack + Deserialize<'a>,
@@ -251,18 +816,54 @@ where
 }
 
 pub mod player {
-    use crate::storage::TypeName;
+    use crate::{
+        component::player::role::Role,
+        entity::player::Player,
+        storage::{
+            IntoData,
+            IntoDataSized,
+            TypeName,
+        },
+        PLAYER_TABLE,
+    };
+    use anyhow::Result;
+    use redb::{
+        Database,
+        ReadableTable,
+    };
     use serde::{
         Deserialize,
         Serialize,
     };
-    use voxbrix_common::pack::Pack;
+    use voxbrix_common::{
+        component::actor::{
+            inventory::Inventory,
+            orientation::Orientation,
+            position::Position,
+        },
+        pack::{
+            Pack,
+            Packer,
+        },
+    };
+
+    /// State of the player actor kept between the sessions.
+    /// The position chunk has the dimension the player has left in.
+    #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+    pub struct PlayerState {
+        pub position: Position,
+        pub orientation: Option<Orientation>,
+        pub inventory: Inventory,
+    }
 
     #[derive(Serialize, Deserialize, Debug)]
     pub struct PlayerProfile {
         pub username: String,
         #[serde(with = "serde_big_array::BigArray")]
         pub public_key: [u8; 33],
+        pub role: Role,
+        /// Empty until the player has been in the world.
+        pub state: Option<PlayerState>,
     }
 
     impl Pack for PlayerProfile {
@@ -272,4 +873,35 @@ pub mod player {
     impl TypeName for PlayerProfile {
         const NAME: &'static str = "PlayerProfile";
     }
+
+    /// Saves the states of the players in a single transaction.
+    pub fn save_states(
+        database: &Database,
+        states: &[(Player, PlayerState)],
+        packer: &mut Packer,
+    ) -> Result<()> {
+        let db_write = database.begin_write()?;
+
+        {
+            let mut table = db_write.open_table(PLAYER_TABLE)?;
+
+            for (player, state) in states {
+                let profile = table
+                    .get(player.into_data_sized())?
+                    .map(|profile| profile.value().into_inner(packer));
+
+                let Some(mut profile) = profile else {
+                    continue;
+                };
+
+                profile.state = Some(state.clone());
+
+                table.insert(player.into_data_sized(), profile.into_data(packer))?;
+            }
+        }
+
+        db_write.commit()?;
+
+        Ok(())
+    }
 }
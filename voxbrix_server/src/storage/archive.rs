@@ -0,0 +1,181 @@
+//! Portable world archive.
+//!
+//! The archive starts with `MAGIC` and the format version, followed by postcard-encoded
+//! `Record`s until the end of the file. Player profiles and chunk block classes are kept
+//! packed, the same way they are stored by the server.
+use crate::{
+    entity::player::Player,
+    storage::{
+        player::PlayerProfile,
+        ChunkStorage,
+        Data,
+        IntoDataSized,
+    },
+    PLAYER_TABLE,
+    USERNAME_TABLE,
+};
+use anyhow::{
+    Error,
+    Result,
+};
+use redb::{
+    Database,
+    ReadableTable,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    fs::{
+        self,
+        File,
+    },
+    io::{
+        BufWriter,
+        Write,
+    },
+    path::Path,
+};
+use voxbrix_common::{
+    entity::chunk::Chunk,
+    pack,
+};
+
+const MAGIC: &[u8] = b"VOXBRIX-WORLD";
+const VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+enum Record<'a> {
+    Player {
+        player: Player,
+        profile: &'a [u8],
+    },
+    Username {
+        username: &'a str,
+        player: Player,
+    },
+    Chunk {
+        chunk: Chunk,
+        block_classes: &'a [u8],
+    },
+}
+
+/// Write players and chunks of the world into the archive file.
+pub fn export(database: &Database, chunk_storage: &ChunkStorage, path: &Path) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let mut buffer = Vec::new();
+
+    writer.write_all(MAGIC)?;
+    pack::encode_into(&VERSION, &mut buffer);
+    writer.write_all(&buffer)?;
+
+    let db_read = database.begin_read()?;
+
+    let players = db_read.open_table(PLAYER_TABLE)?;
+
+    for entry in players.iter()? {
+        let (player, profile) = entry?;
+        let profile = profile.value();
+
+        let record = Record::Player {
+            player: player.value().into_inner(),
+            profile: profile.as_ref(),
+        };
+
+        pack::encode_into(&record, &mut buffer);
+        writer.write_all(&buffer)?;
+    }
+
+    let usernames = db_read.open_table(USERNAME_TABLE)?;
+
+    for entry in usernames.iter()? {
+        let (username, player) = entry?;
+
+        let record = Record::Username {
+            username: username.value(),
+            player: player.value().into_inner(),
+        };
+
+        pack::encode_into(&record, &mut buffer);
+        writer.write_all(&buffer)?;
+    }
+
+    chunk_storage.for_each_packed(|chunk, block_classes| {
+        let record = Record::Chunk {
+            chunk,
+            block_classes,
+        };
+
+        pack::encode_into(&record, &mut buffer);
+        writer.write_all(&buffer)?;
+
+        Ok(())
+    })?;
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Replace players and chunks of the world with the ones from the archive file.
+/// Nothing is changed if the archive is corrupted.
+pub fn import(database: &Database, chunk_storage: &ChunkStorage, path: &Path) -> Result<()> {
+    let archive = fs::read(path)?;
+
+    let input = archive
+        .strip_prefix(MAGIC)
+        .ok_or_else(|| Error::msg("not a world archive"))?;
+
+    let (version, read) = pack::decode_from_slice::<u32>(input)
+        .ok_or_else(|| Error::msg("corrupted world archive header"))?;
+
+    if version != VERSION {
+        return Err(Error::msg(format!(
+            "unsupported world archive version {}",
+            version
+        )));
+    }
+
+    let mut input = &input[read ..];
+    let mut chunks = Vec::new();
+
+    let db_write = database.begin_write()?;
+    db_write.delete_table(PLAYER_TABLE)?;
+    db_write.delete_table(USERNAME_TABLE)?;
+    {
+        let mut players = db_write.open_table(PLAYER_TABLE)?;
+        let mut usernames = db_write.open_table(USERNAME_TABLE)?;
+
+        while !input.is_empty() {
+            let (record, read) = pack::decode_from_slice::<Record>(input)
+                .ok_or_else(|| Error::msg("corrupted world archive record"))?;
+
+            input = &input[read ..];
+
+            match record {
+                Record::Player { player, profile } => {
+                    players.insert(
+                        player.into_data_sized(),
+                        Data::<PlayerProfile>::new_shared(profile),
+                    )?;
+                },
+                Record::Username { username, player } => {
+                    usernames.insert(username, player.into_data_sized())?;
+                },
+                Record::Chunk {
+                    chunk,
+                    block_classes,
+                } => {
+                    chunks.push((chunk, block_classes));
+                },
+            }
+        }
+    }
+    db_write.commit()?;
+
+    chunk_storage.clear()?;
+    chunk_storage.save_packed(chunks)?;
+
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use crate::{
+    storage::{
+        Data,
+        TypeName,
+    },
+    LABEL_TABLE,
+};
+use anyhow::{
+    Context,
+    Error,
+    Result,
+};
+use log::info;
+use redb::{
+    Database,
+    ReadableTable,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    fmt::Debug,
+    path::Path,
+    sync::Arc,
+};
+use tokio::task;
+use voxbrix_common::{
+    pack::{
+        Pack,
+        Packer,
+    },
+    system::{
+        list_loading::List,
+        pack_loading::PackSet,
+    },
+};
+
+/// Labels in the order of their ids.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LabelOrder {
+    pub labels: Vec<String>,
+}
+
+impl Pack for LabelOrder {
+    const DEFAULT_COMPRESSED: bool = false;
+}
+
+impl TypeName for LabelOrder {
+    const NAME: &'static str = "LabelOrder";
+}
+
+/// Keeps the ids assigned to the labels of the `kind` the same when the packs change.
+/// The labels stored for the world keep their positions, the new labels of the `list`
+/// are appended in the list order. Fails if a stored label is missing from the list,
+/// the saved world could refer to it.
+pub fn stable_list(database: &Database, kind: &str, list: List) -> Result<List> {
+    let mut packer = Packer::new();
+
+    let db_write = database.begin_write()?;
+
+    let labels = {
+        let mut table = db_write.open_table(LABEL_TABLE)?;
+
+        let mut labels = table
+            .get(kind)?
+            .map(|bytes| bytes.value().into_inner(&mut packer))
+            .map(|order: LabelOrder| order.labels)
+            .unwrap_or_default();
+
+        if let Some(missing) = labels.iter().find(|label| !list.list.contains(label)) {
+            return Err(Error::msg(format!(
+                "{} \"{}\" is used by the world but none of the packs define it",
+                kind, missing
+            )));
+        }
+
+        let stored_len = labels.len();
+
+        for label in list.list {
+            if !labels.contains(&label) {
+                labels.push(label);
+            }
+        }
+
+        if labels.len() != stored_len {
+            info!("{} new {} labels assigned", labels.len() - stored_len, kind);
+
+            let order = LabelOrder { labels };
+
+            table.insert(kind, Data::from_inner(&order, &mut packer))?;
+
+            order.labels
+        } else {
+            labels
+        }
+    };
+
+    db_write.commit()?;
+
+    Ok(List { list: labels })
+}
+
+/// Loads the list merged from the packs, with the ids stable for the world.
+pub async fn load_stable_list(
+    database: Arc<Database>,
+    packs: &PackSet,
+    kind: &'static str,
+    path: impl 'static + AsRef<Path> + Debug + Send + Clone,
+) -> Result<List> {
+    let list = packs.load_list(path).await?;
+
+    task::spawn_blocking(move || stable_list(&database, kind, list))
+        .await
+        .unwrap()
+        .with_context(|| format!("unable to assign ids to {} labels", kind))
+}
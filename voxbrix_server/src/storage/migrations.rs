@@ -0,0 +1,352 @@
+use crate::{
+    component::player::role::Role,
+    storage::{
+        player::PlayerProfile,
+        Data,
+        IntoData,
+    },
+    BLOCK_CLASS_TABLE,
+    BLOCK_ENTITY_TABLE,
+    BLOCK_METADATA_TABLE,
+    LABEL_TABLE,
+    PLAYER_TABLE,
+    SCHEMA_TABLE,
+    STRUCTURE_PLACEMENT_TABLE,
+    USERNAME_TABLE,
+    WORLD_TABLE,
+};
+use anyhow::{
+    Error,
+    Result,
+};
+use log::info;
+use redb::{
+    Database,
+    ReadableTable,
+    WriteTransaction,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use voxbrix_common::{
+    component::block::BlocksVec,
+    entity::{
+        block::BLOCKS_IN_CHUNK,
+        block_class::BlockClass,
+    },
+    pack::Packer,
+};
+
+const VERSION_KEY: &str = "version";
+
+type Migration = fn(&WriteTransaction) -> Result<()>;
+
+/// Migrations in the order of the schema versions, the migration at index `i`
+/// upgrades the database from version `i` to version `i + 1`.
+/// New ones are only ever appended, the existing ones must not change.
+const MIGRATIONS: &[Migration] = &[create_tables, add_player_state];
+
+/// Schema version of the database the server works with.
+pub const SCHEMA_VERSION: u64 = MIGRATIONS.len() as u64;
+
+/// Brings the database to the current schema version.
+/// All the pending migrations run in a single transaction, so a failed one leaves
+/// the database as it was.
+/// Databases of a newer version are refused, the older server would corrupt them.
+pub fn run(database: &Database) -> Result<()> {
+    let db_write = database.begin_write()?;
+
+    {
+        let mut table = db_write.open_table(SCHEMA_TABLE)?;
+
+        // Databases created before the versioning have no version and start from the beginning,
+        // the first migration only creates the tables that are missing
+        let version = table.get(VERSION_KEY)?.map(|v| v.value()).unwrap_or(0);
+
+        if version > SCHEMA_VERSION {
+            return Err(Error::msg(format!(
+                "database schema version {} is newer than the supported version {}",
+                version, SCHEMA_VERSION
+            )));
+        }
+
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            let to_version = index as u64 + 1;
+
+            migration(&db_write)?;
+
+            info!("database schema migrated to version {}", to_version);
+        }
+
+        table.insert(VERSION_KEY, SCHEMA_VERSION)?;
+    }
+
+    db_write.commit()?;
+
+    Ok(())
+}
+
+/// Version 1, the tables of the world.
+/// The databases from before the versioning get the player roles
+/// and the run-length encoded chunks.
+fn create_tables(db_write: &WriteTransaction) -> Result<()> {
+    db_write.open_table(USERNAME_TABLE)?;
+    db_write.open_table(PLAYER_TABLE)?;
+    db_write.open_table(BLOCK_CLASS_TABLE)?;
+    db_write.open_table(BLOCK_METADATA_TABLE)?;
+    db_write.open_table(BLOCK_ENTITY_TABLE)?;
+    db_write.open_table(STRUCTURE_PLACEMENT_TABLE)?;
+    db_write.open_table(WORLD_TABLE)?;
+    db_write.open_table(LABEL_TABLE)?;
+
+    add_player_role(db_write)?;
+    encode_block_class_runs(db_write)
+}
+
+/// `PlayerProfile` of the databases from before the versioning.
+#[derive(Serialize, Deserialize)]
+struct PlayerProfileV0 {
+    username: String,
+    #[serde(with = "serde_big_array::BigArray")]
+    public_key: [u8; 33],
+}
+
+fn add_player_role(db_write: &WriteTransaction) -> Result<()> {
+    let mut packer = Packer::new();
+    let mut table = db_write.open_table(PLAYER_TABLE)?;
+
+    let profiles = table
+        .iter()?
+        .map(|entry| {
+            let (player, profile) = entry?;
+
+            let PlayerProfileV0 {
+                username,
+                public_key,
+            } = packer
+                .unpack_uncompressed(profile.value().as_ref())
+                .map_err(|_| Error::msg("corrupted player profile"))?;
+
+            // Everyone could modify the world before the roles
+            let profile = PlayerProfileV1 {
+                username,
+                public_key,
+                role: Role::Player,
+            };
+
+            Ok((player.value(), packer.pack_uncompressed_to_vec(&profile)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    for (player, profile) in profiles {
+        table.insert(player, Data::new_owned(profile))?;
+    }
+
+    Ok(())
+}
+
+/// `BlocksVec<BlockClass>` of the databases from before the versioning,
+/// all the block classes of the chunk one after another.
+#[derive(Serialize, Deserialize)]
+struct BlockClassesV0(#[serde(with = "serde_big_array::BigArray")] [BlockClass; BLOCKS_IN_CHUNK]);
+
+fn encode_block_class_runs(db_write: &WriteTransaction) -> Result<()> {
+    let mut packer = Packer::new();
+    let mut table = db_write.open_table(BLOCK_CLASS_TABLE)?;
+
+    let chunks = table
+        .iter()?
+        .map(|entry| {
+            let (chunk, block_classes) = entry?;
+
+            let BlockClassesV0(block_classes) = packer
+                .unpack_compressed(block_classes.value().as_ref())
+                .map_err(|_| Error::msg("corrupted chunk block classes"))?;
+
+            let mut builder = BlocksVec::new();
+
+            for block_class in block_classes {
+                builder.push(block_class);
+            }
+
+            Ok((chunk.value(), builder.build().into_data(&mut packer)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    for (chunk, block_classes) in chunks {
+        table.insert(chunk, block_classes)?;
+    }
+
+    Ok(())
+}
+
+/// `PlayerProfile` of version 1.
+#[derive(Serialize, Deserialize)]
+struct PlayerProfileV1 {
+    username: String,
+    #[serde(with = "serde_big_array::BigArray")]
+    public_key: [u8; 33],
+    role: Role,
+}
+
+/// Version 2, the player profiles have the state of the player actor.
+fn add_player_state(db_write: &WriteTransaction) -> Result<()> {
+    let mut packer = Packer::new();
+    let mut table = db_write.open_table(PLAYER_TABLE)?;
+
+    let profiles = table
+        .iter()?
+        .map(|entry| {
+            let (player, profile) = entry?;
+
+            let PlayerProfileV1 {
+                username,
+                public_key,
+                role,
+            } = packer
+                .unpack_uncompressed(profile.value().as_ref())
+                .map_err(|_| Error::msg("corrupted player profile"))?;
+
+            let profile = PlayerProfile {
+                username,
+                public_key,
+                role,
+                state: None,
+            };
+
+            Ok((player.value(), profile.into_data(&mut packer)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    for (player, profile) in profiles {
+        table.insert(player, profile)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        entity::player::Player,
+        storage::IntoDataSized,
+    };
+    use redb::backends::InMemoryBackend;
+    use voxbrix_common::entity::{
+        block::Block,
+        chunk::{
+            Chunk,
+            Dimension,
+            DimensionKind,
+        },
+    };
+
+    fn database() -> Database {
+        Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .unwrap()
+    }
+
+    #[test]
+    fn migrate_unversioned_database() {
+        let database = database();
+        let mut packer = Packer::new();
+
+        let chunk = Chunk {
+            position: [1, -2, 3],
+            dimension: Dimension {
+                kind: DimensionKind(0),
+                phase: 0,
+            },
+        };
+
+        let block_classes = BlockClassesV0(std::array::from_fn(|i| BlockClass(i as u64 % 5 / 4)));
+
+        // Written the way the server did before the versioning
+        let db_write = database.begin_write().unwrap();
+        {
+            db_write
+                .open_table(BLOCK_CLASS_TABLE)
+                .unwrap()
+                .insert(
+                    chunk.into_data_sized(),
+                    Data::new_owned(packer.pack_compressed_to_vec(&block_classes)),
+                )
+                .unwrap();
+
+            let mut table = db_write.open_table(PLAYER_TABLE).unwrap();
+            let profile = PlayerProfileV0 {
+                username: "player".to_owned(),
+                public_key: [7; 33],
+            };
+
+            table
+                .insert(
+                    Player(1).into_data_sized(),
+                    Data::new_owned(packer.pack_uncompressed_to_vec(&profile)),
+                )
+                .unwrap();
+        }
+        db_write.commit().unwrap();
+
+        run(&database).unwrap();
+
+        let db_read = database.begin_read().unwrap();
+        let version = db_read
+            .open_table(SCHEMA_TABLE)
+            .unwrap()
+            .get(VERSION_KEY)
+            .unwrap()
+            .map(|v| v.value());
+
+        assert_eq!(version, Some(SCHEMA_VERSION));
+
+        let profile = db_read
+            .open_table(PLAYER_TABLE)
+            .unwrap()
+            .get(Player(1).into_data_sized())
+            .unwrap()
+            .unwrap()
+            .value()
+            .into_inner(&mut packer);
+
+        assert_eq!(profile.username, "player");
+        assert_eq!(profile.public_key, [7; 33]);
+        assert_eq!(profile.role, Role::Player);
+        assert!(profile.state.is_none());
+
+        let migrated = db_read
+            .open_table(BLOCK_CLASS_TABLE)
+            .unwrap()
+            .get(chunk.into_data_sized())
+            .unwrap()
+            .unwrap()
+            .value()
+            .into_inner(&mut packer);
+
+        for (i, block_class) in block_classes.0.iter().enumerate() {
+            assert_eq!(migrated.get(Block::from_usize(i).unwrap()), block_class);
+        }
+
+        // Already migrated database is left as it is
+        drop(db_read);
+        run(&database).unwrap();
+    }
+
+    #[test]
+    fn refuse_newer_database() {
+        let database = database();
+
+        let db_write = database.begin_write().unwrap();
+        db_write
+            .open_table(SCHEMA_TABLE)
+            .unwrap()
+            .insert(VERSION_KEY, SCHEMA_VERSION + 1)
+            .unwrap();
+        db_write.commit().unwrap();
+
+        assert!(run(&database).is_err());
+    }
+}
@@ -0,0 +1,265 @@
+//! Chunk persistence in region files.
+//!
+//! Each region file holds `REGION_EDGE`³ chunks of one dimension and is named
+//! `{dimension kind}.{phase}.{x}.{y}.{z}.region` after the region coordinates.
+//! The file starts with a header of `REGION_CHUNKS` entries, one per chunk, each being
+//! `offset: u64, length: u32, capacity: u32` in little-endian.
+//! The offset of `0` means that the chunk is not saved.
+//!
+//! Chunk data follows the header. The chunk is overwritten in place if the new data fits
+//! into its capacity, otherwise the data is appended to the end of the file.
+use anyhow::Result;
+use std::{
+    fs::{
+        self,
+        File,
+        OpenOptions,
+    },
+    io::{
+        Error as IoError,
+        ErrorKind as IoErrorKind,
+        Read,
+        Result as IoResult,
+        Seek,
+        SeekFrom,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::Mutex,
+};
+use voxbrix_common::entity::chunk::{
+    Chunk,
+    Dimension,
+    DimensionKind,
+};
+
+/// Number of chunks along each of the region edges.
+pub const REGION_EDGE: i32 = 8;
+const REGION_CHUNKS: usize = (REGION_EDGE * REGION_EDGE * REGION_EDGE) as usize;
+const ENTRY_SIZE: usize = 16;
+const HEADER_SIZE: u64 = (REGION_CHUNKS * ENTRY_SIZE) as u64;
+/// Appended chunk data is padded to this size, so slightly larger data could be
+/// written in place later.
+const SECTOR_SIZE: u32 = 256;
+
+#[derive(Clone, Copy)]
+struct Entry {
+    offset: u64,
+    length: u32,
+    capacity: u32,
+}
+
+impl Entry {
+    fn from_bytes(bytes: &[u8; ENTRY_SIZE]) -> Self {
+        Self {
+            offset: u64::from_le_bytes(bytes[0 .. 8].try_into().unwrap()),
+            length: u32::from_le_bytes(bytes[8 .. 12].try_into().unwrap()),
+            capacity: u32::from_le_bytes(bytes[12 .. 16].try_into().unwrap()),
+        }
+    }
+
+    fn to_bytes(self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0 .. 8].copy_from_slice(&self.offset.to_le_bytes());
+        bytes[8 .. 12].copy_from_slice(&self.length.to_le_bytes());
+        bytes[12 .. 16].copy_from_slice(&self.capacity.to_le_bytes());
+        bytes
+    }
+
+    fn read(file: &mut File, position: u64) -> IoResult<Self> {
+        let mut bytes = [0; ENTRY_SIZE];
+        file.seek(SeekFrom::Start(position))?;
+        file.read_exact(&mut bytes)?;
+        Ok(Self::from_bytes(&bytes))
+    }
+}
+
+pub struct RegionStorage {
+    directory: PathBuf,
+    // Region files are accessed from the storage thread, the generation thread
+    // and the blocking tasks of the chunk activation.
+    lock: Mutex<()>,
+}
+
+impl RegionStorage {
+    /// Open the storage in the directory, creating the directory if it does not exist.
+    pub fn open(directory: impl Into<PathBuf>) -> IoResult<Self> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+
+        Ok(Self {
+            directory,
+            lock: Mutex::new(()),
+        })
+    }
+
+    /// Region file path and the position of the chunk entry in its header.
+    fn locate(&self, chunk: &Chunk) -> (PathBuf, u64) {
+        let [rx, ry, rz] = chunk.position.map(|c| c.div_euclid(REGION_EDGE));
+        let [x, y, z] = chunk.position.map(|c| c.rem_euclid(REGION_EDGE) as usize);
+        let edge = REGION_EDGE as usize;
+        let index = (z * edge + y) * edge + x;
+
+        let path = self.directory.join(format!(
+            "{}.{}.{}.{}.{}.region",
+            chunk.dimension.kind.0, chunk.dimension.phase, rx, ry, rz
+        ));
+
+        (path, (index * ENTRY_SIZE) as u64)
+    }
+
+    /// Dimension and region coordinates from the region file name.
+    fn parse_path(path: &Path) -> Option<(Dimension, [i32; 3])> {
+        let name = path.file_name()?.to_str()?.strip_suffix(".region")?;
+        let mut parts = name.split('.');
+
+        let kind = DimensionKind(parts.next()?.parse().ok()?);
+        let phase = parts.next()?.parse().ok()?;
+        let mut region = [0; 3];
+
+        for coord in region.iter_mut() {
+            *coord = parts.next()?.parse().ok()?;
+        }
+
+        if parts.next().is_some() {
+            return None;
+        }
+
+        Some((Dimension { kind, phase }, region))
+    }
+
+    /// Call `f` for every saved chunk with its data.
+    pub fn for_each(&self, mut f: impl FnMut(Chunk, &[u8]) -> Result<()>) -> Result<()> {
+        let _lock = self.lock.lock().unwrap();
+
+        let mut data = Vec::new();
+
+        for dir_entry in fs::read_dir(&self.directory)? {
+            let path = dir_entry?.path();
+
+            let Some((dimension, region)) = Self::parse_path(&path) else {
+                continue;
+            };
+
+            let mut file = File::open(&path)?;
+            let mut header = vec![0; HEADER_SIZE as usize];
+            file.read_exact(&mut header)?;
+
+            let edge = REGION_EDGE as usize;
+
+            for (index, bytes) in header.chunks_exact(ENTRY_SIZE).enumerate() {
+                let entry = Entry::from_bytes(bytes.try_into().unwrap());
+
+                if entry.offset == 0 {
+                    continue;
+                }
+
+                let local = [index % edge, index / edge % edge, index / edge / edge];
+
+                let chunk = Chunk {
+                    position: [0, 1, 2].map(|i| region[i] * REGION_EDGE + local[i] as i32),
+                    dimension,
+                };
+
+                data.resize(entry.length as usize, 0);
+                file.seek(SeekFrom::Start(entry.offset))?;
+                file.read_exact(&mut data)?;
+
+                f(chunk, &data)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove all region files.
+    pub fn clear(&self) -> IoResult<()> {
+        let _lock = self.lock.lock().unwrap();
+
+        for dir_entry in fs::read_dir(&self.directory)? {
+            let path = dir_entry?.path();
+
+            if Self::parse_path(&path).is_some() {
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read the chunk data, `None` if the chunk was never saved.
+    pub fn load(&self, chunk: &Chunk) -> IoResult<Option<Vec<u8>>> {
+        let _lock = self.lock.lock().unwrap();
+
+        let (path, entry_position) = self.locate(chunk);
+
+        let mut file = match File::open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == IoErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let entry = Entry::read(&mut file, entry_position)?;
+
+        if entry.offset == 0 {
+            return Ok(None);
+        }
+
+        let mut data = vec![0; entry.length as usize];
+        file.seek(SeekFrom::Start(entry.offset))?;
+        file.read_exact(&mut data)?;
+
+        Ok(Some(data))
+    }
+
+    /// Write the chunk data, replacing the previously saved one.
+    pub fn save(&self, chunk: &Chunk, data: &[u8]) -> IoResult<()> {
+        let _lock = self.lock.lock().unwrap();
+
+        let length: u32 = data
+            .len()
+            .try_into()
+            .map_err(|_| IoError::new(IoErrorKind::InvalidInput, "chunk data is too large"))?;
+
+        let (path, entry_position) = self.locate(chunk);
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        let file_length = file.metadata()?.len();
+
+        if file_length < HEADER_SIZE {
+            file.set_len(HEADER_SIZE)?;
+        }
+
+        let mut entry = Entry::read(&mut file, entry_position)?;
+
+        let append = entry.offset == 0 || length > entry.capacity;
+
+        if append {
+            entry.offset = file_length.max(HEADER_SIZE);
+            entry.capacity = length.next_multiple_of(SECTOR_SIZE);
+        }
+
+        entry.length = length;
+
+        file.seek(SeekFrom::Start(entry.offset))?;
+        file.write_all(data)?;
+
+        if append {
+            file.set_len(entry.offset + entry.capacity as u64)?;
+        }
+
+        file.seek(SeekFrom::Start(entry_position))?;
+        file.write_all(&entry.to_bytes())?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use crate::WORLD_TABLE;
+use anyhow::Result;
+use log::{
+    info,
+    warn,
+};
+use redb::{
+    Database,
+    ReadableTable,
+};
+use std::time::{
+    SystemTime,
+    UNIX_EPOCH,
+};
+
+const SEED_KEY: &str = "seed";
+const TIME_KEY: &str = "time";
+
+/// Seed of the world, stored once on the world creation.
+/// New worlds use the `configured` seed, or a random one if it is not set.
+pub fn load_or_create_seed(database: &Database, configured: Option<u64>) -> Result<u64> {
+    let db_write = database.begin_write()?;
+
+    let seed = {
+        let mut table = db_write.open_table(WORLD_TABLE)?;
+
+        let stored = table.get(SEED_KEY)?.map(|seed| seed.value());
+
+        match stored {
+            Some(seed) => {
+                if configured.is_some_and(|configured| configured != seed) {
+                    warn!(
+                        "configured world seed is ignored, the existing world has seed {}",
+                        seed
+                    );
+                }
+
+                seed
+            },
+            None => {
+                let seed = configured.unwrap_or_else(|| {
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|d| d.as_nanos() as u64)
+                        .unwrap_or(0)
+                });
+
+                table.insert(SEED_KEY, seed)?;
+
+                info!("new world created with seed {}", seed);
+
+                seed
+            },
+        }
+    };
+
+    db_write.commit()?;
+
+    Ok(seed)
+}
+
+/// World time in ticks, zero for the new worlds.
+pub fn load_time(database: &Database) -> Result<u64> {
+    let db_write = database.begin_write()?;
+
+    let time = {
+        let table = db_write.open_table(WORLD_TABLE)?;
+        let time = table.get(TIME_KEY)?.map(|time| time.value());
+        time.unwrap_or(0)
+    };
+
+    db_write.commit()?;
+
+    Ok(time)
+}
+
+pub fn save_time(database: &Database, time: u64) -> Result<()> {
+    let db_write = database.begin_write()?;
+
+    {
+        let mut table = db_write.open_table(WORLD_TABLE)?;
+        table.insert(TIME_KEY, time)?;
+    }
+
+    db_write.commit()?;
+
+    Ok(())
+}
+
+/// Replaces the seed of the world.
+/// Only the chunks generated afterwards are affected, the saved ones are kept.
+pub fn set_seed(database: &Database, seed: u64) -> Result<()> {
+    let db_write = database.begin_write()?;
+
+    {
+        let mut table = db_write.open_table(WORLD_TABLE)?;
+        table.insert(SEED_KEY, seed)?;
+    }
+
+    db_write.commit()?;
+
+    Ok(())
+}
@@ -1,4 +1,22 @@
+pub mod actor_ai;
+pub mod actor_transfer;
+pub mod asset_sync;
+pub mod biome;
+pub mod bulk_edit;
 pub mod chunk_activation;
 pub mod chunk_generation;
+pub mod client_script_dispatch;
+pub mod damage;
+pub mod effect;
+pub mod fluid;
 pub mod map_loading;
+pub mod movement_validation;
+pub mod neighbor_update;
+pub mod pathfinding;
 pub mod position;
+pub mod projectile;
+pub mod random_tick;
+pub mod rate_limit;
+pub mod script_reload;
+pub mod script_schedule;
+pub mod spawn;
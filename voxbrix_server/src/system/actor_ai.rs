@@ -0,0 +1,226 @@
+use crate::{
+    component::{
+        actor::{
+            class::ClassActorComponent,
+            goal::{
+                Goal,
+                GoalActorComponent,
+            },
+            player::PlayerActorComponent,
+            position::PositionActorComponent,
+            velocity::VelocityActorComponent,
+        },
+        actor_class::behavior::{
+            Behavior,
+            BehaviorActorClassComponent,
+        },
+        block::class::ClassBlockComponent,
+    },
+    system::pathfinding::{
+        self,
+        NodeCoords,
+        Pathfinder,
+    },
+    world_rng::WorldRng,
+};
+use voxbrix_common::{
+    component::{
+        actor::{
+            position::Position,
+            velocity::Velocity,
+        },
+        actor_class::collider::ColliderActorClassComponent,
+        block_class::collision::CollisionBlockClassComponent,
+    },
+    entity::{
+        actor::Actor,
+        chunk::Chunk,
+        snapshot::Snapshot,
+    },
+    math::Vec3F32,
+};
+
+/// Ticks before the wandering actor chooses a new destination,
+/// the actor stands still after reaching the previous one.
+const WANDER_GOAL_TICKS: u64 = 200;
+/// Ticks before the following actor looks for the path again, the player moves meanwhile.
+const FOLLOW_GOAL_TICKS: u64 = 20;
+/// Horizontal distance to the node center the node counts as reached on.
+const ARRIVAL_DISTANCE: f32 = 0.2;
+
+/// Block the actor stands in, relative to the `origin` chunk.
+fn node_of(origin: &Chunk, position: &Position, half_height: f32) -> Option<NodeCoords> {
+    let chunk_offset = pathfinding::chunk_offset(origin, &position.chunk)?;
+    let offset = position.offset;
+
+    Some([
+        chunk_offset[0] + offset[0].floor() as i32,
+        chunk_offset[1] + offset[1].floor() as i32,
+        chunk_offset[2] + (offset[2] - half_height + 0.01).floor() as i32,
+    ])
+}
+
+/// Chooses the goals of the non-player actors by their class behavior
+/// and steers them along the paths by setting their velocity.
+pub struct ActorAiSystem;
+
+/// Random offset within `-radius ..= radius`.
+fn random_offset(world_rng: &mut WorldRng, radius: i32) -> i32 {
+    let range = radius.max(0) as u64 * 2 + 1;
+
+    world_rng.below(range) as i32 - radius.max(0)
+}
+
+impl ActorAiSystem {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn choose_target(
+        world_rng: &mut WorldRng,
+        behavior: &Behavior,
+        actor_position: &Position,
+        half_height: f32,
+        player_ac: &PlayerActorComponent,
+        position_ac: &PositionActorComponent,
+    ) -> Option<NodeCoords> {
+        let start = node_of(&actor_position.chunk, actor_position, half_height)?;
+
+        match behavior {
+            Behavior::Wander { radius, .. } => {
+                Some([
+                    start[0] + random_offset(world_rng, *radius),
+                    start[1] + random_offset(world_rng, *radius),
+                    start[2],
+                ])
+            },
+            Behavior::FollowPlayer { radius, .. } => {
+                player_ac
+                    .iter()
+                    .filter_map(|(player_actor, _)| {
+                        let player_position = position_ac.get(&player_actor)?;
+                        // Players are assumed to be of the same height
+                        let node = node_of(&actor_position.chunk, player_position, half_height)?;
+
+                        let distance = (0 .. 3)
+                            .map(|i| node[i].abs_diff(start[i]))
+                            .max()
+                            .unwrap_or(0);
+
+                        (distance <= *radius as u32).then_some((distance, node))
+                    })
+                    .min_by_key(|(distance, _)| *distance)
+                    .map(|(_, node)| node)
+            },
+        }
+    }
+
+    pub fn process(
+        &mut self,
+        snapshot: Snapshot,
+        world_rng: &mut WorldRng,
+        class_bc: &ClassBlockComponent,
+        collision_bcc: &CollisionBlockClassComponent,
+        class_ac: &ClassActorComponent,
+        collider_acc: &ColliderActorClassComponent,
+        behavior_acc: &BehaviorActorClassComponent,
+        player_ac: &PlayerActorComponent,
+        position_ac: &PositionActorComponent,
+        velocity_ac: &mut VelocityActorComponent,
+        goal_ac: &mut GoalActorComponent,
+    ) {
+        let actors = class_ac
+            .iter()
+            .filter(|(actor, _)| player_ac.get(actor).is_none())
+            .filter_map(|(actor, class)| Some((actor, *behavior_acc.get(class)?)))
+            .collect::<Vec<(Actor, Behavior)>>();
+
+        for (actor, behavior) in actors {
+            let Some(position) = position_ac.get(&actor) else {
+                continue;
+            };
+
+            let collider = class_ac
+                .get(&actor)
+                .and_then(|class| collider_acc.get(class))
+                .copied()
+                .unwrap_or_default();
+
+            let half_height = collider.radius[2];
+
+            let needs_goal = goal_ac
+                .get(&actor)
+                .map(|goal| goal.expires_at <= snapshot)
+                .unwrap_or(true);
+
+            if needs_goal {
+                let goal_ticks = match behavior {
+                    Behavior::Wander { .. } => WANDER_GOAL_TICKS,
+                    Behavior::FollowPlayer { .. } => FOLLOW_GOAL_TICKS,
+                };
+
+                let path = Self::choose_target(
+                    world_rng,
+                    &behavior,
+                    position,
+                    half_height,
+                    player_ac,
+                    position_ac,
+                )
+                .and_then(|target| {
+                    let start = node_of(&position.chunk, position, half_height)?;
+                    let height = (half_height * 2.0).ceil() as i32;
+
+                    let mut path = Pathfinder::new(position.chunk, height, class_bc, collision_bcc)
+                        .find_path(start, target);
+
+                    path.reverse();
+
+                    Some(path)
+                })
+                .unwrap_or_default();
+
+                goal_ac.insert(
+                    actor,
+                    Goal {
+                        origin: position.chunk,
+                        path,
+                        expires_at: Snapshot(snapshot.0 + goal_ticks),
+                    },
+                );
+            }
+
+            let goal = goal_ac.get_mut(&actor).unwrap();
+
+            let mut vector = Vec3F32::ZERO;
+
+            if let Some(chunk_offset) = pathfinding::chunk_offset(&goal.origin, &position.chunk) {
+                let current = Vec3F32::from(chunk_offset.map(|i| i as f32)) + position.offset;
+
+                while let Some(node) = goal.path.last() {
+                    let target = Vec3F32::new(
+                        node[0] as f32 + 0.5,
+                        node[1] as f32 + 0.5,
+                        node[2] as f32 + half_height,
+                    );
+
+                    let delta = target - current;
+
+                    if delta.truncate().length() < ARRIVAL_DISTANCE && delta[2].abs() < 0.5 {
+                        goal.path.pop();
+                        continue;
+                    }
+
+                    vector = delta.normalize_or_zero() * behavior.speed();
+                    break;
+                }
+            }
+
+            let velocity = Velocity { vector };
+
+            if velocity_ac.get(&actor) != Some(&velocity) {
+                velocity_ac.insert(actor, velocity, snapshot);
+            }
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use std::mem;
+use voxbrix_common::{
+    component::actor::position::Position,
+    entity::{
+        actor::Actor,
+        chunk::DimensionKind,
+    },
+    LabelMap,
+};
+
+pub struct ActorTransfer {
+    pub actor: Actor,
+    pub position: Position,
+}
+
+/// Collects the actor moves requested by the scripts,
+/// these are applied after the scripts of the tick have run.
+pub struct ActorTransferSystem {
+    dimension_kind_label_map: LabelMap<DimensionKind>,
+    transfers: Vec<ActorTransfer>,
+}
+
+impl ActorTransferSystem {
+    pub fn new(dimension_kind_label_map: LabelMap<DimensionKind>) -> Self {
+        Self {
+            dimension_kind_label_map,
+            transfers: Vec::new(),
+        }
+    }
+
+    /// Returns `false` if the dimension kind of the position is not registered.
+    pub fn transfer(&mut self, actor: Actor, position: Position) -> bool {
+        if self
+            .dimension_kind_label_map
+            .get_label(&position.chunk.dimension.kind)
+            .is_none()
+        {
+            return false;
+        }
+
+        self.transfers.push(ActorTransfer { actor, position });
+
+        true
+    }
+
+    pub fn take_transfers(&mut self) -> Vec<ActorTransfer> {
+        mem::take(&mut self.transfers)
+    }
+}
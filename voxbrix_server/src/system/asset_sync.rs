@@ -0,0 +1,166 @@
+use crate::storage::label;
+use anyhow::{
+    Context,
+    Error,
+};
+use redb::Database;
+use std::{
+    fs,
+    path::{
+        Component,
+        Path,
+    },
+    sync::Arc,
+};
+use tokio::task;
+use voxbrix_common::{
+    assets::{
+        ACTION_LIST_PATH,
+        ACTOR_CLASS_LIST_PATH,
+        ACTOR_MODEL_LIST_PATH,
+        BLOCK_CLASS_LIST_PATH,
+        COMMON_ASSET_DIR,
+        DIMENSION_KIND_LIST_PATH,
+        EFFECT_LIST_PATH,
+        ITEM_CLASS_LIST_PATH,
+        STATE_COMPONENTS_PATH,
+    },
+    messages::client::{
+        AssetEntry,
+        AssetList,
+        AssetManifest,
+    },
+    system::pack_loading::{
+        self,
+        PackSet,
+    },
+};
+
+/// Lists with the ids kept stable for the world, by the label kind.
+const STABLE_LISTS: [(&str, &str); 5] = [
+    ("actor_class", ACTOR_CLASS_LIST_PATH),
+    ("block_class", BLOCK_CLASS_LIST_PATH),
+    ("item_class", ITEM_CLASS_LIST_PATH),
+    ("effect", EFFECT_LIST_PATH),
+    ("dimension_kind", DIMENSION_KIND_LIST_PATH),
+];
+
+/// Lists merged from the packs as is.
+const MERGED_LISTS: [&str; 3] = [
+    STATE_COMPONENTS_PATH,
+    ACTOR_MODEL_LIST_PATH,
+    ACTION_LIST_PATH,
+];
+
+/// Common assets of the server packs, kept in memory to be sent to the clients on login.
+/// The clients build their label maps from the lists of the manifest, so they do not depend
+/// on the assets they ship with.
+pub struct AssetSyncSystem {
+    manifest: AssetManifest,
+    files: Vec<Vec<u8>>,
+}
+
+impl AssetSyncSystem {
+    pub async fn load(database: Arc<Database>, packs: PackSet) -> Result<Self, Error> {
+        let mut lists = Vec::new();
+
+        for (kind, path) in STABLE_LISTS {
+            let list = label::load_stable_list(database.clone(), &packs, kind, path).await?;
+
+            lists.push(AssetList {
+                path: path.to_owned(),
+                labels: list.list,
+            });
+        }
+
+        for path in MERGED_LISTS {
+            let list = packs.load_list(path).await?;
+
+            lists.push(AssetList {
+                path: path.to_owned(),
+                labels: list.list,
+            });
+        }
+
+        task::spawn_blocking(move || {
+            let mut entries = Vec::new();
+            let mut files = Vec::new();
+
+            for (pack_index, pack) in packs.packs().iter().enumerate() {
+                let mut paths = Vec::new();
+                collect_files(&pack.root, Path::new(COMMON_ASSET_DIR), &mut paths)
+                    .with_context(|| format!("unable to read pack \"{}\"", pack.label))?;
+
+                paths.sort();
+
+                for path in paths {
+                    let data = fs::read(pack.root.join(&path))
+                        .with_context(|| format!("unable to read {:?}", path))?;
+
+                    entries.push(AssetEntry {
+                        pack: pack_index as u32,
+                        path,
+                        hash: pack_loading::asset_hash(&data),
+                    });
+
+                    files.push(data);
+                }
+            }
+
+            Ok(Self {
+                manifest: AssetManifest {
+                    packs: packs.packs().iter().map(|p| p.label.clone()).collect(),
+                    files: entries,
+                    lists,
+                },
+                files,
+            })
+        })
+        .await
+        .unwrap()
+    }
+
+    pub fn manifest(&self) -> &AssetManifest {
+        &self.manifest
+    }
+
+    /// Contents of the file by its index in the manifest.
+    pub fn file(&self, index: u32) -> Option<&[u8]> {
+        self.files.get(index as usize).map(|data| data.as_slice())
+    }
+}
+
+/// Paths of all files in the `dir` relative to the `root`, with `/` separators.
+/// Blocking IO, must not be used directly in async
+fn collect_files(root: &Path, dir: &Path, paths: &mut Vec<String>) -> Result<(), Error> {
+    let full_dir = root.join(dir);
+
+    if !full_dir.is_dir() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(full_dir)? {
+        let entry = entry?;
+        let path = dir.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            collect_files(root, &path, paths)?;
+        } else {
+            let path = path
+                .components()
+                .map(|component| {
+                    match component {
+                        Component::Normal(name) => name.to_str(),
+                        _ => None,
+                    }
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| Error::msg(format!("unsupported asset path {:?}", path)))?
+                .join("/");
+
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
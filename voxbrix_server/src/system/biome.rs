@@ -0,0 +1,181 @@
+//! Biomes are chosen by the climate of the block column: the temperature and the humidity
+//! come from two deterministic noise layers and the biome with the closest climate wins.
+use crate::{
+    assets::BIOME_LIST,
+    entity::biome::Biome,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+use serde::Deserialize;
+use tokio::task;
+use voxbrix_common::{
+    entity::{
+        block::BLOCKS_IN_CHUNK_EDGE_I32,
+        block_class::BlockClass,
+        chunk::Dimension,
+    },
+    system::pack_loading::PackSet,
+    LabelMap,
+};
+use voxbrix_noise::{
+    Fbm,
+    Hasher64,
+    Noise2D,
+    Octaves,
+};
+
+/// Size of the largest climate noise cell in blocks.
+const CLIMATE_SCALE: f64 = 512.0;
+const CLIMATE_OCTAVES: u32 = 3;
+/// Derive the temperature and the humidity noise seeds from the world seed.
+const TEMPERATURE_SALT: u64 = 0x7465_6d70_6572_6174;
+const HUMIDITY_SALT: u64 = 0x6875_6d69_6469_7479;
+
+#[derive(Deserialize, Debug)]
+struct BiomeDescriptor {
+    label: String,
+    temperature: f32,
+    humidity: f32,
+    surface_block: String,
+    /// Chance of a vegetation feature per surface block, from `0.0` to `1.0`.
+    vegetation_density: f32,
+}
+
+#[derive(Deserialize, Debug)]
+struct BiomeList {
+    list: Vec<BiomeDescriptor>,
+}
+
+#[derive(Debug)]
+pub struct BiomeData {
+    pub temperature: f32,
+    pub humidity: f32,
+    pub surface_block: BlockClass,
+    pub vegetation_density: f32,
+}
+
+fn climate_noise(seed: u64, column: [i64; 2]) -> f32 {
+    Fbm(Octaves::new(seed, CLIMATE_OCTAVES)).sample_2d(column.map(|c| c as f64 / CLIMATE_SCALE))
+        as f32
+}
+
+/// Biomes loaded from the biome lists of the packs.
+/// A biome of a later pack replaces the one with the same label.
+pub struct BiomeRegistry {
+    label_map: LabelMap<Biome>,
+    biomes: Vec<BiomeData>,
+}
+
+impl BiomeRegistry {
+    pub async fn load(
+        packs: PackSet,
+        block_class_label_map: LabelMap<BlockClass>,
+    ) -> Result<Self, Error> {
+        task::spawn_blocking(move || {
+            let mut list: Vec<BiomeDescriptor> = Vec::new();
+
+            for pack_list in packs.read_data_files::<BiomeList>(BIOME_LIST)? {
+                for biome in pack_list.list {
+                    match list.iter_mut().find(|other| other.label == biome.label) {
+                        Some(other) => *other = biome,
+                        None => list.push(biome),
+                    }
+                }
+            }
+
+            if list.is_empty() {
+                return Err(Error::msg("biome list must not be empty"));
+            }
+
+            let labels = list
+                .iter()
+                .map(|biome| biome.label.clone())
+                .collect::<Vec<_>>();
+
+            let biomes = list
+                .into_iter()
+                .map(|biome| {
+                    let surface_block = block_class_label_map
+                        .get(&biome.surface_block)
+                        .ok_or_else(|| {
+                            Error::msg(format!(
+                                "block class \"{}\" not found in the block class list",
+                                biome.surface_block
+                            ))
+                        })
+                        .with_context(|| format!("while loading biome \"{}\"", biome.label))?;
+
+                    Ok(BiomeData {
+                        temperature: biome.temperature,
+                        humidity: biome.humidity,
+                        surface_block,
+                        vegetation_density: biome.vegetation_density.clamp(0.0, 1.0),
+                    })
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+
+            Ok(Self {
+                label_map: LabelMap::from_list(&labels),
+                biomes,
+            })
+        })
+        .await
+        .unwrap()
+    }
+
+    pub fn get(&self, biome: &Biome) -> Option<&BiomeData> {
+        self.biomes.get(biome.0 as usize)
+    }
+
+    pub fn label_map(&self) -> &LabelMap<Biome> {
+        &self.label_map
+    }
+
+    /// Temperature and humidity of the block column in global block coordinates,
+    /// both about `-1.0 ..= 1.0`.
+    pub fn climate(seed: u64, dimension: Dimension, column: [i64; 2]) -> [f32; 2] {
+        let mut hasher = Hasher64::new(seed);
+        hasher.write(&dimension.kind.0.to_le_bytes());
+        hasher.write(&dimension.phase.to_le_bytes());
+        let seed = hasher.finish();
+
+        [
+            climate_noise(seed ^ TEMPERATURE_SALT, column),
+            climate_noise(seed ^ HUMIDITY_SALT, column),
+        ]
+    }
+
+    /// Biome of the block column with the `column` index within the chunk layer.
+    pub fn biome_at(
+        &self,
+        seed: u64,
+        dimension: Dimension,
+        chunk_position: [i32; 2],
+        column: usize,
+    ) -> Biome {
+        let edge = BLOCKS_IN_CHUNK_EDGE_I32 as i64;
+        let local = [column as i64 % edge, column as i64 / edge % edge];
+
+        let global = [0, 1].map(|i| chunk_position[i] as i64 * edge + local[i]);
+
+        let [temperature, humidity] = Self::climate(seed, dimension, global);
+
+        let closest = self
+            .biomes
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                let distance = |biome: &BiomeData| {
+                    (biome.temperature - temperature).powi(2) + (biome.humidity - humidity).powi(2)
+                };
+
+                distance(a).total_cmp(&distance(b))
+            })
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+
+        Biome(closest as u64)
+    }
+}
@@ -0,0 +1,151 @@
+use crate::component::block::{
+    block_entity::BlockEntityBlockComponent,
+    class::ClassBlockComponent,
+    metadata::MetadataBlockComponent,
+};
+use std::fmt;
+use voxbrix_common::entity::{
+    block::{
+        Block,
+        BLOCKS_IN_CHUNK_EDGE,
+        BLOCKS_IN_CHUNK_EDGE_I32,
+    },
+    block_class::BlockClass,
+    chunk::Chunk,
+};
+
+/// Largest amount of blocks a single fill can cover, a 128-block cube.
+pub const MAX_FILL_BLOCKS: u64 = 128 * 128 * 128;
+
+#[derive(Debug)]
+pub enum FillError {
+    DifferentDimensions,
+    TooLarge { blocks: u64 },
+    ChunkNotLoaded(Chunk),
+}
+
+impl fmt::Display for FillError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DifferentDimensions => write!(f, "corners are in different dimensions"),
+            Self::TooLarge { blocks } => {
+                write!(
+                    f,
+                    "region of {} blocks is larger than {} blocks",
+                    blocks, MAX_FILL_BLOCKS
+                )
+            },
+            Self::ChunkNotLoaded(chunk) => {
+                write!(f, "chunk {:?} is not loaded", chunk.position)
+            },
+        }
+    }
+}
+
+/// Coordinates of the block counted from the origin of the dimension.
+fn global_coords(chunk: &Chunk, block: Block) -> [i64; 3] {
+    let block_coords = block.into_coords();
+
+    [0, 1, 2]
+        .map(|i| chunk.position[i] as i64 * BLOCKS_IN_CHUNK_EDGE as i64 + block_coords[i] as i64)
+}
+
+/// Sets the class of all blocks in the box between the two corners, both inclusive.
+/// Either all the blocks are changed or none, all the chunks of the box must be loaded.
+/// The metadata and the block entities of the changed blocks are removed.
+///
+/// The changes go through the usual change tracking of the `class_bc`,
+/// so the chunks are cached, saved and sent to the players once per chunk on the next tick.
+/// Returns the amount of the blocks changed, the ones that already had the class are skipped.
+pub fn fill_region(
+    class_bc: &mut ClassBlockComponent,
+    metadata_bc: &mut MetadataBlockComponent,
+    block_entity_bc: &mut BlockEntityBlockComponent,
+    (chunk_a, block_a): (Chunk, Block),
+    (chunk_b, block_b): (Chunk, Block),
+    block_class: BlockClass,
+) -> Result<usize, FillError> {
+    if chunk_a.dimension != chunk_b.dimension {
+        return Err(FillError::DifferentDimensions);
+    }
+
+    let dimension = chunk_a.dimension;
+    let coords_a = global_coords(&chunk_a, block_a);
+    let coords_b = global_coords(&chunk_b, block_b);
+    let min = [0, 1, 2].map(|i| coords_a[i].min(coords_b[i]));
+    let max = [0, 1, 2].map(|i| coords_a[i].max(coords_b[i]));
+
+    let blocks = (0 .. 3)
+        .map(|i| (max[i] - min[i] + 1) as u64)
+        .try_fold(1u64, |total, edge| total.checked_mul(edge))
+        .unwrap_or(u64::MAX);
+
+    if blocks > MAX_FILL_BLOCKS {
+        return Err(FillError::TooLarge { blocks });
+    }
+
+    let edge = BLOCKS_IN_CHUNK_EDGE_I32 as i64;
+    let min_chunk = min.map(|c| c.div_euclid(edge) as i32);
+    let max_chunk = max.map(|c| c.div_euclid(edge) as i32);
+
+    let chunks = (min_chunk[2] ..= max_chunk[2])
+        .flat_map(|z| {
+            (min_chunk[1] ..= max_chunk[1])
+                .flat_map(move |y| (min_chunk[0] ..= max_chunk[0]).map(move |x| [x, y, z]))
+        })
+        .map(|position| {
+            Chunk {
+                position,
+                dimension,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Checking first, so that the region is not left half-filled
+    if let Some(chunk) = chunks
+        .iter()
+        .find(|chunk| class_bc.get_chunk(chunk).is_none())
+    {
+        return Err(FillError::ChunkNotLoaded(*chunk));
+    }
+
+    let mut changed = 0;
+
+    for chunk in chunks {
+        // Part of the box within the chunk, in the chunk block coordinates
+        let chunk_origin = chunk.position.map(|c| c as i64 * edge);
+        let from = [0, 1, 2].map(|i| (min[i] - chunk_origin[i]).max(0) as usize);
+        let to = [0, 1, 2].map(|i| (max[i] - chunk_origin[i]).min(edge - 1) as usize);
+
+        let previous_classes = class_bc.get_chunk(&chunk).unwrap();
+
+        let to_change = (from[2] ..= to[2])
+            .flat_map(|z| {
+                (from[1] ..= to[1]).flat_map(move |y| {
+                    (from[0] ..= to[0]).map(move |x| Block::from_coords([x, y, z]))
+                })
+            })
+            .filter(|block| *previous_classes.get(*block) != block_class)
+            .collect::<Vec<_>>();
+
+        if to_change.is_empty() {
+            continue;
+        }
+
+        let mut classes = class_bc.get_mut_chunk(&chunk).unwrap();
+
+        for block in to_change.iter() {
+            classes.set(*block, block_class);
+        }
+
+        // Metadata and the block entities belong to the replaced blocks
+        for block in to_change.iter() {
+            metadata_bc.set(&chunk, *block, None);
+            block_entity_bc.set(&chunk, *block, None);
+        }
+
+        changed += to_change.len();
+    }
+
+    Ok(changed)
+}
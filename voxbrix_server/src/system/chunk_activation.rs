@@ -7,35 +7,77 @@ use crate::{
             },
             position::PositionActorComponent,
         },
+        block::metadata::ChunkMetadata,
         chunk::status::{
             ChunkStatus,
             StatusChunkComponent,
         },
     },
-    storage::IntoDataSized,
-    BLOCK_CLASS_TABLE,
+    storage::{
+        BlockEntityStorage,
+        ChunkStorage,
+        MetadataStorage,
+        PendingWrites,
+        StructureStorage,
+    },
 };
 use ahash::AHashMap;
-use redb::Database;
-use std::sync::Arc;
 use tokio::runtime::Handle;
 use voxbrix_common::{
-    component::block::BlocksVec,
+    component::block::{
+        block_entity::ChunkBlockEntities,
+        BlocksVec,
+    },
     entity::{
         block_class::BlockClass,
         chunk::Chunk,
+        snapshot::Snapshot,
     },
     pack::Packer,
 };
 
+/// Tickets with a larger radius are rejected, the ticket activates `(2 * radius + 1)^3` chunks.
+pub const MAX_TICKET_RADIUS: i32 = 8;
+/// The ticket chunks load after the chunks close to the actors.
+const TICKET_PRIORITY: f64 = 0.0;
+
 pub enum ChunkActivationOutcome {
-    ChunkActivated(BlocksVec<BlockClass>),
+    ChunkActivated(BlocksVec<BlockClass>, ChunkMetadata, ChunkBlockEntities),
     ChunkNeedsGeneration,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TicketId(pub u64);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TicketSource {
+    /// Keeps the chunks around the spawn position active.
+    SpawnAnchor,
+    /// Forced-load region added by the admin command.
+    Admin,
+    /// Forced-load region added by a script.
+    Script,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Ticket {
+    pub source: TicketSource,
+    pub center: Chunk,
+    pub radius: i32,
+    /// The ticket is removed in the snapshot, never if not set.
+    pub expires_at: Option<Snapshot>,
+}
+
+/// Keeps the chunks around the actors with the chunk activation component and the chunks
+/// covered by the tickets active.
+/// Tickets are counted per chunk, the chunk stays active while any ticket covers it.
+/// Tickets are not persisted, they must be added again after the server restart.
 pub struct ChunkActivationSystem {
     target: AHashMap<Chunk, f64>,
     missing: Vec<(Chunk, f64)>,
+    next_ticket_id: u64,
+    tickets: AHashMap<TicketId, Ticket>,
+    ticket_refs: AHashMap<Chunk, usize>,
 }
 
 impl ChunkActivationSystem {
@@ -43,6 +85,79 @@ impl ChunkActivationSystem {
         Self {
             target: AHashMap::new(),
             missing: Vec::new(),
+            next_ticket_id: 0,
+            tickets: AHashMap::new(),
+            ticket_refs: AHashMap::new(),
+        }
+    }
+
+    /// Returns `None` if the radius is negative or larger than [`MAX_TICKET_RADIUS`].
+    pub fn add_ticket(&mut self, ticket: Ticket) -> Option<TicketId> {
+        if !(0 ..= MAX_TICKET_RADIUS).contains(&ticket.radius) {
+            return None;
+        }
+
+        let id = TicketId(self.next_ticket_id);
+        self.next_ticket_id += 1;
+
+        for chunk in ticket.center.radius(ticket.radius).into_iter_simple() {
+            *self.ticket_refs.entry(chunk).or_insert(0) += 1;
+        }
+
+        self.tickets.insert(id, ticket);
+
+        Some(id)
+    }
+
+    /// Returns `false` if there is no such ticket.
+    pub fn remove_ticket(&mut self, id: TicketId) -> bool {
+        let Some(ticket) = self.tickets.remove(&id) else {
+            return false;
+        };
+
+        self.release_ticket(&ticket);
+
+        true
+    }
+
+    fn release_ticket(&mut self, ticket: &Ticket) {
+        for chunk in ticket.center.radius(ticket.radius).into_iter_simple() {
+            if let Some(refs) = self.ticket_refs.get_mut(&chunk) {
+                *refs -= 1;
+
+                if *refs == 0 {
+                    self.ticket_refs.remove(&chunk);
+                }
+            }
+        }
+    }
+
+    pub fn tickets(&self) -> impl Iterator<Item = (TicketId, &Ticket)> {
+        self.tickets.iter().map(|(id, ticket)| (*id, ticket))
+    }
+
+    /// Removes the tickets that expire in the `snapshot` or earlier.
+    pub fn expire_tickets(&mut self, snapshot: Snapshot) {
+        let expired = self
+            .tickets
+            .iter()
+            .filter(|(_, ticket)| {
+                ticket
+                    .expires_at
+                    .is_some_and(|expires_at| expires_at <= snapshot)
+            })
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+
+        for id in expired {
+            self.remove_ticket(id);
+        }
+    }
+
+    /// Should be called after [`Self::clear`], with [`Self::actor_activations`].
+    pub fn ticket_activations(&mut self) {
+        for chunk in self.ticket_refs.keys() {
+            self.target.entry(*chunk).or_insert(TICKET_PRIORITY);
         }
     }
 
@@ -91,7 +206,11 @@ impl ChunkActivationSystem {
 
     pub fn activate(
         &mut self,
-        database: &Arc<Database>,
+        chunk_storage: &ChunkStorage,
+        metadata_storage: &MetadataStorage,
+        block_entity_storage: &BlockEntityStorage,
+        structure_storage: &StructureStorage,
+        pending_writes: &PendingWrites,
         status_cc: &mut StatusChunkComponent,
         send_fn: impl Fn(Chunk, ChunkActivationOutcome, &mut Packer) + Clone + Send + 'static,
         rt_handle: &Handle,
@@ -119,24 +238,50 @@ impl ChunkActivationSystem {
 
         for (chunk, _) in self.missing.iter().copied() {
             let send_fn = send_fn.clone();
-            let database = database.clone();
+            let chunk_storage = chunk_storage.clone();
+            let metadata_storage = metadata_storage.clone();
+            let block_entity_storage = block_entity_storage.clone();
+            let structure_storage = structure_storage.clone();
+            let pending_writes = pending_writes.clone();
             rt_handle.spawn_blocking(move || {
                 let mut packer = Packer::new();
 
-                let db_read = database.begin_read().unwrap();
-                let table = db_read
-                    .open_table(BLOCK_CLASS_TABLE)
-                    .expect("server_loop: database read");
+                // The writes not flushed yet are newer than the storage data
+                let pending = pending_writes.get(&chunk).unwrap_or_default();
+
+                let block_classes = match pending.block_classes {
+                    Some(block_classes) => Some(block_classes.as_ref().clone()),
+                    None => chunk_storage.load(chunk, &mut packer),
+                };
+
+                if let Some(mut block_classes) = block_classes {
+                    let placements = structure_storage.take(chunk, &mut packer);
+
+                    if !placements.is_empty() {
+                        for (block, block_class) in placements {
+                            *block_classes.get_mut(block) = block_class;
+                        }
+
+                        pending_writes.save_block_classes(chunk, block_classes.clone());
+                    }
+
+                    let metadata = match pending.metadata {
+                        Some(metadata) => metadata.as_ref().clone(),
+                        None => metadata_storage.load(chunk, &mut packer),
+                    };
 
-                let block_classes = table
-                    .get(chunk.into_data_sized())
-                    .unwrap()
-                    .map(|bytes| bytes.value().into_inner(&mut packer));
+                    let block_entities = match pending.block_entities {
+                        Some(block_entities) => block_entities.as_ref().clone(),
+                        None => block_entity_storage.load(chunk, &mut packer),
+                    };
 
-                if let Some(block_classes) = block_classes {
                     send_fn(
                         chunk,
-                        ChunkActivationOutcome::ChunkActivated(block_classes),
+                        ChunkActivationOutcome::ChunkActivated(
+                            block_classes,
+                            metadata,
+                            block_entities,
+                        ),
                         &mut packer,
                     );
                 } else {
@@ -1,32 +1,60 @@
+//! Chunks are generated by the ordered passes listed in the assets, e.g. terrain, caves, ores.
+//! Each dimension kind maps the passes to the generation scripts, the passes without a script
+//! are skipped for the dimension kind.
+//!
+//! Every script exports `generate_chunk(seed, phase, x, y, z)`, the seed being the world seed
+//! also available with `get_world_seed()`. The first pass fills the chunk
+//! with `push_block`, the later ones see the output of the previous passes with `get_block`
+//! and replace the blocks with `set_block`.
+//!
+//! Instead of calling `push_block` for every block, a pass could write all the block classes
+//! as little-endian `u64` into its memory and submit them with `submit_blocks(ptr, len)`,
+//! `len` being the number of blocks. The submitted blocks replace the ones generated so far,
+//! the call traps unless exactly the whole chunk is submitted.
+//!
+//! Structures use `place_block(x, y, z, block_class)` with the coordinates relative to the chunk
+//! being generated, so they may cross the chunk borders. Placements into other chunks are queued
+//! in the `StructureStorage` and applied once those chunks are generated or loaded.
+//!
+//! `get_biome(chunk_x, chunk_y, column)` returns the biome of the block column, `column` being
+//! `y * edge + x` within the chunk layer. Chunks with the same `x` and `y` share the biomes,
+//! so the scripts may ask for the columns of the neighbor chunks too. The biome parameters are
+//! read with `get_biome_surface_block(biome)` and `get_biome_vegetation_density(biome)`,
+//! `get_biome_by_label(ptr, len)` gives the biome of the label to compare with.
 use crate::{
     assets::{
+        CHUNK_GENERATION_PASS_LIST,
         CHUNK_GENERATION_SCRIPT_DIR,
         CHUNK_GENERATION_SCRIPT_LIST,
         DIMENSION_KIND_GENERATION_MAP,
     },
+    entity::biome::Biome,
     storage::{
-        IntoData,
-        IntoDataSized,
+        ChunkStorage,
+        StructureStorage,
     },
-    system::map_loading::Map,
-    BLOCK_CLASS_TABLE,
+    system::biome::BiomeRegistry,
 };
+use ahash::AHashMap;
 use anyhow::Error;
 use flume::Sender;
-use redb::Database;
+use serde::Deserialize;
 use std::{
+    collections::HashMap,
     mem,
-    path::PathBuf,
+    path::Path,
     sync::Arc,
     thread,
 };
+use tokio::task;
 use voxbrix_common::{
-    component::block::{
-        BlocksVec,
-        BlocksVecBuilder,
-    },
+    component::block::BlocksVec,
     entity::{
-        block::BLOCKS_IN_CHUNK_EDGE,
+        block::{
+            Block,
+            BLOCKS_IN_CHUNK,
+            BLOCKS_IN_CHUNK_EDGE,
+        },
         block_class::BlockClass,
         chunk::{
             Chunk,
@@ -36,7 +64,7 @@ use voxbrix_common::{
         script::Script,
     },
     pack::Packer,
-    system::list_loading::List,
+    system::pack_loading::PackSet,
     AsFromUsize,
     LabelMap,
 };
@@ -49,56 +77,131 @@ use wasmtime::{
     Store,
 };
 
+/// Returned by `get_block` for the blocks not generated yet.
+const NO_BLOCK_CLASS: u64 = u64::MAX;
+
+/// Maps of the packs are merged, a later pack overrides the scripts of the passes it lists.
+#[derive(Deserialize, Debug)]
+struct DimensionKindGenerationMap {
+    /// Dimension kind label to the pass labels mapped to the script labels.
+    map: HashMap<String, HashMap<String, String>>,
+}
+
 pub struct ChunkGenerationSystem {
     new_chunks_tx: Sender<Chunk>,
 }
 
 struct GenerationData {
+    seed: u64,
+    chunk: Chunk,
     block_class_label_map: LabelMap<BlockClass>,
-    block_classes: BlocksVecBuilder<BlockClass>,
+    block_classes: Vec<BlockClass>,
+    /// Placements that could not be applied to the generated blocks right away.
+    placements: Vec<(Chunk, Block, BlockClass)>,
 }
 
-impl ChunkGenerationSystem {
-    pub async fn new(
-        database: Arc<Database>,
+/// Runs the generation passes of the chunks, shared by the generation thread of the server loop
+/// and the world pre-generation. Chunks may be generated on multiple threads at once.
+pub struct ChunkGenerator {
+    engine: Engine,
+    linker: Linker<GenerationData>,
+    /// Pass modules of every dimension kind, in the pass order.
+    modules: Vec<Vec<Module>>,
+    world_seed: u64,
+    block_class_label_map: LabelMap<BlockClass>,
+    dimension_kind_label_map: LabelMap<DimensionKind>,
+    chunk_storage: ChunkStorage,
+    structure_storage: StructureStorage,
+}
+
+impl ChunkGenerator {
+    pub async fn load(
+        chunk_storage: ChunkStorage,
+        structure_storage: StructureStorage,
+        world_seed: u64,
+        packs: PackSet,
         block_class_label_map: LabelMap<BlockClass>,
         dimension_kind_label_map: LabelMap<DimensionKind>,
-        send_chunk_data: impl Fn(Chunk, BlocksVec<BlockClass>, &mut Packer) + Send + 'static,
+        biome_registry: Arc<BiomeRegistry>,
     ) -> Self {
-        let (new_chunks_tx, new_chunks_rx) = flume::unbounded();
-
-        let script_labels: LabelMap<Script> = List::load(CHUNK_GENERATION_SCRIPT_LIST)
+        let script_labels: LabelMap<Script> = packs
+            .load_list(CHUNK_GENERATION_SCRIPT_LIST)
             .await
             .expect("unable to load chunk generation script list")
             .into_label_map();
 
-        let dimension_kind_script_map = Map::load(DIMENSION_KIND_GENERATION_MAP)
+        let pass_labels = packs
+            .load_list(CHUNK_GENERATION_PASS_LIST)
+            .await
+            .expect("unable to load chunk generation pass list")
+            .list;
+
+        let dimension_kind_script_map = {
+            let packs = packs.clone();
+
+            task::spawn_blocking(move || {
+                let mut map: HashMap<String, HashMap<String, String>> = HashMap::new();
+
+                for pack_map in packs
+                    .read_data_files::<DimensionKindGenerationMap>(DIMENSION_KIND_GENERATION_MAP)?
+                {
+                    for (dimension_label, pass_scripts) in pack_map.map {
+                        map.entry(dimension_label).or_default().extend(pass_scripts);
+                    }
+                }
+
+                Ok::<_, Error>(DimensionKindGenerationMap { map })
+            })
             .await
-            .expect("unable to load dimension kind chunk generation script map");
+            .unwrap()
+            .expect("unable to load dimension kind chunk generation script map")
+        };
 
+        // Script labels in the pass order for each of the dimension kinds
         let dimension_scripts = dimension_kind_label_map
             .iter()
             .map(|(_, dimension_label)| {
-                let script_label = dimension_kind_script_map
+                let pass_scripts = dimension_kind_script_map
+                    .map
                     .get(dimension_label)
-                    .map(|s| s.to_owned())
                     .ok_or_else(|| {
                         anyhow::anyhow!(
-                            "no script for dimension kind \"{}\" defined",
+                            "no scripts for dimension kind \"{}\" defined",
                             dimension_label,
                         )
                     })?;
 
-                script_labels
-                    .get(&script_label)
-                    .ok_or_else(|| anyhow::anyhow!("no script \"{}\" defined", script_label))?;
+                for (pass_label, script_label) in pass_scripts.iter() {
+                    if !pass_labels.contains(pass_label) {
+                        return Err(anyhow::anyhow!(
+                            "no generation pass \"{}\" defined",
+                            pass_label
+                        ));
+                    }
+
+                    script_labels
+                        .get(script_label)
+                        .ok_or_else(|| anyhow::anyhow!("no script \"{}\" defined", script_label))?;
+                }
+
+                let scripts = pass_labels
+                    .iter()
+                    .filter_map(|pass_label| pass_scripts.get(pass_label).cloned())
+                    .collect::<Vec<_>>();
 
-                Ok(script_label.clone())
+                if scripts.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "no scripts for dimension kind \"{}\" defined",
+                        dimension_label,
+                    ));
+                }
+
+                Ok(scripts)
             })
             .collect::<Result<Vec<_>, Error>>()
             .expect("unable to define scripts for dimension generation");
 
-        thread::spawn(move || {
+        task::spawn_blocking(move || {
             let mut engine_config = Config::new();
 
             engine_config
@@ -119,6 +222,14 @@ impl ChunkGenerationSystem {
                 )
                 .unwrap();
 
+            linker
+                .func_wrap(
+                    "env",
+                    "get_world_seed",
+                    |caller: Caller<'_, GenerationData>| -> u64 { caller.data().seed },
+                )
+                .unwrap();
+
             linker
                 .func_wrap(
                     "env",
@@ -139,88 +250,365 @@ impl ChunkGenerationSystem {
                     "env",
                     "push_block",
                     |mut caller: Caller<'_, GenerationData>, block_class: u64| {
+                        let block_classes = &mut caller.data_mut().block_classes;
+
+                        if block_classes.len() < BLOCKS_IN_CHUNK {
+                            block_classes.push(BlockClass(block_class));
+                        }
+                    },
+                )
+                .unwrap();
+
+            linker
+                .func_wrap(
+                    "env",
+                    "submit_blocks",
+                    |mut caller: Caller<'_, GenerationData>,
+                     ptr: u32,
+                     len: u32|
+                     -> Result<(), Error> {
+                        let len = len as usize;
+
+                        if len != BLOCKS_IN_CHUNK {
+                            return Err(anyhow::anyhow!(
+                                "submitted {} blocks instead of {}",
+                                len,
+                                BLOCKS_IN_CHUNK
+                            ));
+                        }
+
+                        let start = ptr as usize;
+                        let end = start + len * mem::size_of::<u64>();
+
+                        let memory = caller
+                            .get_export("memory")
+                            .and_then(|export| export.into_memory())
+                            .ok_or_else(|| anyhow::anyhow!("script memory is not exported"))?;
+
+                        let (bytes, data) = memory.data_and_store_mut(&mut caller);
+
+                        let bytes = bytes
+                            .get(start .. end)
+                            .ok_or_else(|| anyhow::anyhow!("submitted blocks are out of bounds"))?;
+
+                        data.block_classes.clear();
+                        data.block_classes
+                            .extend(bytes.chunks_exact(mem::size_of::<u64>()).map(|bytes| {
+                                BlockClass(u64::from_le_bytes(bytes.try_into().unwrap()))
+                            }));
+
+                        Ok(())
+                    },
+                )
+                .unwrap();
+
+            linker
+                .func_wrap(
+                    "env",
+                    "get_block",
+                    |caller: Caller<'_, GenerationData>, block: u32| -> u64 {
                         caller
-                            .data_mut()
+                            .data()
                             .block_classes
-                            .push(BlockClass(block_class));
+                            .get(block as usize)
+                            .map(|block_class| block_class.0)
+                            .unwrap_or(NO_BLOCK_CLASS)
                     },
                 )
                 .unwrap();
 
-            let mut path_buf: PathBuf = CHUNK_GENERATION_SCRIPT_DIR
-                .parse()
-                .expect("unable to parse chunk generation script dir path");
+            linker
+                .func_wrap(
+                    "env",
+                    "set_block",
+                    |mut caller: Caller<'_, GenerationData>, block: u32, block_class: u64| {
+                        if let Some(value) = caller.data_mut().block_classes.get_mut(block as usize)
+                        {
+                            *value = BlockClass(block_class);
+                        }
+                    },
+                )
+                .unwrap();
 
-            let mut modules = Vec::with_capacity(dimension_scripts.len());
+            linker
+                .func_wrap(
+                    "env",
+                    "place_block",
+                    |mut caller: Caller<'_, GenerationData>,
+                     x: i32,
+                     y: i32,
+                     z: i32,
+                     block_class: u64| {
+                        let data = caller.data_mut();
+                        let block_class = BlockClass(block_class);
+
+                        let Some((chunk, block)) = Block::from_chunk_offset(data.chunk, [x, y, z])
+                        else {
+                            return;
+                        };
+
+                        if chunk == data.chunk {
+                            if let Some(value) = data.block_classes.get_mut(block.as_usize()) {
+                                *value = block_class;
+                                return;
+                            }
+                        }
+
+                        data.placements.push((chunk, block, block_class));
+                    },
+                )
+                .unwrap();
 
-            for label in dimension_scripts.iter() {
-                path_buf.push(label);
-                path_buf.set_extension("wasm");
+            let registry = biome_registry.clone();
 
-                let module = Module::from_file(&engine, &path_buf)
-                    .expect("unable to load chunk generation script module");
+            linker
+                .func_wrap(
+                    "env",
+                    "get_biome",
+                    move |caller: Caller<'_, GenerationData>,
+                          chunk_x: i32,
+                          chunk_y: i32,
+                          column: u32|
+                          -> u64 {
+                        let data = caller.data();
+
+                        registry
+                            .biome_at(
+                                data.seed,
+                                data.chunk.dimension,
+                                [chunk_x, chunk_y],
+                                column as usize,
+                            )
+                            .0
+                    },
+                )
+                .unwrap();
 
-                modules.push(module);
+            let registry = biome_registry.clone();
 
-                path_buf.pop();
-            }
+            linker
+                .func_wrap(
+                    "env",
+                    "get_biome_by_label",
+                    move |mut caller: Caller<'_, GenerationData>, ptr: u32, len: u32| -> u64 {
+                        let ptr = ptr as usize;
+                        let len = len as usize;
+                        let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
+                        let label =
+                            std::str::from_utf8(&memory.data(&caller)[ptr .. ptr + len]).unwrap();
+                        registry.label_map().get(label).unwrap().0
+                    },
+                )
+                .unwrap();
 
-            let mut packer = Packer::new();
+            let registry = biome_registry.clone();
 
-            let seed = 0;
+            linker
+                .func_wrap(
+                    "env",
+                    "get_biome_surface_block",
+                    move |_caller: Caller<'_, GenerationData>, biome: u64| -> u64 {
+                        registry
+                            .get(&Biome(biome))
+                            .map(|biome| biome.surface_block.0)
+                            .unwrap_or(NO_BLOCK_CLASS)
+                    },
+                )
+                .unwrap();
 
-            while let Ok(chunk) = new_chunks_rx.recv() {
-                let Chunk {
-                    position,
-                    dimension: Dimension { kind, phase },
-                } = chunk;
-
-                let mut store = Store::new(
-                    &engine,
-                    GenerationData {
-                        block_class_label_map: block_class_label_map.clone(),
-                        block_classes: BlocksVecBuilder::new(),
+            let registry = biome_registry;
+
+            linker
+                .func_wrap(
+                    "env",
+                    "get_biome_vegetation_density",
+                    move |_caller: Caller<'_, GenerationData>, biome: u64| -> f32 {
+                        registry
+                            .get(&Biome(biome))
+                            .map(|biome| biome.vegetation_density)
+                            .unwrap_or(0.0)
                     },
-                );
+                )
+                .unwrap();
 
-                let module = modules
-                    .get(kind.as_usize())
-                    .ok_or_else(|| {
-                        anyhow::anyhow!(
-                            "unable to find generation script for dimension kind \"{}\"",
-                            dimension_kind_label_map.get_label(&kind).unwrap(),
-                        )
-                    })
-                    .unwrap();
+            let mut loaded_modules: AHashMap<&str, Module> = AHashMap::new();
 
-                let instance = linker.instantiate(&mut store, &module).unwrap();
+            let mut modules = Vec::with_capacity(dimension_scripts.len());
 
-                let generate_fn = instance
-                    .get_typed_func::<(u64, u64, i32, i32, i32), ()>(&mut store, "generate_chunk")
-                    .unwrap();
+            for labels in dimension_scripts.iter() {
+                let mut pass_modules = Vec::with_capacity(labels.len());
 
-                generate_fn
-                    .call(
-                        &mut store,
-                        (seed, phase, position[0], position[1], position[2]),
-                    )
-                    .expect("generate_fn call error");
+                for label in labels.iter() {
+                    let module = loaded_modules.entry(label.as_str()).or_insert_with(|| {
+                        let path = packs
+                            .resolve(
+                                Path::new(CHUNK_GENERATION_SCRIPT_DIR)
+                                    .join(label)
+                                    .with_extension("wasm"),
+                            )
+                            .expect("unable to find chunk generation script module");
 
-                let block_classes =
-                    mem::replace(&mut store.data_mut().block_classes, BlocksVecBuilder::new())
-                        .build();
+                        Module::from_file(&engine, path)
+                            .expect("unable to load chunk generation script module")
+                    });
 
-                let db_write = database.begin_write().unwrap();
-                {
-                    let mut table = db_write.open_table(BLOCK_CLASS_TABLE).unwrap();
-                    table
-                        .insert(
-                            chunk.into_data_sized(),
-                            block_classes.into_data(&mut packer),
-                        )
-                        .expect("server_loop: database write");
+                    pass_modules.push(module.clone());
                 }
-                db_write.commit().unwrap();
+
+                modules.push(pass_modules);
+            }
+
+            Self {
+                engine,
+                linker,
+                modules,
+                world_seed,
+                block_class_label_map,
+                dimension_kind_label_map,
+                chunk_storage,
+                structure_storage,
+            }
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Generates the chunk, applies the structure placements queued for it and saves it.
+    /// Placements into the other chunks are queued, `structure_queued` is called for each of
+    /// those chunks.
+    pub fn generate(
+        &self,
+        chunk: Chunk,
+        packer: &mut Packer,
+        structure_queued: impl Fn(Chunk),
+    ) -> BlocksVec<BlockClass> {
+        let Chunk {
+            position,
+            dimension: Dimension { kind, phase },
+        } = chunk;
+
+        let mut store = Store::new(
+            &self.engine,
+            GenerationData {
+                seed: self.world_seed,
+                chunk,
+                block_class_label_map: self.block_class_label_map.clone(),
+                block_classes: Vec::with_capacity(BLOCKS_IN_CHUNK),
+                placements: Vec::new(),
+            },
+        );
+
+        let pass_modules = self
+            .modules
+            .get(kind.as_usize())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "unable to find generation scripts for dimension kind \"{}\"",
+                    self.dimension_kind_label_map.get_label(&kind).unwrap(),
+                )
+            })
+            .unwrap();
+
+        for module in pass_modules.iter() {
+            let instance = self.linker.instantiate(&mut store, module).unwrap();
+
+            let generate_fn = instance
+                .get_typed_func::<(u64, u64, i32, i32, i32), ()>(&mut store, "generate_chunk")
+                .unwrap();
+
+            generate_fn
+                .call(
+                    &mut store,
+                    (
+                        self.world_seed,
+                        phase,
+                        position[0],
+                        position[1],
+                        position[2],
+                    ),
+                )
+                .expect("generate_fn call error");
+        }
+
+        let generated = mem::take(&mut store.data_mut().block_classes);
+
+        if generated.len() != BLOCKS_IN_CHUNK {
+            panic!(
+                "generation scripts of dimension kind \"{}\" have produced {} blocks instead of {}",
+                self.dimension_kind_label_map.get_label(&kind).unwrap(),
+                generated.len(),
+                BLOCKS_IN_CHUNK,
+            );
+        }
+
+        let mut block_classes = BlocksVec::new();
+
+        for block_class in generated {
+            block_classes.push(block_class);
+        }
+
+        let mut block_classes = block_classes.build();
+
+        let mut queued: AHashMap<Chunk, Vec<(Block, BlockClass)>> = AHashMap::new();
+
+        for (target_chunk, block, block_class) in mem::take(&mut store.data_mut().placements) {
+            if target_chunk == chunk {
+                *block_classes.get_mut(block) = block_class;
+            } else {
+                queued
+                    .entry(target_chunk)
+                    .or_default()
+                    .push((block, block_class));
+            }
+        }
+
+        // Structures of the neighbors generated earlier
+        for (block, block_class) in self.structure_storage.take(chunk, packer) {
+            *block_classes.get_mut(block) = block_class;
+        }
+
+        for (target_chunk, placements) in queued {
+            self.structure_storage
+                .add(target_chunk, &placements, packer);
+            structure_queued(target_chunk);
+        }
+
+        self.chunk_storage.save(chunk, &block_classes, packer);
+
+        block_classes
+    }
+}
+
+impl ChunkGenerationSystem {
+    pub async fn new(
+        chunk_storage: ChunkStorage,
+        structure_storage: StructureStorage,
+        world_seed: u64,
+        packs: PackSet,
+        block_class_label_map: LabelMap<BlockClass>,
+        dimension_kind_label_map: LabelMap<DimensionKind>,
+        biome_registry: Arc<BiomeRegistry>,
+        send_chunk_data: impl Fn(Chunk, BlocksVec<BlockClass>, &mut Packer) + Send + 'static,
+        send_structure_queued: impl Fn(Chunk) + Send + 'static,
+    ) -> Self {
+        let (new_chunks_tx, new_chunks_rx) = flume::unbounded();
+
+        let generator = ChunkGenerator::load(
+            chunk_storage,
+            structure_storage,
+            world_seed,
+            packs,
+            block_class_label_map,
+            dimension_kind_label_map,
+            biome_registry,
+        )
+        .await;
+
+        thread::spawn(move || {
+            let mut packer = Packer::new();
+
+            while let Ok(chunk) = new_chunks_rx.recv() {
+                let block_classes = generator.generate(chunk, &mut packer, &send_structure_queued);
 
                 send_chunk_data(chunk, block_classes, &mut packer);
             }
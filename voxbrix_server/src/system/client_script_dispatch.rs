@@ -0,0 +1,34 @@
+use crate::entity::player::Player;
+use std::mem;
+
+pub struct ClientScriptDispatch {
+    pub player: Player,
+    pub script: String,
+    pub payload: Vec<u8>,
+}
+
+/// Collects the client script runs requested by the server scripts,
+/// these are sent to the players after the scripts of the tick have run.
+pub struct ClientScriptDispatchSystem {
+    dispatches: Vec<ClientScriptDispatch>,
+}
+
+impl ClientScriptDispatchSystem {
+    pub fn new() -> Self {
+        Self {
+            dispatches: Vec::new(),
+        }
+    }
+
+    pub fn dispatch(&mut self, player: Player, script: String, payload: Vec<u8>) {
+        self.dispatches.push(ClientScriptDispatch {
+            player,
+            script,
+            payload,
+        });
+    }
+
+    pub fn take_dispatches(&mut self) -> Vec<ClientScriptDispatch> {
+        mem::take(&mut self.dispatches)
+    }
+}
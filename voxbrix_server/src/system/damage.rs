@@ -0,0 +1,51 @@
+use crate::component::actor::health::HealthActorComponent;
+use std::mem;
+use voxbrix_common::entity::{
+    actor::Actor,
+    snapshot::Snapshot,
+};
+
+pub struct Death {
+    pub actor: Actor,
+    pub source: Option<Actor>,
+}
+
+/// Applies damage to the actor health and collects the resulting deaths.
+pub struct DamageSystem {
+    deaths: Vec<Death>,
+}
+
+impl DamageSystem {
+    pub fn new() -> Self {
+        Self { deaths: Vec::new() }
+    }
+
+    /// Returns `true` if the damage has killed the actor.
+    /// Actors without health are not affected.
+    pub fn damage(
+        &mut self,
+        health_ac: &mut HealthActorComponent,
+        snapshot: Snapshot,
+        actor: Actor,
+        amount: u32,
+        source: Option<Actor>,
+    ) -> bool {
+        let Some(mut health) = health_ac.get(&actor).copied() else {
+            return false;
+        };
+
+        let killed = health.damage(amount);
+
+        health_ac.insert(actor, health, snapshot);
+
+        if killed {
+            self.deaths.push(Death { actor, source });
+        }
+
+        killed
+    }
+
+    pub fn take_deaths(&mut self) -> Vec<Death> {
+        mem::take(&mut self.deaths)
+    }
+}
@@ -0,0 +1,67 @@
+use crate::component::actor::effect::EffectActorComponent;
+use std::mem;
+use voxbrix_common::entity::{
+    actor::Actor,
+    effect::Effect,
+    snapshot::Snapshot,
+};
+
+pub struct EffectTick {
+    pub actor: Actor,
+    pub effect: Effect,
+    pub magnitude: u32,
+    pub remaining_ticks: u64,
+}
+
+/// Expires the actor effects and collects their periodic ticks.
+pub struct EffectSystem {
+    ticks: Vec<EffectTick>,
+}
+
+impl EffectSystem {
+    pub fn new() -> Self {
+        Self { ticks: Vec::new() }
+    }
+
+    pub fn process(&mut self, effect_ac: &mut EffectActorComponent, snapshot: Snapshot) {
+        let mut expired = Vec::new();
+
+        for (actor, effects) in effect_ac.iter() {
+            for (effect, instance) in effects.iter() {
+                if instance.ticks_on(snapshot) {
+                    self.ticks.push(EffectTick {
+                        actor,
+                        effect,
+                        magnitude: instance.magnitude,
+                        remaining_ticks: instance.remaining_ticks(snapshot),
+                    });
+                }
+            }
+
+            if effects
+                .iter()
+                .any(|(_, instance)| instance.expires_at <= snapshot)
+            {
+                expired.push(actor);
+            }
+        }
+
+        for actor in expired {
+            let Some(mut effects) = effect_ac.get(&actor).cloned() else {
+                continue;
+            };
+
+            effects.remove_expired(snapshot);
+
+            if effects.is_empty() {
+                effect_ac.remove(&actor, snapshot);
+            } else {
+                effect_ac.insert(actor, effects, snapshot);
+            }
+        }
+    }
+
+    pub fn take_ticks(&mut self) -> Vec<EffectTick> {
+        mem::take(&mut self.ticks)
+    }
+}
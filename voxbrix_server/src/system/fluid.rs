@@ -0,0 +1,283 @@
+use crate::component::block::class::ClassBlockComponent;
+use ahash::{
+    AHashMap,
+    AHashSet,
+};
+use std::collections::VecDeque;
+use voxbrix_common::{
+    component::block_class::{
+        collision::CollisionBlockClassComponent,
+        fluid::{
+            FluidBlockClassComponent,
+            MAX_FLUID_LEVEL,
+        },
+    },
+    entity::{
+        block::{
+            Block,
+            Neighbor,
+        },
+        block_class::BlockClass,
+        chunk::Chunk,
+    },
+    LabelMap,
+};
+
+/// Delay of the fluid flowing into the next block, in ticks.
+const FLOW_DELAY_TICKS: u64 = 5;
+
+const SIDE_OFFSETS: [[i32; 3]; 6] = [
+    [-1, 0, 0],
+    [1, 0, 0],
+    [0, -1, 0],
+    [0, 1, 0],
+    [0, 0, -1],
+    [0, 0, 1],
+];
+
+const DOWN_SIDE: usize = 4;
+const HORIZONTAL_SIDES: [usize; 4] = [0, 1, 2, 3];
+
+struct FluidUpdate {
+    tick: u64,
+    chunk: Chunk,
+    block: Block,
+}
+
+/// Cellular flow of the fluids with the finite amount of levels.
+/// The fluid first falls down and then spreads sideways one level at a time,
+/// the total amount of the fluid stays the same.
+pub struct FluidSystem {
+    tick: u64,
+    /// Block classes of each fluid by level, level 1 has index 0.
+    fluid_classes: AHashMap<String, [Option<BlockClass>; MAX_FLUID_LEVEL as usize]>,
+    /// The class the fluid leaves behind.
+    empty_class: BlockClass,
+    /// Updates in the order of their ticks, the delay is the same for all of them.
+    queue: VecDeque<FluidUpdate>,
+    queued: AHashSet<(Chunk, Block)>,
+}
+
+impl FluidSystem {
+    pub fn new(
+        fluid_bcc: &FluidBlockClassComponent,
+        block_class_label_map: &LabelMap<BlockClass>,
+    ) -> Self {
+        let mut fluid_classes = AHashMap::new();
+
+        for (block_class, _) in block_class_label_map.iter() {
+            let Some(fluid) = fluid_bcc.get(&block_class) else {
+                continue;
+            };
+
+            let Some(index) = fluid
+                .level
+                .checked_sub(1)
+                .filter(|index| *index < MAX_FLUID_LEVEL)
+            else {
+                continue;
+            };
+
+            fluid_classes
+                .entry(fluid.fluid.clone())
+                .or_insert([None; MAX_FLUID_LEVEL as usize])[index as usize] = Some(block_class);
+        }
+
+        Self {
+            tick: 0,
+            fluid_classes,
+            empty_class: block_class_label_map
+                .get("air")
+                .expect("block class \"air\" is not defined"),
+            queue: VecDeque::new(),
+            queued: AHashSet::new(),
+        }
+    }
+
+    fn schedule(&mut self, chunk: Chunk, block: Block) {
+        if self.queued.insert((chunk, block)) {
+            self.queue.push_back(FluidUpdate {
+                tick: self.tick + FLOW_DELAY_TICKS,
+                chunk,
+                block,
+            });
+        }
+    }
+
+    /// Schedules the updates of the fluid blocks that changed or have the neighbors changed.
+    /// Must be called before the changes of the `class_bc` are cleared.
+    pub fn schedule_changes(
+        &mut self,
+        class_bc: &ClassBlockComponent,
+        fluid_bcc: &FluidBlockClassComponent,
+    ) {
+        if self.fluid_classes.is_empty() {
+            return;
+        }
+
+        let is_fluid = |chunk: &Chunk, block: Block| {
+            class_bc
+                .get_chunk(chunk)
+                .is_some_and(|classes| fluid_bcc.get(classes.get(block)).is_some())
+        };
+
+        let mut to_schedule = Vec::new();
+
+        for chunk_changes in class_bc.changed_chunks() {
+            let changed_chunk = *chunk_changes.chunk;
+
+            for (changed_block, _) in chunk_changes.changes() {
+                if is_fluid(&changed_chunk, *changed_block) {
+                    to_schedule.push((changed_chunk, *changed_block));
+                }
+
+                for side in 0 .. SIDE_OFFSETS.len() {
+                    let Some((chunk, block)) = neighbor(changed_chunk, *changed_block, side) else {
+                        continue;
+                    };
+
+                    if is_fluid(&chunk, block) {
+                        to_schedule.push((chunk, block));
+                    }
+                }
+            }
+        }
+
+        for (chunk, block) in to_schedule {
+            self.schedule(chunk, block);
+        }
+    }
+
+    /// Flows the fluid of the blocks due on this tick.
+    /// The changed blocks get updated again with the next `schedule_changes`.
+    pub fn process(
+        &mut self,
+        class_bc: &mut ClassBlockComponent,
+        collision_bcc: &CollisionBlockClassComponent,
+        fluid_bcc: &FluidBlockClassComponent,
+    ) {
+        self.tick += 1;
+
+        while self
+            .queue
+            .front()
+            .is_some_and(|update| update.tick <= self.tick)
+        {
+            let update = self.queue.pop_front().unwrap();
+            self.queued.remove(&(update.chunk, update.block));
+
+            self.flow(
+                update.chunk,
+                update.block,
+                class_bc,
+                collision_bcc,
+                fluid_bcc,
+            );
+        }
+    }
+
+    fn flow(
+        &self,
+        chunk: Chunk,
+        block: Block,
+        class_bc: &mut ClassBlockComponent,
+        collision_bcc: &CollisionBlockClassComponent,
+        fluid_bcc: &FluidBlockClassComponent,
+    ) {
+        let Some(fluid) = class_bc
+            .get_chunk(&chunk)
+            .and_then(|classes| fluid_bcc.get(classes.get(block)))
+        else {
+            return;
+        };
+
+        let Some(levels) = self.fluid_classes.get(&fluid.fluid) else {
+            return;
+        };
+
+        // Level of the same fluid in the block, zero for the empty block,
+        // `None` if the fluid cannot flow there
+        let level_at = |class_bc: &ClassBlockComponent, chunk: &Chunk, block: Block| {
+            let block_class = class_bc.get_chunk(chunk)?.get(block);
+
+            match fluid_bcc.get(block_class) {
+                Some(other) if other.is_same_fluid(fluid) => Some(other.level),
+                Some(_) => None,
+                None if collision_bcc.get(block_class).is_some() => None,
+                None => Some(0),
+            }
+        };
+
+        let class_of_level = |level: u8| {
+            match level {
+                0 => Some(self.empty_class),
+                level => levels.get(level as usize - 1).copied().flatten(),
+            }
+        };
+
+        let mut level = fluid.level;
+        let mut changes = Vec::new();
+
+        // Falling down first
+        if let Some((below_chunk, below_block)) = neighbor(chunk, block, DOWN_SIDE) {
+            if let Some(below_level) = level_at(class_bc, &below_chunk, below_block) {
+                let moved = level.min(MAX_FLUID_LEVEL.saturating_sub(below_level));
+
+                if moved > 0 {
+                    level -= moved;
+                    changes.push((below_chunk, below_block, below_level + moved));
+                }
+            }
+        }
+
+        // The rest spreads to the lower neighbors, the lowest ones first
+        let mut sides = HORIZONTAL_SIDES
+            .into_iter()
+            .filter_map(|side| {
+                let (side_chunk, side_block) = neighbor(chunk, block, side)?;
+                let side_level = level_at(class_bc, &side_chunk, side_block)?;
+
+                Some((side_chunk, side_block, side_level))
+            })
+            .collect::<Vec<_>>();
+
+        sides.sort_by_key(|(_, _, side_level)| *side_level);
+
+        for (side_chunk, side_block, side_level) in sides {
+            if side_level + 1 >= level {
+                break;
+            }
+
+            level -= 1;
+            changes.push((side_chunk, side_block, side_level + 1));
+        }
+
+        if changes.is_empty() {
+            return;
+        }
+
+        changes.push((chunk, block, level));
+
+        // Level classes must all exist, the fluid would be lost otherwise
+        let Some(changes) = changes
+            .into_iter()
+            .map(|(chunk, block, level)| Some((chunk, block, class_of_level(level)?)))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return;
+        };
+
+        for (chunk, block, block_class) in changes {
+            if let Some(mut classes) = class_bc.get_mut_chunk(&chunk) {
+                classes.set(block, block_class);
+            }
+        }
+    }
+}
+
+fn neighbor(chunk: Chunk, block: Block, side: usize) -> Option<(Chunk, Block)> {
+    match block.neighbors()[side] {
+        Neighbor::ThisChunk(block) => Some((chunk, block)),
+        Neighbor::OtherChunk(block) => Some((chunk.checked_add(SIDE_OFFSETS[side])?, block)),
+    }
+}
@@ -9,7 +9,7 @@ use std::{
     path::Path,
 };
 use tokio::task;
-use voxbrix_common::read_data_file;
+use voxbrix_common::system::pack_loading::PackSet;
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct Map {
@@ -17,22 +17,28 @@ pub struct Map {
 }
 
 impl Map {
+    /// Maps of all the packs merged, the later packs override the keys.
     pub async fn load(
+        packs: PackSet,
         path: impl 'static + AsRef<Path> + Debug + Send + Clone,
     ) -> Result<Self, Error> {
         let read_path = path.clone();
 
-        task::spawn_blocking(move || read_data_file::<Map>(read_path))
-            .await
-            .unwrap()
-            .with_context(|| format!("unable to load map \"{:?}\"", path))
+        task::spawn_blocking(move || {
+            let mut map = HashMap::new();
+
+            for pack_map in packs.read_data_files::<Map>(read_path)? {
+                map.extend(pack_map.map);
+            }
+
+            Ok::<_, Error>(Map { map })
+        })
+        .await
+        .unwrap()
+        .with_context(|| format!("unable to load map \"{:?}\"", path))
     }
 
     pub fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = (&'a str, &'a str)> {
         self.map.iter().map(|(a, s)| (a.as_str(), s.as_str()))
     }
-
-    pub fn get(&self, key: &str) -> Option<&str> {
-        self.map.get(key).map(|s| s.as_str())
-    }
 }
@@ -0,0 +1,129 @@
+use crate::{
+    component::block::class::ClassBlockComponent,
+    entity::player::Player,
+};
+use nohash_hasher::IntMap;
+use std::time::Duration;
+use voxbrix_common::{
+    component::{
+        actor::position::Position,
+        actor_class::collider::Collider,
+        block_class::collision::CollisionBlockClassComponent,
+    },
+    entity::snapshot::Snapshot,
+    system::position,
+};
+
+/// Movement the player could save up while standing still, in seconds of moving at the full
+/// speed. Covers the client states delayed by the network and then delivered at once.
+const MAX_BUDGET_SECS: f32 = 1.0;
+/// Distance between the position the player has sent and the one the collider could reach
+/// that is still accepted, in blocks.
+const COLLISION_TOLERANCE: f32 = 0.05;
+
+struct PlayerMovement {
+    /// Distance the player could move now, in blocks.
+    budget: f32,
+    last_move: Snapshot,
+    violations: u64,
+}
+
+/// Checks the positions the players send against the speed limit and the solid blocks.
+/// Too long moves are cut to the distance the player could have moved, the moves through
+/// the solid blocks stop before them.
+/// The time is counted in the server ticks, so the replays validate the same way.
+pub struct MovementValidationSystem {
+    /// In blocks per second, positions are not checked if not set.
+    max_speed: Option<f32>,
+    process_interval: Duration,
+    players: IntMap<Player, PlayerMovement>,
+}
+
+impl MovementValidationSystem {
+    pub fn new(max_speed: Option<f32>, process_interval: Duration) -> Self {
+        Self {
+            max_speed,
+            process_interval,
+            players: IntMap::default(),
+        }
+    }
+
+    /// Returns the position the player must be corrected to if the move is invalid.
+    pub fn validate(
+        &mut self,
+        player: &Player,
+        snapshot: Snapshot,
+        old_position: &Position,
+        new_position: &Position,
+        collider: &Collider,
+        class_bc: &ClassBlockComponent,
+        collision_bcc: &CollisionBlockClassComponent,
+    ) -> Option<Position> {
+        let max_speed = self.max_speed?;
+        let max_budget = max_speed * MAX_BUDGET_SECS;
+
+        let movement = self.players.entry(*player).or_insert(PlayerMovement {
+            budget: max_budget,
+            last_move: snapshot,
+            violations: 0,
+        });
+
+        let elapsed = snapshot.0.saturating_sub(movement.last_move.0) as f32
+            * self.process_interval.as_secs_f32();
+
+        movement.budget = (movement.budget + elapsed * max_speed).min(max_budget);
+        movement.last_move = snapshot;
+
+        // Players change the dimension only by the server
+        let Some(vector) = old_position.vector_to(new_position) else {
+            movement.violations += 1;
+            return Some(*old_position);
+        };
+
+        let distance = vector.length();
+
+        let target = if distance > movement.budget {
+            position::to_chunk_position(
+                old_position.chunk,
+                old_position.offset + vector * (movement.budget / distance),
+            )
+        } else {
+            *new_position
+        };
+
+        let reached =
+            position::sweep_actor(class_bc, collision_bcc, old_position, &target, collider)
+                .unwrap_or(*old_position);
+
+        let travelled = old_position
+            .vector_to(&reached)
+            .map(|vector| vector.length())
+            .unwrap_or(0.0);
+
+        movement.budget = (movement.budget - travelled).max(0.0);
+
+        let is_valid = reached
+            .vector_to(new_position)
+            .is_some_and(|vector| vector.length() <= COLLISION_TOLERANCE);
+
+        if is_valid {
+            return None;
+        }
+
+        movement.violations += 1;
+
+        Some(reached)
+    }
+
+    /// Number of the invalid moves the player has made.
+    pub fn violations(&self, player: &Player) -> u64 {
+        self.players
+            .get(player)
+            .map(|movement| movement.violations)
+            .unwrap_or(0)
+    }
+
+    pub fn remove_player(&mut self, player: &Player) {
+        self.players.remove(player);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::component::{
+    block::class::ClassBlockComponent,
+    block_class::neighbor_changed::NeighborChangedBlockClassComponent,
+};
+use std::mem;
+use voxbrix_common::entity::{
+    block::{
+        Block,
+        Neighbor,
+    },
+    block_class::BlockClass,
+    chunk::Chunk,
+    script::Script,
+};
+
+const SIDE_OFFSETS: [[i32; 3]; 6] = [
+    [-1, 0, 0],
+    [1, 0, 0],
+    [0, -1, 0],
+    [0, 1, 0],
+    [0, 0, -1],
+    [0, 0, 1],
+];
+
+pub struct NeighborUpdate {
+    pub chunk: Chunk,
+    pub block: Block,
+    pub block_class: BlockClass,
+    pub script: Script,
+    pub changed_chunk: Chunk,
+    pub changed_block: Block,
+}
+
+/// Notifies the blocks about the class changes of their neighbors.
+pub struct NeighborUpdateSystem {
+    updates: Vec<NeighborUpdate>,
+}
+
+impl NeighborUpdateSystem {
+    pub fn new() -> Self {
+        Self {
+            updates: Vec::new(),
+        }
+    }
+
+    /// Enqueues the neighbors of the changed blocks that have the neighbor changed script.
+    /// Must be called before the changes of the `class_bc` are cleared.
+    pub fn process(
+        &mut self,
+        class_bc: &ClassBlockComponent,
+        neighbor_changed_bcc: &NeighborChangedBlockClassComponent,
+    ) {
+        for chunk_changes in class_bc.changed_chunks() {
+            let changed_chunk = *chunk_changes.chunk;
+
+            for (changed_block, _) in chunk_changes.changes() {
+                for (neighbor, offset) in changed_block.neighbors().into_iter().zip(SIDE_OFFSETS) {
+                    let (chunk, block) = match neighbor {
+                        Neighbor::ThisChunk(block) => (changed_chunk, block),
+                        Neighbor::OtherChunk(block) => {
+                            let Some(chunk) = changed_chunk.checked_add(offset) else {
+                                continue;
+                            };
+
+                            (chunk, block)
+                        },
+                    };
+
+                    let Some(block_class) = class_bc
+                        .get_chunk(&chunk)
+                        .map(|classes| *classes.get(block))
+                    else {
+                        continue;
+                    };
+
+                    let Some(script) = neighbor_changed_bcc.get(&block_class) else {
+                        continue;
+                    };
+
+                    self.updates.push(NeighborUpdate {
+                        chunk,
+                        block,
+                        block_class,
+                        script: *script,
+                        changed_chunk,
+                        changed_block: *changed_block,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Updates enqueued since the last call.
+    pub fn take_updates(&mut self) -> Vec<NeighborUpdate> {
+        mem::take(&mut self.updates)
+    }
+}
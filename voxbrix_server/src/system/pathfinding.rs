@@ -0,0 +1,206 @@
+use crate::component::block::class::ClassBlockComponent;
+use std::{
+    cmp::Reverse,
+    collections::{
+        BinaryHeap,
+        HashMap,
+    },
+};
+use voxbrix_common::{
+    component::block_class::collision::CollisionBlockClassComponent,
+    entity::{
+        block::{
+            Block,
+            BLOCKS_IN_CHUNK_EDGE_I32,
+        },
+        chunk::Chunk,
+    },
+};
+
+/// Maximum number of nodes expanded by a single search.
+const MAX_EXPANDED_NODES: usize = 2048;
+/// Height of the ledge an actor climbs onto in a single step.
+const MAX_CLIMB: i32 = 1;
+/// Height of the ledge an actor walks down from in a single step.
+const MAX_DROP: i32 = 3;
+
+const HORIZONTAL_DIRECTIONS: [[i32; 3]; 4] = [[1, 0, 0], [-1, 0, 0], [0, 1, 0], [0, -1, 0]];
+
+/// Block coordinates relative to the origin chunk of the search.
+pub type NodeCoords = [i32; 3];
+
+fn add(a: NodeCoords, b: NodeCoords) -> NodeCoords {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn distance(a: NodeCoords, b: NodeCoords) -> u32 {
+    a.iter().zip(b.iter()).map(|(a, b)| a.abs_diff(*b)).sum()
+}
+
+/// Offset of the `chunk` from the `origin` in blocks.
+/// `None` if the chunks are in different dimensions.
+pub fn chunk_offset(origin: &Chunk, chunk: &Chunk) -> Option<NodeCoords> {
+    if origin.dimension != chunk.dimension {
+        return None;
+    }
+
+    let mut offset = [0; 3];
+
+    for i in 0 .. 3 {
+        offset[i] = chunk.position[i]
+            .checked_sub(origin.position[i])?
+            .checked_mul(BLOCKS_IN_CHUNK_EDGE_I32)?;
+    }
+
+    Some(offset)
+}
+
+/// A* search over the block classes for the actors walking on top of the solid blocks.
+pub struct Pathfinder<'a> {
+    origin: Chunk,
+    /// Number of the free blocks the actor needs above the block it stands on.
+    height: i32,
+    class_bc: &'a ClassBlockComponent,
+    collision_bcc: &'a CollisionBlockClassComponent,
+}
+
+impl<'a> Pathfinder<'a> {
+    pub fn new(
+        origin: Chunk,
+        height: i32,
+        class_bc: &'a ClassBlockComponent,
+        collision_bcc: &'a CollisionBlockClassComponent,
+    ) -> Self {
+        Self {
+            origin,
+            height: height.max(1),
+            class_bc,
+            collision_bcc,
+        }
+    }
+
+    /// Blocks of the chunks that are not loaded are considered solid.
+    fn is_solid(&self, node: NodeCoords) -> bool {
+        let Some((chunk, block)) = Block::from_chunk_offset(self.origin, node) else {
+            return true;
+        };
+
+        let Some(blocks) = self.class_bc.get_chunk(&chunk) else {
+            return true;
+        };
+
+        self.collision_bcc.get(blocks.get(block)).is_some()
+    }
+
+    fn is_free(&self, node: NodeCoords, height: i32) -> bool {
+        (0 .. height).all(|z| !self.is_solid(add(node, [0, 0, z])))
+    }
+
+    fn can_stand(&self, node: NodeCoords) -> bool {
+        self.is_solid(add(node, [0, 0, -1])) && self.is_free(node, self.height)
+    }
+
+    fn for_each_neighbor(&self, node: NodeCoords, mut f: impl FnMut(NodeCoords, u32)) {
+        for direction in HORIZONTAL_DIRECTIONS {
+            let next = add(node, direction);
+
+            if self.is_free(next, self.height) {
+                if self.can_stand(next) {
+                    f(next, 1);
+                    continue;
+                }
+
+                for drop in 1 ..= MAX_DROP {
+                    let lower = add(next, [0, 0, -drop]);
+
+                    if self.is_solid(lower) {
+                        break;
+                    }
+
+                    if self.can_stand(lower) {
+                        f(lower, 1 + drop as u32);
+                        break;
+                    }
+                }
+            } else {
+                for climb in 1 ..= MAX_CLIMB {
+                    let upper = add(next, [0, 0, climb]);
+
+                    // The actor rises above its current block before moving onto the ledge
+                    if !self.is_free(node, self.height + climb) {
+                        break;
+                    }
+
+                    if self.can_stand(upper) {
+                        f(upper, 1 + climb as u32);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Finds the path from the `start` to the `goal`, both being the blocks the actor stands in.
+    /// The start is not included into the path.
+    /// If the goal is unreachable or too far, the path leads to the closest node found.
+    pub fn find_path(&self, start: NodeCoords, goal: NodeCoords) -> Vec<NodeCoords> {
+        let mut open = BinaryHeap::new();
+        // Node to its parent and the cost of reaching it
+        let mut visited: HashMap<NodeCoords, (NodeCoords, u32)> = HashMap::new();
+
+        let mut closest = (distance(start, goal), start);
+        let mut expanded = 0;
+
+        open.push(Reverse((distance(start, goal), 0, start)));
+        visited.insert(start, (start, 0));
+
+        while let Some(Reverse((_, cost, node))) = open.pop() {
+            if node == goal {
+                closest = (0, node);
+                break;
+            }
+
+            if visited.get(&node).is_some_and(|(_, c)| *c < cost) {
+                continue;
+            }
+
+            expanded += 1;
+            if expanded > MAX_EXPANDED_NODES {
+                break;
+            }
+
+            self.for_each_neighbor(node, |next, step_cost| {
+                let next_cost = cost + step_cost;
+
+                if visited
+                    .get(&next)
+                    .is_some_and(|(_, visited_cost)| *visited_cost <= next_cost)
+                {
+                    return;
+                }
+
+                visited.insert(next, (node, next_cost));
+
+                let remaining = distance(next, goal);
+
+                if remaining < closest.0 {
+                    closest = (remaining, next);
+                }
+
+                open.push(Reverse((next_cost + remaining, next_cost, next)));
+            });
+        }
+
+        let mut path = Vec::new();
+        let mut node = closest.1;
+
+        while node != start {
+            path.push(node);
+            node = visited[&node].0;
+        }
+
+        path.reverse();
+
+        path
+    }
+}
@@ -1,14 +1,22 @@
 use crate::component::{
     actor::{
+        class::ClassActorComponent,
         player::PlayerActorComponent,
         position::PositionActorComponent,
+        projectile::ProjectileActorComponent,
         velocity::VelocityActorComponent,
     },
     block::class::ClassBlockComponent,
 };
 use std::time::Duration;
 use voxbrix_common::{
-    component::block_class::collision::CollisionBlockClassComponent,
+    component::{
+        actor_class::collider::ColliderActorClassComponent,
+        block_class::{
+            collision::CollisionBlockClassComponent,
+            fluid::FluidBlockClassComponent,
+        },
+    },
     entity::snapshot::Snapshot,
     system::position,
 };
@@ -25,27 +33,38 @@ impl PositionSystem {
         dt: Duration,
         class_bc: &ClassBlockComponent,
         collision_bcc: &CollisionBlockClassComponent,
+        fluid_bcc: &FluidBlockClassComponent,
+        class_ac: &ClassActorComponent,
+        collider_acc: &ColliderActorClassComponent,
         position_ac: &mut PositionActorComponent,
         velocity_ac: &VelocityActorComponent,
         player_ac: &PlayerActorComponent,
+        projectile_ac: &ProjectileActorComponent,
         snapshot: Snapshot,
     ) {
-        // TODO: replace
-        let h_radius = 0.45;
-        let v_radius = 0.95;
-        let radius = [h_radius, h_radius, v_radius];
-
-        for (actor, velocity) in velocity_ac
-            .iter()
-            .filter(|(actor, _)| player_ac.get(actor).is_none())
-        {
+        for (actor, velocity) in velocity_ac.iter().filter(|(actor, _)| {
+            player_ac.get(actor).is_none() && projectile_ac.get(actor).is_none()
+        }) {
             let mut position = match position_ac.get_writable(&actor, snapshot) {
                 Some(v) => v,
                 None => continue,
             };
 
-            let new_pos =
-                position::process_actor(dt, class_bc, collision_bcc, &position, velocity, &radius);
+            let collider = class_ac
+                .get(&actor)
+                .and_then(|class| collider_acc.get(class))
+                .copied()
+                .unwrap_or_default();
+
+            let new_pos = position::process_actor(
+                dt,
+                class_bc,
+                collision_bcc,
+                fluid_bcc,
+                &position,
+                velocity,
+                &collider,
+            );
 
             position.update(new_pos);
         }
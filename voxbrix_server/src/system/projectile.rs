@@ -0,0 +1,75 @@
+use crate::component::{
+    actor::{
+        position::PositionActorComponent,
+        projectile::ProjectileActorComponent,
+        velocity::VelocityActorComponent,
+    },
+    block::class::ClassBlockComponent,
+};
+use std::{
+    mem,
+    time::Duration,
+};
+use voxbrix_common::{
+    component::block_class::collision::CollisionBlockClassComponent,
+    entity::{
+        actor::Actor,
+        snapshot::Snapshot,
+    },
+    system::projectile::{
+        self,
+        ProjectileHit,
+    },
+};
+
+pub struct ProjectileSystem {
+    hits: Vec<(Actor, ProjectileHit)>,
+}
+
+impl ProjectileSystem {
+    pub fn new() -> Self {
+        Self { hits: Vec::new() }
+    }
+
+    pub fn process(
+        &mut self,
+        dt: Duration,
+        class_bc: &ClassBlockComponent,
+        collision_bcc: &CollisionBlockClassComponent,
+        projectile_ac: &ProjectileActorComponent,
+        position_ac: &mut PositionActorComponent,
+        velocity_ac: &mut VelocityActorComponent,
+        snapshot: Snapshot,
+    ) {
+        for (actor, projectile) in projectile_ac.iter() {
+            let Some(velocity) = velocity_ac.get(&actor) else {
+                continue;
+            };
+
+            let Some(mut position) = position_ac.get_writable(&actor, snapshot) else {
+                continue;
+            };
+
+            let step = projectile::process_projectile(
+                dt,
+                class_bc,
+                collision_bcc,
+                &position,
+                velocity,
+                projectile.gravity,
+            );
+
+            position.update(step.position);
+            velocity_ac.insert(actor, step.velocity, snapshot);
+
+            if let Some(hit) = step.hit {
+                self.hits.push((actor, hit));
+            }
+        }
+    }
+
+    /// Hits registered since the last call.
+    pub fn take_hits(&mut self) -> Vec<(Actor, ProjectileHit)> {
+        mem::take(&mut self.hits)
+    }
+}
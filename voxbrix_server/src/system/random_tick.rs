@@ -0,0 +1,155 @@
+use crate::{
+    component::{
+        block::class::ClassBlockComponent,
+        block_class::random_tick::RandomTickBlockClassComponent,
+        chunk::status::{
+            ChunkStatus,
+            StatusChunkComponent,
+        },
+    },
+    world_rng::WorldRng,
+};
+use std::mem;
+use voxbrix_common::entity::{
+    block::{
+        Block,
+        BLOCKS_IN_CHUNK,
+    },
+    block_class::BlockClass,
+    chunk::Chunk,
+    script::Script,
+};
+
+pub struct RandomTick {
+    pub chunk: Chunk,
+    pub block: Block,
+    pub block_class: BlockClass,
+    pub script: Script,
+}
+
+/// Selects random blocks in the active chunks to be updated by the block class scripts.
+pub struct RandomTickSystem {
+    chunks: Vec<Chunk>,
+    ticks: Vec<RandomTick>,
+}
+
+impl RandomTickSystem {
+    pub fn new() -> Self {
+        Self {
+            chunks: Vec::new(),
+            ticks: Vec::new(),
+        }
+    }
+
+    /// Selects `ticks_per_chunk` random blocks in each active chunk,
+    /// only the blocks with the random tick script are kept.
+    pub fn process(
+        &mut self,
+        world_rng: &mut WorldRng,
+        ticks_per_chunk: usize,
+        status_cc: &StatusChunkComponent,
+        class_bc: &ClassBlockComponent,
+        random_tick_bcc: &RandomTickBlockClassComponent,
+    ) {
+        self.chunks.clear();
+        self.chunks.extend(
+            status_cc
+                .iter()
+                .filter(|(_, status)| **status == ChunkStatus::Active)
+                .map(|(chunk, _)| *chunk),
+        );
+
+        // The chunk map order differs between the runs,
+        // the blocks are only the same if drawn for the chunks in the same order
+        self.chunks.sort_unstable();
+
+        for chunk in self.chunks.iter() {
+            let Some(classes) = class_bc.get_chunk(chunk) else {
+                continue;
+            };
+
+            for _ in 0 .. ticks_per_chunk {
+                let block =
+                    Block::from_usize(world_rng.below(BLOCKS_IN_CHUNK as u64) as usize).unwrap();
+                let block_class = *classes.get(block);
+
+                if let Some(script) = random_tick_bcc.get(&block_class) {
+                    self.ticks.push(RandomTick {
+                        chunk: *chunk,
+                        block,
+                        block_class,
+                        script: *script,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Ticks selected since the last call.
+    pub fn take_ticks(&mut self) -> Vec<RandomTick> {
+        mem::take(&mut self.ticks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use voxbrix_common::{
+        component::block::BlocksVec,
+        entity::{
+            chunk::{
+                Dimension,
+                DimensionKind,
+            },
+            snapshot::Snapshot,
+        },
+    };
+
+    fn select(chunks: impl Iterator<Item = Chunk>) -> Vec<(Chunk, Block)> {
+        // Every map has its own hasher state
+        let mut status_cc = StatusChunkComponent::new();
+        let mut class_bc = ClassBlockComponent::new();
+        let mut random_tick_bcc = RandomTickBlockClassComponent::new();
+
+        random_tick_bcc.reload(vec![Some(Script(0))]);
+
+        for chunk in chunks {
+            status_cc.insert(chunk, ChunkStatus::Active);
+            class_bc.insert_chunk(chunk, BlocksVec::new_cloned(BlockClass(0)));
+        }
+
+        let mut system = RandomTickSystem::new();
+
+        system.process(
+            &mut WorldRng::new(42, Snapshot(7)),
+            3,
+            &status_cc,
+            &class_bc,
+            &random_tick_bcc,
+        );
+
+        system
+            .take_ticks()
+            .into_iter()
+            .map(|tick| (tick.chunk, tick.block))
+            .collect()
+    }
+
+    #[test]
+    fn same_blocks_for_same_seed() {
+        let chunks = (0 .. 64).map(|i| {
+            Chunk {
+                position: [i % 4, i / 4 % 4, i / 16],
+                dimension: Dimension {
+                    kind: DimensionKind(0),
+                    phase: 0,
+                },
+            }
+        });
+
+        let ticks = select(chunks.clone());
+
+        assert_eq!(ticks.len(), 64 * 3);
+        assert_eq!(ticks, select(chunks.rev()));
+    }
+}
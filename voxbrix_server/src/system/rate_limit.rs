@@ -0,0 +1,215 @@
+use crate::entity::player::Player;
+use log::warn;
+use nohash_hasher::IntMap;
+use voxbrix_common::entity::snapshot::Snapshot;
+
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimits {
+    /// New actions accepted from a player every tick, not limited if not set.
+    pub actions_per_tick: Option<usize>,
+    /// Bytes of the messages accepted from a player every tick, not limited if not set.
+    pub bytes_per_tick: Option<usize>,
+    /// Players with more messages and actions dropped than that are disconnected,
+    /// never if not set. One drop is forgiven every tick, so only a sustained excess counts.
+    pub max_dropped: Option<u64>,
+}
+
+struct PlayerUsage {
+    snapshot: Snapshot,
+    actions: usize,
+    bytes: usize,
+    /// Dropped within the `snapshot`, only the first drop of the tick is logged.
+    dropped_in_tick: bool,
+    /// Decreases by one every tick.
+    dropped: u64,
+    /// Sent a reliable message over the limit. The client does not resend those,
+    /// so they cannot be dropped without losing them.
+    reliable_exceeded: bool,
+}
+
+/// Counts the actions and the message bytes every player sends within a tick,
+/// the ones over the limits are dropped.
+pub struct RateLimitSystem {
+    limits: RateLimits,
+    players: IntMap<Player, PlayerUsage>,
+}
+
+impl RateLimitSystem {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            players: IntMap::default(),
+        }
+    }
+
+    fn usage(&mut self, player: &Player, snapshot: Snapshot) -> &mut PlayerUsage {
+        let usage = self.players.entry(*player).or_insert(PlayerUsage {
+            snapshot,
+            actions: 0,
+            bytes: 0,
+            dropped_in_tick: false,
+            dropped: 0,
+            reliable_exceeded: false,
+        });
+
+        if usage.snapshot != snapshot {
+            let ticks = snapshot.0.saturating_sub(usage.snapshot.0);
+
+            usage.snapshot = snapshot;
+            usage.actions = 0;
+            usage.bytes = 0;
+            usage.dropped_in_tick = false;
+            usage.dropped = usage.dropped.saturating_sub(ticks);
+        }
+
+        usage
+    }
+
+    fn record_drop(usage: &mut PlayerUsage, player: &Player, what: &str) {
+        usage.dropped += 1;
+
+        if !usage.dropped_in_tick {
+            usage.dropped_in_tick = true;
+            warn!(
+                "player {:?} exceeded the {} limit, {} dropped recently",
+                player, what, usage.dropped
+            );
+        }
+    }
+
+    /// Returns `false` if the message must not be handled.
+    /// A reliable message over the limit makes the player exceed the limits right away.
+    pub fn message(
+        &mut self,
+        player: &Player,
+        snapshot: Snapshot,
+        bytes: usize,
+        reliable: bool,
+    ) -> bool {
+        let limit = self.limits.bytes_per_tick;
+        let usage = self.usage(player, snapshot);
+
+        usage.bytes += bytes;
+
+        if limit.is_some_and(|limit| usage.bytes > limit) {
+            if reliable {
+                usage.reliable_exceeded = true;
+                warn!(
+                    "player {:?} exceeded the bytes per tick limit with a reliable message",
+                    player
+                );
+            } else {
+                Self::record_drop(usage, player, "bytes per tick");
+            }
+
+            return false;
+        }
+
+        true
+    }
+
+    /// Returns `false` if the action must be dropped.
+    pub fn action(&mut self, player: &Player, snapshot: Snapshot) -> bool {
+        let limit = self.limits.actions_per_tick;
+        let usage = self.usage(player, snapshot);
+
+        usage.actions += 1;
+
+        if limit.is_some_and(|limit| usage.actions > limit) {
+            Self::record_drop(usage, player, "actions per tick");
+            return false;
+        }
+
+        true
+    }
+
+    /// The player has dropped too much and should be disconnected.
+    pub fn is_exceeded(&self, player: &Player) -> bool {
+        let Some(usage) = self.players.get(player) else {
+            return false;
+        };
+
+        usage.reliable_exceeded
+            || self
+                .limits
+                .max_dropped
+                .is_some_and(|max_dropped| usage.dropped > max_dropped)
+    }
+
+    pub fn remove_player(&mut self, player: &Player) {
+        self.players.remove(player);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYER: Player = Player(1);
+
+    fn rate_limit(actions_per_tick: usize, max_dropped: u64) -> RateLimitSystem {
+        RateLimitSystem::new(RateLimits {
+            actions_per_tick: Some(actions_per_tick),
+            bytes_per_tick: Some(100),
+            max_dropped: Some(max_dropped),
+        })
+    }
+
+    #[test]
+    fn tick_limit() {
+        let mut system = rate_limit(2, 10);
+
+        assert!(system.action(&PLAYER, Snapshot(1)));
+        assert!(system.action(&PLAYER, Snapshot(1)));
+        assert!(!system.action(&PLAYER, Snapshot(1)));
+
+        assert!(system.message(&PLAYER, Snapshot(1), 60, false));
+        assert!(!system.message(&PLAYER, Snapshot(1), 60, false));
+
+        // Other players have their own limits
+        assert!(system.action(&Player(2), Snapshot(1)));
+        assert!(!system.is_exceeded(&PLAYER));
+    }
+
+    #[test]
+    fn new_tick_reset() {
+        let mut system = rate_limit(1, 10);
+
+        assert!(system.action(&PLAYER, Snapshot(1)));
+        assert!(!system.action(&PLAYER, Snapshot(1)));
+        assert!(system.message(&PLAYER, Snapshot(1), 100, false));
+
+        assert!(system.action(&PLAYER, Snapshot(2)));
+        assert!(system.message(&PLAYER, Snapshot(2), 100, false));
+    }
+
+    #[test]
+    fn disconnect_threshold() {
+        let mut system = rate_limit(0, 2);
+
+        for _ in 0 .. 3 {
+            assert!(!system.action(&PLAYER, Snapshot(1)));
+        }
+
+        assert!(system.is_exceeded(&PLAYER));
+
+        // Drops are forgiven over time, occasional ones never add up
+        let mut system = rate_limit(0, 2);
+
+        for snapshot in 1 .. 100 {
+            assert!(!system.action(&PLAYER, Snapshot(snapshot)));
+            assert!(!system.is_exceeded(&PLAYER));
+        }
+    }
+
+    #[test]
+    fn reliable_message_not_dropped() {
+        let mut system = rate_limit(1, 10);
+
+        assert!(!system.message(&PLAYER, Snapshot(1), 200, false));
+        assert!(!system.is_exceeded(&PLAYER));
+
+        assert!(!system.message(&PLAYER, Snapshot(2), 200, true));
+        assert!(system.is_exceeded(&PLAYER));
+    }
+}
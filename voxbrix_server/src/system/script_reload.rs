@@ -0,0 +1,180 @@
+use anyhow::Error;
+use log::{
+    error,
+    info,
+};
+use server_loop_api::ACTION_LABEL_SECTION;
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    thread,
+    time::{
+        Duration,
+        SystemTime,
+    },
+};
+use voxbrix_common::{
+    entity::{
+        action::Action,
+        script::Script,
+    },
+    script_registry,
+    LabelMap,
+};
+use wasmtime::{
+    Engine,
+    Module,
+};
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Scripts would fail when run with the actions they refer to missing.
+///
+/// Blocking IO, must not be used directly in async
+pub fn check_action_labels(path: &Path, action_label_map: &LabelMap<Action>) -> Result<(), Error> {
+    for label in script_registry::read_module_labels(path, ACTION_LABEL_SECTION)? {
+        if action_label_map.get(&label).is_none() {
+            return Err(Error::msg(format!(
+                "action \"{}\" of script {:?} not found in the action list",
+                label, path
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// The module with the missing actions is rejected, the old one stays in use.
+fn load_module(
+    engine: &Engine,
+    path: &Path,
+    action_label_map: &LabelMap<Action>,
+) -> Result<Module, Error> {
+    check_action_labels(path, action_label_map)?;
+    script_registry::load_module(engine, path)
+}
+
+/// Watches the script module files and recompiles the changed ones.
+/// The compilation happens on a separate thread, the compiled modules are handed over
+/// with `send_modules` to be swapped in between the ticks.
+pub struct ScriptReloadSystem;
+
+impl ScriptReloadSystem {
+    /// Recompiles all the modules once, regardless of the changes.
+    pub fn reload_all(
+        engine: Engine,
+        module_paths: Vec<PathBuf>,
+        action_label_map: LabelMap<Action>,
+        send_modules: impl FnOnce(Vec<(Script, Module)>) + Send + 'static,
+    ) {
+        thread::spawn(move || {
+            let mut modules = Vec::new();
+
+            for (i, path) in module_paths.iter().enumerate() {
+                match load_module(&engine, path, &action_label_map) {
+                    Ok(module) => modules.push((Script(i as u64), module)),
+                    Err(err) => error!("unable to reload script module: {:?}", err),
+                }
+            }
+
+            if !modules.is_empty() {
+                send_modules(modules);
+            }
+        });
+    }
+
+    pub fn spawn(
+        engine: Engine,
+        module_paths: Vec<PathBuf>,
+        action_label_map: LabelMap<Action>,
+        interval: Duration,
+        send_modules: impl Fn(Vec<(Script, Module)>) + Send + 'static,
+    ) {
+        thread::spawn(move || {
+            let mut modified_times = module_paths
+                .iter()
+                .map(|path| modified(path))
+                .collect::<Vec<_>>();
+
+            loop {
+                thread::sleep(interval);
+
+                let mut modules = Vec::new();
+
+                for (i, path) in module_paths.iter().enumerate() {
+                    let time = modified(path);
+
+                    if time.is_none() || time == modified_times[i] {
+                        continue;
+                    }
+
+                    // Even if the compilation fails, the file is not retried until changed again
+                    modified_times[i] = time;
+
+                    match load_module(&engine, path, &action_label_map) {
+                        Ok(module) => {
+                            info!("script module {:?} changed, reloading", path);
+                            modules.push((Script(i as u64), module));
+                        },
+                        Err(err) => error!("unable to reload script module: {:?}", err),
+                    }
+                }
+
+                if !modules.is_empty() {
+                    send_modules(modules);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty module with a single custom section.
+    fn write_module(name: &str, section: &str, payload: &[u8]) -> PathBuf {
+        let mut content = Vec::new();
+        content.extend_from_slice(&[section.len() as u8]);
+        content.extend_from_slice(section.as_bytes());
+        content.extend_from_slice(payload);
+
+        let mut bytes = b"\0asm\x01\0\0\0".to_vec();
+        bytes.push(0);
+        bytes.push(content.len() as u8);
+        bytes.extend_from_slice(&content);
+
+        let path = std::env::temp_dir().join(format!(
+            "voxbrix_script_reload_{}_{}.wasm",
+            std::process::id(),
+            name
+        ));
+
+        fs::write(&path, bytes).unwrap();
+
+        path
+    }
+
+    #[test]
+    fn reject_missing_action() {
+        let engine = Engine::default();
+        let action_label_map = LabelMap::from_list(&["place_block".to_owned()]);
+
+        let valid = write_module("valid", ACTION_LABEL_SECTION, b"place_block\n");
+        let mismatched = write_module("mismatched", ACTION_LABEL_SECTION, b"missing\n");
+
+        let valid_result = load_module(&engine, &valid, &action_label_map);
+        let mismatched_result = load_module(&engine, &mismatched, &action_label_map);
+
+        fs::remove_file(&valid).unwrap();
+        fs::remove_file(&mismatched).unwrap();
+
+        assert!(valid_result.is_ok());
+        assert!(mismatched_result.is_err());
+    }
+}
@@ -0,0 +1,62 @@
+use std::mem;
+use voxbrix_common::entity::script::Script;
+
+/// Number of the wheel slots, delays longer than that take several turns of the wheel.
+const WHEEL_SIZE: usize = 256;
+
+pub struct ScheduledScript {
+    pub script: Script,
+    pub payload: Vec<u8>,
+}
+
+struct WheelEntry {
+    turns: u64,
+    scheduled: ScheduledScript,
+}
+
+/// Timer wheel of the scripts scheduled to run on the later ticks.
+pub struct ScriptScheduleSystem {
+    tick: u64,
+    slots: Vec<Vec<WheelEntry>>,
+}
+
+impl ScriptScheduleSystem {
+    pub fn new() -> Self {
+        Self {
+            tick: 0,
+            slots: (0 .. WHEEL_SIZE).map(|_| Vec::new()).collect(),
+        }
+    }
+
+    /// Schedules the script to run in `delay_ticks` ticks.
+    /// The delay of `0` runs the script on the next tick.
+    pub fn schedule(&mut self, script: Script, delay_ticks: u64, payload: Vec<u8>) {
+        let delay = delay_ticks.max(1);
+        let target_tick = self.tick + delay;
+        let slot = (target_tick % WHEEL_SIZE as u64) as usize;
+
+        self.slots[slot].push(WheelEntry {
+            turns: (delay - 1) / WHEEL_SIZE as u64,
+            scheduled: ScheduledScript { script, payload },
+        });
+    }
+
+    /// Advances the wheel by one tick and returns the scripts due on it.
+    pub fn next_tick(&mut self) -> Vec<ScheduledScript> {
+        self.tick += 1;
+
+        let slot = &mut self.slots[(self.tick % WHEEL_SIZE as u64) as usize];
+        let mut due = Vec::new();
+
+        for mut entry in mem::take(slot) {
+            if entry.turns == 0 {
+                due.push(entry.scheduled);
+            } else {
+                entry.turns -= 1;
+                slot.push(entry);
+            }
+        }
+
+        due
+    }
+}
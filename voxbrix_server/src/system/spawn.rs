@@ -0,0 +1,293 @@
+use crate::{
+    component::{
+        actor::{
+            class::ClassActorComponent,
+            player::PlayerActorComponent,
+            position::PositionActorComponent,
+        },
+        block::class::ClassBlockComponent,
+        chunk::status::{
+            ChunkStatus,
+            StatusChunkComponent,
+        },
+    },
+    system::chunk_activation::ChunkActivationSystem,
+    world_rng::WorldRng,
+};
+use ahash::AHashMap;
+use anyhow::{
+    Context,
+    Error,
+};
+use serde::Deserialize;
+use std::{
+    fmt::Debug,
+    mem,
+    path::Path,
+};
+use tokio::task;
+use voxbrix_common::{
+    component::{
+        block::BlocksVec,
+        block_class::collision::CollisionBlockClassComponent,
+    },
+    entity::{
+        actor::Actor,
+        actor_class::ActorClass,
+        block::{
+            Block,
+            BLOCKS_IN_CHUNK,
+            BLOCKS_IN_CHUNK_EDGE,
+        },
+        block_class::BlockClass,
+        chunk::{
+            Chunk,
+            DimensionKind,
+        },
+    },
+    system::pack_loading::PackSet,
+    LabelMap,
+};
+
+#[derive(Deserialize, Debug)]
+struct SpawnRuleDescriptor {
+    dimension_kind: String,
+    actor_class: String,
+    /// Empty list allows any solid block.
+    #[serde(default)]
+    blocks_beneath: Vec<String>,
+    /// The server does not track the light, so the exposure to the sky stands for the light level.
+    #[serde(default)]
+    require_sky: bool,
+    max_per_chunk: usize,
+    max_total: usize,
+}
+
+#[derive(Deserialize, Debug)]
+struct SpawnRuleList {
+    list: Vec<SpawnRuleDescriptor>,
+}
+
+struct SpawnRule {
+    dimension_kind: DimensionKind,
+    actor_class: ActorClass,
+    blocks_beneath: Vec<BlockClass>,
+    require_sky: bool,
+    max_per_chunk: usize,
+    max_total: usize,
+}
+
+pub struct Spawn {
+    pub actor_class: ActorClass,
+    pub chunk: Chunk,
+    /// Block the actor stands in.
+    pub block: Block,
+}
+
+/// Spawns the non-player actors in the active chunks by the data-driven rules
+/// and despawns them once their chunks are no longer active.
+/// The rules of all the packs apply.
+pub struct SpawnSystem {
+    rules: Vec<SpawnRule>,
+    chunks: Vec<Chunk>,
+    spawns: Vec<Spawn>,
+    despawns: Vec<Actor>,
+}
+
+impl SpawnSystem {
+    pub async fn load(
+        packs: PackSet,
+        path: impl 'static + AsRef<Path> + Debug + Send + Clone,
+        dimension_kind_label_map: LabelMap<DimensionKind>,
+        actor_class_label_map: LabelMap<ActorClass>,
+        block_class_label_map: LabelMap<BlockClass>,
+    ) -> Result<Self, Error> {
+        let read_path = path.clone();
+
+        let rules = task::spawn_blocking(move || {
+            let list = packs.read_data_files::<SpawnRuleList>(read_path)?;
+
+            list.into_iter()
+                .flat_map(|pack_list| pack_list.list)
+                .map(|desc| {
+                    let undefined = |kind: &str, label: &str| {
+                        Error::msg(format!("{} \"{}\" is undefined", kind, label))
+                    };
+
+                    let dimension_kind = dimension_kind_label_map
+                        .get(&desc.dimension_kind)
+                        .ok_or_else(|| undefined("dimension kind", &desc.dimension_kind))?;
+
+                    let actor_class = actor_class_label_map
+                        .get(&desc.actor_class)
+                        .ok_or_else(|| undefined("actor class", &desc.actor_class))?;
+
+                    let blocks_beneath = desc
+                        .blocks_beneath
+                        .iter()
+                        .map(|label| {
+                            block_class_label_map
+                                .get(label)
+                                .ok_or_else(|| undefined("block class", label))
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    Ok(SpawnRule {
+                        dimension_kind,
+                        actor_class,
+                        blocks_beneath,
+                        require_sky: desc.require_sky,
+                        max_per_chunk: desc.max_per_chunk,
+                        max_total: desc.max_total,
+                    })
+                })
+                .collect::<Result<Vec<_>, Error>>()
+        })
+        .await
+        .unwrap()
+        .with_context(|| format!("unable to load spawn rules \"{:?}\"", path))?;
+
+        Ok(Self {
+            rules,
+            chunks: Vec::new(),
+            spawns: Vec::new(),
+            despawns: Vec::new(),
+        })
+    }
+
+    /// Checks the block the actor would stand in against the rule.
+    /// Only the blocks within the same chunk are looked at.
+    fn fits(
+        rule: &SpawnRule,
+        blocks: &BlocksVec<BlockClass>,
+        collision_bcc: &CollisionBlockClassComponent,
+        block: Block,
+    ) -> bool {
+        let is_solid = |block: Block| collision_bcc.get(blocks.get(block)).is_some();
+
+        let [x, y, z] = block.into_coords();
+
+        if z == 0 || is_solid(block) {
+            return false;
+        }
+
+        let beneath = Block::from_coords([x, y, z - 1]);
+
+        if !is_solid(beneath) {
+            return false;
+        }
+
+        if !rule.blocks_beneath.is_empty() && !rule.blocks_beneath.contains(blocks.get(beneath)) {
+            return false;
+        }
+
+        let mut above = (z + 1 .. BLOCKS_IN_CHUNK_EDGE).map(|z| Block::from_coords([x, y, z]));
+
+        if let Some(head) = above.next() {
+            if is_solid(head) {
+                return false;
+            }
+        }
+
+        !rule.require_sky || above.all(|block| !is_solid(block))
+    }
+
+    /// Should be called after the chunk activations of the tick are collected.
+    pub fn process(
+        &mut self,
+        world_rng: &mut WorldRng,
+        status_cc: &StatusChunkComponent,
+        chunk_activation_system: &ChunkActivationSystem,
+        class_bc: &ClassBlockComponent,
+        collision_bcc: &CollisionBlockClassComponent,
+        class_ac: &ClassActorComponent,
+        player_ac: &PlayerActorComponent,
+        position_ac: &PositionActorComponent,
+    ) {
+        let mut per_chunk: AHashMap<(Chunk, ActorClass), usize> = AHashMap::new();
+        let mut total: AHashMap<(DimensionKind, ActorClass), usize> = AHashMap::new();
+
+        for (actor, class) in class_ac.iter() {
+            if player_ac.get(&actor).is_some() {
+                continue;
+            }
+
+            let Some(position) = position_ac.get(&actor) else {
+                continue;
+            };
+
+            if !chunk_activation_system.is_active(&position.chunk) {
+                self.despawns.push(actor);
+                continue;
+            }
+
+            *per_chunk.entry((position.chunk, *class)).or_default() += 1;
+            *total
+                .entry((position.chunk.dimension.kind, *class))
+                .or_default() += 1;
+        }
+
+        let rules = mem::take(&mut self.rules);
+
+        self.chunks.clear();
+        self.chunks.extend(
+            status_cc
+                .iter()
+                .filter(|(chunk, status)| {
+                    **status == ChunkStatus::Active && chunk_activation_system.is_active(chunk)
+                })
+                .map(|(chunk, _)| *chunk),
+        );
+
+        // The chunk map order differs between the runs, the same spawns need
+        // the blocks drawn and the limits reached for the chunks in the same order
+        self.chunks.sort_unstable();
+
+        for chunk in self.chunks.iter() {
+            let Some(blocks) = class_bc.get_chunk(chunk) else {
+                continue;
+            };
+
+            for rule in rules
+                .iter()
+                .filter(|rule| rule.dimension_kind == chunk.dimension.kind)
+            {
+                let chunk_count = per_chunk.entry((*chunk, rule.actor_class)).or_default();
+                let total_count = total
+                    .entry((rule.dimension_kind, rule.actor_class))
+                    .or_default();
+
+                if *chunk_count >= rule.max_per_chunk || *total_count >= rule.max_total {
+                    continue;
+                }
+
+                let block =
+                    Block::from_usize(world_rng.below(BLOCKS_IN_CHUNK as u64) as usize).unwrap();
+
+                if !Self::fits(rule, blocks, collision_bcc, block) {
+                    continue;
+                }
+
+                *chunk_count += 1;
+                *total_count += 1;
+
+                self.spawns.push(Spawn {
+                    actor_class: rule.actor_class,
+                    chunk: *chunk,
+                    block,
+                });
+            }
+        }
+
+        self.rules = rules;
+    }
+
+    pub fn take_spawns(&mut self) -> Vec<Spawn> {
+        mem::take(&mut self.spawns)
+    }
+
+    /// Non-player actors that are out of the active chunks.
+    pub fn take_despawns(&mut self) -> Vec<Actor> {
+        mem::take(&mut self.despawns)
+    }
+}
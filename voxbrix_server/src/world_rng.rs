@@ -0,0 +1,64 @@
+//! Random numbers for the gameplay, reproducible for the same world and the same inputs.
+use voxbrix_common::entity::snapshot::Snapshot;
+
+/// Xoshiro256** generator reseeded at the start of every snapshot from the world seed and
+/// the snapshot number, so the numbers drawn within a snapshot only depend on the world and
+/// on what has happened within the snapshot. The replays and the restarted servers get the
+/// same numbers as long as the inputs are the same.
+pub struct WorldRng {
+    world_seed: u64,
+    state: [u64; 4],
+}
+
+impl WorldRng {
+    pub fn new(world_seed: u64, snapshot: Snapshot) -> Self {
+        let mut rng = Self {
+            world_seed,
+            state: [0; 4],
+        };
+
+        rng.start_snapshot(snapshot);
+
+        rng
+    }
+
+    /// Should be called every time the snapshot changes.
+    pub fn start_snapshot(&mut self, snapshot: Snapshot) {
+        let mut seed = self.world_seed ^ snapshot.0.wrapping_mul(0x9E3779B97F4A7C15);
+
+        // Never all zeroes, SplitMix64 gives distinct outputs for the distinct states
+        self.state = [(); 4].map(|_| split_mix(&mut seed));
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let [s0, s1, s2, s3] = &mut self.state;
+
+        let result = s1.wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = *s1 << 17;
+
+        *s2 ^= *s0;
+        *s3 ^= *s1;
+        *s1 ^= *s2;
+        *s0 ^= *s3;
+        *s2 ^= t;
+        *s3 = s3.rotate_left(45);
+
+        result
+    }
+
+    /// Uniformly distributed within `0 .. bound`, `bound` must not be zero.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        // Multiply-shift, the bias is negligible for the bounds used in the game
+        ((self.next_u64() as u128 * bound as u128) >> 64) as u64
+    }
+}
+
+fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+
+    z ^ (z >> 31)
+}